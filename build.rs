@@ -0,0 +1,26 @@
+fn main() {
+    // the protobuf types are generated with a vendored protoc, no system install is needed
+    #[cfg(feature = "proto")]
+    {
+        println!("cargo:rerun-if-changed=proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+        let mut config = prost_build::Config::new();
+        config.protoc_executable(protoc);
+
+        #[cfg(not(feature = "grpc"))]
+        config
+            .compile_protos(&["proto/orderbook.proto"], &["proto/"])
+            .expect("failed to compile proto/orderbook.proto");
+
+        #[cfg(feature = "grpc")]
+        tonic_build::configure()
+            .build_client(true)
+            .build_server(true)
+            .compile_protos_with_config(
+                config,
+                &["proto/orderbook.proto", "proto/service.proto"],
+                &["proto/"],
+            )
+            .expect("failed to compile proto/service.proto");
+    }
+}
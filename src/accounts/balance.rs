@@ -0,0 +1,43 @@
+use crate::enums::payment_status::PaymentStatus;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use serde::{Deserialize, Serialize};
+
+/// Balance of a user in an asset
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Balance {
+    /// Amount free to be reserved or withdrawn
+    pub available: f64,
+    /// Amount reserved by the resting orders
+    pub reserved: f64,
+}
+
+impl Balance {
+    pub fn total(&self) -> f64 {
+        self.available + self.reserved
+    }
+}
+
+/// Exchange of assets between the buyer and the seller of a trade
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Settlement {
+    pub symbol: Symbol,
+    #[serde(rename = "buyOrderId")]
+    pub buy_order_id: OrderId,
+    #[serde(rename = "sellOrderId")]
+    pub sell_order_id: OrderId,
+    #[serde(rename = "buyUserId")]
+    pub buy_user_id: UserId,
+    #[serde(rename = "sellUserId")]
+    pub sell_user_id: UserId,
+    /// Asset received by the buyer
+    #[serde(rename = "baseAsset")]
+    pub base_asset: u128,
+    /// Asset received by the seller
+    #[serde(rename = "quoteAsset")]
+    pub quote_asset: u128,
+    #[serde(rename = "baseQuantity")]
+    pub base_quantity: f64,
+    #[serde(rename = "quoteQuantity")]
+    pub quote_quantity: f64,
+    pub status: PaymentStatus,
+}
@@ -0,0 +1,404 @@
+use super::balance::{Balance, Settlement};
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::payment_status::PaymentStatus;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::trade::Trade;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// Share of a settled amount a reservation may fall short of, the rounding of the notional of the partial fills
+const ROUNDING_TOLERANCE: f64 = 1e-9;
+
+fn insufficient_balance() -> Error {
+    Error::new(ErrorKind::PermissionDenied, "Insufficient balance")
+}
+
+/// Amount of an asset held for an order until it is filled, cancelled or expired
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Reservation {
+    user_id: UserId,
+    asset: u128,
+    amount: f64,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    /// Base and quote assets of each symbol
+    markets: HashMap<Symbol, (u128, u128)>,
+    balances: HashMap<(UserId, u128), Balance>,
+    reservations: HashMap<OrderId, Reservation>,
+    /// Orders which left the book, their reservations are released once their trades are settled
+    closed: Vec<OrderId>,
+    listeners: Vec<Sender<Settlement>>,
+}
+
+impl LedgerState {
+    fn balance_mut(&mut self, user_id: UserId, asset: u128) -> &mut Balance {
+        self.balances.entry((user_id, asset)).or_default()
+    }
+
+    /// Asset and amount to reserve for an order, a market buy reserves the whole quote balance
+    fn required(&self, order: &Order) -> Result<(u128, f64), Error> {
+        let Some(&(base, quote)) = self.markets.get(&order.symbol) else {
+            return Err(Error::new(ErrorKind::NotFound, "Market not found"));
+        };
+        let quantity = order.quantity + order.hidden_quantity;
+        Ok(match (order.side, order.order_type, order.price) {
+            (OrderSide::Buy, OrderType::Limit, Some(price)) => (quote, price * quantity),
+            (OrderSide::Buy, _, _) => {
+                let available = self
+                    .balances
+                    .get(&(order.user_id, quote))
+                    .map_or(0.0, |b| b.available);
+                (quote, available)
+            }
+            (OrderSide::Sell, _, _) => (base, quantity),
+        })
+    }
+
+    /// Whether the reservation of an order covers an amount, up to the rounding of the partial fills
+    fn covers(&self, order_id: OrderId, amount: f64) -> bool {
+        let reserved = self.reservations.get(&order_id).map_or(0.0, |r| r.amount);
+        amount - reserved <= ROUNDING_TOLERANCE * amount.max(1.0)
+    }
+
+    /// Take an amount from the reservation of an order, which covers it
+    fn consume(&mut self, order_id: OrderId, user_id: UserId, asset: u128, amount: f64) {
+        let Some(reservation) = self.reservations.get_mut(&order_id) else {
+            return;
+        };
+        let taken = reservation.amount.min(amount);
+        reservation.amount -= taken;
+        self.balance_mut(user_id, asset).reserved -= taken;
+    }
+
+    /// Exchange the assets of a trade out of the reservations of its orders. A trade an order has
+    /// not reserved enough for moves nothing and its settlement is Failed.
+    fn settle(&mut self, trade: &Trade) -> Option<Settlement> {
+        let &(base, quote) = self.markets.get(&trade.symbol)?;
+        let notional = trade.price * trade.quantity;
+        let mut settlement = Settlement {
+            symbol: trade.symbol,
+            buy_order_id: trade.buy_order_id,
+            sell_order_id: trade.sell_order_id,
+            buy_user_id: trade.buy_user_id,
+            sell_user_id: trade.sell_user_id,
+            base_asset: base,
+            quote_asset: quote,
+            base_quantity: trade.quantity,
+            quote_quantity: notional,
+            status: PaymentStatus::Paid,
+        };
+        if !(self.covers(trade.buy_order_id, notional)
+            && self.covers(trade.sell_order_id, trade.quantity))
+        {
+            settlement.status = PaymentStatus::Failed;
+            return Some(settlement);
+        }
+        self.consume(trade.buy_order_id, trade.buy_user_id, quote, notional);
+        self.balance_mut(trade.buy_user_id, base).available += trade.quantity;
+        self.consume(
+            trade.sell_order_id,
+            trade.sell_user_id,
+            base,
+            trade.quantity,
+        );
+        self.balance_mut(trade.sell_user_id, quote).available += notional;
+        Some(settlement)
+    }
+
+    /// Resize the reservation of a resting order to what its price and quantity require, the orders
+    /// holding no reservation are left alone
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An error changing nothing if the available balance does not cover the increase
+    fn resize(&mut self, order: &Order) -> Result<(), Error> {
+        let Some(reserved) = self.reservations.get(&order.id).map(|r| r.amount) else {
+            return Ok(());
+        };
+        let (asset, amount) = self.required(order)?;
+        let extra = amount - reserved;
+        let balance = self.balance_mut(order.user_id, asset);
+        if extra > balance.available {
+            return Err(insufficient_balance());
+        }
+        balance.available -= extra;
+        balance.reserved += extra;
+        self.reservations.insert(
+            order.id,
+            Reservation {
+                user_id: order.user_id,
+                asset,
+                amount,
+            },
+        );
+        Ok(())
+    }
+
+    fn release(&mut self, order_id: OrderId) {
+        if let Some(reservation) = self.reservations.remove(&order_id) {
+            let balance = self.balance_mut(reservation.user_id, reservation.asset);
+            balance.reserved -= reservation.amount;
+            balance.available += reservation.amount;
+        }
+    }
+}
+
+/// Balances of the users per asset. Placing an order reserves the asset it pays with,
+/// the trades settle the reservations and the cancels release them.
+#[derive(Debug, Clone, Default)]
+pub struct Accounts {
+    state: Arc<Mutex<LedgerState>>,
+}
+
+impl Accounts {
+    pub fn new() -> Accounts {
+        Accounts::default()
+    }
+
+    /// Declare the assets exchanged on a symbol
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'base_asset' - The asset bought and sold
+    /// * 'quote_asset' - The asset the prices are expressed in
+    pub fn register_market(&self, symbol: Symbol, base_asset: u128, quote_asset: u128) {
+        let mut state = self.state.lock().unwrap();
+        state.markets.insert(symbol, (base_asset, quote_asset));
+    }
+
+    /// Credit the available balance of a user
+    pub fn deposit(&self, user_id: UserId, asset: u128, amount: f64) {
+        self.state
+            .lock()
+            .unwrap()
+            .balance_mut(user_id, asset)
+            .available += amount;
+    }
+
+    /// Debit the available balance of a user
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An error if the available balance is insufficient
+    pub fn withdraw(&self, user_id: UserId, asset: u128, amount: f64) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        let balance = state.balance_mut(user_id, asset);
+        if balance.available < amount {
+            return Err(insufficient_balance());
+        }
+        balance.available -= amount;
+        Ok(())
+    }
+
+    pub fn balance(&self, user_id: UserId, asset: u128) -> Balance {
+        let state = self.state.lock().unwrap();
+        state
+            .balances
+            .get(&(user_id, asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Balances of a user by asset
+    pub fn get_balance(&self, user_id: UserId) -> HashMap<u128, Balance> {
+        let state = self.state.lock().unwrap();
+        state
+            .balances
+            .iter()
+            .filter(|((user, _), _)| *user == user_id)
+            .map(|((_, asset), balance)| (*asset, *balance))
+            .collect()
+    }
+
+    /// Check the user can pay for an order, nothing is reserved
+    pub fn check_order(&self, order: &Order) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        let (asset, amount) = state.required(order)?;
+        let available = state
+            .balances
+            .get(&(order.user_id, asset))
+            .map_or(0.0, |b| b.available);
+        if amount <= 0.0 || available < amount {
+            return Err(insufficient_balance());
+        }
+        Ok(())
+    }
+
+    /// Resize the reservation of a resting order to its amended price and quantity: the increase is
+    /// reserved and what a decrease frees is released
+    ///
+    /// #Parameters
+    /// * 'order' - The amended order, its hidden quantity included
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An error changing nothing if the available balance does not cover the increase
+    pub fn amend(&self, order: &Order) -> Result<(), Error> {
+        self.state.lock().unwrap().resize(order)
+    }
+
+    /// Reserve the amount an order pays with
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An error if the market is unknown or the available balance is insufficient
+    pub fn reserve(&self, order: &Order) -> Result<(), Error> {
+        self.check_order(order)?;
+        let mut state = self.state.lock().unwrap();
+        let (asset, amount) = state.required(order)?;
+        let balance = state.balance_mut(order.user_id, asset);
+        balance.available -= amount;
+        balance.reserved += amount;
+        state.reservations.insert(
+            order.id,
+            Reservation {
+                user_id: order.user_id,
+                asset,
+                amount,
+            },
+        );
+        Ok(())
+    }
+
+    /// Give back what is left of the reservation of an order
+    pub fn release(&self, order_id: OrderId) {
+        self.state.lock().unwrap().release(order_id);
+    }
+
+    /// Settle the trades and follow the amended orders. The reservations of the orders leaving the book
+    /// are released by release_closed, the fill of an order is published before its trades.
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                if let Some(settlement) = update.trade.as_ref().and_then(|t| state.settle(t)) {
+                    state
+                        .listeners
+                        .retain(|listener| listener.send(settlement).is_ok());
+                }
+            }
+            OrderbookUpdateType::Amended | OrderbookUpdateType::Replace => {
+                // The manager resized the reservation before the amend, this follows the amends made
+                // on the orderbook directly. The published order of an iceberg hides its reserve.
+                if let Some(order) = update.order.filter(|o| !o.is_iceberg()) {
+                    let _ = state.resize(&order);
+                }
+            }
+            OrderbookUpdateType::Cancel
+            | OrderbookUpdateType::Filled
+            | OrderbookUpdateType::Expired => {
+                if let Some(order) = update.order {
+                    state.closed.push(order.id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Release the reservations of the orders which left the book, once their trades are settled
+    pub fn release_closed(&self) {
+        let mut state = self.state.lock().unwrap();
+        for order_id in std::mem::take(&mut state.closed) {
+            state.release(order_id);
+        }
+    }
+
+    /// Listen to the settlements of the trades
+    pub fn subscribe_settlements(&self) -> Receiver<Settlement> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_reserve_settle_and_release() {
+        let accounts = Accounts::new();
+        let (symbol, base, quote) = (Ulid::new().into(), Ulid::new().into(), Ulid::new().into());
+        accounts.register_market(symbol, base, quote);
+        let (buyer, seller) = (Ulid::new().into(), Ulid::new().into());
+        accounts.deposit(buyer, quote, 100.0);
+        accounts.deposit(seller, base, 5.0);
+        let settlements = accounts.subscribe_settlements();
+
+        let buy = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            4.0,
+            Some(30.0),
+            OrderType::Limit,
+        );
+        assert!(accounts.reserve(&buy).is_err());
+        let buy = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            4.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        let sell = Order::new(
+            seller,
+            symbol,
+            OrderSide::Sell,
+            3.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        accounts.reserve(&buy).unwrap();
+        accounts.reserve(&sell).unwrap();
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 60.0,
+                reserved: 40.0
+            }
+        );
+
+        let trade = Trade {
+            buy_order_id: buy.id,
+            sell_order_id: sell.id,
+            buy_user_id: buyer,
+            sell_user_id: seller,
+            price: 10.0,
+            quantity: 3.0,
+            symbol,
+            ..Default::default()
+        };
+        accounts.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(trade),
+            ..Default::default()
+        });
+        accounts.release(buy.id);
+        accounts.release(sell.id);
+
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 70.0,
+                reserved: 0.0
+            }
+        );
+        assert_eq!(accounts.balance(buyer, base).available, 3.0);
+        assert_eq!(
+            accounts.balance(seller, base),
+            Balance {
+                available: 2.0,
+                reserved: 0.0
+            }
+        );
+        assert_eq!(accounts.balance(seller, quote).available, 30.0);
+        assert_eq!(settlements.try_recv().unwrap().quote_quantity, 30.0);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod balance;
+pub mod ledger;
+pub mod settlement;
+pub mod settlement_gate;
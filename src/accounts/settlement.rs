@@ -0,0 +1,169 @@
+use crate::enums::settlement_direction::SettlementDirection;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::symbol_registry::SymbolInfo;
+use crate::structs::trade::Trade;
+use serde::{Deserialize, Serialize};
+
+/// Movement of an asset on the account of a user
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SettlementLeg {
+    #[serde(rename = "userId")]
+    pub user_id: UserId,
+    pub asset: u128,
+    pub direction: SettlementDirection,
+    /// Always positive, the direction tells whether it is taken or given
+    pub amount: f64,
+}
+
+/// Settlement instruction of a trade, the debit and credit of each user in each asset of the symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementInstruction {
+    #[serde(rename = "tradeId")]
+    pub trade_id: Option<u128>,
+    pub symbol: Symbol,
+    #[serde(rename = "buyOrderId")]
+    pub buy_order_id: OrderId,
+    #[serde(rename = "sellOrderId")]
+    pub sell_order_id: OrderId,
+    /// The buyer pays the quote and receives the base, the seller delivers the base and receives the quote
+    pub legs: Vec<SettlementLeg>,
+    /// Fees in the quote asset, a rebate is a credit
+    pub fees: Vec<SettlementLeg>,
+    /// Time of the trade, in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+}
+
+impl SettlementInstruction {
+    /// Build the settlement instruction of a trade
+    ///
+    /// #Parameters
+    /// * 'trade' - The trade to settle
+    /// * 'info' - The reference data of the symbol of the trade
+    pub fn from_trade(trade: &Trade, info: &SymbolInfo) -> SettlementInstruction {
+        let notional = trade.price * trade.quantity;
+        let leg = |user_id, asset, direction, amount| SettlementLeg {
+            user_id,
+            asset,
+            direction,
+            amount,
+        };
+        let legs = vec![
+            leg(
+                trade.buy_user_id,
+                info.quote_asset,
+                SettlementDirection::Debit,
+                notional,
+            ),
+            leg(
+                trade.buy_user_id,
+                info.base_asset,
+                SettlementDirection::Credit,
+                trade.quantity,
+            ),
+            leg(
+                trade.sell_user_id,
+                info.base_asset,
+                SettlementDirection::Debit,
+                trade.quantity,
+            ),
+            leg(
+                trade.sell_user_id,
+                info.quote_asset,
+                SettlementDirection::Credit,
+                notional,
+            ),
+        ];
+        let (taker, maker) = match trade.taker_side {
+            OrderSide::Buy => (trade.buy_user_id, trade.sell_user_id),
+            OrderSide::Sell => (trade.sell_user_id, trade.buy_user_id),
+        };
+        let fees = [(taker, trade.taker_fee), (maker, trade.maker_fee)]
+            .into_iter()
+            .filter(|(_, fee)| *fee != 0.0)
+            .map(|(user_id, fee)| {
+                let direction = if fee > 0.0 {
+                    SettlementDirection::Debit
+                } else {
+                    SettlementDirection::Credit
+                };
+                leg(user_id, info.quote_asset, direction, fee.abs())
+            })
+            .collect();
+        SettlementInstruction {
+            trade_id: trade.id,
+            symbol: trade.symbol,
+            buy_order_id: trade.buy_order_id,
+            sell_order_id: trade.sell_order_id,
+            legs,
+            fees,
+            timestamp: trade.created_at.unwrap_or_default(),
+        }
+    }
+
+    /// Net amount of an asset for a user over the legs and the fees, positive when the user receives it
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'asset' - The asset
+    pub fn net(&self, user_id: UserId, asset: u128) -> f64 {
+        self.legs
+            .iter()
+            .chain(self.fees.iter())
+            .filter(|leg| leg.user_id == user_id && leg.asset == asset)
+            .map(|leg| match leg.direction {
+                SettlementDirection::Debit => -leg.amount,
+                SettlementDirection::Credit => leg.amount,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::order::Order;
+
+    #[test]
+    fn test_instruction_of_a_trade() {
+        let symbol = Symbol(1);
+        let (buyer, seller) = (UserId(1), UserId(2));
+        let info = SymbolInfo::new(10, 20);
+        let buy = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            2.0,
+            Some(5.0),
+            OrderType::Limit,
+        );
+        let sell = Order::new(
+            seller,
+            symbol,
+            OrderSide::Sell,
+            2.0,
+            Some(5.0),
+            OrderType::Limit,
+        );
+        let mut trade = Trade::between(symbol, 5.0, 2.0, &buy, &sell, OrderSide::Sell);
+        trade.taker_fee = 0.1;
+        trade.maker_fee = -0.02;
+
+        let instruction = SettlementInstruction::from_trade(&trade, &info);
+        assert_eq!(instruction.legs.len(), 4);
+        assert_eq!(instruction.net(buyer, 10), 2.0);
+        assert_eq!(instruction.net(buyer, 20), -10.0 + 0.02);
+        assert_eq!(instruction.net(seller, 10), -2.0);
+        assert_eq!(instruction.net(seller, 20), 10.0 - 0.1);
+        assert_eq!(
+            instruction.fees[0],
+            SettlementLeg {
+                user_id: seller,
+                asset: 20,
+                direction: SettlementDirection::Debit,
+                amount: 0.1,
+            }
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::payment_status::PaymentStatus;
+use crate::enums::settlement_failure_policy::SettlementFailurePolicy;
+use crate::enums::side::OrderSide;
+use crate::enums::trade_status::TradeStatus;
+use crate::structs::ids::OrderId;
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::trade::Trade;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// Change of the settlement status of a trade, the new status being the status of the trade
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeStatusEvent {
+    pub trade: Trade,
+    /// Status of the trade before the change, None when the trade enters the gate
+    pub previous: Option<TradeStatus>,
+    /// Time of the change in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+    /// Order putting the quantity of a failed trade back in the book, when the compensation re-booked it
+    pub rebooked: Option<OrderId>,
+}
+
+#[derive(Debug, Default)]
+struct GateState {
+    policy: SettlementFailurePolicy,
+    /// Trades waiting for their settlement, by trade ID
+    pending: HashMap<u128, Trade>,
+    /// Number of trades waiting for their settlement and payment status, by order
+    payments: HashMap<OrderId, (usize, PaymentStatus)>,
+    listeners: Vec<Sender<TradeStatusEvent>>,
+}
+
+impl GateState {
+    fn publish(&mut self, event: &TradeStatusEvent) {
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+    }
+}
+
+/// Trades waiting for an external settlement service. Every trade starts Pending, the service confirms
+/// or fails it, and the payment status of an order follows the settlement of its trades.
+#[derive(Debug, Clone, Default)]
+pub struct SettlementGate {
+    state: Arc<Mutex<GateState>>,
+}
+
+impl SettlementGate {
+    /// Create a gate
+    ///
+    /// #Parameters
+    /// * 'policy' - What is done when the settlement of a trade fails
+    pub fn new(policy: SettlementFailurePolicy) -> SettlementGate {
+        let gate = SettlementGate::default();
+        gate.state.lock().unwrap().policy = policy;
+        gate
+    }
+
+    /// What is done when the settlement of a trade fails
+    pub fn policy(&self) -> SettlementFailurePolicy {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Trade waiting for its settlement
+    ///
+    /// #Parameters
+    /// * 'trade_id' - The trade ID
+    pub fn get(&self, trade_id: u128) -> Option<Trade> {
+        self.state.lock().unwrap().pending.get(&trade_id).cloned()
+    }
+
+    /// Trades waiting for their settlement
+    pub fn pending(&self) -> Vec<Trade> {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Payment status of an order: Pending while one of its trades is, Failed once one of them failed
+    /// and Paid once they are all confirmed
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    ///
+    /// #Returns
+    /// * Option<PaymentStatus> - None if the order has no trade
+    pub fn payment_status(&self, order_id: OrderId) -> Option<PaymentStatus> {
+        let state = self.state.lock().unwrap();
+        state.payments.get(&order_id).map(|&(_, status)| status)
+    }
+
+    /// Hold the new trades until their settlement
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        if update.update_type != OrderbookUpdateType::NewTrades {
+            return;
+        }
+        let Some(trade) = update.trade.as_ref() else {
+            return;
+        };
+        let Some(trade_id) = trade.id.filter(|_| trade.status == TradeStatus::Pending) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        for order_id in [trade.buy_order_id, trade.sell_order_id] {
+            let payment = state
+                .payments
+                .entry(order_id)
+                .or_insert((0, PaymentStatus::Pending));
+            payment.0 += 1;
+            if payment.1 == PaymentStatus::Paid {
+                payment.1 = PaymentStatus::Pending;
+            }
+        }
+        state.pending.insert(trade_id, trade.clone());
+        state.publish(&TradeStatusEvent {
+            trade: trade.clone(),
+            previous: None,
+            timestamp: update.timestamp,
+            rebooked: None,
+        });
+    }
+
+    /// Settle a pending trade, the event is left to publish once the compensation is done
+    ///
+    /// #Parameters
+    /// * 'trade_id' - The trade ID
+    /// * 'status' - Swapped if the settlement succeeded, Failed otherwise
+    /// * 'timestamp' - Time of the settlement in nanoseconds since UNIX epoch
+    ///
+    /// #Returns
+    /// * TradeStatusEvent - The change of status of the trade, an error if the trade is not pending
+    pub(crate) fn settle(
+        &self,
+        trade_id: u128,
+        status: TradeStatus,
+        timestamp: u64,
+    ) -> Result<TradeStatusEvent, Error> {
+        let mut state = self.state.lock().unwrap();
+        let Some(mut trade) = state.pending.remove(&trade_id) else {
+            return Err(Error::new(ErrorKind::NotFound, "Pending trade not found"));
+        };
+        let previous = std::mem::replace(&mut trade.status, status);
+        for order_id in [trade.buy_order_id, trade.sell_order_id] {
+            if let Some(payment) = state.payments.get_mut(&order_id) {
+                payment.0 -= 1;
+                payment.1 = match (payment.1, status) {
+                    (PaymentStatus::Failed, _) | (_, TradeStatus::Failed) => PaymentStatus::Failed,
+                    _ if payment.0 == 0 => PaymentStatus::Paid,
+                    (current, _) => current,
+                };
+            }
+        }
+        Ok(TradeStatusEvent {
+            trade,
+            previous: Some(previous),
+            timestamp,
+            rebooked: None,
+        })
+    }
+
+    /// Send an event to the listeners of the trade statuses
+    pub(crate) fn publish(&self, event: &TradeStatusEvent) {
+        self.state.lock().unwrap().publish(event);
+    }
+
+    /// Listen to the changes of the settlement status of the trades
+    pub fn subscribe(&self) -> Receiver<TradeStatusEvent> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+}
+
+impl OrderbooksManager {
+    /// Hold the trades until an external settlement service confirms or fails them
+    ///
+    /// #Parameters
+    /// * 'policy' - What is done when the settlement of a trade fails
+    pub fn enable_settlement(&mut self, policy: SettlementFailurePolicy) -> &SettlementGate {
+        self.settlement
+            .get_or_insert_with(|| SettlementGate::new(policy))
+    }
+
+    fn settlement_gate(&self) -> Result<&SettlementGate, Error> {
+        self.settlement
+            .as_ref()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Settlement not enabled"))
+    }
+
+    /// Confirm the settlement of a pending trade
+    ///
+    /// Parameters
+    /// * 'trade_id' : The trade ID
+    ///
+    /// #Returns
+    /// * Trade - The trade, Swapped, an error if the trade is not pending
+    pub fn confirm_trade(&mut self, trade_id: u128) -> Result<Trade, Error> {
+        let gate = self.settlement_gate()?;
+        let event = gate.settle(trade_id, TradeStatus::Swapped, self.clock.now())?;
+        gate.publish(&event);
+        Ok(event.trade)
+    }
+
+    /// Fail the settlement of a pending trade. The users are notified through the trade status stream
+    /// and, with the Rebook policy, the quantity of the trade is put back in the book at the trade price
+    /// as a new order of the user of the resting order
+    ///
+    /// Parameters
+    /// * 'trade_id' : The trade ID
+    ///
+    /// #Returns
+    /// * Trade - The trade, Failed, an error if the trade is not pending
+    pub fn fail_trade(&mut self, trade_id: u128) -> Result<Trade, Error> {
+        let gate = self.settlement_gate()?.clone();
+        let mut event = gate.settle(trade_id, TradeStatus::Failed, self.clock.now())?;
+        if gate.policy() == SettlementFailurePolicy::Rebook {
+            let trade = &event.trade;
+            let (side, user_id) = match trade.taker_side {
+                OrderSide::Buy => (OrderSide::Sell, trade.sell_user_id),
+                OrderSide::Sell => (OrderSide::Buy, trade.buy_user_id),
+            };
+            let order = Order::new(
+                user_id,
+                trade.symbol,
+                side,
+                trade.quantity,
+                Some(trade.price),
+                OrderType::Limit,
+            )
+            .stamped(self.clock.as_ref());
+            // a rejected re-booking leaves the compensation to the users, notified below
+            event.rebooked = self.add_order(order).ok().map(|_| order.id);
+        }
+        gate.publish(&event);
+        Ok(event.trade)
+    }
+
+    /// Listen to the changes of the settlement status of the trades
+    ///
+    /// #Returns
+    /// * Receiver<TradeStatusEvent> - The changes, an error if the settlement is not enabled
+    pub fn subscribe_trade_status(&self) -> Result<Receiver<TradeStatusEvent>, Error> {
+        Ok(self.settlement_gate()?.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ids::Symbol;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_trades_wait_for_their_settlement() {
+        let symbol: Symbol = Ulid::new().into();
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(symbol);
+        manager.enable_settlement(SettlementFailurePolicy::Rebook);
+        let events = manager.subscribe_trade_status().unwrap();
+        let order = |side, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        let maker = order(OrderSide::Sell, 3.0);
+        manager.add_order(maker).unwrap();
+        let (first, second) = (order(OrderSide::Buy, 1.0), order(OrderSide::Buy, 2.0));
+        manager.add_order(first).unwrap();
+        manager.add_order(second).unwrap();
+        let trades: Vec<Trade> = events.try_iter().map(|event| event.trade).collect();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|t| t.status == TradeStatus::Pending));
+        let gate = manager.settlement.clone().unwrap();
+        assert_eq!(gate.pending().len(), 2);
+        assert_eq!(gate.payment_status(maker.id), Some(PaymentStatus::Pending));
+
+        let confirmed = manager.confirm_trade(trades[0].id.unwrap()).unwrap();
+        assert_eq!(confirmed.status, TradeStatus::Swapped);
+        assert_eq!(gate.payment_status(first.id), Some(PaymentStatus::Paid));
+        assert_eq!(gate.payment_status(maker.id), Some(PaymentStatus::Pending));
+        let error = manager.confirm_trade(trades[0].id.unwrap()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+
+        // the failed quantity goes back to the seller
+        let failed = manager.fail_trade(trades[1].id.unwrap()).unwrap();
+        assert_eq!(failed.status, TradeStatus::Failed);
+        assert_eq!(gate.payment_status(maker.id), Some(PaymentStatus::Failed));
+        let changes: Vec<TradeStatusEvent> = events.try_iter().collect();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].previous, Some(TradeStatus::Pending));
+        let rebooked = changes[1].rebooked.unwrap();
+        let resting = manager.orderbooks[&symbol].asks.peek().unwrap();
+        assert_eq!(
+            (resting.id, resting.user_id, resting.quantity),
+            (rebooked, maker.user_id, 2.0)
+        );
+        assert!(gate.pending().is_empty());
+    }
+}
@@ -0,0 +1,773 @@
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::router::ChildOrder;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "native")]
+use std::time::Duration;
+
+/// How an algo order slices its quantity over its time window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AlgoStrategy {
+    /// Equal slices evenly spread over the window
+    Twap { slices: usize },
+    /// Slices evenly spread over the window, each sized after its weight in the expected volume curve
+    /// of the symbol, e.g. the share of the daily volume traded in each period
+    Vwap { profile: Vec<f64> },
+    /// Follow the market: whenever it runs, the order catches up with this share of the volume traded
+    /// by the other orders of the symbol since its start
+    Pov { rate: f64 },
+}
+
+/// Parent order sliced into child orders over time by the algo scheduler
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlgoOrder {
+    #[serde(rename = "algoId")]
+    pub algo_id: OrderId,
+    #[serde(rename = "userId")]
+    pub user_id: UserId,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub quantity: f64,
+    /// Limit price of the children, None to send them as market orders
+    pub price: Option<f64>,
+    pub strategy: AlgoStrategy,
+    /// Start of the window, in milliseconds since UNIX epoch
+    #[serde(rename = "startAt")]
+    pub start_at: u64,
+    /// End of the window, in milliseconds since UNIX epoch, the open children are cancelled then
+    #[serde(rename = "endAt")]
+    pub end_at: u64,
+    /// Quantity sent in the children so far
+    #[serde(rename = "sentQuantity")]
+    pub sent_quantity: f64,
+    /// Quantity traded by the other orders of the symbol since the start, for the POV orders
+    #[serde(rename = "marketVolume")]
+    pub market_volume: f64,
+    pub children: Vec<ChildOrder>,
+    /// Why no more slices are sent: Cancelled by its owner or Expired at the end of the window
+    #[serde(rename = "stopStatus")]
+    pub stop_status: Option<OrderStatus>,
+}
+
+impl AlgoOrder {
+    /// Algo order slicing an order over a time window
+    ///
+    /// #Parameters
+    /// * 'order' - The parent order: its ID, owner, symbol, side, quantity and limit price
+    /// * 'strategy' - The slicing strategy
+    /// * 'start_at' - The start of the window, in milliseconds since UNIX epoch
+    /// * 'end_at' - The end of the window, in milliseconds since UNIX epoch
+    pub fn new(order: &Order, strategy: AlgoStrategy, start_at: u64, end_at: u64) -> AlgoOrder {
+        AlgoOrder {
+            algo_id: order.id,
+            user_id: order.user_id,
+            symbol: order.symbol,
+            side: order.side,
+            quantity: order.quantity,
+            price: order.price,
+            strategy,
+            start_at,
+            end_at,
+            sent_quantity: 0.0,
+            market_volume: 0.0,
+            children: Vec::new(),
+            stop_status: None,
+        }
+    }
+
+    /// Check the parameters of the strategy and the window
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message| Err(Error::new(ErrorKind::InvalidInput, message));
+        if !(self.quantity.is_finite() && self.quantity > 0.0) {
+            return invalid("Order quantity must be positive");
+        }
+        if self.end_at <= self.start_at {
+            return invalid("Algo window must end after its start");
+        }
+        match &self.strategy {
+            AlgoStrategy::Twap { slices } if *slices == 0 => invalid("TWAP needs a slice"),
+            AlgoStrategy::Vwap { profile }
+                if profile.iter().any(|w| !(w.is_finite() && *w >= 0.0))
+                    || profile.iter().sum::<f64>() <= 0.0 =>
+            {
+                invalid("VWAP profile must have positive weights")
+            }
+            AlgoStrategy::Pov { rate } if !(*rate > 0.0 && *rate <= 1.0) => {
+                invalid("POV rate must be in ]0, 1]")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of the slices of the window due at a time, the first one is due at the start
+    fn due_slices(&self, now: u64, slices: usize) -> usize {
+        if now < self.start_at {
+            return 0;
+        }
+        let elapsed = (now - self.start_at) as u128;
+        let window = (self.end_at - self.start_at) as u128;
+        ((elapsed * slices as u128 / window) as usize + 1).min(slices)
+    }
+
+    /// Quantity the children should have been sent by a time
+    pub fn target(&self, now: u64) -> f64 {
+        if now < self.start_at {
+            return 0.0;
+        }
+        match &self.strategy {
+            AlgoStrategy::Twap { slices } => {
+                self.quantity * self.due_slices(now, *slices) as f64 / *slices as f64
+            }
+            AlgoStrategy::Vwap { profile } => {
+                let due = self.due_slices(now, profile.len());
+                let total: f64 = profile.iter().sum();
+                match due == profile.len() {
+                    true => self.quantity,
+                    false => self.quantity * profile[..due].iter().sum::<f64>() / total,
+                }
+            }
+            AlgoStrategy::Pov { rate } => (rate * self.market_volume).min(self.quantity),
+        }
+    }
+
+    /// Quantity executed over all the children
+    pub fn filled_quantity(&self) -> f64 {
+        self.children
+            .iter()
+            .map(|child| child.filled_quantity)
+            .sum()
+    }
+
+    /// Average execution price over all the children, None before the first trade
+    pub fn average_price(&self) -> Option<f64> {
+        let filled = self.filled_quantity();
+        if filled <= 0.0 {
+            return None;
+        }
+        Some(
+            self.children
+                .iter()
+                .map(|child| child.notional)
+                .sum::<f64>()
+                / filled,
+        )
+    }
+
+    /// Children still resting or waiting in their orderbook
+    pub fn open_children(&self) -> impl Iterator<Item = &ChildOrder> {
+        self.children.iter().filter(|child| !child.is_done())
+    }
+
+    /// Aggregated status: Filled once the quantity is executed, the stop status once stopped and the
+    /// children are all done, PartiallyFilled as soon as a child traded, Open otherwise
+    pub fn status(&self) -> OrderStatus {
+        if self.filled_quantity() >= self.quantity {
+            OrderStatus::Filled
+        } else if let Some(status) = self
+            .stop_status
+            .filter(|_| self.children.iter().all(ChildOrder::is_done))
+        {
+            status
+        } else if self.filled_quantity() > 0.0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Open
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.status(),
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Expired
+        )
+    }
+}
+
+/// Event emitted when an algo order is submitted, sends a slice, or one of its children changes,
+/// with the state of the whole algo order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlgoEvent {
+    pub algo: AlgoOrder,
+    /// Aggregated status of the algo order
+    pub status: OrderStatus,
+    /// The child which was sent or changed, None when the algo order is submitted or stopped
+    #[serde(rename = "orderId")]
+    pub order_id: Option<OrderId>,
+}
+
+#[derive(Debug, Default)]
+struct AlgoState {
+    algos: HashMap<OrderId, AlgoOrder>,
+    /// Algo order of each child
+    children: HashMap<OrderId, OrderId>,
+    listeners: Vec<Sender<AlgoEvent>>,
+}
+
+impl AlgoState {
+    fn publish(&mut self, algo_id: OrderId, order_id: Option<OrderId>) {
+        let Some(algo) = self.algos.get(&algo_id) else {
+            return;
+        };
+        let event = AlgoEvent {
+            algo: algo.clone(),
+            status: algo.status(),
+            order_id,
+        };
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+        // A done algo order is forgotten once its last event is out
+        if algo.is_done() {
+            for child in algo.children.iter() {
+                self.children.remove(&child.order_id);
+            }
+            self.algos.remove(&algo_id);
+        }
+    }
+}
+
+/// Scheduler of the live algo orders: `due` hands out the slices to send at a time, and it is fed with
+/// the updates of the orderbooks to follow the children and the market volume
+#[derive(Debug, Clone, Default)]
+pub struct AlgoScheduler {
+    state: Arc<Mutex<AlgoState>>,
+}
+
+impl AlgoScheduler {
+    pub fn new() -> AlgoScheduler {
+        AlgoScheduler::default()
+    }
+
+    /// Register an algo order, its slices are sent by the next runs
+    ///
+    /// #Returns
+    /// * bool - False if a live algo order already has this ID
+    pub fn submit(&self, algo: AlgoOrder) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.algos.contains_key(&algo.algo_id) {
+            return false;
+        }
+        let algo_id = algo.algo_id;
+        state.algos.insert(algo_id, algo);
+        state.publish(algo_id, None);
+        true
+    }
+
+    /// Current state of a live algo order
+    pub fn get(&self, algo_id: OrderId) -> Option<AlgoOrder> {
+        self.state.lock().unwrap().algos.get(&algo_id).cloned()
+    }
+
+    /// Algo order of an order, None if the order is not the child of a live algo order
+    pub fn algo_of(&self, order_id: OrderId) -> Option<OrderId> {
+        self.state.lock().unwrap().children.get(&order_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().algos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().algos.is_empty()
+    }
+
+    /// Receive every algo event emitted from now on
+    pub fn subscribe(&self) -> Receiver<AlgoEvent> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+
+    /// Children to send at a time, counted as sent, and the algo orders whose window ended, stopped.
+    /// The slices are rounded down to the lot size of their orderbook, the remainder goes with the
+    /// next slice.
+    ///
+    /// #Parameters
+    /// * 'now' - The current time in milliseconds since UNIX epoch
+    /// * 'lot_size' - The lot size of the orderbook of a symbol
+    ///
+    /// #Returns
+    /// * (Vec<Order>, Vec<AlgoOrder>) - The children to send, and the algo orders stopped at the end
+    ///   of their window whose open children are to cancel
+    pub fn due(
+        &self,
+        now: u64,
+        lot_size: impl Fn(Symbol) -> Option<f64>,
+    ) -> (Vec<Order>, Vec<AlgoOrder>) {
+        let mut state = self.state.lock().unwrap();
+        let mut slices = Vec::new();
+        let mut expired = Vec::new();
+        let algo_ids: Vec<OrderId> = state.algos.keys().copied().collect();
+        for algo_id in algo_ids {
+            let algo = state.algos.get_mut(&algo_id).unwrap();
+            if algo.stop_status.is_some() {
+                continue;
+            }
+            if now >= algo.end_at {
+                algo.stop_status = Some(OrderStatus::Expired);
+                expired.push(algo.clone());
+                state.publish(algo_id, None);
+                continue;
+            }
+            let mut quantity = algo.target(now) - algo.sent_quantity;
+            if algo.sent_quantity + quantity < algo.quantity {
+                if let Some(lot_size) = lot_size(algo.symbol) {
+                    quantity = (quantity / lot_size + 1e-9).floor() * lot_size;
+                }
+            }
+            if quantity <= 1e-9 {
+                continue;
+            }
+            let order_type = match algo.price {
+                Some(_) => OrderType::Limit,
+                None => OrderType::Market,
+            };
+            let child = Order {
+                client_order_id: Some(algo.algo_id.into()),
+                ..Order::new(
+                    algo.user_id,
+                    algo.symbol,
+                    algo.side,
+                    quantity,
+                    algo.price,
+                    order_type,
+                )
+            };
+            algo.sent_quantity += quantity;
+            algo.children.push(ChildOrder::new(&child));
+            state.children.insert(child.id, algo_id);
+            state.publish(algo_id, Some(child.id));
+            slices.push(child);
+        }
+        (slices, expired)
+    }
+
+    /// Stop an algo order, no more slices are sent
+    ///
+    /// #Returns
+    /// * Option<AlgoOrder> - The stopped algo order whose open children are to cancel, None if it is
+    ///   unknown or already stopped
+    pub fn stop(&self, algo_id: OrderId) -> Option<AlgoOrder> {
+        let mut state = self.state.lock().unwrap();
+        let algo = state
+            .algos
+            .get_mut(&algo_id)
+            .filter(|algo| algo.stop_status.is_none())?;
+        algo.stop_status = Some(OrderStatus::Cancelled);
+        let algo = algo.clone();
+        state.publish(algo_id, None);
+        Some(algo)
+    }
+
+    /// Mark a child which was not accepted by its orderbook as cancelled, its quantity is sent again
+    /// with the next slice
+    pub fn drop_child(&self, order: &Order) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let Some(&algo_id) = state.children.get(&order.id) else {
+                return;
+            };
+            if let Some(algo) = state.algos.get_mut(&algo_id) {
+                algo.sent_quantity -= order.quantity;
+            }
+        }
+        self.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            order: Some(*order),
+            cancel_id: Some(order.id),
+            ..Default::default()
+        });
+    }
+
+    /// Update the children and the market volume from an update published by an orderbook, and emit
+    /// an event for each child which changed
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        if state.algos.is_empty() {
+            return;
+        }
+        // (child, executed quantity, execution price, new status)
+        let changes: Vec<(OrderId, f64, f64, OrderStatus)> = match update.update_type {
+            // The Filled updates come before the trades of the fills, the children are filled by
+            // their trades so that the price of the last fill is counted
+            OrderbookUpdateType::NewTrades => {
+                let Some(trade) = update.trade.as_ref() else {
+                    return;
+                };
+                let owners = [
+                    state.children.get(&trade.buy_order_id).copied(),
+                    state.children.get(&trade.sell_order_id).copied(),
+                ];
+                for algo in state.algos.values_mut() {
+                    if algo.symbol == trade.symbol && !owners.contains(&Some(algo.algo_id)) {
+                        algo.market_volume += trade.quantity;
+                    }
+                }
+                let status = |remaining: f64| match remaining > 0.0 {
+                    true => OrderStatus::PartiallyFilled,
+                    false => OrderStatus::Filled,
+                };
+                vec![
+                    (
+                        trade.buy_order_id,
+                        trade.quantity,
+                        trade.price,
+                        status(trade.buy_remaining),
+                    ),
+                    (
+                        trade.sell_order_id,
+                        trade.quantity,
+                        trade.price,
+                        status(trade.sell_remaining),
+                    ),
+                ]
+            }
+            // A cancel of an order which is not in the book carries no order
+            OrderbookUpdateType::Cancel => update
+                .order
+                .map(|o| (o.id, 0.0, 0.0, OrderStatus::Cancelled))
+                .into_iter()
+                .collect(),
+            OrderbookUpdateType::Expired => update
+                .order
+                .map(|o| (o.id, 0.0, 0.0, OrderStatus::Expired))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (order_id, filled, price, status) in changes {
+            let Some(&algo_id) = state.children.get(&order_id) else {
+                continue;
+            };
+            let Some(child) = state.algos.get_mut(&algo_id).and_then(|algo| {
+                algo.children
+                    .iter_mut()
+                    .find(|child| child.order_id == order_id)
+            }) else {
+                continue;
+            };
+            if child.is_done() {
+                continue;
+            }
+            child.filled_quantity += filled;
+            child.notional += filled * price;
+            child.status = status;
+            if child.status == OrderStatus::Filled {
+                child.filled_quantity = child.quantity;
+            }
+            state.publish(algo_id, Some(order_id));
+        }
+    }
+}
+
+impl OrderbooksManager {
+    /// Submit an algo order slicing a parent order into child orders over its window, the slices
+    /// are sent by `run_algos`. The children carry the algo order ID as client order ID.
+    ///
+    /// #Parameters
+    /// * 'algo' - The algo order
+    ///
+    /// #Returns
+    /// * OrderId - The ID of the algo order, an error if it is invalid, if its orderbook is unknown
+    ///   or if a live algo order has its ID
+    pub fn submit_algo(&mut self, algo: AlgoOrder) -> Result<OrderId, Error> {
+        algo.validate()?;
+        let parent = Order::new(
+            algo.user_id,
+            algo.symbol,
+            algo.side,
+            algo.quantity,
+            algo.price,
+            match algo.price {
+                Some(_) => OrderType::Limit,
+                None => OrderType::Market,
+            },
+        );
+        self.validate_order(&parent)?;
+        let algo_id = algo.algo_id;
+        if !self.algos.submit(algo) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "Algo order already exists",
+            ));
+        }
+        Ok(algo_id)
+    }
+
+    /// Send the slices of the algo orders due now, to be called periodically, see `spawn_algo_thread`
+    pub fn run_algos(&mut self) -> Vec<Order> {
+        self.run_algos_at(self.clock.now_millis())
+    }
+
+    /// Same as run_algos with an explicit current time in milliseconds since UNIX epoch
+    ///
+    /// #Returns
+    /// * Vec<Order> - The children accepted by their orderbook
+    pub fn run_algos_at(&mut self, now: u64) -> Vec<Order> {
+        let (slices, expired) = self.algos.due(now, |symbol| {
+            self.orderbooks
+                .get(&symbol)
+                .and_then(|orderbook| orderbook.lot_size)
+        });
+        for algo in expired.iter() {
+            self.cancel_children(algo);
+        }
+        self.dispatch();
+        let mut sent = Vec::with_capacity(slices.len());
+        for child in slices {
+            match self.add_order(child) {
+                Ok(_) => sent.push(child),
+                Err(_) => self.algos.drop_child(&child),
+            }
+        }
+        sent
+    }
+
+    /// Cancel an algo order: no more slices are sent and its open children are cancelled
+    ///
+    /// #Parameters
+    /// * 'algo_id' - The ID of the algo order
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled children, an error if the algo order is unknown or already stopped
+    pub fn cancel_algo(&mut self, algo_id: OrderId) -> Result<Vec<Order>, Error> {
+        let algo = self
+            .algos
+            .stop(algo_id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Algo order not found"))?;
+        self.cancel_children(&algo);
+        let mut cancelled = Vec::new();
+        self.dispatch_with(|update| {
+            if let Some(order) = update.order.filter(|o| {
+                update.update_type == OrderbookUpdateType::Cancel
+                    && algo.children.iter().any(|child| child.order_id == o.id)
+            }) {
+                cancelled.push(order);
+            }
+        });
+        Ok(cancelled)
+    }
+
+    /// Cancel the open children of a stopped algo order in their orderbook
+    fn cancel_children(&mut self, algo: &AlgoOrder) {
+        if let Some(orderbook) = self.orderbooks.get_mut(&algo.symbol) {
+            for child in algo.open_children() {
+                let _ = orderbook.cancel_order(child.order_id, algo.side);
+            }
+        }
+    }
+
+    /// Current state of a live algo order, the algo orders are forgotten once done
+    ///
+    /// #Parameters
+    /// * 'algo_id' - The ID of the algo order
+    pub fn get_algo(&self, algo_id: OrderId) -> Result<AlgoOrder, Error> {
+        self.algos
+            .get(algo_id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Algo order not found"))
+    }
+
+    /// Receive every algo event emitted from now on
+    pub fn subscribe_algo_events(&self) -> Receiver<AlgoEvent> {
+        self.algos.subscribe()
+    }
+}
+
+/// Send the due slices of the algo orders of a manager in a background thread, every period.
+/// The thread stops once the manager is dropped.
+///
+/// #Parameters
+/// * 'manager' - The manager
+/// * 'period' - Time between two runs, typically a fraction of the slice interval
+#[cfg(feature = "native")]
+pub fn spawn_algo_thread(
+    manager: &Arc<Mutex<OrderbooksManager>>,
+    period: Duration,
+) -> std::thread::JoinHandle<()> {
+    let manager = Arc::downgrade(manager);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(period);
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        let Ok(mut manager) = manager.lock() else {
+            return;
+        };
+        manager.run_algos();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::orderbook_config::OrderbookConfig;
+
+    fn order(symbol: Symbol, side: OrderSide, quantity: f64, price: Option<f64>) -> Order {
+        let order_type = match price {
+            Some(_) => OrderType::Limit,
+            None => OrderType::Market,
+        };
+        Order::new(UserId(1), symbol, side, quantity, price, order_type)
+    }
+
+    #[test]
+    fn test_targets_of_the_strategies() {
+        let parent = order(Symbol(1), OrderSide::Buy, 8.0, None);
+        let twap = AlgoOrder::new(&parent, AlgoStrategy::Twap { slices: 4 }, 1000, 5000);
+        assert_eq!(twap.target(999), 0.0);
+        assert_eq!(twap.target(1000), 2.0);
+        assert_eq!(twap.target(2999), 4.0);
+        assert_eq!(twap.target(4000), 8.0);
+
+        let vwap = AlgoOrder::new(
+            &parent,
+            AlgoStrategy::Vwap {
+                profile: vec![1.0, 3.0],
+            },
+            1000,
+            5000,
+        );
+        assert_eq!(vwap.target(1000), 2.0);
+        assert_eq!(vwap.target(3000), 8.0);
+
+        let mut pov = AlgoOrder::new(&parent, AlgoStrategy::Pov { rate: 0.1 }, 1000, 5000);
+        pov.market_volume = 30.0;
+        assert_eq!(pov.target(1000), 3.0);
+        pov.market_volume = 300.0;
+        assert_eq!(pov.target(1000), 8.0);
+
+        for strategy in [
+            AlgoStrategy::Twap { slices: 0 },
+            AlgoStrategy::Vwap { profile: vec![0.0] },
+            AlgoStrategy::Pov { rate: 1.5 },
+        ] {
+            assert!(AlgoOrder::new(&parent, strategy, 1000, 5000)
+                .validate()
+                .is_err());
+        }
+        assert!(
+            AlgoOrder::new(&parent, AlgoStrategy::Twap { slices: 1 }, 5000, 5000)
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_twap_slices_over_the_window() {
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook_with_config(Symbol(1), OrderbookConfig::default().with_lot_size(1.0));
+        manager
+            .add_order(order(Symbol(1), OrderSide::Sell, 10.0, Some(100.0)))
+            .unwrap();
+        let events = manager.subscribe_algo_events();
+
+        let parent = order(Symbol(1), OrderSide::Buy, 5.0, Some(101.0));
+        let algo = AlgoOrder::new(&parent, AlgoStrategy::Twap { slices: 2 }, 1000, 3000);
+        assert_eq!(manager.submit_algo(algo.clone()).unwrap(), parent.id);
+        assert!(manager.submit_algo(algo).is_err());
+
+        // 2.5 rounded down to the lot size, the remainder goes with the last slice
+        assert!(manager.run_algos_at(999).is_empty());
+        let sent = manager.run_algos_at(1000);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].quantity, 2.0);
+        assert_eq!(sent[0].client_order_id, Some(parent.id.into()));
+        assert!(manager.run_algos_at(1999).is_empty());
+        let state = manager.get_algo(parent.id).unwrap();
+        assert_eq!(state.status(), OrderStatus::PartiallyFilled);
+        assert_eq!(state.filled_quantity(), 2.0);
+
+        assert_eq!(manager.run_algos_at(2000)[0].quantity, 3.0);
+        assert!(manager.get_algo(parent.id).is_err());
+        let last = events.try_iter().last().unwrap();
+        assert_eq!(last.status, OrderStatus::Filled);
+        assert_eq!(last.algo.average_price(), Some(100.0));
+        assert!(manager.algos.is_empty());
+    }
+
+    #[test]
+    fn test_pov_follows_the_market_until_cancelled() {
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(Symbol(1));
+        let parent = order(Symbol(1), OrderSide::Sell, 10.0, Some(100.0));
+        manager
+            .submit_algo(AlgoOrder::new(
+                &parent,
+                AlgoStrategy::Pov { rate: 0.5 },
+                0,
+                u64::MAX,
+            ))
+            .unwrap();
+        assert!(manager.run_algos_at(1).is_empty());
+
+        // the market trades 4, the algo sells 2 which rest
+        manager
+            .add_order(order(Symbol(1), OrderSide::Sell, 4.0, Some(99.0)))
+            .unwrap();
+        manager
+            .add_order(order(Symbol(1), OrderSide::Buy, 4.0, Some(99.0)))
+            .unwrap();
+        let sent = manager.run_algos_at(2);
+        assert_eq!(sent[0].quantity, 2.0);
+        assert!(manager.run_algos_at(3).is_empty());
+        // the fills of the algo are not counted as market volume
+        manager
+            .add_order(order(Symbol(1), OrderSide::Buy, 2.0, Some(100.0)))
+            .unwrap();
+        let state = manager.get_algo(parent.id).unwrap();
+        assert_eq!((state.market_volume, state.filled_quantity()), (4.0, 2.0));
+
+        manager
+            .add_order(order(Symbol(1), OrderSide::Sell, 2.0, Some(100.0)))
+            .unwrap();
+        manager
+            .add_order(order(Symbol(1), OrderSide::Buy, 2.0, Some(100.0)))
+            .unwrap();
+        let resting = manager.run_algos_at(4)[0];
+        assert_eq!(resting.quantity, 1.0);
+
+        let events = manager.subscribe_algo_events();
+        let cancelled = manager.cancel_algo(parent.id).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].id, resting.id);
+        let last = events.try_iter().last().unwrap();
+        assert_eq!(last.status, OrderStatus::Cancelled);
+        assert_eq!(last.algo.filled_quantity(), 2.0);
+        assert!(manager.cancel_algo(parent.id).is_err());
+    }
+
+    #[test]
+    fn test_open_children_cancelled_at_the_end_of_the_window() {
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(Symbol(1));
+        let parent = order(Symbol(1), OrderSide::Buy, 3.0, Some(100.0));
+        manager
+            .submit_algo(AlgoOrder::new(
+                &parent,
+                AlgoStrategy::Twap { slices: 3 },
+                0,
+                3000,
+            ))
+            .unwrap();
+        manager.run_algos_at(0);
+        manager.run_algos_at(1000);
+        assert_eq!(manager.orderbooks[&Symbol(1)].bids.len(), 2);
+
+        let events = manager.subscribe_algo_events();
+        assert!(manager.run_algos_at(3000).is_empty());
+        assert!(manager.orderbooks[&Symbol(1)].bids.is_empty());
+        let last = events.try_iter().last().unwrap();
+        assert_eq!(last.status, OrderStatus::Expired);
+        assert_eq!(last.algo.sent_quantity, 2.0);
+        assert!(manager.get_algo(parent.id).is_err());
+    }
+}
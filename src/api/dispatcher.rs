@@ -0,0 +1,225 @@
+use super::requests::{
+    AmendRequest, ApiRequest, BookDepthRequest, CancelRequest, PlaceOrderRequest,
+};
+use super::responses::{ApiResponse, BookDepthResponse, ErrorResponse};
+use crate::structs::order_ack::OrderAck;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use std::io::{Error, ErrorKind};
+
+/// Apply the API requests on an OrderbooksManager, so that a transport only has to decode the
+/// requests and encode the responses.
+///
+/// ```ignore
+/// async fn place_order(
+///     State(manager): State<Arc<Mutex<OrderbooksManager>>>,
+///     Json(request): Json<PlaceOrderRequest>,
+/// ) -> Result<Json<OrderAck>, (StatusCode, Json<ErrorResponse>)> {
+///     let mut manager = manager.lock().unwrap();
+///     Dispatcher::new(&mut manager)
+///         .place_order(request)
+///         .map(Json)
+///         .map_err(|e| (StatusCode::from_u16(e.http_status()).unwrap(), Json(e)))
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Dispatcher<'a> {
+    manager: &'a mut OrderbooksManager,
+}
+
+impl<'a> Dispatcher<'a> {
+    pub fn new(manager: &'a mut OrderbooksManager) -> Dispatcher<'a> {
+        Dispatcher { manager }
+    }
+
+    /// Run any request, returning the matching response
+    pub fn dispatch(&mut self, request: ApiRequest) -> Result<ApiResponse, ErrorResponse> {
+        match request {
+            ApiRequest::PlaceOrder(request) => self.place_order(request).map(ApiResponse::Ack),
+            ApiRequest::Cancel(request) => self.cancel(request).map(ApiResponse::Ack),
+            ApiRequest::Amend(request) => self.amend(request).map(|_| ApiResponse::Amended),
+            ApiRequest::BookDepth(request) => self.book_depth(request).map(ApiResponse::BookDepth),
+        }
+    }
+
+    pub fn place_order(&mut self, request: PlaceOrderRequest) -> Result<OrderAck, ErrorResponse> {
+        let metadata = request.metadata.clone();
+        let order = request.into_order()?;
+        Ok(self.manager.add_client_order(order, metadata)?)
+    }
+
+    pub fn cancel(&mut self, request: CancelRequest) -> Result<OrderAck, ErrorResponse> {
+        Ok(self
+            .manager
+            .cancel_order(request.order_id, request.symbol, request.side)?)
+    }
+
+    pub fn amend(&mut self, request: AmendRequest) -> Result<(), ErrorResponse> {
+        request.validate()?;
+        match (request.price, request.quantity) {
+            (Some(price), Some(quantity)) => {
+                self.manager
+                    .replace_order(request.order_id, request.symbol, price, quantity)?;
+            }
+            (Some(price), None) => {
+                self.manager.amend_order_price(
+                    request.symbol,
+                    request.order_id,
+                    price,
+                    request.side,
+                )?;
+            }
+            (None, Some(quantity)) => {
+                self.manager.amend_order_quantity(
+                    request.symbol,
+                    request.order_id,
+                    quantity,
+                    request.side,
+                )?;
+            }
+            (None, None) => unreachable!("validated amend request"),
+        }
+        Ok(())
+    }
+
+    pub fn book_depth(
+        &self,
+        request: BookDepthRequest,
+    ) -> Result<BookDepthResponse, ErrorResponse> {
+        let orderbook = self
+            .manager
+            .orderbooks
+            .get(&request.symbol)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Orderbook not found"))?;
+        let (bids, asks) = orderbook.depth(request.depth.unwrap_or(usize::MAX));
+        let precision = self.manager.symbols.precision(request.symbol);
+        let round = |levels: Vec<_>| {
+            levels
+                .into_iter()
+                .map(|l| precision.round_level(l))
+                .collect()
+        };
+        Ok(BookDepthResponse {
+            symbol: request.symbol,
+            sequence: orderbook.sequence,
+            bids: round(bids),
+            asks: round(asks),
+            metrics: orderbook.metrics(),
+            checksum: orderbook.checksum(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_status::OrderStatus;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::ids::{OrderId, Symbol};
+    use ulid::Ulid;
+
+    fn place(symbol: Symbol, side: OrderSide, price: f64) -> PlaceOrderRequest {
+        PlaceOrderRequest {
+            user_id: Ulid::new().into(),
+            symbol,
+            side,
+            quantity: 2.0,
+            price: Some(price),
+            order_type: OrderType::Limit,
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            client_order_id: Some(1),
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_dispatch_json_requests() {
+        let symbol: Symbol = Ulid::new().into();
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(symbol);
+        let mut dispatcher = Dispatcher::new(&mut manager);
+
+        let request =
+            serde_json::to_string(&ApiRequest::PlaceOrder(place(symbol, OrderSide::Buy, 10.0)))
+                .unwrap();
+        let request: ApiRequest = serde_json::from_str(&request).unwrap();
+        let ack = match dispatcher.dispatch(request).unwrap() {
+            ApiResponse::Ack(ack) => ack,
+            other => panic!("unexpected response {:?}", other),
+        };
+        assert_eq!(ack.status, OrderStatus::Open);
+        assert_eq!(ack.client_order_id, Some(1));
+
+        let amend = AmendRequest {
+            symbol,
+            order_id: ack.order_id,
+            side: OrderSide::Buy,
+            price: None,
+            quantity: Some(3.0),
+        };
+        assert_eq!(
+            dispatcher.dispatch(ApiRequest::Amend(amend)).unwrap(),
+            ApiResponse::Amended
+        );
+        dispatcher
+            .place_order(place(symbol, OrderSide::Sell, 12.0))
+            .unwrap();
+
+        let depth = dispatcher
+            .book_depth(BookDepthRequest {
+                symbol,
+                depth: Some(1),
+            })
+            .unwrap();
+        assert_eq!(depth.bids.len(), 1);
+        assert_eq!(depth.bids[0].quantity, 3.0);
+        assert_eq!(depth.asks[0].price, 12.0);
+        assert_eq!(depth.metrics.bid_volume, 3.0);
+
+        let cancel = CancelRequest {
+            symbol,
+            order_id: ack.order_id,
+            side: OrderSide::Buy,
+        };
+        assert_eq!(
+            dispatcher.cancel(cancel).unwrap().status,
+            OrderStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let symbol: Symbol = Ulid::new().into();
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(symbol);
+        let mut dispatcher = Dispatcher::new(&mut manager);
+
+        let mut market_with_price = place(symbol, OrderSide::Buy, 10.0);
+        market_with_price.order_type = OrderType::Market;
+        let error = dispatcher.place_order(market_with_price).unwrap_err();
+        assert_eq!(error.code, "INVALID_INPUT");
+        assert_eq!(error.http_status(), 400);
+
+        let mut oversized_display = place(symbol, OrderSide::Buy, 10.0);
+        oversized_display.display_quantity = Some(5.0);
+        assert!(dispatcher.place_order(oversized_display).is_err());
+
+        let unknown_symbol = place(Ulid::new().into(), OrderSide::Buy, 10.0);
+        let error = dispatcher.place_order(unknown_symbol).unwrap_err();
+        assert_eq!(error.http_status(), 404);
+
+        let empty_amend = AmendRequest {
+            symbol,
+            order_id: OrderId(1),
+            side: OrderSide::Buy,
+            price: None,
+            quantity: None,
+        };
+        assert!(dispatcher.amend(empty_amend).is_err());
+    }
+}
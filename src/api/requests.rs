@@ -0,0 +1,176 @@
+use crate::enums::order_type::OrderType;
+use crate::enums::peg_reference::PegReference;
+use crate::enums::side::OrderSide;
+use crate::enums::time_in_force::TimeInForce;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::{Metadata, Order};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Place a new order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceOrderRequest {
+    pub user_id: UserId,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub quantity: f64,
+    /// None for market orders
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub order_type: OrderType,
+    /// Good-Till-Date expiry in milliseconds since UNIX epoch
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Day orders are expired at the close of the trading session
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    /// Visible slice of an iceberg order
+    #[serde(default)]
+    pub display_quantity: Option<f64>,
+    #[serde(default)]
+    pub client_order_id: Option<u128>,
+    /// Smallest execution the order accepts
+    #[serde(default)]
+    pub min_fill_quantity: Option<f64>,
+    /// Price tracked by a pegged order, which then needs no price
+    #[serde(default)]
+    pub peg_reference: Option<PegReference>,
+    /// Distance of the pegged price from its reference
+    #[serde(default)]
+    pub peg_offset: f64,
+    /// Tags carried into the updates and the trades of the order
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+}
+
+impl PlaceOrderRequest {
+    /// Check the request is well formed, the orderbook checks its own rules (tick size, state, risk...) on placement
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(self.quantity.is_finite() && self.quantity > 0.0) {
+            return Err(invalid("Order quantity must be positive"));
+        }
+        if self.peg_reference.is_some()
+            && (self.order_type != OrderType::Limit || !self.peg_offset.is_finite())
+        {
+            return Err(invalid(
+                "Pegged orders must be limit orders with a finite offset",
+            ));
+        }
+        match (self.order_type, self.price) {
+            (OrderType::Limit, None) if self.peg_reference.is_none() => {
+                return Err(invalid("Limit orders need a price"))
+            }
+            (OrderType::Market, Some(_)) => return Err(invalid("Market orders take no price")),
+            (_, Some(price)) if !(price.is_finite() && price > 0.0) => {
+                return Err(invalid("Order price must be positive"))
+            }
+            _ => {}
+        }
+        if let Some(display_quantity) = self.display_quantity {
+            if !(display_quantity > 0.0 && display_quantity <= self.quantity) {
+                return Err(invalid(
+                    "Display quantity must be positive and at most the order quantity",
+                ));
+            }
+        }
+        if let Some(min_fill_quantity) = self.min_fill_quantity {
+            if !(min_fill_quantity > 0.0 && min_fill_quantity <= self.quantity) {
+                return Err(invalid(
+                    "Minimum fill quantity must be positive and at most the order quantity",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the request and build the order it places
+    pub fn into_order(self) -> Result<Order, Error> {
+        self.validate()?;
+        let mut order = Order::new(
+            self.user_id,
+            self.symbol,
+            self.side,
+            self.quantity,
+            self.price,
+            self.order_type,
+        );
+        order.expires_at = self.expires_at;
+        order.time_in_force = self.time_in_force;
+        order.client_order_id = self.client_order_id;
+        order.min_fill_quantity = self.min_fill_quantity;
+        order.peg_reference = self.peg_reference;
+        order.peg_offset = self.peg_offset;
+        if let Some(display_quantity) = self.display_quantity {
+            order = order.with_display_quantity(display_quantity);
+        }
+        Ok(order)
+    }
+}
+
+/// Cancel a resting order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelRequest {
+    pub symbol: Symbol,
+    pub order_id: OrderId,
+    pub side: OrderSide,
+}
+
+/// Amend the price, the quantity or both of a resting order, both at once is a cancel-replace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AmendRequest {
+    pub symbol: Symbol,
+    pub order_id: OrderId,
+    pub side: OrderSide,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+}
+
+impl AmendRequest {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.price.is_none() && self.quantity.is_none() {
+            return Err(invalid("Either the price or the quantity must be amended"));
+        }
+        if self
+            .price
+            .is_some_and(|price| !(price.is_finite() && price > 0.0))
+        {
+            return Err(invalid("Order price must be positive"));
+        }
+        if self
+            .quantity
+            .is_some_and(|quantity| !(quantity.is_finite() && quantity > 0.0))
+        {
+            return Err(invalid("Order quantity must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Read the best price levels of an orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookDepthRequest {
+    pub symbol: Symbol,
+    /// Number of levels per side, every level when None
+    #[serde(default)]
+    pub depth: Option<usize>,
+}
+
+/// Any request of the API, keyed by its name (`{"PlaceOrder": {...}}`) so a single endpoint or message can carry them all
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ApiRequest {
+    PlaceOrder(PlaceOrderRequest),
+    Cancel(CancelRequest),
+    Amend(AmendRequest),
+    BookDepth(BookDepthRequest),
+}
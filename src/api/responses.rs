@@ -0,0 +1,69 @@
+use crate::structs::book_metrics::BookMetrics;
+use crate::structs::ids::Symbol;
+use crate::structs::level_book::PriceLevel;
+use crate::structs::order_ack::OrderAck;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// Best price levels of an orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookDepthResponse {
+    pub symbol: Symbol,
+    /// Sequence number of the last update applied to the orderbook
+    pub sequence: u64,
+    /// Best price first
+    pub bids: Vec<PriceLevel>,
+    /// Best price first
+    pub asks: Vec<PriceLevel>,
+    pub metrics: BookMetrics,
+    /// CRC32 of the best levels of the whole book, see `book_checksum`
+    pub checksum: u32,
+}
+
+/// Result of any request of the API, keyed by its name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ApiResponse {
+    Ack(OrderAck),
+    Amended,
+    BookDepth(BookDepthResponse),
+}
+
+/// Error body of a failed request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// Stable code of the error, e.g. NOT_FOUND or INVALID_INPUT
+    pub code: String,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    /// HTTP status matching the error, for the handlers built on top of the API
+    pub fn http_status(&self) -> u16 {
+        match self.code.as_str() {
+            "INVALID_INPUT" => 400,
+            "REJECTED" => 403,
+            "NOT_FOUND" => 404,
+            "ALREADY_EXISTS" => 409,
+            "UNAVAILABLE" => 503,
+            _ => 500,
+        }
+    }
+}
+
+impl From<Error> for ErrorResponse {
+    fn from(error: Error) -> ErrorResponse {
+        let code = match error.kind() {
+            ErrorKind::InvalidInput | ErrorKind::InvalidData => "INVALID_INPUT",
+            ErrorKind::PermissionDenied => "REJECTED",
+            ErrorKind::NotFound => "NOT_FOUND",
+            ErrorKind::AlreadyExists => "ALREADY_EXISTS",
+            ErrorKind::BrokenPipe => "UNAVAILABLE",
+            _ => "INTERNAL",
+        };
+        ErrorResponse {
+            code: code.to_string(),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::metrics::LatencyHistogram;
+use crate::structs::ids::{Symbol, UserId};
+use crate::structs::order::Order;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::sharded_manager::ShardedManager;
+use crate::testing::rng::SplitMix64;
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Throughput and latency of the engine over a run of `run_scaling`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalingReport {
+    pub symbols: usize,
+    /// Number of shard threads matching the orders, each fed by its own client thread
+    pub threads: usize,
+    /// Orders submitted over every symbol
+    pub orders: usize,
+    pub trades: u64,
+    /// Wall time from the first order submitted to the last order acknowledged
+    pub elapsed: Duration,
+    /// Orders acknowledged per second
+    pub throughput: f64,
+    /// Time from the submission of an order to its acknowledgement, in nanoseconds
+    pub latency: LatencyHistogram,
+}
+
+impl ScalingReport {
+    /// Throughput of this run relative to another, e.g. the run on a single thread
+    ///
+    /// #Parameters
+    /// * 'baseline' - The run to compare with
+    pub fn speedup(&self, baseline: &ScalingReport) -> f64 {
+        if baseline.throughput == 0.0 {
+            return 0.0;
+        }
+        self.throughput / baseline.throughput
+    }
+}
+
+/// Limit orders around a mid of 100, half of them crossing the spread, generated from a fixed seed
+fn generate_orders(symbol: Symbol, count: usize) -> Vec<Order> {
+    let mut rng = SplitMix64::new(symbol.0 as u64);
+    (0..count)
+        .map(|_| {
+            let side = match rng.next_u64() % 2 {
+                0 => OrderSide::Buy,
+                _ => OrderSide::Sell,
+            };
+            let ticks = rng.next_in(0, 20) as f64 - 10.0;
+            Order::new(
+                UserId(rng.next_in(1, 100) as u128),
+                symbol,
+                side,
+                rng.next_in(1, 10) as f64,
+                Some(100.0 + ticks * 0.01),
+                OrderType::Limit,
+            )
+        })
+        .collect()
+}
+
+/// Measure the engine on this hardware: the orderbooks of `symbols` symbols are spread over
+/// `threads` shards of a `ShardedManager`, and as many client threads submit the orders of the
+/// shards in parallel, each waiting for the acknowledgement of an order before sending the next.
+/// The orders are generated before the clock starts and are the same from one run to the other.
+///
+/// #Parameters
+/// * 'symbols' - The number of orderbooks
+/// * 'threads' - The number of shard threads, and of client threads
+/// * 'orders' - The number of orders, spread evenly over the symbols
+///
+/// #Returns
+/// * ScalingReport - The throughput, the latencies and the trades of the run
+pub fn run_scaling(symbols: usize, threads: usize, orders: usize) -> ScalingReport {
+    assert!(symbols > 0, "the benchmark needs at least one symbol");
+    assert!(threads > 0, "the benchmark needs at least one thread");
+    let template = OrderbooksManager::new();
+    let manager = ShardedManager::with_template(&template, threads);
+    // the symbol IDs are chosen so that client k feeds shard k only
+    let mut flows: Vec<Vec<Vec<Order>>> = vec![Vec::new(); threads];
+    for index in 0..symbols {
+        let symbol = Symbol(index as u128 + 1);
+        manager.new_orderbook(symbol).unwrap();
+        let count = orders / symbols + usize::from(index < orders % symbols);
+        flows[manager.shard_of(symbol)].push(generate_orders(symbol, count));
+    }
+
+    let start = Instant::now();
+    let clients: Vec<_> = flows
+        .into_iter()
+        .map(|flow| {
+            let manager = manager.clone();
+            thread::spawn(move || {
+                let mut latency = LatencyHistogram::default();
+                let longest = flow.iter().map(|o| o.len()).max().unwrap_or(0);
+                // the symbols of the shard are fed in turn
+                for index in 0..longest {
+                    for order in flow.iter().filter_map(|o| o.get(index)) {
+                        let sent = Instant::now();
+                        let _ = manager.add_order(*order);
+                        latency.record(sent.elapsed().as_nanos() as u64);
+                    }
+                }
+                latency
+            })
+        })
+        .collect();
+    let mut latency = LatencyHistogram::default();
+    for client in clients {
+        latency.merge(&client.join().unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    ScalingReport {
+        symbols,
+        threads,
+        orders,
+        trades: template.metrics().global.trades,
+        elapsed,
+        throughput: latency.count as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+        latency,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_scaling() {
+        let report = run_scaling(5, 2, 1_001);
+        assert_eq!(report.latency.count, 1_001);
+        assert!(report.trades > 0);
+        assert!(report.throughput > 0.0);
+        assert!(report.latency.quantile(0.99) >= report.latency.quantile(0.5));
+        assert_eq!(report.speedup(&report), 1.0);
+    }
+}
@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens when a match would execute outside of the price band
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum BandAction {
+    /// Stop matching and cancel the order which triggered it
+    #[default]
+    Reject,
+    /// Stop matching and halt the orderbook
+    Halt,
+}
+
+impl Eq for BandAction {}
+
+impl fmt::Display for BandAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BandAction::Reject => write!(f, "Reject"),
+            BandAction::Halt => write!(f, "Halt"),
+        }
+    }
+}
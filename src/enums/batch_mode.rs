@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How a batch of orders is applied
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum BatchMode {
+    /// Every order is admitted first along with the previous ones, the whole batch is rejected if one of them is not
+    #[default]
+    Atomic,
+    /// Each order is applied on its own, the invalid ones are rejected individually
+    BestEffort,
+}
+
+impl Eq for BatchMode {}
+
+impl fmt::Display for BatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchMode::Atomic => write!(f, "Atomic"),
+            BatchMode::BestEffort => write!(f, "BestEffort"),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What an orderbook does with an update it cannot deliver because the receiving end of its channel is gone
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum DeliveryFailurePolicy {
+    /// Keep the update in the dead-letter queue, to be inspected or drained later
+    #[default]
+    DeadLetter,
+    /// Lose the update
+    Drop,
+    /// Panic, taking the engine down
+    Panic,
+}
+
+impl Eq for DeliveryFailurePolicy {}
+
+impl fmt::Display for DeliveryFailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryFailurePolicy::DeadLetter => write!(f, "DeadLetter"),
+            DeliveryFailurePolicy::Drop => write!(f, "Drop"),
+            DeliveryFailurePolicy::Panic => write!(f, "Panic"),
+        }
+    }
+}
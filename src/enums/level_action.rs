@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happened to a price level, for the market-by-price feed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum LevelAction {
+    /// First order at the price
+    #[default]
+    Add,
+    /// New quantity or number of orders of an existing level
+    Change,
+    /// Last order of the level gone
+    Delete,
+}
+
+impl Eq for LevelAction {}
+
+impl fmt::Display for LevelAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelAction::Add => write!(f, "Add"),
+            LevelAction::Change => write!(f, "Change"),
+            LevelAction::Delete => write!(f, "Delete"),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Role of an order in an execution
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum Liquidity {
+    /// The order was resting in the book and provided the liquidity
+    #[default]
+    Maker,
+    /// The order was incoming and took the liquidity
+    Taker,
+}
+
+impl Eq for Liquidity {}
+
+impl fmt::Display for Liquidity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Liquidity::Maker => write!(f, "Maker"),
+            Liquidity::Taker => write!(f, "Taker"),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Flavor of the update stream chosen when subscribing
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum MarketFeed {
+    /// Every update as published, order by order
+    #[default]
+    MarketByOrder,
+    /// Only the price levels changed by the updates, without the orders nor the trades.
+    /// The levels are aggregated by the orderbook, a subscriber misses the changes of the updates
+    /// dropped or coalesced by its queue
+    MarketByPrice,
+}
+
+impl Eq for MarketFeed {}
+
+impl fmt::Display for MarketFeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarketFeed::MarketByOrder => write!(f, "MarketByOrder"),
+            MarketFeed::MarketByPrice => write!(f, "MarketByPrice"),
+        }
+    }
+}
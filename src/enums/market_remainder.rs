@@ -0,0 +1,25 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens to the unfilled quantity of a market order which exhausts the opposite side of the book
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum MarketRemainder {
+    /// Cancel the remainder, a Cancel update is published for it
+    #[default]
+    Cancel,
+    /// Rest the remainder in the book as a limit order at the last executed price,
+    /// it is cancelled as well when nothing was executed
+    ConvertToLimit,
+}
+
+impl Eq for MarketRemainder {}
+
+impl fmt::Display for MarketRemainder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarketRemainder::Cancel => write!(f, "Cancel"),
+            MarketRemainder::ConvertToLimit => write!(f, "ConvertToLimit"),
+        }
+    }
+}
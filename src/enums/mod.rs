@@ -1,6 +1,28 @@
+pub mod band_action;
+pub mod batch_mode;
+pub mod delivery_failure_policy;
+pub mod level_action;
+pub mod liquidity;
+pub mod market_feed;
+pub mod market_remainder;
 pub mod order_status;
 pub mod order_type;
+pub mod orderbook_state;
 pub mod orderbook_update_type;
+pub mod overflow_policy;
 pub mod payment_status;
+pub mod peg_reference;
+pub mod price_reference;
+pub mod ratio_action;
+pub mod reference_source;
+pub mod self_trade_prevention;
+pub mod settlement_direction;
+pub mod settlement_failure_policy;
+pub mod session_event_type;
+pub mod session_phase;
+pub mod shutdown_policy;
 pub mod side;
+pub mod sink_format;
+pub mod summary_privacy;
+pub mod time_in_force;
 pub mod trade_status;
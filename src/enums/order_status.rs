@@ -2,9 +2,10 @@ use core::fmt;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
 pub enum OrderStatus {
     #[serde(rename = "OPEN")]
+    #[default]
     Open,
     #[serde(rename = "CLOSED")]
     Closed,
@@ -16,26 +17,11 @@ pub enum OrderStatus {
     PartiallyFilled,
     #[serde(rename = "FILLED")]
     Filled,
-}
-
-impl Default for OrderStatus {
-    fn default() -> Self {
-        OrderStatus::Open
-    }
+    #[serde(rename = "EXPIRED")]
+    Expired,
 }
 
 impl OrderStatus {
-    pub fn to_string(&self) -> String {
-        match self {
-            OrderStatus::Open => "Open".to_string(),
-            OrderStatus::Closed => "Closed".to_string(),
-            OrderStatus::Cancelled => "Cancelled".to_string(),
-            OrderStatus::Pending => "Pending".to_string(),
-            OrderStatus::PartiallyFilled => "PartiallyFilled".to_string(),
-            OrderStatus::Filled => "Filled".to_string(),
-        }
-    }
-
     pub fn from_string(s: &str) -> OrderStatus {
         match s {
             "Open" => OrderStatus::Open,
@@ -44,22 +30,51 @@ impl OrderStatus {
             "Pending" => OrderStatus::Pending,
             "PartiallyFilled" => OrderStatus::PartiallyFilled,
             "Filled" => OrderStatus::Filled,
+            "Expired" => OrderStatus::Expired,
             _ => OrderStatus::Open,
         }
     }
+
+    /// Whether an order with this status left the book for good
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Closed
+                | OrderStatus::Cancelled
+                | OrderStatus::Filled
+                | OrderStatus::Expired
+        )
+    }
+
+    /// Whether an order with this status may move to another one. A pending order may become any status,
+    /// a live order may be filled further or leave the book and a terminal status is final.
+    ///
+    /// #Parameters
+    /// * 'next' - The new status
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        match self {
+            OrderStatus::Pending => true,
+            OrderStatus::Open => next != OrderStatus::Pending,
+            OrderStatus::PartiallyFilled => {
+                !matches!(next, OrderStatus::Pending | OrderStatus::Open)
+            }
+            _ => false,
+        }
+    }
 }
 
 impl Eq for OrderStatus {}
 
-impl Into<i32> for OrderStatus {
-    fn into(self) -> i32 {
-        match self {
+impl From<OrderStatus> for i32 {
+    fn from(value: OrderStatus) -> i32 {
+        match value {
             OrderStatus::Open => 0,
             OrderStatus::Closed => 1,
             OrderStatus::Cancelled => 2,
             OrderStatus::Pending => 3,
             OrderStatus::PartiallyFilled => 4,
             OrderStatus::Filled => 5,
+            OrderStatus::Expired => 6,
         }
     }
 }
@@ -73,7 +88,7 @@ impl fmt::Display for OrderStatus {
             OrderStatus::Pending => write!(f, "Pending"),
             OrderStatus::PartiallyFilled => write!(f, "PartiallyFilled"),
             OrderStatus::Filled => write!(f, "Filled"),
+            OrderStatus::Expired => write!(f, "Expired"),
         }
     }
 }
-
@@ -1,9 +1,10 @@
 use core::fmt;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
 pub enum OrderType {
     #[serde(rename = "LIMIT")]
+    #[default]
     Limit,
     #[serde(rename = "MARKET")]
     Market,
@@ -11,15 +12,9 @@ pub enum OrderType {
 
 impl Eq for OrderType {}
 
-impl Default for OrderType {
-    fn default() -> Self {
-        OrderType::Limit
-    }
-}
-
-impl Into<i32> for OrderType {
-    fn into(self) -> i32 {
-        match self {
+impl From<OrderType> for i32 {
+    fn from(value: OrderType) -> i32 {
+        match value {
             OrderType::Limit => 0,
             OrderType::Market => 1,
         }
@@ -27,13 +22,6 @@ impl Into<i32> for OrderType {
 }
 
 impl OrderType {
-    pub fn to_string(&self) -> String {
-        match self {
-            OrderType::Market => "MARKET".to_string(),
-            OrderType::Limit => "LIMIT".to_string(),
-        }
-    }
-
     pub fn from_string(s: &str) -> OrderType {
         match s {
             "MARKET" => OrderType::Market,
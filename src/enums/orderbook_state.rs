@@ -0,0 +1,86 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Trading state of an orderbook
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum OrderbookState {
+    /// Orders are matched as they arrive
+    #[default]
+    Continuous,
+    /// Trading is suspended, only cancels are accepted
+    Halted,
+    /// Limit orders accumulate without matching until the auction is run
+    AuctionCall,
+    /// Only cancels are accepted, e.g. before the close
+    CancelOnly,
+    /// No operation is accepted
+    Closed,
+    /// Mirror of the book of an external venue, its levels are set by a feed and no operation is accepted
+    Mirror,
+}
+
+impl OrderbookState {
+    /// Whether new orders are accepted
+    pub fn accepts_orders(&self) -> bool {
+        matches!(
+            self,
+            OrderbookState::Continuous | OrderbookState::AuctionCall
+        )
+    }
+
+    /// Whether the resting orders can be amended or replaced
+    pub fn accepts_amends(&self) -> bool {
+        self.accepts_orders()
+    }
+
+    /// Whether the resting orders can be cancelled
+    pub fn accepts_cancels(&self) -> bool {
+        !matches!(self, OrderbookState::Closed | OrderbookState::Mirror)
+    }
+
+    /// Whether the crossing orders are matched
+    pub fn matches_orders(&self) -> bool {
+        *self == OrderbookState::Continuous
+    }
+
+    pub fn from_string(s: &str) -> OrderbookState {
+        match s {
+            "Continuous" => OrderbookState::Continuous,
+            "Halted" => OrderbookState::Halted,
+            "AuctionCall" => OrderbookState::AuctionCall,
+            "CancelOnly" => OrderbookState::CancelOnly,
+            "Closed" => OrderbookState::Closed,
+            "Mirror" => OrderbookState::Mirror,
+            _ => OrderbookState::Continuous,
+        }
+    }
+}
+
+impl Eq for OrderbookState {}
+
+impl From<OrderbookState> for i32 {
+    fn from(value: OrderbookState) -> i32 {
+        match value {
+            OrderbookState::Continuous => 0,
+            OrderbookState::Halted => 1,
+            OrderbookState::AuctionCall => 2,
+            OrderbookState::CancelOnly => 3,
+            OrderbookState::Closed => 4,
+            OrderbookState::Mirror => 5,
+        }
+    }
+}
+
+impl fmt::Display for OrderbookState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderbookState::Continuous => write!(f, "Continuous"),
+            OrderbookState::Halted => write!(f, "Halted"),
+            OrderbookState::AuctionCall => write!(f, "AuctionCall"),
+            OrderbookState::CancelOnly => write!(f, "CancelOnly"),
+            OrderbookState::Closed => write!(f, "Closed"),
+            OrderbookState::Mirror => write!(f, "Mirror"),
+        }
+    }
+}
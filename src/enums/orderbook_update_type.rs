@@ -2,19 +2,50 @@ use core::fmt;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
 pub enum OrderbookUpdateType {
-    ///Trigger saving of the new order with Pending Status
+    ///Order received, saved with `Persistence::persist_order`
+    #[default]
     New,
-    ///Trigger saving of the new order with Open Status
+    ///Order resting in the book, saved with `Persistence::persist_order`
     Place,
-    ///Trigger saving of the new order with Cancelled Status
+    ///Order cancelled, saved with `Persistence::persist_status`
     Cancel,
-    ///Trigger saving of the new order with Partially Filled Status
-    Update,
-    ///Trigger saving of the new order with Filled Status
+    ///Resting order amended by its owner, saved with `Persistence::persist_order`
+    Amended,
+    ///Execution between two orders, saved with `Persistence::persist_trade`
     NewTrades,
+    ///Order fully filled, saved with `Persistence::persist_status`
     Filled,
+    ///Order whose Good-Till-Date is reached, saved with `Persistence::persist_status`
+    Expired,
+    ///Order replaced with a new price and quantity, saved with `Persistence::persist_order`
+    Replace,
+    ///Result of a call auction
+    AuctionResult,
+    ///New trading state of the orderbook
+    StateChange,
+    ///Circuit breaker trip
+    CircuitBreaker,
+    ///Removal of the orderbook
+    Delisted,
+    ///Resting order partially filled, with the executed quantity, saved with `Persistence::persist_order`
+    PartiallyFilled,
+    ///Pegged order moved to the new price of its reference, saved with `Persistence::persist_order`
+    Repriced,
+    ///Theoretical result of the call auction, republished when the orders of the auction change
+    IndicativeAuction,
+    ///User crossing the message-to-trade ratio threshold, or getting back under it, published by the manager
+    Compliance,
+    ///Last update of the orderbook before the engine shuts down and closes the update streams
+    Shutdown,
+    ///Checksum of the best levels of the book, published periodically so that the consumers can validate their mirror
+    Heartbeat,
+    ///Resting order moved to another orderbook by a symbol migration, with its adjusted price and quantity,
+    ///saved with `Persistence::persist_order`
+    Migrated,
+    ///New reference price of the orderbook set from outside, the trades moving it carry it as well
+    ReferencePrice,
 }
 
 impl fmt::Display for OrderbookUpdateType {
@@ -23,28 +54,50 @@ impl fmt::Display for OrderbookUpdateType {
             OrderbookUpdateType::New => write!(f, "New"),
             OrderbookUpdateType::Place => write!(f, "Place"),
             OrderbookUpdateType::Cancel => write!(f, "Cancel"),
-            OrderbookUpdateType::Update => write!(f, "Update"),
+            OrderbookUpdateType::Amended => write!(f, "Amended"),
             OrderbookUpdateType::NewTrades => write!(f, "NewTrades"),
             OrderbookUpdateType::Filled => write!(f, "Filled"),
+            OrderbookUpdateType::Expired => write!(f, "Expired"),
+            OrderbookUpdateType::Replace => write!(f, "Replace"),
+            OrderbookUpdateType::AuctionResult => write!(f, "AuctionResult"),
+            OrderbookUpdateType::StateChange => write!(f, "StateChange"),
+            OrderbookUpdateType::CircuitBreaker => write!(f, "CircuitBreaker"),
+            OrderbookUpdateType::Delisted => write!(f, "Delisted"),
+            OrderbookUpdateType::PartiallyFilled => write!(f, "PartiallyFilled"),
+            OrderbookUpdateType::Repriced => write!(f, "Repriced"),
+            OrderbookUpdateType::IndicativeAuction => write!(f, "IndicativeAuction"),
+            OrderbookUpdateType::Compliance => write!(f, "Compliance"),
+            OrderbookUpdateType::Shutdown => write!(f, "Shutdown"),
+            OrderbookUpdateType::Heartbeat => write!(f, "Heartbeat"),
+            OrderbookUpdateType::Migrated => write!(f, "Migrated"),
+            OrderbookUpdateType::ReferencePrice => write!(f, "ReferencePrice"),
         }
     }
 }
 
-impl Into<i32> for OrderbookUpdateType {
-    fn into(self) -> i32 {
-        match self {
+impl From<OrderbookUpdateType> for i32 {
+    fn from(value: OrderbookUpdateType) -> i32 {
+        match value {
             OrderbookUpdateType::New => 0,
             OrderbookUpdateType::Place => 1,
             OrderbookUpdateType::Cancel => 2,
-            OrderbookUpdateType::Update => 3,
+            OrderbookUpdateType::Amended => 3,
             OrderbookUpdateType::NewTrades => 4,
             OrderbookUpdateType::Filled => 5,
+            OrderbookUpdateType::Expired => 6,
+            OrderbookUpdateType::Replace => 7,
+            OrderbookUpdateType::AuctionResult => 8,
+            OrderbookUpdateType::StateChange => 9,
+            OrderbookUpdateType::CircuitBreaker => 10,
+            OrderbookUpdateType::Delisted => 11,
+            OrderbookUpdateType::PartiallyFilled => 12,
+            OrderbookUpdateType::Repriced => 13,
+            OrderbookUpdateType::IndicativeAuction => 14,
+            OrderbookUpdateType::Compliance => 15,
+            OrderbookUpdateType::Shutdown => 16,
+            OrderbookUpdateType::Heartbeat => 17,
+            OrderbookUpdateType::Migrated => 18,
+            OrderbookUpdateType::ReferencePrice => 19,
         }
     }
 }
-
-impl Default for OrderbookUpdateType {
-    fn default() -> Self {
-        OrderbookUpdateType::New
-    }
-}
@@ -0,0 +1,32 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens when an update is published to a full subscription queue
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued update to make room for the new one
+    #[default]
+    DropOldest,
+    /// Block the publisher until the subscriber makes room
+    Block,
+    /// Close the subscription, the subscriber receives the queued updates then the end of the stream
+    Error,
+    /// Replace the queued update of the same order with the new one, so that the subscriber only sees
+    /// the latest state of each order, enough to maintain summaries. Trades are never coalesced,
+    /// the oldest update is dropped when there is nothing to coalesce
+    Coalesce,
+}
+
+impl Eq for OverflowPolicy {}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverflowPolicy::DropOldest => write!(f, "DropOldest"),
+            OverflowPolicy::Block => write!(f, "Block"),
+            OverflowPolicy::Error => write!(f, "Error"),
+            OverflowPolicy::Coalesce => write!(f, "Coalesce"),
+        }
+    }
+}
@@ -2,8 +2,9 @@ use serde::{Deserialize, Serialize};
 
 use std::fmt;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Default)]
 pub enum PaymentStatus {
+    #[default]
     Pending,
     Paid,
     Failed,
@@ -12,9 +13,9 @@ pub enum PaymentStatus {
     Unknown,
 }
 
-impl Into<i32> for PaymentStatus {
-    fn into(self) -> i32 {
-        match self {
+impl From<PaymentStatus> for i32 {
+    fn from(value: PaymentStatus) -> i32 {
+        match value {
             PaymentStatus::Pending => 0,
             PaymentStatus::Paid => 1,
             PaymentStatus::Failed => 2,
@@ -25,12 +26,6 @@ impl Into<i32> for PaymentStatus {
     }
 }
 
-impl Default for PaymentStatus {
-    fn default() -> Self {
-        PaymentStatus::Pending
-    }
-}
-
 impl PaymentStatus {
     pub fn from_string(s: &str) -> PaymentStatus {
         match s {
@@ -42,17 +37,6 @@ impl PaymentStatus {
             _ => PaymentStatus::Unknown,
         }
     }
-
-    pub fn to_string(&self) -> String {
-        match self {
-            PaymentStatus::Pending => "Pending".to_string(),
-            PaymentStatus::Paid => "Paid".to_string(),
-            PaymentStatus::Failed => "Failed".to_string(),
-            PaymentStatus::Cancelled => "Cancelled".to_string(),
-            PaymentStatus::Refunded => "Refunded".to_string(),
-            PaymentStatus::Unknown => "Unknown".to_string(),
-        }
-    }
 }
 
 impl fmt::Display for PaymentStatus {
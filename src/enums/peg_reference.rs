@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Price a pegged order tracks, computed from the orders of the book which are not pegged
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum PegReference {
+    /// The mid price between the best bid and the best ask
+    #[default]
+    Mid,
+    /// The best bid price
+    BestBid,
+    /// The best ask price
+    BestAsk,
+}
+
+impl Eq for PegReference {}
+
+impl fmt::Display for PegReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PegReference::Mid => write!(f, "Mid"),
+            PegReference::BestBid => write!(f, "BestBid"),
+            PegReference::BestAsk => write!(f, "BestAsk"),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Reference price a price band is centered on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum PriceReference {
+    /// The price of the last trade
+    #[default]
+    LastTrade,
+    /// The mid price of the book when it was last uncrossed
+    Mid,
+    /// The reference price of the orderbook, see `Orderbook::reference_price`
+    Reference,
+}
+
+impl Eq for PriceReference {}
+
+impl fmt::Display for PriceReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceReference::LastTrade => write!(f, "LastTrade"),
+            PriceReference::Mid => write!(f, "Mid"),
+            PriceReference::Reference => write!(f, "Reference"),
+        }
+    }
+}
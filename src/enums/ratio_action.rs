@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens to a user whose message-to-trade ratio exceeds the threshold
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum RatioAction {
+    /// Publish a compliance event and let the orders through
+    #[default]
+    Warn,
+    /// Publish a compliance event and reject the new orders of the user until its ratio is back under the threshold
+    Throttle,
+}
+
+impl Eq for RatioAction {}
+
+impl fmt::Display for RatioAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RatioAction::Warn => write!(f, "Warn"),
+            RatioAction::Throttle => write!(f, "Throttle"),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What maintains the reference price of an orderbook, `set_reference_price` overriding it in every case
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum ReferenceSource {
+    /// The price of each trade
+    #[default]
+    LastTrade,
+    /// Only the prices set from outside, e.g. an external index
+    External,
+    /// The price of the first trade once the orderbook opens, e.g. the opening auction, kept for the session
+    SessionOpen,
+}
+
+impl Eq for ReferenceSource {}
+
+impl fmt::Display for ReferenceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReferenceSource::LastTrade => write!(f, "LastTrade"),
+            ReferenceSource::External => write!(f, "External"),
+            ReferenceSource::SessionOpen => write!(f, "SessionOpen"),
+        }
+    }
+}
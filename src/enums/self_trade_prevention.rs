@@ -0,0 +1,30 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens when the two orders of a match belong to the same user
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SelfTradePrevention {
+    /// Let the user trade with itself
+    #[default]
+    Allow,
+    /// Cancel the incoming order
+    CancelTaker,
+    /// Cancel the resting order and keep matching the incoming one
+    CancelMaker,
+    /// Cancel both orders
+    CancelBoth,
+}
+
+impl Eq for SelfTradePrevention {}
+
+impl fmt::Display for SelfTradePrevention {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelfTradePrevention::Allow => write!(f, "Allow"),
+            SelfTradePrevention::CancelTaker => write!(f, "CancelTaker"),
+            SelfTradePrevention::CancelMaker => write!(f, "CancelMaker"),
+            SelfTradePrevention::CancelBoth => write!(f, "CancelBoth"),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle events of a user session
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SessionEventType {
+    /// The gateway registered the session
+    #[default]
+    Connected,
+    /// The gateway disconnected the session, its orders were cancelled
+    Disconnected,
+    /// No heartbeat was received in time, its orders were cancelled
+    TimedOut,
+}
+
+impl Eq for SessionEventType {}
+
+impl fmt::Display for SessionEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionEventType::Connected => write!(f, "Connected"),
+            SessionEventType::Disconnected => write!(f, "Disconnected"),
+            SessionEventType::TimedOut => write!(f, "TimedOut"),
+        }
+    }
+}
+
+impl From<SessionEventType> for i32 {
+    fn from(value: SessionEventType) -> i32 {
+        match value {
+            SessionEventType::Connected => 0,
+            SessionEventType::Disconnected => 1,
+            SessionEventType::TimedOut => 2,
+        }
+    }
+}
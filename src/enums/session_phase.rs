@@ -0,0 +1,27 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Phase of the trading session of a symbol, given by its trading hours
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SessionPhase {
+    /// Out of the trading hours, only cancels are accepted
+    #[default]
+    Closed,
+    /// Continuous trading
+    Open,
+    /// Call auction before the close, run at the close
+    ClosingAuction,
+}
+
+impl Eq for SessionPhase {}
+
+impl fmt::Display for SessionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionPhase::Closed => write!(f, "Closed"),
+            SessionPhase::Open => write!(f, "Open"),
+            SessionPhase::ClosingAuction => write!(f, "ClosingAuction"),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Whether a settlement leg takes an asset from a user or gives it to the user
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SettlementDirection {
+    #[default]
+    Debit,
+    Credit,
+}
+
+impl Eq for SettlementDirection {}
+
+impl fmt::Display for SettlementDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettlementDirection::Debit => write!(f, "Debit"),
+            SettlementDirection::Credit => write!(f, "Credit"),
+        }
+    }
+}
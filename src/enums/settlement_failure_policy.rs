@@ -0,0 +1,25 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What the manager does when the settlement of a trade fails
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SettlementFailurePolicy {
+    /// Only notify the users of the trade through the trade status stream
+    #[default]
+    Notify,
+    /// Notify the users and put the quantity of the failed trade back in the book at the trade price,
+    /// for the user of the resting order
+    Rebook,
+}
+
+impl Eq for SettlementFailurePolicy {}
+
+impl fmt::Display for SettlementFailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettlementFailurePolicy::Notify => write!(f, "Notify"),
+            SettlementFailurePolicy::Rebook => write!(f, "Rebook"),
+        }
+    }
+}
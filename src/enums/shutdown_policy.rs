@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What happens to the resting orders when the engine shuts down
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum ShutdownPolicy {
+    /// Cancel the resting orders, each with a Cancel update
+    #[default]
+    Cancel,
+    /// Leave the resting orders as they were last persisted, to be restored on restart
+    Persist,
+}
+
+impl Eq for ShutdownPolicy {}
+
+impl fmt::Display for ShutdownPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShutdownPolicy::Cancel => write!(f, "Cancel"),
+            ShutdownPolicy::Persist => write!(f, "Persist"),
+        }
+    }
+}
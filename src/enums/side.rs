@@ -3,17 +3,18 @@ use core::fmt;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
 pub enum OrderSide {
     #[serde(rename = "BUY")]
+    #[default]
     Buy,
     #[serde(rename = "SELL")]
     Sell,
 }
 
-impl Into<i32> for OrderSide {
-    fn into(self) -> i32 {
-        match self {
+impl From<OrderSide> for i32 {
+    fn from(value: OrderSide) -> i32 {
+        match value {
             OrderSide::Buy => 0,
             OrderSide::Sell => 1,
         }
@@ -22,12 +23,6 @@ impl Into<i32> for OrderSide {
 
 impl Eq for OrderSide {}
 
-impl Default for OrderSide {
-    fn default() -> Self {
-        OrderSide::Buy
-    }
-}
-
 impl fmt::Display for OrderSide {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Encoding of the updates forwarded by the event sinks
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SinkFormat {
+    /// The serde JSON representation of the OrderbookUpdate
+    #[default]
+    Json,
+    /// The protobuf OrderbookUpdate of proto/orderbook.proto, needs the `proto` feature
+    Protobuf,
+}
+
+impl Eq for SinkFormat {}
+
+impl fmt::Display for SinkFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SinkFormat::Json => write!(f, "Json"),
+            SinkFormat::Protobuf => write!(f, "Protobuf"),
+        }
+    }
+}
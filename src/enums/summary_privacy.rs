@@ -0,0 +1,25 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Audience of an orderbook summary, deciding whether the hidden size of the icebergs is shown
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum SummaryPrivacy {
+    /// Only the visible quantities, for the public feeds
+    #[default]
+    Public,
+    /// The visible and total quantity of each level and its icebergs, for the surveillance
+    /// dashboards of the engine, never to be published
+    Internal,
+}
+
+impl Eq for SummaryPrivacy {}
+
+impl fmt::Display for SummaryPrivacy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SummaryPrivacy::Public => write!(f, "Public"),
+            SummaryPrivacy::Internal => write!(f, "Internal"),
+        }
+    }
+}
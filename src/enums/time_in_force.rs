@@ -0,0 +1,24 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How long an order stays in the book, a Good-Till-Date expiry is given with `Order::expires_at`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy, Default)]
+pub enum TimeInForce {
+    /// Until it is filled or cancelled
+    #[default]
+    GoodTillCancel,
+    /// Until the close of the trading session of its symbol, see `TradingCalendar`
+    Day,
+}
+
+impl Eq for TimeInForce {}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeInForce::GoodTillCancel => write!(f, "GoodTillCancel"),
+            TimeInForce::Day => write!(f, "Day"),
+        }
+    }
+}
@@ -4,16 +4,17 @@ use serde::{Deserialize, Serialize};
 
 use std::fmt;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Default)]
 pub enum TradeStatus {
     Swapped,
+    #[default]
     Pending,
     Failed,
 }
 
-impl Into<i32> for TradeStatus {
-    fn into(self) -> i32 {
-        match self {
+impl From<TradeStatus> for i32 {
+    fn from(value: TradeStatus) -> i32 {
+        match value {
             TradeStatus::Swapped => 0,
             TradeStatus::Pending => 1,
             TradeStatus::Failed => 2,
@@ -21,12 +22,6 @@ impl Into<i32> for TradeStatus {
     }
 }
 
-impl Default for TradeStatus {
-    fn default() -> Self {
-        TradeStatus::Pending
-    }
-}
-
 impl TradeStatus {
     pub fn from_string(s: &str) -> TradeStatus {
         match s {
@@ -36,14 +31,6 @@ impl TradeStatus {
             _ => TradeStatus::Failed,
         }
     }
-
-    pub fn to_string(&self) -> String {
-        match self {
-            TradeStatus::Swapped => "Swapped".to_string(),
-            TradeStatus::Pending => "Pending".to_string(),
-            TradeStatus::Failed => "Failed".to_string(),
-        }
-    }
 }
 
 impl fmt::Display for TradeStatus {
@@ -0,0 +1,471 @@
+//! C API to embed the engine in non-Rust stacks, enabled with the `ffi` feature.
+//!
+//! Build the library with `cargo rustc --lib --release --features ffi --crate-type cdylib`
+//! (or `staticlib`) and generate the header with `cbindgen --config cbindgen.toml --output orderbook.h`.
+//!
+//! Every function returns one of the `OB_*` status codes. IDs are 128-bit and passed as an
+//! `ObId` made of two 64-bit halves. Enums are passed as their i32 value (OrderSide: Buy=0, Sell=1,
+//! OrderType: Limit=0, Market=1, OrderStatus and OrderbookUpdateType as in the Rust enums).
+//! The updates of every orderbook are queued by the manager until polled with `ob_poll_updates`.
+
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::Order;
+use crate::structs::order_ack::OrderAck;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::subscription::Subscription;
+use std::io::{Error, ErrorKind};
+use std::ptr;
+
+pub const OB_OK: i32 = 0;
+pub const OB_NULL_POINTER: i32 = -1;
+pub const OB_NOT_FOUND: i32 = -2;
+pub const OB_INVALID_INPUT: i32 = -3;
+pub const OB_REJECTED: i32 = -4;
+pub const OB_ALREADY_EXISTS: i32 = -5;
+pub const OB_INTERNAL: i32 = -99;
+
+/// Opaque handle on a manager and the queue of its updates
+pub struct ObManager {
+    manager: OrderbooksManager,
+    updates: Subscription,
+}
+
+/// 128-bit ID split in two halves, `hi` holding the most significant bits
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ObId {
+    pub hi: u64,
+    pub lo: u64,
+}
+
+impl From<u128> for ObId {
+    fn from(id: u128) -> ObId {
+        ObId {
+            hi: (id >> 64) as u64,
+            lo: id as u64,
+        }
+    }
+}
+
+impl From<ObId> for u128 {
+    fn from(id: ObId) -> u128 {
+        ((id.hi as u128) << 64) | id.lo as u128
+    }
+}
+
+/// Convert the typed IDs of the engine from and to their two halves
+macro_rules! ob_id_conversions {
+    ($($id:ty),*) => {$(
+        impl From<$id> for ObId {
+            fn from(id: $id) -> ObId {
+                ObId::from(u128::from(id))
+            }
+        }
+
+        impl From<ObId> for $id {
+            fn from(id: ObId) -> $id {
+                <$id>::from(u128::from(id))
+            }
+        }
+    )*};
+}
+
+ob_id_conversions!(OrderId, UserId, Symbol);
+
+/// Order to submit, `price` is ignored for market orders and a zero `client_order_id` means none
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObOrderRequest {
+    pub user_id: ObId,
+    pub symbol: ObId,
+    pub side: i32,
+    pub order_type: i32,
+    pub quantity: f64,
+    pub price: f64,
+    pub client_order_id: ObId,
+}
+
+/// Acknowledgment of a submit or a cancel
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObAck {
+    pub order_id: ObId,
+    pub status: i32,
+    pub filled_quantity: f64,
+    /// Whether the orderbook refused or cancelled the order
+    pub rejected: bool,
+}
+
+/// Flattened update, the order fields are set when `has_order` is true and the trade fields when `has_trade` is true.
+/// For Cancel and Filled updates `order_id` is the cancelled or filled order.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObUpdate {
+    pub symbol: ObId,
+    pub update_type: i32,
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub has_order: bool,
+    pub order_id: ObId,
+    pub user_id: ObId,
+    pub side: i32,
+    pub price: f64,
+    pub quantity: f64,
+    pub status: i32,
+    pub has_trade: bool,
+    pub buy_order_id: ObId,
+    pub sell_order_id: ObId,
+    pub trade_price: f64,
+    pub trade_quantity: f64,
+}
+
+impl From<&OrderAck> for ObAck {
+    fn from(ack: &OrderAck) -> ObAck {
+        ObAck {
+            order_id: ack.order_id.into(),
+            status: ack.status.into(),
+            filled_quantity: ack.filled_quantity,
+            rejected: ack.is_rejected(),
+        }
+    }
+}
+
+impl From<&OrderbookUpdate> for ObUpdate {
+    fn from(update: &OrderbookUpdate) -> ObUpdate {
+        let mut flat = ObUpdate {
+            symbol: update.symbol.into(),
+            update_type: update.update_type.into(),
+            sequence: update.sequence,
+            timestamp: update.timestamp,
+            ..Default::default()
+        };
+        if let Some(order) = update.order {
+            flat.has_order = true;
+            flat.order_id = order.id.into();
+            flat.user_id = order.user_id.into();
+            flat.side = order.side.into();
+            flat.price = order.price.unwrap_or(0.0);
+            flat.quantity = order.quantity;
+            flat.status = order.status.into();
+        }
+        if let Some(id) = update.cancel_id.or(update.filled_id) {
+            flat.order_id = id.into();
+        }
+        if let Some(trade) = &update.trade {
+            flat.has_trade = true;
+            flat.buy_order_id = trade.buy_order_id.into();
+            flat.sell_order_id = trade.sell_order_id.into();
+            flat.trade_price = trade.price;
+            flat.trade_quantity = trade.quantity;
+        }
+        flat
+    }
+}
+
+fn code(error: &Error) -> i32 {
+    match error.kind() {
+        ErrorKind::NotFound => OB_NOT_FOUND,
+        ErrorKind::InvalidInput | ErrorKind::InvalidData => OB_INVALID_INPUT,
+        ErrorKind::PermissionDenied => OB_REJECTED,
+        ErrorKind::AlreadyExists => OB_ALREADY_EXISTS,
+        _ => OB_INTERNAL,
+    }
+}
+
+fn side(value: i32) -> Option<OrderSide> {
+    match value {
+        0 => Some(OrderSide::Buy),
+        1 => Some(OrderSide::Sell),
+        _ => None,
+    }
+}
+
+fn order_type(value: i32) -> Option<OrderType> {
+    match value {
+        0 => Some(OrderType::Limit),
+        1 => Some(OrderType::Market),
+        _ => None,
+    }
+}
+
+fn write_ack(result: Result<OrderAck, Error>, ack: *mut ObAck) -> i32 {
+    match result {
+        Ok(order_ack) => {
+            if !ack.is_null() {
+                // SAFETY: the caller passes either null or a valid pointer to an ObAck
+                unsafe { ptr::write(ack, ObAck::from(&order_ack)) };
+            }
+            OB_OK
+        }
+        Err(error) => code(&error),
+    }
+}
+
+fn status(result: Result<(), Error>) -> i32 {
+    result.map_or_else(|error| code(&error), |_| OB_OK)
+}
+
+/// Create a manager, to release with `ob_manager_free`
+#[no_mangle]
+pub extern "C" fn ob_manager_new() -> *mut ObManager {
+    let manager = OrderbooksManager::new();
+    let updates = manager.subscribe_updates();
+    Box::into_raw(Box::new(ObManager { manager, updates }))
+}
+
+/// Release a manager and its orderbooks
+///
+/// # Safety
+/// `manager` must be null or a pointer returned by `ob_manager_new`, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ob_manager_free(manager: *mut ObManager) {
+    if !manager.is_null() {
+        drop(Box::from_raw(manager));
+    }
+}
+
+/// Create the orderbook of a symbol
+///
+/// # Safety
+/// `manager` must be null or a valid pointer returned by `ob_manager_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ob_orderbook_new(manager: *mut ObManager, symbol: ObId) -> i32 {
+    let Some(handle) = manager.as_mut() else {
+        return OB_NULL_POINTER;
+    };
+    let symbol = Symbol::from(symbol);
+    if handle.manager.orderbooks.contains_key(&symbol) {
+        return OB_ALREADY_EXISTS;
+    }
+    handle.manager.new_orderbook(symbol);
+    OB_OK
+}
+
+/// Submit an order, its acknowledgment is written to `ack` when not null
+///
+/// # Safety
+/// `manager` must be a valid pointer returned by `ob_manager_new`, `request` must point to an
+/// ObOrderRequest and `ack` must be null or point to writable memory for an ObAck.
+#[no_mangle]
+pub unsafe extern "C" fn ob_order_submit(
+    manager: *mut ObManager,
+    request: *const ObOrderRequest,
+    ack: *mut ObAck,
+) -> i32 {
+    let (Some(handle), Some(request)) = (manager.as_mut(), request.as_ref()) else {
+        return OB_NULL_POINTER;
+    };
+    let (Some(side), Some(order_type)) = (side(request.side), order_type(request.order_type))
+    else {
+        return OB_INVALID_INPUT;
+    };
+    let price = (order_type == OrderType::Limit).then_some(request.price);
+    let mut order = Order::new(
+        request.user_id.into(),
+        request.symbol.into(),
+        side,
+        request.quantity,
+        price,
+        order_type,
+    );
+    let client_order_id = u128::from(request.client_order_id);
+    if client_order_id != 0 {
+        order = order.with_client_order_id(client_order_id);
+    }
+    write_ack(handle.manager.add_order(order), ack)
+}
+
+/// Cancel an order, the acknowledgment is written to `ack` when not null
+///
+/// # Safety
+/// `manager` must be a valid pointer returned by `ob_manager_new` and `ack` must be null or
+/// point to writable memory for an ObAck.
+#[no_mangle]
+pub unsafe extern "C" fn ob_order_cancel(
+    manager: *mut ObManager,
+    symbol: ObId,
+    order_id: ObId,
+    order_side: i32,
+    ack: *mut ObAck,
+) -> i32 {
+    let Some(handle) = manager.as_mut() else {
+        return OB_NULL_POINTER;
+    };
+    let Some(order_side) = side(order_side) else {
+        return OB_INVALID_INPUT;
+    };
+    write_ack(
+        handle
+            .manager
+            .cancel_order(order_id.into(), symbol.into(), order_side),
+        ack,
+    )
+}
+
+/// Amend the price of a resting order
+///
+/// # Safety
+/// `manager` must be null or a valid pointer returned by `ob_manager_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ob_order_amend_price(
+    manager: *mut ObManager,
+    symbol: ObId,
+    order_id: ObId,
+    order_side: i32,
+    price: f64,
+) -> i32 {
+    let Some(handle) = manager.as_mut() else {
+        return OB_NULL_POINTER;
+    };
+    let Some(order_side) = side(order_side) else {
+        return OB_INVALID_INPUT;
+    };
+    status(
+        handle
+            .manager
+            .amend_order_price(symbol.into(), order_id.into(), price, order_side)
+            .map(|_| ()),
+    )
+}
+
+/// Amend the quantity of a resting order
+///
+/// # Safety
+/// `manager` must be null or a valid pointer returned by `ob_manager_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ob_order_amend_quantity(
+    manager: *mut ObManager,
+    symbol: ObId,
+    order_id: ObId,
+    order_side: i32,
+    quantity: f64,
+) -> i32 {
+    let Some(handle) = manager.as_mut() else {
+        return OB_NULL_POINTER;
+    };
+    let Some(order_side) = side(order_side) else {
+        return OB_INVALID_INPUT;
+    };
+    status(
+        handle
+            .manager
+            .amend_order_quantity(symbol.into(), order_id.into(), quantity, order_side)
+            .map(|_| ()),
+    )
+}
+
+/// Move up to `capacity` queued updates into `buffer`, oldest first.
+/// The number of updates written is stored in `written`, the remaining ones are kept for the next poll.
+///
+/// # Safety
+/// `manager` must be a valid pointer returned by `ob_manager_new`, `buffer` must point to
+/// writable memory for `capacity` ObUpdate and `written` to a writable usize.
+#[no_mangle]
+pub unsafe extern "C" fn ob_poll_updates(
+    manager: *mut ObManager,
+    buffer: *mut ObUpdate,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    let Some(handle) = manager.as_mut() else {
+        return OB_NULL_POINTER;
+    };
+    if written.is_null() || (buffer.is_null() && capacity > 0) {
+        return OB_NULL_POINTER;
+    }
+    let mut count = 0;
+    while count < capacity {
+        let Ok(update) = handle.updates.try_recv() else {
+            break;
+        };
+        ptr::write(buffer.add(count), ObUpdate::from(&update));
+        count += 1;
+    }
+    ptr::write(written, count);
+    OB_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_status::OrderStatus;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+
+    #[test]
+    fn test_ids_round_trip() {
+        let id = u128::MAX - 42;
+        assert_eq!(u128::from(ObId::from(id)), id);
+        assert_eq!(ObId::from(1), ObId { hi: 0, lo: 1 });
+    }
+
+    #[test]
+    fn test_submit_and_poll() {
+        let symbol = ObId::from(7);
+        let order = |side: i32| ObOrderRequest {
+            user_id: ObId::from(side as u128 + 1),
+            symbol,
+            side,
+            order_type: 0,
+            quantity: 1.0,
+            price: 10.0,
+            client_order_id: ObId::default(),
+        };
+        unsafe {
+            let manager = ob_manager_new();
+            assert_eq!(ob_orderbook_new(manager, symbol), OB_OK);
+            assert_eq!(ob_orderbook_new(manager, symbol), OB_ALREADY_EXISTS);
+            assert_eq!(ob_orderbook_new(ptr::null_mut(), symbol), OB_NULL_POINTER);
+
+            let mut ack = ObAck::default();
+            assert_eq!(ob_order_submit(manager, &order(0), &mut ack), OB_OK);
+            assert_eq!(ack.status, i32::from(OrderStatus::Open));
+            assert_eq!(
+                ob_order_amend_quantity(manager, symbol, ack.order_id, 0, 2.0),
+                OB_OK
+            );
+            assert_eq!(ob_order_submit(manager, &order(1), &mut ack), OB_OK);
+            assert_eq!(ack.status, i32::from(OrderStatus::Filled));
+            assert_eq!(ack.filled_quantity, 1.0);
+
+            let mut invalid = order(1);
+            invalid.side = 3;
+            assert_eq!(
+                ob_order_submit(manager, &invalid, ptr::null_mut()),
+                OB_INVALID_INPUT
+            );
+            invalid.side = 1;
+            invalid.symbol = ObId::from(8);
+            assert_eq!(
+                ob_order_submit(manager, &invalid, ptr::null_mut()),
+                OB_NOT_FOUND
+            );
+
+            let mut buffer = [ObUpdate::default(); 2];
+            let mut written = 0;
+            let mut updates = Vec::new();
+            loop {
+                assert_eq!(
+                    ob_poll_updates(manager, buffer.as_mut_ptr(), buffer.len(), &mut written),
+                    OB_OK
+                );
+                updates.extend_from_slice(&buffer[..written]);
+                if written < buffer.len() {
+                    break;
+                }
+            }
+            let trade = updates
+                .iter()
+                .find(|u| u.update_type == i32::from(OrderbookUpdateType::NewTrades))
+                .unwrap();
+            assert!(trade.has_trade);
+            assert_eq!(trade.trade_quantity, 1.0);
+            assert_eq!(trade.symbol, symbol);
+            assert!(updates.windows(2).all(|w| w[0].sequence < w[1].sequence));
+
+            ob_manager_free(manager);
+        }
+    }
+}
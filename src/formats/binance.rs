@@ -0,0 +1,152 @@
+use super::level_diff::LevelChange;
+use crate::enums::side::OrderSide;
+use crate::structs::book_snapshot::BookSnapshot;
+use serde::{Deserialize, Serialize};
+
+/// Price and quantity rendered as strings with 8 decimals
+pub type BinanceLevel = [String; 2];
+
+fn level(price: f64, quantity: f64) -> BinanceLevel {
+    [format!("{:.8}", price), format!("{:.8}", quantity)]
+}
+
+fn levels(changes: &[LevelChange], side: OrderSide) -> Vec<BinanceLevel> {
+    changes
+        .iter()
+        .filter(|c| c.side == side)
+        .map(|c| level(c.price, c.quantity))
+        .collect()
+}
+
+/// Order book snapshot, the `GET /api/v3/depth` response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BinanceDepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    pub bids: Vec<BinanceLevel>,
+    pub asks: Vec<BinanceLevel>,
+}
+
+impl From<&BookSnapshot> for BinanceDepthSnapshot {
+    fn from(snapshot: &BookSnapshot) -> Self {
+        let side = |side| {
+            snapshot
+                .price_levels(side)
+                .into_iter()
+                .map(|(price, quantity)| level(price, quantity))
+                .collect()
+        };
+        BinanceDepthSnapshot {
+            last_update_id: snapshot.sequence,
+            bids: side(OrderSide::Buy),
+            asks: side(OrderSide::Sell),
+        }
+    }
+}
+
+/// Diff of the order book, the `<symbol>@depth` stream event.
+/// The quantities are the new quantities of the levels, 0 removes a level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BinanceDepthUpdate {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    /// Event time in milliseconds
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<BinanceLevel>,
+    #[serde(rename = "a")]
+    pub asks: Vec<BinanceLevel>,
+}
+
+impl BinanceDepthUpdate {
+    /// Render the level changes of a range of updates
+    ///
+    /// #Parameters
+    /// * 'symbol' - The name of the symbol, e.g. BTCUSDT
+    /// * 'first_update_id' - The sequence number of the first update of the range
+    /// * 'final_update_id' - The sequence number of the last update of the range
+    /// * 'event_time' - The time of the last update, in nanoseconds
+    /// * 'changes' - The level changes of the updates
+    pub fn new(
+        symbol: &str,
+        first_update_id: u64,
+        final_update_id: u64,
+        event_time: u64,
+        changes: &[LevelChange],
+    ) -> BinanceDepthUpdate {
+        BinanceDepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: event_time / 1_000_000,
+            symbol: symbol.to_string(),
+            first_update_id,
+            final_update_id,
+            bids: levels(changes, OrderSide::Buy),
+            asks: levels(changes, OrderSide::Sell),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::ids::{Symbol, UserId};
+    use crate::structs::order::Order;
+
+    #[test]
+    fn test_binance_shapes() {
+        let order = |side, price, quantity| {
+            Order::new(
+                UserId(0),
+                Symbol(1),
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let snapshot = BookSnapshot::new(
+            Symbol(1),
+            42,
+            vec![
+                order(OrderSide::Buy, 4.0, 1.0),
+                order(OrderSide::Buy, 4.0, 2.0),
+            ],
+            vec![order(OrderSide::Sell, 5.0, 0.5)],
+        );
+        assert_eq!(
+            serde_json::to_value(BinanceDepthSnapshot::from(&snapshot)).unwrap(),
+            serde_json::json!({
+                "lastUpdateId": 42,
+                "bids": [["4.00000000", "3.00000000"]],
+                "asks": [["5.00000000", "0.50000000"]],
+            })
+        );
+
+        let changes = [LevelChange {
+            side: OrderSide::Sell,
+            price: 5.0,
+            quantity: 0.0,
+        }];
+        let update = BinanceDepthUpdate::new("BNBBTC", 43, 44, 1_700_000_000_000_000_000, &changes);
+        assert_eq!(
+            serde_json::to_value(update).unwrap(),
+            serde_json::json!({
+                "e": "depthUpdate",
+                "E": 1_700_000_000_000u64,
+                "s": "BNBBTC",
+                "U": 43,
+                "u": 44,
+                "b": [],
+                "a": [["5.00000000", "0.00000000"]],
+            })
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Write;
+
+/// Levels per side covered by the book checksums, the depth of the OKX checksum
+pub const CHECKSUM_DEPTH: usize = 25;
+
+/// Lookup table of the CRC32 (IEEE 802.3) polynomial, reversed
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 of bytes, the checksum used by zlib, PNG and the exchanges book checksums
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Checksum of the top levels of a book, computed like the OKX book checksum: the CRC32 of the
+/// best `depth` levels interleaved as "bidPrice:bidSize:askPrice:askSize:...", a side without
+/// level at a depth being skipped. Prices and sizes are written with their shortest decimal form.
+///
+/// #Parameters
+/// * 'bids' - The [price, size] pairs of the bids, best price first
+/// * 'asks' - The [price, size] pairs of the asks, best price first
+/// * 'depth' - The number of levels covered per side
+///
+/// #Returns
+/// * u32 - The checksum, the CRC32 of an empty string (0) for an empty book
+pub fn book_checksum(bids: &[[f64; 2]], asks: &[[f64; 2]], depth: usize) -> u32 {
+    let mut payload = String::new();
+    for i in 0..depth.min(bids.len().max(asks.len())) {
+        for level in [bids.get(i), asks.get(i)].into_iter().flatten() {
+            if !payload.is_empty() {
+                payload.push(':');
+            }
+            let _ = write!(payload, "{}:{}", level[0], level[1]);
+        }
+    }
+    crc32(payload.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let bids = [[10.0, 1.5], [9.0, 2.0]];
+        let asks = [[11.0, 3.0]];
+        assert_eq!(
+            book_checksum(&bids, &asks, CHECKSUM_DEPTH),
+            crc32(b"10:1.5:11:3:9:2")
+        );
+        assert_eq!(book_checksum(&bids, &asks, 1), crc32(b"10:1.5:11:3"));
+        assert_eq!(book_checksum(&[], &[], CHECKSUM_DEPTH), 0);
+        assert_ne!(
+            book_checksum(&bids, &asks, CHECKSUM_DEPTH),
+            book_checksum(&bids, &[[11.0, 2.0]], CHECKSUM_DEPTH)
+        );
+    }
+}
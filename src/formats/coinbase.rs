@@ -0,0 +1,147 @@
+use super::level_diff::LevelChange;
+use crate::enums::side::OrderSide;
+use crate::structs::book_snapshot::BookSnapshot;
+use serde::{Deserialize, Serialize};
+
+fn amount(value: f64) -> String {
+    format!("{:.8}", value)
+}
+
+/// Time in nanoseconds since UNIX epoch rendered as an ISO 8601 UTC date with milliseconds
+pub fn iso8601(nanos: u64) -> String {
+    let millis = nanos / 1_000_000;
+    let (days, millis_of_day) = (millis / 86_400_000, millis % 86_400_000);
+    // civil date from the days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1_000 % 60,
+        millis_of_day % 1_000
+    )
+}
+
+/// Order book snapshot, the first message of the `level2` channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoinbaseSnapshot {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub product_id: String,
+    pub bids: Vec<[String; 2]>,
+    pub asks: Vec<[String; 2]>,
+}
+
+impl CoinbaseSnapshot {
+    /// Render a snapshot of the book
+    ///
+    /// #Parameters
+    /// * 'product_id' - The name of the product, e.g. BTC-USD
+    /// * 'snapshot' - The snapshot of the book
+    pub fn new(product_id: &str, snapshot: &BookSnapshot) -> CoinbaseSnapshot {
+        let side = |side| {
+            snapshot
+                .price_levels(side)
+                .into_iter()
+                .map(|(price, quantity)| [amount(price), amount(quantity)])
+                .collect()
+        };
+        CoinbaseSnapshot {
+            message_type: "snapshot".to_string(),
+            product_id: product_id.to_string(),
+            bids: side(OrderSide::Buy),
+            asks: side(OrderSide::Sell),
+        }
+    }
+}
+
+/// Diff of the order book, the `l2update` message of the `level2` channel.
+/// Each change is a [side, price, size] triple where size is the new size of the level, 0 removes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoinbaseL2Update {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub product_id: String,
+    pub time: String,
+    pub changes: Vec<[String; 3]>,
+}
+
+impl CoinbaseL2Update {
+    /// Render the level changes of an update
+    ///
+    /// #Parameters
+    /// * 'product_id' - The name of the product, e.g. BTC-USD
+    /// * 'time' - The time of the update, in nanoseconds
+    /// * 'changes' - The level changes
+    pub fn new(product_id: &str, time: u64, changes: &[LevelChange]) -> CoinbaseL2Update {
+        CoinbaseL2Update {
+            message_type: "l2update".to_string(),
+            product_id: product_id.to_string(),
+            time: iso8601(time),
+            changes: changes
+                .iter()
+                .map(|c| {
+                    let side = match c.side {
+                        OrderSide::Buy => "buy",
+                        OrderSide::Sell => "sell",
+                    };
+                    [side.to_string(), amount(c.price), amount(c.quantity)]
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ids::Symbol;
+
+    #[test]
+    fn test_coinbase_shapes() {
+        assert_eq!(
+            iso8601(1_565_815_347_265_000_000),
+            "2019-08-14T20:42:27.265Z"
+        );
+        let snapshot = BookSnapshot::new(Symbol(1), 1, vec![], vec![]);
+        assert_eq!(
+            serde_json::to_value(CoinbaseSnapshot::new("BTC-USD", &snapshot)).unwrap(),
+            serde_json::json!({"type": "snapshot", "product_id": "BTC-USD", "bids": [], "asks": []})
+        );
+        let changes = [LevelChange {
+            side: OrderSide::Buy,
+            price: 10101.8,
+            quantity: 0.162567,
+        }];
+        assert_eq!(
+            serde_json::to_value(CoinbaseL2Update::new(
+                "BTC-USD",
+                1_565_815_347_265_000_000,
+                &changes
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "type": "l2update",
+                "product_id": "BTC-USD",
+                "time": "2019-08-14T20:42:27.265Z",
+                "changes": [["buy", "10101.80000000", "0.16256700"]],
+            })
+        );
+    }
+}
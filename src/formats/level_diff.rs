@@ -0,0 +1,145 @@
+use crate::enums::side::OrderSide;
+use crate::structs::book_snapshot::BookSnapshot;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use serde::{Deserialize, Serialize};
+
+/// New quantity of a price level, 0.0 when the level is gone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LevelChange {
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// Turns the order by order updates of an orderbook into price level changes,
+/// the shape of the L2 diff feeds of the exchanges
+#[derive(Debug, Clone)]
+pub struct LevelDiffs {
+    book: BookSnapshot,
+}
+
+impl LevelDiffs {
+    /// Start from a snapshot of the orderbook, the updates up to its sequence number are skipped
+    pub fn new(snapshot: BookSnapshot) -> LevelDiffs {
+        LevelDiffs { book: snapshot }
+    }
+
+    /// The book the diffs are computed on
+    pub fn book(&self) -> &BookSnapshot {
+        &self.book
+    }
+
+    /// Apply an update and return the levels it changed
+    ///
+    /// #Parameters
+    /// * 'update' - An update of the orderbook of the snapshot
+    ///
+    /// #Returns
+    /// * Vec<LevelChange> - The new quantities of the changed levels, empty for the updates which leave the levels as they are
+    pub fn apply(&mut self, update: &OrderbookUpdate) -> Vec<LevelChange> {
+        if update.symbol != self.book.symbol || update.sequence <= self.book.sequence {
+            return Vec::new();
+        }
+        // levels of the order before the update, then after it
+        let mut touched: Vec<(OrderSide, f64)> = Vec::new();
+        let order_id = update
+            .order
+            .map(|o| o.id)
+            .or(update.cancel_id)
+            .or(update.filled_id);
+        if let Some(order_id) = order_id {
+            for side in [OrderSide::Buy, OrderSide::Sell] {
+                if let Some(price) = self
+                    .book
+                    .side(side)
+                    .iter()
+                    .find(|o| o.id == order_id)
+                    .and_then(|o| o.price)
+                {
+                    touched.push((side, price));
+                }
+            }
+        }
+        let before: Vec<f64> = touched
+            .iter()
+            .map(|&(side, price)| self.book.level_quantity(side, price))
+            .collect();
+        self.book.apply(update);
+        if let Some(price) = update.order.and_then(|o| o.price) {
+            let side = update.order.unwrap().side;
+            if !touched.contains(&(side, price)) {
+                touched.push((side, price));
+            }
+        }
+        touched
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(side, price))| {
+                let quantity = self.book.level_quantity(side, price);
+                match before.get(index) {
+                    Some(&previous) if previous == quantity => None,
+                    None if quantity == 0.0 => None,
+                    _ => Some(LevelChange {
+                        side,
+                        price,
+                        quantity,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use crate::structs::ids::{Symbol, UserId};
+    use crate::structs::order::Order;
+
+    #[test]
+    fn test_updates_become_level_changes() {
+        let symbol = Symbol(1);
+        let mut diffs = LevelDiffs::new(BookSnapshot::new(symbol, 0, vec![], vec![]));
+        let order = Order::new(
+            UserId(2),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        let update = |sequence, update_type, order: Order| OrderbookUpdate {
+            symbol,
+            sequence,
+            update_type,
+            order: Some(order),
+            ..Default::default()
+        };
+        let change = |price, quantity| LevelChange {
+            side: OrderSide::Buy,
+            price,
+            quantity,
+        };
+
+        assert!(diffs
+            .apply(&update(1, OrderbookUpdateType::New, order))
+            .is_empty());
+        assert_eq!(
+            diffs.apply(&update(2, OrderbookUpdateType::Place, order)),
+            vec![change(10.0, 1.0)]
+        );
+        let moved = Order {
+            price: Some(11.0),
+            ..order
+        };
+        assert_eq!(
+            diffs.apply(&update(3, OrderbookUpdateType::Amended, moved)),
+            vec![change(10.0, 0.0), change(11.0, 1.0)]
+        );
+        assert!(diffs
+            .apply(&update(3, OrderbookUpdateType::Amended, moved))
+            .is_empty());
+    }
+}
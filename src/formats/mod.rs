@@ -0,0 +1,4 @@
+pub mod binance;
+pub mod checksum;
+pub mod coinbase;
+pub mod level_diff;
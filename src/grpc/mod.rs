@@ -0,0 +1,333 @@
+//! gRPC service for order entry and market data, generated by tonic from `proto/service.proto`.
+//!
+//! ```ignore
+//! let manager = Arc::new(Mutex::new(OrderbooksManager::new()));
+//! tonic::transport::Server::builder()
+//!     .add_service(GrpcService::new(manager).into_server())
+//!     .serve("[::1]:50051".parse()?)
+//!     .await?;
+//! ```
+
+// tonic::Status is the error type of the generated service trait
+#![allow(clippy::result_large_err)]
+
+use crate::enums::side::OrderSide;
+use crate::proto as pb;
+use crate::structs::ids::Symbol;
+use crate::structs::order::Order;
+use crate::structs::order_ack::OrderAck;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::subscription_builder::FilteredSubscription;
+use futures_util::{Stream, StreamExt};
+use std::io::{Error, ErrorKind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tonic::{Request, Response, Status};
+
+pub use pb::matching_service_client::MatchingServiceClient;
+pub use pb::matching_service_server::{MatchingService, MatchingServiceServer};
+
+type UpdateStream = Pin<Box<dyn Stream<Item = Result<pb::OrderbookUpdate, Status>> + Send>>;
+
+/// Implementation of the MatchingService over an OrderbooksManager shared with the rest of the application
+#[derive(Debug, Clone)]
+pub struct GrpcService {
+    manager: Arc<Mutex<OrderbooksManager>>,
+}
+
+impl GrpcService {
+    pub fn new(manager: Arc<Mutex<OrderbooksManager>>) -> GrpcService {
+        GrpcService { manager }
+    }
+
+    /// Wrap the service into the tonic server to add to a `tonic::transport::Server`
+    pub fn into_server(self) -> MatchingServiceServer<GrpcService> {
+        MatchingServiceServer::new(self)
+    }
+
+    fn manager(&self) -> Result<MutexGuard<'_, OrderbooksManager>, Status> {
+        self.manager
+            .lock()
+            .map_err(|_| Status::internal("Orderbooks manager poisoned"))
+    }
+
+    fn updates(
+        &self,
+        filter: impl FnOnce(&OrderbooksManager) -> FilteredSubscription,
+    ) -> Result<UpdateStream, Status> {
+        let subscription = filter(&*self.manager()?);
+        Ok(Box::pin(
+            subscription.map(|update| Ok(pb::OrderbookUpdate::from(&update))),
+        ))
+    }
+}
+
+/// Map the errors of the manager to the closest gRPC status
+fn status(error: Error) -> Status {
+    let message = error.to_string();
+    match error.kind() {
+        ErrorKind::NotFound => Status::not_found(message),
+        ErrorKind::InvalidInput | ErrorKind::InvalidData => Status::invalid_argument(message),
+        ErrorKind::AlreadyExists => Status::already_exists(message),
+        ErrorKind::PermissionDenied => Status::failed_precondition(message),
+        ErrorKind::BrokenPipe => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn parse_id<T: From<u128>>(value: &str) -> Result<T, Status> {
+    value
+        .parse::<u128>()
+        .map(T::from)
+        .map_err(|_| Status::invalid_argument(format!("Invalid ID: {}", value)))
+}
+
+fn parse_side(value: i32) -> Result<OrderSide, Status> {
+    pb::OrderSide::try_from(value)
+        .map(OrderSide::from)
+        .map_err(|_| Status::invalid_argument("Unknown order side"))
+}
+
+impl From<&OrderAck> for pb::OrderAck {
+    fn from(ack: &OrderAck) -> pb::OrderAck {
+        pb::OrderAck {
+            order_id: ack.order_id.to_string(),
+            client_order_id: ack.client_order_id.map(|id| id.to_string()),
+            status: pb::OrderStatus::from(ack.status) as i32,
+            filled_quantity: ack.filled_quantity,
+            reject_reason: ack.reject_reason.clone(),
+        }
+    }
+}
+
+impl TryFrom<pb::AddOrderRequest> for Order {
+    type Error = Status;
+
+    fn try_from(request: pb::AddOrderRequest) -> Result<Order, Status> {
+        let order_type = pb::OrderType::try_from(request.order_type)
+            .map_err(|_| Status::invalid_argument("Unknown order type"))?;
+        let mut order = Order::new(
+            parse_id(&request.user_id)?,
+            parse_id(&request.symbol)?,
+            parse_side(request.side)?,
+            request.quantity,
+            request.price,
+            order_type.into(),
+        );
+        order.expires_at = request.expires_at;
+        order.client_order_id = request
+            .client_order_id
+            .as_deref()
+            .map(parse_id)
+            .transpose()?;
+        if let Some(display_quantity) = request.display_quantity {
+            order = order.with_display_quantity(display_quantity);
+        }
+        Ok(order)
+    }
+}
+
+#[tonic::async_trait]
+impl MatchingService for GrpcService {
+    async fn add_order(
+        &self,
+        request: Request<pb::AddOrderRequest>,
+    ) -> Result<Response<pb::OrderAck>, Status> {
+        let order = Order::try_from(request.into_inner())?;
+        let ack = self.manager()?.add_order(order).map_err(status)?;
+        Ok(Response::new(pb::OrderAck::from(&ack)))
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<pb::CancelOrderRequest>,
+    ) -> Result<Response<pb::OrderAck>, Status> {
+        let request = request.into_inner();
+        let ack = self
+            .manager()?
+            .cancel_order(
+                parse_id(&request.order_id)?,
+                parse_id(&request.symbol)?,
+                parse_side(request.side)?,
+            )
+            .map_err(status)?;
+        Ok(Response::new(pb::OrderAck::from(&ack)))
+    }
+
+    async fn amend_order(
+        &self,
+        request: Request<pb::AmendOrderRequest>,
+    ) -> Result<Response<pb::AmendOrderResponse>, Status> {
+        let request = request.into_inner();
+        let order_id = parse_id(&request.order_id)?;
+        let symbol = parse_id(&request.symbol)?;
+        let side = parse_side(request.side)?;
+        let mut manager = self.manager()?;
+        match (request.price, request.quantity) {
+            (None, None) => {
+                return Err(Status::invalid_argument(
+                    "Either the price or the quantity must be set",
+                ))
+            }
+            (Some(price), Some(quantity)) => manager
+                .replace_order(order_id, symbol, price, quantity)
+                .map(|_| ()),
+            (Some(price), None) => manager
+                .amend_order_price(symbol, order_id, price, side)
+                .map(|_| ()),
+            (None, Some(quantity)) => manager
+                .amend_order_quantity(symbol, order_id, quantity, side)
+                .map(|_| ()),
+        }
+        .map_err(status)?;
+        Ok(Response::new(pb::AmendOrderResponse {}))
+    }
+
+    type MarketDataStream = UpdateStream;
+
+    async fn market_data(
+        &self,
+        request: Request<pb::MarketDataRequest>,
+    ) -> Result<Response<UpdateStream>, Status> {
+        let symbols = request
+            .into_inner()
+            .symbols
+            .iter()
+            .map(|symbol| parse_id(symbol))
+            .collect::<Result<Vec<Symbol>, Status>>()?;
+        let updates = self.updates(|manager| manager.subscribe().symbols(symbols).stream())?;
+        Ok(Response::new(updates))
+    }
+
+    type UserUpdatesStream = UpdateStream;
+
+    async fn user_updates(
+        &self,
+        request: Request<pb::UserUpdatesRequest>,
+    ) -> Result<Response<UpdateStream>, Status> {
+        let user_id = parse_id(&request.into_inner().user_id)?;
+        let updates = self.updates(|manager| manager.subscribe().user(user_id).stream())?;
+        Ok(Response::new(updates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use crate::structs::ids::UserId;
+    use crate::structs::orderbook_update::OrderbookUpdate;
+    use ulid::Ulid;
+
+    fn add_request(user_id: UserId, symbol: Symbol, side: pb::OrderSide) -> pb::AddOrderRequest {
+        pb::AddOrderRequest {
+            user_id: user_id.to_string(),
+            symbol: symbol.to_string(),
+            side: side as i32,
+            quantity: 1.0,
+            price: Some(10.0),
+            client_order_id: Some("7".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_order_entry_and_streams() {
+        let symbol: Symbol = Ulid::new().into();
+        let user_id: UserId = Ulid::new().into();
+        let manager = Arc::new(Mutex::new(OrderbooksManager::new()));
+        manager.lock().unwrap().new_orderbook(symbol);
+        let service = GrpcService::new(manager);
+
+        let mut market_data = service
+            .market_data(Request::new(pb::MarketDataRequest {
+                symbols: vec![symbol.to_string()],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut user_updates = service
+            .user_updates(Request::new(pb::UserUpdatesRequest {
+                user_id: user_id.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let ack = service
+            .add_order(Request::new(add_request(
+                user_id,
+                symbol,
+                pb::OrderSide::Buy,
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(ack.client_order_id.as_deref(), Some("7"));
+        assert_eq!(ack.status(), pb::OrderStatus::Open);
+
+        let amend = pb::AmendOrderRequest {
+            order_id: ack.order_id.clone(),
+            symbol: symbol.to_string(),
+            side: pb::OrderSide::Buy as i32,
+            price: Some(11.0),
+            quantity: None,
+        };
+        service.amend_order(Request::new(amend)).await.unwrap();
+
+        let ack = service
+            .add_order(Request::new(add_request(
+                Ulid::new().into(),
+                symbol,
+                pb::OrderSide::Sell,
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(ack.status(), pb::OrderStatus::Filled);
+        assert_eq!(ack.filled_quantity, 1.0);
+
+        let update = market_data.next().await.unwrap().unwrap();
+        assert_eq!(update.update_type(), pb::OrderbookUpdateType::New);
+        let mut user_events = Vec::new();
+        while user_events.last() != Some(&OrderbookUpdateType::NewTrades) {
+            let update =
+                OrderbookUpdate::try_from(user_updates.next().await.unwrap().unwrap()).unwrap();
+            if update.update_type == OrderbookUpdateType::Amended {
+                assert_eq!(update.order.unwrap().price, Some(11.0));
+            }
+            user_events.push(update.update_type);
+        }
+        assert!(user_events.contains(&OrderbookUpdateType::Amended));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_mapped_to_statuses() {
+        let service = GrpcService::new(Arc::new(Mutex::new(OrderbooksManager::new())));
+        let unknown_symbol = add_request(UserId(1), Symbol(2), pb::OrderSide::Buy);
+        let error = service
+            .add_order(Request::new(unknown_symbol))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+
+        let mut invalid_id = add_request(UserId(1), Symbol(2), pb::OrderSide::Buy);
+        invalid_id.user_id = "user".to_string();
+        let error = service
+            .add_order(Request::new(invalid_id))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+
+        let empty_amend = pb::AmendOrderRequest {
+            order_id: "1".to_string(),
+            symbol: "2".to_string(),
+            ..Default::default()
+        };
+        let error = service
+            .amend_order(Request::new(empty_amend))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+    }
+}
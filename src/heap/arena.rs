@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+/// Usage of an arena, to tune the initial capacity of the books
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Number of slots, live or free
+    pub capacity: usize,
+    /// Number of slots holding an element
+    pub live: usize,
+    /// Number of slots waiting to be recycled
+    pub free: usize,
+    /// Number of slots created since the arena was created
+    pub allocated: u64,
+    /// Number of insertions which reused a freed slot
+    pub recycled: u64,
+}
+
+impl PoolStats {
+    /// Sum of the statistics of two arenas
+    pub fn merge(self, other: PoolStats) -> PoolStats {
+        PoolStats {
+            capacity: self.capacity + other.capacity,
+            live: self.live + other.live,
+            free: self.free + other.free,
+            allocated: self.allocated + other.allocated,
+            recycled: self.recycled + other.recycled,
+        }
+    }
+}
+
+/// Slab of elements addressed by index handles, the slots of the removed elements are recycled
+#[derive(Debug, Clone)]
+pub struct Arena<T> {
+    slots: Vec<Option<T>>,
+    free: Vec<usize>,
+    allocated: u64,
+    recycled: u64,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::with_capacity(0)
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            allocated: 0,
+            recycled: 0,
+        }
+    }
+
+    /// Store an element, in a freed slot if there is one
+    ///
+    /// #Returns
+    /// * usize - The handle of the element
+    pub fn insert(&mut self, item: T) -> usize {
+        match self.free.pop() {
+            Some(handle) => {
+                self.slots[handle] = Some(item);
+                self.recycled += 1;
+                handle
+            }
+            None => {
+                self.slots.push(Some(item));
+                self.allocated += 1;
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// Take an element out, its slot is recycled
+    pub fn remove(&mut self, handle: usize) -> Option<T> {
+        let item = self.slots.get_mut(handle)?.take();
+        if item.is_some() {
+            self.free.push(handle);
+        }
+        item
+    }
+
+    pub fn get(&self, handle: usize) -> Option<&T> {
+        self.slots.get(handle)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: usize) -> Option<&mut T> {
+        self.slots.get_mut(handle)?.as_mut()
+    }
+
+    /// Number of live elements
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Move the live elements to the first slots and release the freed slots
+    ///
+    /// #Parameters
+    /// * 'handles' - The handles of every live element, rewritten to their new slots
+    ///
+    /// #Returns
+    /// * usize - The number of slots released
+    pub fn compact(&mut self, handles: &mut [usize]) -> usize {
+        let released = self.slots.len() - handles.len();
+        let mut slots = Vec::with_capacity(handles.len());
+        for handle in handles.iter_mut() {
+            slots.push(self.slots[*handle].take());
+            *handle = slots.len() - 1;
+        }
+        self.slots = slots;
+        self.free = Vec::new();
+        released
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            capacity: self.slots.len(),
+            live: self.len(),
+            free: self.free.len(),
+            allocated: self.allocated,
+            recycled: self.recycled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_recycled() {
+        let mut arena = Arena::default();
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+        assert_eq!(arena.remove(a), Some("a"));
+        assert_eq!(arena.remove(a), None);
+        let c = arena.insert("c");
+        assert_eq!(c, a);
+        assert_eq!(arena.get(b), Some(&"b"));
+        assert_eq!(
+            arena.stats(),
+            PoolStats {
+                capacity: 2,
+                live: 2,
+                free: 0,
+                allocated: 2,
+                recycled: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compaction_releases_the_freed_slots() {
+        let mut arena = Arena::default();
+        let mut handles: Vec<usize> = ["a", "b", "c", "d"].map(|i| arena.insert(i)).to_vec();
+        arena.remove(handles.remove(0));
+        arena.remove(handles.remove(1));
+        assert_eq!(arena.compact(&mut handles), 2);
+        assert_eq!(handles, vec![0, 1]);
+        assert_eq!(arena.get(0), Some(&"b"));
+        assert_eq!(arena.get(1), Some(&"d"));
+        assert_eq!((arena.stats().capacity, arena.stats().free), (2, 0));
+        assert_eq!(arena.insert("e"), 2);
+    }
+}
@@ -1,91 +1,368 @@
-use std::cell::RefCell;
-use std::collections::binary_heap::IntoIter;
-use std::collections::BinaryHeap;
+use super::arena::{Arena, PoolStats};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::vec::IntoIter;
 
-use crate::structs::order::Order;
+/// Element of a heap which can be looked up by a key, e.g. an order by its ID
+pub trait Keyed {
+    type Key: Copy + Eq + Hash;
 
+    fn key(&self) -> Self::Key;
+}
+
+impl<T: Keyed> Keyed for Reverse<T> {
+    type Key = T::Key;
+
+    fn key(&self) -> T::Key {
+        self.0.key()
+    }
+}
+
+/// Binary heap whose elements live in a recycling arena: the heap itself only moves slot
+/// handles around, so pushing, popping and modifying elements doesn't churn the allocator
+/// once the book has warmed up. The handles are indexed by the key of their element and
+/// each slot knows its position in the heap, so an element is found, modified or removed
+/// without scanning the heap. Mutated through `&mut`, so it is Send and Sync whenever
+/// its elements are
 #[derive(Debug, Clone)]
-pub struct ModifiableBinaryHeap<T: Clone + Ord> {
-    heap: RefCell<BinaryHeap<T>>,
+pub struct ModifiableBinaryHeap<T: Clone + Ord + Keyed> {
+    arena: Arena<T>,
+    handles: Vec<usize>,
+    index: HashMap<T::Key, usize>,
+    positions: Vec<usize>,
 }
 
-unsafe impl Sync for ModifiableBinaryHeap<Order> {}
+impl<T: Clone + Ord + Keyed> Default for ModifiableBinaryHeap<T> {
+    fn default() -> Self {
+        ModifiableBinaryHeap::new()
+    }
+}
 
-impl<T: Clone + Ord> ModifiableBinaryHeap<T> {
+impl<T: Clone + Ord + Keyed> ModifiableBinaryHeap<T> {
     // Constructor to create a new empty heap
     pub fn new() -> Self {
+        ModifiableBinaryHeap::with_capacity(0)
+    }
+
+    // Constructor reserving room for `capacity` elements
+    pub fn with_capacity(capacity: usize) -> Self {
         ModifiableBinaryHeap {
-            heap: RefCell::new(BinaryHeap::new()),
+            arena: Arena::with_capacity(capacity),
+            handles: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+            positions: Vec::with_capacity(capacity),
         }
     }
 
     // Method to push an element onto the heap
-    pub fn push(&self, item: T) {
-        self.heap.borrow_mut().push(item);
+    pub fn push(&mut self, item: T) {
+        let key = item.key();
+        let handle = self.arena.insert(item);
+        self.index.insert(key, handle);
+        self.handles.push(handle);
+        self.place(self.handles.len() - 1);
+        self.sift_up(self.handles.len() - 1);
+    }
+
+    // Method to borrow the element with a key
+    pub fn get(&self, key: &T::Key) -> Option<&T> {
+        self.index.get(key).map(|&handle| self.item(handle))
     }
 
     // Method to peek at the top element of the heap
     pub fn peek(&self) -> Option<T> {
-        self.heap.borrow().peek().cloned()
+        self.peek_ref().cloned()
+    }
+
+    // Method to borrow the top element of the heap
+    pub fn peek_ref(&self) -> Option<&T> {
+        self.handles.first().map(|&handle| self.item(handle))
     }
 
-    // Method to retain elements based on a closure
-    pub fn retain<F>(&self, retain_fn: F)
+    // Method to retain elements based on a closure, the slots of the others are recycled
+    pub fn retain<F>(&mut self, mut retain_fn: F)
     where
         F: FnMut(&T) -> bool,
     {
-        self.heap.borrow_mut().retain(retain_fn);
+        let arena = &mut self.arena;
+        let before = self.handles.len();
+        self.handles.retain(|&handle| {
+            let keep = retain_fn(arena.get(handle).expect("live slot"));
+            if !keep {
+                arena.remove(handle);
+            }
+            keep
+        });
+        if self.handles.len() != before {
+            self.reindex();
+            self.rebuild();
+        }
     }
 
     // Method to pop the top element from the heap
-    pub fn pop(&self) -> Option<T> {
-        self.heap.borrow_mut().pop()
+    pub fn pop(&mut self) -> Option<T> {
+        if self.handles.is_empty() {
+            return None;
+        }
+        self.remove_at(0)
     }
 
     // Method to check if the heap is empty
     pub fn is_empty(&self) -> bool {
-        self.heap.borrow().is_empty()
+        self.handles.is_empty()
     }
 
     // Method to iterate over the heap (not ordered)
     pub fn iter(&self) -> IntoIter<T> {
-        self.heap.borrow().clone().into_iter()
+        self.to_vec().into_iter()
     }
 
     // Method to iterate over the heap in sorted order
     pub fn iter_sorted(&self) -> Vec<T> {
-        let mut heap_borrow = self.heap.borrow_mut().clone();
-        let mut heap_vec: Vec<_> = heap_borrow.drain().collect();
+        let mut heap_vec = self.to_vec();
         heap_vec.sort();
         heap_vec
     }
 
     // Method to get the length of the heap
     pub fn len(&self) -> usize {
-        self.heap.borrow().len()
+        self.handles.len()
     }
 
-    // Method to convert the heap into a vector
-    pub fn into_vec(&self) -> Vec<T> {
-        self.heap.clone().into_inner().into_vec()
+    // Method to copy the heap into a vector
+    pub fn to_vec(&self) -> Vec<T> {
+        self.handles
+            .iter()
+            .map(|&handle| self.item(handle).clone())
+            .collect()
     }
 
-    // Method to modify an element (example implementation)
-    // This is just a stub function; it doesn't do anything meaningful without specific requirements.
-    pub fn modify<F>(&self, mut modify_fn: F)
+    // Method to iterate over the heap by reference (not ordered)
+    pub fn iter_ref(&self) -> impl Iterator<Item = &T> + '_ {
+        self.handles.iter().map(|&handle| self.item(handle))
+    }
+
+    // Method to borrow the elements in sorted order, without copying them
+    pub fn sorted_refs(&self) -> Vec<&T> {
+        let mut refs: Vec<&T> = self.iter_ref().collect();
+        refs.sort();
+        refs
+    }
+
+    // Method to borrow the elements lazily in priority order, the top element first. Only the part of
+    // the heap walked so far is expanded, so taking the first k elements costs O(k log k)
+    pub fn iter_priority(&self) -> PriorityIter<'_, T> {
+        let mut frontier = BinaryHeap::new();
+        if let Some(&handle) = self.handles.first() {
+            frontier.push(Candidate(self.item(handle), 0));
+        }
+        PriorityIter {
+            heap: self,
+            frontier,
+        }
+    }
+
+    // Method to remove the element with a key
+    pub fn remove(&mut self, key: &T::Key) -> Option<T> {
+        let handle = *self.index.get(key)?;
+        self.remove_at(self.positions[handle])
+    }
+
+    // Method to modify the element with a key in place, its key must not change
+    //
+    // Returns a copy of the modified element
+    pub fn update<F>(&mut self, key: &T::Key, modify_fn: F) -> Option<T>
+    where
+        F: FnOnce(&mut T),
+    {
+        let handle = *self.index.get(key)?;
+        let item = self.arena.get_mut(handle).expect("live slot");
+        modify_fn(item);
+        let updated = item.clone();
+        let position = self.positions[handle];
+        self.sift_down(position);
+        self.sift_up(position);
+        Some(updated)
+    }
+
+    // Method to modify the elements in place, the heap is then reordered
+    pub fn modify<F>(&mut self, mut modify_fn: F)
     where
         F: FnMut(&mut T),
     {
-        let mut heap_borrow = self.heap.borrow_mut();
-        let mut heap_vec: Vec<_> = heap_borrow.drain().collect();
+        for &handle in self.handles.iter() {
+            modify_fn(self.arena.get_mut(handle).expect("live slot"));
+        }
+        self.reindex();
+        self.rebuild();
+    }
+
+    // Method to check that every handle points to its own live slot, that the arena holds no other element
+    // and that the index points each key to the slot holding it
+    pub fn handles_consistent(&self) -> bool {
+        let mut handles = self.handles.clone();
+        handles.sort_unstable();
+        handles.dedup();
+        handles.len() == self.handles.len()
+            && self.arena.len() == self.handles.len()
+            && self.handles.iter().enumerate().all(|(position, &handle)| {
+                self.arena.get(handle).is_some() && self.positions.get(handle) == Some(&position)
+            })
+            && self.index.iter().all(|(key, &handle)| {
+                self.arena
+                    .get(handle)
+                    .is_some_and(|item| item.key() == *key)
+            })
+    }
+
+    // Method to check that no element is greater than its parent, i.e. the top element is the greatest
+    pub fn is_ordered(&self) -> bool {
+        (1..self.handles.len())
+            .all(|position| self.compare(position, (position - 1) / 2) != Ordering::Greater)
+    }
+
+    // Method to get the usage of the arena holding the elements
+    pub fn pool_stats(&self) -> PoolStats {
+        self.arena.stats()
+    }
+
+    // Method to release the freed slots of the arena, the elements keep their place in the heap
+    pub fn compact(&mut self) -> usize {
+        let released = self.arena.compact(&mut self.handles);
+        self.handles.shrink_to_fit();
+        self.reindex();
+        self.index.shrink_to_fit();
+        self.positions.shrink_to_fit();
+        released
+    }
+
+    fn item(&self, handle: usize) -> &T {
+        self.arena.get(handle).expect("live slot")
+    }
+
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        self.item(self.handles[a]).cmp(self.item(self.handles[b]))
+    }
+
+    // Record the position of the handle at a position of the heap
+    fn place(&mut self, position: usize) {
+        let handle = self.handles[position];
+        if handle >= self.positions.len() {
+            self.positions.resize(handle + 1, 0);
+        }
+        self.positions[handle] = position;
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.handles.swap(a, b);
+        self.place(a);
+        self.place(b);
+    }
+
+    fn remove_at(&mut self, position: usize) -> Option<T> {
+        let handle = self.handles.swap_remove(position);
+        if position < self.handles.len() {
+            self.place(position);
+            self.sift_down(position);
+            self.sift_up(position);
+        }
+        let item = self.arena.remove(handle)?;
+        // An element pushed twice under the same key is only indexed at its last slot
+        let key = item.key();
+        if self.index.get(&key) == Some(&handle) {
+            self.index.remove(&key);
+        }
+        Some(item)
+    }
+
+    // Rebuild the index and the positions from the handles
+    fn reindex(&mut self) {
+        self.index.clear();
+        self.positions.clear();
+        for position in 0..self.handles.len() {
+            let handle = self.handles[position];
+            self.index.insert(self.item(handle).key(), handle);
+            self.place(position);
+        }
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.compare(position, parent) != Ordering::Greater {
+                break;
+            }
+            self.swap(position, parent);
+            position = parent;
+        }
+    }
 
-        for item in &mut heap_vec {
-            modify_fn(item);
+    fn sift_down(&mut self, mut position: usize) {
+        let len = self.handles.len();
+        loop {
+            let mut largest = position;
+            for child in [2 * position + 1, 2 * position + 2] {
+                if child < len && self.compare(child, largest) == Ordering::Greater {
+                    largest = child;
+                }
+            }
+            if largest == position {
+                break;
+            }
+            self.swap(position, largest);
+            position = largest;
         }
+    }
 
-        // Rebuild the heap after modification
-        heap_vec.sort(); // This sort is needed to maintain the heap property.
-        *heap_borrow = BinaryHeap::from(heap_vec);
+    fn rebuild(&mut self) {
+        for position in (0..self.handles.len() / 2).rev() {
+            self.sift_down(position);
+        }
+    }
+}
+
+/// Element of the heap waiting to be visited, with its position
+struct Candidate<'a, T>(&'a T, usize);
+
+impl<T: Ord> PartialEq for Candidate<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Ord> Eq for Candidate<'_, T> {}
+
+impl<T: Ord> PartialOrd for Candidate<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Candidate<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(other.0)
+    }
+}
+
+/// Iterator borrowing the elements of a heap in priority order: the next element is the greatest
+/// of the children of the elements already visited
+pub struct PriorityIter<'a, T: Clone + Ord + Keyed> {
+    heap: &'a ModifiableBinaryHeap<T>,
+    frontier: BinaryHeap<Candidate<'a, T>>,
+}
+
+impl<'a, T: Clone + Ord + Keyed> Iterator for PriorityIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let Candidate(item, position) = self.frontier.pop()?;
+        for child in [2 * position + 1, 2 * position + 2] {
+            if let Some(&handle) = self.heap.handles.get(child) {
+                self.frontier.push(Candidate(self.heap.item(handle), child));
+            }
+        }
+        Some(item)
     }
 }
 
@@ -103,7 +380,7 @@ mod tests {
 
     #[test]
     fn test_modifiable_binary_heap() {
-        let heap = ModifiableBinaryHeap::new();
+        let mut heap = ModifiableBinaryHeap::new();
         let order1 = Order {
             id: Ulid::new().into(),
             user_id: Ulid::new().into(),
@@ -117,6 +394,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let order2 = Order {
             id: Ulid::new().into(),
@@ -131,6 +417,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let id = Ulid::new().into();
         let order3 = Order {
@@ -146,10 +441,19 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
-        heap.push(order3.clone());
-        heap.push(order2.clone());
-        heap.push(order1.clone());
+        heap.push(order3);
+        heap.push(order2);
+        heap.push(order1);
 
         assert_eq!(heap.len(), 3);
 
@@ -166,7 +470,7 @@ mod tests {
 
     #[test]
     fn test_sell_modifiable_binary_heap() {
-        let heap = ModifiableBinaryHeap::new();
+        let mut heap = ModifiableBinaryHeap::new();
         let order1 = Order {
             id: Ulid::new().into(),
             user_id: Ulid::new().into(),
@@ -180,6 +484,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let order2 = Order {
             id: Ulid::new().into(),
@@ -194,6 +507,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let id = Ulid::new().into();
         let order3 = Order {
@@ -209,10 +531,19 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
-        heap.push(order3.clone());
-        heap.push(order2.clone());
-        heap.push(order1.clone());
+        heap.push(order3);
+        heap.push(order2);
+        heap.push(order1);
 
         assert_eq!(heap.len(), 3);
 
@@ -229,7 +560,7 @@ mod tests {
 
     #[test]
     fn test_reversed_modifiable_binary_heap() {
-        let heap = ModifiableBinaryHeap::new();
+        let mut heap = ModifiableBinaryHeap::new();
         let order1 = Order {
             id: Ulid::new().into(),
             user_id: Ulid::new().into(),
@@ -243,6 +574,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let order2 = Order {
             id: Ulid::new().into(),
@@ -257,6 +597,15 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
         let id = Ulid::new().into();
         let order3 = Order {
@@ -272,10 +621,19 @@ mod tests {
             payment_status: Default::default(),
             created_at: Instant::now().elapsed().as_secs(),
             updated_at: Instant::now().elapsed().as_secs(),
+            expires_at: None,
+            time_in_force: Default::default(),
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         };
-        heap.push(Reverse(order3.clone()));
-        heap.push(Reverse(order2.clone()));
-        heap.push(Reverse(order1.clone()));
+        heap.push(Reverse(order3));
+        heap.push(Reverse(order2));
+        heap.push(Reverse(order1));
 
         assert_eq!(heap.len(), 3);
 
@@ -289,4 +647,90 @@ mod tests {
         let modified_order = heap.peek().unwrap();
         assert_eq!(modified_order.0.quantity, 1.0);
     }
+
+    #[test]
+    fn test_popped_slots_are_recycled() {
+        let mut heap = ModifiableBinaryHeap::new();
+        for round in 0..3 {
+            for price in [2.0, 3.0, 1.0] {
+                heap.push(Order::new(
+                    Ulid::new().into(),
+                    Ulid::new().into(),
+                    OrderSide::Buy,
+                    1.0,
+                    Some(price + round as f64),
+                    OrderType::Limit,
+                ));
+            }
+            heap.retain(|order| order.price != Some(1.0 + round as f64));
+            assert_eq!(heap.pop().unwrap().price, Some(3.0 + round as f64));
+            assert_eq!(heap.pop().unwrap().price, Some(2.0 + round as f64));
+            assert!(heap.pop().is_none());
+        }
+        let stats = heap.pool_stats();
+        assert_eq!(stats.allocated, 3);
+        assert_eq!(stats.recycled, 6);
+        assert_eq!(stats.live, 0);
+        assert_eq!(stats.free, 3);
+    }
+
+    impl Keyed for (i32, char) {
+        type Key = char;
+
+        fn key(&self) -> char {
+            self.1
+        }
+    }
+
+    impl Keyed for i32 {
+        type Key = i32;
+
+        fn key(&self) -> i32 {
+            *self
+        }
+    }
+
+    #[test]
+    fn test_remove_and_update_by_key() {
+        let mut heap = ModifiableBinaryHeap::new();
+        for (value, key) in [(5, 'a'), (1, 'b'), (8, 'c'), (3, 'd'), (9, 'e'), (2, 'f')] {
+            heap.push((value, key));
+        }
+        assert_eq!(heap.get(&'c'), Some(&(8, 'c')));
+        assert_eq!(heap.remove(&'e'), Some((9, 'e')));
+        assert_eq!(heap.remove(&'d'), Some((3, 'd')));
+        assert_eq!(heap.remove(&'d'), None);
+        assert_eq!(heap.update(&'b', |v| v.0 = 10), Some((10, 'b')));
+        assert_eq!(heap.update(&'c', |v| v.0 = 0), Some((0, 'c')));
+        assert!(heap.handles_consistent());
+        assert_eq!(
+            heap.sorted_refs(),
+            vec![&(0, 'c'), &(2, 'f'), &(5, 'a'), &(10, 'b')]
+        );
+        heap.push((7, 'g'));
+        heap.compact();
+        assert!(heap.handles_consistent());
+        assert_eq!(heap.get(&'g'), Some(&(7, 'g')));
+        heap.retain(|v| v.0 != 7);
+        assert_eq!(heap.get(&'g'), None);
+        assert!(heap.handles_consistent());
+        let popped: Vec<i32> = std::iter::from_fn(|| heap.pop()).map(|v| v.0).collect();
+        assert_eq!(popped, vec![10, 5, 2, 0]);
+        assert!(heap.get(&'b').is_none());
+    }
+
+    #[test]
+    fn test_iter_priority_walks_the_heap_in_order() {
+        let mut heap = ModifiableBinaryHeap::new();
+        for value in [5, 1, 8, 3, 9, 2, 8, 7] {
+            heap.push(value);
+        }
+        let walked: Vec<i32> = heap.iter_priority().copied().collect();
+        assert_eq!(walked, vec![9, 8, 8, 7, 5, 3, 2, 1]);
+        assert_eq!(heap.iter_priority().take(2).count(), 2);
+        assert!(ModifiableBinaryHeap::<i32>::new()
+            .iter_priority()
+            .next()
+            .is_none());
+    }
 }
@@ -1 +1,2 @@
-pub mod main;
\ No newline at end of file
+pub mod arena;
+pub mod main;
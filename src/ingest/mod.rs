@@ -0,0 +1,329 @@
+use crate::enums::orderbook_state::OrderbookState;
+use crate::enums::side::OrderSide;
+use crate::formats::binance::{BinanceDepthSnapshot, BinanceDepthUpdate, BinanceLevel};
+use crate::formats::level_diff::LevelChange;
+use crate::structs::ids::Symbol;
+use crate::structs::orderbook::Orderbook;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// Levels of the book of an external venue at a sequence number of its feed
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct L2Snapshot {
+    pub sequence: u64,
+    /// (price, quantity) of the bid levels
+    pub bids: Vec<(f64, f64)>,
+    /// (price, quantity) of the ask levels
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Diff of the book of an external venue: the new quantities of the changed levels, 0 removes a
+/// level, covering a range of sequence numbers of its feed
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct L2Diff {
+    #[serde(rename = "firstSequence")]
+    pub first_sequence: u64,
+    #[serde(rename = "lastSequence")]
+    pub last_sequence: u64,
+    pub changes: Vec<LevelChange>,
+}
+
+impl L2Diff {
+    pub fn new(first_sequence: u64, last_sequence: u64, changes: Vec<LevelChange>) -> L2Diff {
+        L2Diff {
+            first_sequence,
+            last_sequence,
+            changes,
+        }
+    }
+}
+
+fn parse_level(level: &BinanceLevel) -> Result<(f64, f64), Error> {
+    let parse = |value: &String| {
+        value
+            .parse::<f64>()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid price level"))
+    };
+    Ok((parse(&level[0])?, parse(&level[1])?))
+}
+
+fn parse_levels(levels: &[BinanceLevel]) -> Result<Vec<(f64, f64)>, Error> {
+    levels.iter().map(parse_level).collect()
+}
+
+impl TryFrom<&BinanceDepthSnapshot> for L2Snapshot {
+    type Error = Error;
+
+    fn try_from(snapshot: &BinanceDepthSnapshot) -> Result<L2Snapshot, Error> {
+        Ok(L2Snapshot {
+            sequence: snapshot.last_update_id,
+            bids: parse_levels(&snapshot.bids)?,
+            asks: parse_levels(&snapshot.asks)?,
+        })
+    }
+}
+
+impl TryFrom<&BinanceDepthUpdate> for L2Diff {
+    type Error = Error;
+
+    fn try_from(update: &BinanceDepthUpdate) -> Result<L2Diff, Error> {
+        let side = |levels: &[BinanceLevel], side| {
+            parse_levels(levels).map(|levels| {
+                levels
+                    .into_iter()
+                    .map(move |(price, quantity)| LevelChange {
+                        side,
+                        price,
+                        quantity,
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let mut changes = side(&update.bids, OrderSide::Buy)?;
+        changes.extend(side(&update.asks, OrderSide::Sell)?);
+        Ok(L2Diff::new(
+            update.first_update_id,
+            update.final_update_id,
+            changes,
+        ))
+    }
+}
+
+/// Keeps a mirrored orderbook in step with the L2 feed of an external venue: the diffs received
+/// before the snapshot are buffered, then replayed on top of it, and every diff must follow the
+/// previous one without a gap in the sequence numbers
+#[derive(Debug, Clone, Default)]
+pub struct MirrorFeed {
+    /// Sequence number of the last change applied, None until a snapshot is applied
+    sequence: Option<u64>,
+    /// Diffs received while waiting for a snapshot
+    pending: Vec<L2Diff>,
+}
+
+impl MirrorFeed {
+    pub fn new() -> MirrorFeed {
+        MirrorFeed::default()
+    }
+
+    /// Sequence number of the last change applied, None while waiting for a snapshot
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// Whether the book is in step with the feed
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    /// Replace the levels of the book with a snapshot, then apply the buffered diffs which follow it
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook, in Mirror state
+    /// * 'snapshot' - The snapshot of the external book
+    ///
+    /// #Returns
+    /// * () - An error if the orderbook is not a mirror or a buffered diff leaves a gap after the
+    ///   snapshot, in which case a newer snapshot is needed
+    pub fn apply_snapshot(
+        &mut self,
+        orderbook: &mut Orderbook,
+        snapshot: &L2Snapshot,
+    ) -> Result<(), Error> {
+        check_mirror(orderbook)?;
+        orderbook.clear_levels();
+        for (side, levels) in [
+            (OrderSide::Buy, &snapshot.bids),
+            (OrderSide::Sell, &snapshot.asks),
+        ] {
+            for &(price, quantity) in levels.iter() {
+                orderbook.set_level(side, price, quantity);
+            }
+        }
+        self.sequence = Some(snapshot.sequence);
+        for diff in std::mem::take(&mut self.pending) {
+            self.apply_diff(orderbook, &diff)?;
+        }
+        Ok(())
+    }
+
+    /// Apply a diff to the book, or buffer it while waiting for a snapshot. The diffs already
+    /// covered by the book are skipped
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook, in Mirror state
+    /// * 'diff' - The diff of the external book
+    ///
+    /// #Returns
+    /// * () - An error if the orderbook is not a mirror, or if the diff leaves a gap after the last
+    ///   one applied, in which case the feed waits for a new snapshot
+    pub fn apply_diff(&mut self, orderbook: &mut Orderbook, diff: &L2Diff) -> Result<(), Error> {
+        check_mirror(orderbook)?;
+        let Some(sequence) = self.sequence else {
+            self.pending.push(diff.clone());
+            return Ok(());
+        };
+        if diff.last_sequence <= sequence {
+            return Ok(());
+        }
+        if diff.first_sequence > sequence + 1 {
+            self.sequence = None;
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Sequence gap in the L2 feed, a new snapshot is needed",
+            ));
+        }
+        for change in diff.changes.iter() {
+            orderbook.set_level(change.side, change.price, change.quantity);
+        }
+        self.sequence = Some(diff.last_sequence);
+        Ok(())
+    }
+}
+
+fn check_mirror(orderbook: &Orderbook) -> Result<(), Error> {
+    match orderbook.state {
+        OrderbookState::Mirror => Ok(()),
+        _ => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Orderbook is not a mirror",
+        )),
+    }
+}
+
+impl OrderbooksManager {
+    /// Create an orderbook mirroring the book of an external venue: it matches nothing and rejects
+    /// the orders, its levels are set by `apply_l2_snapshot` and `apply_l2_diff`, and the summary,
+    /// depth and metrics APIs work on it as on any orderbook
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn new_mirror_orderbook(&mut self, symbol: Symbol) -> Result<(), Error> {
+        if self.orderbooks.contains_key(&symbol) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "Orderbook already exists",
+            ));
+        }
+        self.new_orderbook(symbol);
+        self.set_state(symbol, OrderbookState::Mirror)?;
+        self.mirrors.insert(symbol, MirrorFeed::new());
+        Ok(())
+    }
+
+    /// Replace the levels of a mirrored orderbook with a snapshot of the external book
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'snapshot' - The snapshot
+    pub fn apply_l2_snapshot(
+        &mut self,
+        symbol: Symbol,
+        snapshot: &L2Snapshot,
+    ) -> Result<(), Error> {
+        let (Some(feed), Some(orderbook)) = (
+            self.mirrors.get_mut(&symbol),
+            self.orderbooks.get_mut(&symbol),
+        ) else {
+            return Err(Error::new(ErrorKind::NotFound, "Mirror not found"));
+        };
+        let applied = feed.apply_snapshot(orderbook, snapshot);
+        self.dispatch();
+        applied
+    }
+
+    /// Apply a diff of the external book to a mirrored orderbook, checking its sequence numbers
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'diff' - The diff
+    ///
+    /// #Returns
+    /// * () - An error on a gap in the sequence numbers, a new snapshot is then needed
+    pub fn apply_l2_diff(&mut self, symbol: Symbol, diff: &L2Diff) -> Result<(), Error> {
+        let (Some(feed), Some(orderbook)) = (
+            self.mirrors.get_mut(&symbol),
+            self.orderbooks.get_mut(&symbol),
+        ) else {
+            return Err(Error::new(ErrorKind::NotFound, "Mirror not found"));
+        };
+        let applied = feed.apply_diff(orderbook, diff);
+        self.dispatch();
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::ids::UserId;
+    use crate::structs::order::Order;
+
+    fn level(price: &str, quantity: &str) -> BinanceLevel {
+        [price.to_string(), quantity.to_string()]
+    }
+
+    fn update(first: u64, last: u64, bids: Vec<BinanceLevel>) -> BinanceDepthUpdate {
+        BinanceDepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 0,
+            symbol: "BNBBTC".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            bids,
+            asks: vec![],
+        }
+    }
+
+    #[test]
+    fn test_mirror_follows_the_feed() {
+        let mut manager = OrderbooksManager::new();
+        let symbol = Symbol(1);
+        manager.new_mirror_orderbook(symbol).unwrap();
+        assert!(manager.new_mirror_orderbook(symbol).is_err());
+
+        // buffered until the snapshot, the part already in the snapshot is skipped
+        let early = L2Diff::try_from(&update(8, 11, vec![level("4.5", "1.0")])).unwrap();
+        manager.apply_l2_diff(symbol, &early).unwrap();
+        assert!(manager.orderbooks[&symbol].bids.is_empty());
+        let snapshot = L2Snapshot::try_from(&BinanceDepthSnapshot {
+            last_update_id: 10,
+            bids: vec![level("4.00000000", "2.0"), level("3.9", "1.0")],
+            asks: vec![level("5.0", "3.0")],
+        })
+        .unwrap();
+        manager.apply_l2_snapshot(symbol, &snapshot).unwrap();
+        assert_eq!(manager.mirrors[&symbol].sequence(), Some(11));
+        let summary = manager.get_orderbook(symbol).unwrap();
+        assert_eq!(summary.bids.len(), 3);
+        assert_eq!(summary.asks[0].price, 5.0);
+
+        let diff = update(12, 12, vec![level("4.5", "0"), level("4.0", "2.5")]);
+        manager
+            .apply_l2_diff(symbol, &L2Diff::try_from(&diff).unwrap())
+            .unwrap();
+        let (bids, _) = manager.orderbooks[&symbol].depth(1);
+        assert_eq!((bids[0].price, bids[0].quantity), (4.0, 2.5));
+        assert_eq!(manager.orderbooks[&symbol].level_count(OrderSide::Buy), 2);
+
+        // a mirror matches nothing and accepts no order
+        let order = Order::new(
+            UserId(1),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(3.0),
+            OrderType::Limit,
+        );
+        assert!(manager.add_order(order).is_err());
+
+        let gap = L2Diff::try_from(&update(14, 15, vec![level("4.0", "1.0")])).unwrap();
+        assert_eq!(
+            manager.apply_l2_diff(symbol, &gap).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(!manager.mirrors[&symbol].is_synced());
+        assert!(L2Diff::try_from(&update(1, 1, vec![level("x", "1.0")])).is_err());
+    }
+}
@@ -1,6 +1,29 @@
+mod accounts;
+mod algos;
+mod api;
+#[cfg(feature = "native")]
+pub mod bench;
 mod enums;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod formats;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 mod heap;
+mod ingest;
+mod metrics;
+mod persistence;
+#[cfg(feature = "proto")]
+pub mod proto;
+mod recorder;
+mod risk;
+#[cfg(feature = "native")]
+mod sinks;
+pub mod sim;
 mod structs;
+pub mod testing;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub type OrderBook = structs::orderbook::Orderbook;
 pub type Order = structs::order::Order;
@@ -14,3 +37,184 @@ pub type OrderStatus = enums::order_status::OrderStatus;
 pub type TradeStatus = enums::trade_status::TradeStatus;
 pub type PaymentStatus = enums::payment_status::PaymentStatus;
 pub type OrderBookSummarized = structs::orderbook_sum::OrderBookSummarized;
+pub type TradeHistory = structs::trade_history::TradeHistory;
+pub type BookSnapshot = structs::book_snapshot::BookSnapshot;
+pub type MarketDataFeed = structs::market_data_feed::MarketDataFeed;
+pub type UpdateBus = structs::update_bus::UpdateBus;
+pub type OverflowPolicy = enums::overflow_policy::OverflowPolicy;
+pub type Subscription = structs::subscription::Subscription;
+pub type SubscriptionBuilder<'a> = structs::subscription_builder::SubscriptionBuilder<'a>;
+pub type FilteredSubscription = structs::subscription_builder::FilteredSubscription;
+pub type UpdateFilter = structs::subscription_builder::UpdateFilter;
+pub type SessionRegistry = structs::session::SessionRegistry;
+pub type SessionEvent = structs::session::SessionEvent;
+pub type SessionEventType = enums::session_event_type::SessionEventType;
+pub type BatchMode = enums::batch_mode::BatchMode;
+pub use structs::matching_algorithm::MatchingAlgorithm;
+pub use structs::match_observer::MatchObserver;
+pub type PriceTimeMatcher = structs::matching_algorithm::PriceTimeMatcher;
+pub type AuctionResult = structs::auction::AuctionResult;
+pub type OrderbookState = enums::orderbook_state::OrderbookState;
+pub type PriceBand = structs::price_band::PriceBand;
+pub type CircuitBreakerEvent = structs::price_band::CircuitBreakerEvent;
+pub type PriceReference = enums::price_reference::PriceReference;
+pub type ReferenceSource = enums::reference_source::ReferenceSource;
+pub type BandAction = enums::band_action::BandAction;
+pub type RiskEngine = risk::engine::RiskEngine;
+pub type RiskLimits = risk::limits::RiskLimits;
+pub use risk::limits::RiskCheck;
+pub type Accounts = accounts::ledger::Accounts;
+pub type Balance = accounts::balance::Balance;
+pub type Settlement = accounts::balance::Settlement;
+pub use structs::clock::Clock;
+pub type SystemClock = structs::clock::SystemClock;
+pub type MockClock = structs::clock::MockClock;
+pub type OrderbookConfig = structs::orderbook_config::OrderbookConfig;
+pub type FeeSchedule = structs::orderbook_config::FeeSchedule;
+pub type SelfTradePrevention = enums::self_trade_prevention::SelfTradePrevention;
+#[cfg(feature = "native")]
+pub type ShardedManager = structs::sharded_manager::ShardedManager;
+#[cfg(feature = "native")]
+pub type EngineHandle = structs::engine::EngineHandle;
+#[cfg(feature = "native")]
+pub type Command = structs::engine::Command;
+pub type OrderAck = structs::order_ack::OrderAck;
+pub type PoolStats = heap::arena::PoolStats;
+pub type PriceLevel = structs::level_book::PriceLevel;
+pub type BookMetrics = structs::book_metrics::BookMetrics;
+pub type BidAskSummarize = structs::orderbook_sum::BidAskSummarize;
+pub type LevelDiffs = formats::level_diff::LevelDiffs;
+pub type LevelChange = formats::level_diff::LevelChange;
+pub type BinanceDepthSnapshot = formats::binance::BinanceDepthSnapshot;
+pub type BinanceDepthUpdate = formats::binance::BinanceDepthUpdate;
+pub type CoinbaseSnapshot = formats::coinbase::CoinbaseSnapshot;
+pub type CoinbaseL2Update = formats::coinbase::CoinbaseL2Update;
+pub type PlaceOrderRequest = api::requests::PlaceOrderRequest;
+pub type CancelRequest = api::requests::CancelRequest;
+pub type AmendRequest = api::requests::AmendRequest;
+pub type BookDepthRequest = api::requests::BookDepthRequest;
+pub type ApiRequest = api::requests::ApiRequest;
+pub type BookDepthResponse = api::responses::BookDepthResponse;
+pub type ApiResponse = api::responses::ApiResponse;
+pub type ErrorResponse = api::responses::ErrorResponse;
+pub type Dispatcher<'a> = api::dispatcher::Dispatcher<'a>;
+pub use persistence::Persistence;
+pub type StatusTransition = persistence::StatusTransition;
+#[cfg(feature = "postgres")]
+pub type PostgresPersistence = persistence::postgres::PostgresPersistence;
+pub type SinkFormat = enums::sink_format::SinkFormat;
+#[cfg(feature = "native")]
+pub type SinkConfig = sinks::SinkConfig;
+#[cfg(feature = "native")]
+pub type SinkRecord = sinks::SinkRecord;
+#[cfg(feature = "native")]
+pub type SinkStats = sinks::SinkStats;
+#[cfg(feature = "native")]
+pub use sinks::{forward_updates, SinkTransport};
+#[cfg(feature = "kafka")]
+pub type KafkaSink = sinks::kafka::KafkaSink;
+#[cfg(feature = "nats")]
+pub type NatsSink = sinks::nats::NatsSink;
+pub type EngineMetrics = metrics::EngineMetrics;
+pub type SymbolMetrics = metrics::SymbolMetrics;
+pub type LatencyHistogram = metrics::LatencyHistogram;
+pub type MetricsRecorder = metrics::MetricsRecorder;
+pub type HdrHistogram = metrics::hdr::HdrHistogram;
+pub type OrderLatency = metrics::OrderLatency;
+pub type StageLatencies = metrics::StageLatencies;
+#[cfg(feature = "metrics")]
+pub type PrometheusMetrics = metrics::prometheus::PrometheusMetrics;
+pub type InvariantReport = structs::invariants::InvariantReport;
+pub type InvariantViolation = structs::invariants::InvariantViolation;
+pub type Liquidity = enums::liquidity::Liquidity;
+pub type MarketRemainder = enums::market_remainder::MarketRemainder;
+pub type PegReference = enums::peg_reference::PegReference;
+pub type Basket = structs::basket::Basket;
+pub type BasketLeg = structs::basket::BasketLeg;
+pub type BasketEvent = structs::basket::BasketEvent;
+pub type BasketRegistry = structs::basket::BasketRegistry;
+pub type SpreadDefinition = structs::spread::SpreadDefinition;
+pub type ImpliedPrice = structs::spread::ImpliedPrice;
+pub type SpreadExecution = structs::spread::SpreadExecution;
+pub type SpreadAck = structs::spread::SpreadAck;
+pub type OrderEvent = structs::order_history::OrderEvent;
+pub type OrderHistory = structs::order_history::OrderHistory;
+pub type Position = structs::positions::Position;
+pub type PositionTracker = structs::positions::PositionTracker;
+pub use structs::ids::{OrderId, Symbol, TenantId, UserId};
+pub type DepthLevel<'a> = structs::level_book::DepthLevel<'a>;
+pub type DepthIter<'a> = structs::level_book::DepthIter<'a>;
+pub type FillCost = structs::level_book::FillCost;
+pub type RateLimit = risk::rate_limit::RateLimit;
+pub type RateLimiter = risk::rate_limit::RateLimiter;
+pub type MessageRatioMonitor = risk::message_ratio::MessageRatioMonitor;
+pub type MessageRatioPolicy = risk::message_ratio::MessageRatioPolicy;
+pub type MessageRatio = risk::message_ratio::MessageRatio;
+pub type ComplianceEvent = risk::message_ratio::ComplianceEvent;
+pub type RatioAction = enums::ratio_action::RatioAction;
+pub type KillSwitches = structs::kill_switch::KillSwitches;
+pub type KillSwitchTarget = structs::kill_switch::KillSwitchTarget;
+pub type KillSwitchEvent = structs::kill_switch::KillSwitchEvent;
+pub type ShutdownPolicy = enums::shutdown_policy::ShutdownPolicy;
+pub type ShutdownReport = structs::shutdown::ShutdownReport;
+pub use formats::checksum::{book_checksum, crc32, CHECKSUM_DEPTH};
+pub type SymbolInfo = structs::symbol_registry::SymbolInfo;
+pub type SymbolRegistry = structs::symbol_registry::SymbolRegistry;
+pub type SettlementDirection = enums::settlement_direction::SettlementDirection;
+pub type SettlementLeg = accounts::settlement::SettlementLeg;
+pub type SettlementInstruction = accounts::settlement::SettlementInstruction;
+pub type Metadata = structs::order::Metadata;
+pub type DeliveryFailurePolicy = enums::delivery_failure_policy::DeliveryFailurePolicy;
+pub type DeadLetter = structs::dead_letter::DeadLetter;
+pub type DeadLetterQueue = structs::dead_letter::DeadLetterQueue;
+pub use persistence::journal::Journal;
+pub type MemoryJournal = persistence::journal::MemoryJournal;
+pub type FileJournal = persistence::journal::FileJournal;
+pub use persistence::snapshot_store::SnapshotStore;
+pub type MemorySnapshotStore = persistence::snapshot_store::MemorySnapshotStore;
+pub type FileSnapshotStore = persistence::snapshot_store::FileSnapshotStore;
+pub type SnapshotPolicy = persistence::snapshots::SnapshotPolicy;
+pub type SnapshotService = persistence::snapshots::SnapshotService;
+#[cfg(feature = "native")]
+pub use persistence::snapshots::spawn_snapshot_thread;
+pub type RecoveryReport = persistence::recovery::RecoveryReport;
+pub type LevelStats = structs::level_book::LevelStats;
+pub type LevelDelta = structs::level_book::LevelDelta;
+pub type LevelAction = enums::level_action::LevelAction;
+pub type MarketFeed = enums::market_feed::MarketFeed;
+pub type Router = structs::router::Router;
+pub type ParentOrder = structs::router::ParentOrder;
+pub type ChildOrder = structs::router::ChildOrder;
+pub type ChildAllocation = structs::router::ChildAllocation;
+pub type ParentEvent = structs::router::ParentEvent;
+pub type AlgoOrder = algos::AlgoOrder;
+pub type AlgoStrategy = algos::AlgoStrategy;
+pub type AlgoEvent = algos::AlgoEvent;
+pub type AlgoScheduler = algos::AlgoScheduler;
+#[cfg(feature = "native")]
+pub use algos::spawn_algo_thread;
+pub type BookCondition = structs::book_condition::BookCondition;
+pub type TimeInForce = enums::time_in_force::TimeInForce;
+pub type SessionPhase = enums::session_phase::SessionPhase;
+pub type TradingHours = structs::trading_calendar::TradingHours;
+pub type TradingCalendar = structs::trading_calendar::TradingCalendar;
+pub type SessionTransition = structs::trading_calendar::SessionTransition;
+pub type L2Snapshot = ingest::L2Snapshot;
+pub type L2Diff = ingest::L2Diff;
+pub type MirrorFeed = ingest::MirrorFeed;
+pub use persistence::format::FORMAT_VERSION;
+pub type SettlementGate = accounts::settlement_gate::SettlementGate;
+pub type TradeStatusEvent = accounts::settlement_gate::TradeStatusEvent;
+pub type SettlementFailurePolicy = enums::settlement_failure_policy::SettlementFailurePolicy;
+pub type IdempotencyGuard = risk::idempotency::IdempotencyGuard;
+pub type OrderbookView = structs::orderbook_view::OrderbookView;
+pub type DepthRecorder = recorder::DepthRecorder;
+pub type RecorderPolicy = recorder::RecorderPolicy;
+pub type DepthSample = recorder::DepthSample;
+pub type BboSample = recorder::BboSample;
+#[cfg(feature = "native")]
+pub use recorder::spawn_recorder_thread;
+pub type TenantQuota = structs::tenant::TenantQuota;
+pub type TenantRegistry = structs::tenant::TenantRegistry;
+pub type SummaryPrivacy = enums::summary_privacy::SummaryPrivacy;
+pub type LevelLiquidity = structs::orderbook_sum::LevelLiquidity;
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+/// Bits of the sub-buckets splitting each power of two, the recorded values keep a relative
+/// precision better than 1 / 2^HDR_PRECISION_BITS
+pub const HDR_PRECISION_BITS: u32 = 7;
+
+const SUB_BUCKETS: u64 = 1 << HDR_PRECISION_BITS;
+
+/// Index of the bucket counting a value: exact below SUB_BUCKETS, then SUB_BUCKETS buckets per power of two
+fn bucket_of(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let shift = 63 - value.leading_zeros() - HDR_PRECISION_BITS;
+    let mantissa = value >> shift;
+    ((shift as u64 + 1) * SUB_BUCKETS + mantissa - SUB_BUCKETS) as usize
+}
+
+/// Highest value counted by a bucket
+fn highest_of(bucket: usize) -> u64 {
+    let bucket = bucket as u64;
+    if bucket < SUB_BUCKETS {
+        return bucket;
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    let lowest = (bucket % SUB_BUCKETS + SUB_BUCKETS) << shift;
+    lowest + (1 << shift) - 1
+}
+
+/// High dynamic range histogram of latencies: log-linear buckets keeping the same relative precision
+/// from nanoseconds to minutes, so that the tail quantiles are read without fixed bucket bounds
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct HdrHistogram {
+    /// Number of values per bucket, up to the bucket of the largest value
+    pub counts: Vec<u64>,
+    /// Number of values recorded
+    pub count: u64,
+    /// Sum of the values
+    pub sum: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl HdrHistogram {
+    /// Record a value, e.g. a latency in nanoseconds
+    pub fn record(&mut self, value: u64) {
+        let bucket = bucket_of(value);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// Mean of the values, 0 when nothing was recorded
+    pub fn mean(&self) -> u64 {
+        match self.count {
+            0 => 0,
+            count => self.sum / count,
+        }
+    }
+
+    /// Value at a quantile, within the precision of the buckets
+    ///
+    /// #Parameters
+    /// * 'quantile' - The quantile, between 0 and 1, e.g. 0.999
+    ///
+    /// #Returns
+    /// * u64 - The highest value of the bucket holding the quantile, capped by the largest value, 0 when nothing was recorded
+    pub fn value_at_quantile(&self, quantile: f64) -> u64 {
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64)
+            .ceil()
+            .max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return highest_of(bucket).min(self.max);
+            }
+        }
+        0
+    }
+
+    /// Add the values recorded by another histogram
+    pub fn merge(&mut self, other: &HdrHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdr_histogram() {
+        let mut histogram = HdrHistogram::default();
+        assert_eq!(histogram.value_at_quantile(0.99), 0);
+        for value in 1..=10_000 {
+            histogram.record(value * 1_000);
+        }
+        histogram.record(60_000_000_000);
+        assert_eq!((histogram.min, histogram.max), (1_000, 60_000_000_000));
+        for (quantile, expected) in [(0.5, 5_000_000.0), (0.99, 9_900_000.0)] {
+            let value = histogram.value_at_quantile(quantile) as f64;
+            assert!((value - expected).abs() / expected < 1.0 / SUB_BUCKETS as f64);
+        }
+        assert_eq!(histogram.value_at_quantile(1.0), 60_000_000_000);
+        // the buckets only grow up to the largest value
+        assert!(histogram.counts.len() < 64 * SUB_BUCKETS as usize);
+
+        let mut merged = HdrHistogram::default();
+        merged.merge(&histogram);
+        merged.merge(&HdrHistogram::default());
+        assert_eq!(merged, histogram);
+        for value in 0..SUB_BUCKETS {
+            assert_eq!(highest_of(bucket_of(value)), value);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+pub mod hdr;
+#[cfg(feature = "metrics")]
+pub mod prometheus;
+
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::structs::ids::Symbol;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use hdr::HdrHistogram;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Upper bounds in nanoseconds of the match latency buckets, the last bucket counts the slower matches
+pub const LATENCY_BUCKETS: [u64; 10] = [
+    1_000,
+    5_000,
+    10_000,
+    50_000,
+    100_000,
+    500_000,
+    1_000_000,
+    5_000_000,
+    10_000_000,
+    100_000_000,
+];
+
+/// Histogram of the time the orderbooks took to match the incoming orders
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct LatencyHistogram {
+    /// Number of matches per bucket of `LATENCY_BUCKETS`, plus one bucket for the slower matches
+    pub counts: Vec<u64>,
+    /// Number of matches recorded
+    pub count: u64,
+    /// Sum of the latencies in nanoseconds
+    pub sum: u64,
+    /// Slowest match in nanoseconds
+    pub max: u64,
+}
+
+impl LatencyHistogram {
+    /// Record the latency of a match
+    ///
+    /// #Parameters
+    /// * 'nanos' - The latency in nanoseconds
+    pub fn record(&mut self, nanos: u64) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        let bucket = LATENCY_BUCKETS.partition_point(|&bound| bound < nanos);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += nanos;
+        self.max = self.max.max(nanos);
+    }
+
+    /// Mean latency in nanoseconds, 0 when nothing was recorded
+    pub fn mean(&self) -> u64 {
+        match self.count {
+            0 => 0,
+            count => self.sum / count,
+        }
+    }
+
+    /// Upper bound of the bucket holding the given quantile of the latencies
+    ///
+    /// #Parameters
+    /// * 'quantile' - The quantile, between 0 and 1, e.g. 0.99
+    ///
+    /// #Returns
+    /// * u64 - The latency in nanoseconds, the slowest match for the last bucket, 0 when nothing was recorded
+    pub fn quantile(&self, quantile: f64) -> u64 {
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64)
+            .ceil()
+            .max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return LATENCY_BUCKETS.get(bucket).copied().unwrap_or(self.max);
+            }
+        }
+        0
+    }
+
+    /// Add the latencies recorded by another histogram
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Time an order spent in each stage of the engine, in nanoseconds
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OrderLatency {
+    /// From the ingress of the order to its hand over to the orderbook, the admission checks included
+    pub queued: u64,
+    /// Matching of the order by the orderbook
+    pub matching: u64,
+    /// Dispatching of the updates of the order to the subscribers
+    pub publishing: u64,
+}
+
+impl OrderLatency {
+    /// Time from the ingress of the order to the publication of its updates
+    pub fn total(&self) -> u64 {
+        self.queued + self.matching + self.publishing
+    }
+}
+
+/// Distributions of the time the orders spent in each stage of the engine
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct StageLatencies {
+    pub queued: HdrHistogram,
+    pub matching: HdrHistogram,
+    pub publishing: HdrHistogram,
+    /// From the ingress of the orders to the publication of their updates
+    pub total: HdrHistogram,
+}
+
+impl StageLatencies {
+    /// Record the latency of an order
+    pub fn record(&mut self, latency: &OrderLatency) {
+        self.queued.record(latency.queued);
+        self.matching.record(latency.matching);
+        self.publishing.record(latency.publishing);
+        self.total.record(latency.total());
+    }
+
+    /// Add the latencies recorded by other stages
+    pub fn merge(&mut self, other: &StageLatencies) {
+        self.queued.merge(&other.queued);
+        self.matching.merge(&other.matching);
+        self.publishing.merge(&other.publishing);
+        self.total.merge(&other.total);
+    }
+}
+
+/// Activity counters of one orderbook, or of all of them
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SymbolMetrics {
+    /// Orders which passed the validation, the risk checks and the balance reservation
+    pub orders_accepted: u64,
+    /// Orders rejected before reaching the orderbook
+    pub orders_rejected: u64,
+    /// Rejected orders refused by the rate limiter of their user
+    pub orders_rate_limited: u64,
+    /// Rejected orders over the notional limits of their user
+    #[serde(default)]
+    pub orders_notional_throttled: u64,
+    /// Orders cancelled, by their user or by the engine
+    pub cancels: u64,
+    pub trades: u64,
+    /// Sum of the quantities traded
+    pub matched_volume: f64,
+    /// Time taken by the orderbook to match the accepted orders
+    pub match_latency: LatencyHistogram,
+    /// Time the orders added one at a time spent queued, matching and publishing
+    #[serde(default)]
+    pub stage_latencies: StageLatencies,
+}
+
+impl SymbolMetrics {
+    /// Share of the accepted orders which were cancelled, 0 when no order was accepted
+    pub fn cancel_rate(&self) -> f64 {
+        match self.orders_accepted {
+            0 => 0.0,
+            accepted => self.cancels as f64 / accepted as f64,
+        }
+    }
+
+    /// Add the counters of another orderbook, e.g. to total the orderbooks of a tenant
+    pub fn merge(&mut self, other: &SymbolMetrics) {
+        self.orders_accepted += other.orders_accepted;
+        self.orders_rejected += other.orders_rejected;
+        self.orders_rate_limited += other.orders_rate_limited;
+        self.orders_notional_throttled += other.orders_notional_throttled;
+        self.cancels += other.cancels;
+        self.trades += other.trades;
+        self.matched_volume += other.matched_volume;
+        self.match_latency.merge(&other.match_latency);
+        self.stage_latencies.merge(&other.stage_latencies);
+    }
+}
+
+/// Statistics of the engine, as returned by `OrderbooksManager::metrics`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct EngineMetrics {
+    /// Totals over every orderbook
+    pub global: SymbolMetrics,
+    /// Counters per symbol ID
+    pub symbols: HashMap<Symbol, SymbolMetrics>,
+    /// Updates published by the orderbooks and not dispatched yet
+    pub channel_depth: usize,
+    /// Updates queued for the most lagging subscriber
+    pub subscriber_depth: usize,
+}
+
+impl EngineMetrics {
+    /// Counters of an orderbook
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn symbol(&self, symbol: Symbol) -> Option<&SymbolMetrics> {
+        self.symbols.get(&symbol)
+    }
+
+    /// Apply a change to the global counters and to the counters of a symbol
+    fn update(&mut self, symbol: Option<Symbol>, change: impl Fn(&mut SymbolMetrics)) {
+        change(&mut self.global);
+        if let Some(symbol) = symbol {
+            change(self.symbols.entry(symbol).or_default());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    metrics: EngineMetrics,
+    #[cfg(feature = "metrics")]
+    exporter: Option<prometheus::PrometheusMetrics>,
+}
+
+/// Collects the engine metrics as the manager accepts orders and dispatches the updates.
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRecorder {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+impl MetricsRecorder {
+    pub fn new() -> MetricsRecorder {
+        MetricsRecorder::default()
+    }
+
+    /// Count an order handed to its orderbook
+    pub fn on_accepted(&self, symbol: Symbol) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .metrics
+            .update(Some(symbol), |m| m.orders_accepted += 1);
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_accepted(symbol);
+        }
+    }
+
+    /// Count a rejected order, the symbol is None when the order does not target a known orderbook
+    pub fn on_rejected(&self, symbol: Option<Symbol>) {
+        let mut state = self.state.lock().unwrap();
+        state.metrics.update(symbol, |m| m.orders_rejected += 1);
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_rejected(symbol);
+        }
+    }
+
+    /// Count an order refused by the rate limiter, on top of its count as a rejected order
+    pub fn on_rate_limited(&self, symbol: Option<Symbol>) {
+        let mut state = self.state.lock().unwrap();
+        state.metrics.update(symbol, |m| m.orders_rate_limited += 1);
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_rate_limited(symbol);
+        }
+    }
+
+    /// Count an order over the notional limits of its user, on top of its count as a rejected order
+    pub fn on_notional_throttled(&self, symbol: Option<Symbol>) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .metrics
+            .update(symbol, |m| m.orders_notional_throttled += 1);
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_notional_throttled(symbol);
+        }
+    }
+
+    /// Record the time an orderbook took to match an order
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'nanos' - The latency in nanoseconds
+    pub fn on_match(&self, symbol: Symbol, nanos: u64) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .metrics
+            .update(Some(symbol), |m| m.match_latency.record(nanos));
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_match(symbol, nanos);
+        }
+    }
+
+    /// Record the time an order spent in each stage of the engine
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'latency' - The latency of the order
+    pub fn on_latency(&self, symbol: Symbol, latency: &OrderLatency) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .metrics
+            .update(Some(symbol), |m| m.stage_latencies.record(latency));
+    }
+
+    /// Count the trades and the cancels of a dispatched update
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                let quantity = update.trade.as_ref().map_or(0.0, |t| t.quantity);
+                state.metrics.update(Some(update.symbol), |m| {
+                    m.trades += 1;
+                    m.matched_volume += quantity;
+                });
+            }
+            OrderbookUpdateType::Cancel => state
+                .metrics
+                .update(Some(update.symbol), |m| m.cancels += 1),
+            _ => {}
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &state.exporter {
+            exporter.on_update(update);
+        }
+    }
+
+    /// Copy of the counters
+    pub fn snapshot(&self) -> EngineMetrics {
+        self.state.lock().unwrap().metrics.clone()
+    }
+
+    /// Export the counters recorded from now on to a Prometheus registry
+    ///
+    /// #Parameters
+    /// * 'registry' - The registry scraped by Prometheus
+    #[cfg(feature = "metrics")]
+    pub fn export(&self, registry: &::prometheus::Registry) -> Result<(), std::io::Error> {
+        let exporter = prometheus::PrometheusMetrics::register(registry)?;
+        self.state.lock().unwrap().exporter = Some(exporter);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), 0);
+        for nanos in [500, 800, 20_000, 200_000_000] {
+            histogram.record(nanos);
+        }
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.counts[0], 2);
+        assert_eq!(histogram.counts[3], 1);
+        assert_eq!(histogram.quantile(0.5), 1_000);
+        assert_eq!(histogram.quantile(0.75), 50_000);
+        assert_eq!(histogram.quantile(1.0), 200_000_000);
+        assert_eq!(histogram.mean(), 50_005_325);
+    }
+}
@@ -0,0 +1,167 @@
+use super::LATENCY_BUCKETS;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::structs::ids::Symbol;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use prometheus::{CounterVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use std::io::Error;
+
+/// Label of the orders rejected before their orderbook was found
+const UNKNOWN_SYMBOL: &str = "unknown";
+
+/// Prometheus counters of the engine, labelled by symbol ID
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    orders_accepted: IntCounterVec,
+    orders_rejected: IntCounterVec,
+    orders_rate_limited: IntCounterVec,
+    orders_notional_throttled: IntCounterVec,
+    cancels: IntCounterVec,
+    trades: IntCounterVec,
+    matched_volume: CounterVec,
+    match_latency: HistogramVec,
+}
+
+fn int_counter(registry: &Registry, name: &str, help: &str) -> Result<IntCounterVec, Error> {
+    let counter = IntCounterVec::new(Opts::new(name, help), &["symbol"]).map_err(Error::other)?;
+    registry
+        .register(Box::new(counter.clone()))
+        .map_err(Error::other)?;
+    Ok(counter)
+}
+
+impl PrometheusMetrics {
+    /// Create the counters and register them
+    ///
+    /// #Parameters
+    /// * 'registry' - The registry scraped by Prometheus
+    ///
+    /// #Returns
+    /// * PrometheusMetrics - The counters, an error if they are already registered
+    pub fn register(registry: &Registry) -> Result<PrometheusMetrics, Error> {
+        let matched_volume = CounterVec::new(
+            Opts::new(
+                "orderbook_matched_volume_total",
+                "Sum of the quantities traded",
+            ),
+            &["symbol"],
+        )
+        .map_err(Error::other)?;
+        registry
+            .register(Box::new(matched_volume.clone()))
+            .map_err(Error::other)?;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .map(|&nanos| nanos as f64 / 1e9)
+            .collect();
+        let match_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "orderbook_match_latency_seconds",
+                "Time taken by the orderbooks to match the incoming orders",
+            )
+            .buckets(buckets),
+            &["symbol"],
+        )
+        .map_err(Error::other)?;
+        registry
+            .register(Box::new(match_latency.clone()))
+            .map_err(Error::other)?;
+        Ok(PrometheusMetrics {
+            orders_accepted: int_counter(
+                registry,
+                "orderbook_orders_accepted_total",
+                "Orders handed to their orderbook",
+            )?,
+            orders_rejected: int_counter(
+                registry,
+                "orderbook_orders_rejected_total",
+                "Orders rejected before reaching their orderbook",
+            )?,
+            orders_rate_limited: int_counter(
+                registry,
+                "orderbook_orders_rate_limited_total",
+                "Orders refused by the rate limiter of their user",
+            )?,
+            orders_notional_throttled: int_counter(
+                registry,
+                "orderbook_orders_notional_throttled_total",
+                "Orders over the notional limits of their user",
+            )?,
+            cancels: int_counter(registry, "orderbook_cancels_total", "Orders cancelled")?,
+            trades: int_counter(registry, "orderbook_trades_total", "Trades executed")?,
+            matched_volume,
+            match_latency,
+        })
+    }
+
+    pub(crate) fn on_accepted(&self, symbol: Symbol) {
+        self.orders_accepted
+            .with_label_values(&[&symbol.to_string()])
+            .inc();
+    }
+
+    pub(crate) fn on_rejected(&self, symbol: Option<Symbol>) {
+        let label = symbol.map_or(UNKNOWN_SYMBOL.to_string(), |s| s.to_string());
+        self.orders_rejected.with_label_values(&[&label]).inc();
+    }
+
+    pub(crate) fn on_rate_limited(&self, symbol: Option<Symbol>) {
+        let label = symbol.map_or(UNKNOWN_SYMBOL.to_string(), |s| s.to_string());
+        self.orders_rate_limited.with_label_values(&[&label]).inc();
+    }
+
+    pub(crate) fn on_notional_throttled(&self, symbol: Option<Symbol>) {
+        let label = symbol.map_or(UNKNOWN_SYMBOL.to_string(), |s| s.to_string());
+        self.orders_notional_throttled
+            .with_label_values(&[&label])
+            .inc();
+    }
+
+    pub(crate) fn on_match(&self, symbol: Symbol, nanos: u64) {
+        self.match_latency
+            .with_label_values(&[&symbol.to_string()])
+            .observe(nanos as f64 / 1e9);
+    }
+
+    pub(crate) fn on_update(&self, update: &OrderbookUpdate) {
+        let label = update.symbol.to_string();
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                self.trades.with_label_values(&[&label]).inc();
+                if let Some(trade) = update.trade.as_ref() {
+                    self.matched_volume
+                        .with_label_values(&[&label])
+                        .inc_by(trade.quantity);
+                }
+            }
+            OrderbookUpdateType::Cancel => self.cancels.with_label_values(&[&label]).inc(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricsRecorder;
+    use prometheus::{Encoder, TextEncoder};
+
+    #[test]
+    fn test_counters_are_exported() {
+        let registry = Registry::new();
+        let recorder = MetricsRecorder::new();
+        recorder.export(&registry).unwrap();
+        assert!(recorder.export(&registry).is_err());
+        recorder.on_accepted(Symbol(7));
+        recorder.on_rejected(None);
+        recorder.on_match(Symbol(7), 2_000);
+
+        let mut text = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut text)
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("orderbook_orders_accepted_total{symbol=\"7\"} 1"));
+        assert!(text.contains("orderbook_orders_rejected_total{symbol=\"unknown\"} 1"));
+        assert!(text.contains("orderbook_match_latency_seconds_count{symbol=\"7\"} 1"));
+    }
+}
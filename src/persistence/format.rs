@@ -0,0 +1,132 @@
+//! Versioned framing of the journal and snapshot files.
+//!
+//! A file starts with an 8 bytes header: the magic bytes `OBRS`, the format version (u16, little
+//! endian), the kind of file (`J` for a journal, `S` for a snapshot) and a reserved byte. The records
+//! follow, each framed by its length (u32, little endian) and the CRC32 of its bytes (u32, little
+//! endian). A record is the JSON of an update or a snapshot, so that the fields added by later
+//! versions are ignored and the fields missing from older versions take their default value.
+//!
+//! The files written before the format, made of bare JSON, are still read. A file of a newer
+//! format version, a corrupted record or a file of the wrong kind is an `InvalidData` error
+//! rather than a silently wrong book.
+
+use crate::formats::checksum::crc32;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Error, ErrorKind};
+
+/// Magic bytes starting every file of the format
+pub const MAGIC: [u8; 4] = *b"OBRS";
+/// Version of the format written, the readers accept this one and the older ones
+pub const FORMAT_VERSION: u16 = 1;
+/// Kind of the journal files
+pub const JOURNAL: u8 = b'J';
+/// Kind of the snapshot files
+pub const SNAPSHOT: u8 = b'S';
+
+const HEADER_LENGTH: usize = 8;
+const FRAME_LENGTH: usize = 8;
+
+/// Header of a file of a kind, at the current format version
+pub fn header(kind: u8) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&[kind, 0]);
+    header
+}
+
+/// Whether bytes start with the magic bytes of the format, the files written before it do not
+pub fn is_framed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Append a record framed by its length and checksum
+///
+/// #Parameters
+/// * 'out' - The bytes the record is appended to
+/// * 'value' - The record
+pub fn encode_record<T: Serialize>(out: &mut Vec<u8>, value: &T) -> Result<(), Error> {
+    let payload = serde_json::to_vec(value)?;
+    let length = u32::try_from(payload.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "Record too large"))?;
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(&crc32(&payload).to_le_bytes());
+    out.extend_from_slice(&payload);
+    Ok(())
+}
+
+/// Read the records of a file of the format
+///
+/// #Parameters
+/// * 'bytes' - The content of the file
+/// * 'kind' - The kind of file expected
+///
+/// #Returns
+/// * (Vec<T>, usize) - The records and the length of the bytes holding them. A last record cut
+///   short by a crash while it was written is left out of both, an error if the header or a
+///   complete record is invalid
+pub fn decode_records<T: DeserializeOwned>(
+    bytes: &[u8],
+    kind: u8,
+) -> Result<(Vec<T>, usize), Error> {
+    let invalid = |message| Error::new(ErrorKind::InvalidData, message);
+    if bytes.len() < HEADER_LENGTH || !is_framed(bytes) {
+        return Err(invalid("Missing file header"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(invalid("Unsupported file format version"));
+    }
+    if bytes[6] != kind {
+        return Err(invalid("Unexpected kind of file"));
+    }
+    let mut records = Vec::new();
+    let mut offset = HEADER_LENGTH;
+    while bytes.len() - offset >= FRAME_LENGTH {
+        let frame = &bytes[offset..offset + FRAME_LENGTH];
+        let length = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+        let checksum = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+        let start = offset + FRAME_LENGTH;
+        if bytes.len() - start < length {
+            break;
+        }
+        let payload = &bytes[start..start + length];
+        if crc32(payload) != checksum {
+            return Err(invalid("Record checksum mismatch"));
+        }
+        records.push(serde_json::from_slice(payload)?);
+        offset = start + length;
+    }
+    Ok((records, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framed_records() {
+        let mut bytes = header(JOURNAL);
+        encode_record(&mut bytes, &vec![1, 2]).unwrap();
+        encode_record(&mut bytes, &vec![3]).unwrap();
+        let complete = bytes.len();
+        let (records, length) = decode_records::<Vec<u8>>(&bytes, JOURNAL).unwrap();
+        assert_eq!((records, length), (vec![vec![1, 2], vec![3]], complete));
+
+        // a record cut short is left out
+        encode_record(&mut bytes, &vec![4]).unwrap();
+        bytes.pop();
+        let (records, length) = decode_records::<Vec<u8>>(&bytes, JOURNAL).unwrap();
+        assert_eq!((records.len(), length), (2, complete));
+
+        let kind = decode_records::<Vec<u8>>(&bytes, SNAPSHOT).unwrap_err();
+        assert_eq!(kind.kind(), ErrorKind::InvalidData);
+        let mut corrupted = bytes[..complete].to_vec();
+        corrupted[complete - 2] ^= 1;
+        assert!(decode_records::<Vec<u8>>(&corrupted, JOURNAL).is_err());
+        let mut newer = bytes[..complete].to_vec();
+        newer[4] = 0xFF;
+        assert!(decode_records::<Vec<u8>>(&newer, JOURNAL).is_err());
+        assert!(decode_records::<Vec<u8>>(b"{\"symbol\":1}", JOURNAL).is_err());
+    }
+}
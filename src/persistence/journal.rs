@@ -0,0 +1,230 @@
+use crate::persistence::format;
+use crate::structs::ids::Symbol;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Log of the sequenced updates of the orderbooks, appended by the manager before the updates are
+/// published. Replayed on top of the latest snapshot of a book, it brings the book back to its last state.
+pub trait Journal: fmt::Debug + Send + Sync {
+    /// Append an update, called in sequence order for each orderbook
+    fn append(&self, update: &OrderbookUpdate) -> Result<(), Error>;
+
+    /// Updates of an orderbook with a sequence number above the given one, in sequence order
+    fn read_after(&self, symbol: Symbol, sequence: u64) -> Result<Vec<OrderbookUpdate>, Error>;
+
+    /// Forget the updates of an orderbook up to a sequence number, called once a snapshot at this
+    /// sequence number is stored
+    fn truncate(&self, _symbol: Symbol, _sequence: u64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Make the appended updates durable, called when the engine shuts down
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called when appending an update failed, the update is published anyway
+    fn on_error(&self, _update: &OrderbookUpdate, _error: Error) {}
+}
+
+/// Journal kept in memory, for tests and for engines recovering from a snapshot only
+#[derive(Debug, Default)]
+pub struct MemoryJournal {
+    updates: Mutex<HashMap<Symbol, Vec<OrderbookUpdate>>>,
+}
+
+impl MemoryJournal {
+    pub fn new() -> MemoryJournal {
+        MemoryJournal::default()
+    }
+
+    /// Number of updates held for an orderbook
+    pub fn len(&self, symbol: Symbol) -> usize {
+        self.updates
+            .lock()
+            .unwrap()
+            .get(&symbol)
+            .map_or(0, |updates| updates.len())
+    }
+}
+
+impl Journal for MemoryJournal {
+    fn append(&self, update: &OrderbookUpdate) -> Result<(), Error> {
+        self.updates
+            .lock()
+            .unwrap()
+            .entry(update.symbol)
+            .or_default()
+            .push(update.clone());
+        Ok(())
+    }
+
+    fn read_after(&self, symbol: Symbol, sequence: u64) -> Result<Vec<OrderbookUpdate>, Error> {
+        let updates = self.updates.lock().unwrap();
+        Ok(updates
+            .get(&symbol)
+            .map(|updates| {
+                updates
+                    .iter()
+                    .filter(|u| u.sequence > sequence)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn truncate(&self, symbol: Symbol, sequence: u64) -> Result<(), Error> {
+        if let Some(updates) = self.updates.lock().unwrap().get_mut(&symbol) {
+            updates.retain(|u| u.sequence > sequence);
+        }
+        Ok(())
+    }
+}
+
+/// Journal writing the updates of each orderbook as framed records to `<directory>/<symbol>.journal`,
+/// see the `format` module. The JSON lines files of the older versions, `<symbol>.jsonl`, are
+/// rewritten in the format when their orderbook is first used.
+#[derive(Debug)]
+pub struct FileJournal {
+    directory: PathBuf,
+    /// Symbols whose file was migrated and cleared of a record cut short by a crash
+    checked: Mutex<HashSet<Symbol>>,
+}
+
+impl FileJournal {
+    /// Open a journal in a directory, created if missing
+    ///
+    /// #Parameters
+    /// * 'directory' - The directory holding the journal files
+    pub fn open(directory: impl AsRef<Path>) -> Result<FileJournal, Error> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(FileJournal {
+            directory: directory.as_ref().to_path_buf(),
+            checked: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn path(&self, symbol: Symbol) -> PathBuf {
+        self.directory.join(format!("{symbol}.journal"))
+    }
+
+    fn legacy_path(&self, symbol: Symbol) -> PathBuf {
+        self.directory.join(format!("{symbol}.jsonl"))
+    }
+
+    fn read(&self, symbol: Symbol) -> Result<Vec<OrderbookUpdate>, Error> {
+        match fs::read(self.path(symbol)) {
+            Ok(bytes) => Ok(format::decode_records(&bytes, format::JOURNAL)?.0),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn read_legacy(&self, symbol: Symbol) -> Result<Option<Vec<OrderbookUpdate>>, Error> {
+        let file = match File::open(self.legacy_path(symbol)) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        BufReader::new(file)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<_, Error>>()
+            .map(Some)
+    }
+
+    /// Replace the file of an orderbook with a file holding the updates, through a temporary file
+    fn write(&self, symbol: Symbol, updates: &[OrderbookUpdate]) -> Result<(), Error> {
+        let mut content = format::header(format::JOURNAL);
+        for update in updates {
+            format::encode_record(&mut content, update)?;
+        }
+        let path = self.path(symbol);
+        let temporary = path.with_extension("journal.tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(temporary, path)
+    }
+
+    /// On the first use of an orderbook, rewrite its legacy file in the format, or cut its file
+    /// after the last complete record so that the next appends are readable
+    fn check(&self, checked: &mut HashSet<Symbol>, symbol: Symbol) -> Result<(), Error> {
+        if checked.contains(&symbol) {
+            return Ok(());
+        }
+        let path = self.path(symbol);
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let (_, length) =
+                    format::decode_records::<OrderbookUpdate>(&bytes, format::JOURNAL)?;
+                if length < bytes.len() {
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&path)?
+                        .set_len(length as u64)?;
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                if let Some(updates) = self.read_legacy(symbol)? {
+                    self.write(symbol, &updates)?;
+                    fs::remove_file(self.legacy_path(symbol))?;
+                }
+            }
+            Err(error) => return Err(error),
+        }
+        checked.insert(symbol);
+        Ok(())
+    }
+}
+
+impl Journal for FileJournal {
+    fn append(&self, update: &OrderbookUpdate) -> Result<(), Error> {
+        let mut checked = self.checked.lock().unwrap();
+        self.check(&mut checked, update.symbol)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(update.symbol))?;
+        let mut record = if file.metadata()?.len() == 0 {
+            format::header(format::JOURNAL)
+        } else {
+            Vec::new()
+        };
+        format::encode_record(&mut record, update)?;
+        file.write_all(&record)
+    }
+
+    fn read_after(&self, symbol: Symbol, sequence: u64) -> Result<Vec<OrderbookUpdate>, Error> {
+        let mut checked = self.checked.lock().unwrap();
+        self.check(&mut checked, symbol)?;
+        let mut updates = self.read(symbol)?;
+        updates.retain(|u| u.sequence > sequence);
+        Ok(updates)
+    }
+
+    /// The file is rewritten without the truncated updates then renamed over the old one
+    fn truncate(&self, symbol: Symbol, sequence: u64) -> Result<(), Error> {
+        let mut checked = self.checked.lock().unwrap();
+        self.check(&mut checked, symbol)?;
+        let mut updates = self.read(symbol)?;
+        updates.retain(|u| u.sequence > sequence);
+        self.write(symbol, &updates)
+    }
+
+    /// The files of the orderbooks used since the journal was opened are synced to the disk
+    fn flush(&self) -> Result<(), Error> {
+        let checked = self.checked.lock().unwrap();
+        for symbol in checked.iter() {
+            match OpenOptions::new().append(true).open(self.path(*symbol)) {
+                Ok(file) => file.sync_all()?,
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod format;
+pub mod journal;
+pub mod recovery;
+pub mod snapshot_store;
+pub mod snapshots;
+
+use crate::enums::order_status::OrderStatus;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::structs::ids::{OrderId, Symbol};
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::trade::Trade;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Error;
+
+/// Change of status of an order leaving the book
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub symbol: Symbol,
+    pub order_id: OrderId,
+    pub status: OrderStatus,
+    /// Sequence number of the update which caused the transition
+    pub sequence: u64,
+    /// Time of the transition in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+}
+
+/// Storage of the orders, trades and status transitions, fed by the manager with every
+/// update before it is published to the subscribers.
+///
+/// The calls are made on the thread operating the orderbooks, an implementation talking to a
+/// remote store should queue the records and write them in the background.
+pub trait Persistence: fmt::Debug + Send + Sync {
+    /// Save the current state of an order, called when it is received, rests, or is amended
+    fn persist_order(&self, order: &Order) -> Result<(), Error>;
+
+    /// Save an execution
+    fn persist_trade(&self, trade: &Trade) -> Result<(), Error>;
+
+    /// Save the cancellation, fill or expiry of an order
+    fn persist_status(&self, transition: &StatusTransition) -> Result<(), Error>;
+
+    /// Called when saving an update failed, the update is published anyway
+    fn on_error(&self, _update: &OrderbookUpdate, _error: Error) {}
+
+    /// Wait until the records queued so far are written, called when the engine shuts down
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Save what an update changed
+///
+/// #Parameters
+/// * 'persistence' - The storage
+/// * 'update' - The update published by an orderbook
+///
+/// #Returns
+/// * Result<(), Error> - The error of the storage, Ok for the updates with nothing to save
+pub fn persist_update(
+    persistence: &dyn Persistence,
+    update: &OrderbookUpdate,
+) -> Result<(), Error> {
+    let status = match update.update_type {
+        OrderbookUpdateType::New
+        | OrderbookUpdateType::Place
+        | OrderbookUpdateType::Amended
+        | OrderbookUpdateType::PartiallyFilled
+        | OrderbookUpdateType::Repriced
+        | OrderbookUpdateType::Replace
+        | OrderbookUpdateType::Migrated => {
+            return update
+                .order
+                .as_ref()
+                .map_or(Ok(()), |order| persistence.persist_order(order));
+        }
+        OrderbookUpdateType::NewTrades => {
+            return update
+                .trade
+                .as_ref()
+                .map_or(Ok(()), |trade| persistence.persist_trade(trade));
+        }
+        OrderbookUpdateType::Cancel => OrderStatus::Cancelled,
+        OrderbookUpdateType::Filled => OrderStatus::Filled,
+        OrderbookUpdateType::Expired => OrderStatus::Expired,
+        _ => return Ok(()),
+    };
+    let order_id = update
+        .cancel_id
+        .or(update.filled_id)
+        .or(update.order.map(|o| o.id));
+    match order_id {
+        Some(order_id) => persistence.persist_status(&StatusTransition {
+            symbol: update.symbol,
+            order_id,
+            status,
+            sequence: update.sequence,
+            timestamp: update.timestamp,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::orderbooks_manager::OrderbooksManager;
+    use std::sync::{Arc, Mutex};
+    use ulid::Ulid;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        orders: Mutex<Vec<Order>>,
+        trades: Mutex<Vec<Trade>>,
+        transitions: Mutex<Vec<StatusTransition>>,
+    }
+
+    impl Persistence for Recorder {
+        fn persist_order(&self, order: &Order) -> Result<(), Error> {
+            self.orders.lock().unwrap().push(*order);
+            Ok(())
+        }
+
+        fn persist_trade(&self, trade: &Trade) -> Result<(), Error> {
+            self.trades.lock().unwrap().push(trade.clone());
+            Ok(())
+        }
+
+        fn persist_status(&self, transition: &StatusTransition) -> Result<(), Error> {
+            self.transitions.lock().unwrap().push(*transition);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_manager_persists_the_updates() {
+        let symbol: Symbol = Ulid::new().into();
+        let recorder = Arc::new(Recorder::default());
+        let mut manager = OrderbooksManager::new();
+        manager.set_persistence(recorder.clone());
+        manager.new_orderbook(symbol);
+
+        let order = |side: OrderSide, quantity: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        let bid = order(OrderSide::Buy, 2.0);
+        let resting = order(OrderSide::Buy, 1.0);
+        manager.add_order(bid).unwrap();
+        manager.add_order(resting).unwrap();
+        manager.add_order(order(OrderSide::Sell, 2.0)).unwrap();
+        manager
+            .cancel_order(resting.id, symbol, OrderSide::Buy)
+            .unwrap();
+
+        assert!(recorder
+            .orders
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|o| o.id == bid.id));
+        assert_eq!(recorder.trades.lock().unwrap().len(), 1);
+        let transitions = recorder.transitions.lock().unwrap();
+        let status_of = |id: OrderId| {
+            transitions
+                .iter()
+                .find(|t| t.order_id == id)
+                .map(|t| t.status)
+        };
+        assert_eq!(status_of(bid.id), Some(OrderStatus::Filled));
+        assert_eq!(status_of(resting.id), Some(OrderStatus::Cancelled));
+        assert!(transitions
+            .iter()
+            .all(|t| t.symbol == symbol && t.sequence > 0));
+    }
+}
@@ -0,0 +1,313 @@
+use super::journal::Journal;
+use super::snapshot_store::SnapshotStore;
+use crate::structs::book_snapshot::BookSnapshot;
+use crate::structs::ids::Symbol;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// How an orderbook was recovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub symbol: Symbol,
+    /// Sequence number of the snapshot the book was rebuilt from, None when there was no snapshot
+    #[serde(rename = "snapshotSequence")]
+    pub snapshot_sequence: Option<u64>,
+    /// Number of journal updates replayed on top of the snapshot
+    pub replayed: usize,
+    /// Number of checksums of the journal matched during the replay
+    #[serde(rename = "checksumsVerified")]
+    pub checksums_verified: usize,
+    /// Sequence number of the last update of the book, the next update follows it
+    pub sequence: u64,
+}
+
+/// Rebuild the state of an orderbook from its latest snapshot and the journal updates which followed it.
+/// Each update must follow the previous one, without a gap nor a duplicate, and the book must match the
+/// checksums they carry.
+///
+/// #Parameters
+/// * 'store' - Where the snapshots are saved, if any
+/// * 'journal' - The journal of the updates, if any
+/// * 'symbol' - The symbol of the orderbook
+///
+/// #Returns
+/// * Result<(BookSnapshot, RecoveryReport), Error> - The state of the book and how it was rebuilt,
+///   InvalidData on a gap or a duplicate in the sequence numbers or a checksum mismatch
+pub fn rebuild(
+    store: Option<&dyn SnapshotStore>,
+    journal: Option<&dyn Journal>,
+    symbol: Symbol,
+) -> Result<(BookSnapshot, RecoveryReport), Error> {
+    let snapshot = match store {
+        Some(store) => store.latest(symbol)?,
+        None => None,
+    };
+    let mut report = RecoveryReport {
+        symbol,
+        snapshot_sequence: snapshot.as_ref().map(|s| s.sequence),
+        replayed: 0,
+        checksums_verified: 0,
+        sequence: 0,
+    };
+    let mut book = snapshot.unwrap_or_else(|| BookSnapshot::new(symbol, 0, vec![], vec![]));
+    let updates = match journal {
+        Some(journal) => journal.read_after(symbol, book.sequence)?,
+        None => vec![],
+    };
+    for update in updates.iter() {
+        if update.sequence != book.sequence + 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Journal update {} does not follow sequence {}",
+                    update.sequence, book.sequence
+                ),
+            ));
+        }
+        book.apply(update);
+        report.replayed += 1;
+        if let Some(checksum) = update.checksum {
+            if checksum != book.checksum() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Checksum mismatch at sequence {}", update.sequence),
+                ));
+            }
+            report.checksums_verified += 1;
+        }
+    }
+    report.sequence = book.sequence;
+    Ok((book, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use crate::enums::shutdown_policy::ShutdownPolicy;
+    use crate::enums::side::OrderSide;
+    use crate::persistence::journal::MemoryJournal;
+    use crate::persistence::snapshot_store::MemorySnapshotStore;
+    use crate::persistence::snapshots::SnapshotPolicy;
+    use crate::structs::clock::MockClock;
+    use crate::structs::ids::UserId;
+    use crate::structs::order::Order;
+    use crate::structs::orderbook_update::OrderbookUpdate;
+    use crate::structs::orderbooks_manager::OrderbooksManager;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use ulid::Ulid;
+
+    fn order(symbol: Symbol, side: OrderSide, quantity: f64, price: f64) -> Order {
+        Order::new(
+            Ulid::new().into(),
+            symbol,
+            side,
+            quantity,
+            Some(price),
+            OrderType::Limit,
+        )
+    }
+
+    #[test]
+    fn test_recover_from_the_snapshot_and_the_journal() {
+        let symbol: Symbol = Ulid::new().into();
+        let store = Arc::new(MemorySnapshotStore::new());
+        let journal = Arc::new(MemoryJournal::new());
+        let policy = SnapshotPolicy {
+            interval: Some(Duration::from_secs(1)),
+            every_updates: None,
+        };
+        let clock = MockClock::new(0);
+        let mut manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
+        manager.set_journal(journal.clone());
+        manager.set_snapshot_store(store.clone(), policy);
+        manager.new_orderbook(symbol);
+        for price in 1..=5 {
+            manager
+                .add_order(order(symbol, OrderSide::Buy, 1.0, price as f64))
+                .unwrap();
+            manager
+                .add_order(order(symbol, OrderSide::Sell, 1.0, 10.0 + price as f64))
+                .unwrap();
+        }
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(manager.take_due_snapshots().unwrap(), 1);
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 1.5, 5.0))
+            .unwrap();
+        manager.publish_heartbeats();
+        manager
+            .add_order(order(symbol, OrderSide::Buy, 2.0, 8.0))
+            .unwrap();
+        manager.publish_heartbeats();
+        let before = manager.snapshot(symbol).unwrap();
+        drop(manager);
+
+        let mut recovered = OrderbooksManager::new();
+        recovered.set_journal(journal.clone());
+        recovered.set_snapshot_store(store.clone(), policy);
+        let reports = recovered.recover().unwrap();
+        assert_eq!(reports.len(), 1);
+        let report = reports[0];
+        assert!(report.snapshot_sequence.is_some());
+        assert!(report.replayed > 0);
+        assert!(report.checksums_verified > 0);
+        assert_eq!(report.sequence, before.sequence);
+        assert_eq!(recovered.snapshot(symbol).unwrap(), before);
+
+        // the sequence numbers go on where they stopped
+        let subscription = recovered.subscribe_updates();
+        recovered
+            .add_order(order(symbol, OrderSide::Buy, 1.0, 1.0))
+            .unwrap();
+        let update = subscription.try_recv().unwrap();
+        assert_eq!(update.sequence, before.sequence + 1);
+        assert!(recovered.recover().is_err());
+    }
+
+    #[test]
+    fn test_shutdown_snapshots_the_books() {
+        let symbol: Symbol = Ulid::new().into();
+        let store = Arc::new(MemorySnapshotStore::new());
+        let journal = Arc::new(MemoryJournal::new());
+        let policy = SnapshotPolicy {
+            interval: Some(Duration::from_secs(3600)),
+            every_updates: None,
+        };
+        let mut manager = OrderbooksManager::new();
+        manager.shutdown_policy = ShutdownPolicy::Persist;
+        manager.set_journal(journal.clone());
+        manager.set_snapshot_store(store.clone(), policy);
+        manager.new_orderbook(symbol);
+        manager
+            .add_order(order(symbol, OrderSide::Buy, 1.0, 9.0))
+            .unwrap();
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 1.0, 11.0))
+            .unwrap();
+        assert!(store.latest(symbol).unwrap().is_none());
+        manager.shutdown().unwrap();
+
+        // only the updates closing the book are left in the journal
+        let snapshot = store.latest(symbol).unwrap().unwrap();
+        assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (1, 1));
+        let left = journal.read_after(symbol, 0).unwrap();
+        assert!(!left.is_empty());
+        assert!(left.iter().all(|u| u.sequence > snapshot.sequence));
+    }
+
+    #[test]
+    fn test_gaps_and_checksum_mismatches_fail_the_recovery() {
+        let symbol: Symbol = Ulid::new().into();
+        let update = |sequence, checksum| OrderbookUpdate {
+            symbol,
+            sequence,
+            update_type: OrderbookUpdateType::Heartbeat,
+            checksum,
+            ..Default::default()
+        };
+        let empty = BookSnapshot::new(symbol, 0, vec![], vec![]).checksum();
+
+        let journal = MemoryJournal::new();
+        journal.append(&update(1, Some(empty))).unwrap();
+        let (book, report) = rebuild(None, Some(&journal), symbol).unwrap();
+        assert_eq!((book.sequence, report.checksums_verified), (1, 1));
+        journal.append(&update(3, None)).unwrap();
+        let error = rebuild(None, Some(&journal), symbol).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let journal = MemoryJournal::new();
+        journal.append(&update(1, None)).unwrap();
+        journal.append(&update(1, None)).unwrap();
+        let error = rebuild(None, Some(&journal), symbol).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let journal = MemoryJournal::new();
+        journal.append(&update(1, Some(empty ^ 1))).unwrap();
+        let mut manager = OrderbooksManager::new();
+        manager.set_journal(Arc::new(journal));
+        manager.new_orderbook(symbol);
+        let error = manager.recover().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(manager.snapshot(symbol).unwrap().sequence, 0);
+    }
+
+    #[test]
+    fn test_a_checksum_mismatch_restores_no_book() {
+        let (first, second) = (Symbol(1), Symbol(2));
+        let store = Arc::new(MemorySnapshotStore::new());
+        for symbol in [first, second] {
+            store
+                .save(&BookSnapshot::new(
+                    symbol,
+                    1,
+                    vec![order(symbol, OrderSide::Buy, 1.0, 9.0)],
+                    vec![],
+                ))
+                .unwrap();
+        }
+        let checksum = store.latest(second).unwrap().unwrap().checksum() ^ 1;
+        let journal = MemoryJournal::new();
+        journal
+            .append(&OrderbookUpdate {
+                symbol: second,
+                sequence: 2,
+                update_type: OrderbookUpdateType::Heartbeat,
+                checksum: Some(checksum),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut manager = OrderbooksManager::new();
+        manager.set_journal(Arc::new(journal));
+        manager.set_snapshot_store(store, SnapshotPolicy::default());
+        let error = manager.recover().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(manager.orderbooks.is_empty());
+    }
+
+    #[test]
+    fn test_restored_orders_are_registered() {
+        let symbol = Symbol(1);
+        let (user, base, quote) = (UserId(7), 1, 2);
+        let store = Arc::new(MemorySnapshotStore::new());
+        let resting = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            10.0,
+            Some(5.0),
+            OrderType::Limit,
+        )
+        .with_display_quantity(2.0);
+        let mut manager = OrderbooksManager::new();
+        manager.shutdown_policy = ShutdownPolicy::Persist;
+        manager.set_snapshot_store(store.clone(), SnapshotPolicy::default());
+        manager.new_orderbook(symbol);
+        let accounts = manager.enable_accounts();
+        accounts.register_market(symbol, base, quote);
+        accounts.deposit(user, quote, 100.0);
+        manager.add_order(resting).unwrap();
+        manager.shutdown().unwrap();
+
+        let mut recovered = OrderbooksManager::new();
+        recovered.set_snapshot_store(store, SnapshotPolicy::default());
+        let accounts = recovered.enable_accounts();
+        accounts.register_market(symbol, base, quote);
+        accounts.deposit(user, quote, 100.0);
+        recovered.recover().unwrap();
+        let accounts = recovered.accounts.as_ref().unwrap();
+        assert_eq!(accounts.balance(user, quote).reserved, 50.0);
+        assert_eq!(recovered.risk.open_orders(user), 1);
+        let error = recovered.add_order(resting).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+
+        recovered
+            .cancel_order(resting.id, symbol, OrderSide::Buy)
+            .unwrap();
+        let accounts = recovered.accounts.as_ref().unwrap();
+        assert_eq!(accounts.balance(user, quote).reserved, 0.0);
+        assert_eq!(recovered.risk.open_orders(user), 0);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::persistence::format;
+use crate::structs::book_snapshot::BookSnapshot;
+use crate::structs::ids::Symbol;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Storage of the snapshots of the orderbooks, e.g. files or an object store such as S3.
+/// Only the latest snapshot of each orderbook is needed to recover it.
+pub trait SnapshotStore: fmt::Debug + Send + Sync {
+    /// Save a snapshot, replacing the older snapshots of its orderbook
+    fn save(&self, snapshot: &BookSnapshot) -> Result<(), Error>;
+
+    /// Latest snapshot of an orderbook, None if none was saved
+    fn latest(&self, symbol: Symbol) -> Result<Option<BookSnapshot>, Error>;
+
+    /// Symbols with a snapshot
+    fn symbols(&self) -> Result<Vec<Symbol>, Error>;
+}
+
+/// Snapshots kept in memory, for tests
+#[derive(Debug, Default)]
+pub struct MemorySnapshotStore {
+    snapshots: Mutex<HashMap<Symbol, BookSnapshot>>,
+}
+
+impl MemorySnapshotStore {
+    pub fn new() -> MemorySnapshotStore {
+        MemorySnapshotStore::default()
+    }
+}
+
+impl SnapshotStore for MemorySnapshotStore {
+    fn save(&self, snapshot: &BookSnapshot) -> Result<(), Error> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .insert(snapshot.symbol, snapshot.clone());
+        Ok(())
+    }
+
+    fn latest(&self, symbol: Symbol) -> Result<Option<BookSnapshot>, Error> {
+        Ok(self.snapshots.lock().unwrap().get(&symbol).cloned())
+    }
+
+    fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        Ok(self.snapshots.lock().unwrap().keys().copied().collect())
+    }
+}
+
+/// Snapshots written as a framed record to `<directory>/<symbol>.snapshot`, see the `format` module.
+/// A snapshot is written to a temporary file renamed over the previous one, so that a crash never
+/// leaves a partial snapshot. The JSON snapshots of the older versions, `<symbol>.json`, are still
+/// read until a newer snapshot replaces them.
+#[derive(Debug)]
+pub struct FileSnapshotStore {
+    directory: PathBuf,
+}
+
+impl FileSnapshotStore {
+    /// Open a store in a directory, created if missing
+    ///
+    /// #Parameters
+    /// * 'directory' - The directory holding the snapshot files
+    pub fn open(directory: impl AsRef<Path>) -> Result<FileSnapshotStore, Error> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(FileSnapshotStore {
+            directory: directory.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, symbol: Symbol) -> PathBuf {
+        self.directory.join(format!("{symbol}.snapshot"))
+    }
+
+    fn legacy_path(&self, symbol: Symbol) -> PathBuf {
+        self.directory.join(format!("{symbol}.json"))
+    }
+}
+
+impl SnapshotStore for FileSnapshotStore {
+    fn save(&self, snapshot: &BookSnapshot) -> Result<(), Error> {
+        let path = self.path(snapshot.symbol);
+        let temporary = path.with_extension("snapshot.tmp");
+        let mut content = format::header(format::SNAPSHOT);
+        format::encode_record(&mut content, snapshot)?;
+        fs::write(&temporary, content)?;
+        fs::rename(temporary, path)?;
+        match fs::remove_file(self.legacy_path(snapshot.symbol)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    fn latest(&self, symbol: Symbol) -> Result<Option<BookSnapshot>, Error> {
+        match fs::read(self.path(symbol)) {
+            Ok(bytes) => {
+                let (records, _) = format::decode_records(&bytes, format::SNAPSHOT)?;
+                return records.into_iter().next().map(Some).ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, "Snapshot file without snapshot")
+                });
+            }
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+            Err(_) => {}
+        }
+        match fs::read(self.legacy_path(symbol)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        let mut symbols = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "snapshot" || extension == "json")
+            {
+                let symbol = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u128>().ok())
+                    .map(Symbol);
+                symbols.extend(symbol.filter(|symbol| !symbols.contains(symbol)));
+            }
+        }
+        Ok(symbols)
+    }
+}
@@ -0,0 +1,383 @@
+use super::journal::Journal;
+use super::snapshot_store::SnapshotStore;
+use crate::structs::book_snapshot::BookSnapshot;
+use crate::structs::ids::Symbol;
+#[cfg(feature = "native")]
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// When an orderbook which changed is snapshotted, whichever comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SnapshotPolicy {
+    /// Time since the last snapshot of the orderbook, None to snapshot on the updates only
+    pub interval: Option<Duration>,
+    /// Updates published since the last snapshot of the orderbook, None to snapshot on time only
+    #[serde(rename = "everyUpdates")]
+    pub every_updates: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct BookProgress {
+    /// Updates since the last snapshot
+    updates: u64,
+    /// Monotonic time of the last snapshot, in nanoseconds
+    snapshotted_at: u64,
+}
+
+#[derive(Debug, Default)]
+struct ServiceState {
+    books: HashMap<Symbol, BookProgress>,
+    saved: u64,
+    failures: u64,
+    last_error: Option<String>,
+}
+
+/// Takes the snapshots of the orderbooks as they change, following a policy, and stores them.
+/// Once a snapshot is stored the journal is truncated up to its sequence number, so that a book
+/// is recovered from its latest snapshot and the updates which followed it.
+/// Clones share the same store and progress.
+#[derive(Debug, Clone)]
+pub struct SnapshotService {
+    store: Arc<dyn SnapshotStore>,
+    policy: SnapshotPolicy,
+    state: Arc<Mutex<ServiceState>>,
+}
+
+impl SnapshotService {
+    /// Create a service
+    ///
+    /// #Parameters
+    /// * 'store' - Where the snapshots are saved
+    /// * 'policy' - When the orderbooks are snapshotted
+    pub fn new(store: Arc<dyn SnapshotStore>, policy: SnapshotPolicy) -> SnapshotService {
+        SnapshotService {
+            store,
+            policy,
+            state: Arc::new(Mutex::new(ServiceState::default())),
+        }
+    }
+
+    pub fn store(&self) -> &Arc<dyn SnapshotStore> {
+        &self.store
+    }
+
+    pub fn policy(&self) -> SnapshotPolicy {
+        self.policy
+    }
+
+    /// Count an update published by an orderbook
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol of the orderbook
+    /// * 'now' - The monotonic time in nanoseconds
+    ///
+    /// #Returns
+    /// * bool - Whether the orderbook is due for a snapshot
+    pub fn on_update(&self, symbol: Symbol, now: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let progress = state.books.entry(symbol).or_insert(BookProgress {
+            updates: 0,
+            snapshotted_at: now,
+        });
+        progress.updates += 1;
+        let progress = *progress;
+        self.is_due(progress, now)
+    }
+
+    /// Orderbooks which changed and are due for a snapshot, e.g. books which went quiet before
+    /// reaching the number of updates of the policy
+    ///
+    /// #Parameters
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn due(&self, now: u64) -> Vec<Symbol> {
+        let state = self.state.lock().unwrap();
+        state
+            .books
+            .iter()
+            .filter(|(_, &progress)| self.is_due(progress, now))
+            .map(|(&symbol, _)| symbol)
+            .collect()
+    }
+
+    fn is_due(&self, progress: BookProgress, now: u64) -> bool {
+        progress.updates > 0
+            && (self
+                .policy
+                .every_updates
+                .is_some_and(|updates| progress.updates >= updates)
+                || self.policy.interval.is_some_and(|interval| {
+                    now.saturating_sub(progress.snapshotted_at) >= interval.as_nanos() as u64
+                }))
+    }
+
+    /// Store a snapshot then truncate the journal up to its sequence number
+    ///
+    /// #Parameters
+    /// * 'snapshot' - The snapshot of an orderbook
+    /// * 'journal' - The journal of the updates, if any
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn save(
+        &self,
+        snapshot: &BookSnapshot,
+        journal: Option<&dyn Journal>,
+        now: u64,
+    ) -> Result<(), Error> {
+        let result = self.store.save(snapshot).and_then(|_| {
+            journal.map_or(Ok(()), |j| j.truncate(snapshot.symbol, snapshot.sequence))
+        });
+        let mut state = self.state.lock().unwrap();
+        match &result {
+            Ok(()) => {
+                state.saved += 1;
+                state.books.insert(
+                    snapshot.symbol,
+                    BookProgress {
+                        updates: 0,
+                        snapshotted_at: now,
+                    },
+                );
+            }
+            Err(error) => {
+                state.failures += 1;
+                state.last_error = Some(error.to_string());
+            }
+        }
+        result
+    }
+
+    /// Number of snapshots stored
+    pub fn saved(&self) -> u64 {
+        self.state.lock().unwrap().saved
+    }
+
+    /// Number of snapshots which could not be stored, and the error of the last one
+    pub fn failures(&self) -> (u64, Option<String>) {
+        let state = self.state.lock().unwrap();
+        (state.failures, state.last_error.clone())
+    }
+}
+
+/// Snapshot the due orderbooks of a manager in a background thread, every period, so that the
+/// books which went quiet are snapshotted on time too. The thread stops once the manager is dropped.
+///
+/// #Parameters
+/// * 'manager' - The manager, with a snapshot store
+/// * 'period' - Time between two checks, typically a fraction of the interval of the policy
+#[cfg(feature = "native")]
+pub fn spawn_snapshot_thread(
+    manager: &Arc<Mutex<OrderbooksManager>>,
+    period: Duration,
+) -> std::thread::JoinHandle<()> {
+    let manager = Arc::downgrade(manager);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(period);
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        let Ok(manager) = manager.lock() else {
+            return;
+        };
+        // failures are recorded by the service and retried on the next period
+        let _ = manager.take_due_snapshots();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::persistence::journal::{FileJournal, MemoryJournal};
+    use crate::persistence::snapshot_store::{FileSnapshotStore, MemorySnapshotStore};
+    use crate::structs::clock::MockClock;
+    use crate::structs::order::Order;
+    use crate::structs::orderbooks_manager::OrderbooksManager;
+    use ulid::Ulid;
+
+    fn order(symbol: Symbol, side: OrderSide, price: f64) -> Order {
+        Order::new(
+            Ulid::new().into(),
+            symbol,
+            side,
+            1.0,
+            Some(price),
+            OrderType::Limit,
+        )
+    }
+
+    /// Rebuild a book from its latest snapshot and the tail of the journal
+    fn recover(store: &dyn SnapshotStore, journal: &dyn Journal, symbol: Symbol) -> BookSnapshot {
+        let mut book = store.latest(symbol).unwrap().unwrap();
+        for update in journal.read_after(symbol, book.sequence).unwrap() {
+            book.apply(&update);
+            book.sequence = update.sequence;
+        }
+        book
+    }
+
+    #[test]
+    fn test_snapshot_every_updates_truncates_the_journal() {
+        let symbol: Symbol = Ulid::new().into();
+        let store = Arc::new(MemorySnapshotStore::new());
+        let journal = Arc::new(MemoryJournal::new());
+        let mut manager = OrderbooksManager::new();
+        manager.set_journal(journal.clone());
+        manager.set_snapshot_store(
+            store.clone(),
+            SnapshotPolicy {
+                interval: None,
+                every_updates: Some(3),
+            },
+        );
+        manager.new_orderbook(symbol);
+        for price in 1..=7 {
+            manager
+                .add_order(order(symbol, OrderSide::Buy, price as f64))
+                .unwrap();
+        }
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 7.0))
+            .unwrap();
+
+        let snapshots = manager.snapshots.as_ref().unwrap();
+        assert!(snapshots.saved() >= 2);
+        assert_eq!(snapshots.failures(), (0, None));
+        let snapshot = store.latest(symbol).unwrap().unwrap();
+        assert!(snapshot.sequence > 0);
+        // the updates published with the snapshot sequence are left, the others are truncated
+        assert!(journal.len(symbol) < 3);
+        assert_eq!(
+            recover(store.as_ref(), journal.as_ref(), symbol),
+            manager.snapshot(symbol).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_quiet_books_are_snapshotted_on_interval() {
+        let clock = MockClock::new(0);
+        let (quiet, idle): (Symbol, Symbol) = (Ulid::new().into(), Ulid::new().into());
+        let store = Arc::new(MemorySnapshotStore::new());
+        let mut manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
+        manager.set_snapshot_store(
+            store.clone(),
+            SnapshotPolicy {
+                interval: Some(Duration::from_secs(1)),
+                every_updates: Some(1_000),
+            },
+        );
+        manager.new_orderbook(quiet);
+        manager.new_orderbook(idle);
+        manager
+            .add_order(order(quiet, OrderSide::Buy, 10.0))
+            .unwrap();
+
+        assert_eq!(manager.take_due_snapshots().unwrap(), 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(manager.take_due_snapshots().unwrap(), 1);
+        assert_eq!(
+            store.latest(quiet).unwrap(),
+            Some(manager.snapshot(quiet).unwrap())
+        );
+        assert_eq!(store.latest(idle).unwrap(), None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(manager.take_due_snapshots().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_recovery_from_the_files() {
+        let directory = std::env::temp_dir().join(format!("orderbook-{}", Ulid::new()));
+        let symbol: Symbol = Ulid::new().into();
+        let mut manager = OrderbooksManager::new();
+        manager.set_journal(Arc::new(
+            FileJournal::open(directory.join("journal")).unwrap(),
+        ));
+        manager.set_snapshot_store(
+            Arc::new(FileSnapshotStore::open(directory.join("snapshots")).unwrap()),
+            SnapshotPolicy {
+                interval: None,
+                every_updates: Some(2),
+            },
+        );
+        manager.new_orderbook(symbol);
+        for price in 1..=5 {
+            manager
+                .add_order(order(symbol, OrderSide::Sell, price as f64))
+                .unwrap();
+        }
+
+        let store = FileSnapshotStore::open(directory.join("snapshots")).unwrap();
+        let journal = FileJournal::open(directory.join("journal")).unwrap();
+        assert_eq!(store.symbols().unwrap(), vec![symbol]);
+        assert_eq!(
+            recover(&store, &journal, symbol),
+            manager.snapshot(symbol).unwrap()
+        );
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_files_of_older_versions_are_read() {
+        let directory = std::env::temp_dir().join(format!("orderbook-{}", Ulid::new()));
+        let symbol: Symbol = Ulid::new().into();
+        let journal = Arc::new(MemoryJournal::new());
+        let mut manager = OrderbooksManager::new();
+        manager.set_journal(journal.clone());
+        manager.new_orderbook(symbol);
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 1.0))
+            .unwrap();
+        let snapshot = manager.snapshot(symbol).unwrap();
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 2.0))
+            .unwrap();
+        let updates = journal.read_after(symbol, snapshot.sequence).unwrap();
+
+        // the JSON files written before the framed format
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join(format!("{symbol}.json")),
+            serde_json::to_vec(&snapshot).unwrap(),
+        )
+        .unwrap();
+        let lines: Vec<String> = updates
+            .iter()
+            .map(|update| serde_json::to_string(update).unwrap() + "\n")
+            .collect();
+        std::fs::write(directory.join(format!("{symbol}.jsonl")), lines.concat()).unwrap();
+
+        let store = FileSnapshotStore::open(&directory).unwrap();
+        let files = FileJournal::open(&directory).unwrap();
+        assert_eq!(store.symbols().unwrap(), vec![symbol]);
+        assert_eq!(
+            recover(&store, &files, symbol),
+            manager.snapshot(symbol).unwrap()
+        );
+        // the journal is migrated, a record cut short by a crash is dropped before appending
+        assert!(!directory.join(format!("{symbol}.jsonl")).exists());
+        let path = directory.join(format!("{symbol}.journal"));
+        let length = std::fs::metadata(&path).unwrap().len();
+        let mut torn = std::fs::read(&path).unwrap();
+        torn.extend_from_slice(&[9, 0, 0]);
+        std::fs::write(&path, torn).unwrap();
+        let files = FileJournal::open(&directory).unwrap();
+        manager.set_journal(Arc::new(FileJournal::open(&directory).unwrap()));
+        manager
+            .add_order(order(symbol, OrderSide::Sell, 3.0))
+            .unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > length);
+        assert_eq!(
+            recover(&store, &files, symbol),
+            manager.snapshot(symbol).unwrap()
+        );
+
+        // a snapshot saved in the format replaces the older file
+        store.save(&manager.snapshot(symbol).unwrap()).unwrap();
+        assert!(!directory.join(format!("{symbol}.json")).exists());
+        assert_eq!(store.symbols().unwrap(), vec![symbol]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
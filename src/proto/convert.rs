@@ -0,0 +1,540 @@
+use super as pb;
+use crate::enums::band_action::BandAction;
+use crate::enums::level_action::LevelAction;
+use crate::enums::liquidity::Liquidity;
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_state::OrderbookState;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::payment_status::PaymentStatus;
+use crate::enums::peg_reference::PegReference;
+use crate::enums::ratio_action::RatioAction;
+use crate::enums::side::OrderSide;
+use crate::enums::time_in_force::TimeInForce;
+use crate::enums::trade_status::TradeStatus;
+use crate::risk::message_ratio::{ComplianceEvent, MessageRatio};
+use crate::structs::auction::AuctionResult;
+use crate::structs::book_snapshot::BookSnapshot;
+use crate::structs::level_book::LevelDelta;
+use crate::structs::order::{Metadata, Order};
+use crate::structs::orderbook_update::OrderbookUpdate;
+use crate::structs::price_band::CircuitBreakerEvent;
+use crate::structs::trade::Trade;
+use std::io::{Error, ErrorKind};
+
+/// Map the variants of a native enum to the protobuf enum of the same variant names, both ways
+macro_rules! enum_conversions {
+    ($native:ident, $proto:ident, [$($variant:ident),+]) => {
+        impl From<$native> for pb::$proto {
+            fn from(value: $native) -> pb::$proto {
+                match value {
+                    $($native::$variant => pb::$proto::$variant,)+
+                }
+            }
+        }
+
+        impl From<pb::$proto> for $native {
+            fn from(value: pb::$proto) -> $native {
+                match value {
+                    $(pb::$proto::$variant => $native::$variant,)+
+                }
+            }
+        }
+    };
+}
+
+enum_conversions!(OrderSide, OrderSide, [Buy, Sell]);
+enum_conversions!(OrderType, OrderType, [Limit, Market]);
+enum_conversions!(
+    OrderStatus,
+    OrderStatus,
+    [
+        Open,
+        Closed,
+        Cancelled,
+        Pending,
+        PartiallyFilled,
+        Filled,
+        Expired
+    ]
+);
+enum_conversions!(
+    PaymentStatus,
+    PaymentStatus,
+    [Pending, Paid, Failed, Cancelled, Refunded, Unknown]
+);
+enum_conversions!(TradeStatus, TradeStatus, [Swapped, Pending, Failed]);
+enum_conversions!(Liquidity, Liquidity, [Maker, Taker]);
+enum_conversions!(PegReference, PegReference, [Mid, BestBid, BestAsk]);
+enum_conversions!(TimeInForce, TimeInForce, [GoodTillCancel, Day]);
+enum_conversions!(
+    OrderbookUpdateType,
+    OrderbookUpdateType,
+    [
+        New,
+        Place,
+        Cancel,
+        Amended,
+        NewTrades,
+        Filled,
+        Expired,
+        Replace,
+        AuctionResult,
+        StateChange,
+        CircuitBreaker,
+        Delisted,
+        PartiallyFilled,
+        Repriced,
+        IndicativeAuction,
+        Compliance,
+        Shutdown,
+        Heartbeat,
+        Migrated,
+        ReferencePrice
+    ]
+);
+enum_conversions!(
+    OrderbookState,
+    OrderbookState,
+    [Continuous, Halted, AuctionCall, CancelOnly, Closed, Mirror]
+);
+enum_conversions!(BandAction, BandAction, [Reject, Halt]);
+enum_conversions!(RatioAction, RatioAction, [Warn, Throttle]);
+enum_conversions!(LevelAction, LevelAction, [Add, Change, Delete]);
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn parse_id<T: From<u128>>(value: &str) -> Result<T, Error> {
+    value
+        .parse::<u128>()
+        .map(T::from)
+        .map_err(|_| invalid("Invalid ID"))
+}
+
+fn parse_enum<P, N>(value: i32) -> Result<N, Error>
+where
+    P: TryFrom<i32>,
+    N: From<P>,
+{
+    P::try_from(value)
+        .map(N::from)
+        .map_err(|_| invalid("Unknown enum value"))
+}
+
+impl From<&Order> for pb::Order {
+    fn from(order: &Order) -> pb::Order {
+        pb::Order {
+            id: order.id.to_string(),
+            user_id: order.user_id.to_string(),
+            symbol: order.symbol.to_string(),
+            side: pb::OrderSide::from(order.side) as i32,
+            quantity: order.quantity,
+            non_mut_quantity: order.non_mut_quantity,
+            price: order.price,
+            order_type: pb::OrderType::from(order.order_type) as i32,
+            status: pb::OrderStatus::from(order.status) as i32,
+            payment_status: pb::PaymentStatus::from(order.payment_status) as i32,
+            created_at: order.created_at,
+            updated_at: order.updated_at,
+            expires_at: order.expires_at,
+            display_quantity: order.display_quantity,
+            hidden_quantity: order.hidden_quantity,
+            client_order_id: order.client_order_id.map(|id| id.to_string()),
+            min_fill_quantity: order.min_fill_quantity,
+            peg_reference: order
+                .peg_reference
+                .map(|reference| pb::PegReference::from(reference) as i32),
+            peg_offset: order.peg_offset,
+            arrival_seq: order.arrival_seq,
+            time_in_force: pb::TimeInForce::from(order.time_in_force) as i32,
+        }
+    }
+}
+
+impl TryFrom<pb::Order> for Order {
+    type Error = Error;
+
+    fn try_from(order: pb::Order) -> Result<Order, Error> {
+        Ok(Order {
+            id: parse_id(&order.id)?,
+            user_id: parse_id(&order.user_id)?,
+            symbol: parse_id(&order.symbol)?,
+            side: parse_enum::<pb::OrderSide, _>(order.side)?,
+            quantity: order.quantity,
+            non_mut_quantity: order.non_mut_quantity,
+            price: order.price,
+            order_type: parse_enum::<pb::OrderType, _>(order.order_type)?,
+            status: parse_enum::<pb::OrderStatus, _>(order.status)?,
+            payment_status: parse_enum::<pb::PaymentStatus, _>(order.payment_status)?,
+            created_at: order.created_at,
+            updated_at: order.updated_at,
+            expires_at: order.expires_at,
+            display_quantity: order.display_quantity,
+            hidden_quantity: order.hidden_quantity,
+            client_order_id: order.client_order_id.as_deref().map(parse_id).transpose()?,
+            min_fill_quantity: order.min_fill_quantity,
+            peg_reference: order
+                .peg_reference
+                .map(parse_enum::<pb::PegReference, _>)
+                .transpose()?,
+            peg_offset: order.peg_offset,
+            arrival_seq: order.arrival_seq,
+            time_in_force: parse_enum::<pb::TimeInForce, _>(order.time_in_force)?,
+        })
+    }
+}
+
+impl From<&Trade> for pb::Trade {
+    fn from(trade: &Trade) -> pb::Trade {
+        pb::Trade {
+            id: trade.id.map(|id| id.to_string()),
+            buy_order_id: trade.buy_order_id.to_string(),
+            sell_order_id: trade.sell_order_id.to_string(),
+            buy_user_id: trade.buy_user_id.to_string(),
+            sell_user_id: trade.sell_user_id.to_string(),
+            taker_side: pb::OrderSide::from(trade.taker_side) as i32,
+            maker_order_id: trade.maker_order_id.to_string(),
+            maker_fee: trade.maker_fee,
+            taker_fee: trade.taker_fee,
+            price: trade.price,
+            quantity: trade.quantity,
+            status: pb::TradeStatus::from(trade.status) as i32,
+            symbol: trade.symbol.to_string(),
+            created_at: trade.created_at,
+            updated_at: trade.updated_at,
+            buy_remaining: trade.buy_remaining,
+            sell_remaining: trade.sell_remaining,
+            buy_liquidity: pb::Liquidity::from(trade.buy_liquidity) as i32,
+            sell_liquidity: pb::Liquidity::from(trade.sell_liquidity) as i32,
+            buy_metadata: trade.buy_metadata.as_ref().map(pb::Metadata::from),
+            sell_metadata: trade.sell_metadata.as_ref().map(pb::Metadata::from),
+        }
+    }
+}
+
+impl TryFrom<pb::Trade> for Trade {
+    type Error = Error;
+
+    fn try_from(trade: pb::Trade) -> Result<Trade, Error> {
+        Ok(Trade {
+            id: trade.id.as_deref().map(parse_id).transpose()?,
+            buy_order_id: parse_id(&trade.buy_order_id)?,
+            sell_order_id: parse_id(&trade.sell_order_id)?,
+            buy_user_id: parse_id(&trade.buy_user_id)?,
+            sell_user_id: parse_id(&trade.sell_user_id)?,
+            taker_side: parse_enum::<pb::OrderSide, _>(trade.taker_side)?,
+            maker_order_id: parse_id(&trade.maker_order_id)?,
+            maker_fee: trade.maker_fee,
+            taker_fee: trade.taker_fee,
+            price: trade.price,
+            quantity: trade.quantity,
+            status: parse_enum::<pb::TradeStatus, _>(trade.status)?,
+            symbol: parse_id(&trade.symbol)?,
+            created_at: trade.created_at,
+            updated_at: trade.updated_at,
+            buy_remaining: trade.buy_remaining,
+            sell_remaining: trade.sell_remaining,
+            buy_liquidity: parse_enum::<pb::Liquidity, _>(trade.buy_liquidity)?,
+            sell_liquidity: parse_enum::<pb::Liquidity, _>(trade.sell_liquidity)?,
+            buy_metadata: trade.buy_metadata.map(Metadata::from),
+            sell_metadata: trade.sell_metadata.map(Metadata::from),
+        })
+    }
+}
+
+impl From<&Metadata> for pb::Metadata {
+    fn from(metadata: &Metadata) -> pb::Metadata {
+        pb::Metadata {
+            entries: metadata.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl From<pb::Metadata> for Metadata {
+    fn from(metadata: pb::Metadata) -> Metadata {
+        metadata.entries.into_iter().collect()
+    }
+}
+
+impl From<&AuctionResult> for pb::AuctionResult {
+    fn from(auction: &AuctionResult) -> pb::AuctionResult {
+        pb::AuctionResult {
+            price: auction.price,
+            volume: auction.volume,
+            imbalance: auction.imbalance,
+        }
+    }
+}
+
+impl From<pb::AuctionResult> for AuctionResult {
+    fn from(auction: pb::AuctionResult) -> AuctionResult {
+        AuctionResult {
+            price: auction.price,
+            volume: auction.volume,
+            imbalance: auction.imbalance,
+        }
+    }
+}
+
+impl From<&CircuitBreakerEvent> for pb::CircuitBreakerEvent {
+    fn from(event: &CircuitBreakerEvent) -> pb::CircuitBreakerEvent {
+        pb::CircuitBreakerEvent {
+            price: event.price,
+            reference_price: event.reference_price,
+            action: pb::BandAction::from(event.action) as i32,
+        }
+    }
+}
+
+impl TryFrom<pb::CircuitBreakerEvent> for CircuitBreakerEvent {
+    type Error = Error;
+
+    fn try_from(event: pb::CircuitBreakerEvent) -> Result<CircuitBreakerEvent, Error> {
+        Ok(CircuitBreakerEvent {
+            price: event.price,
+            reference_price: event.reference_price,
+            action: parse_enum::<pb::BandAction, _>(event.action)?,
+        })
+    }
+}
+
+impl From<&ComplianceEvent> for pb::ComplianceEvent {
+    fn from(event: &ComplianceEvent) -> pb::ComplianceEvent {
+        pb::ComplianceEvent {
+            user_id: event.user_id.to_string(),
+            messages: event.ratio.messages as u64,
+            trades: event.ratio.trades as u64,
+            ratio: event.ratio.ratio,
+            threshold: event.threshold,
+            action: pb::RatioAction::from(event.action) as i32,
+            breached: event.breached,
+        }
+    }
+}
+
+impl TryFrom<pb::ComplianceEvent> for ComplianceEvent {
+    type Error = Error;
+
+    fn try_from(event: pb::ComplianceEvent) -> Result<ComplianceEvent, Error> {
+        Ok(ComplianceEvent {
+            user_id: parse_id(&event.user_id)?,
+            ratio: MessageRatio {
+                messages: event.messages as usize,
+                trades: event.trades as usize,
+                ratio: event.ratio,
+            },
+            threshold: event.threshold,
+            action: parse_enum::<pb::RatioAction, _>(event.action)?,
+            breached: event.breached,
+        })
+    }
+}
+
+impl From<&LevelDelta> for pb::LevelDelta {
+    fn from(delta: &LevelDelta) -> pb::LevelDelta {
+        pb::LevelDelta {
+            side: pb::OrderSide::from(delta.side) as i32,
+            action: pb::LevelAction::from(delta.action) as i32,
+            price: delta.price,
+            quantity: delta.quantity,
+            orders: delta.orders as u64,
+        }
+    }
+}
+
+impl TryFrom<pb::LevelDelta> for LevelDelta {
+    type Error = Error;
+
+    fn try_from(delta: pb::LevelDelta) -> Result<LevelDelta, Error> {
+        Ok(LevelDelta {
+            side: parse_enum::<pb::OrderSide, _>(delta.side)?,
+            action: parse_enum::<pb::LevelAction, _>(delta.action)?,
+            price: delta.price,
+            quantity: delta.quantity,
+            orders: delta.orders as usize,
+        })
+    }
+}
+
+impl From<&OrderbookUpdate> for pb::OrderbookUpdate {
+    fn from(update: &OrderbookUpdate) -> pb::OrderbookUpdate {
+        pb::OrderbookUpdate {
+            symbol: update.symbol.to_string(),
+            update_type: pb::OrderbookUpdateType::from(update.update_type) as i32,
+            order: update.order.as_ref().map(pb::Order::from),
+            trade: update.trade.as_ref().map(pb::Trade::from),
+            cancel_id: update.cancel_id.map(|id| id.to_string()),
+            filled_id: update.filled_id.map(|id| id.to_string()),
+            sequence: update.sequence,
+            auction: update.auction.as_ref().map(pb::AuctionResult::from),
+            state: update
+                .state
+                .map(|state| pb::OrderbookState::from(state) as i32),
+            circuit_breaker: update
+                .circuit_breaker
+                .as_ref()
+                .map(pb::CircuitBreakerEvent::from),
+            timestamp: update.timestamp,
+            fill_quantity: update.fill_quantity,
+            compliance: update.compliance.as_ref().map(pb::ComplianceEvent::from),
+            checksum: update.checksum,
+            metadata: update.metadata.as_ref().map(pb::Metadata::from),
+            levels: update.levels.iter().map(pb::LevelDelta::from).collect(),
+            previous_status: update
+                .previous_status
+                .map(|status| pb::OrderStatus::from(status) as i32),
+            reference_price: update.reference_price,
+        }
+    }
+}
+
+impl TryFrom<pb::OrderbookUpdate> for OrderbookUpdate {
+    type Error = Error;
+
+    fn try_from(update: pb::OrderbookUpdate) -> Result<OrderbookUpdate, Error> {
+        Ok(OrderbookUpdate {
+            symbol: parse_id(&update.symbol)?,
+            update_type: parse_enum::<pb::OrderbookUpdateType, _>(update.update_type)?,
+            order: update.order.map(Order::try_from).transpose()?,
+            trade: update.trade.map(Trade::try_from).transpose()?,
+            cancel_id: update.cancel_id.as_deref().map(parse_id).transpose()?,
+            filled_id: update.filled_id.as_deref().map(parse_id).transpose()?,
+            sequence: update.sequence,
+            auction: update.auction.map(AuctionResult::from),
+            state: update
+                .state
+                .map(parse_enum::<pb::OrderbookState, _>)
+                .transpose()?,
+            circuit_breaker: update
+                .circuit_breaker
+                .map(CircuitBreakerEvent::try_from)
+                .transpose()?,
+            timestamp: update.timestamp,
+            fill_quantity: update.fill_quantity,
+            compliance: update
+                .compliance
+                .map(ComplianceEvent::try_from)
+                .transpose()?,
+            checksum: update.checksum,
+            metadata: update.metadata.map(Metadata::from),
+            levels: update
+                .levels
+                .into_iter()
+                .map(LevelDelta::try_from)
+                .collect::<Result<Vec<LevelDelta>, Error>>()?,
+            previous_status: update
+                .previous_status
+                .map(parse_enum::<pb::OrderStatus, _>)
+                .transpose()?,
+            reference_price: update.reference_price,
+        })
+    }
+}
+
+impl From<&BookSnapshot> for pb::BookSnapshot {
+    fn from(snapshot: &BookSnapshot) -> pb::BookSnapshot {
+        pb::BookSnapshot {
+            symbol: snapshot.symbol.to_string(),
+            sequence: snapshot.sequence,
+            bids: snapshot.bids.iter().map(pb::Order::from).collect(),
+            asks: snapshot.asks.iter().map(pb::Order::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::BookSnapshot> for BookSnapshot {
+    type Error = Error;
+
+    fn try_from(snapshot: pb::BookSnapshot) -> Result<BookSnapshot, Error> {
+        let orders = |orders: Vec<pb::Order>| {
+            orders
+                .into_iter()
+                .map(Order::try_from)
+                .collect::<Result<Vec<Order>, Error>>()
+        };
+        Ok(BookSnapshot::new(
+            parse_id(&snapshot.symbol)?,
+            snapshot.sequence,
+            orders(snapshot.bids)?,
+            orders(snapshot.asks)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ids::{Symbol, UserId};
+    use prost::Message;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_update_round_trip() {
+        let symbol: Symbol = Ulid::new().into();
+        let mut order = Order::get_test_order(symbol, Ulid::new().into());
+        order.client_order_id = Some(u128::MAX);
+        let update = OrderbookUpdate {
+            symbol,
+            update_type: OrderbookUpdateType::StateChange,
+            order: Some(order),
+            trade: None,
+            cancel_id: None,
+            filled_id: Some(order.id),
+            sequence: 7,
+            auction: None,
+            state: Some(OrderbookState::Halted),
+            circuit_breaker: Some(CircuitBreakerEvent {
+                price: 11.0,
+                reference_price: 10.0,
+                action: BandAction::Halt,
+            }),
+            timestamp: 42,
+            fill_quantity: Some(1.5),
+            compliance: Some(ComplianceEvent {
+                user_id: order.user_id,
+                ratio: MessageRatio {
+                    messages: 12,
+                    trades: 2,
+                    ratio: 6.0,
+                },
+                threshold: 5.0,
+                action: RatioAction::Throttle,
+                breached: true,
+            }),
+            checksum: Some(u32::MAX),
+            metadata: Some(Metadata::from([("algo".to_string(), "twap".to_string())])),
+            levels: vec![LevelDelta {
+                side: OrderSide::Sell,
+                action: LevelAction::Delete,
+                price: 11.0,
+                quantity: 0.0,
+                orders: 0,
+            }],
+            previous_status: Some(OrderStatus::PartiallyFilled),
+            reference_price: Some(10.5),
+        };
+
+        let bytes = pb::OrderbookUpdate::from(&update).encode_to_vec();
+        let decoded = pb::OrderbookUpdate::decode(bytes.as_slice()).unwrap();
+        assert_eq!(OrderbookUpdate::try_from(decoded).unwrap(), update);
+
+        let snapshot = BookSnapshot::new(symbol, 7, vec![order], vec![]);
+        let decoded = BookSnapshot::try_from(pb::BookSnapshot::from(&snapshot)).unwrap();
+        assert_eq!(decoded, snapshot);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut order = pb::Order::from(&Order::get_test_order(Symbol(1), UserId(2)));
+        order.side = 5;
+        assert_eq!(
+            Order::try_from(order.clone()).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        order.side = 0;
+        order.id = "not an id".to_string();
+        assert!(Order::try_from(order).is_err());
+    }
+}
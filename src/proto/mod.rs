@@ -0,0 +1,9 @@
+//! Protobuf types of the event stream, generated by prost from `proto/orderbook.proto`.
+//!
+//! IDs and symbols are carried as decimal strings since protobuf has no 128-bit integer.
+//! The native types convert into the protobuf ones with `From` and back with `TryFrom`,
+//! which fails with an `InvalidData` error on a malformed ID or an unknown enum value.
+
+mod convert;
+
+include!(concat!(env!("OUT_DIR"), "/orderbook.rs"));
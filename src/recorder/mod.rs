@@ -0,0 +1,465 @@
+use crate::structs::ids::Symbol;
+use crate::structs::orderbook_sum::PriceSizeLevels;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often and how much of the orderbooks the depth recorder captures
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecorderPolicy {
+    /// Time between two samples of an orderbook
+    pub interval: Duration,
+    /// Number of levels captured per side, the first one being the best bid or offer
+    pub depth: usize,
+    /// Number of samples kept in memory per orderbook, the oldest are dropped first
+    pub capacity: usize,
+    /// Directory the samples are appended to, one JSON lines file per orderbook, None to keep them in memory only
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+}
+
+impl RecorderPolicy {
+    /// Sample the best levels of the orderbooks every interval, in memory
+    ///
+    /// #Parameters
+    /// * 'interval' - The time between two samples of an orderbook
+    /// * 'depth' - The number of levels captured per side, at least one
+    /// * 'capacity' - The number of samples kept per orderbook
+    pub fn new(interval: Duration, depth: usize, capacity: usize) -> RecorderPolicy {
+        RecorderPolicy {
+            interval,
+            depth,
+            capacity,
+            directory: None,
+        }
+    }
+
+    /// Append the samples to files in a directory as they are flushed
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> RecorderPolicy {
+        self.directory = Some(directory.into());
+        self
+    }
+}
+
+/// Depth of an orderbook at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthSample {
+    /// Time of the sample in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+    /// Sequence number of the last update of the orderbook
+    pub sequence: u64,
+    /// Best bid levels as [price, size] pairs, best price first
+    pub bids: PriceSizeLevels,
+    /// Best ask levels as [price, size] pairs, best price first
+    pub asks: PriceSizeLevels,
+}
+
+/// Best bid and offer of an orderbook at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BboSample {
+    /// Time of the sample in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+    /// Best bid as a [price, size] pair, None for an empty side
+    pub bid: Option<[f64; 2]>,
+    /// Best ask as a [price, size] pair, None for an empty side
+    pub ask: Option<[f64; 2]>,
+}
+
+/// Samples of an orderbook stored by column, the levels of a sample being `depth` consecutive entries
+/// of the price and quantity columns of its side, padded with NaN prices
+#[derive(Debug, Default)]
+struct DepthSeries {
+    timestamps: VecDeque<u64>,
+    sequences: VecDeque<u64>,
+    bid_prices: VecDeque<f64>,
+    bid_quantities: VecDeque<f64>,
+    ask_prices: VecDeque<f64>,
+    ask_quantities: VecDeque<f64>,
+    /// Monotonic time of the last sample, in nanoseconds
+    sampled_at: Option<u64>,
+    /// Number of the oldest samples already appended to the file
+    flushed: usize,
+}
+
+impl DepthSeries {
+    fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    fn push(&mut self, depth: usize, sample: &DepthSample) {
+        self.timestamps.push_back(sample.timestamp);
+        self.sequences.push_back(sample.sequence);
+        for (levels, prices, quantities) in [
+            (&sample.bids, &mut self.bid_prices, &mut self.bid_quantities),
+            (&sample.asks, &mut self.ask_prices, &mut self.ask_quantities),
+        ] {
+            for i in 0..depth {
+                let [price, quantity] = levels.get(i).copied().unwrap_or([f64::NAN, 0.0]);
+                prices.push_back(price);
+                quantities.push_back(quantity);
+            }
+        }
+    }
+
+    fn pop_front(&mut self, depth: usize) {
+        self.timestamps.pop_front();
+        self.sequences.pop_front();
+        for column in [
+            &mut self.bid_prices,
+            &mut self.bid_quantities,
+            &mut self.ask_prices,
+            &mut self.ask_quantities,
+        ] {
+            column.drain(..depth);
+        }
+        self.flushed = self.flushed.saturating_sub(1);
+    }
+
+    fn level(prices: &VecDeque<f64>, quantities: &VecDeque<f64>, i: usize) -> Option<[f64; 2]> {
+        Some([prices[i], quantities[i]]).filter(|level| !level[0].is_nan())
+    }
+
+    fn sample(&self, depth: usize, index: usize) -> DepthSample {
+        let levels = |prices, quantities| {
+            (index * depth..(index + 1) * depth)
+                .map_while(|i| DepthSeries::level(prices, quantities, i))
+                .collect()
+        };
+        DepthSample {
+            timestamp: self.timestamps[index],
+            sequence: self.sequences[index],
+            bids: levels(&self.bid_prices, &self.bid_quantities),
+            asks: levels(&self.ask_prices, &self.ask_quantities),
+        }
+    }
+
+    /// Indexes of the samples taken from `from` included to `to` excluded
+    fn range(&self, from: u64, to: u64) -> std::ops::Range<usize> {
+        let start = self.timestamps.partition_point(|&t| t < from);
+        let end = self.timestamps.partition_point(|&t| t < to);
+        start..end.max(start)
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    series: HashMap<Symbol, DepthSeries>,
+}
+
+/// Time series of the depth and the best bid and offer of the orderbooks, sampled every interval into
+/// an in-memory columnar buffer, to chart the history of a book without an external database.
+/// Clones share the same samples.
+#[derive(Debug, Clone)]
+pub struct DepthRecorder {
+    policy: RecorderPolicy,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl DepthRecorder {
+    pub fn new(policy: RecorderPolicy) -> DepthRecorder {
+        DepthRecorder {
+            policy: RecorderPolicy {
+                depth: policy.depth.max(1),
+                capacity: policy.capacity.max(1),
+                ..policy
+            },
+            state: Arc::new(Mutex::new(RecorderState::default())),
+        }
+    }
+
+    pub fn policy(&self) -> &RecorderPolicy {
+        &self.policy
+    }
+
+    /// Whether an orderbook is due for a sample
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol of the orderbook
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn is_due(&self, symbol: Symbol, now: u64) -> bool {
+        let state = self.state.lock().unwrap();
+        match state
+            .series
+            .get(&symbol)
+            .and_then(|series| series.sampled_at)
+        {
+            Some(sampled_at) => {
+                now.saturating_sub(sampled_at) >= self.policy.interval.as_nanos() as u64
+            }
+            None => true,
+        }
+    }
+
+    /// Store a sample of an orderbook, dropping its oldest sample once the capacity is reached
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol of the orderbook
+    /// * 'sample' - The depth of the orderbook, the levels past the depth of the policy are dropped
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn record(&self, symbol: Symbol, sample: &DepthSample, now: u64) {
+        let depth = self.policy.depth;
+        let mut state = self.state.lock().unwrap();
+        let series = state.series.entry(symbol).or_default();
+        if series.len() == self.policy.capacity {
+            series.pop_front(depth);
+        }
+        series.push(depth, sample);
+        series.sampled_at = Some(now);
+    }
+
+    /// Samples of an orderbook taken within a time range, the oldest first
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol of the orderbook
+    /// * 'from' - The start of the range in nanoseconds since UNIX epoch, included
+    /// * 'to' - The end of the range in nanoseconds since UNIX epoch, excluded
+    pub fn query(&self, symbol: Symbol, from: u64, to: u64) -> Vec<DepthSample> {
+        let state = self.state.lock().unwrap();
+        let Some(series) = state.series.get(&symbol) else {
+            return Vec::new();
+        };
+        series
+            .range(from, to)
+            .map(|index| series.sample(self.policy.depth, index))
+            .collect()
+    }
+
+    /// Best bid and offer of an orderbook within a time range, the oldest first
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol of the orderbook
+    /// * 'from' - The start of the range in nanoseconds since UNIX epoch, included
+    /// * 'to' - The end of the range in nanoseconds since UNIX epoch, excluded
+    pub fn query_bbo(&self, symbol: Symbol, from: u64, to: u64) -> Vec<BboSample> {
+        let depth = self.policy.depth;
+        let state = self.state.lock().unwrap();
+        let Some(series) = state.series.get(&symbol) else {
+            return Vec::new();
+        };
+        series
+            .range(from, to)
+            .map(|index| BboSample {
+                timestamp: series.timestamps[index],
+                bid: DepthSeries::level(&series.bid_prices, &series.bid_quantities, index * depth),
+                ask: DepthSeries::level(&series.ask_prices, &series.ask_quantities, index * depth),
+            })
+            .collect()
+    }
+
+    /// Number of samples kept for an orderbook
+    pub fn len(&self, symbol: Symbol) -> usize {
+        let state = self.state.lock().unwrap();
+        state.series.get(&symbol).map_or(0, |series| series.len())
+    }
+
+    /// Forget the samples of an orderbook
+    pub fn clear(&self, symbol: Symbol) {
+        self.state.lock().unwrap().series.remove(&symbol);
+    }
+
+    /// Append the samples not flushed yet to the file of their orderbook, `<symbol>.depth.jsonl` in the
+    /// directory of the policy. The samples dropped from memory before they were flushed are lost.
+    ///
+    /// #Returns
+    /// * Result<usize, Error> - The number of samples written, an error if the policy has no directory
+    pub fn flush(&self) -> Result<usize, Error> {
+        let Some(directory) = &self.policy.directory else {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "No directory to flush the samples to",
+            ));
+        };
+        fs::create_dir_all(directory)?;
+        let depth = self.policy.depth;
+        let mut state = self.state.lock().unwrap();
+        let mut written = 0;
+        for (symbol, series) in state.series.iter_mut() {
+            if series.flushed == series.len() {
+                continue;
+            }
+            let mut lines = Vec::new();
+            for index in series.flushed..series.len() {
+                serde_json::to_writer(&mut lines, &series.sample(depth, index))?;
+                lines.push(b'\n');
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(directory.join(format!("{symbol}.depth.jsonl")))?
+                .write_all(&lines)?;
+            written += series.len() - series.flushed;
+            series.flushed = series.len();
+        }
+        Ok(written)
+    }
+}
+
+impl OrderbooksManager {
+    /// Record the depth of the orderbooks every interval of the policy, see `record_depth`
+    ///
+    /// Parameters
+    /// * 'policy' : How often and how much of the orderbooks is captured
+    pub fn enable_depth_recorder(&mut self, policy: RecorderPolicy) -> &DepthRecorder {
+        self.depth_recorder
+            .get_or_insert_with(|| DepthRecorder::new(policy))
+    }
+
+    /// Sample the orderbooks due according to the recorder policy, then flush the samples when the
+    /// policy has a directory. To be called periodically, e.g. by `spawn_recorder_thread`.
+    ///
+    /// #Returns
+    /// * Result<usize, Error> - The number of orderbooks sampled, an error if the flush failed
+    pub fn record_depth(&self) -> Result<usize, Error> {
+        let Some(recorder) = &self.depth_recorder else {
+            return Ok(0);
+        };
+        let now = self.clock.monotonic();
+        let mut sampled = 0;
+        for (symbol, orderbook) in self.orderbooks.iter() {
+            if !recorder.is_due(*symbol, now) {
+                continue;
+            }
+            let precision = self.symbols.precision(*symbol);
+            let (bids, asks) = orderbook.depth(recorder.policy().depth);
+            let pairs = |levels: Vec<_>| {
+                levels
+                    .into_iter()
+                    .map(|level| precision.round_level(level))
+                    .map(|level| [level.price, level.quantity])
+                    .collect()
+            };
+            let sample = DepthSample {
+                timestamp: self.clock.now(),
+                sequence: orderbook.sequence,
+                bids: pairs(bids),
+                asks: pairs(asks),
+            };
+            recorder.record(*symbol, &sample, now);
+            sampled += 1;
+        }
+        if recorder.policy().directory.is_some() {
+            recorder.flush()?;
+        }
+        Ok(sampled)
+    }
+
+    /// Depth samples of an orderbook within a time range
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'from' : The start of the range in nanoseconds since UNIX epoch, included
+    /// * 'to' : The end of the range in nanoseconds since UNIX epoch, excluded
+    ///
+    /// #Returns
+    /// * Vec<DepthSample> - The samples, the oldest first, an error if the recorder is not enabled
+    pub fn depth_history(
+        &self,
+        symbol: Symbol,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<DepthSample>, Error> {
+        self.depth_recorder
+            .as_ref()
+            .map(|recorder| recorder.query(symbol, from, to))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Depth recorder not enabled"))
+    }
+}
+
+/// Sample the orderbooks of a manager in a background thread, every period, see `record_depth`.
+/// The thread stops once the manager is dropped.
+///
+/// #Parameters
+/// * 'manager' - The manager, with a depth recorder
+/// * 'period' - Time between two passes, typically the interval of the policy or a fraction of it
+#[cfg(feature = "native")]
+pub fn spawn_recorder_thread(
+    manager: &Arc<Mutex<OrderbooksManager>>,
+    period: Duration,
+) -> std::thread::JoinHandle<()> {
+    let manager = Arc::downgrade(manager);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(period);
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        let Ok(manager) = manager.lock() else {
+            return;
+        };
+        // the samples not flushed are written by the next pass
+        let _ = manager.record_depth();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::clock::MockClock;
+    use crate::structs::order::Order;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_depth_is_recorded_every_interval() {
+        let clock = MockClock::new(0);
+        let symbol: Symbol = Ulid::new().into();
+        let directory = std::env::temp_dir().join(format!("orderbook-{}", Ulid::new()));
+        let mut manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
+        manager.new_orderbook(symbol);
+        manager.enable_depth_recorder(
+            RecorderPolicy::new(Duration::from_secs(1), 2, 3).with_directory(&directory),
+        );
+        let order = |side, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+
+        assert_eq!(manager.record_depth().unwrap(), 1);
+        assert_eq!(manager.record_depth().unwrap(), 0);
+        for price in [9.0, 8.0, 7.0] {
+            manager.add_order(order(OrderSide::Buy, price)).unwrap();
+        }
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(1));
+            manager.record_depth().unwrap();
+        }
+        manager.add_order(order(OrderSide::Sell, 11.0)).unwrap();
+
+        // the first, empty, sample was dropped for the capacity
+        let recorder = manager.depth_recorder.clone().unwrap();
+        assert_eq!(recorder.len(symbol), 3);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let history = manager.depth_history(symbol, second, 3 * second).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].bids, vec![[9.0, 1.0], [8.0, 1.0]]);
+        assert!(history[0].asks.is_empty());
+        assert_eq!(
+            recorder.query_bbo(symbol, 3 * second, u64::MAX),
+            vec![BboSample {
+                timestamp: 3 * second,
+                bid: Some([9.0, 1.0]),
+                ask: None,
+            }]
+        );
+
+        let file = fs::read_to_string(directory.join(format!("{symbol}.depth.jsonl"))).unwrap();
+        let flushed: Vec<DepthSample> = file
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(flushed.len(), 4);
+        assert_eq!(flushed[1..], recorder.query(symbol, 0, u64::MAX)[..]);
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,506 @@
+use super::limits::{RiskCheck, RiskLimits};
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct RiskState {
+    limits: HashMap<UserId, RiskLimits>,
+    /// Resting orders per user, with their total remaining quantity
+    open_orders: HashMap<UserId, HashMap<OrderId, Order>>,
+    /// Position per (user, symbol), positive when long
+    positions: HashMap<(UserId, Symbol), f64>,
+    checks: Vec<Box<dyn RiskCheck>>,
+    /// Mark price per symbol, the last trade price unless set from outside
+    marks: HashMap<Symbol, f64>,
+}
+
+/// Pre-trade risk engine: per user limits on open orders, notional exposure and positions,
+/// plus pluggable external checks. Its state is fed with the updates of the orderbooks.
+#[derive(Debug, Clone, Default)]
+pub struct RiskEngine {
+    state: Arc<Mutex<RiskState>>,
+}
+
+/// Remaining quantity of an order, its hidden quantity included
+fn open_quantity(order: &Order) -> f64 {
+    order.quantity + order.hidden_quantity
+}
+
+/// Notional of the remaining quantity of an order, a market order has none
+fn open_notional(order: &Order) -> f64 {
+    order.price.unwrap_or_default() * open_quantity(order)
+}
+
+fn rejected(reason: &str) -> Error {
+    Error::new(
+        ErrorKind::PermissionDenied,
+        format!("Risk limit exceeded: {}", reason),
+    )
+}
+
+impl RiskEngine {
+    pub fn new() -> RiskEngine {
+        RiskEngine::default()
+    }
+
+    /// Set the limits of a user
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'limits' - The limits enforced on the orders of the user
+    pub fn set_limits(&self, user_id: UserId, limits: RiskLimits) {
+        self.state.lock().unwrap().limits.insert(user_id, limits);
+    }
+
+    pub fn limits(&self, user_id: UserId) -> Option<RiskLimits> {
+        self.state.lock().unwrap().limits.get(&user_id).copied()
+    }
+
+    /// Plug an external check, run after the limits on every order
+    pub fn add_check(&self, check: Box<dyn RiskCheck>) {
+        self.state.lock().unwrap().checks.push(check);
+    }
+
+    /// Position of a user on a symbol, positive when long
+    pub fn position(&self, user_id: UserId, symbol: Symbol) -> f64 {
+        let state = self.state.lock().unwrap();
+        state
+            .positions
+            .get(&(user_id, symbol))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Number of resting orders of a user
+    pub fn open_orders(&self, user_id: UserId) -> usize {
+        let state = self.state.lock().unwrap();
+        state.open_orders.get(&user_id).map_or(0, |o| o.len())
+    }
+
+    /// Notional of the resting orders of a user, across symbols
+    pub fn notional_exposure(&self, user_id: UserId) -> f64 {
+        let state = self.state.lock().unwrap();
+        Self::exposure(&state, user_id)
+    }
+
+    fn exposure(state: &RiskState, user_id: UserId) -> f64 {
+        state
+            .open_orders
+            .get(&user_id)
+            .map_or(0.0, |orders| orders.values().map(open_notional).sum())
+    }
+
+    /// Notional of the tracked version of an order already resting, which an amended order replaces
+    fn resting_order_notional(state: &RiskState, order: &Order) -> f64 {
+        state
+            .open_orders
+            .get(&order.user_id)
+            .and_then(|orders| orders.get(&order.id))
+            .map_or(0.0, open_notional)
+    }
+
+    /// Set the mark price of a symbol, e.g. from an external index, until the next trade
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'price' - The price the market orders of the symbol are valued at
+    pub fn set_mark_price(&self, symbol: Symbol, price: f64) {
+        self.state.lock().unwrap().marks.insert(symbol, price);
+    }
+
+    /// Mark price of a symbol, None before its first trade
+    pub fn mark_price(&self, symbol: Symbol) -> Option<f64> {
+        self.state.lock().unwrap().marks.get(&symbol).copied()
+    }
+
+    /// Notional of the resting orders of a user on a symbol, the hidden size of the icebergs included
+    pub fn resting_notional(&self, user_id: UserId, symbol: Symbol) -> f64 {
+        let state = self.state.lock().unwrap();
+        Self::symbol_exposure(&state, user_id, symbol)
+    }
+
+    fn symbol_exposure(state: &RiskState, user_id: UserId, symbol: Symbol) -> f64 {
+        state.open_orders.get(&user_id).map_or(0.0, |orders| {
+            orders
+                .values()
+                .filter(|o| o.symbol == symbol)
+                .map(open_notional)
+                .sum()
+        })
+    }
+
+    /// Throttle an order on its notional, price * quantity hidden quantity included, checked at ingress
+    /// before the other limits. A market order is valued at the mark price of its symbol, an amended
+    /// order is checked in place of its resting version.
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A PermissionDenied error if the order exceeds the max order notional, if it
+    ///   would take the resting notional of its user on the symbol over its maximum, or if a market
+    ///   order cannot be valued as its symbol has no mark price yet
+    pub fn check_notional(&self, order: &Order) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        let Some(limits) = state.limits.get(&order.user_id) else {
+            return Ok(());
+        };
+        if limits.max_order_notional.is_none() && limits.max_resting_notional.is_none() {
+            return Ok(());
+        }
+        let price = match order.price {
+            Some(price) => price,
+            None => match state.marks.get(&order.symbol) {
+                Some(&mark) => mark,
+                None => return Err(rejected("no mark price to value the market order")),
+            },
+        };
+        let notional = price * open_quantity(order);
+        if limits
+            .max_order_notional
+            .is_some_and(|maximum| notional > maximum)
+        {
+            return Err(rejected("max order notional"));
+        }
+        if order.order_type != OrderType::Market {
+            if let Some(max_resting_notional) = limits.max_resting_notional {
+                let resting = Self::symbol_exposure(&state, order.user_id, order.symbol)
+                    - Self::resting_order_notional(&state, order);
+                if resting + notional > max_resting_notional {
+                    return Err(rejected("max resting notional"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Accept or reject an order against the limits of its user and the plugged checks.
+    /// The notional of a market order is not known and is not counted, an amended order is checked
+    /// in place of its resting version.
+    pub fn check_order(&self, order: &Order) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        if let Some(limits) = state.limits.get(&order.user_id) {
+            let open_orders = state.open_orders.get(&order.user_id);
+            let resting = open_orders.is_some_and(|o| o.contains_key(&order.id));
+            if let Some(max_open_orders) = limits.max_open_orders {
+                if open_orders.map_or(0, |o| o.len()) - usize::from(resting) >= max_open_orders {
+                    return Err(rejected("max open orders"));
+                }
+            }
+            if let Some(max_notional_exposure) = limits.max_notional_exposure {
+                let notional = open_notional(order);
+                if Self::exposure(&state, order.user_id)
+                    - Self::resting_order_notional(&state, order)
+                    + notional
+                    > max_notional_exposure
+                {
+                    return Err(rejected("max notional exposure"));
+                }
+            }
+            if let Some(max_position) = limits.max_position {
+                let position = state
+                    .positions
+                    .get(&(order.user_id, order.symbol))
+                    .copied()
+                    .unwrap_or_default();
+                let resting: f64 = open_orders.map_or(0.0, |orders| {
+                    orders
+                        .values()
+                        .filter(|o| {
+                            o.symbol == order.symbol && o.side == order.side && o.id != order.id
+                        })
+                        .map(open_quantity)
+                        .sum()
+                });
+                let worst_case = match order.side {
+                    OrderSide::Buy => position + resting + open_quantity(order),
+                    OrderSide::Sell => position - resting - open_quantity(order),
+                };
+                if worst_case.abs() > max_position {
+                    return Err(rejected("max position"));
+                }
+            }
+        }
+        for check in state.checks.iter() {
+            check.check_order(order)?;
+        }
+        Ok(())
+    }
+
+    /// Record an order accepted by the manager, its total quantity including the hidden one is tracked.
+    /// Market orders never rest in the books and are not recorded.
+    pub fn on_order_accepted(&self, order: &Order) {
+        if order.order_type == OrderType::Market {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state
+            .open_orders
+            .entry(order.user_id)
+            .or_default()
+            .insert(order.id, *order);
+    }
+
+    /// Forget an accepted order which never reached its book
+    pub fn on_order_revoked(&self, order: &Order) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(orders) = state.open_orders.get_mut(&order.user_id) {
+            orders.remove(&order.id);
+        }
+    }
+
+    /// Update the open orders and the positions from an update published by an orderbook
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                if let Some(trade) = &update.trade {
+                    state.marks.insert(trade.symbol, trade.price);
+                    *state
+                        .positions
+                        .entry((trade.buy_user_id, trade.symbol))
+                        .or_default() += trade.quantity;
+                    *state
+                        .positions
+                        .entry((trade.sell_user_id, trade.symbol))
+                        .or_default() -= trade.quantity;
+                    for (user_id, order_id) in [
+                        (trade.buy_user_id, trade.buy_order_id),
+                        (trade.sell_user_id, trade.sell_order_id),
+                    ] {
+                        if let Some(order) = state
+                            .open_orders
+                            .get_mut(&user_id)
+                            .and_then(|orders| orders.get_mut(&order_id))
+                        {
+                            // The visible quantity trades first, then the reserve it is replenished from
+                            let visible = trade.quantity.min(order.quantity);
+                            order.quantity -= visible;
+                            order.hidden_quantity =
+                                (order.hidden_quantity - (trade.quantity - visible)).max(0.0);
+                        }
+                    }
+                }
+            }
+            OrderbookUpdateType::Replace | OrderbookUpdateType::Amended => {
+                if let Some(update_order) = update.order {
+                    if let Some(order) = state
+                        .open_orders
+                        .get_mut(&update_order.user_id)
+                        .and_then(|orders| orders.get_mut(&update_order.id))
+                    {
+                        order.price = update_order.price;
+                        // The hidden size of an iceberg is not published, keep the tracked one
+                        if !order.is_iceberg() {
+                            order.quantity = update_order.quantity;
+                        }
+                    }
+                }
+            }
+            OrderbookUpdateType::Repriced => {
+                if let Some(update_order) = update.order {
+                    if let Some(order) = state
+                        .open_orders
+                        .get_mut(&update_order.user_id)
+                        .and_then(|orders| orders.get_mut(&update_order.id))
+                    {
+                        order.price = update_order.price;
+                    }
+                }
+            }
+            OrderbookUpdateType::Migrated => {
+                if let Some(update_order) = update.order {
+                    if let Some(order) = state
+                        .open_orders
+                        .get_mut(&update_order.user_id)
+                        .and_then(|orders| orders.get_mut(&update_order.id))
+                    {
+                        // The hidden size of an iceberg is not published, it is scaled as its display
+                        order.quantity =
+                            match (order.display_quantity, update_order.display_quantity) {
+                                (Some(before), Some(after)) if order.is_iceberg() => {
+                                    order.quantity * after / before
+                                }
+                                _ => update_order.quantity,
+                            };
+                        order.symbol = update_order.symbol;
+                        order.price = update_order.price;
+                        order.display_quantity = update_order.display_quantity;
+                    }
+                }
+            }
+            OrderbookUpdateType::Cancel
+            | OrderbookUpdateType::Filled
+            | OrderbookUpdateType::Expired => {
+                if let Some(order) = update.order {
+                    if let Some(orders) = state.open_orders.get_mut(&order.user_id) {
+                        orders.remove(&order.id);
+                    }
+                }
+            }
+            _ => {}
+        }
+        for check in state.checks.iter_mut() {
+            check.on_update(update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::trade::Trade;
+    use ulid::Ulid;
+
+    #[derive(Debug)]
+    struct MaxQuantityCheck(f64);
+
+    impl RiskCheck for MaxQuantityCheck {
+        fn check_order(&self, order: &Order) -> Result<(), Error> {
+            if order.quantity > self.0 {
+                return Err(rejected("fat finger"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_limits_and_checks() {
+        let risk = RiskEngine::new();
+        let user = Ulid::new().into();
+        let symbol = Ulid::new().into();
+        risk.set_limits(
+            user,
+            RiskLimits {
+                max_open_orders: Some(2),
+                max_notional_exposure: Some(100.0),
+                max_position: Some(5.0),
+                ..Default::default()
+            },
+        );
+        risk.add_check(Box::new(MaxQuantityCheck(10.0)));
+        let order = |quantity: f64, price: f64| {
+            Order::new(
+                user,
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+
+        let resting = order(3.0, 10.0);
+        assert!(risk.check_order(&resting).is_ok());
+        risk.on_order_accepted(&resting);
+        assert_eq!(risk.notional_exposure(user), 30.0);
+        // 3 resting + 3 > 5
+        assert!(risk.check_order(&order(3.0, 1.0)).is_err());
+        // 30 + 80 > 100
+        assert!(risk.check_order(&order(1.0, 80.0)).is_err());
+        assert!(risk.check_order(&order(11.0, 1.0)).is_err());
+        risk.on_order_accepted(&order(1.0, 1.0));
+        assert!(risk.check_order(&order(1.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_hidden_quantity_counts_against_the_limits() {
+        let risk = RiskEngine::new();
+        let (user, other) = (Ulid::new().into(), Ulid::new().into());
+        let symbol = Ulid::new().into();
+        risk.set_limits(
+            user,
+            RiskLimits {
+                max_notional_exposure: Some(100.0),
+                max_position: Some(5.0),
+                ..Default::default()
+            },
+        );
+        let mut iceberg = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        )
+        .with_display_quantity(1.0);
+        iceberg.hidden_quantity = 5.0;
+        assert!(risk.check_order(&iceberg).is_err());
+        iceberg.hidden_quantity = 4.0;
+        risk.check_order(&iceberg).unwrap();
+        risk.on_order_accepted(&iceberg);
+        assert_eq!(risk.notional_exposure(user), 50.0);
+
+        // The trade takes the visible quantity, then the reserve
+        risk.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(Trade {
+                symbol,
+                price: 10.0,
+                quantity: 2.0,
+                buy_order_id: iceberg.id,
+                buy_user_id: user,
+                sell_user_id: other,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(risk.notional_exposure(user), 30.0);
+        assert_eq!(risk.position(user, symbol), 2.0);
+        let buy = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        // 2 long + 3 resting + 1 > 5
+        assert!(risk.check_order(&buy).is_err());
+    }
+
+    #[test]
+    fn test_resting_notional_counts_the_hidden_quantity() {
+        let risk = RiskEngine::new();
+        let user = Ulid::new().into();
+        let symbol = Ulid::new().into();
+        risk.set_limits(
+            user,
+            RiskLimits {
+                max_resting_notional: Some(60.0),
+                ..Default::default()
+            },
+        );
+        let mut iceberg = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        )
+        .with_display_quantity(1.0);
+        iceberg.hidden_quantity = 4.0;
+        risk.check_notional(&iceberg).unwrap();
+        risk.on_order_accepted(&iceberg);
+        assert_eq!(risk.resting_notional(user, symbol), 50.0);
+
+        // the iceberg is checked in place of its resting version
+        risk.check_notional(&iceberg).unwrap();
+        let buy = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            2.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        // 50 resting + 20 > 60
+        assert!(risk.check_notional(&buy).is_err());
+    }
+}
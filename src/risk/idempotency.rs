@@ -0,0 +1,206 @@
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::structs::ids::{OrderId, UserId};
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// User and client order ID of an order
+type ClientKey = (UserId, u128);
+
+#[derive(Debug, Default)]
+struct GuardState {
+    /// How long the IDs are remembered, zero to only reject the IDs of the live orders
+    window: Duration,
+    /// Orders accepted which did not leave their book yet
+    live: HashSet<OrderId>,
+    /// Orders which left their book within the window, with the monotonic time they did
+    closed: HashMap<OrderId, u64>,
+    /// Orders accepted within the window by user and client order ID, with the monotonic time they were
+    clients: HashMap<ClientKey, (OrderId, u64)>,
+    /// Entries to forget once the window is over, the oldest first
+    expiries: VecDeque<(u64, OrderId, Option<ClientKey>)>,
+}
+
+impl GuardState {
+    fn expire(&mut self, now: u64) {
+        let window = self.window.as_nanos() as u64;
+        while let Some(&(at, order_id, client)) = self.expiries.front() {
+            if at.saturating_add(window) > now {
+                break;
+            }
+            self.expiries.pop_front();
+            if self.closed.get(&order_id) == Some(&at) {
+                self.closed.remove(&order_id);
+            }
+            if let Some(client) = client {
+                if self.clients.get(&client).is_some_and(|&(_, t)| t == at) {
+                    self.clients.remove(&client);
+                }
+            }
+        }
+    }
+}
+
+/// Protection against orders booked twice: an order reusing the ID of a live order, or of an order
+/// which left its book within the dedup window, is rejected, and so is a retry of an order accepted
+/// with the same client order ID within the window. Clones share the same IDs.
+#[derive(Debug, Clone, Default)]
+pub struct IdempotencyGuard {
+    state: Arc<Mutex<GuardState>>,
+}
+
+impl IdempotencyGuard {
+    pub fn new() -> IdempotencyGuard {
+        IdempotencyGuard::default()
+    }
+
+    /// Set how long the order IDs and the client order IDs are remembered, zero to only reject the
+    /// IDs of the live orders and accept any client order ID
+    pub fn set_window(&self, window: Duration) {
+        self.state.lock().unwrap().window = window;
+    }
+
+    /// How long the order IDs and the client order IDs are remembered
+    pub fn window(&self) -> Duration {
+        self.state.lock().unwrap().window
+    }
+
+    /// Whether an order ID is taken by a live order or an order closed within the window
+    pub fn is_taken(&self, order_id: OrderId) -> bool {
+        let state = self.state.lock().unwrap();
+        state.live.contains(&order_id) || state.closed.contains_key(&order_id)
+    }
+
+    /// Check the ID of a new order is not taken
+    ///
+    /// #Parameters
+    /// * 'order' - The order
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn check_order(&self, order: &Order, now: u64) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.expire(now);
+        if state.live.contains(&order.id) || state.closed.contains_key(&order.id) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "Duplicate order ID"));
+        }
+        Ok(())
+    }
+
+    /// Check the client order ID of a new order was not accepted within the window
+    ///
+    /// #Parameters
+    /// * 'order' - The order
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn check_client_order(&self, order: &Order, now: u64) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.expire(now);
+        let Some(client_order_id) = order.client_order_id else {
+            return Ok(());
+        };
+        match state.clients.get(&(order.user_id, client_order_id)) {
+            Some(&(order_id, _)) => Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Duplicate client order ID, already accepted as order {order_id}"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Take the ID of an accepted order until it leaves its book
+    pub fn on_order_accepted(&self, order: &Order) {
+        self.state.lock().unwrap().live.insert(order.id);
+    }
+
+    /// Free the ID of an accepted order which never reached its book, it can be sent again
+    pub fn on_order_revoked(&self, order_id: OrderId) {
+        self.state.lock().unwrap().live.remove(&order_id);
+    }
+
+    /// Remember the client order ID of an accepted order for the window
+    ///
+    /// #Parameters
+    /// * 'order' - The order
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn on_client_order_accepted(&self, order: &Order, now: u64) {
+        let mut state = self.state.lock().unwrap();
+        let Some(client_order_id) = order.client_order_id.filter(|_| !state.window.is_zero())
+        else {
+            return;
+        };
+        let client = (order.user_id, client_order_id);
+        state.clients.insert(client, (order.id, now));
+        state.expiries.push_back((now, order.id, Some(client)));
+    }
+
+    /// Release the ID of an order which left its book, it stays taken for the window
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn on_order_closed(&self, order_id: OrderId, now: u64) {
+        let mut state = self.state.lock().unwrap();
+        if !state.live.remove(&order_id) || state.window.is_zero() {
+            return;
+        }
+        state.closed.insert(order_id, now);
+        state.expiries.push_back((now, order_id, None));
+    }
+
+    /// Release the IDs of the orders leaving their book
+    pub fn on_update(&self, update: &OrderbookUpdate, now: u64) {
+        if let (
+            OrderbookUpdateType::Cancel
+            | OrderbookUpdateType::Filled
+            | OrderbookUpdateType::Expired,
+            Some(order),
+        ) = (update.update_type, update.order)
+        {
+            self.on_order_closed(order.id, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_ids_are_taken_for_the_window() {
+        let guard = IdempotencyGuard::new();
+        let order = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        )
+        .with_client_order_id(7);
+        let second = 1_000_000_000;
+        guard.set_window(Duration::from_secs(1));
+        guard.check_order(&order, 0).unwrap();
+        guard.on_order_accepted(&order);
+        guard.on_client_order_accepted(&order, 0);
+        let retry = Order {
+            id: Ulid::new().into(),
+            ..order
+        };
+        assert_eq!(
+            guard.check_order(&order, 0).unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+        assert!(guard.check_client_order(&retry, second - 1).is_err());
+        guard.check_client_order(&retry, second).unwrap();
+
+        // a live order keeps its ID past the window, a closed one keeps it for the window
+        assert!(guard.is_taken(order.id));
+        guard.on_order_closed(order.id, 3 * second);
+        assert!(guard.check_order(&order, 4 * second - 1).is_err());
+        guard.check_order(&order, 4 * second).unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+use crate::structs::order::Order;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Error;
+
+/// Limits of a user, a None limit is not enforced
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct RiskLimits {
+    /// Maximum number of orders resting in the books
+    pub max_open_orders: Option<usize>,
+    /// Maximum notional (price * quantity) of the orders resting in the books, across symbols
+    pub max_notional_exposure: Option<f64>,
+    /// Maximum absolute position per symbol, counting the resting orders as if they were filled
+    pub max_position: Option<f64>,
+    /// Maximum notional of a single order, a market order being valued at the mark price
+    #[serde(default)]
+    pub max_order_notional: Option<f64>,
+    /// Maximum notional of the orders resting in the book of each symbol
+    #[serde(default)]
+    pub max_resting_notional: Option<f64>,
+}
+
+/// Pre-trade check plugged into the risk engine, e.g. a call to an external risk system
+pub trait RiskCheck: fmt::Debug + Send {
+    /// Accept or reject an order before it reaches the orderbook
+    fn check_order(&self, order: &Order) -> Result<(), Error>;
+
+    /// Observe the updates published by the orderbooks, e.g. to track positions
+    fn on_update(&mut self, _update: &OrderbookUpdate) {}
+}
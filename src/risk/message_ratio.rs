@@ -0,0 +1,277 @@
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::ratio_action::RatioAction;
+use crate::structs::ids::UserId;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Threshold on the messages sent by a user for each of its trades
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MessageRatioPolicy {
+    /// Rolling window the messages and the trades are counted over
+    pub window: Duration,
+    /// Messages allowed per trade, a user without trades counts as having one
+    #[serde(rename = "maxRatio")]
+    pub max_ratio: f64,
+    /// Messages under which a user is never flagged, so that a quiet user isn't flagged for its first orders
+    #[serde(rename = "minMessages")]
+    pub min_messages: usize,
+    pub action: RatioAction,
+}
+
+/// Messages and trades of a user over the window of the policy
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct MessageRatio {
+    /// Orders, amends, replaces and cancels
+    pub messages: usize,
+    pub trades: usize,
+    /// Messages per trade
+    pub ratio: f64,
+}
+
+/// Event published on the update stream when a user crosses the threshold of the policy, or gets back under it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ComplianceEvent {
+    #[serde(rename = "userId")]
+    pub user_id: UserId,
+    pub ratio: MessageRatio,
+    /// Messages allowed per trade by the policy
+    pub threshold: f64,
+    pub action: RatioAction,
+    /// True when the user exceeds the threshold, false when it is back under it
+    pub breached: bool,
+}
+
+#[derive(Debug, Default)]
+struct UserFlow {
+    /// Monotonic times of the messages and the trades in the window, the oldest first
+    messages: VecDeque<u64>,
+    trades: VecDeque<u64>,
+    breached: bool,
+}
+
+impl UserFlow {
+    fn ratio(&mut self, window: Duration, now: u64) -> MessageRatio {
+        let start = now.saturating_sub(window.as_nanos() as u64);
+        for times in [&mut self.messages, &mut self.trades] {
+            while times.front().is_some_and(|&time| time < start) {
+                times.pop_front();
+            }
+        }
+        MessageRatio {
+            messages: self.messages.len(),
+            trades: self.trades.len(),
+            ratio: self.messages.len() as f64 / self.trades.len().max(1) as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    policy: Option<MessageRatioPolicy>,
+    users: HashMap<UserId, UserFlow>,
+}
+
+impl MonitorState {
+    /// Compare the ratio of a user to the policy, returns an event when the user crossed the threshold
+    fn evaluate(&mut self, user_id: UserId, now: u64) -> Option<ComplianceEvent> {
+        let policy = self.policy?;
+        let flow = self.users.get_mut(&user_id)?;
+        let ratio = flow.ratio(policy.window, now);
+        let breached = ratio.messages >= policy.min_messages && ratio.ratio > policy.max_ratio;
+        if ratio.messages == 0 && ratio.trades == 0 && !flow.breached {
+            self.users.remove(&user_id);
+            return None;
+        }
+        if breached == flow.breached {
+            return None;
+        }
+        flow.breached = breached;
+        Some(ComplianceEvent {
+            user_id,
+            ratio,
+            threshold: policy.max_ratio,
+            action: policy.action,
+            breached,
+        })
+    }
+}
+
+/// Message-to-trade ratio of the users over a rolling window, fed with the updates of the orderbooks.
+/// Nothing is tracked until a policy is set. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct MessageRatioMonitor {
+    state: Arc<Mutex<MonitorState>>,
+}
+
+impl MessageRatioMonitor {
+    pub fn new() -> MessageRatioMonitor {
+        MessageRatioMonitor::default()
+    }
+
+    /// Set the policy enforced on every user, None to stop monitoring and lift the throttles
+    pub fn set_policy(&self, policy: Option<MessageRatioPolicy>) {
+        let mut state = self.state.lock().unwrap();
+        state.policy = policy;
+        state.users.clear();
+    }
+
+    pub fn policy(&self) -> Option<MessageRatioPolicy> {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Messages and trades of a user over the window of the policy
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'now' - The monotonic time in nanoseconds
+    pub fn ratio(&self, user_id: UserId, now: u64) -> MessageRatio {
+        let mut state = self.state.lock().unwrap();
+        let Some(policy) = state.policy else {
+            return MessageRatio::default();
+        };
+        state
+            .users
+            .get_mut(&user_id)
+            .map(|flow| flow.ratio(policy.window, now))
+            .unwrap_or_default()
+    }
+
+    /// Whether the new orders of a user are rejected, its ratio exceeding a Throttle policy
+    pub fn is_throttled(&self, user_id: UserId) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .policy
+            .is_some_and(|p| p.action == RatioAction::Throttle)
+            && state.users.get(&user_id).is_some_and(|flow| flow.breached)
+    }
+
+    /// Evaluate a user again, its old messages leaving the window can bring it back under the threshold
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'now' - The monotonic time in nanoseconds
+    ///
+    /// #Returns
+    /// * Option<ComplianceEvent> - The event to publish when the user crossed the threshold
+    pub fn evaluate(&self, user_id: UserId, now: u64) -> Option<ComplianceEvent> {
+        self.state.lock().unwrap().evaluate(user_id, now)
+    }
+
+    /// Count the messages and the trades of an update published by an orderbook.
+    /// New orders, amends, replaces and cancels are messages of the owner of the order,
+    /// a trade counts for both its users.
+    ///
+    /// #Returns
+    /// * Vec<ComplianceEvent> - The events of the users who crossed the threshold
+    pub fn on_update(&self, update: &OrderbookUpdate, now: u64) -> Vec<ComplianceEvent> {
+        let mut state = self.state.lock().unwrap();
+        if state.policy.is_none() {
+            return vec![];
+        }
+        let mut users = vec![];
+        match update.update_type {
+            OrderbookUpdateType::New
+            | OrderbookUpdateType::Amended
+            | OrderbookUpdateType::Replace
+            | OrderbookUpdateType::Cancel => {
+                if let Some(order) = update.order.as_ref() {
+                    let flow = state.users.entry(order.user_id).or_default();
+                    flow.messages.push_back(now);
+                    users.push(order.user_id);
+                }
+            }
+            OrderbookUpdateType::NewTrades => {
+                if let Some(trade) = update.trade.as_ref() {
+                    users.push(trade.buy_user_id);
+                    if trade.sell_user_id != trade.buy_user_id {
+                        users.push(trade.sell_user_id);
+                    }
+                    for &user_id in users.iter() {
+                        state
+                            .users
+                            .entry(user_id)
+                            .or_default()
+                            .trades
+                            .push_back(now);
+                    }
+                }
+            }
+            _ => {}
+        }
+        users
+            .into_iter()
+            .filter_map(|user_id| state.evaluate(user_id, now))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::ids::Symbol;
+    use crate::structs::order::Order;
+    use crate::structs::trade::Trade;
+
+    #[test]
+    fn test_ratio_over_the_window() {
+        let monitor = MessageRatioMonitor::new();
+        monitor.set_policy(Some(MessageRatioPolicy {
+            window: Duration::from_secs(1),
+            max_ratio: 2.0,
+            min_messages: 3,
+            action: RatioAction::Throttle,
+        }));
+        let user = UserId(1);
+        let order = Order::new(
+            user,
+            Symbol(1),
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let new = OrderbookUpdate {
+            update_type: OrderbookUpdateType::New,
+            order: Some(order),
+            ..Default::default()
+        };
+        let mut sell = order;
+        sell.user_id = UserId(2);
+        sell.side = OrderSide::Sell;
+        let trade = OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(Trade::between(
+                Symbol(1),
+                1.0,
+                1.0,
+                &order,
+                &sell,
+                OrderSide::Sell,
+            )),
+            ..Default::default()
+        };
+        assert!(monitor.on_update(&new, 0).is_empty());
+        assert!(monitor.on_update(&new, 0).is_empty());
+        // three messages without trade
+        let events = monitor.on_update(&new, 0);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].breached);
+        assert_eq!(events[0].ratio.ratio, 3.0);
+        assert!(monitor.is_throttled(user));
+
+        // two trades bring the ratio to 1.5
+        assert!(monitor.on_update(&trade, 100).is_empty());
+        let events = monitor.on_update(&trade, 100);
+        assert!(!events[0].breached);
+        assert!(!monitor.is_throttled(user));
+        assert_eq!(monitor.ratio(user, 100).trades, 2);
+
+        // the messages leave the window
+        assert_eq!(monitor.ratio(user, 2_000_000_000), MessageRatio::default());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod engine;
+pub mod idempotency;
+pub mod limits;
+pub mod message_ratio;
+pub mod rate_limit;
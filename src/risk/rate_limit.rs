@@ -0,0 +1,150 @@
+use crate::structs::ids::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// Order flow allowed to a user: a token bucket refilled at `rate` messages per second and holding
+/// at most `burst` messages
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Messages per second allowed in the long run
+    pub rate: f64,
+    /// Messages which can be sent at once after a quiet period
+    pub burst: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    /// Monotonic time of the last refill, in nanoseconds
+    refilled_at: u64,
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterState {
+    /// Limit of the users without one of their own, None to let them through
+    default_limit: Option<RateLimit>,
+    limits: HashMap<UserId, RateLimit>,
+    buckets: HashMap<UserId, TokenBucket>,
+    /// Messages refused per user
+    throttled: HashMap<UserId, u64>,
+}
+
+/// Per user token buckets throttling the order flow before it reaches the engine.
+/// Clones share the same buckets.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> RateLimiter {
+        RateLimiter::default()
+    }
+
+    /// Set the limit of the users without a limit of their own, None to stop throttling them
+    pub fn set_default_limit(&self, limit: Option<RateLimit>) {
+        self.state.lock().unwrap().default_limit = limit;
+    }
+
+    /// Set the limit of a user, its bucket starts full
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'limit' - The rate and burst allowed to the user
+    pub fn set_limit(&self, user_id: UserId, limit: RateLimit) {
+        let mut state = self.state.lock().unwrap();
+        state.limits.insert(user_id, limit);
+        state.buckets.remove(&user_id);
+    }
+
+    /// Remove the limit of a user, the default limit applies again
+    pub fn remove_limit(&self, user_id: UserId) {
+        let mut state = self.state.lock().unwrap();
+        state.limits.remove(&user_id);
+        state.buckets.remove(&user_id);
+    }
+
+    /// Limit enforced on a user, None when the user is not throttled
+    pub fn limit(&self, user_id: UserId) -> Option<RateLimit> {
+        let state = self.state.lock().unwrap();
+        state.limits.get(&user_id).copied().or(state.default_limit)
+    }
+
+    /// Number of messages of a user refused so far
+    pub fn throttled(&self, user_id: UserId) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.throttled.get(&user_id).copied().unwrap_or_default()
+    }
+
+    /// Take a token from the bucket of a user, refilled for the time elapsed since the last message
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user sending the message
+    /// * 'now' - The monotonic time in nanoseconds
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A PermissionDenied error when the bucket of the user is empty
+    pub fn acquire(&self, user_id: UserId, now: u64) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        let Some(limit) = state.limits.get(&user_id).copied().or(state.default_limit) else {
+            return Ok(());
+        };
+        let bucket = state.buckets.entry(user_id).or_insert(TokenBucket {
+            tokens: limit.burst,
+            refilled_at: now,
+        });
+        let elapsed = now.saturating_sub(bucket.refilled_at) as f64 / 1e9;
+        bucket.tokens = (bucket.tokens + elapsed * limit.rate).min(limit.burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        *state.throttled.entry(user_id).or_default() += 1;
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Rate limited: too many messages",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new();
+        let (user, other) = (UserId(1), UserId(2));
+        limiter.set_limit(
+            user,
+            RateLimit {
+                rate: 10.0,
+                burst: 2.0,
+            },
+        );
+        assert!(limiter.acquire(user, 0).is_ok());
+        assert!(limiter.acquire(user, 0).is_ok());
+        assert!(limiter.acquire(user, 0).is_err());
+        // a token every 100ms
+        assert!(limiter.acquire(user, 50_000_000).is_err());
+        assert!(limiter.acquire(user, 100_000_000).is_ok());
+        // the bucket never holds more than the burst
+        assert!(limiter.acquire(user, 10_000_000_000).is_ok());
+        assert!(limiter.acquire(user, 10_000_000_000).is_ok());
+        assert!(limiter.acquire(user, 10_000_000_000).is_err());
+        assert_eq!(limiter.throttled(user), 3);
+
+        // users without a limit are let through until a default is set
+        assert!((0..100).all(|_| limiter.acquire(other, 0).is_ok()));
+        limiter.set_default_limit(Some(RateLimit {
+            rate: 1.0,
+            burst: 1.0,
+        }));
+        assert!(limiter.acquire(other, 0).is_ok());
+        assert!(limiter.acquire(other, 0).is_err());
+        assert_eq!(limiter.limit(user).unwrap().burst, 2.0);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::structs::clock::{Clock, MockClock};
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::match_observer::MatchObserver;
+use crate::structs::order::Order;
+use crate::structs::orderbook::Orderbook;
+use crate::structs::trade::Trade;
+use crate::testing::generator::FlowCommand;
+use crate::testing::rng::SplitMix64;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Order flow model of the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimConfig {
+    pub symbol: Symbol,
+    /// Mean number of commands per second, the arrivals are a Poisson process
+    pub arrival_rate: f64,
+    /// Mid price at the start of the simulation
+    pub initial_mid: f64,
+    /// Standard deviation of the mid price over one second, the mid follows a random walk
+    pub volatility: f64,
+    pub tick_size: f64,
+    /// Distance in ticks from the mid of the farthest limit orders,
+    /// the nearest ones are one tick through the mid and take liquidity
+    pub depth: u32,
+    /// Largest order quantity, the quantities are whole numbers from 1
+    pub max_quantity: u32,
+    /// Share of the orders which are market orders
+    pub market_ratio: f64,
+    /// Share of the commands which cancel a resting order
+    pub cancel_ratio: f64,
+    /// Number of users placing the orders, their IDs go from 1 to `users`
+    pub users: u32,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            symbol: Symbol(1),
+            arrival_rate: 1_000.0,
+            initial_mid: 100.0,
+            volatility: 0.5,
+            tick_size: 0.01,
+            depth: 20,
+            max_quantity: 10,
+            market_ratio: 0.05,
+            cancel_ratio: 0.3,
+            users: 100,
+        }
+    }
+}
+
+/// Command of the simulation with its arrival time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimEvent {
+    /// Arrival time in nanoseconds since UNIX epoch
+    pub at: u64,
+    pub command: FlowCommand,
+}
+
+/// Outcome of a simulation run
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SimReport {
+    pub orders: usize,
+    pub cancels: usize,
+    pub amends: usize,
+    /// Trades executed during the run, in execution order
+    pub tape: Vec<Trade>,
+    /// Mid price of the model at the end of the run
+    pub mid: f64,
+    /// Simulated time at the end of the run, in nanoseconds since UNIX epoch
+    pub time: u64,
+}
+
+/// Observer recording the trades of the orderbook during a run
+#[derive(Debug, Default)]
+struct TapeRecorder {
+    trades: Mutex<Vec<Trade>>,
+}
+
+impl MatchObserver for TapeRecorder {
+    fn on_trade(&self, trade: &Trade) {
+        self.trades.lock().unwrap().push(trade.clone());
+    }
+}
+
+/// Market simulator generating synthetic order flow against an orderbook, to produce realistic
+/// books and trade tapes for demos, benchmarks and downstream testing.
+/// The same seed always produces the same run.
+///
+/// The simulator drives the clock of the orderbook, so the order times, the trade times and the
+/// expiries follow the simulated time.
+#[derive(Debug, Clone)]
+pub struct MarketSimulator {
+    pub config: SimConfig,
+    rng: SplitMix64,
+    clock: MockClock,
+    mid: f64,
+    next_id: u128,
+}
+
+impl MarketSimulator {
+    /// Create a simulator
+    ///
+    /// #Parameters
+    /// * 'config' - The order flow model
+    /// * 'seed' - The seed of the random number generator
+    pub fn new(config: SimConfig, seed: u64) -> MarketSimulator {
+        MarketSimulator {
+            config,
+            rng: SplitMix64::new(seed),
+            clock: MockClock::new(1_000_000_000),
+            mid: config.initial_mid,
+            next_id: 0,
+        }
+    }
+
+    /// Current mid price of the model
+    pub fn mid(&self) -> f64 {
+        self.mid
+    }
+
+    /// Simulated time in nanoseconds since UNIX epoch
+    pub fn time(&self) -> u64 {
+        self.clock.now()
+    }
+
+    /// Draw the next arrival: advance the time and the mid price, then pick a cancel of a resting order or a new order
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook the cancels are picked from
+    pub fn next_event(&mut self, orderbook: &Orderbook) -> SimEvent {
+        let config = self.config;
+        let elapsed = self.rng.next_exponential(config.arrival_rate);
+        self.clock
+            .advance(Duration::from_nanos((elapsed * 1e9).max(1.0) as u64));
+        let step = config.volatility * elapsed.sqrt() * self.rng.next_normal();
+        self.mid = self.round_to_tick(self.mid + step).max(config.tick_size);
+
+        let resting = orderbook.bids.len() + orderbook.asks.len();
+        if resting > 0 && self.rng.next_f64() < config.cancel_ratio {
+            let index = self.rng.next_u64() as usize % resting;
+            let order = orderbook
+                .bids
+                .iter_ref()
+                .chain(orderbook.asks.iter_ref())
+                .nth(index)
+                .unwrap();
+            return SimEvent {
+                at: self.time(),
+                command: FlowCommand::Cancel {
+                    order_id: order.id,
+                    side: order.side,
+                },
+            };
+        }
+        SimEvent {
+            at: self.time(),
+            command: FlowCommand::Add(self.next_order()),
+        }
+    }
+
+    fn round_to_tick(&self, price: f64) -> f64 {
+        (price / self.config.tick_size).round() * self.config.tick_size
+    }
+
+    fn next_order(&mut self) -> Order {
+        let config = self.config;
+        self.next_id += 1;
+        let side = match self.rng.next_u64() % 2 {
+            0 => OrderSide::Buy,
+            _ => OrderSide::Sell,
+        };
+        let user_id = UserId(self.rng.next_in(1, config.users.max(1) as u64) as u128);
+        let quantity = self.rng.next_in(1, config.max_quantity.max(1) as u64) as f64;
+        let (order_type, price) = if self.rng.next_f64() < config.market_ratio {
+            (OrderType::Market, None)
+        } else {
+            // From one tick through the mid to `depth` ticks away from it
+            let ticks = self.rng.next_in(0, config.depth.max(1) as u64) as f64 - 1.0;
+            let distance = ticks * config.tick_size;
+            let price = match side {
+                OrderSide::Buy => self.mid - distance,
+                OrderSide::Sell => self.mid + distance,
+            };
+            let price = self.round_to_tick(price).max(config.tick_size);
+            (OrderType::Limit, Some(price))
+        };
+        let mut order = Order::new(user_id, config.symbol, side, quantity, price, order_type)
+            .stamped(&self.clock);
+        order.id = OrderId(self.next_id);
+        order
+    }
+
+    /// Apply an event to the orderbook
+    pub fn apply(orderbook: &mut Orderbook, event: &SimEvent) {
+        // the flow may target orders which already left the book, such commands change nothing
+        let _ = match event.command {
+            FlowCommand::Add(order) => {
+                orderbook.add_order(order);
+                return;
+            }
+            FlowCommand::Cancel { order_id, side } => orderbook.cancel_order(order_id, side),
+            FlowCommand::AmendPrice {
+                order_id,
+                side,
+                price,
+            } => orderbook.amend_order_price(order_id, price, side),
+            FlowCommand::AmendQuantity {
+                order_id,
+                side,
+                quantity,
+            } => orderbook.amend_order_quantity(order_id, quantity, side),
+        };
+    }
+
+    /// Run the simulation against an orderbook for a number of events.
+    /// The receiver of the orderbook channel must be kept alive during the run.
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook, its clock is replaced by the simulated clock
+    /// * 'events' - The number of events
+    ///
+    /// #Returns
+    /// * SimReport - The number of orders, cancels and amends sent and the trade tape
+    pub fn run(&mut self, orderbook: &mut Orderbook, events: usize) -> SimReport {
+        self.run_while(orderbook, |_, sent| sent < events)
+    }
+
+    /// Run the simulation against an orderbook for a simulated duration.
+    /// The receiver of the orderbook channel must be kept alive during the run.
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook, its clock is replaced by the simulated clock
+    /// * 'duration' - The simulated duration
+    ///
+    /// #Returns
+    /// * SimReport - The number of orders, cancels and amends sent and the trade tape
+    pub fn run_for(&mut self, orderbook: &mut Orderbook, duration: Duration) -> SimReport {
+        let end = self.time() + duration.as_nanos() as u64;
+        self.run_while(orderbook, |simulator, _| simulator.time() < end)
+    }
+
+    fn run_while(
+        &mut self,
+        orderbook: &mut Orderbook,
+        mut proceed: impl FnMut(&MarketSimulator, usize) -> bool,
+    ) -> SimReport {
+        orderbook.clock = Arc::new(self.clock.clone());
+        let tape = Arc::new(TapeRecorder::default());
+        orderbook.add_observer(tape.clone());
+        let mut report = SimReport::default();
+        while proceed(self, report.orders + report.cancels + report.amends) {
+            let event = self.next_event(orderbook);
+            match event.command {
+                FlowCommand::Add(_) => report.orders += 1,
+                FlowCommand::Cancel { .. } => report.cancels += 1,
+                FlowCommand::AmendPrice { .. } | FlowCommand::AmendQuantity { .. } => {
+                    report.amends += 1
+                }
+            }
+            MarketSimulator::apply(orderbook, &event);
+        }
+        orderbook
+            .observers
+            .retain(|observer| !std::ptr::addr_eq(Arc::as_ptr(observer), Arc::as_ptr(&tape)));
+        report.tape = std::mem::take(&mut *tape.trades.lock().unwrap());
+        report.mid = self.mid;
+        report.time = self.time();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+
+    #[test]
+    fn test_simulation_builds_a_book_and_a_tape() {
+        let config = SimConfig::default();
+        let (tx, _rx) = unbounded();
+        let mut orderbook = Orderbook::new(config.symbol, tx);
+        let mut simulator = MarketSimulator::new(config, 7);
+        let start = simulator.time();
+        let report = simulator.run(&mut orderbook, 2_000);
+
+        assert_eq!(report.orders + report.cancels, 2_000);
+        assert!(report.cancels > 0);
+        assert!(!report.tape.is_empty());
+        assert!(!orderbook.bids.is_empty() && !orderbook.asks.is_empty());
+        assert!(orderbook.observers.is_empty());
+        assert!(orderbook.verify_invariants().is_ok());
+        // About 2 seconds at 1000 arrivals per second
+        assert!(report.time - start > 1_000_000_000 && report.time - start < 3_000_000_000);
+        assert!(report
+            .tape
+            .windows(2)
+            .all(|t| t[0].created_at <= t[1].created_at));
+
+        // Same seed, same run
+        let (tx, _rx) = unbounded();
+        let mut replay = Orderbook::new(config.symbol, tx);
+        let again = MarketSimulator::new(config, 7).run(&mut replay, 2_000);
+        assert_eq!(
+            again
+                .tape
+                .iter()
+                .map(|t| (t.price, t.quantity))
+                .collect::<Vec<_>>(),
+            report
+                .tape
+                .iter()
+                .map(|t| (t.price, t.quantity))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_run_for_a_duration() {
+        let config = SimConfig {
+            arrival_rate: 100.0,
+            ..Default::default()
+        };
+        let (tx, _rx) = unbounded();
+        let mut orderbook = Orderbook::new(config.symbol, tx);
+        let mut simulator = MarketSimulator::new(config, 1);
+        let report = simulator.run_for(&mut orderbook, Duration::from_secs(10));
+        let sent = report.orders + report.cancels;
+        assert!(sent > 800 && sent < 1_200);
+    }
+}
@@ -0,0 +1,49 @@
+use super::{SinkRecord, SinkTransport};
+use futures_util::future::join_all;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use std::io::Error;
+use std::time::Duration;
+
+/// Kafka transport, each record is produced to its symbol topic keyed by the symbol
+pub struct KafkaSink {
+    producer: FutureProducer,
+    /// How long a record may wait in the producer queue
+    queue_timeout: Duration,
+}
+
+impl KafkaSink {
+    /// Create a producer for a list of brokers, e.g. "localhost:9092"
+    pub fn new(brokers: &str) -> Result<KafkaSink, Error> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()
+            .map_err(Error::other)?;
+        Ok(KafkaSink::with_producer(producer))
+    }
+
+    /// Use a producer configured by the caller
+    pub fn with_producer(producer: FutureProducer) -> KafkaSink {
+        KafkaSink {
+            producer,
+            queue_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl SinkTransport for KafkaSink {
+    async fn send_batch(&mut self, records: &[SinkRecord]) -> Result<(), Error> {
+        let deliveries = records.iter().map(|record| {
+            self.producer.send(
+                FutureRecord::to(&record.topic)
+                    .key(&record.key)
+                    .payload(&record.payload),
+                self.queue_timeout,
+            )
+        });
+        for delivery in join_all(deliveries).await {
+            delivery.map_err(|(e, _)| Error::other(e))?;
+        }
+        Ok(())
+    }
+}
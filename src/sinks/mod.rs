@@ -0,0 +1,250 @@
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+
+use crate::enums::sink_format::SinkFormat;
+use crate::structs::orderbook_update::OrderbookUpdate;
+use futures_util::{Stream, StreamExt};
+use std::future::Future;
+use std::io::Error;
+use std::time::Duration;
+use tokio::time::{sleep, timeout_at, Instant};
+
+/// How the updates are forwarded to a sink
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkConfig {
+    /// The updates of a symbol go to the topic `<topic_prefix>.<symbol>`
+    pub topic_prefix: String,
+    pub format: SinkFormat,
+    /// Maximum number of records sent at once
+    pub batch_size: usize,
+    /// How long a batch waits for more records once it holds one
+    pub linger: Duration,
+    /// Number of times a failed batch is sent again before its records are dropped
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled on every retry
+    pub retry_backoff: Duration,
+}
+
+impl Default for SinkConfig {
+    fn default() -> SinkConfig {
+        SinkConfig {
+            topic_prefix: String::from("orderbook"),
+            format: SinkFormat::Json,
+            batch_size: 100,
+            linger: Duration::from_millis(10),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+/// An encoded update, keyed by its symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkRecord {
+    pub topic: String,
+    /// The symbol, so that a partitioned sink keeps the updates of a symbol in order
+    pub key: String,
+    pub payload: Vec<u8>,
+}
+
+impl SinkRecord {
+    pub fn encode(update: &OrderbookUpdate, config: &SinkConfig) -> Result<SinkRecord, Error> {
+        let payload = match config.format {
+            SinkFormat::Json => serde_json::to_vec(update)?,
+            #[cfg(feature = "proto")]
+            SinkFormat::Protobuf => {
+                prost::Message::encode_to_vec(&crate::proto::OrderbookUpdate::from(update))
+            }
+            #[cfg(not(feature = "proto"))]
+            SinkFormat::Protobuf => {
+                return Err(Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "The protobuf format needs the proto feature",
+                ))
+            }
+        };
+        Ok(SinkRecord {
+            topic: format!("{}.{}", config.topic_prefix, update.symbol),
+            key: update.symbol.to_string(),
+            payload,
+        })
+    }
+}
+
+/// Destination of the records, e.g. a Kafka producer or a NATS client
+pub trait SinkTransport {
+    /// Send a batch, the whole batch is sent again if an error is returned
+    fn send_batch(&mut self, records: &[SinkRecord]) -> impl Future<Output = Result<(), Error>>;
+}
+
+/// Counters of a forwarding run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SinkStats {
+    /// Records delivered
+    pub sent: u64,
+    /// Batches delivered
+    pub batches: u64,
+    /// Batches sent again after a failure
+    pub retries: u64,
+    /// Records lost because they could not be encoded or their batch kept failing
+    pub dropped: u64,
+}
+
+/// Forward a stream of updates to a transport until the stream ends, in batches of
+/// `batch_size` records or whatever arrived within `linger`. Delivery is at least once:
+/// a failed batch is sent again in full.
+///
+/// #Parameters
+/// * 'updates' - The updates, e.g. `orderbooks_manager.subscribe_updates()`
+/// * 'transport' - The sink
+/// * 'config' - The topics, format, batching and retries
+///
+/// #Returns
+/// * SinkStats - What was sent, retried and dropped
+pub async fn forward_updates<S, T>(
+    mut updates: S,
+    transport: &mut T,
+    config: &SinkConfig,
+) -> SinkStats
+where
+    S: Stream<Item = OrderbookUpdate> + Unpin,
+    T: SinkTransport,
+{
+    let mut stats = SinkStats::default();
+    let batch_size = config.batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut ended = false;
+    while !ended {
+        let Some(update) = updates.next().await else {
+            break;
+        };
+        let deadline = Instant::now() + config.linger;
+        let mut next = Some(update);
+        while let Some(update) = next.take() {
+            match SinkRecord::encode(&update, config) {
+                Ok(record) => batch.push(record),
+                Err(_) => stats.dropped += 1,
+            }
+            if batch.len() >= batch_size {
+                break;
+            }
+            match timeout_at(deadline, updates.next()).await {
+                Ok(Some(update)) => next = Some(update),
+                Ok(None) => ended = true,
+                Err(_) => {}
+            }
+        }
+        if !batch.is_empty() {
+            send_with_retries(transport, &batch, config, &mut stats).await;
+            batch.clear();
+        }
+    }
+    stats
+}
+
+async fn send_with_retries<T: SinkTransport>(
+    transport: &mut T,
+    batch: &[SinkRecord],
+    config: &SinkConfig,
+    stats: &mut SinkStats,
+) {
+    let mut backoff = config.retry_backoff;
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            stats.retries += 1;
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+        if transport.send_batch(batch).await.is_ok() {
+            stats.sent += batch.len() as u64;
+            stats.batches += 1;
+            return;
+        }
+    }
+    stats.dropped += batch.len() as u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use crate::structs::ids::Symbol;
+    use futures_util::stream;
+    use std::io::ErrorKind;
+
+    #[derive(Debug, Default)]
+    struct MemoryTransport {
+        failures: u32,
+        batches: Vec<Vec<SinkRecord>>,
+    }
+
+    impl SinkTransport for MemoryTransport {
+        async fn send_batch(&mut self, records: &[SinkRecord]) -> Result<(), Error> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(Error::new(ErrorKind::BrokenPipe, "Broker unavailable"));
+            }
+            self.batches.push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    fn update(symbol: Symbol, sequence: u64) -> OrderbookUpdate {
+        OrderbookUpdate {
+            symbol,
+            sequence,
+            update_type: OrderbookUpdateType::Place,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_retries() {
+        let config = SinkConfig {
+            batch_size: 2,
+            retry_backoff: Duration::from_millis(1),
+            max_retries: 1,
+            ..Default::default()
+        };
+        let updates = stream::iter(vec![
+            update(Symbol(1), 1),
+            update(Symbol(2), 1),
+            update(Symbol(1), 2),
+        ]);
+        let mut transport = MemoryTransport {
+            failures: 1,
+            ..Default::default()
+        };
+        let stats = forward_updates(updates, &mut transport, &config).await;
+        assert_eq!(
+            stats,
+            SinkStats {
+                sent: 3,
+                batches: 2,
+                retries: 1,
+                dropped: 0
+            }
+        );
+        assert_eq!(transport.batches[0].len(), 2);
+        let record = &transport.batches[1][0];
+        assert_eq!(record.topic, "orderbook.1");
+        assert_eq!(record.key, "1");
+        let decoded: OrderbookUpdate = serde_json::from_slice(&record.payload).unwrap();
+        assert_eq!(decoded, update(Symbol(1), 2));
+
+        let mut transport = MemoryTransport {
+            failures: 2,
+            ..Default::default()
+        };
+        let stats = forward_updates(
+            stream::iter(vec![update(Symbol(1), 1)]),
+            &mut transport,
+            &config,
+        )
+        .await;
+        assert_eq!(stats.dropped, 1);
+        assert!(transport.batches.is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use super::{SinkRecord, SinkTransport};
+use async_nats::{Client, HeaderMap};
+use std::io::{Error, ErrorKind};
+
+/// NATS transport, each record is published on its symbol subject with the symbol in the `Orderbook-Symbol` header
+pub struct NatsSink {
+    client: Client,
+}
+
+impl NatsSink {
+    /// Connect to a server, e.g. "nats://localhost:4222"
+    pub async fn connect(url: &str) -> Result<NatsSink, Error> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| Error::new(ErrorKind::ConnectionRefused, e))?;
+        Ok(NatsSink::with_client(client))
+    }
+
+    pub fn with_client(client: Client) -> NatsSink {
+        NatsSink { client }
+    }
+}
+
+impl SinkTransport for NatsSink {
+    async fn send_batch(&mut self, records: &[SinkRecord]) -> Result<(), Error> {
+        for record in records {
+            let mut headers = HeaderMap::new();
+            headers.insert("Orderbook-Symbol", record.key.as_str());
+            self.client
+                .publish_with_headers(record.topic.clone(), headers, record.payload.clone().into())
+                .await
+                .map_err(Error::other)?;
+        }
+        self.client
+            .flush()
+            .await
+            .map_err(Error::other)
+    }
+}
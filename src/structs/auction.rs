@@ -0,0 +1,148 @@
+use super::order::Order;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a call auction
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AuctionResult {
+    /// The equilibrium price, None if the book does not cross
+    pub price: Option<f64>,
+    /// The quantity executed at the equilibrium price
+    pub volume: f64,
+    /// Buy quantity minus sell quantity left unmatched at the equilibrium price
+    pub imbalance: f64,
+}
+
+/// Compute the equilibrium price of a call auction: the limit price maximizing the executable volume,
+/// ties are broken by the smallest imbalance then by the closest price to the reference price.
+/// Only the prices within the bounds are candidates, the book does not uncross when none of them executes.
+///
+/// #Parameters
+/// * 'bids' - The buy orders, the hidden quantity of icebergs is included
+/// * 'asks' - The sell orders, the hidden quantity of icebergs is included
+/// * 'reference' - The reference price, e.g. the last traded price
+/// * 'bounds' - The lowest and the highest equilibrium prices allowed, e.g. a collar around the reference price, None for no bounds
+pub fn equilibrium(
+    bids: &[Order],
+    asks: &[Order],
+    reference: Option<f64>,
+    bounds: Option<(f64, f64)>,
+) -> AuctionResult {
+    let bids = sorted_quantities(bids);
+    let asks = sorted_quantities(asks);
+    let mut prices: Vec<f64> = bids.iter().chain(asks.iter()).map(|l| l.0).collect();
+    if let Some((lowest, highest)) = bounds {
+        prices.retain(|&price| lowest <= price && price <= highest);
+    }
+    prices.sort_by(f64::total_cmp);
+    prices.dedup();
+    // Demand at a price is the quantity of the bids from it up, summed once from the highest bid
+    let mut demands = vec![0.0; bids.len() + 1];
+    for (index, level) in bids.iter().enumerate().rev() {
+        demands[index] = demands[index + 1] + level.1;
+    }
+
+    let mut best = AuctionResult::default();
+    let (mut next_bid, mut next_ask, mut supply) = (0, 0, 0.0);
+    for price in prices {
+        while next_bid < bids.len() && bids[next_bid].0 < price {
+            next_bid += 1;
+        }
+        while next_ask < asks.len() && asks[next_ask].0 <= price {
+            supply += asks[next_ask].1;
+            next_ask += 1;
+        }
+        let demand = demands[next_bid];
+        let candidate = AuctionResult {
+            price: Some(price),
+            volume: demand.min(supply),
+            imbalance: demand - supply,
+        };
+        if candidate.volume > 0.0 && is_better(&candidate, &best, reference) {
+            best = candidate;
+        }
+    }
+    if best.price.is_none() {
+        best.imbalance = 0.0;
+    }
+    best
+}
+
+/// The (price, quantity) of the priced orders, the hidden quantity of icebergs included, from the lowest price
+fn sorted_quantities(orders: &[Order]) -> Vec<(f64, f64)> {
+    let mut quantities: Vec<(f64, f64)> = orders
+        .iter()
+        .filter_map(|o| o.price.map(|price| (price, o.quantity + o.hidden_quantity)))
+        .collect();
+    quantities.sort_by(|a, b| a.0.total_cmp(&b.0));
+    quantities
+}
+
+fn is_better(candidate: &AuctionResult, best: &AuctionResult, reference: Option<f64>) -> bool {
+    let Some(best_price) = best.price else {
+        return true;
+    };
+    if candidate.volume != best.volume {
+        return candidate.volume > best.volume;
+    }
+    if candidate.imbalance.abs() != best.imbalance.abs() {
+        return candidate.imbalance.abs() < best.imbalance.abs();
+    }
+    match (reference, candidate.price) {
+        (Some(reference), Some(price)) => {
+            (price - reference).abs() < (best_price - reference).abs()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use ulid::Ulid;
+
+    fn order(side: OrderSide, quantity: f64, price: f64) -> Order {
+        Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            side,
+            quantity,
+            Some(price),
+            OrderType::Limit,
+        )
+    }
+
+    #[test]
+    fn test_equilibrium_maximizes_volume() {
+        let bids = vec![
+            order(OrderSide::Buy, 3.0, 102.0),
+            order(OrderSide::Buy, 2.0, 101.0),
+            order(OrderSide::Buy, 4.0, 99.0),
+        ];
+        let asks = vec![
+            order(OrderSide::Sell, 1.0, 98.0),
+            order(OrderSide::Sell, 3.0, 100.0),
+            order(OrderSide::Sell, 5.0, 103.0),
+        ];
+        // At 100 and 101: demand 5, supply 4
+        let result = equilibrium(&bids, &asks, Some(101.0), None);
+        assert_eq!(result.price, Some(101.0));
+        assert_eq!(result.volume, 4.0);
+        assert_eq!(result.imbalance, 1.0);
+        assert_eq!(equilibrium(&bids, &asks, None, None).price, Some(100.0));
+        // 100 and 101 are out of the bounds
+        let bounded = equilibrium(&bids, &asks, Some(101.0), Some((98.0, 99.0)));
+        assert_eq!((bounded.price, bounded.volume), (Some(99.0), 1.0));
+    }
+
+    #[test]
+    fn test_no_equilibrium_without_cross() {
+        let bids = vec![order(OrderSide::Buy, 1.0, 99.0)];
+        let asks = vec![order(OrderSide::Sell, 1.0, 100.0)];
+        assert_eq!(
+            equilibrium(&bids, &asks, None, None),
+            AuctionResult::default()
+        );
+    }
+}
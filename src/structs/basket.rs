@@ -0,0 +1,319 @@
+use super::ids::{OrderId, Symbol};
+use super::order::Order;
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::order_status::OrderStatus;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Order of a basket, as seen through the updates of its orderbook
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BasketLeg {
+    pub order_id: OrderId,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    /// Total quantity of the order, including the hidden size of an iceberg
+    pub quantity: f64,
+    #[serde(rename = "filledQuantity")]
+    pub filled_quantity: f64,
+    pub status: OrderStatus,
+}
+
+impl BasketLeg {
+    pub fn new(order: &Order) -> BasketLeg {
+        BasketLeg {
+            order_id: order.id,
+            symbol: order.symbol,
+            side: order.side,
+            quantity: order.quantity + order.hidden_quantity,
+            filled_quantity: 0.0,
+            status: OrderStatus::Open,
+        }
+    }
+
+    /// Whether the leg left its orderbook
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.status,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Expired
+        )
+    }
+}
+
+/// Orders submitted together across symbols under a same basket ID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Basket {
+    #[serde(rename = "basketId")]
+    pub basket_id: u128,
+    pub legs: Vec<BasketLeg>,
+}
+
+impl Basket {
+    /// Aggregated status of the legs: Filled once they are all filled, Cancelled once they are all done
+    /// without being all filled, PartiallyFilled as soon as one of them traded, Open otherwise
+    pub fn status(&self) -> OrderStatus {
+        if self
+            .legs
+            .iter()
+            .all(|leg| leg.status == OrderStatus::Filled)
+        {
+            OrderStatus::Filled
+        } else if self.legs.iter().all(BasketLeg::is_done) {
+            OrderStatus::Cancelled
+        } else if self.legs.iter().any(|leg| leg.filled_quantity > 0.0) {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Open
+        }
+    }
+
+    /// Quantity executed over all the legs
+    pub fn filled_quantity(&self) -> f64 {
+        self.legs.iter().map(|leg| leg.filled_quantity).sum()
+    }
+
+    /// Legs still resting or waiting in their orderbook
+    pub fn open_legs(&self) -> impl Iterator<Item = &BasketLeg> {
+        self.legs.iter().filter(|leg| !leg.is_done())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.legs.iter().all(BasketLeg::is_done)
+    }
+}
+
+/// Event emitted when a basket is submitted or one of its legs changes, with the state of the whole basket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasketEvent {
+    pub basket: Basket,
+    /// Aggregated status of the basket
+    pub status: OrderStatus,
+    /// The leg which changed, None when the basket is submitted
+    #[serde(rename = "orderId")]
+    pub order_id: Option<OrderId>,
+}
+
+#[derive(Debug, Default)]
+struct BasketState {
+    baskets: HashMap<u128, Basket>,
+    /// Basket of each leg
+    legs: HashMap<OrderId, u128>,
+    listeners: Vec<Sender<BasketEvent>>,
+}
+
+impl BasketState {
+    fn publish(&mut self, basket_id: u128, order_id: Option<OrderId>) {
+        let Some(basket) = self.baskets.get(&basket_id) else {
+            return;
+        };
+        let event = BasketEvent {
+            basket: basket.clone(),
+            status: basket.status(),
+            order_id,
+        };
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+        // A basket whose legs are all done is forgotten once its last event is out
+        if basket.is_done() {
+            for leg in basket.legs.iter() {
+                self.legs.remove(&leg.order_id);
+            }
+            self.baskets.remove(&basket_id);
+        }
+    }
+}
+
+/// Registry of the live baskets, fed with the updates of the orderbooks to follow their legs
+#[derive(Debug, Clone, Default)]
+pub struct BasketRegistry {
+    state: Arc<Mutex<BasketState>>,
+}
+
+impl BasketRegistry {
+    pub fn new() -> BasketRegistry {
+        BasketRegistry::default()
+    }
+
+    /// Register a basket before its orders are submitted
+    ///
+    /// #Parameters
+    /// * 'basket_id' - The basket ID
+    /// * 'orders' - The orders of the basket
+    ///
+    /// #Returns
+    /// * bool - False if a live basket already has this ID
+    pub fn register(&self, basket_id: u128, orders: &[Order]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.baskets.contains_key(&basket_id) {
+            return false;
+        }
+        let basket = Basket {
+            basket_id,
+            legs: orders.iter().map(BasketLeg::new).collect(),
+        };
+        for leg in basket.legs.iter() {
+            state.legs.insert(leg.order_id, basket_id);
+        }
+        state.baskets.insert(basket_id, basket);
+        state.publish(basket_id, None);
+        true
+    }
+
+    /// Current state of a live basket
+    pub fn get(&self, basket_id: u128) -> Option<Basket> {
+        self.state.lock().unwrap().baskets.get(&basket_id).cloned()
+    }
+
+    /// Basket of an order, None if the order is not the leg of a live basket
+    pub fn basket_of(&self, order_id: OrderId) -> Option<u128> {
+        self.state.lock().unwrap().legs.get(&order_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().baskets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().baskets.is_empty()
+    }
+
+    /// Receive every basket event emitted from now on
+    pub fn subscribe(&self) -> Receiver<BasketEvent> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+
+    /// Mark a leg which was not submitted to its orderbook as cancelled
+    pub fn drop_leg(&self, order: &Order) {
+        self.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            order: Some(*order),
+            cancel_id: Some(order.id),
+            ..Default::default()
+        });
+    }
+
+    /// Update the legs from an update published by an orderbook and emit an event for each leg which changed
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        if state.legs.is_empty() {
+            return;
+        }
+        let changes: Vec<(OrderId, f64, Option<OrderStatus>)> = match update.update_type {
+            OrderbookUpdateType::NewTrades => update.trade.as_ref().map_or(Vec::new(), |trade| {
+                vec![
+                    (trade.buy_order_id, trade.quantity, None),
+                    (trade.sell_order_id, trade.quantity, None),
+                ]
+            }),
+            OrderbookUpdateType::Filled => update
+                .filled_id
+                .map(|id| (id, 0.0, Some(OrderStatus::Filled)))
+                .into_iter()
+                .collect(),
+            // A cancel of an order which is not in the book carries no order
+            OrderbookUpdateType::Cancel => update
+                .order
+                .map(|o| (o.id, 0.0, Some(OrderStatus::Cancelled)))
+                .into_iter()
+                .collect(),
+            OrderbookUpdateType::Expired => update
+                .order
+                .map(|o| (o.id, 0.0, Some(OrderStatus::Expired)))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (order_id, filled, status) in changes {
+            let Some(&basket_id) = state.legs.get(&order_id) else {
+                continue;
+            };
+            let Some(leg) = state
+                .baskets
+                .get_mut(&basket_id)
+                .and_then(|basket| basket.legs.iter_mut().find(|leg| leg.order_id == order_id))
+            else {
+                continue;
+            };
+            if leg.is_done() {
+                continue;
+            }
+            leg.filled_quantity += filled;
+            // A market order is not in the book, its last trade fills it
+            leg.status = match status {
+                Some(status) => status,
+                None if leg.filled_quantity >= leg.quantity => OrderStatus::Filled,
+                None => OrderStatus::PartiallyFilled,
+            };
+            // The trade of the last fill comes after the Filled update and is skipped
+            if leg.status == OrderStatus::Filled {
+                leg.filled_quantity = leg.quantity;
+            }
+            state.publish(basket_id, Some(order_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::ids::UserId;
+
+    #[test]
+    fn test_basket_status_follows_the_legs() {
+        let registry = BasketRegistry::new();
+        let events = registry.subscribe();
+        let buy = Order::new(
+            UserId(1),
+            Symbol(10),
+            OrderSide::Buy,
+            2.0,
+            Some(100.0),
+            OrderType::Limit,
+        );
+        let sell = Order::new(
+            UserId(1),
+            Symbol(20),
+            OrderSide::Sell,
+            1.0,
+            Some(50.0),
+            OrderType::Limit,
+        );
+        assert!(registry.register(7, &[buy, sell]));
+        assert!(!registry.register(7, &[buy]));
+        assert_eq!(registry.basket_of(sell.id), Some(7));
+
+        registry.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Filled,
+            filled_id: Some(sell.id),
+            ..Default::default()
+        });
+        let basket = registry.get(7).unwrap();
+        assert_eq!(basket.status(), OrderStatus::PartiallyFilled);
+        assert_eq!(basket.open_legs().count(), 1);
+
+        registry.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            order: Some(buy),
+            cancel_id: Some(buy.id),
+            ..Default::default()
+        });
+        let statuses: Vec<_> = events.try_iter().map(|e| (e.order_id, e.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (None, OrderStatus::Open),
+                (Some(sell.id), OrderStatus::PartiallyFilled),
+                (Some(buy.id), OrderStatus::Cancelled),
+            ]
+        );
+        // Done baskets are forgotten
+        assert!(registry.is_empty());
+        assert_eq!(registry.basket_of(buy.id), None);
+    }
+}
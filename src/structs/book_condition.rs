@@ -0,0 +1,39 @@
+use super::orderbook::Orderbook;
+use serde::{Deserialize, Serialize};
+
+/// Condition on the state of an orderbook holding a conditional order until it is met,
+/// evaluated on the best levels of the book after each change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BookCondition {
+    /// The mid price is at least the price
+    MidAtLeast(f64),
+    /// The mid price is at most the price
+    MidAtMost(f64),
+    /// The best ask minus the best bid is strictly below the spread
+    SpreadBelow(f64),
+    /// The quantity at the best bid is strictly above the quantity
+    BidVolumeAbove(f64),
+    /// The quantity at the best ask is strictly above the quantity
+    AskVolumeAbove(f64),
+}
+
+impl BookCondition {
+    /// Whether the book meets the condition, the conditions on the mid price and the spread need both sides
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook
+    pub fn is_met(&self, orderbook: &Orderbook) -> bool {
+        let (bids, asks) = orderbook.depth(1);
+        let (bid, ask) = (bids.first(), asks.first());
+        let mid = bid.zip(ask).map(|(bid, ask)| (bid.price + ask.price) / 2.0);
+        match *self {
+            BookCondition::MidAtLeast(price) => mid.is_some_and(|mid| mid >= price),
+            BookCondition::MidAtMost(price) => mid.is_some_and(|mid| mid <= price),
+            BookCondition::SpreadBelow(spread) => bid
+                .zip(ask)
+                .is_some_and(|(bid, ask)| ask.price - bid.price < spread),
+            BookCondition::BidVolumeAbove(quantity) => bid.is_some_and(|l| l.quantity > quantity),
+            BookCondition::AskVolumeAbove(quantity) => ask.is_some_and(|l| l.quantity > quantity),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Aggregate figures of an orderbook, on the visible quantities
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct BookMetrics {
+    /// Quantity resting on the bid side
+    pub bid_volume: f64,
+    /// Quantity resting on the ask side
+    pub ask_volume: f64,
+    /// Number of orders resting on the bid side
+    pub bid_orders: usize,
+    /// Number of orders resting on the ask side
+    pub ask_orders: usize,
+    /// Price times quantity of the best bid level, 0.0 if the side is empty
+    pub bid_notional_at_top: f64,
+    /// Price times quantity of the best ask level, 0.0 if the side is empty
+    pub ask_notional_at_top: f64,
+}
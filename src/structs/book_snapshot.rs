@@ -0,0 +1,215 @@
+use super::book_metrics::BookMetrics;
+use super::ids::{OrderId, Symbol};
+use super::order::{Metadata, Order};
+use super::orderbook_sum::OrderBookSummarized;
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crate::formats::checksum::{book_checksum, CHECKSUM_DEPTH};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Full state of an orderbook at a given sequence number.
+/// Bids and asks are stored best price first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub symbol: Symbol,
+    pub sequence: u64,
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+    /// Metadata of the resting orders which have some, empty in the view shown to the market
+    #[serde(default)]
+    pub metadata: HashMap<OrderId, Metadata>,
+}
+
+impl BookSnapshot {
+    pub fn new(symbol: Symbol, sequence: u64, bids: Vec<Order>, asks: Vec<Order>) -> BookSnapshot {
+        BookSnapshot {
+            symbol,
+            sequence,
+            bids,
+            asks,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// with_metadata attaches the metadata of the resting orders to the snapshot
+    pub fn with_metadata(mut self, metadata: HashMap<OrderId, Metadata>) -> BookSnapshot {
+        self.metadata = metadata;
+        self
+    }
+
+    /// public_view returns the snapshot as shown to the market, without the hidden size of the
+    /// icebergs nor the metadata of the orders
+    pub fn public_view(&self) -> BookSnapshot {
+        BookSnapshot::new(
+            self.symbol,
+            self.sequence,
+            self.bids.iter().map(Order::public_view).collect(),
+            self.asks.iter().map(Order::public_view).collect(),
+        )
+    }
+
+    /// apply an incremental update on top of the snapshot, the sequence is not checked
+    pub fn apply(&mut self, update: &OrderbookUpdate) {
+        match update.update_type {
+            OrderbookUpdateType::Place => {
+                if let Some(order) = update.order {
+                    self.insert(order);
+                    self.attach(order.id, update.metadata.as_ref());
+                }
+            }
+            OrderbookUpdateType::Amended
+            | OrderbookUpdateType::PartiallyFilled
+            | OrderbookUpdateType::Repriced
+            | OrderbookUpdateType::Replace => {
+                if let Some(order) = update.order {
+                    self.remove(order.id);
+                    self.insert(order);
+                    self.attach(order.id, update.metadata.as_ref());
+                }
+            }
+            OrderbookUpdateType::Cancel => {
+                if let Some(id) = update.cancel_id {
+                    self.remove(id);
+                }
+            }
+            OrderbookUpdateType::Filled => {
+                if let Some(id) = update.filled_id {
+                    self.remove(id);
+                }
+            }
+            OrderbookUpdateType::Expired | OrderbookUpdateType::Migrated => {
+                if let Some(order) = update.order {
+                    self.remove(order.id);
+                }
+            }
+            _ => {}
+        }
+        self.sequence = self.sequence.max(update.sequence);
+    }
+
+    /// insert an order behind the orders with a better price, and behind the orders at the same
+    /// price which arrived before it
+    fn insert(&mut self, order: Order) {
+        let Some(price) = order.price else {
+            return;
+        };
+        let arrived_after = |o: &Order| {
+            o.price == Some(price) && order.arrival_seq > 0 && o.arrival_seq > order.arrival_seq
+        };
+        match order.side {
+            OrderSide::Buy => {
+                let index = self
+                    .bids
+                    .iter()
+                    .position(|o| o.price.unwrap_or_default() < price || arrived_after(o))
+                    .unwrap_or(self.bids.len());
+                self.bids.insert(index, order);
+            }
+            OrderSide::Sell => {
+                let index = self
+                    .asks
+                    .iter()
+                    .position(|o| o.price.unwrap_or_default() > price || arrived_after(o))
+                    .unwrap_or(self.asks.len());
+                self.asks.insert(index, order);
+            }
+        }
+    }
+
+    fn remove(&mut self, order_id: OrderId) {
+        self.bids.retain(|o| o.id != order_id);
+        self.asks.retain(|o| o.id != order_id);
+        self.metadata.remove(&order_id);
+    }
+
+    fn attach(&mut self, order_id: OrderId, metadata: Option<&Metadata>) {
+        if let Some(metadata) = metadata {
+            self.metadata.insert(order_id, metadata.clone());
+        }
+    }
+
+    /// orders of a side, best price first
+    pub fn side(&self, side: OrderSide) -> &[Order] {
+        match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        }
+    }
+
+    /// (price, quantity) of the price levels of a side, best price first
+    pub fn price_levels(&self, side: OrderSide) -> Vec<(f64, f64)> {
+        Self::levels(self.side(side).iter())
+            .into_iter()
+            .map(|(price, quantity, _)| (price, quantity))
+            .collect()
+    }
+
+    /// quantity resting at a price on a side, 0.0 if there is no level at this price
+    pub fn level_quantity(&self, side: OrderSide, price: f64) -> f64 {
+        self.side(side)
+            .iter()
+            .filter(|o| o.price == Some(price))
+            .map(|o| o.quantity)
+            .sum()
+    }
+
+    /// checksum of the best levels of the snapshot, equal to the checksum of the orderbook at the
+    /// same sequence number
+    pub fn checksum(&self) -> u32 {
+        let pairs = |side| -> Vec<[f64; 2]> {
+            self.price_levels(side)
+                .into_iter()
+                .map(|(price, quantity)| [price, quantity])
+                .collect()
+        };
+        book_checksum(
+            &pairs(OrderSide::Buy),
+            &pairs(OrderSide::Sell),
+            CHECKSUM_DEPTH,
+        )
+    }
+
+    /// mid price of the snapshot, 0.0 if one side is empty
+    pub fn mid_price(&self) -> f64 {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) => (bid.price.unwrap() + ask.price.unwrap()) / 2.0,
+            _ => 0.0,
+        }
+    }
+
+    /// summarize the snapshot the same way the orderbook does, one entry per price level
+    pub fn summarize(&self) -> OrderBookSummarized {
+        let mut bids = Self::levels(self.bids.iter().rev());
+        bids.reverse();
+        let asks = Self::levels(self.asks.iter());
+        let metrics = BookMetrics {
+            bid_volume: self.bids.iter().map(|o| o.quantity).sum(),
+            ask_volume: self.asks.iter().map(|o| o.quantity).sum(),
+            bid_orders: self.bids.len(),
+            ask_orders: self.asks.len(),
+            bid_notional_at_top: bids.first().map_or(0.0, |l| l.0 * l.1),
+            ask_notional_at_top: asks.first().map_or(0.0, |l| l.0 * l.1),
+        };
+        OrderBookSummarized::new(bids, self.mid_price(), asks).with_metrics(metrics)
+    }
+
+    /// (price, quantity, cumulated quantity) of the levels of orders sorted by price
+    fn levels<'a>(orders: impl Iterator<Item = &'a Order>) -> Vec<(f64, f64, f64)> {
+        let mut levels: Vec<(f64, f64, f64)> = Vec::new();
+        let mut sum = 0.0;
+        for order in orders {
+            let price = order.price.unwrap();
+            sum += order.quantity;
+            match levels.last_mut() {
+                Some(level) if level.0 == price => {
+                    level.1 += order.quantity;
+                    level.2 = sum;
+                }
+                _ => levels.push((price, order.quantity, sum)),
+            }
+        }
+        levels
+    }
+}
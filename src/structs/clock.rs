@@ -0,0 +1,88 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Source of the timestamps of the orders, trades and updates
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Wall-clock time in nanoseconds since UNIX epoch
+    fn now(&self) -> u64;
+
+    /// Monotonic time in nanoseconds, only meaningful as a difference between two readings
+    fn monotonic(&self) -> u64;
+
+    /// Wall-clock time in milliseconds since UNIX epoch, the unit of the order expiries
+    fn now_millis(&self) -> u64 {
+        self.now() / 1_000_000
+    }
+}
+
+/// Clock reading the system time, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    }
+
+    fn monotonic(&self) -> u64 {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+/// Clock only moving when told to, for deterministic tests.
+/// Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Create a clock set to a time in nanoseconds since UNIX epoch
+    pub fn new(nanos: u64) -> MockClock {
+        MockClock {
+            nanos: Arc::new(AtomicU64::new(nanos)),
+        }
+    }
+
+    pub fn set(&self, nanos: u64) {
+        self.nanos.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.nanos.load(Ordering::SeqCst)
+    }
+
+    fn monotonic(&self) -> u64 {
+        self.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1_000_000);
+        let shared = clock.clone();
+        clock.advance(Duration::from_millis(2));
+        assert_eq!(shared.now(), 3_000_000);
+        assert_eq!(shared.now_millis(), 3);
+        let system = SystemClock;
+        assert!(system.monotonic() <= system.monotonic());
+    }
+}
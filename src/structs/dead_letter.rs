@@ -0,0 +1,117 @@
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::delivery_failure_policy::DeliveryFailurePolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Dead letters kept by default, the oldest are lost past it
+pub const DEFAULT_DEAD_LETTER_CAPACITY: usize = 10_000;
+
+/// Update which could not be delivered, with the reason of the failure
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub update: OrderbookUpdate,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+struct DeadLetterState {
+    policy: DeliveryFailurePolicy,
+    capacity: usize,
+    letters: VecDeque<DeadLetter>,
+    /// Dead letters pushed out by newer ones or dropped by the Drop policy
+    lost: u64,
+}
+
+impl Default for DeadLetterState {
+    fn default() -> Self {
+        DeadLetterState {
+            policy: DeliveryFailurePolicy::default(),
+            capacity: DEFAULT_DEAD_LETTER_CAPACITY,
+            letters: VecDeque::new(),
+            lost: 0,
+        }
+    }
+}
+
+/// Bounded buffer of the updates the orderbooks failed to deliver, so that matching goes on when
+/// a consumer is gone. Clones share the same buffer.
+#[derive(Debug, Clone, Default)]
+pub struct DeadLetterQueue {
+    state: Arc<Mutex<DeadLetterState>>,
+}
+
+impl DeadLetterQueue {
+    pub fn new() -> DeadLetterQueue {
+        DeadLetterQueue::default()
+    }
+
+    pub fn set_policy(&self, policy: DeliveryFailurePolicy) {
+        self.state.lock().unwrap().policy = policy;
+    }
+
+    pub fn policy(&self) -> DeliveryFailurePolicy {
+        self.state.lock().unwrap().policy
+    }
+
+    /// Set the number of dead letters kept, the oldest are lost past it
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.capacity = capacity;
+        while state.letters.len() > capacity {
+            state.letters.pop_front();
+            state.lost += 1;
+        }
+    }
+
+    /// Handle an update which could not be delivered according to the policy
+    ///
+    /// #Parameters
+    /// * 'update' - The undelivered update
+    /// * 'reason' - Why the delivery failed
+    pub fn report(&self, update: OrderbookUpdate, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        match state.policy {
+            DeliveryFailurePolicy::Panic => {
+                drop(state);
+                panic!("{reason}: update {} of {}", update.sequence, update.symbol)
+            }
+            DeliveryFailurePolicy::Drop => state.lost += 1,
+            DeliveryFailurePolicy::DeadLetter => {
+                if state.letters.len() >= state.capacity {
+                    state.letters.pop_front();
+                    state.lost += 1;
+                }
+                if state.capacity > 0 {
+                    state.letters.push_back(DeadLetter {
+                        update,
+                        reason: reason.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Copy of the dead letters, the oldest first
+    pub fn peek(&self) -> Vec<DeadLetter> {
+        self.state.lock().unwrap().letters.iter().cloned().collect()
+    }
+
+    /// Take the dead letters out of the queue, the oldest first
+    pub fn drain(&self) -> Vec<DeadLetter> {
+        self.state.lock().unwrap().letters.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().letters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of undelivered updates which were not kept
+    pub fn lost(&self) -> u64 {
+        self.state.lock().unwrap().lost
+    }
+}
@@ -0,0 +1,304 @@
+use super::book_snapshot::BookSnapshot;
+use super::ids::{OrderId, Symbol};
+use super::order_ack::OrderAck;
+use super::orderbook_config::OrderbookConfig;
+use super::orderbooks_manager::OrderbooksManager;
+use super::subscription::Subscription;
+use crate::enums::side::OrderSide;
+use crate::structs::order::Order;
+use crossbeam_channel::{unbounded, Sender};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use tokio::sync::oneshot;
+
+/// Message sent to the worker of a symbol, the result is sent back on `reply`
+#[derive(Debug)]
+pub enum Command {
+    Add {
+        order: Order,
+        /// Monotonic time of the clock of the engine the command was sent at
+        received_at: u64,
+        reply: oneshot::Sender<Result<OrderAck, Error>>,
+    },
+    Cancel {
+        order_id: OrderId,
+        side: OrderSide,
+        reply: oneshot::Sender<Result<OrderAck, Error>>,
+    },
+    /// Amend the price and/or the quantity of an order
+    Amend {
+        order_id: OrderId,
+        side: OrderSide,
+        price: Option<f64>,
+        quantity: Option<f64>,
+        reply: oneshot::Sender<Result<(), Error>>,
+    },
+    Query {
+        reply: oneshot::Sender<Result<BookSnapshot, Error>>,
+    },
+}
+
+impl Command {
+    /// Apply the command to the orderbook of a symbol and send back the result
+    fn run(self, manager: &mut OrderbooksManager, symbol: Symbol) {
+        // the caller may have stopped waiting, the result is then dropped
+        match self {
+            Command::Add {
+                order,
+                received_at,
+                reply,
+            } => {
+                let _ = reply.send(manager.add_order_received(order, received_at));
+            }
+            Command::Cancel {
+                order_id,
+                side,
+                reply,
+            } => {
+                let _ = reply.send(manager.cancel_order(order_id, symbol, side));
+            }
+            Command::Amend {
+                order_id,
+                side,
+                price,
+                quantity,
+                reply,
+            } => {
+                let mut result = Ok(());
+                if let Some(price) = price {
+                    result = manager
+                        .amend_order_price(symbol, order_id, price, side)
+                        .map(|_| ());
+                }
+                if let (Ok(()), Some(quantity)) = (&result, quantity) {
+                    result = manager
+                        .amend_order_quantity(symbol, order_id, quantity, side)
+                        .map(|_| ());
+                }
+                let _ = reply.send(result);
+            }
+            Command::Query { reply } => {
+                let _ = reply.send(manager.snapshot(symbol));
+            }
+        }
+    }
+}
+
+/// Actor-style access to orderbooks for server deployments: each orderbook runs on its own
+/// worker thread, applying the commands of every caller in the order they are received,
+/// and the callers await the result of their commands.
+/// The handle is cheap to clone and can be shared between threads and tasks.
+#[derive(Debug, Clone)]
+pub struct EngineHandle {
+    workers: Arc<RwLock<HashMap<Symbol, Sender<Command>>>>,
+    template: OrderbooksManager,
+}
+
+impl Default for EngineHandle {
+    fn default() -> Self {
+        EngineHandle::with_template(&OrderbooksManager::new())
+    }
+}
+
+impl EngineHandle {
+    pub fn new() -> EngineHandle {
+        EngineHandle::default()
+    }
+
+    /// Create an engine whose workers share the bus, the risk engine, the accounts and the clock of a manager
+    ///
+    /// #Parameters
+    /// * 'template' - The manager the workers are configured from, its orderbooks are not copied
+    pub fn with_template(template: &OrderbooksManager) -> EngineHandle {
+        EngineHandle {
+            workers: Arc::new(RwLock::new(HashMap::new())),
+            template: template.sibling(),
+        }
+    }
+
+    /// Start the worker thread of a new orderbook
+    pub fn spawn_orderbook(&self, symbol: Symbol) -> Result<(), Error> {
+        self.spawn_orderbook_with_config(symbol, OrderbookConfig::default())
+    }
+
+    /// Start the worker thread of a new orderbook behaving as configured
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'config' - The configuration of the orderbook
+    pub fn spawn_orderbook_with_config(
+        &self,
+        symbol: Symbol,
+        config: OrderbookConfig,
+    ) -> Result<(), Error> {
+        let mut workers = self.workers.write().unwrap();
+        if workers.contains_key(&symbol) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "Orderbook already exists",
+            ));
+        }
+        let mut manager = self.template.sibling();
+        let (tx, rx) = unbounded();
+        manager.new_orderbook_with_config(symbol, config);
+        thread::spawn(move || {
+            for command in rx {
+                Command::run(command, &mut manager, symbol);
+            }
+        });
+        workers.insert(symbol, tx);
+        Ok(())
+    }
+
+    /// Stop the worker of an orderbook once it has applied the commands already sent,
+    /// the orderbook is dropped with its orders
+    pub fn stop_orderbook(&self, symbol: Symbol) -> Result<(), Error> {
+        match self.workers.write().unwrap().remove(&symbol) {
+            Some(_) => Ok(()),
+            None => Err(Error::new(ErrorKind::NotFound, "Orderbook not found")),
+        }
+    }
+
+    /// Symbols of the running orderbooks, sorted
+    pub fn list_symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = self.workers.read().unwrap().keys().copied().collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// Send a command to the worker of a symbol without waiting for its result
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'command' - The command, its result is sent on its reply channel
+    pub fn send(&self, symbol: Symbol, command: Command) -> Result<(), Error> {
+        let workers = self.workers.read().unwrap();
+        let Some(worker) = workers.get(&symbol) else {
+            return Err(Error::new(ErrorKind::NotFound, "Orderbook not found"));
+        };
+        worker
+            .send(command)
+            .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Engine stopped"))
+    }
+
+    /// Send a command and wait for its result
+    async fn request<T>(
+        &self,
+        symbol: Symbol,
+        command: impl FnOnce(oneshot::Sender<Result<T, Error>>) -> Command,
+    ) -> Result<T, Error> {
+        let (reply, result) = oneshot::channel();
+        self.send(symbol, command(reply))?;
+        result
+            .await
+            .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Engine stopped"))?
+    }
+
+    pub async fn add_order(&self, order: Order) -> Result<OrderAck, Error> {
+        let received_at = self.template.clock.monotonic();
+        self.request(order.symbol, |reply| Command::Add {
+            order,
+            received_at,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn cancel_order(
+        &self,
+        order_id: OrderId,
+        symbol: Symbol,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.request(symbol, |reply| Command::Cancel {
+            order_id,
+            side,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn amend_order(
+        &self,
+        symbol: Symbol,
+        order_id: OrderId,
+        side: OrderSide,
+        price: Option<f64>,
+        quantity: Option<f64>,
+    ) -> Result<(), Error> {
+        self.request(symbol, |reply| Command::Amend {
+            order_id,
+            side,
+            price,
+            quantity,
+            reply,
+        })
+        .await
+    }
+
+    /// Snapshot of an orderbook, taken after the commands sent before
+    pub async fn query(&self, symbol: Symbol) -> Result<BookSnapshot, Error> {
+        self.request(symbol, |reply| Command::Query { reply }).await
+    }
+
+    /// Subscribe to the updates of every orderbook published from now on
+    pub fn subscribe_updates(&self) -> Subscription {
+        self.template.bus.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use ulid::Ulid;
+
+    #[tokio::test]
+    async fn test_engine_handle() {
+        let engine = EngineHandle::new();
+        let symbols: Vec<Symbol> = (0..2).map(|_| Ulid::new().into()).collect();
+        for symbol in symbols.iter() {
+            engine.spawn_orderbook(*symbol).unwrap();
+        }
+        assert!(engine.spawn_orderbook(symbols[0]).is_err());
+
+        let tasks: Vec<_> = symbols
+            .iter()
+            .map(|&symbol| {
+                let engine = engine.clone();
+                tokio::spawn(async move {
+                    let order = Order::new(
+                        Ulid::new().into(),
+                        symbol,
+                        OrderSide::Buy,
+                        2.0,
+                        Some(1.0),
+                        OrderType::Limit,
+                    );
+                    engine.add_order(order).await.unwrap();
+                    engine
+                        .amend_order(symbol, order.id, order.side, Some(3.0), Some(1.0))
+                        .await
+                        .unwrap();
+                    order
+                })
+            })
+            .collect();
+        for task in tasks {
+            let order = task.await.unwrap();
+            let snapshot = engine.query(order.symbol).await.unwrap();
+            assert_eq!(snapshot.bids.len(), 1);
+            assert_eq!(snapshot.bids[0].price, Some(3.0));
+            assert_eq!(snapshot.bids[0].quantity, 1.0);
+        }
+
+        engine.stop_orderbook(symbols[0]).unwrap();
+        assert_eq!(engine.list_symbols(), vec![symbols[1]]);
+        assert_eq!(
+            engine.query(symbols[0]).await.unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
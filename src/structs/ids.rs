@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use ulid::Ulid;
+
+/// Define a newtype over an u128 ID, serialized as the bare number and converted from and to Ulid and u128
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(pub u128);
+
+        impl From<u128> for $name {
+            fn from(id: u128) -> $name {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for u128 {
+            fn from(id: $name) -> u128 {
+                id.0
+            }
+        }
+
+        impl From<Ulid> for $name {
+            fn from(id: Ulid) -> $name {
+                $name(id.into())
+            }
+        }
+
+        impl From<$name> for Ulid {
+            fn from(id: $name) -> Ulid {
+                Ulid::from(id.0)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+id_type!(
+    /// ID of an order, unique across the orderbooks
+    OrderId
+);
+id_type!(
+    /// ID of the user owning orders and trades
+    UserId
+);
+id_type!(
+    /// ID of the instrument traded in an orderbook
+    Symbol
+);
+id_type!(
+    /// ID of a tenant, a logical exchange owning orderbooks and users
+    TenantId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_serialized_as_numbers() {
+        let ulid = Ulid::new();
+        let symbol = Symbol::from(ulid);
+        assert_eq!(Ulid::from(symbol), ulid);
+        assert_eq!(u128::from(symbol), u128::from(ulid));
+        assert_eq!(serde_json::to_string(&OrderId(42)).unwrap(), "42");
+        assert_eq!(serde_json::from_str::<UserId>("7").unwrap(), UserId(7));
+        assert_eq!(Symbol(5).to_string(), "5");
+    }
+}
@@ -0,0 +1,99 @@
+use super::ids::{OrderId, Symbol};
+use super::level_book::PriceLevel;
+use crate::enums::side::OrderSide;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Broken invariant of an orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InvariantViolation {
+    /// The best bid is at or above the best ask although the orderbook matches its orders
+    Crossed { best_bid: f64, best_ask: f64 },
+    /// A resting order has a visible quantity which is not positive, or a negative hidden quantity
+    InvalidQuantity {
+        order_id: OrderId,
+        quantity: f64,
+        hidden_quantity: f64,
+    },
+    /// A resting order has no price or a price which is not positive
+    InvalidPrice {
+        order_id: OrderId,
+        price: Option<f64>,
+    },
+    /// An order rests on the wrong side or belongs to another symbol
+    Misplaced { order_id: OrderId, side: OrderSide },
+    /// An order ID rests more than once
+    DuplicateId { order_id: OrderId },
+    /// The handles of a side do not match the orders stored in its arena
+    InconsistentIndex { side: OrderSide },
+    /// The orders of a side are not ordered best price first, then oldest first
+    PriorityViolation { side: OrderSide },
+    /// The maintained total of a price level disagrees with the orders resting at that price,
+    /// None when the level is missing on one side of the comparison
+    LevelMismatch {
+        side: OrderSide,
+        price: f64,
+        expected: Option<PriceLevel>,
+        actual: Option<PriceLevel>,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvariantViolation::Crossed { best_bid, best_ask } => {
+                write!(f, "Crossed book: bid {} >= ask {}", best_bid, best_ask)
+            }
+            InvariantViolation::InvalidQuantity {
+                order_id,
+                quantity,
+                hidden_quantity,
+            } => write!(
+                f,
+                "Order {} has quantity {} and hidden quantity {}",
+                order_id, quantity, hidden_quantity
+            ),
+            InvariantViolation::InvalidPrice { order_id, price } => {
+                write!(f, "Order {} has price {:?}", order_id, price)
+            }
+            InvariantViolation::Misplaced { order_id, side } => {
+                write!(f, "Order {} is misplaced on the {} side", order_id, side)
+            }
+            InvariantViolation::DuplicateId { order_id } => {
+                write!(f, "Order {} rests more than once", order_id)
+            }
+            InvariantViolation::InconsistentIndex { side } => {
+                write!(f, "Inconsistent index on the {} side", side)
+            }
+            InvariantViolation::PriorityViolation { side } => {
+                write!(f, "Priority order broken on the {} side", side)
+            }
+            InvariantViolation::LevelMismatch {
+                side,
+                price,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Level {} on the {} side is {:?} instead of {:?}",
+                price, side, actual, expected
+            ),
+        }
+    }
+}
+
+/// Result of `Orderbook::verify_invariants`, for ops tooling
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct InvariantReport {
+    pub symbol: Symbol,
+    /// Sequence number of the last update published before the check
+    pub sequence: u64,
+    pub violations: Vec<InvariantViolation>,
+}
+
+impl InvariantReport {
+    /// Whether every invariant holds
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
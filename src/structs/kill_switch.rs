@@ -0,0 +1,110 @@
+use super::ids::{Symbol, UserId};
+use super::order::Order;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// What a kill switch blocks: the whole flow of a user or of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KillSwitchTarget {
+    User(UserId),
+    Symbol(Symbol),
+}
+
+/// Event emitted once when a kill switch is engaged, with every order it cancelled, and once when it is released
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KillSwitchEvent {
+    pub target: KillSwitchTarget,
+    /// True when the switch is engaged, false when the flow is enabled again
+    pub engaged: bool,
+    #[serde(rename = "cancelledOrders")]
+    pub cancelled_orders: Vec<Order>,
+    /// Time of the event, in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Default)]
+struct KillSwitchState {
+    engaged: HashSet<KillSwitchTarget>,
+    listeners: Vec<Sender<KillSwitchEvent>>,
+}
+
+/// Kill switches engaged on users and symbols, the new orders they target are rejected until released.
+/// Clones share the same switches.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitches {
+    state: Arc<Mutex<KillSwitchState>>,
+}
+
+impl KillSwitches {
+    pub fn new() -> KillSwitches {
+        KillSwitches::default()
+    }
+
+    /// Engage a switch
+    ///
+    /// #Returns
+    /// * bool - False if the switch was already engaged
+    pub fn engage(&self, target: KillSwitchTarget) -> bool {
+        self.state.lock().unwrap().engaged.insert(target)
+    }
+
+    /// Release a switch
+    ///
+    /// #Returns
+    /// * bool - False if the switch was not engaged
+    pub fn release(&self, target: KillSwitchTarget) -> bool {
+        self.state.lock().unwrap().engaged.remove(&target)
+    }
+
+    pub fn is_engaged(&self, target: KillSwitchTarget) -> bool {
+        self.state.lock().unwrap().engaged.contains(&target)
+    }
+
+    /// Switches currently engaged
+    pub fn engaged(&self) -> Vec<KillSwitchTarget> {
+        self.state.lock().unwrap().engaged.iter().copied().collect()
+    }
+
+    /// Reject an order whose user or symbol is blocked by a kill switch
+    pub fn check_order(&self, order: &Order) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        if state
+            .engaged
+            .contains(&KillSwitchTarget::User(order.user_id))
+        {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Kill switch engaged for the user",
+            ));
+        }
+        if state
+            .engaged
+            .contains(&KillSwitchTarget::Symbol(order.symbol))
+        {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Kill switch engaged for the symbol",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Receive every kill switch event emitted from now on
+    pub fn subscribe(&self) -> Receiver<KillSwitchEvent> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+
+    /// Send the event to every listener, listeners which are gone are removed
+    pub fn publish(&self, event: &KillSwitchEvent) {
+        self.state
+            .lock()
+            .unwrap()
+            .listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+    }
+}
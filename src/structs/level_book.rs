@@ -0,0 +1,540 @@
+use super::book_metrics::BookMetrics;
+use super::order::Order;
+use super::orderbook::PriceLevels;
+use crate::enums::level_action::LevelAction;
+use crate::enums::side::OrderSide;
+use crate::heap::main::PriorityIter;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+
+/// Aggregate of the orders resting at a price
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: f64,
+    /// Visible quantity of the orders
+    pub quantity: f64,
+    /// Number of orders
+    pub orders: usize,
+}
+
+/// Shape of the price levels of an orderbook, the empty levels are dropped as they empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LevelStats {
+    #[serde(rename = "bidLevels")]
+    pub bid_levels: usize,
+    #[serde(rename = "askLevels")]
+    pub ask_levels: usize,
+    /// Number of orders of the most crowded level of either side
+    #[serde(rename = "maxOrdersPerLevel")]
+    pub max_orders_per_level: usize,
+    /// Number of orders resting on both sides
+    pub orders: usize,
+}
+
+/// Change of a price level of a side, the market-by-price view of an update
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct LevelDelta {
+    pub side: OrderSide,
+    pub action: LevelAction,
+    pub price: f64,
+    /// Visible quantity of the level after the change, 0.0 once deleted
+    pub quantity: f64,
+    /// Number of orders of the level after the change
+    pub orders: usize,
+}
+
+/// Price keying the levels, ordered with `f64::total_cmp`
+#[derive(Debug, Clone, Copy)]
+struct LevelPrice(f64);
+
+impl PartialEq for LevelPrice {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LevelPrice {}
+
+impl PartialOrd for LevelPrice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LevelPrice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Per price level totals of the resting orders, maintained by the orderbook as orders are
+/// placed, amended, cancelled and filled so that the summaries and the depth don't walk the orders
+#[derive(Debug, Clone, Default)]
+pub struct LevelBook {
+    bids: BTreeMap<LevelPrice, PriceLevel>,
+    asks: BTreeMap<LevelPrice, PriceLevel>,
+    /// Running totals of the levels of each side
+    bid_total: PriceLevel,
+    ask_total: PriceLevel,
+    /// Levels changed since the last `take_deltas`, with their state before the first change
+    touched: Vec<(OrderSide, LevelPrice, Option<PriceLevel>)>,
+}
+
+impl LevelBook {
+    pub fn new() -> LevelBook {
+        LevelBook::default()
+    }
+
+    fn side_mut(
+        &mut self,
+        side: OrderSide,
+    ) -> (&mut BTreeMap<LevelPrice, PriceLevel>, &mut PriceLevel) {
+        match side {
+            OrderSide::Buy => (&mut self.bids, &mut self.bid_total),
+            OrderSide::Sell => (&mut self.asks, &mut self.ask_total),
+        }
+    }
+
+    /// Remember the state of a level before its first change since the last `take_deltas`
+    fn touch(&mut self, side: OrderSide, price: f64) {
+        let price = LevelPrice(price);
+        if self
+            .touched
+            .iter()
+            .any(|&(s, p, _)| s == side && p == price)
+        {
+            return;
+        }
+        let before = self.side_mut(side).0.get(&price).copied();
+        self.touched.push((side, price, before));
+    }
+
+    /// Count an order resting in the book in its level
+    pub fn add(&mut self, order: &Order) {
+        let Some(price) = order.price else {
+            return;
+        };
+        self.touch(order.side, price);
+        let (levels, total) = self.side_mut(order.side);
+        let level = levels.entry(LevelPrice(price)).or_insert(PriceLevel {
+            price,
+            ..Default::default()
+        });
+        for aggregate in [level, total] {
+            aggregate.quantity += order.quantity;
+            aggregate.orders += 1;
+        }
+    }
+
+    /// Remove an order leaving the book from its level, the level is dropped with its last order
+    pub fn remove(&mut self, order: &Order) {
+        let Some(price) = order.price else {
+            return;
+        };
+        self.touch(order.side, price);
+        let (levels, total) = self.side_mut(order.side);
+        let Some(level) = levels.get_mut(&LevelPrice(price)) else {
+            return;
+        };
+        let level_emptied = level.orders == 1;
+        for aggregate in [level, total] {
+            aggregate.orders -= 1;
+            // an empty aggregate is reset so that no rounding residue is left
+            aggregate.quantity = match aggregate.orders {
+                0 => 0.0,
+                _ => aggregate.quantity - order.quantity,
+            };
+        }
+        if level_emptied {
+            levels.remove(&LevelPrice(price));
+        }
+    }
+
+    /// Changes of the levels since the last call, in the order the levels were first changed.
+    /// A level changed back to its previous state is left out
+    pub fn take_deltas(&mut self) -> Vec<LevelDelta> {
+        std::mem::take(&mut self.touched)
+            .into_iter()
+            .filter_map(|(side, price, before)| {
+                let after = self.side_mut(side).0.get(&price).copied();
+                let action = match (before, after) {
+                    (None, Some(_)) => LevelAction::Add,
+                    (Some(before), Some(after)) if before != after => LevelAction::Change,
+                    (Some(_), None) => LevelAction::Delete,
+                    _ => return None,
+                };
+                let level = after.unwrap_or(PriceLevel {
+                    price: price.0,
+                    ..Default::default()
+                });
+                Some(LevelDelta {
+                    side,
+                    action,
+                    price: level.price,
+                    quantity: level.quantity,
+                    orders: level.orders,
+                })
+            })
+            .collect()
+    }
+
+    /// Set a level to its state after a change, to keep a copy of the levels of an orderbook from the
+    /// deltas of its updates
+    pub fn apply(&mut self, delta: &LevelDelta) {
+        let (levels, total) = self.side_mut(delta.side);
+        let before = levels.remove(&LevelPrice(delta.price)).unwrap_or_default();
+        if delta.action != LevelAction::Delete && delta.orders > 0 {
+            levels.insert(
+                LevelPrice(delta.price),
+                PriceLevel {
+                    price: delta.price,
+                    quantity: delta.quantity,
+                    orders: delta.orders,
+                },
+            );
+        }
+        total.orders = total.orders + delta.orders - before.orders;
+        total.quantity = match total.orders {
+            0 => 0.0,
+            _ => total.quantity + delta.quantity - before.quantity,
+        };
+    }
+
+    /// Bid levels, best (highest) price first
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &PriceLevel> {
+        self.bids.values().rev()
+    }
+
+    /// Ask levels, best (lowest) price first
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &PriceLevel> {
+        self.asks.values()
+    }
+
+    /// Quantity and number of orders of a whole side, the price is unused
+    pub fn total(&self, side: OrderSide) -> PriceLevel {
+        match side {
+            OrderSide::Buy => self.bid_total,
+            OrderSide::Sell => self.ask_total,
+        }
+    }
+
+    /// Price times quantity of the best level of a side, 0.0 if it is empty
+    pub fn notional_at_top(&self, side: OrderSide) -> f64 {
+        let best = match side {
+            OrderSide::Buy => self.bids().next(),
+            OrderSide::Sell => self.asks().next(),
+        };
+        best.map_or(0.0, |level| level.price * level.quantity)
+    }
+
+    /// Volumes, order counts and notional at top of both sides
+    pub fn metrics(&self) -> BookMetrics {
+        BookMetrics {
+            bid_volume: self.bid_total.quantity,
+            ask_volume: self.ask_total.quantity,
+            bid_orders: self.bid_total.orders,
+            ask_orders: self.ask_total.orders,
+            bid_notional_at_top: self.notional_at_top(OrderSide::Buy),
+            ask_notional_at_top: self.notional_at_top(OrderSide::Sell),
+        }
+    }
+
+    /// One (price, quantity, cumulated quantity) entry per level, best price first, and the mid price,
+    /// 0.0 unless both sides have a level
+    ///
+    /// #Returns
+    /// * (PriceLevels, f64, PriceLevels) - The bids, the mid price and the asks
+    pub fn summarize(&self) -> (PriceLevels, f64, PriceLevels) {
+        let mut ask_sum = 0.0;
+        let asks = self
+            .asks()
+            .map(|level| {
+                ask_sum += level.quantity;
+                (level.price, level.quantity, ask_sum)
+            })
+            .collect();
+        let mut bid_sum = 0.0;
+        let mut bids: PriceLevels = self
+            .bids()
+            .rev()
+            .map(|level| {
+                bid_sum += level.quantity;
+                (level.price, level.quantity, bid_sum)
+            })
+            .collect();
+        bids.reverse();
+        let mid_price = match (self.bids().next(), self.asks().next()) {
+            (Some(bid), Some(ask)) => (bid.price + ask.price) / 2.0,
+            _ => 0.0,
+        };
+        (bids, mid_price, asks)
+    }
+
+    /// Number of levels of a side
+    pub fn level_count(&self, side: OrderSide) -> usize {
+        match side {
+            OrderSide::Buy => self.bids.len(),
+            OrderSide::Sell => self.asks.len(),
+        }
+    }
+
+    /// Number of levels per side and orders of the most crowded level
+    pub fn stats(&self) -> LevelStats {
+        LevelStats {
+            bid_levels: self.bids.len(),
+            ask_levels: self.asks.len(),
+            max_orders_per_level: self
+                .bids
+                .values()
+                .chain(self.asks.values())
+                .map(|level| level.orders)
+                .max()
+                .unwrap_or(0),
+            orders: self.bid_total.orders + self.ask_total.orders,
+        }
+    }
+
+    /// Walk the levels opposite to an order of a given side until its quantity is executed
+    ///
+    /// #Parameters
+    /// * 'side' - The side of the order, a buy consumes the asks and a sell the bids
+    /// * 'quantity' - The quantity to execute
+    ///
+    /// #Returns
+    /// * Option<FillCost> - The estimated execution, None when nothing can be executed
+    pub fn cost_to_fill(&self, side: OrderSide, quantity: f64) -> Option<FillCost> {
+        let levels: Box<dyn Iterator<Item = &PriceLevel>> = match side {
+            OrderSide::Buy => Box::new(self.asks()),
+            OrderSide::Sell => Box::new(self.bids()),
+        };
+        let mut cost = FillCost {
+            remaining: quantity,
+            ..Default::default()
+        };
+        for level in levels {
+            if cost.remaining <= 0.0 {
+                break;
+            }
+            let executed = level.quantity.min(cost.remaining);
+            cost.quantity += executed;
+            cost.remaining -= executed;
+            cost.notional += executed * level.price;
+            cost.worst_price = level.price;
+            cost.levels += 1;
+        }
+        if cost.levels == 0 {
+            return None;
+        }
+        cost.average_price = cost.notional / cost.quantity;
+        Some(cost)
+    }
+}
+
+/// Estimated execution of a size against the visible levels of a book
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct FillCost {
+    /// Quantity the book can execute, below the requested size when the side runs out
+    pub quantity: f64,
+    /// Requested quantity left unfilled
+    pub remaining: f64,
+    /// Sum of the executed quantities times their prices
+    pub notional: f64,
+    #[serde(rename = "averagePrice")]
+    pub average_price: f64,
+    /// Price of the last level reached
+    #[serde(rename = "worstPrice")]
+    pub worst_price: f64,
+    /// Number of levels consumed, the last one possibly in part
+    pub levels: usize,
+}
+
+/// Price level of a side borrowed from the orderbook, with its orders in priority order
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthLevel<'a> {
+    pub level: &'a PriceLevel,
+    pub orders: Vec<&'a Order>,
+}
+
+/// Lazy walk of the levels of a side, best price first, each level coming with its orders.
+/// Nothing is copied and only the levels walked so far are visited
+pub struct DepthIter<'a> {
+    levels: Box<dyn Iterator<Item = &'a PriceLevel> + 'a>,
+    orders: Peekable<PriorityIter<'a, Order>>,
+}
+
+impl<'a> DepthIter<'a> {
+    /// Walk the levels of a side along with the heap of its orders, both best first
+    pub(crate) fn new(
+        levels: impl Iterator<Item = &'a PriceLevel> + 'a,
+        orders: PriorityIter<'a, Order>,
+    ) -> DepthIter<'a> {
+        DepthIter {
+            levels: Box::new(levels),
+            orders: orders.peekable(),
+        }
+    }
+}
+
+impl<'a> Iterator for DepthIter<'a> {
+    type Item = DepthLevel<'a>;
+
+    fn next(&mut self) -> Option<DepthLevel<'a>> {
+        let level = self.levels.next()?;
+        // orders without a price are not counted in any level
+        while self.orders.next_if(|order| order.price.is_none()).is_some() {}
+        let mut orders = Vec::with_capacity(level.orders);
+        while let Some(order) = self
+            .orders
+            .next_if(|order| order.price == Some(level.price))
+        {
+            orders.push(order);
+        }
+        Some(DepthLevel { level, orders })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::ids::{Symbol, UserId};
+
+    #[test]
+    fn test_levels_follow_the_orders() {
+        let order = |side, price, quantity| {
+            Order::new(
+                UserId(0),
+                Symbol(0),
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let mut levels = LevelBook::new();
+        let first = order(OrderSide::Buy, 1.0, 2.0);
+        let second = order(OrderSide::Buy, 1.0, 3.0);
+        levels.add(&first);
+        levels.add(&second);
+        levels.add(&order(OrderSide::Buy, 2.0, 1.0));
+        levels.add(&order(OrderSide::Sell, 3.0, 1.0));
+        assert_eq!(
+            levels.bids().collect::<Vec<_>>(),
+            vec![
+                &PriceLevel {
+                    price: 2.0,
+                    quantity: 1.0,
+                    orders: 1
+                },
+                &PriceLevel {
+                    price: 1.0,
+                    quantity: 5.0,
+                    orders: 2
+                },
+            ]
+        );
+
+        levels.remove(&first);
+        assert_eq!(levels.bids().nth(1).unwrap().quantity, 3.0);
+        assert_eq!(levels.total(OrderSide::Buy).quantity, 4.0);
+        assert_eq!(levels.total(OrderSide::Buy).orders, 2);
+        levels.remove(&second);
+        assert_eq!(levels.level_count(OrderSide::Buy), 1);
+        assert_eq!(levels.asks().next().unwrap().price, 3.0);
+    }
+
+    #[test]
+    fn test_deltas_of_the_changed_levels() {
+        let order = |side, price, quantity| {
+            Order::new(
+                UserId(0),
+                Symbol(0),
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let delta = |side, action, price, quantity, orders| LevelDelta {
+            side,
+            action,
+            price,
+            quantity,
+            orders,
+        };
+        let mut levels = LevelBook::new();
+        let bid = order(OrderSide::Buy, 1.0, 2.0);
+        levels.add(&bid);
+        levels.add(&order(OrderSide::Buy, 1.0, 1.0));
+        levels.add(&order(OrderSide::Sell, 2.0, 1.0));
+        assert_eq!(
+            levels.take_deltas(),
+            vec![
+                delta(OrderSide::Buy, LevelAction::Add, 1.0, 3.0, 2),
+                delta(OrderSide::Sell, LevelAction::Add, 2.0, 1.0, 1),
+            ]
+        );
+        assert!(levels.take_deltas().is_empty());
+
+        levels.remove(&bid);
+        let moved = Order {
+            price: Some(3.0),
+            ..bid
+        };
+        levels.add(&moved);
+        assert_eq!(
+            levels.take_deltas(),
+            vec![
+                delta(OrderSide::Buy, LevelAction::Change, 1.0, 1.0, 1),
+                delta(OrderSide::Buy, LevelAction::Add, 3.0, 2.0, 1),
+            ]
+        );
+
+        // added then removed before the deltas are taken, nothing to report
+        let fleeting = order(OrderSide::Sell, 4.0, 1.0);
+        levels.add(&fleeting);
+        levels.remove(&fleeting);
+        levels.remove(&moved);
+        assert_eq!(
+            levels.take_deltas(),
+            vec![delta(OrderSide::Buy, LevelAction::Delete, 3.0, 0.0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_cost_to_fill_walks_the_opposite_levels() {
+        let order = |side, price, quantity| {
+            Order::new(
+                UserId(0),
+                Symbol(0),
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let mut levels = LevelBook::new();
+        levels.add(&order(OrderSide::Sell, 101.0, 1.0));
+        levels.add(&order(OrderSide::Sell, 102.0, 2.0));
+        levels.add(&order(OrderSide::Sell, 102.0, 1.0));
+        levels.add(&order(OrderSide::Buy, 100.0, 1.0));
+
+        let cost = levels.cost_to_fill(OrderSide::Buy, 3.0).unwrap();
+        assert_eq!(cost.quantity, 3.0);
+        assert_eq!(cost.remaining, 0.0);
+        assert_eq!(cost.notional, 305.0);
+        assert_eq!(cost.average_price, 305.0 / 3.0);
+        assert_eq!(cost.worst_price, 102.0);
+        assert_eq!(cost.levels, 2);
+
+        // the bids run out before the size is executed
+        let cost = levels.cost_to_fill(OrderSide::Sell, 4.0).unwrap();
+        assert_eq!((cost.quantity, cost.remaining), (1.0, 3.0));
+        assert_eq!((cost.average_price, cost.worst_price), (100.0, 100.0));
+
+        assert_eq!(LevelBook::new().cost_to_fill(OrderSide::Buy, 1.0), None);
+        assert_eq!(levels.cost_to_fill(OrderSide::Buy, 0.0), None);
+    }
+}
@@ -0,0 +1,263 @@
+use super::book_snapshot::BookSnapshot;
+use super::ids::Symbol;
+use super::orderbook_update::OrderbookUpdate;
+use super::orderbooks_manager::OrderbooksManager;
+use super::subscription::Subscription;
+use async_stream::stream;
+use crossbeam_channel::TryRecvError;
+use futures_util::Stream;
+use std::io::{Error, ErrorKind};
+
+/// Gap-free market data feed for a single symbol.
+///
+/// The feed starts from a snapshot tagged with a sequence number, then only hands out the
+/// updates following that sequence. When an update is missing the feed becomes stale and
+/// every read fails until `resync` is called with the manager to fetch a fresh snapshot.
+#[derive(Debug)]
+pub struct MarketDataFeed {
+    symbol: Symbol,
+    rx: Subscription,
+    book: BookSnapshot,
+    stale: bool,
+}
+
+impl MarketDataFeed {
+    /// Subscribe to the market data of a symbol
+    ///
+    /// #Parameters
+    /// * 'manager' - The orderbooks manager
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * MarketDataFeed - The feed, initialized with the current snapshot of the orderbook
+    pub fn subscribe(manager: &OrderbooksManager, symbol: Symbol) -> Result<MarketDataFeed, Error> {
+        let rx = manager.subscribe_updates();
+        let book = manager.snapshot(symbol)?;
+        Ok(MarketDataFeed {
+            symbol,
+            rx,
+            book,
+            stale: false,
+        })
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+    }
+
+    /// Sequence number of the last update applied to the book
+    pub fn sequence(&self) -> u64 {
+        self.book.sequence
+    }
+
+    /// The local book: the initial snapshot with every update received since applied
+    pub fn book(&self) -> &BookSnapshot {
+        &self.book
+    }
+
+    /// Whether a gap was detected and the feed needs a resync
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Block until the next update of the symbol is available
+    pub fn next_update(&mut self) -> Result<OrderbookUpdate, Error> {
+        loop {
+            self.check_stale()?;
+            let update = self
+                .rx
+                .recv()
+                .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Update channel closed"))?;
+            if let Some(result) = self.handle(update) {
+                return result;
+            }
+        }
+    }
+
+    /// Return the next update of the symbol if one is already available
+    pub fn try_next_update(&mut self) -> Option<Result<OrderbookUpdate, Error>> {
+        loop {
+            if let Err(err) = self.check_stale() {
+                return Some(Err(err));
+            }
+            match self.rx.try_recv() {
+                Ok(update) => {
+                    if let Some(result) = self.handle(update) {
+                        return Some(result);
+                    }
+                }
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(Error::new(
+                        ErrorKind::BrokenPipe,
+                        "Update channel closed",
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Stream of the incremental updates, an error is yielded when a gap is detected
+    pub fn updates(&mut self) -> impl Stream<Item = Result<OrderbookUpdate, Error>> + '_ {
+        stream! {
+            loop {
+                let update = self.next_update();
+                let closed = matches!(&update, Err(err) if err.kind() == ErrorKind::BrokenPipe);
+                yield update;
+                if closed || self.stale {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Replace the local book with a fresh snapshot and resume from its sequence
+    pub fn resync(&mut self, manager: &OrderbooksManager) -> Result<&BookSnapshot, Error> {
+        self.book = manager.snapshot(self.symbol)?;
+        self.stale = false;
+        Ok(&self.book)
+    }
+
+    fn check_stale(&self) -> Result<(), Error> {
+        if self.stale {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Market data feed is stale, resync needed",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Apply an update to the local book, None when the update must be skipped
+    fn handle(&mut self, update: OrderbookUpdate) -> Option<Result<OrderbookUpdate, Error>> {
+        if update.symbol != self.symbol || update.sequence <= self.book.sequence {
+            return None;
+        }
+        if update.sequence != self.book.sequence + 1 {
+            self.stale = true;
+            return Some(Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Sequence gap: expected {} received {}",
+                    self.book.sequence + 1,
+                    update.sequence
+                ),
+            )));
+        }
+        self.book.apply(&update);
+        if let Some(checksum) = update.checksum {
+            if checksum != self.book.checksum() {
+                self.stale = true;
+                return Some(Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Checksum mismatch at sequence {}", update.sequence),
+                )));
+            }
+        }
+        Some(Ok(update))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::order::Order;
+    use ulid::Ulid;
+
+    fn limit(symbol: Symbol, side: OrderSide, quantity: f64, price: f64) -> Order {
+        Order::new(
+            Ulid::new().into(),
+            symbol,
+            side,
+            quantity,
+            Some(price),
+            OrderType::Limit,
+        )
+    }
+
+    #[test]
+    fn test_snapshot_then_incremental() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Buy, 1.0, 10.0));
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Buy, 1.0, 11.0));
+
+        let mut feed = MarketDataFeed::subscribe(&orderbooks_manager, symbol).unwrap();
+        assert_eq!(feed.sequence(), 4);
+        assert_eq!(feed.book().bids.len(), 2);
+        assert_eq!(feed.book().bids[0].price, Some(11.0));
+
+        let ask = limit(symbol, OrderSide::Sell, 1.0, 11.0);
+        let _ = orderbooks_manager.add_order(ask);
+        let mut sequences = vec![];
+        while let Some(update) = feed.try_next_update() {
+            sequences.push(update.unwrap().sequence);
+        }
+        assert_eq!(sequences, vec![5, 6, 7, 8, 9]);
+        assert_eq!(feed.book().bids.len(), 1);
+        assert_eq!(feed.book().asks.len(), 0);
+        assert_eq!(feed.book(), &orderbooks_manager.snapshot(symbol).unwrap());
+    }
+
+    #[test]
+    fn test_gap_and_resync() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let mut feed = MarketDataFeed::subscribe(&orderbooks_manager, symbol).unwrap();
+
+        // the orderbook skips a sequence number, as if an update was lost
+        orderbooks_manager
+            .orderbooks
+            .get_mut(&symbol)
+            .unwrap()
+            .sequence += 1;
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Buy, 1.0, 10.0));
+        assert!(feed.try_next_update().unwrap().is_err());
+        assert!(feed.is_stale());
+        assert!(feed.try_next_update().unwrap().is_err());
+
+        feed.resync(&orderbooks_manager).unwrap();
+        assert!(!feed.is_stale());
+        assert_eq!(feed.sequence(), 3);
+        assert_eq!(feed.book().bids.len(), 1);
+        assert!(feed.try_next_update().is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_checksum() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Buy, 1.5, 10.0));
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Sell, 2.0, 11.0));
+        let mut feed = MarketDataFeed::subscribe(&orderbooks_manager, symbol).unwrap();
+        let _ = orderbooks_manager.add_order(limit(symbol, OrderSide::Buy, 0.5, 10.0));
+
+        orderbooks_manager.publish_heartbeats();
+        let mut updates = vec![];
+        while let Some(update) = feed.try_next_update() {
+            updates.push(update.unwrap());
+        }
+        let heartbeat = updates.last().unwrap();
+        assert_eq!(heartbeat.update_type, OrderbookUpdateType::Heartbeat);
+        let checksum = orderbooks_manager.checksum(symbol).unwrap();
+        assert_eq!(heartbeat.checksum, Some(checksum));
+        assert_eq!(feed.book().checksum(), checksum);
+        assert_eq!(
+            orderbooks_manager.get_orderbook(symbol).unwrap().checksum,
+            checksum
+        );
+
+        // a mirror which lost an order is detected on the next heartbeat
+        feed.book.bids.pop();
+        orderbooks_manager.publish_heartbeats();
+        let err = feed.try_next_update().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Checksum mismatch at sequence 8");
+        assert!(feed.is_stale());
+    }
+}
@@ -0,0 +1,31 @@
+use super::ids::Symbol;
+use super::order::Order;
+use super::orderbook_update::OrderbookUpdate;
+use super::trade::Trade;
+use std::fmt;
+
+/// Hook called synchronously by an orderbook while it matches, before the update is sent to the channel.
+/// Risk and liquidation engines see every trade and fill in sequence order,
+/// without racing the asynchronous update stream.
+/// The callbacks run on the matching thread and hold up the orderbook, they should return quickly.
+pub trait MatchObserver: fmt::Debug + Send + Sync {
+    /// Called with each trade executed by the orderbook
+    ///
+    /// #Parameters
+    /// * 'trade' - The trade, stamped with its ID, execution time and fees
+    fn on_trade(&self, _trade: &Trade) {}
+
+    /// Called with each order leaving the orderbook fully filled
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID of the orderbook
+    /// * 'order' - The filled order
+    fn on_fill(&self, _symbol: Symbol, _order: &Order) {}
+
+    /// Called with each update changing the orders resting in the orderbook,
+    /// i.e. placed, updated, replaced, cancelled, filled and expired orders
+    ///
+    /// #Parameters
+    /// * 'update' - The update, stamped with the symbol and its sequence number
+    fn on_book_change(&self, _update: &OrderbookUpdate) {}
+}
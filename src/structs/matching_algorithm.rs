@@ -0,0 +1,367 @@
+use super::order::Order;
+use super::orderbook::Orderbook;
+use super::trade::Trade;
+use crate::enums::order_type::OrderType;
+use crate::enums::self_trade_prevention::SelfTradePrevention;
+use crate::enums::side::OrderSide;
+use std::fmt;
+
+/// Algorithm matching the crossing orders of an orderbook, plugged per orderbook at construction time.
+///
+/// The algorithm applies the fills to the book with `Orderbook::order_filled` and `Orderbook::decrement_for_fill`
+/// and returns the trades in execution order, the orderbook records and publishes them.
+/// Before each execution it checks the price with `Orderbook::within_price_band` and stops when it is outside,
+/// and it applies the `Orderbook::self_trade_prevention` policy to the orders of a same user.
+pub trait MatchingAlgorithm: fmt::Debug + Send + Sync {
+    /// Match the book
+    ///
+    /// #Parameters
+    /// * 'book' - The orderbook to match
+    /// * 'taker' - The order which triggered the matching: a market order which is not in the book,
+    ///   a limit order which was just placed or amended, None to match the whole book
+    ///
+    /// #Returns
+    /// * Vec<Trade> - The trades executed
+    fn match_book(&mut self, book: &mut Orderbook, taker: Option<Order>) -> Vec<Trade>;
+
+    /// Clone the algorithm behind the box
+    fn box_clone(&self) -> Box<dyn MatchingAlgorithm>;
+}
+
+impl Clone for Box<dyn MatchingAlgorithm> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Continuous matching with price then time priority, the default algorithm
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriceTimeMatcher;
+
+impl PriceTimeMatcher {
+    /// Apply the self-trade prevention policy of the book to two orders of the same user
+    ///
+    /// #Returns
+    /// * bool - Whether the taker is cancelled
+    fn prevent_self_trade(book: &mut Orderbook, taker: &Order, maker: &Order) -> bool {
+        let policy = book.self_trade_prevention;
+        if matches!(
+            policy,
+            SelfTradePrevention::CancelMaker | SelfTradePrevention::CancelBoth
+        ) {
+            let _ = book.cancel_order(maker.id, maker.side);
+        }
+        if matches!(
+            policy,
+            SelfTradePrevention::CancelTaker | SelfTradePrevention::CancelBoth
+        ) {
+            // a market taker never rests, there is nothing to cancel in the book
+            let _ = book.cancel_order(taker.id, taker.side);
+            return true;
+        }
+        false
+    }
+
+    /// Whether a buy and a sell order can trade together, none of them requiring a larger execution
+    fn compatible(bid: &Order, ask: &Order) -> bool {
+        let quantity = bid.quantity.min(ask.quantity);
+        bid.accepts_fill(quantity) && ask.accepts_fill(quantity)
+    }
+
+    /// Best order of the opposite side a taker can trade with. The orders whose minimum fill
+    /// the execution would not reach are skipped and keep their priority.
+    fn eligible_maker(book: &Orderbook, taker: &Order) -> Option<Order> {
+        let makers = match taker.side {
+            OrderSide::Buy => &book.asks,
+            OrderSide::Sell => &book.bids,
+        };
+        let compatible = |maker: &Order| match taker.side {
+            OrderSide::Buy => Self::compatible(taker, maker),
+            OrderSide::Sell => Self::compatible(maker, taker),
+        };
+        let top = makers.peek()?;
+        if compatible(&top) {
+            return Some(top);
+        }
+        let crosses = |maker: &&Order| match (taker.side, taker.price) {
+            (_, None) => true,
+            (OrderSide::Buy, price) => price >= maker.price,
+            (OrderSide::Sell, price) => price <= maker.price,
+        };
+        makers
+            .sorted_refs()
+            .into_iter()
+            .rev()
+            .take_while(crosses)
+            .find(|maker| compatible(maker))
+            .copied()
+    }
+
+    /// Next pair of crossing orders able to trade, the best bid and the best ask unless a minimum fill
+    /// prevents it. With a taker the pair is the taker and the best order it can trade with.
+    fn crossing_pair(book: &Orderbook, taker: Option<Order>) -> Option<(Order, Order)> {
+        let (bid, ask) = (book.bids.peek()?, book.asks.peek()?);
+        if bid.price < ask.price {
+            return None;
+        }
+        if taker.is_none_or(|t| t.id == bid.id || t.id == ask.id) && Self::compatible(&bid, &ask) {
+            return Some((bid, ask));
+        }
+        match taker {
+            Some(taker) => {
+                let taker = book.get_order(taker.id, taker.side)?;
+                let maker = Self::eligible_maker(book, &taker)?;
+                Some(match taker.side {
+                    OrderSide::Buy => (taker, maker),
+                    OrderSide::Sell => (maker, taker),
+                })
+            }
+            None => book
+                .bids
+                .sorted_refs()
+                .into_iter()
+                .rev()
+                .take_while(|bid| bid.price >= ask.price)
+                .find_map(|bid| Self::eligible_maker(book, bid).map(|ask| (*bid, ask))),
+        }
+    }
+
+    /// Cross the crossing orders at the price of the maker, the best bid and the best ask first.
+    /// The incoming order is the taker and only it is matched against the book,
+    /// the taker is the later order of the pair when the book is matched without one.
+    fn cross(book: &mut Orderbook, taker: Option<Order>) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        while let Some((bid, ask)) = Self::crossing_pair(book, taker) {
+            let taker_side = taker.map_or_else(|| Trade::later_side(&bid, &ask), |t| t.side);
+            let price = match taker_side {
+                OrderSide::Buy => ask.price,
+                OrderSide::Sell => bid.price,
+            }
+            .unwrap();
+            if !book.within_price_band(price) {
+                break;
+            }
+            if bid.user_id == ask.user_id
+                && book.self_trade_prevention != SelfTradePrevention::Allow
+            {
+                let (taker, maker) = match taker_side {
+                    OrderSide::Buy => (&bid, &ask),
+                    OrderSide::Sell => (&ask, &bid),
+                };
+                Self::prevent_self_trade(book, taker, maker);
+                continue;
+            }
+            let quantity = bid.quantity.min(ask.quantity);
+            if ask.quantity > bid.quantity {
+                book.order_filled(bid.id, bid.side);
+                book.decrement_for_fill(ask.id, quantity, ask.side);
+            } else if ask.quantity < bid.quantity {
+                book.order_filled(ask.id, ask.side);
+                book.decrement_for_fill(bid.id, quantity, bid.side);
+            } else {
+                book.order_filled(ask.id, ask.side);
+                book.order_filled(bid.id, bid.side);
+            }
+            trades.push(Trade::between(
+                book.symbol,
+                price,
+                quantity,
+                &bid,
+                &ask,
+                taker_side,
+            ));
+        }
+        trades
+    }
+
+    /// Sweep the opposite side with a market order until it is filled or no order is left to trade with
+    fn sweep(book: &mut Orderbook, taker: Order) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        let mut quantity = taker.quantity;
+        while !book.is_dust(quantity) {
+            // The taker as it stands before this execution
+            let current = Order { quantity, ..taker };
+            let Some(maker) = Self::eligible_maker(book, &current) else {
+                break;
+            };
+            if !book.within_price_band(maker.price.unwrap()) {
+                break;
+            }
+            if maker.user_id == taker.user_id
+                && book.self_trade_prevention != SelfTradePrevention::Allow
+            {
+                if Self::prevent_self_trade(book, &taker, &maker) {
+                    break;
+                }
+                continue;
+            }
+            let executed = maker.quantity.min(quantity);
+            if maker.quantity <= quantity {
+                book.order_filled(maker.id, maker.side);
+            } else {
+                book.decrement_for_fill(maker.id, quantity, maker.side);
+            }
+            quantity -= executed;
+            let (buy, sell) = match taker.side {
+                OrderSide::Buy => (&current, &maker),
+                OrderSide::Sell => (&maker, &current),
+            };
+            trades.push(Trade::between(
+                book.symbol,
+                maker.price.unwrap(),
+                executed,
+                buy,
+                sell,
+                taker.side,
+            ));
+        }
+        trades
+    }
+}
+
+impl MatchingAlgorithm for PriceTimeMatcher {
+    fn match_book(&mut self, book: &mut Orderbook, taker: Option<Order>) -> Vec<Trade> {
+        match taker {
+            Some(order) if order.order_type == OrderType::Market => Self::sweep(book, order),
+            _ => Self::cross(book, taker),
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn MatchingAlgorithm> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::orderbook_update::OrderbookUpdate;
+    use crossbeam_channel::unbounded;
+    use ulid::Ulid;
+
+    /// Only matches the orders of the same size
+    #[derive(Debug, Clone)]
+    struct SameSizeMatcher;
+
+    impl MatchingAlgorithm for SameSizeMatcher {
+        fn match_book(&mut self, book: &mut Orderbook, _taker: Option<Order>) -> Vec<Trade> {
+            let mut trades = Vec::new();
+            for bid in book.bids.to_vec() {
+                let ask = book
+                    .asks
+                    .to_vec()
+                    .into_iter()
+                    .find(|ask| ask.price <= bid.price && ask.quantity == bid.quantity);
+                if let Some(ask) = ask {
+                    book.order_filled(ask.id, ask.side);
+                    book.order_filled(bid.id, bid.side);
+                    trades.push(Trade::between(
+                        book.symbol,
+                        ask.price.unwrap(),
+                        ask.quantity,
+                        &bid,
+                        &ask,
+                        Trade::later_side(&bid, &ask),
+                    ));
+                }
+            }
+            trades
+        }
+
+        fn box_clone(&self) -> Box<dyn MatchingAlgorithm> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_pluggable_matcher() {
+        let (tx, _r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::with_matcher(symbol, tx, Box::new(SameSizeMatcher));
+        let order = |side: OrderSide, quantity: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(1.0),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 2.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0));
+        assert_eq!((orderbook.bids.len(), orderbook.asks.len()), (1, 1));
+
+        orderbook.add_order(order(OrderSide::Sell, 2.0));
+        assert_eq!((orderbook.bids.len(), orderbook.asks.len()), (0, 1));
+        assert_eq!(orderbook.trade_history.last().unwrap().quantity, 2.0);
+    }
+
+    #[test]
+    fn test_market_order_stops_when_filled() {
+        let (tx, _r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        for price in [1.0, 2.0] {
+            orderbook.add_order(Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Sell,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            ));
+        }
+        orderbook.add_order(Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            None,
+            OrderType::Market,
+        ));
+        assert_eq!(orderbook.asks.len(), 1);
+        assert_eq!(orderbook.asks.peek().unwrap().quantity, 1.0);
+        assert_eq!(orderbook.trade_history.len(), 1);
+    }
+
+    #[test]
+    fn test_trades_identify_the_taker() {
+        let (tx, rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side: OrderSide, price: Option<f64>, order_type: OrderType| {
+            Order::new(Ulid::new().into(), symbol, side, 1.0, price, order_type)
+        };
+        let ask = order(OrderSide::Sell, Some(1.0), OrderType::Limit);
+        let bid = order(OrderSide::Buy, Some(1.0), OrderType::Limit);
+        orderbook.add_order(ask);
+        orderbook.add_order(bid);
+        let trade = rx.try_iter().find_map(|u| u.trade).unwrap();
+        assert_eq!(trade.taker_side, OrderSide::Buy);
+        assert_eq!(trade.maker_order_id, ask.id);
+
+        let bid = order(OrderSide::Buy, Some(1.0), OrderType::Limit);
+        orderbook.add_order(bid);
+        orderbook.add_order(order(OrderSide::Sell, None, OrderType::Market));
+        let trade = rx.try_iter().find_map(|u| u.trade).unwrap();
+        assert_eq!(trade.taker_side, OrderSide::Sell);
+        assert_eq!(trade.maker_order_id, bid.id);
+
+        // A limit sell taker trades at the price of the bid it hits
+        let bid = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(2.0),
+            OrderType::Limit,
+        );
+        orderbook.add_order(bid);
+        orderbook.add_order(order(OrderSide::Sell, Some(1.0), OrderType::Limit));
+        let trade = rx.try_iter().find_map(|u| u.trade).unwrap();
+        assert_eq!(trade.taker_side, OrderSide::Sell);
+        assert_eq!(trade.maker_order_id, bid.id);
+        assert_eq!(trade.price, 2.0);
+    }
+}
@@ -1,6 +1,41 @@
+pub mod auction;
+pub mod basket;
+pub mod book_condition;
+pub mod book_metrics;
+pub mod book_snapshot;
+pub mod clock;
+pub mod dead_letter;
+#[cfg(feature = "native")]
+pub mod engine;
+pub mod invariants;
+pub mod ids;
+pub mod kill_switch;
+pub mod level_book;
+pub mod market_data_feed;
+pub mod match_observer;
+pub mod matching_algorithm;
 pub mod order;
+pub mod order_ack;
+pub mod order_history;
 pub mod orderbook;
+pub mod orderbook_config;
 pub mod orderbook_sum;
 pub mod orderbook_update;
+pub mod orderbook_view;
 pub mod orderbooks_manager;
+pub mod positions;
+pub mod price_band;
+pub mod router;
+pub mod session;
+pub mod shutdown;
+pub mod spread;
+pub mod symbol_registry;
+pub mod tenant;
+#[cfg(feature = "native")]
+pub mod sharded_manager;
+pub mod subscription;
+pub mod subscription_builder;
 pub mod trade;
+pub mod trade_history;
+pub mod trading_calendar;
+pub mod update_bus;
@@ -1,17 +1,27 @@
 use crate::enums::payment_status::PaymentStatus;
+use crate::enums::peg_reference::PegReference;
 use crate::enums::side::OrderSide;
+use crate::enums::time_in_force::TimeInForce;
 use crate::enums::{order_status::OrderStatus, order_type::OrderType};
+use crate::heap::main::Keyed;
+use crate::structs::clock::{Clock, SystemClock};
+use crate::structs::ids::{OrderId, Symbol, UserId};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
 use ulid::Ulid;
-use std::time::Instant;
 
+/// Tags given by the embedder to an order, e.g. client tags, algo IDs or regulatory flags.
+/// They are held by the orderbook next to the order so that the orders stay `Copy`, and are
+/// carried into its updates and trades.
+pub type Metadata = BTreeMap<String, String>;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub struct Order {
-    pub id: u128,
+    pub id: OrderId,
     #[serde(rename = "userId")]
-    pub user_id: u128,
-    pub symbol: u128,
+    pub user_id: UserId,
+    pub symbol: Symbol,
     pub side: OrderSide,
     pub quantity: f64,
     #[serde(rename = "nonMutQuantity")]
@@ -26,12 +36,41 @@ pub struct Order {
     pub created_at: u64,
     #[serde(rename = "updatedAt")]
     pub updated_at: u64,
+    /// Good-Till-Date expiry in milliseconds since UNIX epoch, None for Good-Till-Cancel
+    #[serde(rename = "expiresAt", default)]
+    pub expires_at: Option<u64>,
+    /// Day orders are expired at the close of the trading session of their symbol
+    #[serde(rename = "timeInForce", default)]
+    pub time_in_force: TimeInForce,
+    /// Size of the visible slice of an iceberg order, None for a fully visible order
+    #[serde(rename = "displayQuantity", default)]
+    pub display_quantity: Option<f64>,
+    /// Reserve of an iceberg order not yet shown in the book, `quantity` being the visible slice
+    #[serde(rename = "hiddenQuantity", default)]
+    pub hidden_quantity: f64,
+    /// ID given by the client to correlate its requests with the acknowledgments and updates
+    #[serde(rename = "clientOrderId", default)]
+    pub client_order_id: Option<u128>,
+    /// Smallest execution the order accepts, the smaller matches skip it. Capped to the visible quantity
+    /// left so that the end of the order can still be filled
+    #[serde(rename = "minFillQuantity", default)]
+    pub min_fill_quantity: Option<f64>,
+    /// Price tracked by a pegged order, `price` being the current pegged price. None for an order at a fixed price
+    #[serde(rename = "pegReference", default)]
+    pub peg_reference: Option<PegReference>,
+    /// Distance of the pegged price from its reference, negative below it
+    #[serde(rename = "pegOffset", default)]
+    pub peg_offset: f64,
+    /// Rank of the order in the arrival sequence of its orderbook, given when it enters the book and
+    /// again when it loses its time priority, so that the orders stamped with the same time keep
+    /// the order they arrived in. 0 until the order rests in a book
+    #[serde(rename = "arrivalSeq", default)]
+    pub arrival_seq: u64,
 }
 
 impl Order {
-
     #[cfg(test)]
-    pub fn get_test_order<'a>(symbol: u128, user_id: u128) -> Order {
+    pub fn get_test_order(symbol: Symbol, user_id: UserId) -> Order {
         Order {
             id: Ulid::new().into(),
             symbol,
@@ -42,9 +81,18 @@ impl Order {
             order_type: OrderType::Limit,
             status: OrderStatus::Open,
             non_mut_quantity: 100.0,
-            created_at: Instant::now().elapsed().as_secs(),
-            updated_at: Instant::now().elapsed().as_secs(),
+            created_at: SystemClock.now(),
+            updated_at: SystemClock.now(),
             payment_status: Default::default(),
+            expires_at: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         }
     }
 }
@@ -64,16 +112,25 @@ impl Default for Order {
             order_type: OrderType::Limit,
             status: OrderStatus::Open,
             payment_status: PaymentStatus::Pending,
-            created_at: Instant::now().elapsed().as_secs(),
-            updated_at: Instant::now().elapsed().as_secs(),
+            created_at: SystemClock.now(),
+            updated_at: SystemClock.now(),
+            expires_at: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
         }
     }
 }
 
 impl Order {
     pub fn new(
-        user_id: u128,
-        symbol: u128,
+        user_id: UserId,
+        symbol: Symbol,
         side: OrderSide,
         quantity: f64,
         price: Option<f64>,
@@ -88,20 +145,187 @@ impl Order {
             price,
             order_type,
             non_mut_quantity: quantity,
-            created_at: Instant::now().elapsed().as_secs(),
-            updated_at: Instant::now().elapsed().as_secs(),
+            created_at: SystemClock.now(),
+            updated_at: SystemClock.now(),
             status: Default::default(),
             payment_status: Default::default(),
+            expires_at: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            display_quantity: None,
+            hidden_quantity: 0.0,
+            client_order_id: None,
+            min_fill_quantity: None,
+            peg_reference: None,
+            peg_offset: 0.0,
+            arrival_seq: 0,
+        }
+    }
+}
+
+impl Order {
+    /// Move the order to a new status, following the transitions allowed by OrderStatus::can_transition_to
+    ///
+    /// #Parameters
+    /// * 'status' - The new status
+    ///
+    /// #Returns
+    /// * OrderStatus - The previous status, an InvalidInput error leaving the order untouched if the transition is not allowed
+    pub fn transition(&mut self, status: OrderStatus) -> Result<OrderStatus, Error> {
+        if !self.status.can_transition_to(status) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invalid order status transition from {} to {status}",
+                    self.status
+                ),
+            ));
+        }
+        Ok(std::mem::replace(&mut self.status, status))
+    }
+
+    /// Set how long the order stays in the book
+    ///
+    /// #Parameters
+    /// * 'time_in_force' - Day to expire the order at the close of the trading session of its symbol
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Order {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Make the order Good-Till-Date
+    ///
+    /// #Parameters
+    /// * 'expires_at' - The expiry in milliseconds since UNIX epoch
+    pub fn with_expiry(mut self, expires_at: u64) -> Order {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Tag the order with an ID chosen by the client
+    ///
+    /// #Parameters
+    /// * 'client_order_id' - The ID, returned in the acknowledgments
+    pub fn with_client_order_id(mut self, client_order_id: u128) -> Order {
+        self.client_order_id = Some(client_order_id);
+        self
+    }
+
+    /// Set the smallest execution the order accepts
+    ///
+    /// #Parameters
+    /// * 'min_fill_quantity' - The minimum quantity of each execution
+    pub fn with_min_fill_quantity(mut self, min_fill_quantity: f64) -> Order {
+        self.min_fill_quantity = Some(min_fill_quantity);
+        self
+    }
+
+    /// Peg the price of the order to a reference, the book reprices it whenever the reference moves
+    ///
+    /// #Parameters
+    /// * 'reference' - The price tracked
+    /// * 'offset' - The distance from the reference, negative below it
+    pub fn with_peg(mut self, reference: PegReference, offset: f64) -> Order {
+        self.peg_reference = Some(reference);
+        self.peg_offset = offset;
+        self
+    }
+
+    /// accepts_fill tells whether the order can take part in an execution of a quantity
+    pub fn accepts_fill(&self, quantity: f64) -> bool {
+        self.min_fill_quantity
+            .is_none_or(|minimum| quantity >= minimum.min(self.quantity))
+    }
+
+    /// Stamp the order with the current time of a clock, e.g. the MockClock of a test
+    ///
+    /// #Parameters
+    /// * 'clock' - The clock, its time in nanoseconds becomes the creation time of the order
+    pub fn stamped(mut self, clock: &dyn Clock) -> Order {
+        self.created_at = clock.now();
+        self.updated_at = self.created_at;
+        self
+    }
+
+    /// Make the order an iceberg showing at most `display_quantity` in the book
+    ///
+    /// #Parameters
+    /// * 'display_quantity' - The size of each visible slice
+    pub fn with_display_quantity(mut self, display_quantity: f64) -> Order {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    /// is_iceberg returns true if only a slice of the order is shown in the book
+    pub fn is_iceberg(&self) -> bool {
+        self.display_quantity.is_some_and(|display| display > 0.0)
+    }
+
+    /// split_display moves the size above the display quantity of an iceberg order to its hidden reserve
+    pub fn split_display(&mut self) {
+        if let Some(display) = self.display_quantity.filter(|_| self.is_iceberg()) {
+            if self.quantity > display {
+                self.hidden_quantity += self.quantity - display;
+                self.quantity = display;
+            }
+        }
+    }
+
+    /// replenish shows the next slice of an iceberg order from its hidden reserve
+    ///
+    /// #Returns
+    /// * bool - false if the reserve is empty
+    pub fn replenish(&mut self) -> bool {
+        match self.display_quantity.filter(|_| self.is_iceberg()) {
+            Some(display) if self.hidden_quantity > 0.0 => {
+                let slice = display.min(self.hidden_quantity);
+                self.quantity = slice;
+                self.hidden_quantity -= slice;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// public_view returns the order as shown to the market, without the hidden size of an iceberg
+    pub fn public_view(&self) -> Order {
+        let mut order = *self;
+        if let Some(display) = self.display_quantity.filter(|_| self.is_iceberg()) {
+            order.quantity = order.quantity.min(display);
+            order.non_mut_quantity = display;
+            order.hidden_quantity = 0.0;
         }
+        order
+    }
+
+    /// migrated returns the order moved to another symbol, its prices multiplied by a factor and its
+    /// quantities divided by it so that its notional does not change
+    ///
+    /// #Parameters
+    /// * 'symbol' - The new symbol of the order
+    /// * 'price_factor' - The factor applied to the prices, e.g. 0.5 for a 2-for-1 split
+    pub fn migrated(&self, symbol: Symbol, price_factor: f64) -> Order {
+        Order {
+            symbol,
+            price: self.price.map(|price| price * price_factor),
+            peg_offset: self.peg_offset * price_factor,
+            quantity: self.quantity / price_factor,
+            non_mut_quantity: self.non_mut_quantity / price_factor,
+            hidden_quantity: self.hidden_quantity / price_factor,
+            display_quantity: self.display_quantity.map(|q| q / price_factor),
+            min_fill_quantity: self.min_fill_quantity.map(|q| q / price_factor),
+            ..*self
+        }
+    }
+
+    /// is_expired returns true if the order has an expiry which is reached at `now` (milliseconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
     }
 }
 
 impl PartialOrd for Order {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.side {
-            OrderSide::Buy => self.price.partial_cmp(&other.price),
-            OrderSide::Sell => other.price.partial_cmp(&self.price),
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -111,6 +335,18 @@ impl Ord for Order {
             OrderSide::Buy => self.price.partial_cmp(&other.price).unwrap(),
             OrderSide::Sell => other.price.partial_cmp(&self.price).unwrap(),
         }
+        // Time priority: at the same price the first arrived order comes first
+        .then_with(|| other.arrival_seq.cmp(&self.arrival_seq))
+        .then_with(|| other.created_at.cmp(&self.created_at))
+    }
+}
+
+// Resting orders are looked up in the book by their ID
+impl Keyed for Order {
+    type Key = OrderId;
+
+    fn key(&self) -> OrderId {
+        self.id
     }
 }
 
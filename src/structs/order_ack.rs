@@ -0,0 +1,102 @@
+use super::ids::OrderId;
+use super::order::Order;
+use super::orderbook_config::is_dust;
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::metrics::OrderLatency;
+use serde::{Deserialize, Serialize};
+
+/// Result of an order request, returned once the request is applied so that a gateway
+/// can answer its client without waiting for the update stream
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct OrderAck {
+    #[serde(rename = "orderId")]
+    pub order_id: OrderId,
+    #[serde(rename = "clientOrderId")]
+    pub client_order_id: Option<u128>,
+    /// Status of the order once the request is applied
+    pub status: OrderStatus,
+    /// Quantity executed while the request was applied
+    #[serde(rename = "filledQuantity")]
+    pub filled_quantity: f64,
+    /// Why the orderbook cancelled or refused the order, None when it was accepted
+    #[serde(rename = "rejectReason")]
+    pub reject_reason: Option<String>,
+    /// Time the order spent in each stage of the engine, when the manager attaches it to the acks
+    #[serde(default)]
+    pub latency: Option<OrderLatency>,
+}
+
+impl OrderAck {
+    pub fn new(order: &Order, status: OrderStatus) -> OrderAck {
+        OrderAck {
+            order_id: order.id,
+            client_order_id: order.client_order_id,
+            status,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.reject_reason.is_some()
+    }
+
+    /// Fold an update published while applying the request of an incoming order
+    pub fn apply(&mut self, update: &OrderbookUpdate) {
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                if let Some(trade) = update
+                    .trade
+                    .as_ref()
+                    .filter(|t| t.buy_order_id == self.order_id || t.sell_order_id == self.order_id)
+                {
+                    self.filled_quantity += trade.quantity;
+                    // the trades are published after the fills they come from
+                    if self.status == OrderStatus::Open {
+                        self.status = OrderStatus::PartiallyFilled;
+                    }
+                }
+            }
+            OrderbookUpdateType::Filled if update.filled_id == Some(self.order_id) => {
+                self.status = OrderStatus::Filled;
+            }
+            OrderbookUpdateType::Cancel if update.cancel_id == Some(self.order_id) => {
+                self.status = OrderStatus::Cancelled;
+                // the remainder of a market order which exhausted the book
+                let reason = match update.order {
+                    Some(o) if o.order_type == OrderType::Market => "No liquidity",
+                    // a pegged order placed without a price while its reference is missing
+                    Some(o) if o.peg_reference.is_some() && o.price.is_none() => {
+                        "No peg reference price"
+                    }
+                    _ => "Cancelled by the orderbook",
+                };
+                self.reject_reason = Some(String::from(reason));
+            }
+            OrderbookUpdateType::Expired if update.order.is_some_and(|o| o.id == self.order_id) => {
+                self.status = OrderStatus::Expired;
+                self.reject_reason = Some(String::from("Order expired"));
+            }
+            _ => {}
+        }
+    }
+
+    /// Settle the status of a market order once matched, its remainder never rests in the book
+    ///
+    /// #Parameters
+    /// * 'order' - The order
+    /// * 'dust_threshold' - The remainder below which the orderbook of the order fills an order
+    pub fn close_market_order(&mut self, order: &Order, dust_threshold: Option<f64>) {
+        if order.order_type != OrderType::Market || self.status == OrderStatus::Cancelled {
+            return;
+        }
+        if is_dust(order.quantity - self.filled_quantity, dust_threshold) {
+            self.status = OrderStatus::Filled;
+        } else if self.filled_quantity == 0.0 {
+            self.status = OrderStatus::Cancelled;
+            self.reject_reason = Some(String::from("No liquidity"));
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use super::ids::OrderId;
+use super::order::Order;
+use super::orderbook_update::OrderbookUpdate;
+use super::trade::Trade;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Number of orders whose history is kept by default
+pub const DEFAULT_ORDER_HISTORY_CAPACITY: usize = 10_000;
+/// Number of events kept by default for each order
+pub const DEFAULT_ORDER_HISTORY_DEPTH: usize = 64;
+
+/// Something which happened to an order, taken from an update of its orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    /// Sequence number of the update within its orderbook
+    pub sequence: u64,
+    /// Time of the update, in nanoseconds since UNIX epoch
+    pub timestamp: u64,
+    #[serde(rename = "updateType")]
+    pub update_type: OrderbookUpdateType,
+    /// The order after the event, None for a trade or a fill reported by ID
+    pub order: Option<Order>,
+    /// The execution for NewTrades events
+    pub trade: Option<Trade>,
+    /// Quantity executed for PartiallyFilled events
+    #[serde(rename = "fillQuantity")]
+    pub fill_quantity: Option<f64>,
+}
+
+impl OrderEvent {
+    fn new(update: &OrderbookUpdate) -> OrderEvent {
+        OrderEvent {
+            sequence: update.sequence,
+            timestamp: update.timestamp,
+            update_type: update.update_type,
+            order: update.order,
+            trade: update.trade.clone(),
+            fill_quantity: update.fill_quantity,
+        }
+    }
+}
+
+/// Bounded audit trail of the orders of an orderbook: the last `depth` events of the last `capacity` orders,
+/// the orders seen first are forgotten first
+#[derive(Debug, Clone)]
+pub struct OrderHistory {
+    events: HashMap<OrderId, VecDeque<OrderEvent>>,
+    /// Orders in the order their history started
+    orders: VecDeque<OrderId>,
+    capacity: usize,
+    depth: usize,
+}
+
+impl Default for OrderHistory {
+    fn default() -> Self {
+        OrderHistory::new(DEFAULT_ORDER_HISTORY_CAPACITY, DEFAULT_ORDER_HISTORY_DEPTH)
+    }
+}
+
+impl OrderHistory {
+    /// Create a new order history, a capacity or a depth of 0 disables it
+    ///
+    /// #Parameters
+    /// * 'capacity' - The number of orders followed
+    /// * 'depth' - The number of events kept for each order
+    pub fn new(capacity: usize, depth: usize) -> OrderHistory {
+        OrderHistory {
+            events: HashMap::new(),
+            orders: VecDeque::new(),
+            capacity,
+            depth,
+        }
+    }
+
+    /// Record an update published by the orderbook against the orders it concerns.
+    /// The history of an order starts with an update carrying the order.
+    pub fn record(&mut self, update: &OrderbookUpdate) {
+        if self.capacity == 0 || self.depth == 0 {
+            return;
+        }
+        match update.update_type {
+            OrderbookUpdateType::NewTrades => {
+                if let Some(trade) = update.trade.as_ref() {
+                    self.push(trade.buy_order_id, update, false);
+                    self.push(trade.sell_order_id, update, false);
+                }
+            }
+            OrderbookUpdateType::New
+            | OrderbookUpdateType::Place
+            | OrderbookUpdateType::Amended
+            | OrderbookUpdateType::PartiallyFilled
+            | OrderbookUpdateType::Repriced
+            | OrderbookUpdateType::Replace
+            | OrderbookUpdateType::Filled
+            | OrderbookUpdateType::Cancel
+            | OrderbookUpdateType::Expired
+            | OrderbookUpdateType::Migrated => {
+                if let Some(order) = update.order.as_ref() {
+                    self.push(order.id, update, true);
+                } else if let Some(order_id) = update.filled_id.or(update.cancel_id) {
+                    self.push(order_id, update, false);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, order_id: OrderId, update: &OrderbookUpdate, start: bool) {
+        if !self.events.contains_key(&order_id) {
+            if !start {
+                return;
+            }
+            if self.orders.len() == self.capacity {
+                if let Some(oldest) = self.orders.pop_front() {
+                    self.events.remove(&oldest);
+                }
+            }
+            self.orders.push_back(order_id);
+        }
+        let events = self.events.entry(order_id).or_default();
+        if events.len() == self.depth {
+            events.pop_front();
+        }
+        events.push_back(OrderEvent::new(update));
+    }
+
+    /// Events of an order, the oldest first, empty if the order is unknown or forgotten
+    pub fn get(&self, order_id: OrderId) -> Vec<OrderEvent> {
+        self.events
+            .get(&order_id)
+            .map(|events| events.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of orders followed
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
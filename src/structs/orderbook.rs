@@ -1,63 +1,505 @@
+use super::auction::{self, AuctionResult};
+use super::book_condition::BookCondition;
+use super::book_metrics::BookMetrics;
+use super::book_snapshot::BookSnapshot;
+use super::clock::{Clock, SystemClock};
+use super::dead_letter::DeadLetterQueue;
+use super::ids::{OrderId, Symbol, UserId};
+use super::invariants::{InvariantReport, InvariantViolation};
+use super::level_book::{DepthIter, FillCost, LevelBook, LevelStats, PriceLevel};
+use super::match_observer::MatchObserver;
+use super::matching_algorithm::{MatchingAlgorithm, PriceTimeMatcher};
+use super::order::Metadata;
+use super::order_history::{OrderEvent, OrderHistory};
+use super::orderbook_config::{is_dust, is_multiple_of, FeeSchedule, OrderbookConfig};
+use super::orderbook_sum::LevelLiquidity;
 use super::orderbook_update::OrderbookUpdate;
+use super::price_band::{CircuitBreakerEvent, PriceBand};
 use super::trade::Trade;
+use super::trade_history::TradeHistory;
+use crate::enums::band_action::BandAction;
+use crate::enums::market_remainder::MarketRemainder;
+use crate::enums::order_status::OrderStatus;
 use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_state::OrderbookState;
 use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::peg_reference::PegReference;
+use crate::enums::price_reference::PriceReference;
+use crate::enums::reference_source::ReferenceSource;
+use crate::enums::self_trade_prevention::SelfTradePrevention;
 use crate::enums::side::OrderSide;
+use crate::enums::time_in_force::TimeInForce;
+use crate::formats::checksum::{book_checksum, CHECKSUM_DEPTH};
+use crate::heap::arena::PoolStats;
 use crate::heap::main::ModifiableBinaryHeap;
 use crate::structs::order::Order;
 use crossbeam_channel::Sender;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use std::time::Duration;
+use ulid::Ulid;
+
+/// A list of (price, quantity, cumulated quantity) tuples
+pub type PriceLevels = Vec<(f64, f64, f64)>;
 
 #[derive(Debug, Clone)]
 pub struct Orderbook {
-    pub symbol: u128,
+    pub symbol: Symbol,
     pub bids: ModifiableBinaryHeap<Order>,
     pub asks: ModifiableBinaryHeap<Order>,
+    /// Per price level totals of the bids and asks, kept in step with the heaps
+    levels: LevelBook,
     pub tx: Sender<OrderbookUpdate>,
+    pub trade_history: TradeHistory,
+    /// Sequence number of the last update published by the orderbook
+    pub sequence: u64,
+    /// Min-heap of the (expiry, order ID) of the Good-Till-Date orders,
+    /// entries of orders which left the book are skipped when they come due
+    expirations: BinaryHeap<Reverse<(u64, OrderId)>>,
+    /// Algorithm matching the crossing orders
+    pub matcher: Box<dyn MatchingAlgorithm>,
+    /// Trading state, enforced on the incoming operations
+    pub state: OrderbookState,
+    /// Price band stopping the matches too far from the reference price, None to disable it
+    pub price_band: Option<PriceBand>,
+    /// Mid price at the end of the last matching, when the book was uncrossed
+    last_mid: Option<f64>,
+    /// Set when the price band stopped the current matching
+    band_tripped: bool,
+    /// Clock stamping the trades, the updates and the orders changed by the orderbook,
+    /// the incoming orders keep the time they were created at (see `Order::stamped`)
+    pub clock: Arc<dyn Clock>,
+    /// Updates which could not be sent to the channel, handled according to its policy
+    pub dead_letters: DeadLetterQueue,
+    /// Price increment, the limit prices must be a multiple of it
+    pub tick_size: Option<f64>,
+    /// Quantity increment, the quantities must be a multiple of it
+    pub lot_size: Option<f64>,
+    /// Remainder below which an executed order is filled rather than left in the book
+    pub dust_threshold: Option<f64>,
+    /// Fees charged on the trades
+    pub fees: FeeSchedule,
+    /// What happens when a user would trade with itself
+    pub self_trade_prevention: SelfTradePrevention,
+    /// Maximum number of orders resting in the orderbook
+    pub max_orders: Option<usize>,
+    /// Number of freed slots of the order pools triggering a compaction, None to compact on demand only
+    pub compact_threshold: Option<usize>,
+    /// Hooks called synchronously with the trades, fills and book changes, before they are published
+    pub observers: Vec<Arc<dyn MatchObserver>>,
+    /// Verify the invariants after each operation and panic on a violation, only in debug builds
+    pub check_invariants: bool,
+    /// What happens to the remainder of a market order which exhausts the book
+    pub market_remainder: MarketRemainder,
+    /// Number of pegged orders resting in the book, the pegs are only tracked when there are some
+    pegged_orders: usize,
+    /// Set while the matching algorithm runs, the pegged orders are repriced once it returns
+    matching: bool,
+    /// Last indicative auction published during the current call auction
+    indicative: Option<AuctionResult>,
+    /// Bounded audit trail of the orders, fed with the published updates
+    pub audit_trail: OrderHistory,
+    /// Metadata of the live orders, forgotten once they are filled, cancelled or expired
+    metadata: HashMap<OrderId, Metadata>,
+    /// Last arrival sequence number given to an order, see `Order::arrival_seq`
+    arrivals: u64,
+    /// Orders held until the book meets their condition, in the order they were added
+    conditionals: Vec<(BookCondition, Order)>,
+    /// What maintains the reference price
+    pub reference_source: ReferenceSource,
+    /// Maximum relative distance to the reference price a market order executes at, None to disable it
+    pub market_collar: Option<f64>,
+    /// Maximum relative distance to the reference price of the auction equilibrium price, None to disable it
+    pub auction_collar: Option<f64>,
+    /// Reference price the price protections are centered on, see `reference_price`
+    reference_price: Option<f64>,
+    /// Set until the first trade of the session fixes the reference price of a SessionOpen source
+    session_opening: bool,
+    /// Side and worst price of the market order being matched when a market collar applies
+    collar: Option<(OrderSide, f64)>,
+    /// Set when the market collar stopped the current matching
+    collar_tripped: bool,
 }
 
 impl Orderbook {
     /// Create a new orderbook
-    /// 
+    ///
     /// #Parameters
-    /// 
+    ///
     /// * 'symbol' - The symbol ID
-    /// * 'tx' - The channel Sender [please refer to crossbeam_channel] 
-    /// 
+    /// * 'tx' - The channel Sender [please refer to crossbeam_channel]
+    ///
     /// #Returns
     /// * 'Orderbook' - The instance of the orderbook
-    pub fn new(symbol: u128, tx: Sender<OrderbookUpdate>) -> Orderbook {
+    pub fn new(symbol: Symbol, tx: Sender<OrderbookUpdate>) -> Orderbook {
+        Orderbook::with_config(symbol, tx, OrderbookConfig::default())
+    }
+
+    /// Create a new orderbook matching its orders with the given algorithm
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'tx' - The channel Sender [please refer to crossbeam_channel]
+    /// * 'matcher' - The matching algorithm
+    pub fn with_matcher(
+        symbol: Symbol,
+        tx: Sender<OrderbookUpdate>,
+        matcher: Box<dyn MatchingAlgorithm>,
+    ) -> Orderbook {
+        Orderbook::with_config(symbol, tx, OrderbookConfig::default().with_matcher(matcher))
+    }
+
+    /// Create a new orderbook behaving as configured
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'tx' - The channel Sender [please refer to crossbeam_channel]
+    /// * 'config' - The configuration of the orderbook
+    pub fn with_config(
+        symbol: Symbol,
+        tx: Sender<OrderbookUpdate>,
+        config: OrderbookConfig,
+    ) -> Orderbook {
         Orderbook {
             symbol,
             bids: ModifiableBinaryHeap::new(),
             asks: ModifiableBinaryHeap::new(),
+            levels: LevelBook::new(),
             tx,
+            trade_history: TradeHistory::default(),
+            sequence: 0,
+            expirations: BinaryHeap::new(),
+            matcher: config.matcher,
+            state: OrderbookState::default(),
+            price_band: config.price_band,
+            last_mid: None,
+            band_tripped: false,
+            clock: Arc::new(SystemClock),
+            dead_letters: DeadLetterQueue::new(),
+            tick_size: config.tick_size,
+            lot_size: config.lot_size,
+            dust_threshold: config.dust_threshold,
+            fees: config.fees,
+            self_trade_prevention: config.self_trade_prevention,
+            max_orders: config.max_orders,
+            compact_threshold: config.compact_threshold,
+            observers: Vec::new(),
+            check_invariants: config.check_invariants,
+            market_remainder: config.market_remainder,
+            pegged_orders: 0,
+            matching: false,
+            indicative: None,
+            audit_trail: OrderHistory::new(
+                config.order_history_capacity,
+                config.order_history_depth,
+            ),
+            metadata: HashMap::new(),
+            arrivals: 0,
+            conditionals: Vec::new(),
+            reference_source: config.reference_source,
+            market_collar: config.market_collar,
+            auction_collar: config.auction_collar,
+            reference_price: None,
+            session_opening: true,
+            collar: None,
+            collar_tripped: false,
         }
     }
 
-    /// summarize_orderbook_per_price_level returns a tuple of (Vec<(f64, f64, f64)>, f64, Vec<(f64, f64, f64)>) where the first element is a vector of bids, the second element is the mid price and the third element is a vector of asks
-    pub fn summarize_orderbook_per_price_level(
-        &self,
-    ) -> (Vec<(f64, f64, f64)>, f64, Vec<(f64, f64, f64)>) {
-        let mut asks = Vec::new();
-        let mut bids = Vec::new();
-        let mut ask_sum = 0.0;
-        let mut bid_sum = 0.0;
-        for ask in self.asks.into_vec().iter() {
-            ask_sum += ask.quantity;
-            asks.push((ask.price.unwrap(), ask.quantity, ask_sum));
+    /// summarize_orderbook_per_price_level returns a tuple of (Vec<(f64, f64, f64)>, f64, Vec<(f64, f64, f64)>) where the first element is a vector of bids, the second element is the mid price and the third element is a vector of asks.
+    /// There is one entry per price level, best price first, read from the maintained level totals.
+    pub fn summarize_orderbook_per_price_level(&self) -> (PriceLevels, f64, PriceLevels) {
+        self.levels.summarize()
+    }
+
+    /// total_bid_volume returns the visible quantity resting on the bid side
+    pub fn total_bid_volume(&self) -> f64 {
+        self.levels.total(OrderSide::Buy).quantity
+    }
+
+    /// total_ask_volume returns the visible quantity resting on the ask side
+    pub fn total_ask_volume(&self) -> f64 {
+        self.levels.total(OrderSide::Sell).quantity
+    }
+
+    /// order_count_per_side returns the number of resting orders of each side
+    ///
+    /// #Returns
+    /// * (usize, usize) - The number of bids and the number of asks
+    pub fn order_count_per_side(&self) -> (usize, usize) {
+        (
+            self.levels.total(OrderSide::Buy).orders,
+            self.levels.total(OrderSide::Sell).orders,
+        )
+    }
+
+    /// notional_at_top returns the price times the quantity of the best level of a side, 0.0 if it is empty
+    pub fn notional_at_top(&self, side: OrderSide) -> f64 {
+        self.levels.notional_at_top(side)
+    }
+
+    /// metrics returns the volumes, the order counts and the notional at top of both sides
+    pub fn metrics(&self) -> BookMetrics {
+        self.levels.metrics()
+    }
+
+    /// contains_order tells whether an order rests in the book
+    pub fn contains_order(&self, order_id: OrderId, side: OrderSide) -> bool {
+        self.get_order(order_id, side).is_some()
+    }
+
+    /// get_order returns an order resting in the book
+    pub fn get_order(&self, order_id: OrderId, side: OrderSide) -> Option<Order> {
+        match side {
+            OrderSide::Buy => self.bids.get(&order_id).copied(),
+            OrderSide::Sell => self.asks.get(&order_id).copied(),
+        }
+    }
+
+    /// level_count returns the number of price levels of a side
+    pub fn level_count(&self, side: OrderSide) -> usize {
+        self.levels.level_count(side)
+    }
+
+    /// depth returns the best price levels of each side
+    ///
+    /// #Parameters
+    /// * 'max_levels' - The maximum number of levels returned per side
+    ///
+    /// #Returns
+    /// * (Vec<PriceLevel>, Vec<PriceLevel>) - The bid and ask levels, best price first
+    pub fn depth(&self, max_levels: usize) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        (
+            self.levels.bids().take(max_levels).copied().collect(),
+            self.levels.asks().take(max_levels).copied().collect(),
+        )
+    }
+
+    /// checksum returns the CRC32 of the best levels of the book, for the consumers mirroring the
+    /// book to check they hold the same levels
+    ///
+    /// #Returns
+    /// * u32 - The checksum of the best CHECKSUM_DEPTH levels of each side, see `book_checksum`
+    pub fn checksum(&self) -> u32 {
+        let (bids, asks) = self.depth(CHECKSUM_DEPTH);
+        let pairs = |levels: Vec<PriceLevel>| -> Vec<[f64; 2]> {
+            levels.iter().map(|l| [l.price, l.quantity]).collect()
+        };
+        book_checksum(&pairs(bids), &pairs(asks), CHECKSUM_DEPTH)
+    }
+
+    /// cost_to_fill estimates the execution of a size against the visible liquidity of the book,
+    /// for pre-trade slippage estimates
+    ///
+    /// #Parameters
+    /// * 'side' - The side of the order, a buy consumes the asks and a sell the bids
+    /// * 'quantity' - The quantity to execute
+    ///
+    /// #Returns
+    /// * Option<FillCost> - The average and worst prices and the levels consumed, None when the opposite side is empty
+    pub fn cost_to_fill(&self, side: OrderSide, quantity: f64) -> Option<FillCost> {
+        self.levels.cost_to_fill(side, quantity)
+    }
+
+    /// level_liquidity returns the visible and total quantity of each level of a side, the total
+    /// including the hidden size of the icebergs, which is never published
+    ///
+    /// #Parameters
+    /// * 'side' - The side
+    ///
+    /// #Returns
+    /// * Vec<LevelLiquidity> - The levels, best price first
+    pub fn level_liquidity(&self, side: OrderSide) -> Vec<LevelLiquidity> {
+        let levels = match side {
+            OrderSide::Buy => self.iter_bids(),
+            OrderSide::Sell => self.iter_asks(),
+        };
+        levels
+            .map(|depth| LevelLiquidity {
+                price: depth.level.price,
+                visible_qty: depth.level.quantity,
+                total_qty: depth.level.quantity
+                    + depth.orders.iter().map(|o| o.hidden_quantity).sum::<f64>(),
+                icebergs: depth.orders.iter().filter(|o| o.is_iceberg()).count(),
+            })
+            .collect()
+    }
+
+    /// iter_bids walks the bid levels lazily, best price first, each with its orders in priority order.
+    /// Nothing is cloned, the walk can stop after the top of the book
+    pub fn iter_bids(&self) -> DepthIter<'_> {
+        DepthIter::new(self.levels.bids(), self.bids.iter_priority())
+    }
+
+    /// iter_asks walks the ask levels lazily, best price first, each with its orders in priority order
+    pub fn iter_asks(&self) -> DepthIter<'_> {
+        DepthIter::new(self.levels.asks(), self.asks.iter_priority())
+    }
+
+    /// snapshot returns the full state of the orderbook tagged with the last sequence number: the resting
+    /// orders with the hidden size of the icebergs, and their metadata. `BookSnapshot::public_view` is
+    /// what the market may see of it
+    pub fn snapshot(&self) -> BookSnapshot {
+        let mut bids: Vec<Order> = self.bids.iter_ref().copied().collect();
+        bids.sort_by(|a, b| b.cmp(a));
+        let mut asks: Vec<Order> = self.asks.iter_ref().copied().collect();
+        asks.sort_by(|a, b| b.cmp(a));
+        let metadata = bids
+            .iter()
+            .chain(asks.iter())
+            .filter_map(|o| Some((o.id, self.metadata.get(&o.id)?.clone())))
+            .collect();
+        BookSnapshot::new(self.symbol, self.sequence, bids, asks).with_metadata(metadata)
+    }
+
+    /// restore replaces the resting orders with the orders of a snapshot, without matching nor
+    /// publishing them, and resumes the sequence numbers after the snapshot's. The orders keep their
+    /// arrival sequence numbers, hence their time priority, and the next orders arrive after them.
+    /// The icebergs keep their hidden size and the orders their metadata.
+    ///
+    /// #Parameters
+    /// * 'snapshot' - The snapshot of the orderbook
+    pub fn restore(&mut self, snapshot: &BookSnapshot) {
+        self.bids = ModifiableBinaryHeap::new();
+        self.asks = ModifiableBinaryHeap::new();
+        self.levels = LevelBook::new();
+        self.pegged_orders = 0;
+        self.expirations.clear();
+        self.metadata = snapshot.metadata.clone();
+        self.arrivals = 0;
+        for order in snapshot.bids.iter().chain(snapshot.asks.iter()) {
+            self.arrivals = self.arrivals.max(order.arrival_seq);
+            if let Some(expires_at) = order.expires_at {
+                self.expirations.push(Reverse((expires_at, order.id)));
+            }
+            self.rest(*order);
+        }
+        self.sequence = snapshot.sequence;
+        self.last_mid = None;
+        self.debug_check();
+    }
+
+    /// pool_stats returns the usage of the arenas holding the resting orders of both sides
+    ///
+    /// #Returns
+    /// * PoolStats - The slots allocated, live, free and recycled
+    pub fn pool_stats(&self) -> PoolStats {
+        self.bids.pool_stats().merge(self.asks.pool_stats())
+    }
+
+    /// level_stats returns the number of price levels of each side and the orders of the most crowded one
+    pub fn level_stats(&self) -> LevelStats {
+        self.levels.stats()
+    }
+
+    /// compact releases the memory left by the orders which left the book: the freed slots of the
+    /// order pools and the expiries of the orders gone. The priority of the resting orders is kept.
+    ///
+    /// #Returns
+    /// * usize - The number of pool slots released
+    pub fn compact(&mut self) -> usize {
+        let released = self.bids.compact() + self.asks.compact();
+        let live: HashSet<OrderId> = self
+            .bids
+            .iter_ref()
+            .chain(self.asks.iter_ref())
+            .filter(|o| o.expires_at.is_some())
+            .map(|o| o.id)
+            .collect();
+        self.expirations
+            .retain(|Reverse((_, order_id))| live.contains(order_id));
+        self.expirations.shrink_to_fit();
+        self.metadata.shrink_to_fit();
+        released
+    }
+
+    /// verify_invariants checks the orderbook is consistent: it is not crossed while it matches its orders,
+    /// the resting orders have a positive quantity and price, each order ID rests once on its own side,
+    /// the sides keep their priority order and the level totals match the resting orders
+    ///
+    /// #Returns
+    /// * InvariantReport - The violations found, empty when the orderbook is consistent
+    pub fn verify_invariants(&self) -> InvariantReport {
+        let mut violations = Vec::new();
+        let mut ids = HashSet::new();
+        let mut expected_levels = LevelBook::new();
+        for (side, heap) in [(OrderSide::Buy, &self.bids), (OrderSide::Sell, &self.asks)] {
+            if !heap.handles_consistent() {
+                violations.push(InvariantViolation::InconsistentIndex { side });
+                continue;
+            }
+            let mut prices_valid = true;
+            for order in heap.iter_ref() {
+                if !ids.insert(order.id) {
+                    violations.push(InvariantViolation::DuplicateId { order_id: order.id });
+                }
+                if order.side != side {
+                    violations.push(InvariantViolation::Misplaced {
+                        order_id: order.id,
+                        side,
+                    });
+                }
+                if !(order.quantity.is_finite() && order.quantity > 0.0)
+                    || order.hidden_quantity.is_nan()
+                    || order.hidden_quantity < 0.0
+                {
+                    violations.push(InvariantViolation::InvalidQuantity {
+                        order_id: order.id,
+                        quantity: order.quantity,
+                        hidden_quantity: order.hidden_quantity,
+                    });
+                }
+                if !order.price.is_some_and(|p| p.is_finite() && p > 0.0) {
+                    prices_valid = false;
+                    violations.push(InvariantViolation::InvalidPrice {
+                        order_id: order.id,
+                        price: order.price,
+                    });
+                }
+                expected_levels.add(order);
+            }
+            // The priority order can't be checked without comparable prices
+            if prices_valid && !heap.is_ordered() {
+                violations.push(InvariantViolation::PriorityViolation { side });
+            }
+            compare_levels(side, &expected_levels, &self.levels, &mut violations);
+        }
+        if let (Some(bid), Some(ask)) = (self.bids.peek_ref(), self.asks.peek_ref()) {
+            // The best orders may stay crossed when a minimum fill prevents them from trading
+            let quantity = bid.quantity.min(ask.quantity);
+            let tradable = bid.accepts_fill(quantity) && ask.accepts_fill(quantity);
+            if let (Some(best_bid), Some(best_ask)) = (bid.price, ask.price) {
+                if self.state.matches_orders() && tradable && best_bid >= best_ask {
+                    violations.push(InvariantViolation::Crossed { best_bid, best_ask });
+                }
+            }
         }
-        for bid in self.bids.iter_sorted().iter() {
-            bid_sum += bid.quantity;
-            bids.push((bid.price.unwrap(), bid.quantity, bid_sum));
+        InvariantReport {
+            symbol: self.symbol,
+            sequence: self.sequence,
+            violations,
+        }
+    }
+
+    /// debug_check verifies the invariants after an operation when `check_invariants` is set, in debug builds only
+    fn debug_check(&self) {
+        if cfg!(debug_assertions) && self.check_invariants {
+            let report = self.verify_invariants();
+            assert!(
+                report.is_ok(),
+                "orderbook {} invariants violated: {:?}",
+                self.symbol,
+                report.violations
+            );
         }
-        bids.reverse();
-        (bids, self.get_mid_price(), asks)
     }
 
     /// get_mid_price returns the mid price of the orderbook
-    /// 
+    ///
     /// #Returns
-    /// * f64 - The middle price 
+    /// * f64 - The middle price
     pub fn get_mid_price(&self) -> f64 {
         let bid = self.bids.peek();
         let ask = self.asks.peek();
@@ -67,444 +509,1484 @@ impl Orderbook {
         }
     }
 
-    /// place an order in the orderbook
-    pub fn place_order(&mut self, order: Order) {
-        match order.side {
-            OrderSide::Buy => self.bids.push(order),
-            OrderSide::Sell => self.asks.push(order),
+    /// vwap returns the volume weighted average price of the trades executed during the window
+    ///
+    /// #Parameters
+    /// * 'window' - The duration to look back from now
+    ///
+    /// #Returns
+    /// * Option<f64> - None if no trade happened during the window
+    pub fn vwap(&self, window: Duration) -> Option<f64> {
+        self.trade_history.vwap(window)
+    }
+
+    /// twap returns the time weighted average price of the trades executed during the window
+    ///
+    /// #Parameters
+    /// * 'window' - The duration to look back from now
+    ///
+    /// #Returns
+    /// * Option<f64> - None if no trade happened during the window
+    pub fn twap(&self, window: Duration) -> Option<f64> {
+        self.trade_history.twap(window)
+    }
+
+    /// publish stamps the update with the orderbook symbol and the next sequence number and sends it to the channel,
+    /// the hidden size of iceberg orders is never published
+    fn publish(&mut self, mut update: OrderbookUpdate) {
+        update.order = update.order.map(|o| o.public_view());
+        if let Some(order_id) = update.order.map(|o| o.id).or(update.filled_id) {
+            // a filled order keeps its metadata until its last trade is emitted
+            update.metadata = match update.update_type {
+                OrderbookUpdateType::Cancel
+                | OrderbookUpdateType::Expired
+                | OrderbookUpdateType::Migrated => self.metadata.remove(&order_id),
+                _ => self.metadata.get(&order_id).cloned(),
+            };
+        }
+        self.sequence += 1;
+        update.symbol = self.symbol;
+        update.sequence = self.sequence;
+        update.timestamp = self.clock.now();
+        update.levels = self.levels.take_deltas();
+        self.audit_trail.record(&update);
+        self.notify_observers(&update);
+        if let Err(error) = self.tx.send(update) {
+            self.dead_letters
+                .report(error.into_inner(), "Update channel closed");
         }
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Place,
-                order: Some(order),
-                trade: None,
-                cancel_id: None,
-                filled_id: None,
-            })
-            .unwrap();
-        self.match_orders();
     }
 
-    /// match_orders matches the orders in the orderbook
-    pub fn amend_order_price(&mut self, order_id: u128, new_price: f64, order_side: OrderSide) {
-        let mut order: Option<Order> = None;
-        match order_side {
-            OrderSide::Buy => {
-                self.bids.modify(|o| {
-                    if o.id == order_id {
-                        o.price = Some(new_price);
-                        order = Some(*o);
+    /// notify_observers calls the match observers with a stamped update
+    fn notify_observers(&self, update: &OrderbookUpdate) {
+        for observer in self.observers.iter() {
+            match update.update_type {
+                OrderbookUpdateType::NewTrades => {
+                    if let Some(trade) = update.trade.as_ref() {
+                        observer.on_trade(trade);
                     }
-                });
-            }
-            OrderSide::Sell => {
-                self.asks.modify(|o| {
-                    if o.id == order_id {
-                        o.price = Some(new_price);
-                        order = Some(*o);
+                }
+                OrderbookUpdateType::Filled => {
+                    if let Some(order) = update.order.as_ref() {
+                        observer.on_fill(self.symbol, order);
                     }
+                    observer.on_book_change(update);
+                }
+                OrderbookUpdateType::Place
+                | OrderbookUpdateType::Amended
+                | OrderbookUpdateType::PartiallyFilled
+                | OrderbookUpdateType::Repriced
+                | OrderbookUpdateType::Replace
+                | OrderbookUpdateType::Cancel
+                | OrderbookUpdateType::Expired
+                | OrderbookUpdateType::Migrated => observer.on_book_change(update),
+                _ => {}
+            }
+        }
+    }
+
+    /// add_observer registers a hook called synchronously with the trades, fills and book changes of the orderbook
+    ///
+    /// #Parameters
+    /// * 'observer' - The observer
+    pub fn add_observer(&mut self, observer: Arc<dyn MatchObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// trade_metadata returns the metadata of an order of a trade, forgotten once the order is filled
+    fn trade_metadata(&mut self, order_id: OrderId, remaining: f64) -> Option<Metadata> {
+        if remaining > 0.0 {
+            self.metadata.get(&order_id).cloned()
+        } else {
+            self.metadata.remove(&order_id)
+        }
+    }
+
+    /// emit_trade stamps the trade with an ID, its execution time and its fees, records it in the history and sends it to the channel
+    fn emit_trade(&mut self, mut trade: Trade) {
+        let now = self.clock.now();
+        trade.id.get_or_insert_with(|| Ulid::new().into());
+        trade.created_at = Some(now);
+        trade.updated_at = Some(now);
+        let notional = trade.price * trade.quantity;
+        trade.maker_fee = notional * self.fees.maker_fee;
+        trade.taker_fee = notional * self.fees.taker_fee;
+        trade.buy_metadata = self.trade_metadata(trade.buy_order_id, trade.buy_remaining);
+        trade.sell_metadata = self.trade_metadata(trade.sell_order_id, trade.sell_remaining);
+        self.trade_history.record(trade.price, trade.quantity);
+        let reference_price = self.follow_trade(trade.price);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(trade),
+            reference_price,
+            ..Default::default()
+        });
+    }
+
+    /// place an order in the orderbook
+    pub fn place_order(&mut self, mut order: Order) {
+        if order.peg_reference.is_some() {
+            let (best_bid, best_ask) = self.peg_references();
+            order.price = self.peg_price(&order, best_bid, best_ask).or(order.price);
+            // Nothing to peg to and no price to start from
+            if order.price.is_none() {
+                let previous_status = set_status(&mut order, OrderStatus::Cancelled);
+                self.publish(OrderbookUpdate {
+                    update_type: OrderbookUpdateType::Cancel,
+                    previous_status,
+                    order: Some(order),
+                    cancel_id: Some(order.id),
+                    ..Default::default()
                 });
+                return;
             }
-        };
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Update,
-                order,
-                trade: None,
-                cancel_id: None,
-                filled_id: None,
-            })
-            .unwrap();
-        self.match_orders();
+        }
+        order.split_display();
+        order.arrival_seq = self.next_arrival();
+        if let Some(expires_at) = order.expires_at {
+            self.expirations.push(Reverse((expires_at, order.id)));
+        }
+        self.rest(order);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Place,
+            order: Some(order),
+            ..Default::default()
+        });
+        self.run_matcher(Some(order));
+    }
+
+    /// amend_order_price moves an order to a new price, the order loses its time priority if the price changes.
+    /// If the new price crosses the book, the amended order is matched right away as the taker.
+    ///
+    /// #Returns
+    /// * Order - The amended order before it is matched, an error publishing nothing if the orderbook
+    ///   does not accept amends, the order is not in it or the new price is not a positive multiple of the tick size
+    pub fn amend_order_price(
+        &mut self,
+        order_id: OrderId,
+        new_price: f64,
+        order_side: OrderSide,
+    ) -> Result<Order, Error> {
+        self.check_state(OrderbookState::accepts_amends)?;
+        let order = self
+            .get_order(order_id, order_side)
+            .ok_or_else(order_not_found)?;
+        self.check_amend(&Order {
+            price: Some(new_price),
+            ..order
+        })?;
+        let now = self.clock.now();
+        let arrival = self.arrivals + 1;
+        let order = self.update_resting(order_id, order_side, |o| {
+            if o.price != Some(new_price) {
+                o.created_at = now;
+                o.arrival_seq = arrival;
+            }
+            o.updated_at = now;
+            o.price = Some(new_price);
+        });
+        let order = order.ok_or_else(order_not_found)?;
+        if order.arrival_seq == arrival {
+            self.arrivals = arrival;
+        }
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Amended,
+            order: Some(order),
+            ..Default::default()
+        });
+        self.run_matcher(Some(order));
+        Ok(order)
     }
 
-    ///amend_order_quantity amends the quantity of an order in the orderbook
+    ///amend_order_quantity amends the quantity of an order in the orderbook.
+    /// The order loses its time priority if the quantity increases and keeps it if the quantity decreases.
+    ///
+    /// #Returns
+    /// * Order - The amended order before it is matched, an error publishing nothing if the orderbook
+    ///   does not accept amends, the order is not in it or the new quantity is not a positive multiple of the lot size
     pub fn amend_order_quantity(
         &mut self,
-        order_id: u128,
+        order_id: OrderId,
         new_quantity: f64,
         order_side: OrderSide,
-    ) {
-        let mut order: Option<Order> = None;
-        match order_side {
-            OrderSide::Buy => {
-                self.bids.modify(|o| {
-                    if o.id == order_id {
-                        o.quantity = new_quantity;
-                        order = Some(*o);
-                    }
-                });
-            }
-            OrderSide::Sell => {
-                self.asks.modify(|o| {
-                    if o.id == order_id {
-                        o.quantity = new_quantity;
-                        order = Some(*o);
-                    }
-                });
+    ) -> Result<Order, Error> {
+        self.check_state(OrderbookState::accepts_amends)?;
+        let order = self
+            .get_order(order_id, order_side)
+            .ok_or_else(order_not_found)?;
+        self.check_amend(&Order {
+            quantity: new_quantity,
+            ..order
+        })?;
+        let now = self.clock.now();
+        let arrival = self.arrivals + 1;
+        let order = self.update_resting(order_id, order_side, |o| {
+            if new_quantity > o.quantity {
+                o.created_at = now;
+                o.arrival_seq = arrival;
             }
+            o.updated_at = now;
+            o.quantity = new_quantity;
+        });
+        let order = order.ok_or_else(order_not_found)?;
+        if order.arrival_seq == arrival {
+            self.arrivals = arrival;
         }
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Update,
-                order,
-                trade: None,
-                cancel_id: None,
-                filled_id: None,
-            })
-            .unwrap();
-        self.match_orders();
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Amended,
+            order: Some(order),
+            ..Default::default()
+        });
+        self.run_matcher(Some(order));
+        Ok(order)
     }
 
-    /// update_order updates the quantity of an order in the orderbook
-    pub fn update_order(&mut self, order_id: u128, new_quantity: f64, order_side: OrderSide) {
-        let mut order: Option<Order> = None;
-        match order_side {
-            OrderSide::Buy => {
-                self.bids.modify(|o| {
-                    if o.id == order_id {
-                        o.quantity = new_quantity;
-                        order = Some(*o);
-                    }
-                });
-            }
-            OrderSide::Sell => {
-                self.asks.modify(|o| {
-                    if o.id == order_id {
-                        o.quantity = new_quantity;
-                        order = Some(*o);
-                    }
-                });
-            }
+    /// replace_order cancels and re-inserts an order with a new price and quantity, publishing a single Replace update.
+    /// The order loses its time priority only if the price changes or the quantity increases.
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'new_price' - The new price of the order
+    /// * 'new_quantity' - The new total quantity of the order
+    ///
+    /// #Returns
+    /// * Order - The replaced order before it is matched, an error publishing nothing if the orderbook does
+    ///   not accept amends, the order is not in it or the new price or quantity is invalid
+    pub fn replace_order(
+        &mut self,
+        order_id: OrderId,
+        new_price: f64,
+        new_quantity: f64,
+    ) -> Result<Order, Error> {
+        self.check_state(OrderbookState::accepts_amends)?;
+        let order = self
+            .get_order(order_id, OrderSide::Buy)
+            .or_else(|| self.get_order(order_id, OrderSide::Sell))
+            .ok_or_else(order_not_found)?;
+        self.check_amend(&Order {
+            price: Some(new_price),
+            quantity: new_quantity,
+            hidden_quantity: 0.0,
+            ..order
+        })?;
+        let mut order = self
+            .remove_order(order_id, order.side)
+            .ok_or_else(order_not_found)?;
+        let now = self.clock.now();
+        if order.price != Some(new_price) || new_quantity > order.quantity + order.hidden_quantity {
+            order.created_at = now;
+            order.arrival_seq = self.next_arrival();
         }
+        order.updated_at = now;
+        order.price = Some(new_price);
+        order.quantity = new_quantity;
+        order.hidden_quantity = 0.0;
+        order.split_display();
+        self.rest(order);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Replace,
+            order: Some(order),
+            ..Default::default()
+        });
+        self.match_orders();
+        Ok(order)
+    }
 
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Update,
-                order,
-                trade: None,
-                cancel_id: None,
-                filled_id: None,
-            })
-            .unwrap();
+    /// update_order sets the quantity of an order in the orderbook as an amend, without matching nor priority change
+    ///
+    /// #Returns
+    /// * Order - The updated order, an error publishing nothing if the order is not in the orderbook
+    pub fn update_order(
+        &mut self,
+        order_id: OrderId,
+        new_quantity: f64,
+        order_side: OrderSide,
+    ) -> Result<Order, Error> {
+        let order = self
+            .update_resting(order_id, order_side, |o| o.quantity = new_quantity)
+            .ok_or_else(order_not_found)?;
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Amended,
+            order: Some(order),
+            ..Default::default()
+        });
+        Ok(order)
+    }
+
+    /// decrement_for_fill takes an executed quantity from a resting order which stays in the book,
+    /// publishing a PartiallyFilled update with the executed quantity. An order left with dust is
+    /// filled instead, as order_filled does.
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'fill_quantity' - The executed quantity, less than the quantity of the order
+    /// * 'order_side' - The order side
+    pub fn decrement_for_fill(
+        &mut self,
+        order_id: OrderId,
+        fill_quantity: f64,
+        order_side: OrderSide,
+    ) {
+        let remainder = self
+            .get_order(order_id, order_side)
+            .map(|o| o.quantity - fill_quantity);
+        if remainder.is_some_and(|remainder| self.is_dust(remainder)) {
+            self.order_filled(order_id, order_side);
+            return;
+        }
+        let mut previous_status = None;
+        let order = self.update_resting(order_id, order_side, |o| {
+            o.quantity -= fill_quantity;
+            previous_status = set_status(o, OrderStatus::PartiallyFilled);
+        });
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::PartiallyFilled,
+            order,
+            fill_quantity: Some(fill_quantity),
+            previous_status,
+            ..Default::default()
+        });
     }
 
     /// match orders in the orderbook
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(symbol = %self.symbol))
+    )]
     pub fn match_orders(&mut self) {
-        while let Some(ask) = self.asks.peek() {
-            if let Some(bid) = self.bids.peek() {
-                if bid.price >= ask.price {
-                    if ask.quantity > bid.quantity {
-                        self.order_filled(bid.id, bid.side);
-                        self.update_order(ask.id, ask.quantity - bid.quantity, ask.side);
-                        let trade = Trade {
-                            id: None,
-                            symbol: self.symbol,
-                            price: ask.price.unwrap(),
-                            quantity: bid.quantity,
-                            buy_order_id: bid.id,
-                            sell_order_id: ask.id,
-                            buy_user_id: bid.user_id,
-                            sell_user_id: ask.user_id,
-                            status: Default::default(),
-                            created_at: None,
-                            updated_at: None,
-                        };
-                        self.tx
-                            .send(OrderbookUpdate {
-                                symbol: self.symbol,
-                                update_type: OrderbookUpdateType::NewTrades,
-                                order: None,
-                                trade: Some(trade),
-                                cancel_id: None,
-                                filled_id: None,
-                            })
-                            .unwrap();
-                    } else if ask.quantity < bid.quantity {
-                        self.order_filled(ask.id, ask.side);
-                        self.update_order(bid.id, bid.quantity - ask.quantity, bid.side);
-                        let trade = Trade {
-                            id: None,
-                            symbol: self.symbol,
-                            price: ask.price.unwrap(),
-                            quantity: ask.quantity,
-                            buy_order_id: bid.id,
-                            sell_order_id: ask.id,
-                            buy_user_id: bid.user_id,
-                            sell_user_id: ask.user_id,
-                            status: Default::default(),
-                            created_at: None,
-                            updated_at: None,
-                        };
-                        self.tx
-                            .send(OrderbookUpdate {
-                                symbol: self.symbol,
-                                update_type: OrderbookUpdateType::NewTrades,
-                                order: None,
-                                trade: Some(trade),
-                                cancel_id: None,
-                                filled_id: None,
-                            })
-                            .unwrap();
-                    } else {
-                        self.order_filled(ask.id, ask.side);
-                        self.order_filled(bid.id, bid.side);
-                        let trade = Trade {
-                            id: None,
-                            symbol: self.symbol,
-                            price: ask.price.unwrap(),
-                            quantity: ask.quantity,
-                            buy_order_id: bid.id,
-                            sell_order_id: ask.id,
-                            buy_user_id: bid.user_id,
-                            sell_user_id: ask.user_id,
-                            status: Default::default(),
-                            created_at: None,
-                            updated_at: None,
-                        };
-                        self.tx
-                            .send(OrderbookUpdate {
-                                symbol: self.symbol,
-                                update_type: OrderbookUpdateType::NewTrades,
-                                order: None,
-                                trade: Some(trade),
-                                cancel_id: None,
-                                filled_id: None,
-                            })
-                            .unwrap();
-                    }
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
+        self.run_matcher(None);
     }
 
-    /// cancel_order cancels an order in the orderbook
-    pub fn cancel_order(&mut self, order_id: u128, order_side: OrderSide) {
-        match order_side {
-            OrderSide::Buy => {
-                self.bids.retain(|o| o.id != order_id);
-            }
-            OrderSide::Sell => {
-                self.asks.retain(|o| o.id != order_id);
-            }
-        }
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Cancel,
-                order: None,
-                trade: None,
-                cancel_id: Some(order_id),
-                filled_id: None,
-            })
-            .unwrap();
+    /// run_matcher runs the matching algorithm of the orderbook then records and publishes its trades
+    ///
+    /// #Returns
+    /// * (f64, Option<f64>) - The quantity executed by the taker and the price of its last execution
+    fn run_matcher(&mut self, taker: Option<Order>) -> (f64, Option<f64>) {
+        let executed = match self.state.matches_orders() {
+            true => self.execute(taker),
+            false => (0.0, None),
+        };
+        self.after_book_change();
+        self.debug_check();
+        executed
     }
 
-    /// order_filled marks an order as filled in the orderbook
-    pub fn order_filled(&mut self, order_id: u128, order_side: OrderSide) {
-        match order_side {
-            OrderSide::Buy => {
-                self.bids.retain(|o| o.id != order_id);
+    /// execute runs the matching algorithm once and publishes its trades
+    ///
+    /// #Returns
+    /// * (f64, Option<f64>) - The quantity executed by the taker and the price of its last execution
+    fn execute(&mut self, taker: Option<Order>) -> (f64, Option<f64>) {
+        #[cfg(feature = "tracing")]
+        let start = self.clock.monotonic();
+        let mut matcher = std::mem::replace(&mut self.matcher, Box::new(PriceTimeMatcher));
+        self.collar = taker
+            .filter(|o| o.order_type == OrderType::Market)
+            .and_then(|o| Some((o.side, self.collar_price(o.side)?)));
+        self.matching = true;
+        let trades = matcher.match_book(self, taker);
+        self.matching = false;
+        self.collar = None;
+        self.matcher = matcher;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            symbol = %self.symbol,
+            taker_id = ?taker.map(|o| o.id),
+            trades = trades.len(),
+            latency_ns = self.clock.monotonic().saturating_sub(start),
+            "matching done"
+        );
+        let mut executed = (0.0, None);
+        for trade in trades {
+            if taker.is_some_and(|t| t.id == trade.buy_order_id || t.id == trade.sell_order_id) {
+                executed = (executed.0 + trade.quantity, Some(trade.price));
             }
-            OrderSide::Sell => {
-                self.asks.retain(|o| o.id != order_id);
+            self.emit_trade(trade);
+        }
+        if std::mem::take(&mut self.band_tripped)
+            && self
+                .price_band
+                .is_some_and(|band| band.action == BandAction::Reject)
+        {
+            if let Some(order) = taker.filter(|o| o.order_type == OrderType::Limit) {
+                // the taker may have left the book already
+                let _ = self.cancel_order(order.id, order.side);
             }
         }
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::Filled,
-                order: None,
-                trade: None,
-                cancel_id: None,
-                filled_id: Some(order_id),
-            })
-            .unwrap();
+        if self.bids.peek().is_some() && self.asks.peek().is_some() {
+            self.last_mid = Some(self.get_mid_price());
+        }
+        executed
     }
 
-    /// add_order adds an order to the orderbook without matching it
-    pub fn add_order(&mut self, order: Order) {
-        self.tx
-            .send(OrderbookUpdate {
-                symbol: self.symbol,
-                update_type: OrderbookUpdateType::New,
-                order: Some(order),
-                trade: None,
-                cancel_id: None,
-                filled_id: None,
-            })
-            .unwrap();
-        match order.order_type {
-            OrderType::Limit => self.place_order(order),
-            OrderType::Market => {
-                let mut quantity = order.quantity;
-                if order.side == OrderSide::Buy {
-                    while let Some(ask) = self.asks.peek() {
-                        if ask.quantity <= quantity {
-                            self.order_filled(ask.id, ask.side);
-                            quantity -= ask.quantity;
-                            let trade = Trade {
-                                id: None,
-                                symbol: self.symbol,
-                                price: ask.price.unwrap(),
-                                quantity: ask.quantity,
-                                buy_order_id: order.id,
-                                sell_order_id: ask.id,
-                                buy_user_id: order.user_id,
-                                sell_user_id: ask.user_id,
-                                status: Default::default(),
-                                created_at: None,
-                                updated_at: None,
-                            };
-                            self.tx
-                                .send(OrderbookUpdate {
-                                    symbol: self.symbol,
-                                    update_type: OrderbookUpdateType::NewTrades,
-                                    order: None,
-                                    trade: Some(trade),
-                                    filled_id: None,
-                                    cancel_id: None,
-                                })
-                                .unwrap();
-                        } else {
-                            self.update_order(ask.id, ask.quantity - quantity, ask.side);
-                            let trade = Trade {
-                                id: None,
-                                symbol: self.symbol,
-                                price: ask.price.unwrap(),
-                                quantity,
-                                buy_order_id: order.id,
-                                sell_order_id: ask.id,
-                                buy_user_id: order.user_id,
-                                sell_user_id: ask.user_id,
-                                status: Default::default(),
-                                created_at: None,
-                                updated_at: None,
-                            };
-                            self.tx
-                                .send(OrderbookUpdate {
-                                    symbol: self.symbol,
-                                    update_type: OrderbookUpdateType::NewTrades,
-                                    order: None,
-                                    trade: Some(trade),
-                                    filled_id: None,
-                                    cancel_id: None,
-                                })
-                                .unwrap();
-                            break;
-                        }
-                    }
-                } else {
-                    while let Some(bid) = self.bids.peek() {
-                        if bid.quantity <= quantity {
-                            quantity -= bid.quantity;
-                            self.order_filled(bid.id, bid.side);
-                            let trade = Trade {
-                                id: None,
-                                symbol: self.symbol,
-                                price: bid.price.unwrap(),
-                                quantity: bid.quantity,
-                                buy_order_id: bid.id,
-                                sell_order_id: order.id,
-                                buy_user_id: bid.user_id,
-                                sell_user_id: order.user_id,
-                                status: Default::default(),
-                                created_at: None,
-                                updated_at: None,
-                            };
-                            self.tx
-                                .send(OrderbookUpdate {
-                                    symbol: self.symbol,
-                                    update_type: OrderbookUpdateType::NewTrades,
-                                    order: None,
-                                    trade: Some(trade),
-                                    filled_id: None,
-                                    cancel_id: None,
-                                })
-                                .unwrap();
-                        } else {
-                            self.update_order(bid.id, bid.quantity - quantity, bid.side);
-                            let trade = Trade {
-                                id: None,
-                                symbol: self.symbol,
-                                price: bid.price.unwrap(),
-                                quantity,
-                                buy_order_id: bid.id,
-                                sell_order_id: order.id,
-                                buy_user_id: bid.user_id,
-                                sell_user_id: order.user_id,
-                                status: Default::default(),
-                                created_at: None,
-                                updated_at: None,
-                            };
-                            self.tx
-                                .send(OrderbookUpdate {
-                                    symbol: self.symbol,
-                                    update_type: OrderbookUpdateType::NewTrades,
-                                    order: None,
-                                    trade: Some(trade),
-                                    filled_id: None,
-                                    cancel_id: None,
-                                })
-                                .unwrap();
-                            break;
-                        }
-                    }
-                }
+    /// after_book_change follows a change of the resting orders: the pegged orders are repriced,
+    /// the conditional orders whose condition is met are activated and the indicative auction is
+    /// refreshed during a call auction
+    fn after_book_change(&mut self) {
+        self.track_pegs();
+        self.trigger_conditionals();
+        self.refresh_indicative_auction();
+        if let Some(threshold) = self.compact_threshold {
+            if self.pool_stats().free >= threshold.max(1) {
+                self.compact();
             }
         }
     }
-}
 
+    /// refresh_indicative_auction recomputes the result the call auction would have and publishes
+    /// an IndicativeAuction update when it differs from the last one published
+    fn refresh_indicative_auction(&mut self) {
+        if self.state != OrderbookState::AuctionCall {
+            self.indicative = None;
+            return;
+        }
+        let result = self.indicative_auction();
+        if self.indicative == Some(result) {
+            return;
+        }
+        self.indicative = Some(result);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::IndicativeAuction,
+            auction: Some(result),
+            ..Default::default()
+        });
+    }
 
-#[cfg(test)]
-mod tests {
-    
-    use std::time::Instant;
-    
-
-    use super::*;
-    use crate::enums::order_type::OrderType;
-    use crate::enums::side::OrderSide;
-    use crate::structs::order::Order;
-    use crossbeam_channel::unbounded;
-    use ulid::Ulid;
+    /// trigger_conditionals adds the conditional orders whose condition the book meets, within the
+    /// change which met it. An activated order changes the book in turn and may activate the next ones.
+    fn trigger_conditionals(&mut self) {
+        if self.conditionals.is_empty() || self.matching || !self.state.matches_orders() {
+            return;
+        }
+        let (met, held): (Vec<_>, Vec<_>) = std::mem::take(&mut self.conditionals)
+            .into_iter()
+            .partition(|(condition, _)| condition.is_met(self));
+        self.conditionals = held;
+        for (_, order) in met {
+            self.add_order(order);
+        }
+    }
 
-    #[test]
-    fn test_orderbook_new() {
-        let (tx, _) = unbounded::<OrderbookUpdate>();
-        let orderbook = Orderbook::new(Ulid::new().into(), tx);
-        assert_eq!(orderbook.bids.len(), 0);
-        assert_eq!(orderbook.asks.len(), 0);
+    /// track_pegs reprices the pegged orders after a change of the book and matches the ones which cross it,
+    /// until the pegged prices are stable
+    fn track_pegs(&mut self) {
+        while self.reprice_pegged() && self.state.matches_orders() {
+            self.execute(None);
+        }
     }
 
-    #[test]
-    fn test_orderbook_add_order() {
-        let (tx, r) = unbounded::<OrderbookUpdate>();
-        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
-        let order = Order::new(
-            Ulid::new().into(),
-            Ulid::new().into(),
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
-        );
-        // Spawn a new thread to keep the receiver alive
-        std::thread::spawn(move || {
-            println!("{:?}", r.recv().unwrap());
-        });
-        orderbook.add_order(order.clone());
-        assert_eq!(orderbook.bids.len(), 1);
-        assert_eq!(orderbook.asks.len(), 0);
+    /// reprice_pegged moves the pegged orders to the current price of their reference and publishes a Repriced
+    /// update for each order which moves, a repriced order loses its time priority
+    ///
+    /// #Returns
+    /// * bool - Whether an order moved and the book is crossed
+    fn reprice_pegged(&mut self) -> bool {
+        if self.pegged_orders == 0 || self.matching {
+            return false;
+        }
+        let (best_bid, best_ask) = self.peg_references();
+        let moves: Vec<_> = self
+            .bids
+            .iter_ref()
+            .chain(self.asks.iter_ref())
+            .filter_map(|o| {
+                let price = self.peg_price(o, best_bid, best_ask)?;
+                (o.price != Some(price)).then_some((o.id, o.side, price))
+            })
+            .collect();
+        let now = self.clock.now();
+        for &(order_id, side, price) in moves.iter() {
+            let arrival = self.next_arrival();
+            let order = self.update_resting(order_id, side, |o| {
+                o.price = Some(price);
+                o.arrival_seq = arrival;
+                o.created_at = now;
+                o.updated_at = now;
+            });
+            self.publish(OrderbookUpdate {
+                update_type: OrderbookUpdateType::Repriced,
+                order,
+                ..Default::default()
+            });
+        }
+        !moves.is_empty()
+            && matches!(
+                (self.bids.peek_ref(), self.asks.peek_ref()),
+                (Some(bid), Some(ask)) if bid.price >= ask.price
+            )
+    }
+
+    /// peg_references returns the best bid and the best ask of the orders which are not pegged,
+    /// the prices the pegs track
+    fn peg_references(&self) -> (Option<f64>, Option<f64>) {
+        let best = |heap: &ModifiableBinaryHeap<Order>, better: fn(f64, f64) -> f64| {
+            heap.iter_ref()
+                .filter(|o| o.peg_reference.is_none())
+                .filter_map(|o| o.price)
+                .reduce(better)
+        };
+        (best(&self.bids, f64::max), best(&self.asks, f64::min))
+    }
+
+    /// peg_price computes the price of a pegged order from the reference prices, rounded to the tick size
+    /// away from the opposite side
+    ///
+    /// #Returns
+    /// * Option<f64> - The pegged price, None if the order is not pegged or its reference is missing
+    fn peg_price(
+        &self,
+        order: &Order,
+        best_bid: Option<f64>,
+        best_ask: Option<f64>,
+    ) -> Option<f64> {
+        let reference = match order.peg_reference? {
+            PegReference::Mid => (best_bid? + best_ask?) / 2.0,
+            PegReference::BestBid => best_bid?,
+            PegReference::BestAsk => best_ask?,
+        };
+        let mut price = reference + order.peg_offset;
+        if let Some(tick_size) = self.tick_size {
+            let ticks = price / tick_size;
+            let ticks = match order.side {
+                OrderSide::Buy => (ticks + 1e-9).floor(),
+                OrderSide::Sell => (ticks - 1e-9).ceil(),
+            };
+            price = ticks * tick_size;
+        }
+        (price > 0.0).then_some(price)
+    }
+
+    /// match_market_order sweeps the book with a market order, then cancels or rests its remainder
+    /// as configured if the order exhausted the opposite side
+    fn match_market_order(&mut self, mut order: Order) {
+        let (executed, last_price) = self.run_matcher(Some(order));
+        let collared = std::mem::take(&mut self.collar_tripped);
+        let opposite = match order.side {
+            OrderSide::Buy => &self.asks,
+            OrderSide::Sell => &self.bids,
+        };
+        order.quantity -= executed;
+        // The matching stopped before the side ran out of orders the remainder can trade with
+        let tradable = |maker: &Order| {
+            let quantity = order.quantity.min(maker.quantity);
+            maker.accepts_fill(quantity) && order.accepts_fill(quantity)
+        };
+        if self.is_dust(order.quantity) || (!collared && opposite.iter_ref().any(tradable)) {
+            return;
+        }
+        match (self.market_remainder, last_price) {
+            (MarketRemainder::ConvertToLimit, Some(price)) => {
+                order.order_type = OrderType::Limit;
+                order.price = Some(price);
+                set_status(&mut order, OrderStatus::PartiallyFilled);
+                order.created_at = self.clock.now();
+                order.updated_at = order.created_at;
+                self.place_order(order);
+            }
+            _ => {
+                let previous_status = set_status(&mut order, OrderStatus::Cancelled);
+                self.publish(OrderbookUpdate {
+                    update_type: OrderbookUpdateType::Cancel,
+                    previous_status,
+                    order: Some(order),
+                    cancel_id: Some(order.id),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// within_price_band checks a match price against the market collar and the price band, matching
+    /// algorithms call it before each execution and stop matching when it returns false.
+    /// A market order stopped by the collar is handled as if it exhausted the book.
+    /// When the price is outside of the band a CircuitBreaker update is published and,
+    /// depending on the band action, the orderbook is halted or the taker order is cancelled.
+    ///
+    /// #Parameters
+    /// * 'price' - The price the match would execute at
+    pub fn within_price_band(&mut self, price: f64) -> bool {
+        let beyond_collar = match self.collar {
+            Some((OrderSide::Buy, limit)) => price > limit,
+            Some((OrderSide::Sell, limit)) => price < limit,
+            None => false,
+        };
+        if beyond_collar {
+            self.collar_tripped = true;
+            return false;
+        }
+        let Some(band) = self.price_band else {
+            return true;
+        };
+        let reference_price = match band.reference {
+            PriceReference::LastTrade => self.trade_history.last().map(|e| e.price),
+            PriceReference::Mid => self.last_mid,
+            PriceReference::Reference => self.reference_price,
+        };
+        let Some(reference_price) = reference_price else {
+            return true;
+        };
+        if band.contains(price, reference_price) {
+            return true;
+        }
+        self.band_tripped = true;
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::CircuitBreaker,
+            circuit_breaker: Some(CircuitBreakerEvent {
+                price,
+                reference_price,
+                action: band.action,
+            }),
+            ..Default::default()
+        });
+        if band.action == BandAction::Halt {
+            self.halt();
+        }
+        false
+    }
+
+    /// reference_price returns the price the price protections are centered on: the market collar, the
+    /// auction collar and the price bands referring to it. It is maintained according to the reference
+    /// source of the orderbook, None until a trade or `set_reference_price` gives it.
+    pub fn reference_price(&self) -> Option<f64> {
+        self.reference_price
+    }
+
+    /// set_reference_price sets the reference price from outside, e.g. from an external index, and
+    /// publishes a ReferencePrice update. A LastTrade source replaces it with the next trade.
+    ///
+    /// #Parameters
+    /// * 'price' - The reference price
+    pub fn set_reference_price(&mut self, price: f64) {
+        self.reference_price = Some(price);
+        self.session_opening = false;
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::ReferencePrice,
+            reference_price: Some(price),
+            ..Default::default()
+        });
+    }
+
+    /// follow_trade moves the reference price to the price of a trade when the reference source follows it
+    ///
+    /// #Returns
+    /// * Option<f64> - The new reference price, None if the trade did not move it
+    fn follow_trade(&mut self, price: f64) -> Option<f64> {
+        let follows = match self.reference_source {
+            ReferenceSource::LastTrade => true,
+            ReferenceSource::External => false,
+            ReferenceSource::SessionOpen => std::mem::take(&mut self.session_opening),
+        };
+        if !follows || self.reference_price == Some(price) {
+            return None;
+        }
+        self.reference_price = Some(price);
+        Some(price)
+    }
+
+    /// collar_price gives the worst price a market order of a side executes at, None without a
+    /// market collar or a reference price
+    fn collar_price(&self, side: OrderSide) -> Option<f64> {
+        let (collar, reference) = (self.market_collar?, self.reference_price?);
+        Some(match side {
+            OrderSide::Buy => reference * (1.0 + collar),
+            OrderSide::Sell => reference * (1.0 - collar),
+        })
+    }
+
+    /// next_arrival gives the next arrival sequence number, ranking an order behind the orders already in the book
+    fn next_arrival(&mut self) -> u64 {
+        self.arrivals += 1;
+        self.arrivals
+    }
+
+    /// rest puts an order in its side of the orderbook and counts it in its price level
+    fn rest(&mut self, order: Order) {
+        self.levels.add(&order);
+        if order.peg_reference.is_some() {
+            self.pegged_orders += 1;
+        }
+        match order.side {
+            OrderSide::Buy => self.bids.push(order),
+            OrderSide::Sell => self.asks.push(order),
+        }
+    }
+
+    /// remove_order removes an order from its side of the orderbook and returns it
+    fn remove_order(&mut self, order_id: OrderId, order_side: OrderSide) -> Option<Order> {
+        let removed = match order_side {
+            OrderSide::Buy => self.bids.remove(&order_id),
+            OrderSide::Sell => self.asks.remove(&order_id),
+        };
+        if let Some(order) = removed.as_ref() {
+            self.levels.remove(order);
+            if order.peg_reference.is_some() {
+                self.pegged_orders -= 1;
+            }
+        }
+        removed
+    }
+
+    /// check_amend checks an order amended in place before the book is changed: a finite positive price
+    /// on the tick size and a finite positive quantity on the lot size
+    fn check_amend(&self, amended: &Order) -> Result<(), Error> {
+        if !amended
+            .price
+            .is_some_and(|price| price.is_finite() && price > 0.0)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Limit order price must be positive",
+            ));
+        }
+        if !(amended.quantity.is_finite() && amended.quantity > 0.0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Order quantity must be positive",
+            ));
+        }
+        if let (Some(tick_size), Some(price)) = (self.tick_size, amended.price) {
+            if !is_multiple_of(price, tick_size) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Order price must be a multiple of the tick size",
+                ));
+            }
+        }
+        if let Some(lot_size) = self.lot_size {
+            if !is_multiple_of(amended.quantity + amended.hidden_quantity, lot_size) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Order quantity must be a multiple of the lot size",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// update_resting modifies a resting order in place and returns its new state
+    fn update_resting<F>(
+        &mut self,
+        order_id: OrderId,
+        order_side: OrderSide,
+        modify: F,
+    ) -> Option<Order>
+    where
+        F: FnOnce(&mut Order),
+    {
+        let mut before = None;
+        let modify = |o: &mut Order| {
+            before = Some(*o);
+            modify(o);
+        };
+        let updated = match order_side {
+            OrderSide::Buy => self.bids.update(&order_id, modify),
+            OrderSide::Sell => self.asks.update(&order_id, modify),
+        };
+        if let (Some(before), Some(updated)) = (before, updated.as_ref()) {
+            self.levels.remove(&before);
+            self.levels.add(updated);
+        }
+        updated
+    }
+
+    /// cancel_order cancels an order in the orderbook
+    ///
+    /// #Returns
+    /// * Order - The cancelled order, an error publishing nothing if the orderbook does not accept
+    ///   cancels or the order is not in it
+    pub fn cancel_order(
+        &mut self,
+        order_id: OrderId,
+        order_side: OrderSide,
+    ) -> Result<Order, Error> {
+        self.check_state(OrderbookState::accepts_cancels)?;
+        let mut order = self
+            .remove_order(order_id, order_side)
+            .ok_or_else(order_not_found)?;
+        let previous_status = set_status(&mut order, OrderStatus::Cancelled);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            previous_status,
+            order: Some(order),
+            cancel_id: Some(order_id),
+            ..Default::default()
+        });
+        self.after_book_change();
+        self.debug_check();
+        Ok(order)
+    }
+
+    /// check_state rejects an operation the trading state of the orderbook does not accept
+    fn check_state(&self, accepted: fn(&OrderbookState) -> bool) -> Result<(), Error> {
+        if accepted(&self.state) {
+            return Ok(());
+        }
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "Operation not accepted while the orderbook is {}",
+                self.state
+            ),
+        ))
+    }
+
+    /// cancel_all cancels every order of the orderbook
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn cancel_all(&mut self) -> Vec<Order> {
+        self.cancel_where(|_| true)
+    }
+
+    /// delist cancels every order then publishes a Delisted update and closes the orderbook,
+    /// it is the last update of the orderbook
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn delist(&mut self) -> Vec<Order> {
+        let cancelled = self.cancel_all();
+        self.state = OrderbookState::Closed;
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Delisted,
+            ..Default::default()
+        });
+        cancelled
+    }
+
+    /// migrate_out removes every resting order to move it to another orderbook and publishes a Migrated
+    /// update with the order as it enters its new orderbook, the orders are taken in their arrival order
+    ///
+    /// #Parameters
+    /// * 'migrate' - The order as it enters its new orderbook
+    ///
+    /// #Returns
+    /// * Vec<(Order, Option<Metadata>)> - The migrated orders in their arrival order, with their metadata
+    pub fn migrate_out(
+        &mut self,
+        migrate: impl Fn(&Order) -> Order,
+    ) -> Vec<(Order, Option<Metadata>)> {
+        let mut resting: Vec<Order> = self
+            .bids
+            .iter_ref()
+            .chain(self.asks.iter_ref())
+            .copied()
+            .collect();
+        resting.sort_by_key(|o| o.arrival_seq);
+        let mut migrated = Vec::with_capacity(resting.len());
+        for order in resting {
+            self.remove_order(order.id, order.side);
+            let metadata = self.metadata.get(&order.id).cloned();
+            let order = migrate(&order);
+            self.publish(OrderbookUpdate {
+                update_type: OrderbookUpdateType::Migrated,
+                order: Some(order),
+                ..Default::default()
+            });
+            migrated.push((order, metadata));
+        }
+        self.after_book_change();
+        self.debug_check();
+        migrated
+    }
+
+    /// migrate_in rests the orders migrated from another orderbook in the order given, publishing a
+    /// Place update for each of them, then matches the book
+    ///
+    /// #Parameters
+    /// * 'orders' - The orders returned by migrate_out, with their metadata
+    pub fn migrate_in(&mut self, orders: Vec<(Order, Option<Metadata>)>) {
+        for (mut order, metadata) in orders {
+            if let Some(metadata) = metadata {
+                self.metadata.insert(order.id, metadata);
+            }
+            order.arrival_seq = self.next_arrival();
+            if let Some(expires_at) = order.expires_at {
+                self.expirations.push(Reverse((expires_at, order.id)));
+            }
+            self.rest(order);
+            self.publish(OrderbookUpdate {
+                update_type: OrderbookUpdateType::Place,
+                order: Some(order),
+                ..Default::default()
+            });
+        }
+        self.run_matcher(None);
+    }
+
+    /// cancel_all_for_user cancels every order of a user in the orderbook
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn cancel_all_for_user(&mut self, user_id: UserId) -> Vec<Order> {
+        self.cancel_where(|o| o.user_id == user_id)
+    }
+
+    /// remove_where removes the matching orders in a single pass over each side and returns them
+    fn remove_where<F>(&mut self, mut predicate: F) -> Vec<Order>
+    where
+        F: FnMut(&Order) -> bool,
+    {
+        let mut removed = Vec::new();
+        for heap in [&mut self.bids, &mut self.asks] {
+            heap.retain(|o| {
+                if predicate(o) {
+                    removed.push(*o);
+                    return false;
+                }
+                true
+            });
+        }
+        for order in removed.iter() {
+            self.levels.remove(order);
+        }
+        self.pegged_orders -= removed.iter().filter(|o| o.peg_reference.is_some()).count();
+        removed
+    }
+
+    /// cancel_where removes the matching orders then publishes their cancel updates as one batch
+    fn cancel_where<F>(&mut self, mut predicate: F) -> Vec<Order>
+    where
+        F: FnMut(&Order) -> bool,
+    {
+        let mut cancelled = self.remove_where(&mut predicate);
+        // the held conditional orders are cancelled with the resting ones
+        let (held, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.conditionals)
+            .into_iter()
+            .partition(|(_, order)| predicate(order));
+        self.conditionals = kept;
+        cancelled.extend(held.into_iter().map(|(_, order)| order));
+        for order in cancelled.iter_mut() {
+            let previous_status = set_status(order, OrderStatus::Cancelled);
+            self.publish(OrderbookUpdate {
+                update_type: OrderbookUpdateType::Cancel,
+                previous_status,
+                order: Some(*order),
+                cancel_id: Some(order.id),
+                ..Default::default()
+            });
+        }
+        self.after_book_change();
+        cancelled
+    }
+
+    /// tick expires the Good-Till-Date orders whose expiry is reached and publishes an Expired update for each
+    ///
+    /// #Parameters
+    /// * 'now' - The current time in milliseconds since UNIX epoch
+    ///
+    /// #Returns
+    /// * Vec<Order> - The expired orders
+    pub fn tick(&mut self, now: u64) -> Vec<Order> {
+        let mut due = HashSet::new();
+        while let Some(Reverse((expires_at, order_id))) = self.expirations.peek() {
+            if *expires_at > now {
+                break;
+            }
+            due.insert(*order_id);
+            self.expirations.pop();
+        }
+        if due.is_empty() {
+            return Vec::new();
+        }
+        let mut expired = self.remove_where(|o| due.contains(&o.id));
+        for order in expired.iter_mut() {
+            self.publish_expired(order);
+        }
+        self.after_book_change();
+        self.debug_check();
+        expired
+    }
+
+    /// expire_day_orders expires the Day orders at the close of the trading session, the held
+    /// conditional ones included, and publishes an Expired update for each
+    ///
+    /// #Returns
+    /// * Vec<Order> - The expired orders
+    pub fn expire_day_orders(&mut self) -> Vec<Order> {
+        let is_day = |o: &Order| o.time_in_force == TimeInForce::Day;
+        let mut expired = self.remove_where(is_day);
+        let (held, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.conditionals)
+            .into_iter()
+            .partition(|(_, order)| is_day(order));
+        self.conditionals = kept;
+        expired.extend(held.into_iter().map(|(_, order)| order));
+        for order in expired.iter_mut() {
+            self.publish_expired(order);
+        }
+        self.after_book_change();
+        self.debug_check();
+        expired
+    }
+
+    fn publish_expired(&mut self, order: &mut Order) {
+        let previous_status = set_status(order, OrderStatus::Expired);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Expired,
+            previous_status,
+            order: Some(*order),
+            ..Default::default()
+        });
+    }
+
+    /// is_dust checks whether the remainder of an order is too small to stay in the book: below the
+    /// dust threshold, or nothing but floating point error. Matching algorithms fill such an order.
+    pub fn is_dust(&self, remainder: f64) -> bool {
+        is_dust(remainder, self.dust_threshold)
+    }
+
+    /// order_filled marks an order as filled in the orderbook
+    pub fn order_filled(&mut self, order_id: OrderId, order_side: OrderSide) {
+        let order = self.remove_order(order_id, order_side);
+        if let Some(mut iceberg) = order.filter(|o| o.hidden_quantity > 0.0) {
+            self.replenish(&mut iceberg);
+            return;
+        }
+        let mut previous_status = None;
+        let order = order.map(|mut o| {
+            o.quantity = 0.0;
+            previous_status = set_status(&mut o, OrderStatus::Filled);
+            o
+        });
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Filled,
+            previous_status,
+            order,
+            filled_id: Some(order_id),
+            ..Default::default()
+        });
+    }
+
+    /// replenish shows the next slice of a filled iceberg order, the slice goes behind the orders at its price
+    fn replenish(&mut self, order: &mut Order) {
+        let fill_quantity = order.quantity;
+        order.replenish();
+        let previous_status = set_status(order, OrderStatus::PartiallyFilled);
+        order.created_at = self.clock.now();
+        order.updated_at = order.created_at;
+        order.arrival_seq = self.next_arrival();
+        self.rest(*order);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::PartiallyFilled,
+            order: Some(*order),
+            fill_quantity: Some(fill_quantity),
+            previous_status,
+            ..Default::default()
+        });
+    }
+
+    /// add_order adds an order to the orderbook without matching it
+    pub fn add_order(&mut self, mut order: Order) {
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::New,
+            order: Some(order),
+            ..Default::default()
+        });
+        if order.expires_at.is_some() && order.is_expired(self.clock.now_millis()) {
+            self.publish_expired(&mut order);
+            return;
+        }
+        match order.order_type {
+            OrderType::Limit if self.state.accepts_orders() => self.place_order(order),
+            OrderType::Market if self.state.matches_orders() => self.match_market_order(order),
+            // Rejected by the trading state, a market order has no price to take part in a call auction
+            _ => {
+                let previous_status = set_status(&mut order, OrderStatus::Cancelled);
+                self.publish(OrderbookUpdate {
+                    update_type: OrderbookUpdateType::Cancel,
+                    previous_status,
+                    order: Some(order),
+                    cancel_id: Some(order.id),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// add_conditional_order holds an order until the book meets its condition, then adds it as
+    /// add_order does within the change which met the condition. An order whose condition is already
+    /// met is added at once.
+    ///
+    /// #Parameters
+    /// * 'order' - The order
+    /// * 'condition' - The condition activating the order
+    pub fn add_conditional_order(&mut self, order: Order, condition: BookCondition) {
+        if self.state.matches_orders() && condition.is_met(self) {
+            self.add_order(order);
+            return;
+        }
+        self.conditionals.push((condition, order));
+    }
+
+    /// cancel_conditional_order cancels an order held until its condition is met, and publishes a
+    /// Cancel update
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    ///
+    /// #Returns
+    /// * Option<Order> - The cancelled order, None if no order with this ID is held
+    pub fn cancel_conditional_order(&mut self, order_id: OrderId) -> Option<Order> {
+        let index = self
+            .conditionals
+            .iter()
+            .position(|(_, order)| order.id == order_id)?;
+        let (_, mut order) = self.conditionals.remove(index);
+        let previous_status = set_status(&mut order, OrderStatus::Cancelled);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            previous_status,
+            order: Some(order),
+            cancel_id: Some(order_id),
+            ..Default::default()
+        });
+        Some(order)
+    }
+
+    /// conditional_orders returns the orders held until their condition is met, with their condition
+    pub fn conditional_orders(&self) -> &[(BookCondition, Order)] {
+        &self.conditionals
+    }
+
+    /// set_level sets the quantity of a price level of a mirrored book, where one resting order stands
+    /// for the whole level, and publishes a Place, Amended or Cancel update for it. Only in Mirror state
+    ///
+    /// #Parameters
+    /// * 'side' - The side of the level
+    /// * 'price' - The price of the level
+    /// * 'quantity' - The new quantity of the level, 0 removes it
+    ///
+    /// #Returns
+    /// * bool - False if the orderbook is not a mirror
+    pub fn set_level(&mut self, side: OrderSide, price: f64, quantity: f64) -> bool {
+        if self.state != OrderbookState::Mirror {
+            return false;
+        }
+        let heap = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        let resting = heap
+            .iter_ref()
+            .find(|o| o.price == Some(price))
+            .map(|o| o.id);
+        let mut previous_status = None;
+        let (update_type, order) = match resting {
+            Some(order_id) if quantity > 0.0 => (
+                OrderbookUpdateType::Amended,
+                self.update_resting(order_id, side, |o| {
+                    o.quantity = quantity;
+                    o.non_mut_quantity = quantity;
+                }),
+            ),
+            Some(order_id) => (
+                OrderbookUpdateType::Cancel,
+                self.remove_order(order_id, side).map(|mut o| {
+                    previous_status = set_status(&mut o, OrderStatus::Cancelled);
+                    o
+                }),
+            ),
+            None if quantity > 0.0 => {
+                let mut order = Order::new(
+                    UserId::default(),
+                    self.symbol,
+                    side,
+                    quantity,
+                    Some(price),
+                    OrderType::Limit,
+                );
+                order.arrival_seq = self.next_arrival();
+                order.created_at = self.clock.now();
+                order.updated_at = order.created_at;
+                self.rest(order);
+                (OrderbookUpdateType::Place, Some(order))
+            }
+            None => return true,
+        };
+        self.publish(OrderbookUpdate {
+            update_type,
+            order,
+            cancel_id: order
+                .filter(|_| update_type == OrderbookUpdateType::Cancel)
+                .map(|o| o.id),
+            previous_status,
+            ..Default::default()
+        });
+        true
+    }
+
+    /// clear_levels removes every level of a mirrored book and publishes a Cancel update for each,
+    /// e.g. before applying a new snapshot of the external book. Only in Mirror state
+    ///
+    /// #Returns
+    /// * Vec<Order> - The orders standing for the removed levels
+    pub fn clear_levels(&mut self) -> Vec<Order> {
+        if self.state != OrderbookState::Mirror {
+            return Vec::new();
+        }
+        let mut removed = self.remove_where(|_| true);
+        for order in removed.iter_mut() {
+            let previous_status = set_status(order, OrderStatus::Cancelled);
+            self.publish(OrderbookUpdate {
+                update_type: OrderbookUpdateType::Cancel,
+                previous_status,
+                order: Some(*order),
+                cancel_id: Some(order.id),
+                ..Default::default()
+            });
+        }
+        removed
+    }
+
+    /// set_state changes the trading state and publishes a StateChange update,
+    /// the crossing orders are matched when the orderbook returns to continuous trading
+    ///
+    /// #Parameters
+    /// * 'state' - The new trading state
+    pub fn set_state(&mut self, state: OrderbookState) {
+        if self.state == state {
+            return;
+        }
+        if self.state == OrderbookState::Closed {
+            // the first trade of the new session gives the session open price
+            self.session_opening = true;
+        }
+        self.state = state;
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::StateChange,
+            state: Some(state),
+            ..Default::default()
+        });
+        self.match_orders();
+    }
+
+    /// halt suspends trading, only cancels are accepted
+    pub fn halt(&mut self) {
+        self.set_state(OrderbookState::Halted);
+    }
+
+    /// resume returns to continuous trading
+    pub fn resume(&mut self) {
+        self.set_state(OrderbookState::Continuous);
+    }
+
+    /// shut_down closes the orderbook and publishes its last update, the resting orders are left untouched
+    pub fn shut_down(&mut self) {
+        self.set_state(OrderbookState::Closed);
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Shutdown,
+            state: Some(self.state),
+            ..Default::default()
+        });
+    }
+
+    /// heartbeat publishes the checksum of the book at the current sequence number, to be called
+    /// periodically so that the consumers can validate their mirror of the book
+    pub fn heartbeat(&mut self) {
+        let checksum = self.checksum();
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::Heartbeat,
+            checksum: Some(checksum),
+            ..Default::default()
+        });
+    }
+
+    /// set_metadata attaches metadata to an order, to be called before the order is added so that
+    /// all its updates and trades carry it
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'metadata' - The metadata of the order, replacing the previous one
+    pub fn set_metadata(&mut self, order_id: OrderId, metadata: Metadata) {
+        self.metadata.insert(order_id, metadata);
+    }
+
+    /// metadata returns the metadata of a live order
+    pub fn metadata(&self, order_id: OrderId) -> Option<&Metadata> {
+        self.metadata.get(&order_id)
+    }
+
+    /// remove_metadata forgets the metadata of an order, e.g. when the order is rejected
+    pub fn remove_metadata(&mut self, order_id: OrderId) -> Option<Metadata> {
+        self.metadata.remove(&order_id)
+    }
+
+    /// start_auction puts the orderbook in call auction, the orders accumulate without matching
+    pub fn start_auction(&mut self) {
+        self.set_state(OrderbookState::AuctionCall);
+    }
+
+    /// order_history returns what happened to an order: its placement, amendments, executions and
+    /// how it left the book, with the sequence number and the time of each update
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    ///
+    /// #Returns
+    /// * Vec<OrderEvent> - The events of the order, the oldest first, empty if it is unknown or was forgotten
+    pub fn order_history(&self, order_id: OrderId) -> Vec<OrderEvent> {
+        self.audit_trail.get(order_id)
+    }
+
+    /// indicative_auction returns the result the call auction would have if it was run now
+    /// The equilibrium price is kept within the auction collar of the reference price, when both are set
+    pub fn indicative_auction(&self) -> AuctionResult {
+        let reference = self.reference_price;
+        let bounds = self
+            .auction_collar
+            .zip(reference)
+            .map(|(collar, reference)| (reference * (1.0 - collar), reference * (1.0 + collar)));
+        auction::equilibrium(&self.bids.to_vec(), &self.asks.to_vec(), reference, bounds)
+    }
+
+    /// run_auction crosses the book at the equilibrium price maximizing the executed volume,
+    /// publishes an AuctionResult update then returns to continuous trading
+    ///
+    /// #Returns
+    /// * AuctionResult - The equilibrium price and the executed volume
+    pub fn run_auction(&mut self) -> AuctionResult {
+        let result = self.indicative_auction();
+        if let Some(price) = result.price {
+            let mut trades = Vec::new();
+            while let (Some(bid), Some(ask)) = (self.bids.peek(), self.asks.peek()) {
+                if bid.price < Some(price) || ask.price > Some(price) {
+                    break;
+                }
+                let quantity = bid.quantity.min(ask.quantity);
+                for order in [bid, ask] {
+                    if order.quantity > quantity {
+                        self.decrement_for_fill(order.id, quantity, order.side);
+                    } else {
+                        self.order_filled(order.id, order.side);
+                    }
+                }
+                trades.push(Trade::between(
+                    self.symbol,
+                    price,
+                    quantity,
+                    &bid,
+                    &ask,
+                    Trade::later_side(&bid, &ask),
+                ));
+            }
+            for trade in trades {
+                self.emit_trade(trade);
+            }
+        }
+        self.publish(OrderbookUpdate {
+            update_type: OrderbookUpdateType::AuctionResult,
+            auction: Some(result),
+            ..Default::default()
+        });
+        self.resume();
+        result
+    }
+}
+
+/// order_not_found is the error of the operations on an order which is not in the orderbook
+fn order_not_found() -> Error {
+    Error::new(ErrorKind::NotFound, "Order not found")
+}
+
+/// set_status moves an order to a new status for an update, the engine only makes the allowed transitions
+///
+/// #Returns
+/// * Option<OrderStatus> - The previous status of the order, for the update
+fn set_status(order: &mut Order, status: OrderStatus) -> Option<OrderStatus> {
+    let previous = order.transition(status);
+    debug_assert!(previous.is_ok(), "{previous:?}");
+    previous.ok()
+}
+
+/// compare_levels reports the levels of a side whose maintained total differs from the total of its resting orders
+fn compare_levels(
+    side: OrderSide,
+    expected: &LevelBook,
+    actual: &LevelBook,
+    violations: &mut Vec<InvariantViolation>,
+) {
+    // Both sides are walked from the lowest price
+    let levels = |book: &LevelBook| -> Vec<PriceLevel> {
+        match side {
+            OrderSide::Buy => book.bids().rev().copied().collect(),
+            OrderSide::Sell => book.asks().copied().collect(),
+        }
+    };
+    let mut expected = levels(expected).into_iter().peekable();
+    let mut actual = levels(actual).into_iter().peekable();
+    loop {
+        let (expected_level, actual_level) = match (expected.peek(), actual.peek()) {
+            (None, None) => break,
+            (Some(e), Some(a)) => match e.price.total_cmp(&a.price) {
+                Ordering::Less => (expected.next(), None),
+                Ordering::Greater => (None, actual.next()),
+                Ordering::Equal => (expected.next(), actual.next()),
+            },
+            (Some(_), None) => (expected.next(), None),
+            (None, Some(_)) => (None, actual.next()),
+        };
+        let consistent = match (expected_level, actual_level) {
+            (Some(e), Some(a)) => {
+                e.orders == a.orders
+                    && (e.quantity - a.quantity).abs() <= 1e-9 * e.quantity.abs().max(1.0)
+            }
+            _ => false,
+        };
+        if !consistent {
+            violations.push(InvariantViolation::LevelMismatch {
+                side,
+                price: expected_level.or(actual_level).map_or(0.0, |l| l.price),
+                expected: expected_level,
+                actual: actual_level,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Instant;
+
+    use super::*;
+    use crate::enums::delivery_failure_policy::DeliveryFailurePolicy;
+    use crate::enums::liquidity::Liquidity;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::clock::MockClock;
+    use crate::structs::level_book::DepthLevel;
+    use crate::structs::order::Order;
+    use crossbeam_channel::{unbounded, Receiver};
+    use ulid::Ulid;
+
+    #[test]
+    fn test_orderbook_new() {
+        let (tx, _) = unbounded::<OrderbookUpdate>();
+        let orderbook = Orderbook::new(Ulid::new().into(), tx);
+        assert_eq!(orderbook.bids.len(), 0);
+        assert_eq!(orderbook.asks.len(), 0);
+    }
+
+    #[test]
+    fn test_orderbook_add_order() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
+        let order = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        // Spawn a new thread to keep the receiver alive
+        std::thread::spawn(move || {
+            println!("{:?}", r.recv().unwrap());
+        });
+        orderbook.add_order(order);
+        assert_eq!(orderbook.bids.len(), 1);
+        assert_eq!(orderbook.asks.len(), 0);
     }
 
     #[test]
@@ -522,14 +2004,419 @@ mod tests {
         std::thread::spawn(move || loop {
             println!("{:?}", r.recv().unwrap());
         });
-        orderbook.add_order(order.clone());
-        orderbook.update_order(order.id, 2.0, OrderSide::Buy);
+        orderbook.add_order(order);
+        orderbook
+            .update_order(order.id, 2.0, OrderSide::Buy)
+            .unwrap();
         assert_eq!(orderbook.bids.len(), 1);
         assert_eq!(orderbook.asks.len(), 0);
         let new_order = orderbook.bids.peek().unwrap();
         assert_eq!(new_order.quantity, 2.0);
     }
 
+    #[test]
+    fn test_trades_carry_the_remaining_quantities() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity, price, order_type| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                price,
+                order_type,
+            )
+        };
+        let iceberg =
+            order(OrderSide::Sell, 10.0, Some(1.0), OrderType::Limit).with_display_quantity(2.0);
+        orderbook.add_order(iceberg);
+        orderbook.add_order(order(OrderSide::Sell, 1.0, Some(2.0), OrderType::Limit));
+        let taker = order(OrderSide::Buy, 12.0, None, OrderType::Market);
+        orderbook.add_order(taker);
+
+        let trades: Vec<Trade> = r.try_iter().filter_map(|u| u.trade).collect();
+        let remaining: Vec<(f64, f64)> = trades
+            .iter()
+            .map(|t| (t.buy_remaining, t.sell_remaining))
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![
+                (10.0, 8.0),
+                (8.0, 6.0),
+                (6.0, 4.0),
+                (4.0, 2.0),
+                (2.0, 0.0),
+                (1.0, 0.0)
+            ]
+        );
+        assert!(trades
+            .iter()
+            .all(|t| t.buy_liquidity == Liquidity::Taker && t.sell_liquidity == Liquidity::Maker));
+        assert_eq!(trades[0].remaining(iceberg.id), Some(8.0));
+        assert_eq!(trades[0].remaining(Ulid::new().into()), None);
+    }
+
+    #[test]
+    fn test_market_order_remainder() {
+        let symbol = Ulid::new().into();
+        let order = |side, quantity, price, order_type| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                price,
+                order_type,
+            )
+        };
+        for policy in [MarketRemainder::Cancel, MarketRemainder::ConvertToLimit] {
+            let (tx, r) = unbounded::<OrderbookUpdate>();
+            let config = OrderbookConfig::default().with_market_remainder(policy);
+            let mut orderbook = Orderbook::with_config(symbol, tx, config);
+            orderbook.add_order(order(OrderSide::Sell, 2.0, Some(10.0), OrderType::Limit));
+            orderbook.add_order(order(OrderSide::Sell, 1.0, Some(11.0), OrderType::Limit));
+            let taker = order(OrderSide::Buy, 5.0, None, OrderType::Market);
+            orderbook.add_order(taker);
+
+            let last = r.try_iter().last().unwrap();
+            assert_eq!(last.order.unwrap().id, taker.id);
+            assert_eq!(last.order.unwrap().quantity, 2.0);
+            match policy {
+                MarketRemainder::Cancel => {
+                    assert_eq!(last.update_type, OrderbookUpdateType::Cancel);
+                    assert!(orderbook.bids.is_empty());
+                }
+                MarketRemainder::ConvertToLimit => {
+                    assert_eq!(last.update_type, OrderbookUpdateType::Place);
+                    let resting = orderbook.bids.peek().unwrap();
+                    assert_eq!(resting.order_type, OrderType::Limit);
+                    assert_eq!(resting.price, Some(11.0));
+                    assert!(orderbook.contains_order(taker.id, OrderSide::Buy));
+                }
+            }
+        }
+
+        // Nothing executed, nothing to convert
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let config =
+            OrderbookConfig::default().with_market_remainder(MarketRemainder::ConvertToLimit);
+        let mut orderbook = Orderbook::with_config(symbol, tx, config);
+        orderbook.add_order(order(OrderSide::Sell, 1.0, None, OrderType::Market));
+        assert_eq!(
+            r.try_iter().last().unwrap().update_type,
+            OrderbookUpdateType::Cancel
+        );
+        assert!(orderbook.asks.is_empty());
+    }
+
+    #[test]
+    fn test_min_fill_quantity_skips_smaller_matches() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let block = order(OrderSide::Sell, 5.0, 10.0).with_min_fill_quantity(5.0);
+        let next = order(OrderSide::Sell, 3.0, 10.0);
+        orderbook.add_order(block);
+        orderbook.add_order(next);
+        orderbook.add_order(order(OrderSide::Sell, 4.0, 11.0));
+
+        // Too small for the block, the matching continues down the book
+        let small = order(OrderSide::Buy, 2.0, 11.0);
+        orderbook.add_order(small);
+        let trades: Vec<_> = r.try_iter().filter_map(|u| u.trade).collect();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].sell_order_id, next.id);
+        assert_eq!(trades[0].quantity, 2.0);
+        assert!(orderbook.verify_invariants().is_ok());
+
+        // The block kept its priority and trades in one piece
+        let large = order(OrderSide::Buy, 6.0, 10.0);
+        orderbook.add_order(large);
+        let trades: Vec<_> = r.try_iter().filter_map(|u| u.trade).collect();
+        assert_eq!(trades[0].sell_order_id, block.id);
+        assert_eq!(trades[0].quantity, 5.0);
+        assert_eq!(trades[1].sell_order_id, next.id);
+        assert_eq!(trades[1].quantity, 1.0);
+
+        // The remainder of a bid too small for the block rests crossed with it
+        let block = order(OrderSide::Sell, 5.0, 11.0).with_min_fill_quantity(5.0);
+        orderbook.add_order(block);
+        orderbook.add_order(order(OrderSide::Buy, 5.0, 11.0));
+        let trades: Vec<_> = r.try_iter().filter_map(|u| u.trade).collect();
+        assert_eq!(trades.len(), 1);
+        assert!(trades[0].sell_order_id != block.id);
+        assert_eq!(orderbook.bids.peek().unwrap().quantity, 1.0);
+        assert!(orderbook.contains_order(block.id, OrderSide::Sell));
+        assert!(orderbook.verify_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_conditional_orders_activate_with_the_book_change() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 1.0, 98.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 102.0));
+
+        let chaser = order(OrderSide::Buy, 1.0, 101.0);
+        let joiner = order(OrderSide::Sell, 3.0, 103.0);
+        let cancelled = order(OrderSide::Sell, 1.0, 110.0);
+        orderbook.add_conditional_order(chaser, BookCondition::MidAtLeast(100.5));
+        orderbook.add_conditional_order(joiner, BookCondition::BidVolumeAbove(3.0));
+        orderbook.add_conditional_order(cancelled, BookCondition::SpreadBelow(0.5));
+        assert_eq!(orderbook.conditional_orders().len(), 3);
+        assert!(r.try_iter().next().is_some());
+
+        // the bid moving up lifts the mid to 100.5, the chaser is added by the same call
+        orderbook.add_order(order(OrderSide::Buy, 3.0, 98.5));
+        assert!(r
+            .try_iter()
+            .all(|u| u.order.map(|o| o.id) != Some(chaser.id)));
+        orderbook.add_order(order(OrderSide::Buy, 3.0, 99.0));
+        assert!(r.try_iter().any(
+            |u| u.update_type == OrderbookUpdateType::Place && u.order.unwrap().id == chaser.id
+        ));
+        assert!(orderbook.contains_order(chaser.id, OrderSide::Buy));
+        // the chaser rests 1.0 at the top, the joiner waits for more than 3.0
+        assert_eq!(orderbook.conditional_orders().len(), 2);
+
+        orderbook.add_order(order(OrderSide::Buy, 3.0, 101.0));
+        assert!(orderbook.contains_order(joiner.id, OrderSide::Sell));
+        assert_eq!(
+            orderbook.cancel_conditional_order(cancelled.id).unwrap().id,
+            cancelled.id
+        );
+        assert!(orderbook.cancel_conditional_order(cancelled.id).is_none());
+        let last = r.try_iter().last().unwrap();
+        assert_eq!(last.update_type, OrderbookUpdateType::Cancel);
+        assert_eq!(last.cancel_id, Some(cancelled.id));
+        assert!(orderbook.conditional_orders().is_empty());
+    }
+
+    #[test]
+    fn test_pegged_orders_track_their_reference() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                price,
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 1.0, Some(99.0)));
+        let ask = order(OrderSide::Sell, 5.0, Some(101.0));
+        orderbook.add_order(ask);
+        let mid = order(OrderSide::Buy, 1.0, None).with_peg(PegReference::Mid, -0.5);
+        let joined = order(OrderSide::Buy, 2.0, None).with_peg(PegReference::BestBid, 1.0);
+        orderbook.add_order(mid);
+        orderbook.add_order(joined);
+        assert_eq!(
+            orderbook.get_order(mid.id, OrderSide::Buy).unwrap().price,
+            Some(99.5)
+        );
+        assert_eq!(
+            orderbook
+                .get_order(joined.id, OrderSide::Buy)
+                .unwrap()
+                .price,
+            Some(100.0)
+        );
+        r.try_iter().for_each(drop);
+
+        // The best bid moves up: both pegs follow and the one crossing the ask is matched
+        orderbook.add_order(order(OrderSide::Buy, 1.0, Some(100.0)));
+        let updates: Vec<_> = r.try_iter().collect();
+        let repriced: Vec<_> = updates
+            .iter()
+            .filter(|u| u.update_type == OrderbookUpdateType::Repriced)
+            .map(|u| (u.order.unwrap().id, u.order.unwrap().price))
+            .collect();
+        assert_eq!(repriced.len(), 2);
+        assert!(repriced.contains(&(mid.id, Some(100.0))));
+        assert!(repriced.contains(&(joined.id, Some(101.0))));
+        let trade = updates.iter().find_map(|u| u.trade.as_ref()).unwrap();
+        assert_eq!(trade.buy_order_id, joined.id);
+        assert_eq!((trade.price, trade.quantity), (101.0, 2.0));
+        assert!(!orderbook.contains_order(joined.id, OrderSide::Buy));
+        assert!(orderbook.verify_invariants().is_ok());
+
+        // Without a reference the peg keeps its price, a new peg without a price is cancelled
+        orderbook.cancel_order(ask.id, OrderSide::Sell).unwrap();
+        assert_eq!(
+            orderbook.get_order(mid.id, OrderSide::Buy).unwrap().price,
+            Some(100.0)
+        );
+        let unpriced = order(OrderSide::Sell, 1.0, None).with_peg(PegReference::Mid, 0.0);
+        orderbook.add_order(unpriced);
+        let last = r.try_iter().last().unwrap();
+        assert_eq!(last.update_type, OrderbookUpdateType::Cancel);
+        assert_eq!(last.cancel_id, Some(unpriced.id));
+        assert!(orderbook.asks.is_empty());
+    }
+
+    #[test]
+    fn test_order_history() {
+        let (tx, _r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let config = OrderbookConfig::default().with_order_history(2, 4);
+        let mut orderbook = Orderbook::with_config(symbol, tx, config);
+        let order = |side: OrderSide, quantity: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(100.0),
+                OrderType::Limit,
+            )
+        };
+        let bid = order(OrderSide::Buy, 5.0);
+        orderbook.add_order(bid);
+        orderbook
+            .amend_order_quantity(bid.id, 4.0, OrderSide::Buy)
+            .unwrap();
+        let ask = order(OrderSide::Sell, 1.0);
+        orderbook.add_order(ask);
+
+        let events = orderbook.order_history(bid.id);
+        let types: Vec<_> = events.iter().map(|e| e.update_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                OrderbookUpdateType::Place,
+                OrderbookUpdateType::Amended,
+                OrderbookUpdateType::PartiallyFilled,
+                OrderbookUpdateType::NewTrades,
+            ]
+        );
+        assert!(events.windows(2).all(|w| w[0].sequence < w[1].sequence));
+        assert_eq!(events[2].fill_quantity, Some(1.0));
+        assert_eq!(events[3].trade.as_ref().unwrap().sell_order_id, ask.id);
+        assert_eq!(
+            orderbook.order_history(ask.id).last().unwrap().update_type,
+            OrderbookUpdateType::NewTrades
+        );
+
+        // The oldest events of an order are dropped past the depth
+        orderbook.cancel_order(bid.id, OrderSide::Buy).unwrap();
+        let events = orderbook.order_history(bid.id);
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].update_type, OrderbookUpdateType::Amended);
+        assert_eq!(events[3].update_type, OrderbookUpdateType::Cancel);
+        // and the oldest orders past the capacity
+        orderbook.add_order(order(OrderSide::Buy, 1.0));
+        assert!(orderbook.order_history(bid.id).is_empty());
+        assert_eq!(orderbook.audit_trail.len(), 2);
+    }
+
+    #[test]
+    fn test_iterate_the_depth_in_priority_order() {
+        let (tx, _r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let mut created_at = 0;
+        let mut order = |side, price| {
+            created_at += 1;
+            let mut order = Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            );
+            order.created_at = created_at;
+            order
+        };
+        let first = order(OrderSide::Buy, 99.0);
+        let second = order(OrderSide::Buy, 99.0);
+        let best = order(OrderSide::Buy, 100.0);
+        for o in [first, second, best, order(OrderSide::Buy, 98.0)] {
+            orderbook.add_order(o);
+        }
+        orderbook.add_order(order(OrderSide::Sell, 102.0));
+        orderbook.add_order(order(OrderSide::Sell, 101.0));
+
+        let bids: Vec<DepthLevel> = orderbook.iter_bids().collect();
+        let prices: Vec<f64> = bids.iter().map(|l| l.level.price).collect();
+        assert_eq!(prices, vec![100.0, 99.0, 98.0]);
+        assert_eq!(bids[0].orders[0].id, best.id);
+        let ids: Vec<OrderId> = bids[1].orders.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![first.id, second.id]);
+        assert_eq!(bids[1].level.orders, 2);
+
+        // the walk stops at the top of the book
+        let top = orderbook.iter_asks().next().unwrap();
+        assert_eq!(top.level.price, 101.0);
+        assert_eq!(top.orders.len(), 1);
+    }
+
+    #[test]
+    fn test_fills_and_amends_are_told_apart() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(1.0),
+                OrderType::Limit,
+            )
+        };
+        let resting = order(OrderSide::Buy, 5.0);
+        orderbook.add_order(resting);
+        orderbook
+            .amend_order_quantity(resting.id, 4.0, resting.side)
+            .unwrap();
+        orderbook.add_order(order(OrderSide::Sell, 1.5));
+
+        let updates: Vec<OrderbookUpdate> = r.try_iter().collect();
+        let amended = updates
+            .iter()
+            .find(|u| u.update_type == OrderbookUpdateType::Amended)
+            .unwrap();
+        assert_eq!(amended.order.unwrap().quantity, 4.0);
+        assert_eq!(amended.fill_quantity, None);
+        let filled = updates
+            .iter()
+            .find(|u| u.update_type == OrderbookUpdateType::PartiallyFilled)
+            .unwrap();
+        assert_eq!(filled.fill_quantity, Some(1.5));
+        assert_eq!(filled.order.unwrap().quantity, 2.5);
+        assert_eq!(filled.order.unwrap().status, OrderStatus::PartiallyFilled);
+    }
+
     #[test]
     fn test_case_1() {
         let (tx, r) = unbounded::<OrderbookUpdate>();
@@ -553,8 +2440,8 @@ mod tests {
         std::thread::spawn(move || loop {
             println!("{:?}", r.recv().unwrap());
         });
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
         assert_eq!(orderbook.bids.len(), 0);
         assert_eq!(orderbook.asks.len(), 0);
     }
@@ -590,9 +2477,9 @@ mod tests {
         std::thread::spawn(move || loop {
             println!("{:?}", r.recv().unwrap());
         });
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
-        orderbook.add_order(order3.clone());
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
+        orderbook.add_order(order3);
         let order1 = Order::new(
             Ulid::new().into(),
             Ulid::new().into(),
@@ -618,9 +2505,9 @@ mod tests {
             OrderType::Limit,
         );
 
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
-        orderbook.add_order(order3.clone());
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
+        orderbook.add_order(order3);
         assert_eq!(orderbook.bids.len(), 3);
         assert_eq!(orderbook.asks.len(), 3);
 
@@ -633,7 +2520,7 @@ mod tests {
             OrderType::Market,
         );
 
-        orderbook.add_order(order.clone());
+        orderbook.add_order(order);
         assert_eq!(orderbook.bids.len(), 3);
         assert_eq!(orderbook.asks.len(), 3);
         let order = orderbook.asks.peek().unwrap();
@@ -673,9 +2560,9 @@ mod tests {
         std::thread::spawn(move || loop {
             println!("{:?}", r.recv().unwrap());
         });
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
-        orderbook.add_order(order3.clone());
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
+        orderbook.add_order(order3);
         let order1 = Order::new(
             Ulid::new().into(),
             Ulid::new().into(),
@@ -701,9 +2588,9 @@ mod tests {
             OrderType::Limit,
         );
 
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
-        orderbook.add_order(order3.clone());
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
+        orderbook.add_order(order3);
         assert_eq!(orderbook.bids.len(), 3);
         assert_eq!(orderbook.asks.len(), 3);
 
@@ -711,68 +2598,671 @@ mod tests {
             Ulid::new().into(),
             Ulid::new().into(),
             OrderSide::Buy,
-            100.0,
-            Some(100.02),
+            100.0,
+            Some(100.02),
+            OrderType::Limit,
+        );
+
+        orderbook.add_order(order);
+        assert_eq!(orderbook.bids.len(), 3);
+        assert_eq!(orderbook.asks.len(), 3);
+        let order = orderbook.asks.peek().unwrap();
+        assert_eq!(order.quantity, 800.0);
+        assert_eq!(order.price, Some(100.0));
+    }
+
+    #[test]
+    fn test_case_4() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Sell,
+            100.10,
+            Some(100.10),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Sell,
+            500.0,
+            Some(100.05),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Sell,
+            900.0,
+            Some(100.0),
+            OrderType::Limit,
+        );
+        std::thread::spawn(move || loop {
+            println!("{:?}", r.recv().unwrap());
+        });
+        orderbook.add_order(order1);
+        orderbook.add_order(order2);
+        orderbook.add_order(order3);
+
+        let order = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Buy,
+            2000.0,
+            Some(100.0),
+            OrderType::Market,
+        );
+        orderbook.add_order(order);
+
+        assert_eq!(orderbook.bids.len(), 0);
+        assert_eq!(orderbook.asks.len(), 0);
+    }
+
+    #[test]
+    fn test_updates_carry_the_status_transitions() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        let maker = order(OrderSide::Sell, 3.0);
+        orderbook.add_order(maker);
+        orderbook.add_order(order(OrderSide::Buy, 1.0));
+        orderbook.add_order(order(OrderSide::Buy, 2.0));
+        let changes: Vec<_> = r
+            .try_iter()
+            .filter(|u| u.order.is_some_and(|o| o.id == maker.id))
+            .filter_map(|u| u.status_change())
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (OrderStatus::Open, OrderStatus::PartiallyFilled),
+                (OrderStatus::PartiallyFilled, OrderStatus::Filled),
+            ]
+        );
+
+        let mut filled = maker;
+        filled.status = OrderStatus::Filled;
+        let error = filled.transition(OrderStatus::Cancelled).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(filled.status, OrderStatus::Filled);
+        assert!(!OrderStatus::Cancelled.can_transition_to(OrderStatus::Filled));
+        assert!(!OrderStatus::PartiallyFilled.can_transition_to(OrderStatus::Open));
+    }
+
+    #[test]
+    fn test_cancel_all_for_user() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let user = Ulid::new().into();
+        for (user_id, side, price) in [
+            (user, OrderSide::Buy, 1.0),
+            (user, OrderSide::Sell, 3.0),
+            (Ulid::new().into(), OrderSide::Buy, 1.0),
+        ] {
+            orderbook.add_order(Order::new(
+                user_id,
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            ));
+        }
+        let _: Vec<OrderbookUpdate> = r.try_iter().collect();
+
+        let cancelled = orderbook.cancel_all_for_user(user);
+        assert_eq!(cancelled.len(), 2);
+        assert!(cancelled
+            .iter()
+            .all(|o| o.user_id == user && o.status == OrderStatus::Cancelled));
+        assert_eq!(orderbook.bids.len(), 1);
+        assert_eq!(orderbook.asks.len(), 0);
+        let updates: Vec<OrderbookUpdate> = r.try_iter().collect();
+        assert_eq!(updates.len(), 2);
+        assert!(updates
+            .iter()
+            .all(|u| u.update_type == OrderbookUpdateType::Cancel));
+
+        assert_eq!(orderbook.cancel_all().len(), 1);
+        assert_eq!(orderbook.bids.len(), 0);
+    }
+
+    #[test]
+    fn test_good_till_date_expiry() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let now = TradeHistory::now();
+        let order = |price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let gtc = order(1.0);
+        let gtd = order(2.0).with_expiry(now + 1_000);
+        orderbook.add_order(gtc);
+        orderbook.add_order(gtd);
+        // Already expired on arrival, never rests in the book
+        orderbook.add_order(order(3.0).with_expiry(now - 1));
+        assert_eq!(orderbook.bids.len(), 2);
+
+        assert!(orderbook.tick(now + 999).is_empty());
+        let expired = orderbook.tick(now + 1_000);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, gtd.id);
+        assert_eq!(expired[0].status, OrderStatus::Expired);
+        assert_eq!(orderbook.bids.len(), 1);
+        assert_eq!(
+            orderbook.bids.peek(),
+            Some(Order {
+                arrival_seq: 1,
+                ..gtc
+            })
+        );
+
+        let expired_updates = r
+            .try_iter()
+            .filter(|u| u.update_type == OrderbookUpdateType::Expired)
+            .count();
+        assert_eq!(expired_updates, 2);
+    }
+
+    #[test]
+    fn test_iceberg_order() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let now = TradeHistory::now();
+        let mut iceberg = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            10.0,
+            Some(1.0),
+            OrderType::Limit,
+        )
+        .with_display_quantity(4.0);
+        iceberg.created_at = now - 20;
+        let mut visible = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        visible.created_at = now - 10;
+        orderbook.add_order(iceberg);
+        orderbook.add_order(visible);
+        let (_, _, asks) = orderbook.summarize_orderbook_per_price_level();
+        assert_eq!(asks.iter().map(|level| level.1).sum::<f64>(), 5.0);
+
+        // Fills the first slice, the next one goes behind the visible order
+        let buy = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            5.0,
+            Some(1.0),
             OrderType::Limit,
         );
+        orderbook.add_order(buy);
+        assert_eq!(orderbook.asks.len(), 1);
+        let ask = orderbook.asks.peek().unwrap();
+        assert_eq!(ask.id, iceberg.id);
+        assert_eq!((ask.quantity, ask.hidden_quantity), (4.0, 2.0));
 
-        orderbook.add_order(order.clone());
-        assert_eq!(orderbook.bids.len(), 3);
-        assert_eq!(orderbook.asks.len(), 3);
-        let order = orderbook.asks.peek().unwrap();
-        assert_eq!(order.quantity, 800.0);
-        assert_eq!(order.price, Some(100.0));
+        for update in r.try_iter() {
+            if let Some(order) = update.order.filter(|o| o.id == iceberg.id) {
+                assert!(order.quantity <= 4.0);
+                assert_eq!(order.hidden_quantity, 0.0);
+                assert_eq!(order.non_mut_quantity, 4.0);
+            }
+        }
+        assert_eq!(orderbook.trade_history.len(), 2);
     }
 
     #[test]
-    fn test_case_4() {
+    fn test_replace_order_priority() {
         let (tx, r) = unbounded::<OrderbookUpdate>();
-        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
-        let order1 = Order::new(
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let now = TradeHistory::now();
+        let mut first = Order::new(
             Ulid::new().into(),
-            Ulid::new().into(),
-            OrderSide::Sell,
-            100.10,
-            Some(100.10),
+            symbol,
+            OrderSide::Buy,
+            2.0,
+            Some(1.0),
             OrderType::Limit,
         );
-        let order2 = Order::new(
-            Ulid::new().into(),
-            Ulid::new().into(),
-            OrderSide::Sell,
-            500.0,
-            Some(100.05),
-            OrderType::Limit,
+        first.created_at = now - 20;
+        let mut second = first;
+        second.id = Ulid::new().into();
+        second.created_at = now - 10;
+        orderbook.add_order(first);
+        orderbook.add_order(second);
+        let _: Vec<OrderbookUpdate> = r.try_iter().collect();
+
+        // A size decrease keeps the priority
+        let replaced = orderbook.replace_order(first.id, 1.0, 1.0).unwrap();
+        assert_eq!(replaced.created_at, first.created_at);
+        assert_eq!(orderbook.bids.peek().unwrap().id, first.id);
+
+        // A size increase loses it
+        orderbook.replace_order(first.id, 1.0, 3.0).unwrap();
+        assert_eq!(orderbook.bids.peek().unwrap().id, second.id);
+
+        let updates: Vec<OrderbookUpdate> = r.try_iter().collect();
+        assert_eq!(updates.len(), 2);
+        assert!(updates
+            .iter()
+            .all(|u| u.update_type == OrderbookUpdateType::Replace));
+        let err = orderbook
+            .replace_order(Ulid::new().into(), 1.0, 1.0)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let err = orderbook.replace_order(first.id, -1.0, 1.0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(r.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_call_auction() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        orderbook.start_auction();
+        for (side, quantity, price) in [
+            (OrderSide::Buy, 3.0, 102.0),
+            (OrderSide::Buy, 2.0, 101.0),
+            (OrderSide::Sell, 1.0, 98.0),
+            (OrderSide::Sell, 3.0, 100.0),
+            (OrderSide::Sell, 5.0, 103.0),
+        ] {
+            orderbook.add_order(Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            ));
+        }
+        assert_eq!((orderbook.bids.len(), orderbook.asks.len()), (2, 3));
+        assert_eq!(orderbook.indicative_auction().volume, 4.0);
+
+        let result = orderbook.run_auction();
+        assert_eq!(result.price, Some(100.0));
+        assert_eq!(orderbook.state, OrderbookState::Continuous);
+        assert_eq!(orderbook.bids.peek().unwrap().quantity, 1.0);
+        assert_eq!(orderbook.asks.peek().unwrap().price, Some(103.0));
+
+        let updates: Vec<OrderbookUpdate> = r.try_iter().collect();
+        let trades: Vec<&Trade> = updates.iter().filter_map(|u| u.trade.as_ref()).collect();
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<f64>(), 4.0);
+        assert!(trades.iter().all(|t| t.price == 100.0));
+        assert_eq!(
+            updates
+                .iter()
+                .filter(|u| u.update_type == OrderbookUpdateType::AuctionResult)
+                .find_map(|u| u.auction),
+            Some(result)
         );
-        let order3 = Order::new(
-            Ulid::new().into(),
-            Ulid::new().into(),
-            OrderSide::Sell,
-            900.0,
-            Some(100.0),
-            OrderType::Limit,
+    }
+
+    #[test]
+    fn test_indicative_auction_is_published_during_the_call() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side: OrderSide, quantity: f64, price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let indicatives = |r: &Receiver<OrderbookUpdate>| -> Vec<AuctionResult> {
+            r.try_iter()
+                .filter(|u| u.update_type == OrderbookUpdateType::IndicativeAuction)
+                .filter_map(|u| u.auction)
+                .collect()
+        };
+        orderbook.add_order(order(OrderSide::Buy, 1.0, 100.0));
+        assert!(indicatives(&r).is_empty());
+
+        orderbook.start_auction();
+        assert_eq!(indicatives(&r)[0].volume, 0.0);
+        let ask = order(OrderSide::Sell, 3.0, 100.0);
+        orderbook.add_order(ask);
+        let published = indicatives(&r);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].price, Some(100.0));
+        assert_eq!(published[0].volume, 1.0);
+
+        orderbook.add_order(order(OrderSide::Buy, 2.0, 100.0));
+        assert_eq!(indicatives(&r)[0].volume, 3.0);
+        // An order which doesn't change the result publishes nothing
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 120.0));
+        assert!(indicatives(&r).is_empty());
+
+        orderbook.cancel_order(ask.id, OrderSide::Sell).unwrap();
+        assert_eq!(indicatives(&r)[0].volume, 0.0);
+
+        orderbook.resume();
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 99.0));
+        assert!(indicatives(&r).is_empty());
+    }
+
+    #[test]
+    fn test_no_dust_is_left_in_the_book() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let config = OrderbookConfig::default().with_dust_threshold(0.01);
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::with_config(symbol, tx, config);
+        let order = |side, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        // 0.3 - 0.1 is not 0.2 in floating point
+        let ask = order(OrderSide::Sell, 0.3);
+        let (first, second) = (order(OrderSide::Buy, 0.1), order(OrderSide::Buy, 0.2));
+        orderbook.add_order(ask);
+        orderbook.add_order(first);
+        orderbook.add_order(second);
+        assert_eq!((orderbook.bids.len(), orderbook.asks.len()), (0, 0));
+
+        // the remainder is below the dust threshold
+        let ask = order(OrderSide::Sell, 1.0);
+        orderbook.add_order(ask);
+        orderbook.add_order(order(OrderSide::Buy, 0.995));
+        assert_eq!(orderbook.asks.len(), 0);
+        let filled: Vec<OrderId> = r.try_iter().filter_map(|u| u.filled_id).collect();
+        assert_eq!(filled.len(), 5);
+        assert!([ask.id, second.id].iter().all(|id| filled.contains(id)));
+    }
+
+    #[test]
+    fn test_unknown_orders_are_not_amended() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
+        let unknown: OrderId = Ulid::new().into();
+        let results = [
+            orderbook.amend_order_price(unknown, 2.0, OrderSide::Buy),
+            orderbook.amend_order_quantity(unknown, 2.0, OrderSide::Buy),
+            orderbook.update_order(unknown, 2.0, OrderSide::Buy),
+            orderbook.cancel_order(unknown, OrderSide::Buy),
+        ];
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap_err().kind() == ErrorKind::NotFound));
+        assert_eq!(r.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_halt_and_resume() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side: OrderSide| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(1.0),
+                OrderType::Limit,
+            )
+        };
+        let resting = order(OrderSide::Buy);
+        orderbook.add_order(resting);
+        orderbook.halt();
+        orderbook.add_order(order(OrderSide::Sell));
+        let error = orderbook
+            .amend_order_price(resting.id, 2.0, resting.side)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(orderbook.asks.len(), 0);
+        assert_eq!(orderbook.bids.peek().unwrap().price, Some(1.0));
+
+        orderbook.cancel_order(resting.id, resting.side).unwrap();
+        assert_eq!(orderbook.bids.len(), 0);
+        orderbook.resume();
+        orderbook.add_order(order(OrderSide::Sell));
+        assert_eq!(orderbook.asks.len(), 1);
+
+        let states: Vec<OrderbookState> = r.try_iter().filter_map(|u| u.state).collect();
+        assert_eq!(
+            states,
+            vec![OrderbookState::Halted, OrderbookState::Continuous]
         );
-        std::thread::spawn(move || loop {
-            println!("{:?}", r.recv().unwrap());
-        });
-        orderbook.add_order(order1.clone());
-        orderbook.add_order(order2.clone());
-        orderbook.add_order(order3.clone());
+    }
 
-        let order = Order::new(
-            Ulid::new().into(),
+    #[test]
+    fn test_price_band_halts_orderbook() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        orderbook.price_band = Some(PriceBand::new(
+            0.05,
+            PriceReference::LastTrade,
+            BandAction::Halt,
+        ));
+        let order = |side: OrderSide, price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Sell, 100.0));
+        orderbook.add_order(order(OrderSide::Buy, 100.0));
+        orderbook.add_order(order(OrderSide::Sell, 104.0));
+        orderbook.add_order(order(OrderSide::Sell, 120.0));
+        // Sweeps the 104 offer, stops before the 120 one which is 20% away from the last trade
+        orderbook.add_order(Order::new(
             Ulid::new().into(),
+            symbol,
             OrderSide::Buy,
-            2000.0,
-            Some(100.0),
+            2.0,
+            None,
             OrderType::Market,
+        ));
+        assert_eq!(orderbook.trade_history.len(), 2);
+        assert_eq!(orderbook.asks.peek().unwrap().price, Some(120.0));
+        assert_eq!(orderbook.state, OrderbookState::Halted);
+
+        let event = r.try_iter().find_map(|u| u.circuit_breaker).unwrap();
+        assert_eq!(event.price, 120.0);
+        assert_eq!(event.reference_price, 100.0);
+    }
+
+    #[test]
+    fn test_price_band_rejects_taker() {
+        let (tx, _r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        orderbook.price_band = Some(PriceBand::new(
+            0.05,
+            PriceReference::Mid,
+            BandAction::Reject,
+        ));
+        let order = |side: OrderSide, quantity: f64, price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 1.0, 99.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 101.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 120.0));
+        // 101 is inside the band around the mid at 100, 120 is not
+        orderbook.add_order(order(OrderSide::Buy, 2.0, 200.0));
+        assert_eq!(orderbook.trade_history.len(), 1);
+        assert_eq!(orderbook.state, OrderbookState::Continuous);
+        assert_eq!(orderbook.bids.peek().unwrap().price, Some(99.0));
+        assert_eq!(orderbook.asks.peek().unwrap().price, Some(120.0));
+    }
+
+    #[test]
+    fn test_reference_price_protections() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let config = OrderbookConfig::default()
+            .with_reference_source(ReferenceSource::External)
+            .with_market_collar(0.05)
+            .with_auction_collar(0.1);
+        let mut orderbook = Orderbook::with_config(symbol, tx, config);
+        let order = |side: OrderSide, price: Option<f64>| {
+            let order_type = match price {
+                Some(_) => OrderType::Limit,
+                None => OrderType::Market,
+            };
+            Order::new(Ulid::new().into(), symbol, side, 1.0, price, order_type)
+        };
+        orderbook.set_reference_price(100.0);
+        orderbook.add_order(order(OrderSide::Sell, Some(102.0)));
+        orderbook.add_order(order(OrderSide::Sell, Some(112.0)));
+        // the market buy stops at the collar, 105, and its remainder is cancelled
+        let market = Order {
+            quantity: 2.0,
+            ..order(OrderSide::Buy, None)
+        };
+        orderbook.add_order(market);
+        assert_eq!(orderbook.trade_history.len(), 1);
+        assert_eq!(orderbook.asks.peek().unwrap().price, Some(112.0));
+        // an external reference price ignores the trades
+        assert_eq!(orderbook.reference_price(), Some(100.0));
+        let updates: Vec<OrderbookUpdate> = r.try_iter().collect();
+        assert_eq!(
+            (updates[0].update_type, updates[0].reference_price),
+            (OrderbookUpdateType::ReferencePrice, Some(100.0))
         );
-        orderbook.add_order(order.clone());
+        assert!(updates.iter().any(
+            |u| u.update_type == OrderbookUpdateType::Cancel && u.cancel_id == Some(market.id)
+        ));
 
-        assert_eq!(orderbook.bids.len(), 0);
-        assert_eq!(orderbook.asks.len(), 0);
+        // 112 and 115 are beyond the auction collar of 110
+        orderbook.start_auction();
+        orderbook.add_order(order(OrderSide::Buy, Some(115.0)));
+        assert_eq!(orderbook.indicative_auction().price, None);
+        orderbook.set_reference_price(110.0);
+        assert_eq!(orderbook.indicative_auction().price, Some(112.0));
+    }
+
+    #[test]
+    fn test_session_open_reference_price() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let config = OrderbookConfig::default()
+            .with_reference_source(ReferenceSource::SessionOpen)
+            .with_price_band(PriceBand::new(
+                0.05,
+                PriceReference::Reference,
+                BandAction::Reject,
+            ));
+        let mut orderbook = Orderbook::with_config(symbol, tx, config);
+        let trade = |orderbook: &mut Orderbook, price: f64| {
+            for side in [OrderSide::Sell, OrderSide::Buy] {
+                orderbook.add_order(Order::new(
+                    Ulid::new().into(),
+                    symbol,
+                    side,
+                    1.0,
+                    Some(price),
+                    OrderType::Limit,
+                ));
+            }
+        };
+        trade(&mut orderbook, 100.0);
+        trade(&mut orderbook, 104.0);
+        assert_eq!(orderbook.reference_price(), Some(100.0));
+        // 106 is outside the band around the open of the session
+        trade(&mut orderbook, 106.0);
+        assert_eq!(orderbook.trade_history.len(), 2);
+        orderbook.cancel_all();
+
+        // the opening auction of the next session sets the reference price, the band not applying to it
+        orderbook.set_state(OrderbookState::Closed);
+        orderbook.start_auction();
+        trade(&mut orderbook, 106.0);
+        orderbook.run_auction();
+        assert_eq!(orderbook.reference_price(), Some(106.0));
+        let moves: Vec<Option<f64>> = r
+            .try_iter()
+            .filter(|u| u.update_type == OrderbookUpdateType::NewTrades)
+            .map(|u| u.reference_price)
+            .collect();
+        assert_eq!(moves, vec![Some(100.0), None, Some(106.0)]);
     }
 
+    #[test]
+    fn test_vwap_twap() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
+        std::thread::spawn(move || loop {
+            println!("{:?}", r.recv().unwrap());
+        });
+        assert_eq!(orderbook.vwap(Duration::from_secs(60)), None);
+        let trades = [(10.0, 1.0), (11.0, 3.0)];
+        for (price, quantity) in trades {
+            orderbook.add_order(Order::new(
+                Ulid::new().into(),
+                Ulid::new().into(),
+                OrderSide::Buy,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            ));
+            orderbook.add_order(Order::new(
+                Ulid::new().into(),
+                Ulid::new().into(),
+                OrderSide::Sell,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            ));
+        }
+        assert_eq!(orderbook.trade_history.len(), 2);
+        assert_eq!(orderbook.vwap(Duration::from_secs(60)), Some(10.75));
+        let twap = orderbook.twap(Duration::from_secs(60)).unwrap();
+        assert!((10.0..=11.0).contains(&twap));
+    }
 
     #[test]
     fn test_benchmark() {
@@ -810,4 +3300,467 @@ mod tests {
 
         assert_eq!(orderbook.asks.len(), 1000000);
     }
+
+    #[test]
+    fn test_trades_get_an_id_and_a_timestamp() {
+        let (tx, rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let clock = MockClock::new(1_000);
+        orderbook.clock = Arc::new(clock.clone());
+        for side in [OrderSide::Sell, OrderSide::Buy] {
+            orderbook.add_order(
+                Order::new(
+                    Ulid::new().into(),
+                    symbol,
+                    side,
+                    1.0,
+                    Some(1.0),
+                    OrderType::Limit,
+                )
+                .stamped(&clock),
+            );
+            clock.advance(Duration::from_nanos(500));
+        }
+        let updates: Vec<OrderbookUpdate> = rx.try_iter().collect();
+        let trade = updates.iter().find_map(|u| u.trade.clone()).unwrap();
+        assert!(trade.id.is_some());
+        assert_eq!(trade.created_at, Some(1_500));
+        assert_eq!(trade.updated_at, trade.created_at);
+        assert_eq!(trade.taker_side, OrderSide::Buy);
+        assert_eq!(updates[0].timestamp, 1_000);
+        assert!(updates.iter().skip(2).all(|u| u.timestamp == 1_500));
+    }
+
+    #[test]
+    fn test_amends_lose_priority_unless_the_quantity_decreases() {
+        let (tx, rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let clock = MockClock::new(1_000);
+        orderbook.clock = Arc::new(clock.clone());
+        let mut order = |side, price| {
+            let order = Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                2.0,
+                Some(price),
+                OrderType::Limit,
+            )
+            .stamped(&clock);
+            clock.advance(Duration::from_nanos(100));
+            orderbook.add_order(order);
+            order
+        };
+        let first = order(OrderSide::Buy, 10.0);
+        let second = order(OrderSide::Buy, 10.0);
+        let third = order(OrderSide::Buy, 10.0);
+        let ask = order(OrderSide::Sell, 11.0);
+        let bids = |orderbook: &Orderbook| {
+            orderbook
+                .bids
+                .sorted_refs()
+                .into_iter()
+                .rev()
+                .map(|o| o.id)
+                .collect::<Vec<_>>()
+        };
+
+        orderbook
+            .amend_order_quantity(first.id, 1.0, first.side)
+            .unwrap();
+        orderbook
+            .amend_order_quantity(second.id, 3.0, second.side)
+            .unwrap();
+        assert_eq!(bids(&orderbook), vec![first.id, third.id, second.id]);
+
+        clock.advance(Duration::from_nanos(100));
+        orderbook
+            .amend_order_price(third.id, 9.0, third.side)
+            .unwrap();
+        orderbook
+            .amend_order_price(third.id, 10.0, third.side)
+            .unwrap();
+        assert_eq!(bids(&orderbook), vec![first.id, second.id, third.id]);
+
+        // The amended order crosses and takes liquidity
+        orderbook
+            .amend_order_price(first.id, 11.0, first.side)
+            .unwrap();
+        let trade = rx.try_iter().find_map(|u| u.trade).unwrap();
+        assert_eq!(trade.buy_order_id, first.id);
+        assert_eq!(trade.sell_order_id, ask.id);
+        assert_eq!(trade.taker_side, OrderSide::Buy);
+        assert_eq!((trade.price, trade.quantity), (11.0, 1.0));
+        assert_eq!(bids(&orderbook), vec![second.id, third.id]);
+        assert_eq!(orderbook.asks.peek().unwrap().quantity, 1.0);
+
+        // The invalid amends are rejected before the book changes
+        orderbook.tick_size = Some(0.5);
+        rx.try_iter().for_each(drop);
+        for price in [f64::NAN, 10.3, -1.0] {
+            let err = orderbook
+                .amend_order_price(second.id, price, second.side)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+        assert!(orderbook
+            .amend_order_quantity(second.id, -5.0, second.side)
+            .is_err());
+        assert_eq!(rx.try_iter().count(), 0);
+        assert!(orderbook.verify_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_trades_execute_at_the_maker_price() {
+        let (tx, rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let mut order = |side, price| {
+            let order = Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            );
+            orderbook.add_order(order);
+            order
+        };
+        let trades = |rx: &Receiver<OrderbookUpdate>| {
+            rx.try_iter()
+                .filter_map(|u| u.trade)
+                .map(|t| (t.price, t.taker_side))
+                .collect::<Vec<_>>()
+        };
+
+        // A sell taker crossing a better bid trades at the bid
+        order(OrderSide::Buy, 101.0);
+        order(OrderSide::Sell, 99.0);
+        assert_eq!(trades(&rx), vec![(101.0, OrderSide::Sell)]);
+
+        // A buy taker crossing a better ask trades at the ask
+        order(OrderSide::Sell, 100.0);
+        order(OrderSide::Buy, 105.0);
+        assert_eq!(trades(&rx), vec![(100.0, OrderSide::Buy)]);
+
+        // An amend crossing the book takes the price of the resting order
+        order(OrderSide::Buy, 90.0);
+        let ask = order(OrderSide::Sell, 95.0);
+        assert!(trades(&rx).is_empty());
+        orderbook.amend_order_price(ask.id, 80.0, ask.side).unwrap();
+        assert_eq!(trades(&rx), vec![(90.0, OrderSide::Sell)]);
+        assert_eq!(orderbook.trade_history.last().unwrap().price, 90.0);
+    }
+
+    #[test]
+    fn test_orderbook_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Orderbook>();
+        assert_send_sync::<crate::structs::orderbooks_manager::OrderbooksManager>();
+    }
+
+    #[test]
+    fn test_price_levels_follow_the_book() {
+        let (tx, _rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, price, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let amended = order(OrderSide::Buy, 1.0, 1.0);
+        let cancelled = order(OrderSide::Buy, 2.0, 1.0);
+        for resting in [
+            amended,
+            cancelled,
+            order(OrderSide::Buy, 2.0, 2.0),
+            order(OrderSide::Sell, 3.0, 1.0),
+            order(OrderSide::Sell, 3.0, 4.0),
+        ] {
+            orderbook.add_order(resting);
+        }
+        orderbook
+            .amend_order_quantity(amended.id, 5.0, amended.side)
+            .unwrap();
+        orderbook
+            .cancel_order(cancelled.id, cancelled.side)
+            .unwrap();
+        orderbook.add_order(order(OrderSide::Buy, 3.0, 2.0));
+
+        let (bids, asks) = orderbook.depth(10);
+        let levels = |levels: Vec<PriceLevel>| {
+            levels
+                .iter()
+                .map(|l| (l.price, l.quantity, l.orders))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(levels(bids), vec![(2.0, 2.0, 1), (1.0, 5.0, 1)]);
+        assert_eq!(levels(asks), vec![(3.0, 3.0, 1)]);
+        assert_eq!(orderbook.level_count(OrderSide::Buy), 2);
+        assert_eq!(
+            orderbook.summarize_orderbook_per_price_level().2,
+            vec![(3.0, 3.0, 3.0)]
+        );
+    }
+
+    #[test]
+    fn test_book_metrics() {
+        let (tx, _rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        let order = |side, price, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 1.0, 2.0));
+        orderbook.add_order(order(OrderSide::Buy, 2.0, 1.5));
+        orderbook.add_order(order(OrderSide::Sell, 4.0, 3.0));
+        orderbook.add_order(order(OrderSide::Buy, 4.0, 1.0));
+
+        assert_eq!(
+            orderbook.metrics(),
+            BookMetrics {
+                bid_volume: 3.5,
+                ask_volume: 2.0,
+                bid_orders: 2,
+                ask_orders: 1,
+                bid_notional_at_top: 3.0,
+                ask_notional_at_top: 8.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_invariants() {
+        let (tx, _rx) = unbounded::<OrderbookUpdate>();
+        let mut orderbook = Orderbook::new(Ulid::new().into(), tx);
+        let order = |side: OrderSide, quantity: f64, price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                Ulid::new().into(),
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbook.add_order(order(OrderSide::Buy, 2.0, 9.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 11.0));
+        orderbook.add_order(order(OrderSide::Sell, 1.0, 10.0));
+        assert!(orderbook.verify_invariants().is_ok());
+
+        // Orders pushed behind the back of the orderbook are not counted in its levels
+        let crossing = order(OrderSide::Buy, 0.0, 12.0);
+        orderbook.bids.push(crossing);
+        orderbook.bids.push(crossing);
+        let report = orderbook.verify_invariants();
+        assert_eq!(report.symbol, orderbook.symbol);
+        assert!(report.violations.contains(&InvariantViolation::Crossed {
+            best_bid: 12.0,
+            best_ask: 10.0
+        }));
+        assert!(report
+            .violations
+            .contains(&InvariantViolation::DuplicateId {
+                order_id: crossing.id
+            }));
+        assert!(report
+            .violations
+            .contains(&InvariantViolation::InvalidQuantity {
+                order_id: crossing.id,
+                quantity: 0.0,
+                hidden_quantity: 0.0
+            }));
+        assert!(report.violations.iter().any(|v| matches!(
+            v,
+            InvariantViolation::LevelMismatch {
+                side: OrderSide::Buy,
+                price,
+                actual: None,
+                ..
+            } if *price == 12.0
+        )));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invariants violated")]
+    fn test_invariants_are_checked_after_each_operation() {
+        let (tx, _rx) = unbounded::<OrderbookUpdate>();
+        let config = OrderbookConfig::default().with_invariant_checks();
+        let mut orderbook = Orderbook::with_config(Ulid::new().into(), tx, config);
+        let order = Order::new(
+            Ulid::new().into(),
+            Ulid::new().into(),
+            OrderSide::Sell,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        orderbook.add_order(order);
+        orderbook.asks.push(order);
+        orderbook.add_order(Order {
+            id: Ulid::new().into(),
+            side: OrderSide::Buy,
+            price: Some(9.0),
+            ..order
+        });
+    }
+
+    #[test]
+    fn test_undelivered_updates_go_to_the_dead_letters() {
+        let (tx, rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx);
+        drop(rx);
+
+        let sell = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        let mut buy = sell;
+        buy.id = Ulid::new().into();
+        buy.user_id = Ulid::new().into();
+        buy.side = OrderSide::Buy;
+        orderbook.add_order(sell);
+        orderbook.add_order(buy);
+
+        // matching goes on without a receiver
+        assert!(orderbook.asks.is_empty());
+        let letters = orderbook.dead_letters.peek();
+        assert_eq!(letters.len(), orderbook.sequence as usize);
+        assert_eq!(letters[0].reason, "Update channel closed");
+        assert_eq!(letters[0].update.update_type, OrderbookUpdateType::New);
+
+        orderbook.dead_letters.set_capacity(2);
+        assert_eq!(orderbook.dead_letters.drain().len(), 2);
+        assert!(orderbook.dead_letters.is_empty());
+        assert_eq!(orderbook.dead_letters.lost(), orderbook.sequence - 2);
+
+        orderbook
+            .dead_letters
+            .set_policy(DeliveryFailurePolicy::Drop);
+        orderbook.add_order(sell);
+        assert!(orderbook.dead_letters.is_empty());
+    }
+
+    #[test]
+    fn test_time_priority_survives_a_restore() {
+        let (tx, r) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let clock = MockClock::new(1_000);
+        let mut orderbook = Orderbook::new(symbol, tx.clone());
+        orderbook.clock = Arc::new(clock.clone());
+        // stamped with the same time, only the arrival tells them apart
+        let orders: Vec<Order> = (0..5)
+            .map(|_| {
+                Order::new(
+                    Ulid::new().into(),
+                    symbol,
+                    OrderSide::Buy,
+                    2.0,
+                    Some(10.0),
+                    OrderType::Limit,
+                )
+                .stamped(&clock)
+            })
+            .collect();
+        for order in orders.iter().rev() {
+            orderbook.add_order(*order);
+        }
+        orderbook
+            .amend_order_quantity(orders[4].id, 1.0, OrderSide::Buy)
+            .unwrap();
+        orderbook
+            .amend_order_quantity(orders[3].id, 3.0, OrderSide::Buy)
+            .unwrap();
+        let expected: Vec<OrderId> = [4, 2, 1, 0, 3].map(|i| orders[i].id).to_vec();
+        let priority = |book: &Orderbook| -> Vec<OrderId> {
+            book.iter_bids()
+                .flat_map(|l| l.orders)
+                .map(|o| o.id)
+                .collect()
+        };
+        assert_eq!(priority(&orderbook), expected);
+
+        // the mirror of the book built from the updates agrees
+        let mut mirror = BookSnapshot::new(symbol, 0, vec![], vec![]);
+        for update in r.try_iter() {
+            mirror.apply(&update);
+        }
+        assert_eq!(mirror, orderbook.snapshot());
+
+        let snapshot = serde_json::to_string(&orderbook.snapshot()).unwrap();
+        let mut restored = Orderbook::new(symbol, tx);
+        restored.restore(&serde_json::from_str(&snapshot).unwrap());
+        assert_eq!(priority(&restored), expected);
+        let late = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(10.0),
+            OrderType::Limit,
+        )
+        .stamped(&MockClock::new(0));
+        restored.add_order(late);
+        assert_eq!(priority(&restored).last(), Some(&late.id));
+        assert_eq!(restored.snapshot().bids[..5], orderbook.snapshot().bids[..]);
+    }
+
+    #[test]
+    fn test_snapshots_keep_the_hidden_size_and_the_metadata() {
+        let (tx, _rx) = unbounded::<OrderbookUpdate>();
+        let symbol = Ulid::new().into();
+        let mut orderbook = Orderbook::new(symbol, tx.clone());
+        let iceberg = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            10.0,
+            Some(5.0),
+            OrderType::Limit,
+        )
+        .with_display_quantity(2.0);
+        let metadata = Metadata::from([("desk".to_string(), "rates".to_string())]);
+        orderbook.set_metadata(iceberg.id, metadata.clone());
+        orderbook.add_order(iceberg);
+
+        let snapshot = orderbook.snapshot();
+        assert_eq!(snapshot.asks[0].hidden_quantity, 8.0);
+        assert_eq!(snapshot.metadata.get(&iceberg.id), Some(&metadata));
+        let public = snapshot.public_view();
+        assert_eq!(
+            (public.asks[0].quantity, public.asks[0].hidden_quantity),
+            (2.0, 0.0)
+        );
+        assert!(public.metadata.is_empty());
+
+        let mut restored = Orderbook::new(symbol, tx);
+        restored
+            .restore(&serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap());
+        let order = restored.get_order(iceberg.id, OrderSide::Sell).unwrap();
+        assert_eq!((order.quantity, order.hidden_quantity), (2.0, 8.0));
+        assert_eq!(restored.metadata(iceberg.id), Some(&metadata));
+        assert_eq!(restored.checksum(), orderbook.checksum());
+    }
 }
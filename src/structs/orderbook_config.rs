@@ -0,0 +1,218 @@
+use super::matching_algorithm::{MatchingAlgorithm, PriceTimeMatcher};
+use super::order_history::{DEFAULT_ORDER_HISTORY_CAPACITY, DEFAULT_ORDER_HISTORY_DEPTH};
+use super::price_band::PriceBand;
+use crate::enums::market_remainder::MarketRemainder;
+use crate::enums::reference_source::ReferenceSource;
+use crate::enums::self_trade_prevention::SelfTradePrevention;
+use serde::{Deserialize, Serialize};
+
+/// Fees charged on the notional of each trade, e.g. 0.001 for 10 basis points.
+/// A negative maker fee is a rebate.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker_fee: f64,
+    pub taker_fee: f64,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_fee: f64, taker_fee: f64) -> FeeSchedule {
+        FeeSchedule {
+            maker_fee,
+            taker_fee,
+        }
+    }
+}
+
+/// Behavior of an orderbook, built with the `with_*` methods on top of the default
+///
+/// ```ignore
+/// let config = OrderbookConfig::default()
+///     .with_tick_size(0.01)
+///     .with_lot_size(1.0)
+///     .with_fees(FeeSchedule::new(-0.0001, 0.0005))
+///     .with_self_trade_prevention(SelfTradePrevention::CancelTaker);
+/// orderbooks_manager.new_orderbook_with_config(symbol, config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OrderbookConfig {
+    /// Price increment, the limit prices must be a multiple of it
+    pub tick_size: Option<f64>,
+    /// Quantity increment, the quantities must be a multiple of it
+    pub lot_size: Option<f64>,
+    /// Remainder below which an executed order is filled rather than left in the book, e.g. the minimum lot
+    pub dust_threshold: Option<f64>,
+    pub fees: FeeSchedule,
+    pub self_trade_prevention: SelfTradePrevention,
+    /// Maximum number of orders resting in the orderbook
+    pub max_orders: Option<usize>,
+    /// Compact the order pools once this many freed slots are waiting, None to compact on demand only
+    pub compact_threshold: Option<usize>,
+    pub price_band: Option<PriceBand>,
+    pub matcher: Box<dyn MatchingAlgorithm>,
+    /// Verify the invariants of the orderbook after each operation, only in debug builds
+    pub check_invariants: bool,
+    /// What happens to the remainder of a market order which exhausts the book
+    pub market_remainder: MarketRemainder,
+    /// Number of orders whose events are kept for `Orderbook::order_history`, 0 to disable it
+    pub order_history_capacity: usize,
+    /// Number of events kept for each order
+    pub order_history_depth: usize,
+    /// What maintains the reference price of the orderbook
+    pub reference_source: ReferenceSource,
+    /// Maximum relative distance to the reference price a market order executes at, e.g. 0.02 for 2%
+    pub market_collar: Option<f64>,
+    /// Maximum relative distance to the reference price of the equilibrium price of the call auctions
+    pub auction_collar: Option<f64>,
+}
+
+impl Default for OrderbookConfig {
+    fn default() -> Self {
+        OrderbookConfig {
+            tick_size: None,
+            lot_size: None,
+            dust_threshold: None,
+            fees: FeeSchedule::default(),
+            self_trade_prevention: SelfTradePrevention::default(),
+            max_orders: None,
+            compact_threshold: None,
+            price_band: None,
+            matcher: Box::new(PriceTimeMatcher),
+            check_invariants: false,
+            market_remainder: MarketRemainder::default(),
+            order_history_capacity: DEFAULT_ORDER_HISTORY_CAPACITY,
+            order_history_depth: DEFAULT_ORDER_HISTORY_DEPTH,
+            reference_source: ReferenceSource::default(),
+            market_collar: None,
+            auction_collar: None,
+        }
+    }
+}
+
+impl OrderbookConfig {
+    pub fn with_tick_size(mut self, tick_size: f64) -> Self {
+        self.tick_size = Some(tick_size);
+        self
+    }
+
+    pub fn with_lot_size(mut self, lot_size: f64) -> Self {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    /// Fill the orders whose remainder after an execution is below `threshold` instead of leaving
+    /// the dust in the book
+    pub fn with_dust_threshold(mut self, threshold: f64) -> Self {
+        self.dust_threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_self_trade_prevention(mut self, policy: SelfTradePrevention) -> Self {
+        self.self_trade_prevention = policy;
+        self
+    }
+
+    pub fn with_max_orders(mut self, max_orders: usize) -> Self {
+        self.max_orders = Some(max_orders);
+        self
+    }
+
+    /// Release the freed slots of the order pools once there are `threshold` of them, e.g. after
+    /// a burst of quotes is cancelled
+    pub fn with_compact_threshold(mut self, threshold: usize) -> Self {
+        self.compact_threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_price_band(mut self, price_band: PriceBand) -> Self {
+        self.price_band = Some(price_band);
+        self
+    }
+
+    pub fn with_matcher(mut self, matcher: Box<dyn MatchingAlgorithm>) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    pub fn with_market_remainder(mut self, policy: MarketRemainder) -> Self {
+        self.market_remainder = policy;
+        self
+    }
+
+    pub fn with_reference_source(mut self, source: ReferenceSource) -> Self {
+        self.reference_source = source;
+        self
+    }
+
+    /// Stop the market orders at `max_deviation` from the reference price, their remainder being
+    /// handled as when they exhaust the book
+    pub fn with_market_collar(mut self, max_deviation: f64) -> Self {
+        self.market_collar = Some(max_deviation);
+        self
+    }
+
+    /// Keep the equilibrium price of the call auctions within `max_deviation` of the reference price
+    pub fn with_auction_collar(mut self, max_deviation: f64) -> Self {
+        self.auction_collar = Some(max_deviation);
+        self
+    }
+
+    /// Keep the last `depth` events of the last `capacity` orders, a capacity of 0 disables the order history
+    pub fn with_order_history(mut self, capacity: usize, depth: usize) -> Self {
+        self.order_history_capacity = capacity;
+        self.order_history_depth = depth;
+        self
+    }
+
+    /// Panic as soon as an operation leaves the orderbook inconsistent, in debug builds
+    pub fn with_invariant_checks(mut self) -> Self {
+        self.check_invariants = true;
+        self
+    }
+}
+
+/// Quantity under which a remainder is floating point error, e.g. what is left of 0.3 after
+/// executing 0.1 then 0.2
+pub const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Whether the remainder of an order is too small to trade: nothing but floating point error, or
+/// below the dust threshold
+///
+/// #Parameters
+/// * 'remainder' - The quantity left
+/// * 'dust_threshold' - The remainder below which an order is filled, None for the floating point error only
+pub fn is_dust(remainder: f64, dust_threshold: Option<f64>) -> bool {
+    remainder <= QUANTITY_EPSILON
+        || dust_threshold.is_some_and(|threshold| remainder < threshold - QUANTITY_EPSILON)
+}
+
+/// Whether a value is a multiple of an increment, up to the floating point error
+pub fn is_multiple_of(value: f64, increment: f64) -> bool {
+    let steps = (value / increment).round();
+    (steps * increment - value).abs() <= increment * 1e-9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_multiple_of() {
+        assert!(is_multiple_of(1.23, 0.01));
+        assert!(is_multiple_of(0.3, 0.1));
+        assert!(!is_multiple_of(1.235, 0.01));
+        assert!(!is_multiple_of(2.5, 1.0));
+    }
+
+    #[test]
+    fn test_is_dust() {
+        assert!(is_dust(0.3 - 0.1 - 0.2, None));
+        assert!(!is_dust(0.001, None));
+        assert!(is_dust(0.001, Some(0.01)));
+        assert!(!is_dust(0.01, Some(0.01)));
+    }
+}
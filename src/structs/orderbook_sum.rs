@@ -1,9 +1,17 @@
+use super::book_metrics::BookMetrics;
+use crate::formats::checksum::{book_checksum, CHECKSUM_DEPTH};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+/// A list of [price, size] pairs, the format of the exchanges websocket depth feeds
+pub type PriceSizeLevels = Vec<[f64; 2]>;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct BidAskSummarize {
     pub price: f64,
     pub qty: f64,
+    #[serde(rename = "qtySum")]
     pub qty_sum: f64,
+    #[serde(rename = "qtyPercent")]
     pub qty_percent: f64,
 }
 
@@ -18,11 +26,49 @@ impl BidAskSummarize {
     }
 }
 
-#[derive(Debug)]
+/// Visible and total quantity of a price level, the total including the hidden size of its icebergs
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LevelLiquidity {
+    pub price: f64,
+    #[serde(rename = "visibleQty")]
+    pub visible_qty: f64,
+    #[serde(rename = "totalQty")]
+    pub total_qty: f64,
+    /// Number of iceberg orders of the level
+    pub icebergs: usize,
+}
+
+impl LevelLiquidity {
+    /// Share of the total quantity of the level hidden in its icebergs, between 0 and 1
+    pub fn hidden_ratio(&self) -> f64 {
+        match self.total_qty {
+            total if total > 0.0 => (total - self.visible_qty) / total,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct OrderBookSummarized {
     pub bids: Vec<BidAskSummarize>,
+    #[serde(rename = "midPrice")]
     pub mid_price: f64,
     pub asks: Vec<BidAskSummarize>,
+    /// Volumes, order counts and notional at top of the orderbook
+    #[serde(default)]
+    pub metrics: BookMetrics,
+    /// CRC32 of the best levels of the whole book, see `book_checksum`
+    #[serde(default)]
+    pub checksum: u32,
+    /// Visible and total quantity of the bid levels, best price first, empty in the public summaries
+    #[serde(rename = "bidLiquidity", default)]
+    pub bid_liquidity: Vec<LevelLiquidity>,
+    /// Visible and total quantity of the ask levels, best price first, empty in the public summaries
+    #[serde(rename = "askLiquidity", default)]
+    pub ask_liquidity: Vec<LevelLiquidity>,
+    /// Reference price of the orderbook the price protections are centered on, None until it is known
+    #[serde(rename = "referencePrice", default)]
+    pub reference_price: Option<f64>,
 }
 
 impl OrderBookSummarized {
@@ -31,6 +77,10 @@ impl OrderBookSummarized {
         mid_price: f64,
         asks: Vec<(f64, f64, f64)>,
     ) -> OrderBookSummarized {
+        let pairs = |levels: &[(f64, f64, f64)]| -> PriceSizeLevels {
+            levels.iter().map(|l| [l.0, l.1]).collect()
+        };
+        let checksum = book_checksum(&pairs(&bids), &pairs(&asks), CHECKSUM_DEPTH);
         let bids_volume: f64 = bids.iter().map(|b| b.1).sum();
         let bids = bids
             .iter()
@@ -46,6 +96,95 @@ impl OrderBookSummarized {
             bids,
             mid_price,
             asks,
+            metrics: BookMetrics::default(),
+            checksum,
+            bid_liquidity: Vec::new(),
+            ask_liquidity: Vec::new(),
+            reference_price: None,
         }
     }
+
+    /// Keep the best `depth` levels of each side, the cumulated quantities, the percentages and
+    /// the checksum still refer to the whole side
+    ///
+    /// #Parameters
+    /// * 'depth' - The number of levels kept per side
+    pub fn truncate(&mut self, depth: usize) {
+        self.bids.truncate(depth);
+        self.asks.truncate(depth);
+        self.bid_liquidity.truncate(depth);
+        self.ask_liquidity.truncate(depth);
+    }
+
+    /// Levels of each side as [price, size] pairs, best price first
+    ///
+    /// #Returns
+    /// * (PriceSizeLevels, PriceSizeLevels) - The bids and the asks
+    pub fn price_size_levels(&self) -> (PriceSizeLevels, PriceSizeLevels) {
+        let pairs = |levels: &[BidAskSummarize]| levels.iter().map(|l| [l.price, l.qty]).collect();
+        (pairs(&self.bids), pairs(&self.asks))
+    }
+
+    /// Attach the visible and total quantities of the levels, for the internal summaries only
+    ///
+    /// #Parameters
+    /// * 'bids' - The bid levels, best price first
+    /// * 'asks' - The ask levels, best price first
+    pub fn with_liquidity(
+        mut self,
+        bids: Vec<LevelLiquidity>,
+        asks: Vec<LevelLiquidity>,
+    ) -> OrderBookSummarized {
+        self.bid_liquidity = bids;
+        self.ask_liquidity = asks;
+        self
+    }
+
+    /// Attach the reference price of the orderbook to the summary
+    pub fn with_reference_price(mut self, reference_price: Option<f64>) -> OrderBookSummarized {
+        self.reference_price = reference_price;
+        self
+    }
+
+    /// Attach the metrics of the orderbook to the summary
+    pub fn with_metrics(mut self, metrics: BookMetrics) -> OrderBookSummarized {
+        self.metrics = metrics;
+        self
+    }
+}
+
+impl From<&OrderBookSummarized> for (PriceSizeLevels, PriceSizeLevels) {
+    fn from(summary: &OrderBookSummarized) -> Self {
+        summary.price_size_levels()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_conversions() {
+        let mut summary = OrderBookSummarized::new(
+            vec![(2.0, 1.0, 3.0), (1.0, 2.0, 2.0)],
+            2.5,
+            vec![(3.0, 4.0, 4.0)],
+        );
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains("\"midPrice\":2.5"));
+        assert_eq!(
+            serde_json::from_str::<OrderBookSummarized>(&json).unwrap(),
+            summary
+        );
+
+        summary.truncate(1);
+        let (bids, asks): (PriceSizeLevels, PriceSizeLevels) = (&summary).into();
+        assert_eq!(bids, vec![[2.0, 1.0]]);
+        assert_eq!(asks, vec![[3.0, 4.0]]);
+        assert_eq!(summary.bids[0].qty_percent, 1.0 / 3.0 * 100.0);
+        assert_eq!(
+            summary.checksum,
+            crate::formats::checksum::crc32(b"2:1:3:4:1:2")
+        );
+    }
 }
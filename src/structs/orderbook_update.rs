@@ -1,13 +1,66 @@
-use super::{order::Order, trade::Trade};
+use super::ids::{OrderId, Symbol};
+use super::{
+    auction::AuctionResult, level_book::LevelDelta, order::Metadata, order::Order,
+    price_band::CircuitBreakerEvent, trade::Trade,
+};
+use crate::enums::order_status::OrderStatus;
+use crate::enums::orderbook_state::OrderbookState;
 use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::risk::message_ratio::ComplianceEvent;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
 pub struct OrderbookUpdate {
-    pub symbol: u128,
+    pub symbol: Symbol,
     pub update_type: OrderbookUpdateType,
     pub order: Option<Order>,
     pub trade: Option<Trade>,
-    pub cancel_id: Option<u128>,
-    pub filled_id: Option<u128>,
+    pub cancel_id: Option<OrderId>,
+    pub filled_id: Option<OrderId>,
+    /// Sequence number of the update within its orderbook, starting at 1
+    #[serde(default)]
+    pub sequence: u64,
+    /// Result of the call auction for AuctionResult updates
+    #[serde(default)]
+    pub auction: Option<AuctionResult>,
+    /// New trading state of the orderbook for StateChange updates
+    #[serde(default)]
+    pub state: Option<OrderbookState>,
+    /// Circuit breaker trip for CircuitBreaker updates
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerEvent>,
+    /// Time the update was published by the orderbook, in nanoseconds since UNIX epoch
+    #[serde(default)]
+    pub timestamp: u64,
+    /// Quantity executed for PartiallyFilled updates
+    #[serde(default)]
+    pub fill_quantity: Option<f64>,
+    /// Message-to-trade ratio event of a user for Compliance updates
+    #[serde(default)]
+    pub compliance: Option<ComplianceEvent>,
+    /// Checksum of the best levels of the book after the update for Heartbeat updates
+    #[serde(default)]
+    pub checksum: Option<u32>,
+    /// Metadata of the order of the update, None when the order has none
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+    /// Price levels changed since the previous update of the orderbook, the market-by-price view
+    #[serde(default)]
+    pub levels: Vec<LevelDelta>,
+    /// Status of the order before the update for the updates changing it, the new status being the status of the order
+    #[serde(default)]
+    pub previous_status: Option<OrderStatus>,
+    /// New reference price of the orderbook for ReferencePrice updates and the trades moving it
+    #[serde(default)]
+    pub reference_price: Option<f64>,
+}
+
+impl OrderbookUpdate {
+    /// status_change returns the previous and the new status of the order of the update
+    ///
+    /// #Returns
+    /// * Option<(OrderStatus, OrderStatus)> - None if the update does not change the status of an order
+    pub fn status_change(&self) -> Option<(OrderStatus, OrderStatus)> {
+        Some((self.previous_status?, self.order?.status))
+    }
 }
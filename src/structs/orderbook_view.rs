@@ -0,0 +1,230 @@
+use super::book_metrics::BookMetrics;
+use super::ids::Symbol;
+use super::level_book::{LevelBook, LevelDelta, PriceLevel};
+use super::orderbook::Orderbook;
+use super::orderbook_sum::OrderBookSummarized;
+use super::orderbook_update::OrderbookUpdate;
+use super::symbol_registry::Precision;
+use crate::enums::level_action::LevelAction;
+use crate::enums::orderbook_state::OrderbookState;
+use crate::enums::side::OrderSide;
+use crate::formats::checksum::{book_checksum, CHECKSUM_DEPTH};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Debug, Default)]
+struct ViewState {
+    levels: LevelBook,
+    state: OrderbookState,
+    /// Sequence number of the last update applied
+    sequence: u64,
+    reference_price: Option<f64>,
+}
+
+/// Read-only handle on the price levels of an orderbook, kept up to date by the manager from the
+/// updates it dispatches. The depth, the best bid and offer, the metrics and the summary are read
+/// from any thread without access to the orderbook while it is matched. Clones share the same levels.
+#[derive(Debug, Clone)]
+pub struct OrderbookView {
+    symbol: Symbol,
+    /// Precision of the symbol, the updates applied being rounded by the manager
+    precision: Precision,
+    state: Arc<RwLock<ViewState>>,
+}
+
+impl OrderbookView {
+    /// Create a view of the current levels of an orderbook
+    ///
+    /// #Parameters
+    /// * 'orderbook' - The orderbook
+    /// * 'precision' - The precision the levels and the summary are rounded to
+    pub fn new(orderbook: &Orderbook, precision: Precision) -> OrderbookView {
+        let mut levels = LevelBook::new();
+        let (bids, asks) = orderbook.depth(usize::MAX);
+        for (side, level) in bids
+            .into_iter()
+            .map(|level| (OrderSide::Buy, level))
+            .chain(asks.into_iter().map(|level| (OrderSide::Sell, level)))
+        {
+            let level = precision.round_level(level);
+            levels.apply(&LevelDelta {
+                side,
+                action: LevelAction::Add,
+                price: level.price,
+                quantity: level.quantity,
+                orders: level.orders,
+            });
+        }
+        OrderbookView {
+            symbol: orderbook.symbol,
+            precision,
+            state: Arc::new(RwLock::new(ViewState {
+                levels,
+                state: orderbook.state,
+                sequence: orderbook.sequence,
+                reference_price: orderbook.reference_price(),
+            })),
+        }
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+    }
+
+    /// Sequence number of the last update of the orderbook the view reflects
+    pub fn sequence(&self) -> u64 {
+        self.state.read().unwrap().sequence
+    }
+
+    /// Trading state of the orderbook
+    pub fn state(&self) -> OrderbookState {
+        self.state.read().unwrap().state
+    }
+
+    /// Best price levels of each side
+    ///
+    /// #Parameters
+    /// * 'max_levels' - The maximum number of levels returned per side
+    ///
+    /// #Returns
+    /// * (Vec<PriceLevel>, Vec<PriceLevel>) - The bid and ask levels, best price first
+    pub fn depth(&self, max_levels: usize) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        let state = self.state.read().unwrap();
+        (
+            state.levels.bids().take(max_levels).copied().collect(),
+            state.levels.asks().take(max_levels).copied().collect(),
+        )
+    }
+
+    /// Best bid and best ask levels, None for an empty side
+    pub fn best_bid_offer(&self) -> (Option<PriceLevel>, Option<PriceLevel>) {
+        let state = self.state.read().unwrap();
+        let best_bid = state.levels.bids().next().copied();
+        let best_ask = state.levels.asks().next().copied();
+        (best_bid, best_ask)
+    }
+
+    /// Volumes, order counts and notional at top of both sides
+    pub fn metrics(&self) -> BookMetrics {
+        self.state.read().unwrap().levels.metrics()
+    }
+
+    /// Summary of the orderbook, as `OrderbooksManager::get_orderbook` returns it
+    pub fn summary(&self) -> OrderBookSummarized {
+        let state = self.state.read().unwrap();
+        let (bids, mid_price, asks) = state.levels.summarize();
+        let mut summary = OrderBookSummarized::new(bids, mid_price, asks)
+            .with_metrics(state.levels.metrics())
+            .with_reference_price(state.reference_price);
+        self.precision.round_summary(&mut summary);
+        summary
+    }
+
+    /// CRC32 of the best levels of the book, see `Orderbook::checksum`
+    pub fn checksum(&self) -> u32 {
+        let (bids, asks) = self.depth(CHECKSUM_DEPTH);
+        let pairs = |levels: Vec<PriceLevel>| -> Vec<[f64; 2]> {
+            levels.iter().map(|l| [l.price, l.quantity]).collect()
+        };
+        book_checksum(&pairs(bids), &pairs(asks), CHECKSUM_DEPTH)
+    }
+
+    /// Apply the level changes of an update of the orderbook, the updates already reflected are skipped
+    fn apply(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.write().unwrap();
+        if update.sequence <= state.sequence {
+            return;
+        }
+        state.sequence = update.sequence;
+        for delta in update.levels.iter() {
+            state.levels.apply(delta);
+        }
+        if let Some(orderbook_state) = update.state {
+            state.state = orderbook_state;
+        }
+        if update.reference_price.is_some() {
+            state.reference_price = update.reference_price;
+        }
+    }
+}
+
+/// Views of the orderbooks of a manager by symbol, updated as the manager dispatches the updates
+#[derive(Debug, Clone, Default)]
+pub struct OrderbookViews {
+    views: Arc<Mutex<HashMap<Symbol, OrderbookView>>>,
+}
+
+impl OrderbookViews {
+    pub fn new() -> OrderbookViews {
+        OrderbookViews::default()
+    }
+
+    /// View of an orderbook, created from its current levels the first time it is asked for
+    pub fn get_or_create(&self, orderbook: &Orderbook, precision: Precision) -> OrderbookView {
+        self.views
+            .lock()
+            .unwrap()
+            .entry(orderbook.symbol)
+            .or_insert_with(|| OrderbookView::new(orderbook, precision))
+            .clone()
+    }
+
+    /// Stop updating the view of a removed orderbook
+    pub fn remove(&self, symbol: Symbol) {
+        self.views.lock().unwrap().remove(&symbol);
+    }
+
+    /// Keep the views up to date with the updates of their orderbook
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let views = self.views.lock().unwrap();
+        if let Some(view) = views.get(&update.symbol) {
+            view.apply(update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::structs::order::Order;
+    use crate::structs::orderbooks_manager::OrderbooksManager;
+    use std::thread;
+    use ulid::Ulid;
+
+    #[test]
+    fn test_views_follow_the_orderbook() {
+        let symbol: Symbol = Ulid::new().into();
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(symbol);
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        manager.add_order(order(OrderSide::Buy, 2.0, 9.0)).unwrap();
+        let view = manager.view(symbol).unwrap();
+        let ask = order(OrderSide::Sell, 3.0, 11.0);
+        manager.add_order(ask).unwrap();
+        manager.add_order(order(OrderSide::Buy, 1.0, 11.0)).unwrap();
+
+        let reader = view.clone();
+        let (bid, ask_level) = thread::spawn(move || reader.best_bid_offer())
+            .join()
+            .unwrap();
+        assert_eq!(bid.map(|l| (l.price, l.quantity)), Some((9.0, 2.0)));
+        assert_eq!(ask_level.map(|l| (l.price, l.quantity)), Some((11.0, 2.0)));
+        assert_eq!(view.summary(), manager.get_orderbook(symbol).unwrap());
+        assert_eq!(view.checksum(), manager.checksum(symbol).unwrap());
+        assert_eq!(view.sequence(), manager.orderbooks[&symbol].sequence);
+
+        manager.cancel_order(ask.id, symbol, ask.side).unwrap();
+        assert_eq!(view.depth(10).1, vec![]);
+        assert_eq!(view.metrics(), manager.orderbooks[&symbol].metrics());
+    }
+}
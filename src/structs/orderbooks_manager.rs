@@ -1,20 +1,145 @@
+use super::auction::AuctionResult;
+use super::basket::{Basket, BasketEvent, BasketRegistry};
+use super::book_condition::BookCondition;
+use super::book_snapshot::BookSnapshot;
+use super::clock::{Clock, SystemClock};
+use super::dead_letter::DeadLetterQueue;
+use super::ids::{OrderId, Symbol, UserId};
+use super::invariants::InvariantReport;
+use super::kill_switch::{KillSwitchEvent, KillSwitchTarget, KillSwitches};
+use super::level_book::{FillCost, LevelStats};
+use super::match_observer::MatchObserver;
+use super::matching_algorithm::MatchingAlgorithm;
+use super::order::Metadata;
+use super::order_ack::OrderAck;
+use super::order_history::OrderEvent;
 use super::orderbook::Orderbook;
+use super::orderbook_config::{is_multiple_of, OrderbookConfig};
 use super::orderbook_update::OrderbookUpdate;
+use super::positions::{Position, PositionTracker};
+use super::price_band::PriceBand;
+use super::router::{plan, ParentEvent, ParentOrder, Router};
+use super::session::{SessionEvent, SessionRegistry};
+use super::shutdown::ShutdownReport;
+use super::spread::{takeable, ImpliedPrice, SpreadAck, SpreadDefinition, SpreadExecution};
+use super::subscription::Subscription;
+use super::subscription_builder::SubscriptionBuilder;
+use super::symbol_registry::{Precision, SymbolRegistry};
+use super::tenant::TenantRegistry;
 use super::trade::Trade;
+use super::trading_calendar::{SessionTransition, TradingCalendar, TradingHours};
+use super::update_bus::UpdateBus;
+use crate::accounts::balance::Balance;
+use crate::accounts::ledger::Accounts;
+use crate::accounts::settlement::SettlementInstruction;
+use crate::accounts::settlement_gate::SettlementGate;
+use crate::algos::AlgoScheduler;
+use crate::enums::batch_mode::BatchMode;
+use crate::enums::level_action::LevelAction;
+use crate::enums::market_feed::MarketFeed;
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_state::OrderbookState;
+use crate::enums::overflow_policy::OverflowPolicy;
+use crate::enums::session_event_type::SessionEventType;
+use crate::enums::session_phase::SessionPhase;
+use crate::enums::shutdown_policy::ShutdownPolicy;
+use crate::enums::summary_privacy::SummaryPrivacy;
+use crate::formats::level_diff::LevelChange;
+use crate::heap::arena::PoolStats;
+use crate::ingest::MirrorFeed;
+use crate::metrics::{EngineMetrics, MetricsRecorder, OrderLatency};
+use crate::persistence::journal::Journal;
+use crate::persistence::recovery::{rebuild, RecoveryReport};
+use crate::persistence::snapshot_store::SnapshotStore;
+use crate::persistence::snapshots::{SnapshotPolicy, SnapshotService};
+use crate::persistence::{persist_update, Persistence};
+use crate::recorder::DepthRecorder;
+use crate::risk::engine::RiskEngine;
+use crate::risk::idempotency::IdempotencyGuard;
+use crate::risk::message_ratio::{ComplianceEvent, MessageRatioMonitor};
+use crate::risk::rate_limit::RateLimiter;
 use crate::structs::order::Order;
-use crate::structs::orderbook_sum::{BidAskSummarize, OrderBookSummarized};
+use crate::structs::orderbook_sum::OrderBookSummarized;
+use crate::structs::orderbook_view::{OrderbookView, OrderbookViews};
 use crate::{OrderSide, OrderbookUpdateType};
 use async_stream::stream;
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use futures_util::Stream;
-use std::collections::HashMap;
+use futures_util::{future, Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
 use std::io::Error;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "native")]
+use tokio::time::MissedTickBehavior;
+use web_time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct OrderbooksManager {
-    pub orderbooks: HashMap<u128, Orderbook>,
+    pub orderbooks: HashMap<Symbol, Orderbook>,
     pub tx: Sender<OrderbookUpdate>,
     pub rx: Receiver<OrderbookUpdate>,
+    pub bus: UpdateBus,
+    /// Queue capacity of the subscriptions opened by the listeners, None for unbounded
+    pub subscription_capacity: Option<usize>,
+    /// What the listeners subscriptions do when their queue is full
+    pub overflow_policy: OverflowPolicy,
+    /// Sessions of the connected users, their orders are cancelled when the session ends
+    pub sessions: SessionRegistry,
+    /// Trading hours of the symbols, applied by `run_sessions`
+    pub calendar: TradingCalendar,
+    /// Feeds keeping the orderbooks mirroring external venues in step, by symbol
+    pub mirrors: HashMap<Symbol, MirrorFeed>,
+    /// Read-only views of the orderbooks handed out by `view`, kept up to date as the updates are dispatched
+    pub views: OrderbookViews,
+    /// Pre-trade risk checks, consulted before accepting an order
+    pub risk: RiskEngine,
+    /// Per user throttling of the incoming orders, shared with the siblings
+    pub rate_limiter: RateLimiter,
+    /// Order IDs and client order IDs taken, rejecting the orders booked twice, shared with the siblings
+    pub idempotency: IdempotencyGuard,
+    /// Message-to-trade ratio of the users, publishing Compliance updates, shared with the siblings
+    pub message_ratios: MessageRatioMonitor,
+    /// Kill switches blocking the flow of users and symbols, shared with the siblings
+    pub kill_switches: KillSwitches,
+    /// What `shutdown` does with the resting orders
+    pub shutdown_policy: ShutdownPolicy,
+    /// Set by `shutdown`, the new orders are rejected from then on
+    pub shut_down: bool,
+    /// Balances of the users, None when the orders are not backed by balances
+    pub accounts: Option<Accounts>,
+    /// Trades waiting for an external settlement service, None when the trades are not settled
+    pub settlement: Option<SettlementGate>,
+    /// Clock stamping the orders, trades and updates of every orderbook
+    pub clock: Arc<dyn Clock>,
+    /// Storage of the orders and trades, fed with the updates before they are published
+    pub persistence: Option<Arc<dyn Persistence>>,
+    /// Log of the updates, appended before they are published
+    pub journal: Option<Arc<dyn Journal>>,
+    /// Snapshots of the orderbooks taken as they change, None when the books are not snapshotted
+    pub snapshots: Option<SnapshotService>,
+    /// Time series of the depth of the orderbooks, None when the depth is not recorded
+    pub depth_recorder: Option<DepthRecorder>,
+    /// Counters of the orders, trades and match latencies, shared with the siblings
+    pub metrics_recorder: MetricsRecorder,
+    /// Whether the acks of the orders carry the time they spent in each stage of the engine
+    pub ack_latency: bool,
+    /// Baskets of orders submitted together, shared with the siblings
+    pub baskets: BasketRegistry,
+    /// Routes of the instruments traded on several orderbooks and their parent orders, shared with the siblings
+    pub router: Router,
+    /// Algo orders sliced into children over time, shared with the siblings
+    pub algos: AlgoScheduler,
+    /// Spread instruments by symbol, traded through the orderbooks of their legs
+    pub spreads: HashMap<Symbol, SpreadDefinition>,
+    /// Positions and trade logs of the users, shared with the siblings
+    pub positions: PositionTracker,
+    /// Reference data of the symbols, shared with the siblings
+    pub symbols: SymbolRegistry,
+    /// Tenants owning the orderbooks and the users, shared with the siblings
+    pub tenants: TenantRegistry,
+    /// Updates the orderbooks failed to deliver, shared with the orderbooks and the siblings
+    pub dead_letters: DeadLetterQueue,
 }
 
 impl OrderbooksManager {
@@ -28,29 +153,443 @@ impl OrderbooksManager {
             orderbooks: HashMap::new(),
             tx,
             rx,
+            bus: UpdateBus::new(),
+            subscription_capacity: None,
+            overflow_policy: OverflowPolicy::default(),
+            sessions: SessionRegistry::new(),
+            calendar: TradingCalendar::new(),
+            mirrors: HashMap::new(),
+            views: OrderbookViews::new(),
+            risk: RiskEngine::new(),
+            rate_limiter: RateLimiter::new(),
+            idempotency: IdempotencyGuard::new(),
+            message_ratios: MessageRatioMonitor::new(),
+            kill_switches: KillSwitches::new(),
+            shutdown_policy: ShutdownPolicy::default(),
+            shut_down: false,
+            accounts: None,
+            settlement: None,
+            clock: Arc::new(SystemClock),
+            persistence: None,
+            journal: None,
+            snapshots: None,
+            depth_recorder: None,
+            metrics_recorder: MetricsRecorder::new(),
+            ack_latency: false,
+            baskets: BasketRegistry::new(),
+            router: Router::new(),
+            algos: AlgoScheduler::new(),
+            spreads: HashMap::new(),
+            positions: PositionTracker::default(),
+            symbols: SymbolRegistry::new(),
+            tenants: TenantRegistry::new(),
+            dead_letters: DeadLetterQueue::new(),
         }
     }
 
+    /// Create a new OrderbooksManager whose orderbooks read the time from the given clock
+    ///
+    /// #Parameters
+    /// * 'clock' - The clock, e.g. a MockClock for deterministic tests
+    pub fn with_clock(clock: Arc<dyn Clock>) -> OrderbooksManager {
+        OrderbooksManager {
+            clock,
+            ..OrderbooksManager::new()
+        }
+    }
+
+    /// Create a new OrderbooksManager whose updates never queue more than `capacity` per consumer,
+    /// so that a stalled consumer can't exhaust the memory.
+    /// The orderbooks channel is drained after every operation, the updates pile up in the
+    /// subscription queues of the consumers which are bounded here.
+    ///
+    /// #Parameters
+    /// * 'capacity' - The maximum number of updates queued per listener
+    /// * 'policy' - What to do when an update is published to a full queue: block the publisher,
+    ///   drop the oldest update or coalesce the updates of a same order
+    pub fn with_capacity(capacity: usize, policy: OverflowPolicy) -> OrderbooksManager {
+        OrderbooksManager {
+            subscription_capacity: Some(capacity),
+            overflow_policy: policy,
+            ..OrderbooksManager::new()
+        }
+    }
+
+    /// Create a new OrderbooksManager whose listeners use bounded subscription queues, same as `with_capacity`
+    ///
+    /// #Parameters
+    /// * 'capacity' - The maximum number of updates queued per listener
+    /// * 'policy' - What to do when an update is published to a full queue
+    pub fn with_backpressure(capacity: usize, policy: OverflowPolicy) -> OrderbooksManager {
+        OrderbooksManager::with_capacity(capacity, policy)
+    }
+
+    /// Create a manager without orderbooks sharing the bus, the backpressure settings, the risk engine, the rate limiters, the order IDs, the kill switches,
+    /// the baskets, the routes, the algo orders, the symbols, the tenants, the dead letters, the accounts, the settlement gate, the clock, the persistence, the journal, the snapshots and the depth recorder of this one, e.g. to run orderbooks on other threads
+    ///
+    /// #Returns
+    /// * OrderbooksManager - The new manager, with its own update channel
+    pub fn sibling(&self) -> OrderbooksManager {
+        OrderbooksManager {
+            bus: self.bus.clone(),
+            subscription_capacity: self.subscription_capacity,
+            overflow_policy: self.overflow_policy,
+            risk: self.risk.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            idempotency: self.idempotency.clone(),
+            message_ratios: self.message_ratios.clone(),
+            kill_switches: self.kill_switches.clone(),
+            shutdown_policy: self.shutdown_policy,
+            accounts: self.accounts.clone(),
+            settlement: self.settlement.clone(),
+            clock: self.clock.clone(),
+            persistence: self.persistence.clone(),
+            journal: self.journal.clone(),
+            snapshots: self.snapshots.clone(),
+            depth_recorder: self.depth_recorder.clone(),
+            metrics_recorder: self.metrics_recorder.clone(),
+            ack_latency: self.ack_latency,
+            baskets: self.baskets.clone(),
+            router: self.router.clone(),
+            algos: self.algos.clone(),
+            positions: self.positions.clone(),
+            symbols: self.symbols.clone(),
+            tenants: self.tenants.clone(),
+            dead_letters: self.dead_letters.clone(),
+            ..OrderbooksManager::new()
+        }
+    }
+
+    /// Forward the updates produced by the orderbooks to every subscriber.
+    /// This is done after each operation of the manager, call it after operating an orderbook directly.
+    pub fn dispatch(&self) {
+        self.dispatch_with(|_| {});
+    }
+
+    /// Dispatch the updates, showing each of them to a function first
+    pub(crate) fn dispatch_with(&self, mut inspect: impl FnMut(&OrderbookUpdate)) {
+        for update in self.rx.try_iter() {
+            // the market is shown the prices and quantities rounded to the precision of the symbol,
+            // the engine books and settles them as computed
+            let precision = self.symbols.precision(update.symbol);
+            let rounded = (precision != Precision::default()).then(|| {
+                let mut rounded = update.clone();
+                precision.round_update(&mut rounded);
+                rounded
+            });
+            inspect(&update);
+            self.views.on_update(rounded.as_ref().unwrap_or(&update));
+            self.metrics_recorder.on_update(&update);
+            self.risk.on_update(&update);
+            self.idempotency.on_update(&update, self.clock.monotonic());
+            self.tenants.on_update(&update);
+            self.baskets.on_update(&update);
+            self.router.on_update(&update);
+            self.algos.on_update(&update);
+            self.positions.on_update(&update);
+            if let Some(accounts) = &self.accounts {
+                accounts.on_update(&update);
+            }
+            if let Some(settlement) = &self.settlement {
+                settlement.on_update(&update);
+            }
+            if let Some(persistence) = &self.persistence {
+                if let Err(error) = persist_update(persistence.as_ref(), &update) {
+                    persistence.on_error(&update, error);
+                }
+            }
+            if let Some(journal) = &self.journal {
+                if let Err(error) = journal.append(&update) {
+                    journal.on_error(&update, error);
+                }
+            }
+            let symbol = update.symbol;
+            let now = self.clock.monotonic();
+            let compliance = self.message_ratios.on_update(&update, now);
+            self.bus.publish(rounded.unwrap_or(update));
+            for event in compliance {
+                self.publish_compliance(symbol, event);
+            }
+            if self
+                .snapshots
+                .as_ref()
+                .is_some_and(|snapshots| snapshots.on_update(symbol, now))
+            {
+                // a failure is recorded by the service, the next update tries again
+                let _ = self.save_snapshot(symbol);
+            }
+        }
+        if let Some(accounts) = &self.accounts {
+            accounts.release_closed();
+        }
+    }
+
+    /// Snapshot an orderbook to the snapshot store and truncate the journal up to the snapshot
+    fn save_snapshot(&self, symbol: Symbol) -> Result<(), Error> {
+        let (Some(snapshots), Some(orderbook)) = (&self.snapshots, self.orderbooks.get(&symbol))
+        else {
+            return Ok(());
+        };
+        snapshots.save(
+            &orderbook.snapshot(),
+            self.journal.as_deref(),
+            self.clock.monotonic(),
+        )
+    }
+
+    /// Snapshot the orderbooks which changed and are due according to the snapshot policy, to be
+    /// called periodically so that the books which went quiet are snapshotted too
+    ///
+    /// #Returns
+    /// * Result<usize, Error> - The number of snapshots stored, the error of the first which failed
+    pub fn take_due_snapshots(&self) -> Result<usize, Error> {
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(0);
+        };
+        let due = snapshots.due(self.clock.monotonic());
+        for symbol in due.iter() {
+            self.save_snapshot(*symbol)?;
+        }
+        Ok(due.len())
+    }
+
+    /// Log the updates of the orderbooks to a journal, appended before they are published
+    ///
+    /// #Parameters
+    /// * 'journal' - The journal
+    pub fn set_journal(&mut self, journal: Arc<dyn Journal>) {
+        self.journal = Some(journal);
+    }
+
+    /// Snapshot the orderbooks as they change to a store, the journal is truncated after each snapshot
+    ///
+    /// #Parameters
+    /// * 'store' - Where the snapshots are saved
+    /// * 'policy' - After how long or how many updates a book which changed is snapshotted
+    pub fn set_snapshot_store(&mut self, store: Arc<dyn SnapshotStore>, policy: SnapshotPolicy) {
+        self.snapshots = Some(SnapshotService::new(store, policy));
+    }
+
+    /// Recover the orderbooks after a restart: each book is rebuilt from its latest snapshot and the
+    /// journal updates which followed it, then resumes its sequence numbers after the last of them.
+    /// The books with a snapshot are created if missing, with the default configuration, and the
+    /// books already created are recovered from the journal alone when they have no snapshot.
+    /// The restored orders are registered as accepted orders: they count against the risk limits, their
+    /// IDs can't be reused and their balance is reserved when the accounts are enabled.
+    /// Nothing is restored unless every book could be rebuilt and every order reserved.
+    ///
+    /// #Returns
+    /// * Result<Vec<RecoveryReport>, Error> - How each book was recovered, sorted by symbol,
+    ///   InvalidData on a gap in the journal or a checksum mismatch, the error of the reservation
+    ///   of an order the balance of its user does not cover
+    pub fn recover(&mut self) -> Result<Vec<RecoveryReport>, Error> {
+        if self.journal.is_none() && self.snapshots.is_none() {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No journal nor snapshot store to recover from",
+            ));
+        }
+        let store = self.snapshots.as_ref().map(|s| s.store().as_ref());
+        let mut symbols: Vec<Symbol> = self.orderbooks.keys().copied().collect();
+        if let Some(store) = store {
+            symbols.extend(store.symbols()?);
+        }
+        symbols.sort();
+        symbols.dedup();
+        let mut books = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            if self.orderbooks.get(&symbol).is_some_and(|o| o.sequence > 0) {
+                return Err(Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("Orderbook {symbol} already has updates"),
+                ));
+            }
+            books.push(rebuild(store, self.journal.as_deref(), symbol)?);
+        }
+        // the books are checked on scratch orderbooks, so that a mismatch leaves every book untouched
+        for (book, _) in books.iter() {
+            let mut scratch = Orderbook::new(book.symbol, unbounded().0);
+            scratch.restore(book);
+            if scratch.checksum() != book.checksum() {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Checksum mismatch of the restored orderbook {}",
+                        book.symbol
+                    ),
+                ));
+            }
+        }
+        let restored: Vec<Order> = books
+            .iter()
+            .flat_map(|(book, _)| book.bids.iter().chain(book.asks.iter()).copied())
+            .collect();
+        for (count, order) in restored.iter().enumerate() {
+            if let Err(error) = self.reserve(order) {
+                if let Some(accounts) = &self.accounts {
+                    for order in restored[..count].iter() {
+                        accounts.release(order.id);
+                    }
+                }
+                return Err(error);
+            }
+        }
+        let mut reports = Vec::with_capacity(books.len());
+        for (book, report) in books {
+            if !self.orderbooks.contains_key(&book.symbol) {
+                self.new_orderbook(book.symbol);
+            }
+            self.orderbooks
+                .get_mut(&book.symbol)
+                .unwrap()
+                .restore(&book);
+            reports.push(report);
+        }
+        // the restored orders count against the risk limits and keep their IDs taken, as when accepted
+        for order in restored.iter() {
+            self.risk.on_order_accepted(order);
+            self.idempotency.on_order_accepted(order);
+            self.tenants.on_order_accepted(order);
+        }
+        Ok(reports)
+    }
+
+    /// Publish a message-to-trade ratio event on the update stream, it is not sequenced by the orderbook
+    fn publish_compliance(&self, symbol: Symbol, event: ComplianceEvent) {
+        self.bus.publish(OrderbookUpdate {
+            symbol,
+            update_type: OrderbookUpdateType::Compliance,
+            timestamp: self.clock.now(),
+            compliance: Some(event),
+            ..Default::default()
+        });
+    }
+
+    /// Save the orders, trades and status transitions of every orderbook from now on
+    ///
+    /// #Parameters
+    /// * 'persistence' - The storage, called with each update before it is published
+    pub fn set_persistence(&mut self, persistence: Arc<dyn Persistence>) {
+        self.persistence = Some(persistence);
+    }
+
+    /// Statistics of the engine: orders accepted and rejected, trades, matched volume, cancels
+    /// and match latencies, globally and per symbol, plus the depth of the update queues
+    ///
+    /// #Returns
+    /// * EngineMetrics - A copy of the counters
+    pub fn metrics(&self) -> EngineMetrics {
+        EngineMetrics {
+            channel_depth: self.rx.len(),
+            subscriber_depth: self.bus.max_queued(),
+            ..self.metrics_recorder.snapshot()
+        }
+    }
+
+    /// Export the engine counters to a Prometheus registry
+    ///
+    /// #Parameters
+    /// * 'registry' - The registry scraped by Prometheus
+    #[cfg(feature = "metrics")]
+    pub fn export_metrics(&self, registry: &prometheus::Registry) -> Result<(), Error> {
+        self.metrics_recorder.export(registry)
+    }
+
+    /// Subscribe to every update published from now on, using the manager backpressure settings
+    ///
+    /// #Returns
+    /// * Subscription - The receiving end of the subscription
+    pub fn subscribe_updates(&self) -> Subscription {
+        self.subscribe_updates_with(self.subscription_capacity, self.overflow_policy)
+    }
+
+    /// Subscribe to every update published from now on
+    ///
+    /// #Parameters
+    /// * 'capacity' - The maximum number of queued updates, None for an unbounded queue
+    /// * 'policy' - What to do when an update is published to a full queue
+    pub fn subscribe_updates_with(
+        &self,
+        capacity: Option<usize>,
+        policy: OverflowPolicy,
+    ) -> Subscription {
+        self.dispatch();
+        self.bus.subscribe_with(capacity, policy)
+    }
+
+    /// Build a subscription filtered by symbols, update types and user
+    ///
+    /// #Returns
+    /// * SubscriptionBuilder - The builder, call `stream` on it to subscribe
+    pub fn subscribe(&self) -> SubscriptionBuilder<'_> {
+        SubscriptionBuilder::new(self)
+    }
+
     /// Create a new orderbook with a symbol
     ///
     /// Parameters
     /// * 'symbol' : The symbol ID the new orderbook will be in
-    pub fn new_orderbook<'a>(&mut self, symbol: u128) {
+    pub fn new_orderbook(&mut self, symbol: Symbol) {
         let exist = self.get_orderbook(symbol).is_ok();
-        assert!(exist == false, "the orderbook already exist");
+        assert!(!exist, "the orderbook already exist");
         // Todo!("assert or something else?")
-        let orderbook = Orderbook::new(symbol, self.tx.clone());
+        let mut orderbook = Orderbook::new(symbol, self.tx.clone());
+        orderbook.clock = self.clock.clone();
+        orderbook.dead_letters = self.dead_letters.clone();
         self.orderbooks.insert(symbol, orderbook);
     }
 
-    /// Add an order to the orderbook
+    /// Create a new orderbook with a symbol, matching its orders with the given algorithm
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID the new orderbook will be in
+    /// * 'matcher' : The matching algorithm of the orderbook
+    pub fn new_orderbook_with_matcher(
+        &mut self,
+        symbol: Symbol,
+        matcher: Box<dyn MatchingAlgorithm>,
+    ) {
+        assert!(
+            !self.orderbooks.contains_key(&symbol),
+            "the orderbook already exist"
+        );
+        let mut orderbook = Orderbook::with_matcher(symbol, self.tx.clone(), matcher);
+        orderbook.clock = self.clock.clone();
+        orderbook.dead_letters = self.dead_letters.clone();
+        self.orderbooks.insert(symbol, orderbook);
+    }
+
+    /// Create a new orderbook with a symbol, behaving as configured
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID the new orderbook will be in
+    /// * 'config' : The tick and lot sizes, fees, self-trade prevention, limits and matching algorithm of the orderbook
+    pub fn new_orderbook_with_config(&mut self, symbol: Symbol, config: OrderbookConfig) {
+        assert!(
+            !self.orderbooks.contains_key(&symbol),
+            "the orderbook already exist"
+        );
+        let mut orderbook = Orderbook::with_config(symbol, self.tx.clone(), config);
+        orderbook.clock = self.clock.clone();
+        orderbook.dead_letters = self.dead_letters.clone();
+        self.orderbooks.insert(symbol, orderbook);
+    }
+
+    /// Remove an orderbook, its orders are cancelled and a Delisted update is published
     ///
     /// Parameters
     /// * 'symbol' : The symbol ID
-    pub fn add_order<'a>(&mut self, order: Order) -> Result<(), Error> {
-        if let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) {
-            orderbook.add_order(order);
-            return Ok(());
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn remove_orderbook(&mut self, symbol: Symbol) -> Result<Vec<Order>, Error> {
+        if let Some(mut orderbook) = self.orderbooks.remove(&symbol) {
+            self.mirrors.remove(&symbol);
+            let cancelled = orderbook.delist();
+            self.dispatch();
+            self.views.remove(symbol);
+            self.tenants.release_book(symbol);
+            return Ok(cancelled);
         }
         Err(Error::new(
             std::io::ErrorKind::NotFound,
@@ -58,47 +597,159 @@ impl OrderbooksManager {
         ))
     }
 
-    /// Amend an order price in the orderbook
+    /// Move the resting orders of an orderbook to another one in a single operation, for a corporate
+    /// action such as a rename or a redenomination of the symbol. The prices are multiplied by the
+    /// adjustment and the quantities divided by it so that the notional of the orders does not change,
+    /// and the orders keep their time priority. Each order publishes a Migrated update in its former
+    /// orderbook then a Place update in the new one. The balances of the accounts are left as they are
     ///
     /// Parameters
-    /// * 'symbol' : The symbol ID
-    /// * 'order_id': The order ID to ammend
-    /// * 'price': The new price of the order
-    /// * 'side': The order side
-    pub fn amend_order_price<'a>(
+    /// * 'from_symbol' : The symbol the orders leave
+    /// * 'to_symbol' : The symbol the orders move to, its orderbook must be empty
+    /// * 'price_adjustment' : The factor applied to the prices, 1 for a rename, 0.5 for a 2-for-1 split
+    ///
+    /// #Returns
+    /// * Vec<Order> - The migrated orders in their arrival order, an error moving none of them if the
+    ///   new orderbook cannot take one of them
+    pub fn migrate_orders(
         &mut self,
-        symbol: u128,
-        order_id: u128,
-        price: f64,
-        side: OrderSide,
-    ) -> Result<(), Error> {
-        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
-            orderbook.amend_order_price(order_id, price, side);
-            return Ok(());
+        from_symbol: Symbol,
+        to_symbol: Symbol,
+        price_adjustment: f64,
+    ) -> Result<Vec<Order>, Error> {
+        if !(price_adjustment.is_finite() && price_adjustment > 0.0) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Price adjustment must be positive",
+            ));
         }
-        Err(Error::new(
-            std::io::ErrorKind::NotFound,
-            "Orderbook not found",
-        ))
+        if from_symbol == to_symbol {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Orders cannot migrate to their own orderbook",
+            ));
+        }
+        let (Some(from), Some(to)) = (
+            self.orderbooks.get(&from_symbol),
+            self.orderbooks.get(&to_symbol),
+        ) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        self.check_state(to_symbol, OrderbookState::accepts_orders)?;
+        if to.bids.len() + to.asks.len() > 0 {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Orders can only migrate to an empty orderbook",
+            ));
+        }
+        if to
+            .max_orders
+            .is_some_and(|max_orders| from.bids.len() + from.asks.len() > max_orders)
+        {
+            return Err(Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Orderbook is full",
+            ));
+        }
+        for order in from.bids.iter_ref().chain(from.asks.iter_ref()) {
+            let migrated = order.migrated(to_symbol, price_adjustment);
+            let on_tick = match (migrated.price, to.tick_size) {
+                (Some(price), Some(tick_size)) => is_multiple_of(price, tick_size),
+                _ => true,
+            };
+            let on_lot = to.lot_size.is_none_or(|lot_size| {
+                is_multiple_of(migrated.quantity + migrated.hidden_quantity, lot_size)
+            });
+            if !(on_tick && on_lot) {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Order {} does not fit the tick and lot sizes once migrated",
+                        order.id
+                    ),
+                ));
+            }
+        }
+        let mut migrated = Vec::new();
+        if let Some(from) = self.orderbooks.get_mut(&from_symbol) {
+            migrated = from.migrate_out(|order| order.migrated(to_symbol, price_adjustment));
+        }
+        let orders = migrated.iter().map(|(order, _)| *order).collect();
+        if let Some(to) = self.orderbooks.get_mut(&to_symbol) {
+            to.migrate_in(migrated);
+        }
+        self.dispatch();
+        Ok(orders)
     }
 
-    /// Amend an order quanitty in the orderbook
+    /// List the symbols of the active orderbooks
     ///
-    /// #Parameters
+    /// #Returns
+    /// * Vec<Symbol> - The symbols, sorted
+    pub fn list_symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = self.orderbooks.keys().copied().collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// Add an order to the orderbook
+    ///
+    /// Parameters
     /// * 'symbol' : The symbol ID
-    /// * 'order_id': The order ID to ammend
-    /// * 'quantity': The new quantity of the order
-    /// * 'side': The order side
-    pub fn amend_order_quantity<'a>(
+    ///
+    /// #Returns
+    /// * OrderAck - The status of the order once matched, an error if the order is invalid
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(order_id = %order.id, symbol = %order.symbol),
+            err
+        )
+    )]
+    pub fn add_order(&mut self, order: Order) -> Result<OrderAck, Error> {
+        self.add_order_received(order, self.clock.monotonic())
+    }
+
+    /// Add an order stamped when it entered the engine, e.g. by a gateway or a command queue, so that
+    /// the time it waited is measured with the time it spent matching and publishing
+    ///
+    /// Parameters
+    /// * 'order' : The order
+    /// * 'received_at' : The monotonic time of the clock of the manager the order was received at
+    pub fn add_order_received(
         &mut self,
-        symbol: u128,
-        order_id: u128,
-        quantity: f64,
-        side: OrderSide,
-    ) -> Result<(), Error> {
-        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
-            orderbook.amend_order_quantity(order_id, quantity, side);
-            return Ok(());
+        order: Order,
+        received_at: u64,
+    ) -> Result<OrderAck, Error> {
+        self.admit(&order)?;
+        if self.orderbooks.contains_key(&order.symbol) {
+            let handed_at = self.clock.monotonic();
+            let matching = self.submit(order);
+            let mut ack = OrderAck::new(&order, OrderStatus::Open);
+            self.dispatch_with(|update| ack.apply(update));
+            let latency = OrderLatency {
+                queued: handed_at.saturating_sub(received_at),
+                matching,
+                publishing: self
+                    .clock
+                    .monotonic()
+                    .saturating_sub(handed_at)
+                    .saturating_sub(matching),
+            };
+            self.metrics_recorder.on_latency(order.symbol, &latency);
+            if self.ack_latency {
+                ack.latency = Some(latency);
+            }
+            ack.close_market_order(&order, self.dust_threshold(order.symbol));
+            self.release_market_order(&order);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(status = %ack.status, filled_quantity = ack.filled_quantity, "order applied");
+            return Ok(ack);
         }
         Err(Error::new(
             std::io::ErrorKind::NotFound,
@@ -106,327 +757,3605 @@ impl OrderbooksManager {
         ))
     }
 
-    /// Cancel the order by order_id
+    /// Add an order sent by a client, e.g. through a gateway. A retry of an order accepted with the same
+    /// client order ID within the dedup window of the idempotency guard is rejected instead of booked twice
     ///
-    /// #Parameters
-    /// * 'order_id' - The order ID
-    /// * 'symbol' - The symbol ID
-    /// * 'side'- The order side
-    pub fn cancel_order<'a>(
+    /// Parameters
+    /// * 'order' : The order
+    /// * 'metadata' : The tags of the order, None for no tags
+    pub fn add_client_order(
         &mut self,
-        order_id: u128,
-        symbol: u128,
-        side: OrderSide,
-    ) -> Result<(), Error> {
-        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
-            orderbook.cancel_order(order_id, side);
-            return Ok(());
+        order: Order,
+        metadata: Option<Metadata>,
+    ) -> Result<OrderAck, Error> {
+        let now = self.clock.monotonic();
+        self.idempotency.check_client_order(&order, now)?;
+        let ack = match metadata {
+            Some(metadata) => self.add_order_with_metadata(order, metadata)?,
+            None => self.add_order(order)?,
+        };
+        self.idempotency.on_client_order_accepted(&order, now);
+        Ok(ack)
+    }
+
+    /// Add an order tagged with metadata, carried into its updates and its trades
+    ///
+    /// Parameters
+    /// * 'order' : The order
+    /// * 'metadata' : The tags of the order, e.g. client tags, algo IDs or regulatory flags
+    pub fn add_order_with_metadata(
+        &mut self,
+        order: Order,
+        metadata: Metadata,
+    ) -> Result<OrderAck, Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) {
+            orderbook.set_metadata(order.id, metadata);
+        }
+        let ack = self.add_order(order);
+        if ack.is_err() {
+            if let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) {
+                orderbook.remove_metadata(order.id);
+            }
         }
-        Err(Error::new(std::io::ErrorKind::NotFound, "Order not found"))
+        ack
     }
 
-    /// Get an orderbook summary by symbol
+    /// Add an order held by its orderbook until the book meets a condition, e.g. the mid price reaching
+    /// a level, it is then added within the book change which met the condition
     ///
     /// Parameters
-    /// * 'symbol' - The symbol ID
-    pub fn get_orderbook(&self, symbol: u128) -> Result<OrderBookSummarized, Error> {
-        if let Some(orderbook) = self.orderbooks.get(&symbol) {
-            let summary = orderbook.summarize_orderbook_per_price_level();
-            let bids_volume: f64 = summary.0.iter().map(|b| b.1).sum();
-            let asks_volume: f64 = summary.2.iter().map(|a| a.1).sum();
-            let bids = summary
-                .0
-                .iter()
-                .map(|b| BidAskSummarize::new(b.0, b.1, b.2, b.1 / bids_volume * 100.0))
-                .collect();
-            let asks = summary
-                .2
-                .iter()
-                .map(|a| BidAskSummarize::new(a.0, a.1, a.2, a.1 / asks_volume * 100.0))
-                .collect();
-            let summary_back = OrderBookSummarized {
-                bids,
-                asks,
-                mid_price: summary.1,
-            };
-            return Ok(summary_back);
+    /// * 'order' : The order
+    /// * 'condition' : The condition activating the order
+    ///
+    /// #Returns
+    /// * OrderAck - Pending while the order is held, its status once matched if the condition is
+    ///   already met, an error if the order is invalid
+    pub fn add_conditional_order(
+        &mut self,
+        order: Order,
+        condition: BookCondition,
+    ) -> Result<OrderAck, Error> {
+        self.admit(&order)?;
+        let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        // a held order counts as open for the risk limits
+        self.risk.on_order_accepted(&order);
+        self.metrics_recorder.on_accepted(order.symbol);
+        orderbook.add_conditional_order(order, condition);
+        let held = orderbook
+            .conditional_orders()
+            .iter()
+            .any(|(_, o)| o.id == order.id);
+        if held {
+            self.dispatch();
+            return Ok(OrderAck::new(&order, OrderStatus::Pending));
         }
-        Err(Error::new(
-            std::io::ErrorKind::NotFound,
-            "Orderbook not found",
-        ))
+        let mut ack = OrderAck::new(&order, OrderStatus::Open);
+        self.dispatch_with(|update| ack.apply(update));
+        ack.close_market_order(&order, self.dust_threshold(order.symbol));
+        self.release_market_order(&order);
+        Ok(ack)
     }
 
-    /// Listen to new orders
-    pub fn listen_new_orders<'a>(&'a self) -> impl Stream<Item = Order> {
-        let rx = self.rx.clone();
-        stream! {
+    /// Cancel a conditional order held until its condition is met
+    ///
+    /// Parameters
+    /// * 'order_id' : The order ID
+    /// * 'symbol' : The symbol ID
+    ///
+    /// #Returns
+    /// * Order - The cancelled order, an error if no order with this ID is held
+    pub fn cancel_conditional_order(
+        &mut self,
+        order_id: OrderId,
+        symbol: Symbol,
+    ) -> Result<Order, Error> {
+        let cancelled = self
+            .orderbooks
+            .get_mut(&symbol)
+            .and_then(|orderbook| orderbook.cancel_conditional_order(order_id));
+        self.dispatch();
+        cancelled
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Conditional order not found"))
+    }
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::New => {
-                                if let Some(order) = orderbook_update.order {
-                                    yield order;
-                                }
-                            }
-                            _ => {}
-                        }
+    /// Add a batch of orders, the updates are dispatched once the whole batch is applied
+    ///
+    /// Parameters
+    /// * 'orders' : The orders, applied in the given order
+    /// * 'mode' : Atomic to reject the whole batch if one order is invalid or cannot be admitted along with the
+    ///   previous ones, BestEffort to reject only the invalid orders
+    ///
+    /// #Returns
+    /// * Vec<Result<(), Error>> - The result of each order, the error of the first rejected order in Atomic mode.
+    ///   An Atomic batch reaches the orderbooks only once all its orders are admitted, their reservations,
+    ///   rate limits and risk limits counted together.
+    pub fn add_orders(
+        &mut self,
+        orders: Vec<Order>,
+        mode: BatchMode,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        if mode == BatchMode::Atomic {
+            let mut order_ids = HashSet::new();
+            for order in orders.iter() {
+                self.validate_order(order)?;
+                if !order_ids.insert(order.id) {
+                    return Err(Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        "Duplicate order ID",
+                    ));
+                }
+            }
+            for (index, order) in orders.iter().enumerate() {
+                // The risk limits of the next orders count the admitted ones
+                let admitted = self
+                    .admit(order)
+                    .map(|_| self.risk.on_order_accepted(order));
+                if let Err(error) = admitted {
+                    for order in orders[..index].iter() {
+                        self.revoke(order);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        let results = orders
+            .iter()
+            .map(|&order| {
+                if mode == BatchMode::BestEffort {
+                    self.admit(&order)?;
+                }
+                self.submit(order);
+                Ok(())
+            })
+            .collect();
+        self.dispatch();
+        for order in orders.iter() {
+            self.release_market_order(order);
+        }
+        Ok(results)
+    }
+
+    /// Submit a basket of orders across symbols, every order is validated first and the whole basket
+    /// is rejected if one of them is invalid. The legs are followed by the basket events.
+    ///
+    /// #Parameters
+    /// * 'basket_id' - The basket ID, unique among the live baskets
+    /// * 'orders' - The legs, applied in the given order
+    ///
+    /// #Returns
+    /// * Vec<OrderAck> - The acknowledgement of each leg, the error of the first rejected leg.
+    ///   The legs already placed when a leg is rejected by its balance are cancelled.
+    pub fn submit_basket(
+        &mut self,
+        basket_id: u128,
+        orders: Vec<Order>,
+    ) -> Result<Vec<OrderAck>, Error> {
+        if orders.is_empty() {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Basket has no orders",
+            ));
+        }
+        for order in orders.iter() {
+            self.validate_order(order)?;
+        }
+        if !self.baskets.register(basket_id, &orders) {
+            return Err(Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Basket already exists",
+            ));
+        }
+        let mut acks = Vec::with_capacity(orders.len());
+        for (index, order) in orders.iter().enumerate() {
+            match self.add_order(*order) {
+                Ok(ack) => acks.push(ack),
+                Err(error) => {
+                    // The rejected leg and the next ones never reach their orderbook
+                    for order in orders[index..].iter() {
+                        self.baskets.drop_leg(order);
+                    }
+                    if self.baskets.get(basket_id).is_some() {
+                        self.cancel_basket(basket_id)?;
                     }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(acks)
+    }
 
+    /// Cancel the remaining legs of a basket at once, the updates are dispatched once they are all cancelled
+    ///
+    /// #Parameters
+    /// * 'basket_id' - The basket ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled legs, an error if the basket is unknown or done, or if an orderbook
+    ///   of its remaining legs doesn't accept cancels, in which case no leg is cancelled
+    pub fn cancel_basket(&mut self, basket_id: u128) -> Result<Vec<Order>, Error> {
+        let basket = self
+            .baskets
+            .get(basket_id)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Basket not found"))?;
+        for leg in basket.open_legs() {
+            self.check_state(leg.symbol, OrderbookState::accepts_cancels)?;
+        }
+        for leg in basket.open_legs() {
+            if let Some(orderbook) = self.orderbooks.get_mut(&leg.symbol) {
+                let _ = orderbook.cancel_order(leg.order_id, leg.side);
+            }
         }
+        let mut cancelled = Vec::new();
+        self.dispatch_with(|update| {
+            if let Some(order) = update.order.filter(|o| {
+                update.update_type == OrderbookUpdateType::Cancel
+                    && basket.legs.iter().any(|leg| leg.order_id == o.id)
+            }) {
+                cancelled.push(order);
+            }
+        });
+        Ok(cancelled)
     }
 
-    /// Listen to placed orders
-    pub fn listen_placed_orders<'a>(&'a self) -> impl Stream<Item = Order> {
-        let rx = self.rx.clone();
-        stream! {
+    /// Current state of a live basket, the baskets are forgotten once all their legs are done
+    ///
+    /// #Parameters
+    /// * 'basket_id' - The basket ID
+    pub fn get_basket(&self, basket_id: u128) -> Result<Basket, Error> {
+        self.baskets
+            .get(basket_id)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Basket not found"))
+    }
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Place => {
-                                if let Some(order) = orderbook_update.order {
-                                    yield order;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
+    /// Receive every basket event emitted from now on
+    pub fn subscribe_basket_events(&self) -> Receiver<BasketEvent> {
+        self.baskets.subscribe()
+    }
+
+    /// Route the orders of an instrument to several orderbooks trading it, e.g. different venues or fee tiers
+    ///
+    /// #Parameters
+    /// * 'instrument' - The symbol ID of the instrument, which must not be an orderbook
+    /// * 'symbols' - The orderbooks trading the instrument, in order of preference on a tie
+    pub fn add_route(&mut self, instrument: Symbol, symbols: Vec<Symbol>) -> Result<(), Error> {
+        if symbols.is_empty() {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Route has no orderbooks",
+            ));
+        }
+        if symbols
+            .iter()
+            .any(|symbol| !self.orderbooks.contains_key(symbol))
+        {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        }
+        if self.orderbooks.contains_key(&instrument) || !self.router.add_route(instrument, symbols)
+        {
+            return Err(Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Symbol already exists",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Split an order on a routed instrument across its orderbooks at the lowest cost, fees included,
+    /// see `router::plan`. Each child carries the parent order ID as client order ID and the fills of
+    /// the children are aggregated into the parent events.
+    ///
+    /// #Parameters
+    /// * 'order' - The parent order, its symbol is the instrument
+    ///
+    /// #Returns
+    /// * Vec<OrderAck> - The acknowledgement of each child, an error if the instrument has no route,
+    ///   if no book can take the order, or if a child is rejected, in which case the children
+    ///   already placed are cancelled
+    pub fn route_order(&mut self, order: Order) -> Result<Vec<OrderAck>, Error> {
+        let symbols = self
+            .router
+            .route(order.symbol)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Route not found"))?;
+        if !(order.quantity.is_finite() && order.quantity > 0.0) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Order quantity must be positive",
+            ));
+        }
+        let books: Vec<&Orderbook> = symbols
+            .iter()
+            .filter_map(|symbol| self.orderbooks.get(symbol))
+            .collect();
+        let children: Vec<Order> = plan(&order, &books)
+            .into_iter()
+            .map(|allocation| Order {
+                client_order_id: Some(order.id.into()),
+                expires_at: order.expires_at,
+                time_in_force: order.time_in_force,
+                ..Order::new(
+                    order.user_id,
+                    allocation.symbol,
+                    order.side,
+                    allocation.quantity,
+                    allocation.price,
+                    order.order_type,
+                )
+            })
+            .collect();
+        if children.is_empty() {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No liquidity to route the order to",
+            ));
+        }
+        for child in children.iter() {
+            self.validate_order(child)?;
+        }
+        if !self.router.register(&order, &children) {
+            return Err(Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Parent order already exists",
+            ));
+        }
+        let mut acks = Vec::with_capacity(children.len());
+        for (index, child) in children.iter().enumerate() {
+            match self.add_order(*child) {
+                Ok(ack) => acks.push(ack),
+                Err(error) => {
+                    // The rejected child and the next ones never reach their orderbook
+                    for child in children[index..].iter() {
+                        self.router.drop_child(child);
+                    }
+                    if self.router.get(order.id).is_some() {
+                        self.cancel_routed_order(order.id)?;
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(acks)
+    }
+
+    /// Cancel the remaining children of a parent order at once
+    ///
+    /// #Parameters
+    /// * 'parent_id' - The ID of the parent order
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled children, an error if the parent is unknown or done, or if an
+    ///   orderbook of its remaining children doesn't accept cancels, in which case no child is cancelled
+    pub fn cancel_routed_order(&mut self, parent_id: OrderId) -> Result<Vec<Order>, Error> {
+        let parent = self
+            .router
+            .get(parent_id)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Parent order not found"))?;
+        for child in parent.open_children() {
+            self.check_state(child.symbol, OrderbookState::accepts_cancels)?;
+        }
+        for child in parent.open_children() {
+            if let Some(orderbook) = self.orderbooks.get_mut(&child.symbol) {
+                let _ = orderbook.cancel_order(child.order_id, parent.side);
+            }
+        }
+        let mut cancelled = Vec::new();
+        self.dispatch_with(|update| {
+            if let Some(order) = update.order.filter(|o| {
+                update.update_type == OrderbookUpdateType::Cancel
+                    && parent.children.iter().any(|child| child.order_id == o.id)
+            }) {
+                cancelled.push(order);
+            }
+        });
+        Ok(cancelled)
+    }
+
+    /// Current state of a live parent order, the parents are forgotten once all their children are done
+    ///
+    /// #Parameters
+    /// * 'parent_id' - The ID of the parent order
+    pub fn get_routed_order(&self, parent_id: OrderId) -> Result<ParentOrder, Error> {
+        self.router
+            .get(parent_id)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Parent order not found"))
+    }
+
+    /// Receive every parent order event emitted from now on
+    pub fn subscribe_parent_events(&self) -> Receiver<ParentEvent> {
+        self.router.subscribe()
+    }
+
+    /// Define a spread instrument trading leg A against leg B, its orders are matched against the prices
+    /// implied by the orderbooks of the legs
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID of the spread, which must not be an orderbook
+    /// * 'leg_a' - The symbol ID of the leg bought with the spread
+    /// * 'leg_b' - The symbol ID of the leg sold with the spread
+    pub fn define_spread(
+        &mut self,
+        symbol: Symbol,
+        leg_a: Symbol,
+        leg_b: Symbol,
+    ) -> Result<(), Error> {
+        if self.orderbooks.contains_key(&symbol) || self.spreads.contains_key(&symbol) {
+            return Err(Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Symbol already exists",
+            ));
+        }
+        if leg_a == leg_b {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Spread legs must be different",
+            ));
+        }
+        if !self.orderbooks.contains_key(&leg_a) || !self.orderbooks.contains_key(&leg_b) {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        }
+        self.spreads.insert(
+            symbol,
+            SpreadDefinition {
+                symbol,
+                leg_a,
+                leg_b,
+            },
+        );
+        Ok(())
+    }
+
+    /// Prices of a spread implied by the best levels of the orderbooks of its legs
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID of the spread
+    ///
+    /// #Returns
+    /// * (Option<ImpliedPrice>, Option<ImpliedPrice>) - The implied bid and ask
+    pub fn implied_prices(
+        &self,
+        symbol: Symbol,
+    ) -> Result<(Option<ImpliedPrice>, Option<ImpliedPrice>), Error> {
+        let spread = self
+            .spreads
+            .get(&symbol)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Spread not found"))?;
+        match (
+            self.orderbooks.get(&spread.leg_a),
+            self.orderbooks.get(&spread.leg_b),
+        ) {
+            (Some(leg_a), Some(leg_b)) => Ok(spread.implied(leg_a, leg_b)),
+            _ => Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            )),
+        }
+    }
+
+    /// Match a spread order against the implied prices, each execution sends an order to each leg at
+    /// the price of its best level. The leg orders carry the spread order ID as client order ID.
+    /// Both legs are sized to what each can take, leg A is sent first and leg B for what leg A traded.
+    /// Spread orders are immediate-or-cancel: what can't be executed at once is cancelled.
+    ///
+    /// #Parameters
+    /// * 'order' - The spread order, its price is the spread price limit, None for a market order
+    ///
+    /// #Returns
+    /// * SpreadAck - The acknowledgement of the spread order and the executions of its legs with the
+    ///   quantity each leg traded, an error if the legs of the first execution could not be sent
+    pub fn add_spread_order(&mut self, order: Order) -> Result<SpreadAck, Error> {
+        let spread = *self
+            .spreads
+            .get(&order.symbol)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Spread not found"))?;
+        if !(order.quantity.is_finite() && order.quantity > 0.0) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Order quantity must be positive",
+            ));
+        }
+        let (a_side, b_side) = SpreadDefinition::leg_sides(order.side);
+        let mut result = SpreadAck {
+            ack: OrderAck::new(&order, OrderStatus::Open),
+            executions: Vec::new(),
+        };
+        let mut remaining = order.quantity;
+        while remaining > 0.0 {
+            let (bid, ask) = self.implied_prices(order.symbol)?;
+            let implied = match order.side {
+                OrderSide::Buy => ask.filter(|ask| order.price.is_none_or(|p| ask.price <= p)),
+                OrderSide::Sell => bid.filter(|bid| order.price.is_none_or(|p| bid.price >= p)),
+            };
+            let Some(implied) = implied else {
+                break;
+            };
+            // Both legs are sized to what each can take before either is sent
+            let quantity = remaining
+                .min(implied.quantity)
+                .min(takeable(
+                    &self.orderbooks[&spread.leg_a],
+                    a_side,
+                    implied.leg_a_price,
+                    order.user_id,
+                ))
+                .min(takeable(
+                    &self.orderbooks[&spread.leg_b],
+                    b_side,
+                    implied.leg_b_price,
+                    order.user_id,
+                ));
+            if quantity <= 0.0 {
+                break;
+            }
+            let leg = |symbol, side, price| {
+                Order::new(
+                    order.user_id,
+                    symbol,
+                    side,
+                    quantity,
+                    Some(price),
+                    OrderType::Limit,
+                )
+                .with_client_order_id(order.id.into())
+            };
+            let leg_a = leg(spread.leg_a, a_side, implied.leg_a_price);
+            let leg_b = leg(spread.leg_b, b_side, implied.leg_b_price);
+            // Both legs are checked before either is sent
+            if let Err(error) = self
+                .validate_order(&leg_a)
+                .and_then(|_| self.validate_order(&leg_b))
+            {
+                if result.executions.is_empty() {
+                    return Err(error);
+                }
+                break;
+            }
+            let a_filled = match self.add_order(leg_a) {
+                Ok(ack) => ack.filled_quantity,
+                Err(error) if result.executions.is_empty() => return Err(error),
+                Err(_) => break,
+            };
+            self.cancel_leg_remainder(&leg_a);
+            if a_filled <= 0.0 {
+                break;
+            }
+            // Leg B only takes what leg A traded, whatever happens to it leg A is reported
+            let leg_b = Order {
+                quantity: a_filled,
+                ..leg_b
+            };
+            let b_filled = self.add_order(leg_b).map_or(0.0, |ack| ack.filled_quantity);
+            self.cancel_leg_remainder(&leg_b);
+            result.executions.push(SpreadExecution {
+                quantity: b_filled,
+                price: implied.price,
+                leg_a_order_id: leg_a.id,
+                leg_a_price: implied.leg_a_price,
+                leg_a_quantity: a_filled,
+                leg_b_order_id: leg_b.id,
+                leg_b_price: implied.leg_b_price,
+                leg_b_quantity: b_filled,
+            });
+            remaining -= b_filled;
+            if b_filled < a_filled {
+                result.ack.reject_reason = Some(String::from("Leg B traded less than leg A"));
+                break;
+            }
+            if a_filled < quantity {
+                break;
+            }
+        }
+        result.ack.filled_quantity = order.quantity - remaining;
+        result.ack.status = if remaining <= 0.0 {
+            OrderStatus::Filled
+        } else if result.executions.is_empty() {
+            result.ack.reject_reason = Some(String::from("No implied liquidity"));
+            OrderStatus::Cancelled
+        } else if result.ack.filled_quantity <= 0.0 {
+            OrderStatus::Cancelled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+        Ok(result)
+    }
+
+    /// Cancel what a spread leg left in its book, a leg blocked by a minimum fill or the self-trade
+    /// prevention doesn't rest. A leg the engine already removed is left alone.
+    fn cancel_leg_remainder(&mut self, leg: &Order) {
+        let resting = self
+            .orderbooks
+            .get(&leg.symbol)
+            .is_some_and(|b| b.contains_order(leg.id, leg.side));
+        if resting {
+            // the orderbook accepted the leg, it accepts its cancel
+            let _ = self.cancel_order(leg.id, leg.symbol, leg.side);
+        }
+    }
+
+    /// Throttle, validate an order and reserve its balance, a rejected order is counted in the metrics
+    fn admit(&self, order: &Order) -> Result<(), Error> {
+        let symbol = Some(order.symbol).filter(|s| self.orderbooks.contains_key(s));
+        if self.shut_down {
+            self.metrics_recorder.on_rejected(symbol);
+            return Err(Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "Engine shut down",
+            ));
+        }
+        if let Err(error) = self.kill_switches.check_order(order) {
+            self.metrics_recorder.on_rejected(symbol);
+            return Err(error);
+        }
+        if let Err(error) = self
+            .rate_limiter
+            .acquire(order.user_id, self.clock.monotonic())
+        {
+            self.metrics_recorder.on_rejected(symbol);
+            self.metrics_recorder.on_rate_limited(symbol);
+            return Err(error);
+        }
+        if let Some(event) = self
+            .message_ratios
+            .evaluate(order.user_id, self.clock.monotonic())
+        {
+            self.publish_compliance(order.symbol, event);
+        }
+        if self.message_ratios.is_throttled(order.user_id) {
+            self.metrics_recorder.on_rejected(symbol);
+            return Err(Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Message to trade ratio exceeded",
+            ));
+        }
+        if let Err(error) = self.risk.check_notional(order) {
+            self.metrics_recorder.on_rejected(symbol);
+            self.metrics_recorder.on_notional_throttled(symbol);
+            return Err(error);
+        }
+        let admitted = self.validate_order(order).and_then(|_| self.reserve(order));
+        match admitted {
+            Ok(()) => {
+                self.idempotency.on_order_accepted(order);
+                self.tenants.on_order_accepted(order);
+            }
+            Err(_) => self.metrics_recorder.on_rejected(symbol),
+        }
+        admitted
+    }
+
+    /// Undo the admission of an order which never reached its orderbook, it is counted as rejected
+    fn revoke(&self, order: &Order) {
+        if let Some(accounts) = &self.accounts {
+            accounts.release(order.id);
+        }
+        self.risk.on_order_revoked(order);
+        self.idempotency.on_order_revoked(order.id);
+        self.tenants.on_order_revoked(order.id);
+        self.metrics_recorder.on_rejected(Some(order.symbol));
+    }
+
+    /// Hand an admitted order to its orderbook, timing the matching
+    ///
+    /// #Returns
+    /// * u64 - The time the orderbook took to match the order, in nanoseconds
+    fn submit(&mut self, order: Order) -> u64 {
+        let mut latency = 0;
+        if let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) {
+            self.risk.on_order_accepted(&order);
+            self.metrics_recorder.on_accepted(order.symbol);
+            let start = self.clock.monotonic();
+            orderbook.add_order(order);
+            latency = self.clock.monotonic().saturating_sub(start);
+            self.metrics_recorder.on_match(order.symbol, latency);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(latency_ns = latency, "order matched");
+        }
+        latency
+    }
+
+    /// Reserve the balance an order pays with, when the accounts are enabled
+    fn reserve(&self, order: &Order) -> Result<(), Error> {
+        match &self.accounts {
+            Some(accounts) => accounts.reserve(order),
+            None => Ok(()),
+        }
+    }
+
+    /// Resize the reservation of a resting order to its amended price and quantity before the
+    /// orderbook is changed, when the accounts are enabled
+    fn reserve_amend(&self, amended: &Order) -> Result<(), Error> {
+        match &self.accounts {
+            Some(accounts) => accounts.amend(amended),
+            None => Ok(()),
+        }
+    }
+
+    /// Resting order of an orderbook, on either side when the side is not known
+    fn resting_order(
+        &self,
+        symbol: Symbol,
+        order_id: OrderId,
+        side: Option<OrderSide>,
+    ) -> Option<Order> {
+        let orderbook = self.orderbooks.get(&symbol)?;
+        match side {
+            Some(side) => orderbook.get_order(order_id, side),
+            None => orderbook
+                .get_order(order_id, OrderSide::Buy)
+                .or_else(|| orderbook.get_order(order_id, OrderSide::Sell)),
+        }
+    }
+
+    /// Remainder below which the orderbook of a symbol fills an order
+    fn dust_threshold(&self, symbol: Symbol) -> Option<f64> {
+        self.orderbooks
+            .get(&symbol)
+            .and_then(|orderbook| orderbook.dust_threshold)
+    }
+
+    /// A market order only rests in the book when its remainder is converted to a limit order,
+    /// otherwise what it did not spend is released once it is matched
+    fn release_market_order(&self, order: &Order) {
+        let resting = self
+            .orderbooks
+            .get(&order.symbol)
+            .is_some_and(|b| b.contains_order(order.id, order.side));
+        if order.order_type != OrderType::Market || resting {
+            return;
+        }
+        if let Some(accounts) = &self.accounts {
+            accounts.release(order.id);
+        }
+        self.idempotency
+            .on_order_closed(order.id, self.clock.monotonic());
+    }
+
+    /// Back the orders with the balances of the users, an order is rejected if its user cannot pay for it
+    pub fn enable_accounts(&mut self) -> &Accounts {
+        self.accounts.get_or_insert_with(Accounts::new)
+    }
+
+    /// Get the balances of a user by asset
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    pub fn get_balance(&self, user_id: UserId) -> Result<HashMap<u128, Balance>, Error> {
+        match &self.accounts {
+            Some(accounts) => Ok(accounts.get_balance(user_id)),
+            None => Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Accounts not enabled",
+            )),
+        }
+    }
+
+    /// Get the position of a user on a symbol, built from the trades dispatched by the manager
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * Position - The net quantity, average price and realized PnL, flat if the user never traded the symbol
+    pub fn get_position(&self, user_id: UserId, symbol: Symbol) -> Position {
+        self.positions.position(user_id, symbol)
+    }
+
+    /// Get the trades of a user over every symbol, from the log kept by the manager
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'since' - The time in nanoseconds since UNIX epoch the trades were executed at or after, 0 for the whole log
+    ///
+    /// #Returns
+    /// * Vec<Trade> - The trades, the oldest first
+    pub fn get_user_trades(&self, user_id: UserId, since: u64) -> Vec<Trade> {
+        self.positions.trades(user_id, since)
+    }
+
+    /// Check an order can be added
+    ///
+    /// Parameters
+    /// * 'order' : The order to check
+    pub fn validate_order(&self, order: &Order) -> Result<(), Error> {
+        if !self.orderbooks.contains_key(&order.symbol) {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        }
+        self.symbols.validate(order)?;
+        self.tenants.check_order(order)?;
+        self.check_state(order.symbol, OrderbookState::accepts_orders)?;
+        if order.order_type == OrderType::Market {
+            self.check_state(order.symbol, OrderbookState::matches_orders)?;
+        }
+        // The book moves the orders through their statuses from there
+        if !matches!(order.status, OrderStatus::Open | OrderStatus::Pending) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "New orders must be open or pending",
+            ));
+        }
+        if order.peg_reference.is_some()
+            && (order.order_type != OrderType::Limit || !order.peg_offset.is_finite())
+        {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Pegged orders must be limit orders with a finite offset",
+            ));
+        }
+        self.check_terms(order)?;
+        if order.min_fill_quantity.is_some_and(|minimum| {
+            !(minimum.is_finite()
+                && minimum > 0.0
+                && minimum <= order.quantity + order.hidden_quantity)
+        }) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Minimum fill quantity must be positive and at most the order quantity",
+            ));
+        }
+        let orderbook = &self.orderbooks[&order.symbol];
+        if let Some(max_orders) = orderbook.max_orders {
+            if order.order_type == OrderType::Limit
+                && orderbook.bids.len() + orderbook.asks.len() >= max_orders
+            {
+                return Err(Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "Orderbook is full",
+                ));
+            }
+        }
+        self.idempotency
+            .check_order(order, self.clock.monotonic())?;
+        self.risk.check_order(order)?;
+        match &self.accounts {
+            Some(accounts) => accounts.check_order(order),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the price and quantity of an order, new or amended, against the tick and lot sizes of its orderbook
+    fn check_terms(&self, order: &Order) -> Result<(), Error> {
+        if !(order.quantity.is_finite() && order.quantity > 0.0) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Order quantity must be positive",
+            ));
+        }
+        // A pegged order takes its price from the book, its own price is only a starting point
+        if order.order_type == OrderType::Limit
+            && !(order.peg_reference.is_some() && order.price.is_none())
+            && !order
+                .price
+                .is_some_and(|price| price.is_finite() && price > 0.0)
+        {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Limit order price must be positive",
+            ));
+        }
+        let Some(orderbook) = self.orderbooks.get(&order.symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        if let (Some(tick_size), Some(price)) = (orderbook.tick_size, order.price) {
+            if !is_multiple_of(price, tick_size) {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Order price must be a multiple of the tick size",
+                ));
+            }
+        }
+        if let Some(lot_size) = orderbook.lot_size {
+            if !is_multiple_of(order.quantity + order.hidden_quantity, lot_size) {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Order quantity must be a multiple of the lot size",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate a resting order amended to a new price or quantity before the orderbook is changed, as
+    /// validate_order does for a new order: its terms, the symbol reference data and the risk limits
+    /// in place of the resting version of the order
+    fn validate_amend(&self, amended: &Order) -> Result<(), Error> {
+        self.check_terms(amended)?;
+        self.symbols.validate(amended)?;
+        self.risk.check_notional(amended)?;
+        self.risk.check_order(amended)
+    }
+
+    /// Amend an order price in the orderbook
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'order_id': The order ID to ammend
+    /// * 'price': The new price of the order
+    /// * 'side': The order side
+    ///
+    /// #Returns
+    /// * OrderAck - The status of the order and the quantity executed once the amended order is matched,
+    ///   an error if the order is not in the orderbook
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(symbol = %symbol, order_id = %order_id), err)
+    )]
+    pub fn amend_order_price(
+        &mut self,
+        symbol: Symbol,
+        order_id: OrderId,
+        price: f64,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.check_state(symbol, OrderbookState::accepts_amends)?;
+        if let Some(order) = self.resting_order(symbol, order_id, Some(side)) {
+            let amended = Order {
+                price: Some(price),
+                ..order
+            };
+            self.validate_amend(&amended)?;
+            self.reserve_amend(&amended)?;
+        }
+        let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        let order = orderbook.amend_order_price(order_id, price, side)?;
+        Ok(self.acknowledge_amend(&order))
+    }
+
+    /// Amend an order quanitty in the orderbook
+    ///
+    /// #Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'order_id': The order ID to ammend
+    /// * 'quantity': The new quantity of the order
+    /// * 'side': The order side
+    ///
+    /// #Returns
+    /// * OrderAck - The status of the order and the quantity executed once the amended order is matched,
+    ///   an error if the order is not in the orderbook
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(symbol = %symbol, order_id = %order_id), err)
+    )]
+    pub fn amend_order_quantity(
+        &mut self,
+        symbol: Symbol,
+        order_id: OrderId,
+        quantity: f64,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.check_state(symbol, OrderbookState::accepts_amends)?;
+        if let Some(order) = self.resting_order(symbol, order_id, Some(side)) {
+            let amended = Order { quantity, ..order };
+            self.validate_amend(&amended)?;
+            self.reserve_amend(&amended)?;
+        }
+        let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        let order = orderbook.amend_order_quantity(order_id, quantity, side)?;
+        Ok(self.acknowledge_amend(&order))
+    }
+
+    /// Cancel the order by order_id
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'symbol' - The symbol ID
+    /// * 'side'- The order side
+    ///
+    /// #Returns
+    /// * OrderAck - Cancelled, an error if the order is not in the orderbook
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(symbol = %symbol, order_id = %order_id), err)
+    )]
+    pub fn cancel_order(
+        &mut self,
+        order_id: OrderId,
+        symbol: Symbol,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.check_state(symbol, OrderbookState::accepts_cancels)?;
+        let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        let order = orderbook.cancel_order(order_id, side)?;
+        self.dispatch();
+        let ack = OrderAck::new(&order, OrderStatus::Cancelled);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(status = %ack.status, "cancel applied");
+        Ok(ack)
+    }
+
+    /// Acknowledge an amended order with what happened to it while it was matched
+    fn acknowledge_amend(&self, order: &Order) -> OrderAck {
+        let mut ack = OrderAck::new(order, order.status);
+        self.dispatch_with(|update| ack.apply(update));
+        ack
+    }
+
+    /// Replace an order with a new price and quantity in a single operation
+    ///
+    /// #Parameters
+    /// * 'order_id' - The order ID
+    /// * 'symbol' - The symbol ID
+    /// * 'new_price' - The new price of the order
+    /// * 'new_quantity' - The new quantity of the order
+    ///
+    /// #Returns
+    /// * OrderAck - The status of the replaced order and the quantity executed once it is matched, an error
+    ///   changing nothing if the order is not in the orderbook, the new terms are invalid or cannot be paid for
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(symbol = %symbol, order_id = %order_id), err)
+    )]
+    pub fn replace_order(
+        &mut self,
+        order_id: OrderId,
+        symbol: Symbol,
+        new_price: f64,
+        new_quantity: f64,
+    ) -> Result<OrderAck, Error> {
+        self.check_state(symbol, OrderbookState::accepts_amends)?;
+        if let Some(order) = self.resting_order(symbol, order_id, None) {
+            let replaced = Order {
+                price: Some(new_price),
+                quantity: new_quantity,
+                hidden_quantity: 0.0,
+                ..order
+            };
+            self.validate_amend(&replaced)?;
+            self.reserve_amend(&replaced)?;
+        }
+        let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        let order = orderbook.replace_order(order_id, new_price, new_quantity)?;
+        Ok(self.acknowledge_amend(&order))
+    }
+
+    /// Put an orderbook in call auction, its orders accumulate without matching
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn start_auction(&mut self, symbol: Symbol) -> Result<(), Error> {
+        self.set_state(symbol, OrderbookState::AuctionCall)
+    }
+
+    /// Change the trading state of an orderbook, the change is broadcast as a StateChange update
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'state' - The new trading state
+    pub fn set_state(&mut self, symbol: Symbol, state: OrderbookState) -> Result<(), Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            orderbook.set_state(state);
+            self.dispatch();
+            return Ok(());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Configure the price band of an orderbook, a match outside of the band trips the circuit breaker
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'price_band' - The price band, None to disable it
+    pub fn set_price_band(
+        &mut self,
+        symbol: Symbol,
+        price_band: Option<PriceBand>,
+    ) -> Result<(), Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            orderbook.price_band = price_band;
+            return Ok(());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Set the reference price of an orderbook, e.g. from an external index, the price bands, the market
+    /// collar and the auction collar being centered on it. The change is broadcast as a ReferencePrice update.
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'price' - The reference price, strictly positive
+    pub fn set_reference_price(&mut self, symbol: Symbol, price: f64) -> Result<(), Error> {
+        if !(price.is_finite() && price > 0.0) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Reference price must be strictly positive",
+            ));
+        }
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            orderbook.set_reference_price(price);
+            self.dispatch();
+            return Ok(());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Reference price of an orderbook, rounded to the price precision of its symbol
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn reference_price(&self, symbol: Symbol) -> Option<f64> {
+        let price = self.orderbooks.get(&symbol)?.reference_price()?;
+        Some(self.symbols.round_price(symbol, price))
+    }
+
+    /// Register a hook called synchronously by an orderbook with its trades, fills and book changes,
+    /// before they reach the risk engine, the accounts and the subscribers
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'observer' - The observer
+    pub fn add_match_observer(
+        &mut self,
+        symbol: Symbol,
+        observer: Arc<dyn MatchObserver>,
+    ) -> Result<(), Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            orderbook.add_observer(observer);
+            return Ok(());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Halt an orderbook, only cancels are accepted until it is resumed
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn halt(&mut self, symbol: Symbol) -> Result<(), Error> {
+        self.set_state(symbol, OrderbookState::Halted)
+    }
+
+    /// Resume continuous trading on an orderbook
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn resume(&mut self, symbol: Symbol) -> Result<(), Error> {
+        self.set_state(symbol, OrderbookState::Continuous)
+    }
+
+    /// Check the trading state of an orderbook accepts an operation
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'accepted' - Whether a state accepts the operation
+    fn check_state(
+        &self,
+        symbol: Symbol,
+        accepted: fn(&OrderbookState) -> bool,
+    ) -> Result<(), Error> {
+        match self.orderbooks.get(&symbol) {
+            Some(orderbook) if !accepted(&orderbook.state) => Err(Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "Operation not accepted while the orderbook is {}",
+                    orderbook.state
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run the call auction of an orderbook at its equilibrium price then return to continuous trading
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * AuctionResult - The equilibrium price and the executed volume
+    pub fn run_auction(&mut self, symbol: Symbol) -> Result<AuctionResult, Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            let result = orderbook.run_auction();
+            self.dispatch();
+            return Ok(result);
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Cancel every order of a user on every orderbook, e.g. as a kill switch for a market maker
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn cancel_all_for_user_across_symbols(&mut self, user_id: UserId) -> Vec<Order> {
+        let cancelled = self
+            .orderbooks
+            .values_mut()
+            .flat_map(|orderbook| orderbook.cancel_all_for_user(user_id))
+            .collect();
+        self.dispatch();
+        cancelled
+    }
+
+    /// Block the new orders of a user and cancel its resting orders on every orderbook,
+    /// its orders are rejected until `release_kill_switch_user`
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders, also sent with a single KillSwitchEvent
+    pub fn kill_switch_user(&mut self, user_id: UserId) -> Vec<Order> {
+        let target = KillSwitchTarget::User(user_id);
+        self.kill_switches.engage(target);
+        let cancelled = self.cancel_all_for_user_across_symbols(user_id);
+        self.publish_kill_switch(target, true, cancelled.clone());
+        cancelled
+    }
+
+    /// Block the new orders of a symbol and cancel every order resting in its orderbook,
+    /// the orders are rejected until `release_kill_switch_symbol`
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders, also sent with a single KillSwitchEvent
+    pub fn kill_switch_symbol(&mut self, symbol: Symbol) -> Result<Vec<Order>, Error> {
+        let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        };
+        let target = KillSwitchTarget::Symbol(symbol);
+        self.kill_switches.engage(target);
+        let cancelled = orderbook.cancel_all();
+        self.dispatch();
+        self.publish_kill_switch(target, true, cancelled.clone());
+        Ok(cancelled)
+    }
+
+    /// Enable the order flow of a user again
+    ///
+    /// #Returns
+    /// * bool - False if no kill switch was engaged for the user
+    pub fn release_kill_switch_user(&mut self, user_id: UserId) -> bool {
+        self.release_kill_switch(KillSwitchTarget::User(user_id))
+    }
+
+    /// Enable the order flow of a symbol again
+    ///
+    /// #Returns
+    /// * bool - False if no kill switch was engaged for the symbol
+    pub fn release_kill_switch_symbol(&mut self, symbol: Symbol) -> bool {
+        self.release_kill_switch(KillSwitchTarget::Symbol(symbol))
+    }
+
+    fn release_kill_switch(&mut self, target: KillSwitchTarget) -> bool {
+        let released = self.kill_switches.release(target);
+        if released {
+            self.publish_kill_switch(target, false, vec![]);
+        }
+        released
+    }
+
+    fn publish_kill_switch(
+        &self,
+        target: KillSwitchTarget,
+        engaged: bool,
+        cancelled_orders: Vec<Order>,
+    ) {
+        self.kill_switches.publish(&KillSwitchEvent {
+            target,
+            engaged,
+            cancelled_orders,
+            timestamp: self.clock.now(),
+        });
+    }
+
+    /// Receive every kill switch event emitted from now on
+    pub fn subscribe_kill_switch_events(&self) -> Receiver<KillSwitchEvent> {
+        self.kill_switches.subscribe()
+    }
+
+    /// Shut the engine down gracefully: stop accepting new orders, drain the updates of the orderbooks,
+    /// cancel or keep the resting orders according to the `shutdown_policy`, snapshot the orderbooks and
+    /// flush the persistence, then publish a Shutdown update per orderbook, flush the journal and close
+    /// the update streams
+    ///
+    /// #Returns
+    /// * ShutdownReport - The cancelled or the resting orders, an error if a snapshot could not be stored
+    ///   or the persistence or the journal could not be flushed
+    pub fn shutdown(&mut self) -> Result<ShutdownReport, Error> {
+        self.shut_down = true;
+        for orderbook in self.orderbooks.values_mut() {
+            orderbook.set_state(OrderbookState::CancelOnly);
+        }
+        self.dispatch();
+        let mut report = ShutdownReport {
+            policy: self.shutdown_policy,
+            ..Default::default()
+        };
+        for orderbook in self.orderbooks.values_mut() {
+            match self.shutdown_policy {
+                ShutdownPolicy::Cancel => report.cancelled_orders.extend(orderbook.cancel_all()),
+                ShutdownPolicy::Persist => report.resting_orders.extend(
+                    orderbook
+                        .bids
+                        .iter_ref()
+                        .chain(orderbook.asks.iter_ref())
+                        .copied(),
+                ),
+            }
+        }
+        self.dispatch();
+        for symbol in self.orderbooks.keys() {
+            self.save_snapshot(*symbol)?;
+        }
+        if let Some(persistence) = &self.persistence {
+            persistence.flush()?;
+        }
+        for orderbook in self.orderbooks.values_mut() {
+            orderbook.shut_down();
+        }
+        self.dispatch();
+        if let Some(journal) = &self.journal {
+            journal.flush()?;
+        }
+        self.bus.close();
+        Ok(report)
+    }
+
+    /// Expire the Good-Till-Date orders of every orderbook, to be called periodically
+    ///
+    /// #Returns
+    /// * Vec<Order> - The expired orders
+    pub fn tick(&mut self) -> Vec<Order> {
+        self.tick_at(self.clock.now_millis())
+    }
+
+    /// Same as tick with an explicit current time in milliseconds since UNIX epoch
+    pub fn tick_at(&mut self, now: u64) -> Vec<Order> {
+        let expired = self
+            .orderbooks
+            .values_mut()
+            .flat_map(|orderbook| orderbook.tick(now))
+            .collect();
+        self.dispatch();
+        expired
+    }
+
+    /// Set the daily trading hours of an orderbook: out of them it only accepts cancels, and at the
+    /// close its closing auction is run and its Day orders are expired. Applied by `run_sessions`
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'hours' - The trading hours, in milliseconds since midnight UTC
+    pub fn set_trading_hours(&mut self, symbol: Symbol, hours: TradingHours) -> Result<(), Error> {
+        if !self.orderbooks.contains_key(&symbol) {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            ));
+        }
+        hours.validate()?;
+        self.calendar.set_hours(symbol, hours);
+        Ok(())
+    }
+
+    /// Move the orderbooks to the phase of their trading session, to be called periodically
+    ///
+    /// #Returns
+    /// * Vec<SessionTransition> - The sessions which changed phase
+    pub fn run_sessions(&mut self) -> Vec<SessionTransition> {
+        self.run_sessions_at(self.clock.now_millis())
+    }
+
+    /// Same as run_sessions with an explicit current time in milliseconds since UNIX epoch
+    pub fn run_sessions_at(&mut self, now: u64) -> Vec<SessionTransition> {
+        let mut transitions = Vec::new();
+        for (symbol, phase) in self.calendar.transitions(now) {
+            let Some(orderbook) = self.orderbooks.get_mut(&symbol) else {
+                continue;
+            };
+            let mut transition = SessionTransition {
+                symbol,
+                phase,
+                timestamp: now,
+                expired: Vec::new(),
+                auction: None,
+            };
+            match phase {
+                SessionPhase::Open => orderbook.set_state(OrderbookState::Continuous),
+                SessionPhase::ClosingAuction => orderbook.start_auction(),
+                SessionPhase::Closed => {
+                    if orderbook.state == OrderbookState::AuctionCall {
+                        transition.auction = Some(orderbook.run_auction());
+                    }
+                    transition.expired = orderbook.expire_day_orders();
+                    orderbook.set_state(OrderbookState::CancelOnly);
+                }
+            }
+            self.dispatch();
+            self.calendar.publish(&transition);
+            transitions.push(transition);
+        }
+        transitions
+    }
+
+    /// Receive every session transition emitted from now on
+    pub fn subscribe_session_transitions(&mut self) -> Receiver<SessionTransition> {
+        self.calendar.subscribe()
+    }
+
+    /// Publish a Heartbeat update carrying the checksum of each orderbook, to be called
+    /// periodically so that the consumers mirroring the books can validate them
+    pub fn publish_heartbeats(&mut self) {
+        for orderbook in self.orderbooks.values_mut() {
+            orderbook.heartbeat();
+        }
+        self.dispatch();
+    }
+
+    /// Get the checksum of the best levels of an orderbook
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn checksum(&self, symbol: Symbol) -> Result<u32, Error> {
+        self.orderbooks
+            .get(&symbol)
+            .map(Orderbook::checksum)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Orderbook not found"))
+    }
+
+    /// Register the session of a user, when it ends all the orders of the user are cancelled
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'timeout' - The maximum delay between two heartbeats
+    pub fn register_session(&mut self, user_id: UserId, timeout: Duration) {
+        self.sessions.register(user_id, timeout, Instant::now());
+        self.sessions.publish(&SessionEvent {
+            user_id,
+            event_type: SessionEventType::Connected,
+            cancelled_orders: Vec::new(),
+        });
+    }
+
+    /// Keep the session of a user alive
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    pub fn heartbeat(&mut self, user_id: UserId) -> Result<(), Error> {
+        if self.sessions.heartbeat(user_id, Instant::now()) {
+            return Ok(());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Session not found",
+        ))
+    }
+
+    /// End the session of a user and cancel all of its orders
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    ///
+    /// #Returns
+    /// * Vec<Order> - The cancelled orders
+    pub fn disconnect(&mut self, user_id: UserId) -> Result<Vec<Order>, Error> {
+        if self.sessions.remove(user_id).is_none() {
+            return Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Session not found",
+            ));
+        }
+        Ok(self.end_session(user_id, SessionEventType::Disconnected))
+    }
+
+    /// End the sessions which missed their heartbeat and cancel their orders,
+    /// to be called periodically by the gateway
+    ///
+    /// #Returns
+    /// * Vec<SessionEvent> - The events of the sessions which timed out
+    pub fn check_sessions(&mut self) -> Vec<SessionEvent> {
+        self.check_sessions_at(Instant::now())
+    }
+
+    /// Same as check_sessions with an explicit current time
+    pub fn check_sessions_at(&mut self, now: Instant) -> Vec<SessionEvent> {
+        self.sessions
+            .remove_expired(now)
+            .into_iter()
+            .map(|user_id| SessionEvent {
+                user_id,
+                event_type: SessionEventType::TimedOut,
+                cancelled_orders: self.end_session(user_id, SessionEventType::TimedOut),
+            })
+            .collect()
+    }
+
+    /// Receive every session event emitted from now on
+    pub fn subscribe_session_events(&mut self) -> Receiver<SessionEvent> {
+        self.sessions.subscribe()
+    }
+
+    fn end_session(&mut self, user_id: UserId, event_type: SessionEventType) -> Vec<Order> {
+        let cancelled_orders = self.cancel_all_for_user_across_symbols(user_id);
+        self.sessions.publish(&SessionEvent {
+            user_id,
+            event_type,
+            cancelled_orders: cancelled_orders.clone(),
+        });
+        cancelled_orders
+    }
+
+    /// Get an orderbook summary by symbol
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn get_orderbook(&self, symbol: Symbol) -> Result<OrderBookSummarized, Error> {
+        self.get_orderbook_with_privacy(symbol, SummaryPrivacy::Public)
+    }
+
+    /// Get an orderbook summary by symbol, the internal summary adding the visible and total quantity
+    /// of each level and its icebergs, for surveillance rather than the public feeds
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'privacy' : Whether the hidden size of the icebergs is included
+    pub fn get_orderbook_with_privacy(
+        &self,
+        symbol: Symbol,
+        privacy: SummaryPrivacy,
+    ) -> Result<OrderBookSummarized, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            let (bids, mid_price, asks) = orderbook.summarize_orderbook_per_price_level();
+            let mut summary_back = OrderBookSummarized::new(bids, mid_price, asks)
+                .with_metrics(orderbook.metrics())
+                .with_reference_price(orderbook.reference_price());
+            if privacy == SummaryPrivacy::Internal {
+                summary_back = summary_back.with_liquidity(
+                    orderbook.level_liquidity(OrderSide::Buy),
+                    orderbook.level_liquidity(OrderSide::Sell),
+                );
+            }
+            self.symbols
+                .precision(symbol)
+                .round_summary(&mut summary_back);
+            return Ok(summary_back);
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Round a price to the precision of its symbol, as the trades, summaries and depth are emitted
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'price' : The price
+    pub fn round_price(&self, symbol: Symbol, price: f64) -> f64 {
+        self.symbols.round_price(symbol, price)
+    }
+
+    /// Get a read-only view of an orderbook, serving its depth, best bid and offer, metrics and summary
+    /// to other threads while the orderbook is matched
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * OrderbookView - The view, shared with the views of the orderbook handed out before
+    pub fn view(&self, symbol: Symbol) -> Result<OrderbookView, Error> {
+        match self.orderbooks.get(&symbol) {
+            Some(orderbook) => Ok(self
+                .views
+                .get_or_create(orderbook, self.symbols.precision(symbol))),
+            None => Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Orderbook not found",
+            )),
+        }
+    }
+
+    /// Get the snapshot of an orderbook as shown to the market, tagged with its sequence number: the
+    /// icebergs show their visible slice only and the orders carry no metadata
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn snapshot(&self, symbol: Symbol) -> Result<BookSnapshot, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.snapshot().public_view());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Estimate the execution of a size against an orderbook: average price, worst price and levels consumed
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'side' - The side of the order
+    /// * 'quantity' - The quantity to execute
+    pub fn cost_to_fill(
+        &self,
+        symbol: Symbol,
+        side: OrderSide,
+        quantity: f64,
+    ) -> Result<Option<FillCost>, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.cost_to_fill(side, quantity));
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Get the events of an order: placed, amended, executed, filled or cancelled
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'order_id' - The order ID
+    pub fn order_history(
+        &self,
+        symbol: Symbol,
+        order_id: OrderId,
+    ) -> Result<Vec<OrderEvent>, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.order_history(order_id));
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Get the usage of the order pools of an orderbook, to tune its capacity
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn pool_stats(&self, symbol: Symbol) -> Result<PoolStats, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.pool_stats());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Get the number of price levels of each side of an orderbook and the orders of its most crowded level
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn level_stats(&self, symbol: Symbol) -> Result<LevelStats, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.level_stats());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Release the memory left by the orders which left an orderbook, e.g. after a burst of quotes
+    /// was cancelled
+    ///
+    /// Parameters
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * usize - The number of pool slots released
+    pub fn compact(&mut self, symbol: Symbol) -> Result<usize, Error> {
+        if let Some(orderbook) = self.orderbooks.get_mut(&symbol) {
+            return Ok(orderbook.compact());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Check the consistency of an orderbook: not crossed, positive quantities, unique order IDs,
+    /// priority order and level totals
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    ///
+    /// #Returns
+    /// * InvariantReport - The violations found, empty when the orderbook is consistent
+    pub fn verify_invariants(&self, symbol: Symbol) -> Result<InvariantReport, Error> {
+        if let Some(orderbook) = self.orderbooks.get(&symbol) {
+            return Ok(orderbook.verify_invariants());
+        }
+        Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            "Orderbook not found",
+        ))
+    }
+
+    /// Listen to new orders
+    pub fn listen_new_orders(&self) -> impl Stream<Item = Order> {
+        self.subscribe()
+            .update_type(OrderbookUpdateType::New)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.order))
+    }
+
+    /// Listen to placed orders
+    pub fn listen_placed_orders(&self) -> impl Stream<Item = Order> {
+        self.subscribe()
+            .update_type(OrderbookUpdateType::Place)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.order))
+    }
+
+    /// Listen to new trades
+    pub fn listen_new_trades(&self) -> impl Stream<Item = Trade> {
+        self.subscribe()
+            .update_type(OrderbookUpdateType::NewTrades)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.trade))
+    }
+
+    /// Listen to the settlement instructions of the trades, the trades of the symbols missing from
+    /// the symbol registry are skipped
+    pub fn listen_settlements(&self) -> impl Stream<Item = SettlementInstruction> {
+        let symbols = self.symbols.clone();
+        self.listen_new_trades().filter_map(move |trade| {
+            future::ready(
+                symbols
+                    .get(trade.symbol)
+                    .map(|info| SettlementInstruction::from_trade(&trade, &info)),
+            )
+        })
+    }
+
+    /// Listen to the volume weighted average price of a symbol, the current value is yielded first
+    /// then a new value is yielded after each trade
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'window' - The duration the VWAP is computed over
+    pub fn listen_vwap(&self, symbol: Symbol, window: Duration) -> impl Stream<Item = f64> + '_ {
+        let mut subscription = self.subscribe_updates();
+        stream! {
+            if let Some(vwap) = self.orderbooks.get(&symbol).and_then(|o| o.vwap(window)) {
+                yield vwap;
+            }
+            while let Some(orderbook_update) = subscription.next().await {
+                if orderbook_update.update_type == OrderbookUpdateType::NewTrades
+                    && orderbook_update.symbol == symbol
+                {
+                    if let Some(vwap) = self.orderbooks.get(&symbol).and_then(|o| o.vwap(window)) {
+                        yield vwap;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Listen to the indicative result of the call auction of a symbol: the uncrossing price and the
+    /// matchable volume, republished as the orders of the auction are added and cancelled.
+    /// The current result is yielded first when the orderbook is in call auction
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn listen_indicative_price(&self, symbol: Symbol) -> impl Stream<Item = AuctionResult> {
+        let current = self
+            .orderbooks
+            .get(&symbol)
+            .filter(|o| o.state == OrderbookState::AuctionCall)
+            .map(|o| o.indicative_auction());
+        let updates = self
+            .subscribe()
+            .symbol(symbol)
+            .update_type(OrderbookUpdateType::IndicativeAuction)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.auction));
+        futures_util::stream::iter(current).chain(updates)
+    }
+
+    /// Listen to the price levels of a symbol whose quantity changed since they were last yielded,
+    /// each with its new visible quantity, 0.0 once the level is gone, so that the clients keep
+    /// their depth without receiving the whole book on every update
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    pub fn listen_depth_deltas(&self, symbol: Symbol) -> impl Stream<Item = LevelChange> {
+        let mut levels = self
+            .subscribe()
+            .symbol(symbol)
+            .feed(MarketFeed::MarketByPrice)
+            .stream();
+        stream! {
+            // quantity last yielded per side and price
+            let mut yielded: HashMap<(bool, u64), f64> = HashMap::new();
+            while let Some(update) = levels.next().await {
+                for delta in update.levels {
+                    let key = (delta.side == OrderSide::Buy, delta.price.to_bits());
+                    let previous = match delta.action {
+                        LevelAction::Delete => yielded.remove(&key),
+                        _ => yielded.insert(key, delta.quantity),
+                    };
+                    if previous != Some(delta.quantity) {
+                        yield LevelChange {
+                            side: delta.side,
+                            price: delta.price,
+                            quantity: delta.quantity,
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    /// listen to orderbook summary by symbol, the current summary is yielded first
+    pub fn listen_orderbook_summary_by_symbol(
+        &self,
+        symbol: Symbol,
+    ) -> impl Stream<Item = OrderBookSummarized> + '_ {
+        let mut subscription = self.subscribe_updates();
+        stream! {
+            if let Ok(summary_back) = self.get_orderbook(symbol) {
+                yield summary_back;
+            }
+            while let Some(orderbook_update) = subscription.next().await {
+                match orderbook_update.update_type {
+                    OrderbookUpdateType::Place
+                    | OrderbookUpdateType::Cancel
+                    | OrderbookUpdateType::Amended
+                    | OrderbookUpdateType::PartiallyFilled
+                    | OrderbookUpdateType::Repriced
+                    | OrderbookUpdateType::Replace
+                    | OrderbookUpdateType::Filled
+                    | OrderbookUpdateType::Expired
+                    | OrderbookUpdateType::Migrated
+                        if orderbook_update.symbol == symbol =>
+                    {
+                        if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
+                            yield summary_back;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// listen to orderbook summary by symbol, emitting at most one summary per interval.
+    /// The updates received in between are coalesced into a local copy of the book,
+    /// so a burst of updates costs a single summary. The current summary is yielded first.
+    ///
+    /// Parameters
+    /// * 'symbol' : The symbol ID
+    /// * 'interval' : The minimum time between two summaries
+    #[cfg(feature = "native")]
+    pub fn listen_orderbook_summary_throttled(
+        &self,
+        symbol: Symbol,
+        interval: Duration,
+    ) -> impl Stream<Item = OrderBookSummarized> {
+        let mut subscription = self.subscribe_updates();
+        let snapshot = self.snapshot(symbol);
+        stream! {
+            let Ok(mut book) = snapshot else {
+                return;
+            };
+            yield book.summarize();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker.tick().await;
+            let mut changed = false;
+            loop {
+                let update = tokio::select! {
+                    update = subscription.next() => Some(update),
+                    _ = ticker.tick(), if changed => None,
+                };
+                match update {
+                    Some(Some(update)) => {
+                        if update.symbol == symbol && update.sequence > book.sequence {
+                            book.apply(&update);
+                            changed |= matches!(
+                                update.update_type,
+                                OrderbookUpdateType::Place
+                                    | OrderbookUpdateType::Cancel
+                                    | OrderbookUpdateType::Amended
+                                    | OrderbookUpdateType::PartiallyFilled
+                                    | OrderbookUpdateType::Repriced
+                                    | OrderbookUpdateType::Replace
+                                    | OrderbookUpdateType::Filled
+                                    | OrderbookUpdateType::Expired
+                                    | OrderbookUpdateType::Migrated
+                            );
+                        }
+                    }
+                    Some(None) => {
+                        if changed {
+                            yield book.summarize();
+                        }
+                        break;
+                    }
+                    None => {
+                        changed = false;
+                        yield book.summarize();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Listen to orderbook summary, the current summary of every orderbook is yielded first
+    pub fn listen_orderbook_summary(&self) -> impl Stream<Item = OrderBookSummarized> + '_ {
+        let mut subscription = self.subscribe_updates();
+        stream! {
+            for symbol in self.orderbooks.keys() {
+                if let Ok(summary_back) = self.get_orderbook(*symbol) {
+                    yield summary_back;
+                }
+            }
+            while let Some(orderbook_update) = subscription.next().await {
+                match orderbook_update.update_type {
+                    OrderbookUpdateType::Place
+                    | OrderbookUpdateType::Cancel
+                    | OrderbookUpdateType::Amended
+                    | OrderbookUpdateType::PartiallyFilled
+                    | OrderbookUpdateType::Repriced
+                    | OrderbookUpdateType::Replace
+                    | OrderbookUpdateType::Filled
+                    | OrderbookUpdateType::Expired
+                    | OrderbookUpdateType::Migrated => {
+                        if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
+                            yield summary_back;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Listen to orderbook updates
+    pub fn listen_orderbook_updates(&self) -> impl Stream<Item = Order> {
+        self.subscribe()
+            .update_types([
+                OrderbookUpdateType::Amended,
+                OrderbookUpdateType::PartiallyFilled,
+                OrderbookUpdateType::Repriced,
+            ])
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.order))
+    }
+
+    /// Listen to orderbook cancels
+    pub fn listen_orderbook_cancels(&self) -> impl Stream<Item = OrderId> {
+        self.subscribe()
+            .update_type(OrderbookUpdateType::Cancel)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.cancel_id))
+    }
+
+    /// Listen to orderbook fills
+    pub fn listen_orderbook_fills(&self) -> impl Stream<Item = OrderId> {
+        self.subscribe()
+            .update_type(OrderbookUpdateType::Filled)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.filled_id))
+    }
+
+    /// Listen to the orders of a user: new, placed, updated, cancelled and filled
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    pub fn listen_user_orders(&self, user_id: UserId) -> impl Stream<Item = Order> {
+        self.subscribe()
+            .user(user_id)
+            .stream()
+            .filter_map(move |orderbook_update| {
+                future::ready(orderbook_update.order.filter(|o| o.user_id == user_id))
+            })
+    }
+
+    /// Listen to the trades where a user is the buyer or the seller
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    pub fn listen_user_trades(&self, user_id: UserId) -> impl Stream<Item = Trade> {
+        self.subscribe()
+            .user(user_id)
+            .update_type(OrderbookUpdateType::NewTrades)
+            .stream()
+            .filter_map(|orderbook_update| future::ready(orderbook_update.trade))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::self_trade_prevention::SelfTradePrevention;
+    use crate::enums::side::OrderSide;
+    use crate::enums::time_in_force::TimeInForce;
+    use crate::risk::limits::RiskLimits;
+    use crate::structs::order::Order;
+    use crate::structs::orderbook_config::{FeeSchedule, OrderbookConfig};
+    use crate::structs::orderbook_sum::BidAskSummarize;
+    use futures_util::StreamExt;
+    use std::io::ErrorKind;
+    use ulid::Ulid;
+
+    #[tokio::test]
+    async fn test_listen_to_new_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_new_orders().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+        let _ = orderbooks_manager.add_order(order3);
+
+        let first_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(first_order, order1);
+
+        let second_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(second_order, order2);
+
+        let third_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(third_order, order3);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_placed_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_placed_orders().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+        let _ = orderbooks_manager.add_order(order3);
+
+        // the orders are placed with their rank in the arrival sequence of the orderbook
+        let first_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(
+            first_order,
+            Order {
+                arrival_seq: 1,
+                ..order1
+            }
+        );
+
+        let second_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(
+            second_order,
+            Order {
+                arrival_seq: 2,
+                ..order2
+            }
+        );
+
+        let third_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(
+            third_order,
+            Order {
+                arrival_seq: 3,
+                ..order3
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_depth_deltas() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let resting = order(OrderSide::Buy, 1.0, 10.0);
+        let _ = orderbooks_manager.add_order(resting);
+
+        let mut deltas = orderbooks_manager.listen_depth_deltas(symbol).boxed();
+        let ask = order(OrderSide::Sell, 1.0, 11.0);
+        let _ = orderbooks_manager.add_order(order(OrderSide::Buy, 2.0, 10.0));
+        let _ = orderbooks_manager.add_order(ask);
+        let _ = orderbooks_manager.cancel_order(ask.id, symbol, ask.side);
+        let _ = orderbooks_manager.cancel_order(resting.id, symbol, resting.side);
+
+        let change = |side, price, quantity| LevelChange {
+            side,
+            price,
+            quantity,
+        };
+        for expected in [
+            change(OrderSide::Buy, 10.0, 3.0),
+            change(OrderSide::Sell, 11.0, 1.0),
+            change(OrderSide::Sell, 11.0, 0.0),
+            change(OrderSide::Buy, 10.0, 2.0),
+        ] {
+            assert_eq!(deltas.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_orderbook_summary() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(2.0),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(3.0),
+            OrderType::Limit,
+        );
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+        let _ = orderbooks_manager.add_order(order3);
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_summary().boxed();
+
+        let summary = new_orders_stream.next().await.unwrap();
+        assert_eq!(
+            summary.bids[0],
+            BidAskSummarize {
+                price: 3.0,
+                qty: 1.0,
+                qty_sum: 3.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+        assert_eq!(
+            summary.bids[1],
+            BidAskSummarize {
+                price: 2.0,
+                qty: 1.0,
+                qty_sum: 2.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+        assert_eq!(
+            summary.bids[2],
+            BidAskSummarize {
+                price: 1.0,
+                qty: 1.0,
+                qty_sum: 1.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_orderbook_summary_sell() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(2.0),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(3.0),
+            OrderType::Limit,
+        );
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+        let _ = orderbooks_manager.add_order(order3);
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_summary().boxed();
+
+        let summary = new_orders_stream.next().await.unwrap();
+        assert_eq!(
+            summary.asks[0],
+            BidAskSummarize {
+                price: 1.0,
+                qty: 1.0,
+                qty_sum: 1.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+        assert_eq!(
+            summary.asks[1],
+            BidAskSummarize {
+                price: 2.0,
+                qty: 1.0,
+                qty_sum: 2.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+        assert_eq!(
+            summary.asks[2],
+            BidAskSummarize {
+                price: 3.0,
+                qty: 1.0,
+                qty_sum: 3.0,
+                qty_percent: 33.33333333333333
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_orderbook_updates_amend_price() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_updates().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.amend_order_price(symbol, order1.id, 50.0, order1.side);
+
+        let first_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(first_order.price, Some(50.0));
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_orderbook_updates_amend_quantity() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_updates().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.amend_order_quantity(symbol, order1.id, 10.0, order1.side);
+
+        let first_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(first_order.quantity, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_new_trade() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_new_trades().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+
+        let trade = new_orders_stream.next().await.unwrap();
+        assert_eq!(trade.symbol, order1.symbol);
+        assert_eq!(Some(trade.price), order1.price);
+        assert_eq!(trade.quantity, order1.quantity);
+        assert_eq!(trade.buy_order_id, order1.id);
+        assert_eq!(trade.sell_order_id, order2.id);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_filled_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_fills().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+
+        let order = new_orders_stream.next().await.unwrap();
+        assert_eq!(order, order2.id);
+        let order = new_orders_stream.next().await.unwrap();
+        assert_eq!(order, order1.id);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_cancelled_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut new_orders_stream = orderbooks_manager.listen_orderbook_cancels().boxed();
+
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.cancel_order(order1.id, symbol, order1.side);
+
+        let first_order = new_orders_stream.next().await.unwrap();
+        assert_eq!(first_order, order1.id);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_vwap() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        for (price, quantity) in [(10.0, 1.0), (12.0, 1.0)] {
+            let buy = Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            );
+            let sell = Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Sell,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            );
+            let _ = orderbooks_manager.add_order(buy);
+            let _ = orderbooks_manager.add_order(sell);
+        }
+
+        let mut vwap_stream = orderbooks_manager
+            .listen_vwap(symbol, Duration::from_secs(60))
+            .boxed();
+
+        let vwap = vwap_stream.next().await.unwrap();
+        assert_eq!(vwap, 11.0);
+    }
+
+    #[tokio::test]
+    async fn test_every_listener_gets_every_update() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut trades_stream = orderbooks_manager.listen_new_trades().boxed();
+        let mut fills_stream = orderbooks_manager.listen_orderbook_fills().boxed();
+        let mut new_orders_stream = orderbooks_manager.listen_new_orders().boxed();
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+
+        let trade = trades_stream.next().await.unwrap();
+        assert_eq!(trade.buy_order_id, order1.id);
+        assert_eq!(fills_stream.next().await.unwrap(), order2.id);
+        assert_eq!(fills_stream.next().await.unwrap(), order1.id);
+        assert_eq!(new_orders_stream.next().await.unwrap(), order1);
+        assert_eq!(new_orders_stream.next().await.unwrap(), order2);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_listener_drops_oldest_updates() {
+        let mut orderbooks_manager =
+            OrderbooksManager::with_backpressure(2, OverflowPolicy::DropOldest);
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let subscription = orderbooks_manager.subscribe_updates();
+        let mut placed_stream = orderbooks_manager.listen_placed_orders().boxed();
+        for price in [1.0, 2.0, 3.0] {
+            let order = Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            );
+            let _ = orderbooks_manager.add_order(order);
+        }
+
+        assert_eq!(subscription.len(), 2);
+        assert_eq!(subscription.dropped(), 4);
+        assert_eq!(subscription.try_recv().unwrap().sequence, 5);
+        let placed = placed_stream.next().await.unwrap();
+        assert_eq!(placed.price, Some(3.0));
+    }
+
+    #[test]
+    fn test_coalesced_updates_keep_the_latest_state_of_the_orders() {
+        let mut orderbooks_manager = OrderbooksManager::with_capacity(2, OverflowPolicy::Coalesce);
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let subscription = orderbooks_manager.subscribe_updates();
+        let order = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let _ = orderbooks_manager.add_order(order);
+        for price in [2.0, 3.0, 4.0] {
+            let _ = orderbooks_manager.amend_order_price(symbol, order.id, price, order.side);
+        }
+
+        // the place and the first amends were superseded by the last amend
+        assert_eq!(subscription.dropped(), 3);
+        let new = subscription.try_recv().unwrap();
+        assert_eq!(new.update_type, OrderbookUpdateType::New);
+        let latest = subscription.try_recv().unwrap();
+        assert_eq!(latest.order.unwrap().price, Some(4.0));
+        assert!(subscription.try_recv().is_err());
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_throttled_summary_coalesces_bursts() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let mut summaries = orderbooks_manager
+            .listen_orderbook_summary_throttled(symbol, Duration::from_millis(50))
+            .boxed();
+        assert!(summaries.next().await.unwrap().bids.is_empty());
+
+        for price in 1..=10 {
+            let order = Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(price as f64),
+                OrderType::Limit,
+            );
+            let _ = orderbooks_manager.add_order(order);
+        }
+        // the burst is delivered as a single summary
+        let summary = summaries.next().await.unwrap();
+        assert_eq!(summary.bids.len(), 10);
+        assert_eq!(summary.bids[0].price, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_user_orders_and_trades() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let user = Ulid::new().into();
+        let order1 = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            user,
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+
+        let mut user_orders_stream = orderbooks_manager.listen_user_orders(user).boxed();
+        let mut user_trades_stream = orderbooks_manager.listen_user_trades(user).boxed();
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+
+        // New, Placed then Filled
+        let order = user_orders_stream.next().await.unwrap();
+        assert_eq!(order, order2);
+        let order = user_orders_stream.next().await.unwrap();
+        assert_eq!(
+            order,
+            Order {
+                arrival_seq: 2,
+                ..order2
+            }
+        );
+        let order = user_orders_stream.next().await.unwrap();
+        assert_eq!(order.id, order2.id);
+        assert_eq!(order.status, OrderStatus::Filled);
+        let trade = user_trades_stream.next().await.unwrap();
+        assert_eq!(trade.sell_user_id, user);
+        assert_eq!(trade.buy_order_id, order1.id);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_for_user_across_symbols() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let user = Ulid::new().into();
+        let symbols: Vec<Symbol> = (0..2).map(|_| Ulid::new().into()).collect();
+        for symbol in symbols.iter() {
+            orderbooks_manager.new_orderbook(*symbol);
+            let order = Order::new(
+                user,
+                *symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(1.0),
+                OrderType::Limit,
+            );
+            let _ = orderbooks_manager.add_order(order);
+        }
+
+        let mut cancels_stream = orderbooks_manager.listen_orderbook_cancels().boxed();
+        let cancelled = orderbooks_manager.cancel_all_for_user_across_symbols(user);
+
+        assert_eq!(cancelled.len(), 2);
+        for order in cancelled.iter() {
+            assert_eq!(cancels_stream.next().await, Some(order.id));
+        }
+        assert!(orderbooks_manager
+            .orderbooks
+            .values()
+            .all(|orderbook| orderbook.bids.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_session_timeout_cancels_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let user = Ulid::new().into();
+        let order = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let session_events = orderbooks_manager.subscribe_session_events();
+        orderbooks_manager.register_session(user, Duration::from_secs(1));
+        let _ = orderbooks_manager.add_order(order);
+        assert!(orderbooks_manager.heartbeat(user).is_ok());
+
+        let events = orderbooks_manager.check_sessions();
+        assert!(events.is_empty());
+        let events = orderbooks_manager.check_sessions_at(Instant::now() + Duration::from_secs(2));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, SessionEventType::TimedOut);
+        assert_eq!(events[0].cancelled_orders[0].id, order.id);
+        assert!(orderbooks_manager.heartbeat(user).is_err());
+        assert!(orderbooks_manager.disconnect(user).is_err());
+
+        let connected = session_events.try_recv().unwrap();
+        assert_eq!(connected.event_type, SessionEventType::Connected);
+        let timed_out = session_events.try_recv().unwrap();
+        assert_eq!(timed_out, events[0]);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancels_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let user = Ulid::new().into();
+        let order = Order::new(
+            user,
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.register_session(user, Duration::from_secs(30));
+        let _ = orderbooks_manager.add_order(order);
+
+        let cancelled = orderbooks_manager.disconnect(user).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].status, OrderStatus::Cancelled);
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].asks.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_expired_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let now = SystemClock.now_millis();
+        let order = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        )
+        .with_expiry(now + 60_000);
+
+        let mut expired_stream = orderbooks_manager
+            .subscribe()
+            .update_type(OrderbookUpdateType::Expired)
+            .stream();
+        let _ = orderbooks_manager.add_order(order);
+        assert!(orderbooks_manager.tick().is_empty());
+        assert_eq!(orderbooks_manager.tick_at(now + 60_000).len(), 1);
+
+        let update = expired_stream.next().await.unwrap();
+        assert_eq!(update.order.map(|o| o.id), Some(order.id));
+        assert_eq!(orderbooks_manager.snapshot(symbol).unwrap().asks.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_add_orders_atomic_and_best_effort() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let ladder = |prices: &[f64]| -> Vec<Order> {
+            prices
+                .iter()
+                .map(|price| {
+                    Order::new(
+                        Ulid::new().into(),
+                        symbol,
+                        OrderSide::Sell,
+                        1.0,
+                        Some(*price),
+                        OrderType::Limit,
+                    )
+                })
+                .collect()
+        };
+
+        let mut placed_stream = orderbooks_manager.listen_placed_orders().boxed();
+        let result = orderbooks_manager.add_orders(ladder(&[1.0, -1.0, 3.0]), BatchMode::Atomic);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].asks.len(), 0);
+
+        let results = orderbooks_manager
+            .add_orders(ladder(&[1.0, -1.0, 3.0]), BatchMode::BestEffort)
+            .unwrap();
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].asks.len(), 2);
+        assert_eq!(placed_stream.next().await.unwrap().price, Some(1.0));
+        assert_eq!(placed_stream.next().await.unwrap().price, Some(3.0));
+    }
+
+    #[test]
+    fn test_atomic_batch_admits_its_orders_together() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        let (base, quote) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(symbol);
+        let accounts = orderbooks_manager.enable_accounts().clone();
+        accounts.register_market(symbol, base, quote);
+        let buyer = Ulid::new().into();
+        accounts.deposit(buyer, quote, 150.0);
+        let buy = |quantity: f64| {
+            Order::new(
+                buyer,
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(100.0),
+                OrderType::Limit,
+            )
+        };
+
+        // Each order can be paid for, not both
+        let batch = vec![buy(1.0), buy(1.0)];
+        let err = orderbooks_manager
+            .add_orders(batch.clone(), BatchMode::Atomic)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].bids.len(), 0);
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 150.0,
+                reserved: 0.0
+            }
+        );
+
+        orderbooks_manager.risk.set_limits(
+            buyer,
+            RiskLimits {
+                max_open_orders: Some(1),
+                ..Default::default()
+            },
+        );
+        let err = orderbooks_manager
+            .add_orders(vec![buy(0.5), buy(0.5)], BatchMode::Atomic)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(orderbooks_manager.risk.open_orders(buyer), 0);
+
+        // The orders of a rejected batch can be sent again
+        let results = orderbooks_manager
+            .add_orders(vec![batch[0]], BatchMode::Atomic)
+            .unwrap();
+        assert!(results[0].is_ok());
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].bids.len(), 1);
+        assert_eq!(accounts.balance(buyer, quote).reserved, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_replaced_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let _ = orderbooks_manager.add_order(order);
 
+        let mut replace_stream = orderbooks_manager
+            .subscribe()
+            .update_type(OrderbookUpdateType::Replace)
+            .stream();
+        let ack = orderbooks_manager
+            .replace_order(order.id, symbol, 2.0, 3.0)
+            .unwrap();
+        assert_eq!(
+            (ack.order_id, ack.status, ack.filled_quantity),
+            (order.id, OrderStatus::Open, 0.0)
+        );
+        assert!(orderbooks_manager
+            .replace_order(Ulid::new().into(), symbol, 2.0, 3.0)
+            .is_err());
+        for (price, quantity) in [(-1.0, 3.0), (2.0, 0.0), (f64::NAN, 3.0)] {
+            let err = orderbooks_manager
+                .replace_order(order.id, symbol, price, quantity)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
         }
+
+        let update = replace_stream.next().await.unwrap();
+        let replaced = update.order.unwrap();
+        assert_eq!(replaced.id, order.id);
+        assert_eq!((replaced.price, replaced.quantity), (Some(2.0), 3.0));
+        let snapshot = orderbooks_manager.snapshot(symbol).unwrap();
+        assert_eq!(snapshot.bids, vec![replaced]);
     }
 
-    /// Listen to new trades
-    pub fn listen_new_trades<'a>(&self) -> impl Stream<Item = Trade> {
-        let rx = self.rx.clone();
-        stream! {
+    #[tokio::test]
+    async fn test_halted_orderbook_only_accepts_cancels() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let _ = orderbooks_manager.add_order(order);
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::NewTrades => {
-                                if let Some(trade) = orderbook_update.trade {
-                                    yield trade;
-                                }
-                            }
-                            _ => {}
+        let mut states_stream = orderbooks_manager
+            .subscribe()
+            .update_type(OrderbookUpdateType::StateChange)
+            .stream();
+        orderbooks_manager.halt(symbol).unwrap();
+        let rejected = orderbooks_manager.add_order(order);
+        assert_eq!(
+            rejected.unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+        assert!(orderbooks_manager
+            .amend_order_price(symbol, order.id, 2.0, order.side)
+            .is_err());
+        assert!(orderbooks_manager
+            .cancel_order(order.id, symbol, order.side)
+            .is_ok());
+        orderbooks_manager.resume(symbol).unwrap();
 
-                        }
-                    }
-        }
+        let update = states_stream.next().await.unwrap();
+        assert_eq!(update.state, Some(OrderbookState::Halted));
+        let update = states_stream.next().await.unwrap();
+        assert_eq!(update.state, Some(OrderbookState::Continuous));
     }
 
-    /// listen to orderbook summary by symbol
-    pub fn listen_orderbook_summary_by_symbol<'a>(
-        &'a self,
-        symbol: u128,
-    ) -> impl Stream<Item = OrderBookSummarized> + 'a {
-        let rx = self.rx.clone();
-        stream! {
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Place => {
-
-                                if orderbook_update.symbol == symbol {
-                                    if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                        yield summary_back;
-                                    }
-                                }
-                            }
-                            OrderbookUpdateType::Cancel => {
-                                if orderbook_update.symbol == symbol {
-                                    if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                        yield summary_back;
-                                    }
-                                }
-
-                            }
-                            OrderbookUpdateType::Update=> {
-                                if orderbook_update.symbol == symbol {
-                                    if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                        yield summary_back;
-                                    }
-                                }
-
-                            },
-                            OrderbookUpdateType::Filled=> {
-                                if orderbook_update.symbol == symbol {
-                                    if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                        yield summary_back;
-                                    }
-                                }
-                            },
-
-                            _ => {}
+    #[test]
+    fn test_risk_limits_follow_the_trades() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-                        }
-                    }
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let buyer = Ulid::new().into();
+        orderbooks_manager.risk.set_limits(
+            buyer,
+            RiskLimits {
+                max_open_orders: Some(1),
+                max_position: Some(2.0),
+                ..Default::default()
+            },
+        );
+        let buy = |quantity: f64| {
+            Order::new(
+                buyer,
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(1.0),
+                OrderType::Limit,
+            )
+        };
+        let order = buy(2.0);
+        orderbooks_manager.add_order(order).unwrap();
+        let rejected = orderbooks_manager.add_order(buy(1.0));
+        assert_eq!(
+            rejected.unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
 
-        }
+        let sell = Order::new(
+            Ulid::new().into(),
+            symbol,
+            OrderSide::Sell,
+            2.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.add_order(sell).unwrap();
+        assert_eq!(orderbooks_manager.risk.position(buyer, symbol), 2.0);
+        assert_eq!(orderbooks_manager.risk.open_orders(buyer), 0);
+        // The order count is freed but the position is at its limit
+        assert!(orderbooks_manager.add_order(buy(1.0)).is_err());
     }
 
-    /*
-    * Listen to orderbook summary
-        @return impl Stream<Item = OrderBookSummarized>
-    */
-    pub fn listen_orderbook_summary<'a>(&'a self) -> impl Stream<Item = OrderBookSummarized> + 'a {
-        let rx = self.rx.clone();
-        stream! {
+    #[test]
+    fn test_orders_throttled_by_notional() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Place => {
-                                if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                    yield summary_back;
-                                }                          }
-                            OrderbookUpdateType::Cancel => {
-                                if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                    yield summary_back;
-                                }
-
-                            }
-                            OrderbookUpdateType::Update=> {
-                                if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                    yield summary_back;
-                                }
-
-                            },
-                            OrderbookUpdateType::Filled=> {
-                                if let Ok(summary_back) = self.get_orderbook(orderbook_update.symbol) {
-                                    yield summary_back;
-                                }
-                            },
-
-                            _ => {}
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let (buyer, seller) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.risk.set_limits(
+            buyer,
+            RiskLimits {
+                max_order_notional: Some(50.0),
+                max_resting_notional: Some(60.0),
+                ..Default::default()
+            },
+        );
+        let order = |user_id, side, quantity, price: Option<f64>| {
+            let order_type = match price {
+                Some(_) => OrderType::Limit,
+                None => OrderType::Market,
+            };
+            Order::new(user_id, symbol, side, quantity, price, order_type)
+        };
+        let reason = |result: Result<OrderAck, Error>| result.unwrap_err().to_string();
 
-                        }
-                    }
+        orderbooks_manager
+            .add_order(order(buyer, OrderSide::Buy, 4.0, Some(10.0)))
+            .unwrap();
+        assert_eq!(
+            reason(orderbooks_manager.add_order(order(buyer, OrderSide::Buy, 6.0, Some(10.0)))),
+            "Risk limit exceeded: max order notional"
+        );
+        // 40 resting + 30 > 60
+        assert_eq!(
+            reason(orderbooks_manager.add_order(order(buyer, OrderSide::Buy, 3.0, Some(10.0)))),
+            "Risk limit exceeded: max resting notional"
+        );
+        assert_eq!(
+            reason(orderbooks_manager.add_order(order(buyer, OrderSide::Buy, 1.0, None))),
+            "Risk limit exceeded: no mark price to value the market order"
+        );
 
-        }
+        // the trade sets the mark price the market orders are valued at
+        orderbooks_manager
+            .add_order(order(seller, OrderSide::Sell, 1.0, Some(10.0)))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(seller, OrderSide::Sell, 2.0, Some(11.0)))
+            .unwrap();
+        assert_eq!(orderbooks_manager.risk.mark_price(symbol), Some(10.0));
+        assert_eq!(
+            orderbooks_manager.risk.resting_notional(buyer, symbol),
+            30.0
+        );
+        assert!(orderbooks_manager
+            .add_order(order(buyer, OrderSide::Buy, 6.0, None))
+            .is_err());
+        orderbooks_manager
+            .add_order(order(buyer, OrderSide::Buy, 2.0, None))
+            .unwrap();
+
+        let metrics = orderbooks_manager.metrics();
+        assert_eq!(metrics.global.orders_notional_throttled, 4);
+        assert_eq!(metrics.global.orders_rejected, 4);
     }
 
-    /// Listen to orderbook updates
-    pub fn listen_orderbook_updates<'a>(&self) -> impl Stream<Item = Order> {
-        let rx = self.rx.clone();
-        stream! {
-                loop {
-                    if let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Update=> {
-                                if let Some(order) = orderbook_update.order {
-                                    yield order;
-                                }
-                            }
-                            _ => {}
+    #[test]
+    fn test_orders_reserve_and_settle_balances() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-                        }
-                    }
+        let symbol = Ulid::new().into();
+        let (base, quote) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(symbol);
+        let accounts = orderbooks_manager.enable_accounts().clone();
+        accounts.register_market(symbol, base, quote);
+        let (buyer, seller) = (Ulid::new().into(), Ulid::new().into());
+        accounts.deposit(buyer, quote, 50.0);
+        accounts.deposit(seller, base, 2.0);
+
+        let buy = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            3.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.add_order(buy).unwrap();
+        assert_eq!(accounts.balance(buyer, quote).reserved, 30.0);
+        let too_big = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            3.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        assert!(orderbooks_manager.add_order(too_big).is_err());
+
+        let sell = Order::new(
+            seller,
+            symbol,
+            OrderSide::Sell,
+            2.0,
+            None,
+            OrderType::Market,
+        );
+        orderbooks_manager.add_order(sell).unwrap();
+        let balances = orderbooks_manager.get_balance(buyer).unwrap();
+        assert_eq!(balances[&base].available, 2.0);
+        assert_eq!(balances[&quote].reserved, 10.0);
+        assert_eq!(accounts.balance(seller, quote).available, 20.0);
+
+        orderbooks_manager
+            .cancel_order(buy.id, symbol, buy.side)
+            .unwrap();
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 30.0,
+                reserved: 0.0
             }
-        }
+        );
     }
 
-    /// Listen to orderbook cancels
-    pub fn listen_orderbook_cancels<'a>(&self) -> impl Stream<Item = u128> {
-        let rx = self.rx.clone();
-        stream! {
+    #[test]
+    fn test_amends_are_validated() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Cancel => {
-                                if let Some(id) = orderbook_update.cancel_id {
-                                    yield id;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook_with_config(
+            symbol,
+            OrderbookConfig::default()
+                .with_tick_size(0.5)
+                .with_lot_size(1.0),
+        );
+        let user = Ulid::new().into();
+        orderbooks_manager.risk.set_limits(
+            user,
+            RiskLimits {
+                max_open_orders: Some(1),
+                max_resting_notional: Some(60.0),
+                ..Default::default()
+            },
+        );
+        let buy = Order::new(
+            user,
+            symbol,
+            OrderSide::Buy,
+            2.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.add_order(buy).unwrap();
 
+        for price in [100.3, 0.0, -1.0, f64::NAN] {
+            let err = orderbooks_manager
+                .amend_order_price(symbol, buy.id, price, buy.side)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        for quantity in [1.7, 0.0, -5.0, f64::NAN] {
+            let err = orderbooks_manager
+                .amend_order_quantity(symbol, buy.id, quantity, buy.side)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
         }
+        // The resting order is checked against the risk limits in place of its previous version
+        orderbooks_manager
+            .amend_order_price(symbol, buy.id, 10.5, buy.side)
+            .unwrap();
+        let err = orderbooks_manager
+            .amend_order_quantity(symbol, buy.id, 7.0, buy.side)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        orderbooks_manager
+            .amend_order_quantity(symbol, buy.id, 5.0, buy.side)
+            .unwrap();
+
+        let order = orderbooks_manager
+            .resting_order(symbol, buy.id, Some(buy.side))
+            .unwrap();
+        assert_eq!((order.price, order.quantity), (Some(10.5), 5.0));
+        assert!(orderbooks_manager
+            .verify_invariants(symbol)
+            .unwrap()
+            .is_ok());
     }
 
-    /// Listen to orderbook fills
-    pub fn listen_orderbook_fills<'a>(&self) -> impl Stream<Item = u128> {
-        let rx = self.rx.clone();
-        stream! {
+    #[test]
+    fn test_amends_resize_reservations() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-                    while let Ok(orderbook_update) = rx.recv() {
-                        match orderbook_update.update_type {
-                            OrderbookUpdateType::Filled => {
-                                if let Some(id) = orderbook_update.filled_id {
-                                    yield id;
-                                }
-                            }
-                            _ => {}
+        let symbol = Ulid::new().into();
+        let (base, quote) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(symbol);
+        let accounts = orderbooks_manager.enable_accounts().clone();
+        accounts.register_market(symbol, base, quote);
+        let (buyer, seller) = (Ulid::new().into(), Ulid::new().into());
+        accounts.deposit(buyer, quote, 100.0);
+        accounts.deposit(seller, base, 10.0);
 
-                        }
-                    }
+        let buy = Order::new(
+            buyer,
+            symbol,
+            OrderSide::Buy,
+            2.0,
+            Some(10.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.add_order(buy).unwrap();
+        let err = orderbooks_manager
+            .amend_order_quantity(symbol, buy.id, 20.0, buy.side)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(orderbooks_manager
+            .amend_order_price(symbol, buy.id, 1000.0, buy.side)
+            .is_err());
+        assert!(orderbooks_manager
+            .replace_order(buy.id, symbol, 20.0, 10.0)
+            .is_err());
+        assert_eq!(
+            orderbooks_manager
+                .resting_order(symbol, buy.id, None)
+                .unwrap()
+                .quantity,
+            2.0
+        );
+        assert_eq!(accounts.balance(buyer, quote).reserved, 20.0);
 
-        }
+        orderbooks_manager
+            .amend_order_quantity(symbol, buy.id, 5.0, buy.side)
+            .unwrap();
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 50.0,
+                reserved: 50.0
+            }
+        );
+        orderbooks_manager
+            .amend_order_price(symbol, buy.id, 4.0, buy.side)
+            .unwrap();
+        assert_eq!(accounts.balance(buyer, quote).reserved, 20.0);
+
+        let sell = Order::new(
+            seller,
+            symbol,
+            OrderSide::Sell,
+            5.0,
+            Some(4.0),
+            OrderType::Limit,
+        );
+        orderbooks_manager.add_order(sell).unwrap();
+        assert_eq!(
+            accounts.balance(buyer, quote),
+            Balance {
+                available: 80.0,
+                reserved: 0.0
+            }
+        );
+        assert_eq!(accounts.balance(buyer, base).available, 5.0);
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[derive(Debug, Default)]
+    struct RecordingObserver {
+        events: std::sync::Mutex<Vec<(&'static str, OrderId)>>,
+    }
 
-    use super::*;
-    use crate::enums::order_type::OrderType;
-    use crate::enums::side::OrderSide;
-    use crate::structs::order::Order;
-    use futures_util::StreamExt;
-    use ulid::Ulid;
+    impl MatchObserver for RecordingObserver {
+        fn on_trade(&self, trade: &Trade) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(("trade", trade.maker_order_id));
+        }
 
-    #[tokio::test]
-    async fn test_listen_to_new_orders() {
+        fn on_fill(&self, _symbol: Symbol, order: &Order) {
+            self.events.lock().unwrap().push(("fill", order.id));
+        }
+
+        fn on_book_change(&self, update: &OrderbookUpdate) {
+            let name = match update.update_type {
+                OrderbookUpdateType::Place => "place",
+                OrderbookUpdateType::Filled => "filled",
+                _ => "other",
+            };
+            self.events
+                .lock()
+                .unwrap()
+                .push((name, update.order.unwrap().id));
+        }
+    }
+
+    #[test]
+    fn test_match_observers_see_the_matching_in_sequence() {
         let mut orderbooks_manager = OrderbooksManager::new();
 
         let symbol = Ulid::new().into();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
-        );
-        let order2 = Order::new(
+        let observer = Arc::new(RecordingObserver::default());
+        orderbooks_manager
+            .add_match_observer(symbol, observer.clone())
+            .unwrap();
+        assert!(orderbooks_manager
+            .add_match_observer(Ulid::new().into(), observer.clone())
+            .is_err());
+
+        let sell = Order::new(
             Ulid::new().into(),
             symbol,
-            OrderSide::Buy,
+            OrderSide::Sell,
             1.0,
-            Some(1.0),
+            Some(10.0),
             OrderType::Limit,
         );
-        let order3 = Order::new(
+        let buy = Order::new(
             Ulid::new().into(),
             symbol,
             OrderSide::Buy,
             1.0,
-            Some(1.0),
+            Some(10.0),
             OrderType::Limit,
         );
+        orderbooks_manager.add_order(sell).unwrap();
+        orderbooks_manager.add_order(buy).unwrap();
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-        let _ = orderbooks_manager.add_order(order3.clone());
+        let events = observer.events.lock().unwrap().clone();
+        assert_eq!(events[0], ("place", sell.id));
+        assert_eq!(events[1], ("place", buy.id));
+        // The fills are published while matching, the trades once the matching is done
+        assert_eq!(events.last(), Some(&("trade", sell.id)));
+        // Both orders are filled, each is reported as a fill and as a book change
+        assert_eq!(events.iter().filter(|e| e.0 == "fill").count(), 2);
+        assert_eq!(events.iter().filter(|e| e.0 == "filled").count(), 2);
+        assert!(orderbooks_manager
+            .verify_invariants(symbol)
+            .unwrap()
+            .is_ok());
+    }
 
-        let mut new_orders_stream = orderbooks_manager.listen_new_orders().boxed();
+    #[test]
+    fn test_engine_metrics() {
+        let mut orderbooks_manager = OrderbooksManager::new();
 
-        let first_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(first_order, order1);
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let _updates = orderbooks_manager.subscribe_updates();
+        let order = |side: OrderSide, quantity: f64| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        let sell = order(OrderSide::Sell, 3.0);
+        orderbooks_manager.add_order(sell).unwrap();
+        orderbooks_manager
+            .add_order(order(OrderSide::Buy, 2.0))
+            .unwrap();
+        assert!(orderbooks_manager
+            .add_order(order(OrderSide::Buy, -1.0))
+            .is_err());
+        let mut unknown = order(OrderSide::Buy, 1.0);
+        unknown.symbol = Ulid::new().into();
+        assert!(orderbooks_manager.add_order(unknown).is_err());
+        orderbooks_manager
+            .cancel_order(sell.id, symbol, sell.side)
+            .unwrap();
 
-        let second_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(second_order, order2);
+        let metrics = orderbooks_manager.metrics();
+        assert_eq!(metrics.global.orders_accepted, 2);
+        assert_eq!(metrics.global.orders_rejected, 2);
+        let symbol_metrics = metrics.symbol(symbol).unwrap();
+        assert_eq!(symbol_metrics.orders_rejected, 1);
+        assert_eq!(symbol_metrics.trades, 1);
+        assert_eq!(symbol_metrics.matched_volume, 2.0);
+        assert_eq!(symbol_metrics.cancel_rate(), 0.5);
+        assert_eq!(symbol_metrics.match_latency.count, 2);
+        assert_eq!(metrics.channel_depth, 0);
+        assert!(metrics.subscriber_depth > 0);
+        // Siblings share the counters
+        assert_eq!(orderbooks_manager.sibling().metrics().global.trades, 1);
+    }
 
-        let third_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(third_order, order3);
+    #[test]
+    fn test_reference_price_in_summaries() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let view = orderbooks_manager.view(symbol).unwrap();
+
+        let error = orderbooks_manager
+            .set_reference_price(Ulid::new().into(), 10.0)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let error = orderbooks_manager
+            .set_reference_price(symbol, 0.0)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(orderbooks_manager.reference_price(symbol), None);
+
+        orderbooks_manager
+            .set_reference_price(symbol, 10.0)
+            .unwrap();
+        let summary = orderbooks_manager.get_orderbook(symbol).unwrap();
+        assert_eq!(summary.reference_price, Some(10.0));
+        assert_eq!(view.summary(), summary);
+
+        // the next trade moves the reference price of the default source
+        for side in [OrderSide::Sell, OrderSide::Buy] {
+            orderbooks_manager
+                .add_order(Order::new(
+                    Ulid::new().into(),
+                    symbol,
+                    side,
+                    1.0,
+                    Some(11.0),
+                    OrderType::Limit,
+                ))
+                .unwrap();
+        }
+        assert_eq!(orderbooks_manager.reference_price(symbol), Some(11.0));
+        assert_eq!(view.summary().reference_price, Some(11.0));
     }
 
-    #[tokio::test]
-    async fn test_listen_to_placed_orders() {
+    #[test]
+    fn test_internal_summaries_show_the_hidden_liquidity() {
         let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = |quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Sell,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager
+            .add_order(order(10.0, 11.0).with_display_quantity(2.0))
+            .unwrap();
+        orderbooks_manager.add_order(order(1.0, 11.0)).unwrap();
+        orderbooks_manager.add_order(order(1.0, 12.0)).unwrap();
+
+        let public = orderbooks_manager.get_orderbook(symbol).unwrap();
+        assert!(public.ask_liquidity.is_empty());
+        assert_eq!(public.asks[0].qty, 3.0);
+        let internal = orderbooks_manager
+            .get_orderbook_with_privacy(symbol, SummaryPrivacy::Internal)
+            .unwrap();
+        assert_eq!(internal.asks, public.asks);
+        let best = internal.ask_liquidity[0];
+        assert_eq!(
+            (best.price, best.visible_qty, best.total_qty, best.icebergs),
+            (11.0, 3.0, 11.0, 1)
+        );
+        assert_eq!(internal.ask_liquidity[1].hidden_ratio(), 0.0);
+        assert!(internal.bid_liquidity.is_empty());
+    }
 
+    #[test]
+    fn test_order_latency_stages() {
+        let clock = crate::structs::clock::MockClock::new(10_000);
+        let mut orderbooks_manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
         let symbol = Ulid::new().into();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
+        let order = || {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        let ack = orderbooks_manager
+            .add_order_received(order(), 4_000)
+            .unwrap();
+        assert_eq!(ack.latency, None);
+
+        orderbooks_manager.ack_latency = true;
+        let ack = orderbooks_manager.add_order(order()).unwrap();
+        assert_eq!(ack.latency, Some(OrderLatency::default()));
+        let stages = orderbooks_manager
+            .metrics()
+            .symbol(symbol)
+            .unwrap()
+            .stage_latencies
+            .clone();
+        assert_eq!(stages.queued.count, 2);
+        assert_eq!(stages.queued.max, 6_000);
+        assert_eq!(stages.total.value_at_quantile(0.5), 0);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_order_entry_is_traced() {
+        use std::sync::Mutex;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        /// Subscriber keeping the names of the spans and the fields of the events
+        #[derive(Default)]
+        struct Collector {
+            spans: Mutex<Vec<String>>,
+            fields: Mutex<Vec<String>>,
+        }
+
+        impl Visit for &Collector {
+            fn record_debug(&mut self, field: &Field, _value: &dyn std::fmt::Debug) {
+                self.fields.lock().unwrap().push(field.name().to_string());
+            }
+        }
+
+        impl tracing::Subscriber for Collector {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.spans.lock().unwrap();
+                spans.push(span.metadata().name().to_string());
+                Id::from_u64(spans.len() as u64)
+            }
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut &*self);
+            }
+            fn enter(&self, _span: &Id) {}
+            fn exit(&self, _span: &Id) {}
+        }
+
+        let collector = Arc::new(Collector::default());
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = Order::new(
             Ulid::new().into(),
             symbol,
             OrderSide::Buy,
@@ -434,7 +4363,30 @@ mod tests {
             Some(1.0),
             OrderType::Limit,
         );
-        let order2 = Order::new(
+        tracing::subscriber::with_default(collector.clone(), || {
+            orderbooks_manager.add_order(order).unwrap();
+            orderbooks_manager
+                .cancel_order(order.id, symbol, order.side)
+                .unwrap();
+        });
+
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(*spans, vec!["add_order", "cancel_order"]);
+        let fields = collector.fields.lock().unwrap();
+        for field in ["trades", "latency_ns", "filled_quantity", "status"] {
+            assert!(fields.iter().any(|f| f == field), "{} not traced", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_orderbook() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        let other = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        orderbooks_manager.new_orderbook(other);
+        let order = Order::new(
             Ulid::new().into(),
             symbol,
             OrderSide::Buy,
@@ -442,304 +4394,1123 @@ mod tests {
             Some(1.0),
             OrderType::Limit,
         );
-        let order3 = Order::new(
+        orderbooks_manager.add_order(order).unwrap();
+        let mut updates = orderbooks_manager.subscribe().symbol(symbol).stream();
+
+        let cancelled = orderbooks_manager.remove_orderbook(symbol).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(orderbooks_manager.list_symbols(), vec![other]);
+        assert!(orderbooks_manager.add_order(order).is_err());
+        assert!(orderbooks_manager.remove_orderbook(symbol).is_err());
+
+        let update = updates.next().await.unwrap();
+        assert_eq!(update.cancel_id, Some(order.id));
+        let update = updates.next().await.unwrap();
+        assert_eq!(update.update_type, OrderbookUpdateType::Delisted);
+    }
+
+    #[tokio::test]
+    async fn test_orderbook_config() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook_with_config(
+            symbol,
+            OrderbookConfig::default()
+                .with_tick_size(0.5)
+                .with_lot_size(1.0)
+                .with_max_orders(2)
+                .with_fees(FeeSchedule::new(-0.001, 0.002))
+                .with_self_trade_prevention(SelfTradePrevention::CancelTaker),
+        );
+        let user = Ulid::new().into();
+        let order = |side: OrderSide, quantity: f64, price: f64| {
+            Order::new(user, symbol, side, quantity, Some(price), OrderType::Limit)
+        };
+        assert!(orderbooks_manager
+            .add_order(order(OrderSide::Buy, 1.0, 1.2))
+            .is_err());
+        assert!(orderbooks_manager
+            .add_order(order(OrderSide::Buy, 1.5, 1.0))
+            .is_err());
+
+        let mut updates = orderbooks_manager.subscribe().stream();
+        let bid = order(OrderSide::Buy, 2.0, 1.0);
+        orderbooks_manager.add_order(bid).unwrap();
+        // Same user, the incoming order is cancelled
+        let ask = order(OrderSide::Sell, 2.0, 1.0);
+        orderbooks_manager.add_order(ask).unwrap();
+        let other = Order::new(
             Ulid::new().into(),
             symbol,
-            OrderSide::Buy,
-            1.0,
+            OrderSide::Sell,
+            2.0,
             Some(1.0),
             OrderType::Limit,
         );
+        orderbooks_manager.add_order(other).unwrap();
+
+        let cancel = loop {
+            let update = updates.next().await.unwrap();
+            if update.update_type == OrderbookUpdateType::Cancel {
+                break update;
+            }
+        };
+        assert_eq!(cancel.cancel_id, Some(ask.id));
+        let trade = loop {
+            if let Some(trade) = updates.next().await.unwrap().trade {
+                break trade;
+            }
+        };
+        assert_eq!(
+            (trade.buy_order_id, trade.sell_order_id),
+            (bid.id, other.id)
+        );
+        assert_eq!((trade.maker_fee, trade.taker_fee), (-0.002, 0.004));
+
+        orderbooks_manager
+            .add_order(order(OrderSide::Buy, 1.0, 0.5))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(OrderSide::Buy, 1.0, 0.5))
+            .unwrap();
+        assert_eq!(
+            orderbooks_manager
+                .add_order(order(OrderSide::Buy, 1.0, 0.5))
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn test_migrate_orders() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let (from, to, coarse): (Symbol, Symbol, Symbol) =
+            (Ulid::new().into(), Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(from);
+        orderbooks_manager
+            .new_orderbook_with_config(to, OrderbookConfig::default().with_tick_size(0.5));
+        orderbooks_manager
+            .new_orderbook_with_config(coarse, OrderbookConfig::default().with_tick_size(1.0));
+        let order = |side: OrderSide, price: f64| {
+            Order::new(
+                Ulid::new().into(),
+                from,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let orders = [
+            order(OrderSide::Buy, 10.0),
+            order(OrderSide::Sell, 11.0),
+            order(OrderSide::Buy, 10.0),
+        ];
+        for order in orders {
+            orderbooks_manager.add_order(order).unwrap();
+        }
+        let updates = orderbooks_manager.subscribe_updates();
+
+        // 5.5 is not on the tick size of the coarse orderbook, nothing moves
+        let error = orderbooks_manager
+            .migrate_orders(from, coarse, 0.5)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(orderbooks_manager.orderbooks[&from].bids.len(), 2);
+
+        // a 2-for-1 split halves the prices and doubles the quantities
+        let migrated = orderbooks_manager.migrate_orders(from, to, 0.5).unwrap();
+        let ids: Vec<OrderId> = migrated.iter().map(|o| o.id).collect();
+        assert_eq!(ids, orders.map(|o| o.id));
+        let orderbook = &orderbooks_manager.orderbooks[&to];
+        let best_bid = orderbook.bids.peek().unwrap();
+        assert_eq!(best_bid.id, orders[0].id);
+        assert_eq!(
+            (best_bid.symbol, best_bid.price, best_bid.quantity),
+            (to, Some(5.0), 2.0)
+        );
+        assert_eq!(orderbook.asks.peek().unwrap().price, Some(5.5));
+        assert!(orderbooks_manager.orderbooks[&from].bids.is_empty());
+        let migrations = std::iter::from_fn(|| updates.try_recv().ok())
+            .filter(|u| u.update_type == OrderbookUpdateType::Migrated)
+            .inspect(|u| assert_eq!(u.symbol, from))
+            .count();
+        assert_eq!(migrations, 3);
+
+        let error = orderbooks_manager
+            .migrate_orders(coarse, to, 1.0)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_order_acks() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let order = |side: OrderSide, quantity: f64, price: Option<f64>, order_type: OrderType| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                price,
+                order_type,
+            )
+        };
+
+        let market = order(OrderSide::Buy, 1.0, None, OrderType::Market);
+        let ack = orderbooks_manager.add_order(market).unwrap();
+        assert_eq!(ack.status, OrderStatus::Cancelled);
+        assert_eq!(ack.reject_reason.as_deref(), Some("No liquidity"));
+
+        let ask = order(OrderSide::Sell, 2.0, Some(1.0), OrderType::Limit).with_client_order_id(7);
+        let ack = orderbooks_manager.add_order(ask).unwrap();
+        assert_eq!(
+            (ack.order_id, ack.client_order_id, ack.status),
+            (ask.id, Some(7), OrderStatus::Open)
+        );
+        assert!(!ack.is_rejected());
+
+        let bid = order(OrderSide::Buy, 1.0, Some(1.0), OrderType::Limit);
+        let ack = orderbooks_manager.add_order(bid).unwrap();
+        assert_eq!(
+            (ack.status, ack.filled_quantity),
+            (OrderStatus::Filled, 1.0)
+        );
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-        let _ = orderbooks_manager.add_order(order3.clone());
+        let ack = orderbooks_manager
+            .cancel_order(ask.id, symbol, ask.side)
+            .unwrap();
+        assert_eq!(
+            (ack.client_order_id, ack.status),
+            (Some(7), OrderStatus::Cancelled)
+        );
+        let error = orderbooks_manager
+            .cancel_order(ask.id, symbol, ask.side)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let error = orderbooks_manager
+            .amend_order_price(symbol, ask.id, 2.0, ask.side)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
 
-        let mut new_orders_stream = orderbooks_manager.listen_placed_orders().boxed();
+    #[test]
+    fn test_baskets() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let (first, second): (Symbol, Symbol) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(first);
+        orderbooks_manager.new_orderbook(second);
+        let order = |symbol, side, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let events = orderbooks_manager.subscribe_basket_events();
 
-        let first_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(first_order, order1);
+        // One invalid leg rejects the whole basket
+        let invalid = order(second, OrderSide::Sell, -1.0);
+        assert!(orderbooks_manager
+            .submit_basket(1, vec![order(first, OrderSide::Buy, 10.0), invalid])
+            .is_err());
+        assert!(orderbooks_manager.orderbooks[&first].bids.is_empty());
 
-        let second_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(second_order, order2);
+        let buy = order(first, OrderSide::Buy, 10.0);
+        let sell = order(second, OrderSide::Sell, 20.0);
+        let acks = orderbooks_manager
+            .submit_basket(1, vec![buy, sell])
+            .unwrap();
+        assert_eq!(acks.len(), 2);
+        assert_eq!(
+            orderbooks_manager
+                .submit_basket(1, vec![order(first, OrderSide::Buy, 10.0)])
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
 
-        let third_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(third_order, order3);
+        orderbooks_manager
+            .add_order(order(first, OrderSide::Sell, 10.0))
+            .unwrap();
+        let basket = orderbooks_manager.get_basket(1).unwrap();
+        assert_eq!(basket.status(), OrderStatus::PartiallyFilled);
+        assert_eq!(basket.filled_quantity(), 1.0);
+
+        // The remaining leg is pulled, the basket is done
+        let cancelled = orderbooks_manager.cancel_basket(1).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].id, sell.id);
+        assert!(orderbooks_manager.orderbooks[&second].asks.is_empty());
+        assert!(orderbooks_manager.get_basket(1).is_err());
+        let events: Vec<_> = events.try_iter().collect();
+        assert_eq!(events[0].order_id, None);
+        let last = events.last().unwrap();
+        assert_eq!(
+            (last.order_id, last.status),
+            (Some(sell.id), OrderStatus::Cancelled)
+        );
+        assert_eq!(last.basket.legs[0].status, OrderStatus::Filled);
     }
 
-    #[tokio::test]
-    async fn test_listen_to_orderbook_summary() {
+    #[test]
+    fn test_spread_orders_trade_both_legs() {
         let mut orderbooks_manager = OrderbooksManager::new();
+        let (leg_a, leg_b, spread): (Symbol, Symbol, Symbol) =
+            (Ulid::new().into(), Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(leg_a);
+        orderbooks_manager.new_orderbook(leg_b);
+        orderbooks_manager
+            .define_spread(spread, leg_a, leg_b)
+            .unwrap();
+        assert!(orderbooks_manager
+            .define_spread(leg_a, leg_a, leg_b)
+            .is_err());
+        let order = |symbol, side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                price,
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager
+            .add_order(order(leg_a, OrderSide::Sell, 2.0, Some(100.0)))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(leg_b, OrderSide::Buy, 1.0, Some(95.0)))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(leg_b, OrderSide::Buy, 5.0, Some(94.0)))
+            .unwrap();
+        let (bid, ask) = orderbooks_manager.implied_prices(spread).unwrap();
+        assert!(bid.is_none());
+        let ask = ask.unwrap();
+        assert_eq!((ask.price, ask.quantity), (5.0, 1.0));
 
-        let symbol = Ulid::new().into();
-        orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
-        );
-        let order2 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(2.0),
-            OrderType::Limit,
+        // Buying the spread buys A and sells B, walking the implied prices up to the limit
+        let buy = order(spread, OrderSide::Buy, 2.0, Some(6.0));
+        let result = orderbooks_manager.add_spread_order(buy).unwrap();
+        assert_eq!(result.ack.status, OrderStatus::Filled);
+        let prices: Vec<_> = result
+            .executions
+            .iter()
+            .map(|e| (e.price, e.leg_a_price, e.leg_b_price, e.quantity))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![(5.0, 100.0, 95.0, 1.0), (6.0, 100.0, 94.0, 1.0)]
         );
-        let order3 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(3.0),
-            OrderType::Limit,
+        assert!(orderbooks_manager.orderbooks[&leg_a].asks.is_empty());
+        assert_eq!(
+            orderbooks_manager.orderbooks[&leg_b]
+                .bids
+                .peek()
+                .unwrap()
+                .quantity,
+            4.0
         );
+        assert_eq!(orderbooks_manager.risk.position(buy.user_id, leg_a), 2.0);
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-        let _ = orderbooks_manager.add_order(order3.clone());
+        // Nothing left to buy A from: the spread order is cancelled, no leg rests
+        let result = orderbooks_manager
+            .add_spread_order(order(spread, OrderSide::Buy, 1.0, None))
+            .unwrap();
+        assert_eq!(result.ack.status, OrderStatus::Cancelled);
+        assert!(result.executions.is_empty());
+        assert!(orderbooks_manager.orderbooks[&leg_a].bids.is_empty());
+    }
 
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_summary().boxed();
+    #[test]
+    fn test_spread_legs_report_what_each_traded() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let (leg_a, leg_b, spread): (Symbol, Symbol, Symbol) =
+            (Ulid::new().into(), Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(leg_a);
+        orderbooks_manager.new_orderbook_with_config(
+            leg_b,
+            OrderbookConfig::default().with_self_trade_prevention(SelfTradePrevention::CancelTaker),
+        );
+        orderbooks_manager
+            .define_spread(spread, leg_a, leg_b)
+            .unwrap();
+        let trader = Ulid::new().into();
+        let order = |user_id, symbol, side, quantity, price| {
+            Order::new(user_id, symbol, side, quantity, price, OrderType::Limit)
+        };
+        orderbooks_manager
+            .add_order(order(
+                Ulid::new().into(),
+                leg_a,
+                OrderSide::Sell,
+                3.0,
+                Some(100.0),
+            ))
+            .unwrap();
+        let own = order(trader, leg_b, OrderSide::Buy, 1.0, Some(95.0));
+        orderbooks_manager.add_order(own).unwrap();
+        orderbooks_manager
+            .add_order(order(
+                Ulid::new().into(),
+                leg_b,
+                OrderSide::Buy,
+                2.0,
+                Some(95.0),
+            ))
+            .unwrap();
 
-        let summary = new_orders_stream.next().await.unwrap();
+        // Leg B would hit the own order of the trader first: neither leg is sent
+        let result = orderbooks_manager
+            .add_spread_order(order(trader, spread, OrderSide::Buy, 2.0, None))
+            .unwrap();
+        assert_eq!(result.ack.status, OrderStatus::Cancelled);
+        assert!(result.executions.is_empty());
         assert_eq!(
-            summary.bids[0],
-            BidAskSummarize {
-                price: 3.0,
-                qty: 1.0,
-                qty_sum: 3.0,
-                qty_percent: 33.33333333333333
-            }
+            orderbooks_manager.orderbooks[&leg_a]
+                .asks
+                .peek()
+                .unwrap()
+                .quantity,
+            3.0
         );
+
+        // Leg B is refused once leg A traded, leg A is reported alone
+        orderbooks_manager
+            .cancel_order(own.id, leg_b, own.side)
+            .unwrap();
+        orderbooks_manager.rate_limiter.set_limit(
+            trader,
+            crate::risk::rate_limit::RateLimit {
+                rate: 0.001,
+                burst: 1.0,
+            },
+        );
+        let result = orderbooks_manager
+            .add_spread_order(order(trader, spread, OrderSide::Buy, 2.0, None))
+            .unwrap();
+        assert_eq!(result.ack.status, OrderStatus::Cancelled);
+        assert_eq!(result.ack.filled_quantity, 0.0);
         assert_eq!(
-            summary.bids[1],
-            BidAskSummarize {
-                price: 2.0,
-                qty: 1.0,
-                qty_sum: 2.0,
-                qty_percent: 33.33333333333333
-            }
+            result.ack.reject_reason.as_deref(),
+            Some("Leg B traded less than leg A")
         );
+        let execution = result.executions[0];
         assert_eq!(
-            summary.bids[2],
-            BidAskSummarize {
-                price: 1.0,
-                qty: 1.0,
-                qty_sum: 1.0,
-                qty_percent: 33.33333333333333
-            }
+            (
+                execution.quantity,
+                execution.leg_a_quantity,
+                execution.leg_b_quantity
+            ),
+            (0.0, 2.0, 0.0)
         );
+        assert_eq!(orderbooks_manager.risk.position(trader, leg_a), 2.0);
+        assert_eq!(orderbooks_manager.risk.position(trader, leg_b), 0.0);
     }
 
-    #[tokio::test]
-    async fn test_listen_to_orderbook_summary_sell() {
+    #[test]
+    fn test_conditional_orders_held_until_met() {
         let mut orderbooks_manager = OrderbooksManager::new();
-
-        let symbol = Ulid::new().into();
+        let symbol: Symbol = Ulid::new().into();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Sell,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
-        );
-        let order2 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Sell,
-            1.0,
-            Some(2.0),
-            OrderType::Limit,
-        );
-        let order3 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Sell,
-            1.0,
-            Some(3.0),
-            OrderType::Limit,
+        let order = |side, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager
+            .add_order(order(OrderSide::Sell, 100.0))
+            .unwrap();
+        let held = order(OrderSide::Buy, 100.0);
+        let ack = orderbooks_manager
+            .add_conditional_order(held, BookCondition::SpreadBelow(1.0))
+            .unwrap();
+        assert_eq!(ack.status, OrderStatus::Pending);
+        let other = order(OrderSide::Buy, 100.0);
+        orderbooks_manager
+            .add_conditional_order(other, BookCondition::MidAtMost(1.0))
+            .unwrap();
+
+        // a bid at 99.5 narrows the spread, the held order takes the offer
+        orderbooks_manager
+            .add_order(order(OrderSide::Buy, 99.5))
+            .unwrap();
+        assert!(orderbooks_manager.orderbooks[&symbol].asks.is_empty());
+        assert_eq!(
+            orderbooks_manager
+                .cancel_conditional_order(other.id, symbol)
+                .unwrap()
+                .id,
+            other.id
         );
+        assert!(orderbooks_manager
+            .cancel_conditional_order(held.id, symbol)
+            .is_err());
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-        let _ = orderbooks_manager.add_order(order3.clone());
+        // met on arrival, the order is added at once
+        let ack = orderbooks_manager
+            .add_conditional_order(
+                order(OrderSide::Sell, 99.5),
+                BookCondition::BidVolumeAbove(0.5),
+            )
+            .unwrap();
+        assert_eq!(ack.status, OrderStatus::Filled);
+    }
 
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_summary().boxed();
+    #[test]
+    fn test_day_orders_expire_at_the_session_close() {
+        const HOUR: u64 = 3_600_000;
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol: Symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let hours = TradingHours::new(9 * HOUR, 17 * HOUR).with_closing_auction(HOUR / 4);
+        orderbooks_manager.set_trading_hours(symbol, hours).unwrap();
+        assert!(orderbooks_manager
+            .set_trading_hours(Ulid::new().into(), hours)
+            .is_err());
+        let transitions = orderbooks_manager.subscribe_session_transitions();
+        let order = |side, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
 
-        let summary = new_orders_stream.next().await.unwrap();
+        let day = 20_000 * crate::structs::trading_calendar::DAY_MILLIS;
         assert_eq!(
-            summary.asks[0],
-            BidAskSummarize {
-                price: 1.0,
-                qty: 1.0,
-                qty_sum: 1.0,
-                qty_percent: 33.33333333333333
-            }
+            orderbooks_manager.run_sessions_at(day + 8 * HOUR)[0].phase,
+            SessionPhase::Closed
         );
+        assert!(orderbooks_manager
+            .add_order(order(OrderSide::Buy, 100.0))
+            .is_err());
+        orderbooks_manager.run_sessions_at(day + 9 * HOUR);
+        assert!(orderbooks_manager
+            .run_sessions_at(day + 10 * HOUR)
+            .is_empty());
+        let gtc = order(OrderSide::Buy, 99.0);
+        let day_bid = order(OrderSide::Buy, 98.0).with_time_in_force(TimeInForce::Day);
+        orderbooks_manager.add_order(gtc).unwrap();
+        orderbooks_manager.add_order(day_bid).unwrap();
+
+        // the crossing orders of the closing auction call wait for the close
+        orderbooks_manager.run_sessions_at(day + 17 * HOUR - HOUR / 8);
+        orderbooks_manager
+            .add_order(order(OrderSide::Sell, 99.0))
+            .unwrap();
+        assert_eq!(orderbooks_manager.orderbooks[&symbol].asks.len(), 1);
+        let closed = orderbooks_manager.run_sessions_at(day + 17 * HOUR);
+        assert_eq!(closed[0].auction.unwrap().volume, 1.0);
+        assert_eq!(closed[0].expired.len(), 1);
+        assert_eq!(closed[0].expired[0].id, day_bid.id);
+        assert!(orderbooks_manager.orderbooks[&symbol].bids.is_empty());
         assert_eq!(
-            summary.asks[1],
-            BidAskSummarize {
-                price: 2.0,
-                qty: 1.0,
-                qty_sum: 2.0,
-                qty_percent: 33.33333333333333
-            }
+            orderbooks_manager.orderbooks[&symbol].state,
+            OrderbookState::CancelOnly
         );
+        let phases: Vec<SessionPhase> = transitions.try_iter().map(|t| t.phase).collect();
         assert_eq!(
-            summary.asks[2],
-            BidAskSummarize {
-                price: 3.0,
-                qty: 1.0,
-                qty_sum: 3.0,
-                qty_percent: 33.33333333333333
-            }
+            phases,
+            vec![
+                SessionPhase::Closed,
+                SessionPhase::Open,
+                SessionPhase::ClosingAuction,
+                SessionPhase::Closed
+            ]
         );
     }
 
-    #[tokio::test]
-    async fn test_listen_to_orderbook_updates_amend_price() {
+    #[test]
+    fn test_routed_orders_split_across_books() {
         let mut orderbooks_manager = OrderbooksManager::new();
+        let (venue, tier, instrument): (Symbol, Symbol, Symbol) =
+            (Ulid::new().into(), Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(venue);
+        orderbooks_manager.new_orderbook_with_config(
+            tier,
+            OrderbookConfig::default().with_fees(FeeSchedule::new(-0.001, 0.001)),
+        );
+        assert_eq!(
+            orderbooks_manager
+                .add_route(instrument, vec![venue, Ulid::new().into()])
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::NotFound
+        );
+        orderbooks_manager
+            .add_route(instrument, vec![venue, tier])
+            .unwrap();
+        let order = |symbol, side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager
+            .add_order(order(venue, OrderSide::Sell, 1.0, 101.0))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(tier, OrderSide::Sell, 1.0, 100.0))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(tier, OrderSide::Sell, 1.0, 102.0))
+            .unwrap();
+        let events = orderbooks_manager.subscribe_parent_events();
 
-        let symbol = Ulid::new().into();
-        orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
+        // the cheapest levels of both books are taken, the rest rests on the book with the rebate
+        let parent = order(instrument, OrderSide::Buy, 3.0, 101.0);
+        let acks = orderbooks_manager.route_order(parent).unwrap();
+        assert_eq!(acks.len(), 2);
+        assert_eq!(acks[0].status, OrderStatus::Filled);
+        assert_eq!(acks[1].filled_quantity, 1.0);
+        assert_eq!(acks[1].client_order_id, Some(parent.id.into()));
+        let routed = orderbooks_manager.get_routed_order(parent.id).unwrap();
+        assert_eq!(routed.status(), OrderStatus::PartiallyFilled);
+        assert_eq!(routed.filled_quantity(), 2.0);
+        assert_eq!(routed.average_price(), Some(100.5));
+        let resting = orderbooks_manager.orderbooks[&tier].bids.peek().unwrap();
+        assert_eq!((resting.quantity, resting.price), (1.0, Some(101.0)));
+
+        orderbooks_manager
+            .add_order(order(tier, OrderSide::Sell, 1.0, 101.0))
+            .unwrap();
+        assert!(orderbooks_manager.get_routed_order(parent.id).is_err());
+        let last = events.try_iter().last().unwrap();
+        assert_eq!(last.status, OrderStatus::Filled);
+        assert_eq!(last.parent.average_price(), Some(302.0 / 3.0));
+
+        // no bid to route a market sell to
+        let market = Order::new(
             Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
+            instrument,
+            OrderSide::Sell,
             1.0,
-            Some(1.0),
-            OrderType::Limit,
+            None,
+            OrderType::Market,
+        );
+        assert_eq!(
+            orderbooks_manager.route_order(market).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
         );
+    }
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.amend_order_price(symbol, order1.id, 50.0, order1.side);
+    #[test]
+    fn test_positions_and_user_trades() {
+        let clock = crate::structs::clock::MockClock::new(1_000);
+        let mut orderbooks_manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let (alice, bob) = (Ulid::new().into(), Ulid::new().into());
+        let order = |user_id, side, quantity, price| {
+            Order::new(
+                user_id,
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager
+            .add_order(order(alice, OrderSide::Buy, 3.0, 100.0))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(bob, OrderSide::Sell, 3.0, 100.0))
+            .unwrap();
+        clock.set(2_000);
+        orderbooks_manager
+            .add_order(order(bob, OrderSide::Buy, 1.0, 110.0))
+            .unwrap();
+        orderbooks_manager
+            .add_order(order(alice, OrderSide::Sell, 1.0, 110.0))
+            .unwrap();
 
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_updates().boxed();
+        let position = orderbooks_manager.get_position(alice, symbol);
+        assert_eq!((position.quantity, position.average_price), (2.0, 100.0));
+        assert_eq!(position.realized_pnl, 10.0);
+        let position = orderbooks_manager.get_position(bob, symbol);
+        assert_eq!((position.quantity, position.realized_pnl), (-2.0, -10.0));
+        assert_eq!(
+            orderbooks_manager
+                .get_position(alice, Ulid::new().into())
+                .quantity,
+            0.0
+        );
 
-        let first_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(first_order.price, Some(50.0));
+        assert_eq!(orderbooks_manager.get_user_trades(alice, 0).len(), 2);
+        let trades = orderbooks_manager.get_user_trades(bob, 2_000);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, 110.0);
     }
 
-    #[tokio::test]
-    async fn test_listen_to_orderbook_updates_amend_quantity() {
-        let mut orderbooks_manager = OrderbooksManager::new();
-
+    #[test]
+    fn test_rate_limited_orders_are_rejected() {
+        let clock = crate::structs::clock::MockClock::new(0);
+        let mut orderbooks_manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
         let symbol = Ulid::new().into();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
-            Ulid::new().into(),
-            symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
+        let (flooder, other) = (UserId(1), UserId(2));
+        orderbooks_manager.rate_limiter.set_limit(
+            flooder,
+            crate::risk::rate_limit::RateLimit {
+                rate: 2.0,
+                burst: 2.0,
+            },
         );
+        let order = |user_id| {
+            Order::new(
+                user_id,
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        orderbooks_manager.add_order(order(flooder)).unwrap();
+        orderbooks_manager.add_order(order(flooder)).unwrap();
+        let error = orderbooks_manager.add_order(order(flooder)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        orderbooks_manager.add_order(order(other)).unwrap();
+        clock.advance(Duration::from_millis(500));
+        orderbooks_manager.add_order(order(flooder)).unwrap();
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.amend_order_quantity(symbol, order1.id, 10.0, order1.side);
+        let metrics = orderbooks_manager.metrics();
+        assert_eq!(metrics.global.orders_rate_limited, 1);
+        assert_eq!(metrics.global.orders_rejected, 1);
+        assert_eq!(metrics.symbol(symbol).unwrap().orders_accepted, 4);
+        assert_eq!(orderbooks_manager.rate_limiter.throttled(flooder), 1);
+    }
 
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_updates().boxed();
+    #[test]
+    fn test_message_to_trade_ratio_throttles_the_user() {
+        let clock = crate::structs::clock::MockClock::new(0);
+        let mut orderbooks_manager = OrderbooksManager::with_clock(Arc::new(clock.clone()));
+        let symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        orderbooks_manager.message_ratios.set_policy(Some(
+            crate::risk::message_ratio::MessageRatioPolicy {
+                window: Duration::from_secs(1),
+                max_ratio: 2.0,
+                min_messages: 3,
+                action: crate::enums::ratio_action::RatioAction::Throttle,
+            },
+        ));
+        let subscription = orderbooks_manager.subscribe_updates();
+        let user = UserId(1);
+        let order = |price| {
+            Order::new(
+                user,
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let first = order(1.0);
+        orderbooks_manager.add_order(first).unwrap();
+        orderbooks_manager.add_order(order(2.0)).unwrap();
+        orderbooks_manager
+            .cancel_order(first.id, symbol, first.side)
+            .unwrap();
+        assert!(orderbooks_manager.add_order(order(3.0)).is_err());
 
-        let first_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(first_order.quantity, 10.0);
+        let compliance: Vec<ComplianceEvent> = std::iter::from_fn(|| subscription.try_recv().ok())
+            .filter_map(|u| u.compliance)
+            .collect();
+        assert_eq!(compliance.len(), 1);
+        assert!(compliance[0].breached);
+        assert_eq!(compliance[0].ratio.messages, 3);
+
+        // the messages leave the window and the user is let through
+        clock.advance(Duration::from_secs(2));
+        orderbooks_manager.add_order(order(3.0)).unwrap();
+        let released = std::iter::from_fn(|| subscription.try_recv().ok())
+            .find_map(|u| u.compliance)
+            .unwrap();
+        assert!(!released.breached);
+        assert_eq!(orderbooks_manager.metrics().global.orders_rejected, 1);
+    }
+
+    #[test]
+    fn test_kill_switches_block_the_flow() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let (symbol, other_symbol) = (Symbol(1), Symbol(2));
+        orderbooks_manager.new_orderbook(symbol);
+        orderbooks_manager.new_orderbook(other_symbol);
+        let events = orderbooks_manager.subscribe_kill_switch_events();
+        let (trader, other) = (UserId(1), UserId(2));
+        let order = |user_id, symbol| {
+            Order::new(
+                user_id,
+                symbol,
+                OrderSide::Buy,
+                1.0,
+                Some(10.0),
+                OrderType::Limit,
+            )
+        };
+        for o in [
+            order(trader, symbol),
+            order(trader, other_symbol),
+            order(other, symbol),
+        ] {
+            orderbooks_manager.add_order(o).unwrap();
+        }
+
+        let cancelled = orderbooks_manager.kill_switch_user(trader);
+        assert_eq!(cancelled.len(), 2);
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.target, KillSwitchTarget::User(trader));
+        assert!(event.engaged);
+        assert_eq!(event.cancelled_orders, cancelled);
+        assert!(events.try_recv().is_err());
+        assert!(orderbooks_manager
+            .add_order(order(trader, other_symbol))
+            .is_err());
+
+        let cancelled = orderbooks_manager.kill_switch_symbol(symbol).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].user_id, other);
+        assert!(orderbooks_manager.add_order(order(other, symbol)).is_err());
+        orderbooks_manager
+            .add_order(order(other, other_symbol))
+            .unwrap();
+        assert!(orderbooks_manager.kill_switch_symbol(Symbol(3)).is_err());
+
+        assert!(orderbooks_manager.release_kill_switch_user(trader));
+        assert!(!orderbooks_manager.release_kill_switch_user(trader));
+        orderbooks_manager
+            .add_order(order(trader, other_symbol))
+            .unwrap();
+        assert!(orderbooks_manager.release_kill_switch_symbol(symbol));
+        orderbooks_manager.add_order(order(other, symbol)).unwrap();
+        let released: Vec<KillSwitchEvent> = events.try_iter().collect();
+        assert_eq!(released.len(), 3);
+        assert!(!released[1].engaged && !released[2].engaged);
+    }
+
+    #[test]
+    fn test_shutdown_closes_the_streams() {
+        for policy in [ShutdownPolicy::Cancel, ShutdownPolicy::Persist] {
+            let mut orderbooks_manager = OrderbooksManager::new();
+            orderbooks_manager.shutdown_policy = policy;
+            let symbol = Symbol(1);
+            orderbooks_manager.new_orderbook(symbol);
+            let order = |side, price| {
+                Order::new(UserId(1), symbol, side, 1.0, Some(price), OrderType::Limit)
+            };
+            orderbooks_manager
+                .add_order(order(OrderSide::Buy, 9.0))
+                .unwrap();
+            orderbooks_manager
+                .add_order(order(OrderSide::Sell, 11.0))
+                .unwrap();
+            let subscription = orderbooks_manager.subscribe_updates();
+
+            let report = orderbooks_manager.shutdown().unwrap();
+            let error = orderbooks_manager
+                .add_order(order(OrderSide::Buy, 9.0))
+                .unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+
+            // the stream ends after the Shutdown update
+            let updates: Vec<OrderbookUpdate> =
+                std::iter::from_fn(|| subscription.recv().ok()).collect();
+            assert_eq!(
+                updates.last().unwrap().update_type,
+                OrderbookUpdateType::Shutdown
+            );
+            let cancels = updates
+                .iter()
+                .filter(|u| u.update_type == OrderbookUpdateType::Cancel)
+                .count();
+            match policy {
+                ShutdownPolicy::Cancel => {
+                    assert_eq!(report.cancelled_orders.len(), 2);
+                    assert_eq!(cancels, 2);
+                }
+                ShutdownPolicy::Persist => {
+                    assert_eq!(report.resting_orders.len(), 2);
+                    assert_eq!(cancels, 0);
+                }
+            }
+            assert_eq!(
+                orderbooks_manager.orderbooks[&symbol].state,
+                OrderbookState::Closed
+            );
+        }
     }
 
     #[tokio::test]
-    async fn test_listen_to_new_trade() {
+    async fn test_settlement_instructions_of_the_trades() {
         let mut orderbooks_manager = OrderbooksManager::new();
+        let (symbol, unregistered) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(symbol);
+        orderbooks_manager.new_orderbook(unregistered);
+        let (buyer, seller) = (Ulid::new().into(), Ulid::new().into());
+        let mut settlements = orderbooks_manager.listen_settlements().boxed();
+        // the first trade happens before its symbol is registered
+        for symbol in [unregistered, symbol] {
+            if symbol != unregistered {
+                orderbooks_manager.symbols.register(
+                    symbol,
+                    crate::structs::symbol_registry::SymbolInfo::new(1, 2),
+                );
+            }
+            let _ = orderbooks_manager.add_order(Order::new(
+                seller,
+                symbol,
+                OrderSide::Sell,
+                2.0,
+                Some(3.0),
+                OrderType::Limit,
+            ));
+            let _ = orderbooks_manager.add_order(Order::new(
+                buyer,
+                symbol,
+                OrderSide::Buy,
+                2.0,
+                Some(3.0),
+                OrderType::Limit,
+            ));
+        }
+
+        let instruction = settlements.next().await.unwrap();
+        assert_eq!(instruction.symbol, symbol);
+        assert!(instruction.trade_id.is_some());
+        assert_eq!(instruction.net(buyer, 1), 2.0);
+        assert_eq!(instruction.net(buyer, 2), -6.0);
+        assert_eq!(instruction.net(seller, 1), -2.0);
+        assert_eq!(instruction.net(seller, 2), 6.0);
+        assert!(instruction.fees.is_empty());
+    }
 
+    #[test]
+    fn test_metadata_is_carried_into_updates_and_trades() {
+        let mut orderbooks_manager = OrderbooksManager::new();
         let symbol = Ulid::new().into();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
+        let subscription = orderbooks_manager.subscribe_updates();
+        let tags = |algo: &str| Metadata::from([("algo".to_string(), algo.to_string())]);
+        let sell = Order::new(
             Ulid::new().into(),
             symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
+            OrderSide::Sell,
+            2.0,
+            Some(5.0),
             OrderType::Limit,
         );
-        let order2 = Order::new(
+        let buy = Order::new(
             Ulid::new().into(),
             symbol,
-            OrderSide::Sell,
+            OrderSide::Buy,
             1.0,
-            Some(1.0),
+            Some(5.0),
             OrderType::Limit,
         );
+        orderbooks_manager
+            .add_order_with_metadata(sell, tags("twap"))
+            .unwrap();
+        orderbooks_manager
+            .add_order_with_metadata(buy, tags("sniper"))
+            .unwrap();
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-
-        let mut new_orders_stream = orderbooks_manager.listen_new_trades().boxed();
+        let updates: Vec<OrderbookUpdate> =
+            std::iter::from_fn(|| subscription.try_recv().ok()).collect();
+        let placed = updates
+            .iter()
+            .find(|u| u.update_type == OrderbookUpdateType::Place)
+            .unwrap();
+        assert_eq!(placed.metadata, Some(tags("twap")));
+        let trade = updates.iter().find_map(|u| u.trade.as_ref()).unwrap();
+        assert_eq!(trade.sell_metadata, Some(tags("twap")));
+        assert_eq!(trade.buy_metadata, Some(tags("sniper")));
+        let filled = updates
+            .iter()
+            .find(|u| u.update_type == OrderbookUpdateType::Filled)
+            .unwrap();
+        assert_eq!(filled.filled_id, Some(buy.id));
+        assert_eq!(filled.metadata, Some(tags("sniper")));
 
-        let trade = new_orders_stream.next().await.unwrap();
-        assert_eq!(trade.symbol, order1.symbol);
-        assert_eq!(Some(trade.price), order1.price);
-        assert_eq!(trade.quantity, order1.quantity);
-        assert_eq!(trade.buy_order_id, order1.id);
-        assert_eq!(trade.sell_order_id, order2.id);
+        // the metadata of the filled order is forgotten, the resting one keeps its own
+        let orderbook = &orderbooks_manager.orderbooks[&symbol];
+        assert_eq!(orderbook.metadata(buy.id), None);
+        assert_eq!(orderbook.metadata(sell.id), Some(&tags("twap")));
     }
 
-    #[tokio::test]
-    async fn test_listen_to_filled_orders() {
+    #[test]
+    fn test_orders_are_validated_against_the_reference_data() {
         let mut orderbooks_manager = OrderbooksManager::new();
+        let (symbol, unknown) = (Ulid::new().into(), Ulid::new().into());
+        orderbooks_manager.new_orderbook(symbol);
+        orderbooks_manager.new_orderbook(unknown);
+        orderbooks_manager.symbols.register(
+            symbol,
+            crate::structs::symbol_registry::SymbolInfo {
+                min_quantity: Some(1.0),
+                ..crate::structs::symbol_registry::SymbolInfo::new(1, 2)
+            },
+        );
+        let order = |symbol, quantity| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(1.0),
+                OrderType::Limit,
+            )
+        };
+        let error = orderbooks_manager
+            .add_order(order(unknown, 1.0))
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "Unknown symbol");
+        let error = orderbooks_manager
+            .add_order(order(symbol, 0.5))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Order quantity below the minimum lot");
+        assert!(orderbooks_manager.add_order(order(symbol, 1.0)).is_ok());
+        assert_eq!(
+            orderbooks_manager
+                .metrics_recorder
+                .snapshot()
+                .global
+                .orders_rejected,
+            2
+        );
+    }
 
+    #[test]
+    fn test_quote_stuffing_is_capped_and_compacted() {
+        let mut orderbooks_manager = OrderbooksManager::new();
         let symbol = Ulid::new().into();
-        orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
-            Ulid::new().into(),
+        orderbooks_manager.new_orderbook_with_config(
             symbol,
-            OrderSide::Buy,
-            1.0,
-            Some(1.0),
-            OrderType::Limit,
+            OrderbookConfig::default()
+                .with_max_orders(100)
+                .with_compact_threshold(50),
         );
-        let order2 = Order::new(
+        let quotes: Vec<Order> = (0..100)
+            .map(|i| {
+                Order::new(
+                    Ulid::new().into(),
+                    symbol,
+                    OrderSide::Buy,
+                    1.0,
+                    Some(10.0 + (i % 10) as f64),
+                    OrderType::Limit,
+                )
+            })
+            .collect();
+        for quote in quotes.iter() {
+            orderbooks_manager.add_order(*quote).unwrap();
+        }
+        let stuffing = Order::new(
             Ulid::new().into(),
             symbol,
-            OrderSide::Sell,
+            OrderSide::Buy,
             1.0,
-            Some(1.0),
+            Some(10.0),
             OrderType::Limit,
         );
+        let error = orderbooks_manager.add_order(stuffing).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        let stats = orderbooks_manager.level_stats(symbol).unwrap();
+        assert_eq!((stats.bid_levels, stats.ask_levels), (10, 0));
+        assert_eq!((stats.max_orders_per_level, stats.orders), (10, 100));
 
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.add_order(order2.clone());
-
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_fills().boxed();
-
-        let order = new_orders_stream.next().await.unwrap();
-        assert_eq!(order, order2.id);
-        let order = new_orders_stream.next().await.unwrap();
-        assert_eq!(order, order1.id);
+        // the levels of the cancelled quotes are dropped and the pool is compacted at 50 free slots
+        for quote in quotes.iter().filter(|q| q.price.unwrap() < 16.0) {
+            orderbooks_manager
+                .cancel_order(quote.id, symbol, OrderSide::Buy)
+                .unwrap();
+        }
+        let stats = orderbooks_manager.level_stats(symbol).unwrap();
+        assert_eq!((stats.bid_levels, stats.orders), (4, 40));
+        let pool = orderbooks_manager.pool_stats(symbol).unwrap();
+        assert_eq!((pool.live, pool.free), (40, 10));
+        assert_eq!(orderbooks_manager.compact(symbol).unwrap(), 10);
+        let pool = orderbooks_manager.pool_stats(symbol).unwrap();
+        assert_eq!((pool.capacity, pool.free), (40, 0));
+        assert!(orderbooks_manager
+            .verify_invariants(symbol)
+            .unwrap()
+            .is_ok());
+        assert_eq!(
+            orderbooks_manager.snapshot(symbol).unwrap().bids[0].price,
+            Some(19.0)
+        );
     }
 
-    #[tokio::test]
-    async fn test_listen_to_cancelled_orders() {
-        let mut orderbooks_manager = OrderbooksManager::new();
-
+    #[test]
+    fn test_orders_are_not_booked_twice() {
         let symbol = Ulid::new().into();
+        let mut orderbooks_manager = OrderbooksManager::new();
         orderbooks_manager.new_orderbook(symbol);
-        let order1 = Order::new(
+        let order = Order::new(
             Ulid::new().into(),
             symbol,
             OrderSide::Buy,
             1.0,
             Some(1.0),
             OrderType::Limit,
-        );
-
-        let _ = orderbooks_manager.add_order(order1.clone());
-        let _ = orderbooks_manager.cancel_order(order1.id, symbol, order1.side);
+        )
+        .with_client_order_id(7);
+        orderbooks_manager.add_order(order).unwrap();
+        let duplicate = orderbooks_manager.add_order(order).unwrap_err();
+        assert_eq!(duplicate.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(orderbooks_manager.snapshot(symbol).unwrap().bids.len(), 1);
+        let batch = orderbooks_manager.add_orders(vec![order], BatchMode::BestEffort);
+        assert!(batch.unwrap()[0].is_err());
 
-        let mut new_orders_stream = orderbooks_manager.listen_orderbook_cancels().boxed();
+        // once cancelled, the ID is free again without a dedup window
+        orderbooks_manager
+            .cancel_order(order.id, symbol, order.side)
+            .unwrap();
+        orderbooks_manager.add_order(order).unwrap();
 
-        let first_order = new_orders_stream.next().await.unwrap();
-        assert_eq!(first_order, order1.id);
+        // a gateway retry within the window is rejected even with a new order ID
+        orderbooks_manager
+            .idempotency
+            .set_window(Duration::from_secs(60));
+        let sent = Order {
+            id: Ulid::new().into(),
+            ..order
+        };
+        orderbooks_manager.add_client_order(sent, None).unwrap();
+        let retry = Order {
+            id: Ulid::new().into(),
+            ..sent
+        };
+        let error = orderbooks_manager
+            .add_client_order(retry, None)
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(orderbooks_manager.snapshot(symbol).unwrap().bids.len(), 2);
     }
 }
@@ -0,0 +1,233 @@
+use super::ids::{Symbol, UserId};
+use super::orderbook_update::OrderbookUpdate;
+use super::trade::Trade;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Number of trades kept by default in the log of each user
+pub const DEFAULT_USER_TRADE_CAPACITY: usize = 10_000;
+
+/// Position of a user on a symbol built from its executions, valued at the average cost
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Position {
+    #[serde(rename = "userId")]
+    pub user_id: UserId,
+    pub symbol: Symbol,
+    /// Net quantity, positive when long, negative when short
+    pub quantity: f64,
+    /// Average price of the open quantity, 0 when the position is flat
+    #[serde(rename = "averagePrice")]
+    pub average_price: f64,
+    /// Profit and loss of the quantity closed so far, before fees
+    #[serde(rename = "realizedPnl")]
+    pub realized_pnl: f64,
+    /// Quantity bought over all the trades
+    pub bought: f64,
+    /// Quantity sold over all the trades
+    pub sold: f64,
+    /// Fees paid over all the trades, negative for net rebates
+    pub fees: f64,
+}
+
+impl Position {
+    pub fn new(user_id: UserId, symbol: Symbol) -> Position {
+        Position {
+            user_id,
+            symbol,
+            ..Default::default()
+        }
+    }
+
+    /// Apply an execution: the quantity in the direction of the position raises it at the average cost,
+    /// the quantity against it closes it and realizes the difference with the average price
+    ///
+    /// #Parameters
+    /// * 'side' - The side of the user in the trade
+    /// * 'quantity' - The executed quantity
+    /// * 'price' - The execution price
+    /// * 'fee' - The fee charged to the user, negative for a rebate
+    pub fn apply(&mut self, side: OrderSide, quantity: f64, price: f64, fee: f64) {
+        let signed = match side {
+            OrderSide::Buy => {
+                self.bought += quantity;
+                quantity
+            }
+            OrderSide::Sell => {
+                self.sold += quantity;
+                -quantity
+            }
+        };
+        self.fees += fee;
+        if self.quantity == 0.0 || self.quantity.signum() == signed.signum() {
+            let open = self.quantity.abs();
+            self.average_price = (open * self.average_price + quantity * price) / (open + quantity);
+            self.quantity += signed;
+            return;
+        }
+        let closed = quantity.min(self.quantity.abs());
+        self.realized_pnl += closed * (price - self.average_price) * self.quantity.signum();
+        self.quantity += signed;
+        if closed < quantity {
+            // The position flipped, the rest opens at the execution price
+            self.average_price = price;
+        } else if self.quantity == 0.0 {
+            self.average_price = 0.0;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PositionState {
+    positions: HashMap<(UserId, Symbol), Position>,
+    /// Last trades of each user, the oldest first
+    trades: HashMap<UserId, VecDeque<Trade>>,
+    capacity: usize,
+}
+
+/// Positions and trade logs of the users, fed with the trades published by the orderbooks
+#[derive(Debug, Clone)]
+pub struct PositionTracker {
+    state: Arc<Mutex<PositionState>>,
+}
+
+impl Default for PositionTracker {
+    fn default() -> Self {
+        PositionTracker::new(DEFAULT_USER_TRADE_CAPACITY)
+    }
+}
+
+impl PositionTracker {
+    /// Create a new tracker
+    ///
+    /// #Parameters
+    /// * 'capacity' - The number of trades kept in the log of each user, 0 to keep no log
+    pub fn new(capacity: usize) -> PositionTracker {
+        PositionTracker {
+            state: Arc::new(Mutex::new(PositionState {
+                positions: HashMap::new(),
+                trades: HashMap::new(),
+                capacity,
+            })),
+        }
+    }
+
+    /// Position of a user on a symbol, flat if the user never traded it
+    pub fn position(&self, user_id: UserId, symbol: Symbol) -> Position {
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .get(&(user_id, symbol))
+            .copied()
+            .unwrap_or_else(|| Position::new(user_id, symbol))
+    }
+
+    /// Positions of a user on every symbol it traded
+    pub fn positions(&self, user_id: UserId) -> Vec<Position> {
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .values()
+            .filter(|p| p.user_id == user_id)
+            .copied()
+            .collect()
+    }
+
+    /// Trades of a user executed at or after a time, the oldest first
+    ///
+    /// #Parameters
+    /// * 'user_id' - The user ID
+    /// * 'since' - The time in nanoseconds since UNIX epoch, 0 for the whole log
+    pub fn trades(&self, user_id: UserId, since: u64) -> Vec<Trade> {
+        self.state
+            .lock()
+            .unwrap()
+            .trades
+            .get(&user_id)
+            .map(|trades| {
+                trades
+                    .iter()
+                    .filter(|t| t.created_at.unwrap_or_default() >= since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Update the positions and the trade logs from an update published by an orderbook
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        if update.update_type != OrderbookUpdateType::NewTrades {
+            return;
+        }
+        let Some(trade) = update.trade.as_ref() else {
+            return;
+        };
+        let (buy_fee, sell_fee) = match trade.taker_side {
+            OrderSide::Buy => (trade.taker_fee, trade.maker_fee),
+            OrderSide::Sell => (trade.maker_fee, trade.taker_fee),
+        };
+        let mut state = self.state.lock().unwrap();
+        for (user_id, side, fee) in [
+            (trade.buy_user_id, OrderSide::Buy, buy_fee),
+            (trade.sell_user_id, OrderSide::Sell, sell_fee),
+        ] {
+            state
+                .positions
+                .entry((user_id, trade.symbol))
+                .or_insert_with(|| Position::new(user_id, trade.symbol))
+                .apply(side, trade.quantity, trade.price, fee);
+        }
+        let capacity = state.capacity;
+        if capacity == 0 {
+            return;
+        }
+        let mut users = vec![trade.buy_user_id];
+        if trade.sell_user_id != trade.buy_user_id {
+            users.push(trade.sell_user_id);
+        }
+        for user_id in users {
+            let trades = state.trades.entry(user_id).or_default();
+            if trades.len() == capacity {
+                trades.pop_front();
+            }
+            trades.push_back(trade.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_realizes_the_closed_quantity() {
+        let mut position = Position::new(UserId(1), Symbol(2));
+        position.apply(OrderSide::Buy, 2.0, 100.0, 0.0);
+        position.apply(OrderSide::Buy, 2.0, 110.0, 0.0);
+        assert_eq!(position.quantity, 4.0);
+        assert_eq!(position.average_price, 105.0);
+
+        position.apply(OrderSide::Sell, 1.0, 115.0, 0.0);
+        assert_eq!(position.realized_pnl, 10.0);
+        assert_eq!(position.average_price, 105.0);
+
+        // Selling more than the position flips it short at the execution price
+        position.apply(OrderSide::Sell, 5.0, 95.0, 0.0);
+        assert_eq!(position.quantity, -2.0);
+        assert_eq!(position.average_price, 95.0);
+        assert_eq!(position.realized_pnl, -20.0);
+
+        position.apply(OrderSide::Buy, 2.0, 90.0, 0.5);
+        assert_eq!(position.quantity, 0.0);
+        assert_eq!(position.average_price, 0.0);
+        assert_eq!(position.realized_pnl, -10.0);
+        assert_eq!(
+            (position.bought, position.sold, position.fees),
+            (6.0, 6.0, 0.5)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::enums::band_action::BandAction;
+use crate::enums::price_reference::PriceReference;
+use serde::{Deserialize, Serialize};
+
+/// Price band protecting an orderbook against executions too far from a reference price
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriceBand {
+    /// Maximum relative distance to the reference price, e.g. 0.05 for 5%
+    pub max_deviation: f64,
+    pub reference: PriceReference,
+    pub action: BandAction,
+}
+
+impl PriceBand {
+    pub fn new(max_deviation: f64, reference: PriceReference, action: BandAction) -> PriceBand {
+        PriceBand {
+            max_deviation,
+            reference,
+            action,
+        }
+    }
+
+    /// Whether a price is inside the band centered on the reference price
+    pub fn contains(&self, price: f64, reference_price: f64) -> bool {
+        reference_price <= 0.0
+            || (price - reference_price).abs() / reference_price <= self.max_deviation
+    }
+}
+
+/// Event emitted when a match is stopped by a price band
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerEvent {
+    /// The price the match would have executed at
+    pub price: f64,
+    pub reference_price: f64,
+    pub action: BandAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let band = PriceBand::new(0.05, PriceReference::LastTrade, BandAction::Halt);
+        assert!(band.contains(104.0, 100.0));
+        assert!(band.contains(95.0, 100.0));
+        assert!(!band.contains(106.0, 100.0));
+        assert!(!band.contains(94.0, 100.0));
+    }
+}
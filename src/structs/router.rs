@@ -0,0 +1,563 @@
+use super::ids::{OrderId, Symbol};
+use super::order::Order;
+use super::orderbook::Orderbook;
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Share of a parent order planned for one orderbook of its instrument
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChildAllocation {
+    pub symbol: Symbol,
+    pub quantity: f64,
+    /// Limit price of the child, the worst level it reaches or the limit of the parent when the child
+    /// rests the part no book can execute. None for the children of a market order
+    pub price: Option<f64>,
+    /// Estimated notional of the executable part including the taker fees, paid for a buy and
+    /// received for a sell
+    #[serde(rename = "estimatedCost")]
+    pub estimated_cost: f64,
+}
+
+/// Child order of a routed parent, as seen through the updates of its orderbook
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChildOrder {
+    #[serde(rename = "orderId")]
+    pub order_id: OrderId,
+    pub symbol: Symbol,
+    pub quantity: f64,
+    #[serde(rename = "filledQuantity")]
+    pub filled_quantity: f64,
+    /// Sum of the executed quantities times their prices
+    pub notional: f64,
+    pub status: OrderStatus,
+}
+
+impl ChildOrder {
+    pub fn new(order: &Order) -> ChildOrder {
+        ChildOrder {
+            order_id: order.id,
+            symbol: order.symbol,
+            quantity: order.quantity + order.hidden_quantity,
+            filled_quantity: 0.0,
+            notional: 0.0,
+            status: OrderStatus::Open,
+        }
+    }
+
+    /// Whether the child left its orderbook
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.status,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Expired
+        )
+    }
+}
+
+/// Order on an instrument split by the router across the orderbooks trading it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParentOrder {
+    #[serde(rename = "parentId")]
+    pub parent_id: OrderId,
+    /// Symbol ID of the instrument, which has no orderbook of its own
+    pub instrument: Symbol,
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub children: Vec<ChildOrder>,
+}
+
+impl ParentOrder {
+    /// Aggregated status of the children: Filled once the parent quantity is executed, Cancelled once
+    /// the children are all done without executing it, PartiallyFilled as soon as one of them traded,
+    /// Open otherwise
+    pub fn status(&self) -> OrderStatus {
+        if self.filled_quantity() >= self.quantity {
+            OrderStatus::Filled
+        } else if self.is_done() {
+            OrderStatus::Cancelled
+        } else if self.filled_quantity() > 0.0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Open
+        }
+    }
+
+    /// Quantity executed over all the children
+    pub fn filled_quantity(&self) -> f64 {
+        self.children
+            .iter()
+            .map(|child| child.filled_quantity)
+            .sum()
+    }
+
+    /// Average execution price over all the children, None before the first trade
+    pub fn average_price(&self) -> Option<f64> {
+        let filled = self.filled_quantity();
+        if filled <= 0.0 {
+            return None;
+        }
+        Some(
+            self.children
+                .iter()
+                .map(|child| child.notional)
+                .sum::<f64>()
+                / filled,
+        )
+    }
+
+    /// Children still resting or waiting in their orderbook
+    pub fn open_children(&self) -> impl Iterator<Item = &ChildOrder> {
+        self.children.iter().filter(|child| !child.is_done())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.children.iter().all(ChildOrder::is_done)
+    }
+}
+
+/// Event emitted when a parent order is routed or one of its children changes, with the state of the whole parent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParentEvent {
+    pub parent: ParentOrder,
+    /// Aggregated status of the parent
+    pub status: OrderStatus,
+    /// The child which changed, None when the parent is routed
+    #[serde(rename = "orderId")]
+    pub order_id: Option<OrderId>,
+}
+
+/// Split an order across the orderbooks of its instrument at the lowest cost: the levels of the books
+/// are taken best effective price first, the effective price including the taker fee of the book.
+/// The part of a limit order no book can execute at its limit rests on the book with the lowest
+/// maker fee, the first one on a tie, the part of a market order the books can't execute is not routed
+///
+/// #Parameters
+/// * 'order' - The parent order
+/// * 'books' - The orderbooks trading the instrument, in order of preference
+///
+/// #Returns
+/// * Vec<ChildAllocation> - One allocation per orderbook receiving a child, in the order of the books
+pub fn plan(order: &Order, books: &[&Orderbook]) -> Vec<ChildAllocation> {
+    let within_limit = |price: f64| match (order.side, order.price) {
+        (_, None) => true,
+        (OrderSide::Buy, Some(limit)) => price <= limit,
+        (OrderSide::Sell, Some(limit)) => price >= limit,
+    };
+    // (index of the book, price, quantity, effective price)
+    let mut levels: Vec<(usize, f64, f64, f64)> = Vec::new();
+    for (index, book) in books.iter().enumerate() {
+        let (bids, asks) = book.depth(usize::MAX);
+        let (opposite, fee) = match order.side {
+            OrderSide::Buy => (asks, book.fees.taker_fee),
+            OrderSide::Sell => (bids, -book.fees.taker_fee),
+        };
+        levels.extend(
+            opposite
+                .iter()
+                .take_while(|level| within_limit(level.price))
+                .map(|level| {
+                    (
+                        index,
+                        level.price,
+                        level.quantity,
+                        level.price * (1.0 + fee),
+                    )
+                }),
+        );
+    }
+    // the sort is stable, on a tie the first book is preferred
+    levels.sort_by(|a, b| match order.side {
+        OrderSide::Buy => a.3.total_cmp(&b.3),
+        OrderSide::Sell => b.3.total_cmp(&a.3),
+    });
+    let mut allocations: Vec<Option<ChildAllocation>> = vec![None; books.len()];
+    let mut remaining = order.quantity;
+    for (index, price, quantity, effective_price) in levels {
+        if remaining <= 0.0 {
+            break;
+        }
+        let executed = quantity.min(remaining);
+        remaining -= executed;
+        let allocation = allocations[index].get_or_insert(ChildAllocation {
+            symbol: books[index].symbol,
+            quantity: 0.0,
+            price: None,
+            estimated_cost: 0.0,
+        });
+        allocation.quantity += executed;
+        allocation.estimated_cost += executed * effective_price;
+        // the levels of a book come best first, the last one taken is the worst
+        if order.order_type == OrderType::Limit {
+            allocation.price = Some(price);
+        }
+    }
+    if remaining > 0.0 && order.order_type == OrderType::Limit {
+        if let Some(index) = (0..books.len())
+            .min_by(|&a, &b| books[a].fees.maker_fee.total_cmp(&books[b].fees.maker_fee))
+        {
+            let allocation = allocations[index].get_or_insert(ChildAllocation {
+                symbol: books[index].symbol,
+                quantity: 0.0,
+                price: None,
+                estimated_cost: 0.0,
+            });
+            allocation.quantity += remaining;
+            allocation.price = order.price;
+        }
+    }
+    allocations.into_iter().flatten().collect()
+}
+
+#[derive(Debug, Default)]
+struct RouterState {
+    /// Orderbooks trading each instrument, in order of preference
+    routes: HashMap<Symbol, Vec<Symbol>>,
+    parents: HashMap<OrderId, ParentOrder>,
+    /// Parent of each child
+    children: HashMap<OrderId, OrderId>,
+    listeners: Vec<Sender<ParentEvent>>,
+}
+
+impl RouterState {
+    fn publish(&mut self, parent_id: OrderId, order_id: Option<OrderId>) {
+        let Some(parent) = self.parents.get(&parent_id) else {
+            return;
+        };
+        let event = ParentEvent {
+            parent: parent.clone(),
+            status: parent.status(),
+            order_id,
+        };
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+        // A parent whose children are all done is forgotten once its last event is out
+        if parent.is_done() {
+            for child in parent.children.iter() {
+                self.children.remove(&child.order_id);
+            }
+            self.parents.remove(&parent_id);
+        }
+    }
+}
+
+/// Router of the orders of an instrument traded on several orderbooks, e.g. different venues or fee
+/// tiers. It keeps the routes and the live parent orders, and is fed with the updates of the orderbooks
+/// to aggregate the fills of the children into parent events
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    state: Arc<Mutex<RouterState>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Route the orders of an instrument to orderbooks
+    ///
+    /// #Parameters
+    /// * 'instrument' - The symbol ID of the instrument
+    /// * 'symbols' - The orderbooks trading it, in order of preference
+    ///
+    /// #Returns
+    /// * bool - False if the instrument already has a route
+    pub fn add_route(&self, instrument: Symbol, symbols: Vec<Symbol>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.routes.contains_key(&instrument) {
+            return false;
+        }
+        state.routes.insert(instrument, symbols);
+        true
+    }
+
+    /// Orderbooks trading an instrument, None if it has no route
+    pub fn route(&self, instrument: Symbol) -> Option<Vec<Symbol>> {
+        self.state.lock().unwrap().routes.get(&instrument).cloned()
+    }
+
+    /// Register a parent order before its children are submitted
+    ///
+    /// #Parameters
+    /// * 'parent' - The parent order, its symbol is the instrument
+    /// * 'children' - The child orders
+    ///
+    /// #Returns
+    /// * bool - False if a live parent already has this ID
+    pub fn register(&self, parent: &Order, children: &[Order]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.parents.contains_key(&parent.id) {
+            return false;
+        }
+        let parent_order = ParentOrder {
+            parent_id: parent.id,
+            instrument: parent.symbol,
+            side: parent.side,
+            quantity: parent.quantity,
+            children: children.iter().map(ChildOrder::new).collect(),
+        };
+        for child in parent_order.children.iter() {
+            state.children.insert(child.order_id, parent.id);
+        }
+        state.parents.insert(parent.id, parent_order);
+        state.publish(parent.id, None);
+        true
+    }
+
+    /// Current state of a live parent order
+    pub fn get(&self, parent_id: OrderId) -> Option<ParentOrder> {
+        self.state.lock().unwrap().parents.get(&parent_id).cloned()
+    }
+
+    /// Parent of an order, None if the order is not the child of a live parent
+    pub fn parent_of(&self, order_id: OrderId) -> Option<OrderId> {
+        self.state.lock().unwrap().children.get(&order_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().parents.is_empty()
+    }
+
+    /// Receive every parent event emitted from now on
+    pub fn subscribe(&self) -> Receiver<ParentEvent> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().listeners.push(tx);
+        rx
+    }
+
+    /// Mark a child which was not submitted to its orderbook as cancelled
+    pub fn drop_child(&self, order: &Order) {
+        self.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            order: Some(*order),
+            cancel_id: Some(order.id),
+            ..Default::default()
+        });
+    }
+
+    /// Update the children from an update published by an orderbook and emit an event for each child which changed
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        if state.children.is_empty() {
+            return;
+        }
+        // (child, executed quantity, execution price, new status)
+        let changes: Vec<(OrderId, f64, f64, OrderStatus)> = match update.update_type {
+            // The Filled updates come before the trades of the fills, the children are filled by
+            // their trades so that the price of the last fill is counted
+            OrderbookUpdateType::NewTrades => update.trade.as_ref().map_or(Vec::new(), |trade| {
+                let status = |remaining: f64| match remaining > 0.0 {
+                    true => OrderStatus::PartiallyFilled,
+                    false => OrderStatus::Filled,
+                };
+                vec![
+                    (
+                        trade.buy_order_id,
+                        trade.quantity,
+                        trade.price,
+                        status(trade.buy_remaining),
+                    ),
+                    (
+                        trade.sell_order_id,
+                        trade.quantity,
+                        trade.price,
+                        status(trade.sell_remaining),
+                    ),
+                ]
+            }),
+            // A cancel of an order which is not in the book carries no order
+            OrderbookUpdateType::Cancel => update
+                .order
+                .map(|o| (o.id, 0.0, 0.0, OrderStatus::Cancelled))
+                .into_iter()
+                .collect(),
+            OrderbookUpdateType::Expired => update
+                .order
+                .map(|o| (o.id, 0.0, 0.0, OrderStatus::Expired))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (order_id, filled, price, status) in changes {
+            let Some(&parent_id) = state.children.get(&order_id) else {
+                continue;
+            };
+            let Some(child) = state.parents.get_mut(&parent_id).and_then(|parent| {
+                parent
+                    .children
+                    .iter_mut()
+                    .find(|child| child.order_id == order_id)
+            }) else {
+                continue;
+            };
+            if child.is_done() {
+                continue;
+            }
+            child.filled_quantity += filled;
+            child.notional += filled * price;
+            child.status = status;
+            if child.status == OrderStatus::Filled {
+                child.filled_quantity = child.quantity;
+            }
+            state.publish(parent_id, Some(order_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ids::UserId;
+    use crate::structs::orderbook_config::{FeeSchedule, OrderbookConfig};
+    use crate::structs::trade::Trade;
+
+    #[test]
+    fn test_plan_takes_the_cheapest_levels_across_books() {
+        let (tx, _rx) = unbounded();
+        let config =
+            |taker_fee| OrderbookConfig::default().with_fees(FeeSchedule::new(0.0, taker_fee));
+        let mut cheap = Orderbook::with_config(Symbol(1), tx.clone(), config(0.0));
+        let mut costly = Orderbook::with_config(Symbol(2), tx, config(0.01));
+        let ask = |symbol, quantity, price| {
+            Order::new(
+                UserId(1),
+                symbol,
+                OrderSide::Sell,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        cheap.add_order(ask(Symbol(1), 1.0, 100.5));
+        cheap.add_order(ask(Symbol(1), 1.0, 103.0));
+        // cheaper than 100.5 before the fee, dearer after it
+        costly.add_order(ask(Symbol(2), 1.0, 100.0));
+        costly.add_order(ask(Symbol(2), 1.0, 101.5));
+
+        let buy = |quantity, price| {
+            Order::new(
+                UserId(2),
+                Symbol(3),
+                OrderSide::Buy,
+                quantity,
+                price,
+                if price.is_some() {
+                    OrderType::Limit
+                } else {
+                    OrderType::Market
+                },
+            )
+        };
+        let allocations = plan(&buy(2.0, Some(102.0)), &[&cheap, &costly]);
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(
+            (
+                allocations[0].symbol,
+                allocations[0].quantity,
+                allocations[0].price
+            ),
+            (Symbol(1), 1.0, Some(100.5))
+        );
+        assert_eq!(
+            (
+                allocations[1].symbol,
+                allocations[1].quantity,
+                allocations[1].price
+            ),
+            (Symbol(2), 1.0, Some(100.0))
+        );
+        assert_eq!(allocations[1].estimated_cost, 101.0);
+
+        // what can't be executed within the limit rests on the first book with the lowest maker fee
+        let allocations = plan(&buy(4.0, Some(101.0)), &[&cheap, &costly]);
+        assert_eq!(
+            allocations
+                .iter()
+                .map(|a| (a.quantity, a.price))
+                .collect::<Vec<_>>(),
+            vec![(3.0, Some(101.0)), (1.0, Some(100.0))]
+        );
+
+        // a market order is only routed where it can execute
+        let allocations = plan(&buy(10.0, None), &[&cheap, &costly]);
+        let routed: f64 = allocations.iter().map(|a| a.quantity).sum();
+        assert_eq!(routed, 4.0);
+        assert!(allocations.iter().all(|a| a.price.is_none()));
+    }
+
+    #[test]
+    fn test_parent_aggregates_the_fills_of_its_children() {
+        let router = Router::new();
+        let events = router.subscribe();
+        let parent = Order::new(
+            UserId(1),
+            Symbol(10),
+            OrderSide::Buy,
+            3.0,
+            Some(100.0),
+            OrderType::Limit,
+        );
+        let child = |symbol, quantity| {
+            Order::new(
+                UserId(1),
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                Some(100.0),
+                OrderType::Limit,
+            )
+        };
+        let (first, second) = (child(Symbol(1), 1.0), child(Symbol(2), 2.0));
+        assert!(router.register(&parent, &[first, second]));
+        assert!(!router.register(&parent, &[first]));
+        assert_eq!(router.parent_of(second.id), Some(parent.id));
+
+        let trade = |order_id, price, remaining| OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(Trade {
+                buy_order_id: order_id,
+                buy_remaining: remaining,
+                quantity: 1.0,
+                price,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        router.on_update(&trade(first.id, 99.0, 0.0));
+        router.on_update(&trade(second.id, 100.0, 1.0));
+        let state = router.get(parent.id).unwrap();
+        assert_eq!(state.status(), OrderStatus::PartiallyFilled);
+        assert_eq!(state.filled_quantity(), 2.0);
+        assert_eq!(state.average_price(), Some(99.5));
+        assert_eq!(state.open_children().count(), 1);
+
+        router.on_update(&OrderbookUpdate {
+            update_type: OrderbookUpdateType::Cancel,
+            order: Some(second),
+            cancel_id: Some(second.id),
+            ..Default::default()
+        });
+        let statuses: Vec<_> = events.try_iter().map(|e| (e.order_id, e.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (None, OrderStatus::Open),
+                (Some(first.id), OrderStatus::PartiallyFilled),
+                (Some(second.id), OrderStatus::PartiallyFilled),
+                (Some(second.id), OrderStatus::Cancelled),
+            ]
+        );
+        // Done parents are forgotten
+        assert!(router.is_empty());
+        assert_eq!(router.parent_of(first.id), None);
+    }
+}
@@ -0,0 +1,132 @@
+use super::ids::UserId;
+use super::order::Order;
+use crate::enums::session_event_type::SessionEventType;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::time::Duration;
+use web_time::Instant;
+
+/// A user session registered by a gateway
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Session {
+    pub user_id: UserId,
+    /// Maximum delay between two heartbeats before the session times out
+    pub timeout: Duration,
+    pub last_heartbeat: Instant,
+}
+
+impl Session {
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_heartbeat) > self.timeout
+    }
+}
+
+/// Event emitted when a session starts or ends, with the orders cancelled at the end of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionEvent {
+    pub user_id: UserId,
+    pub event_type: SessionEventType,
+    pub cancelled_orders: Vec<Order>,
+}
+
+/// Registry of the live sessions, one per user
+#[derive(Debug, Clone, Default)]
+pub struct SessionRegistry {
+    sessions: HashMap<UserId, Session>,
+    listeners: Vec<Sender<SessionEvent>>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> SessionRegistry {
+        SessionRegistry::default()
+    }
+
+    /// Register or replace the session of a user, starting its heartbeat clock at `now`
+    pub fn register(&mut self, user_id: UserId, timeout: Duration, now: Instant) {
+        self.sessions.insert(
+            user_id,
+            Session {
+                user_id,
+                timeout,
+                last_heartbeat: now,
+            },
+        );
+    }
+
+    /// Record a heartbeat, returns false if the user has no session
+    pub fn heartbeat(&mut self, user_id: UserId, now: Instant) -> bool {
+        match self.sessions.get_mut(&user_id) {
+            Some(session) => {
+                session.last_heartbeat = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, user_id: UserId) -> Option<Session> {
+        self.sessions.remove(&user_id)
+    }
+
+    pub fn get(&self, user_id: UserId) -> Option<&Session> {
+        self.sessions.get(&user_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Remove the sessions which missed their heartbeat at `now`
+    ///
+    /// #Returns
+    /// * Vec<UserId> - The users whose session timed out
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<UserId> {
+        let expired: Vec<UserId> = self
+            .sessions
+            .values()
+            .filter(|session| session.is_expired(now))
+            .map(|session| session.user_id)
+            .collect();
+        for user_id in expired.iter() {
+            self.sessions.remove(user_id);
+        }
+        expired
+    }
+
+    /// Receive every session event emitted from now on
+    pub fn subscribe(&mut self) -> Receiver<SessionEvent> {
+        let (tx, rx) = unbounded();
+        self.listeners.push(tx);
+        rx
+    }
+
+    /// Send the event to every listener, listeners which are gone are removed
+    pub fn publish(&mut self, event: &SessionEvent) {
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_expired() {
+        let mut registry = SessionRegistry::new();
+        let start = Instant::now();
+        registry.register(UserId(1), Duration::from_secs(1), start);
+        registry.register(UserId(2), Duration::from_secs(1), start);
+        assert!(registry.heartbeat(UserId(2), start + Duration::from_millis(800)));
+        assert!(!registry.heartbeat(UserId(3), start));
+
+        let expired = registry.remove_expired(start + Duration::from_millis(1500));
+        assert_eq!(expired, vec![UserId(1)]);
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get(UserId(2)).is_some());
+    }
+}
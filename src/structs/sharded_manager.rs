@@ -0,0 +1,251 @@
+use super::book_snapshot::BookSnapshot;
+use super::clock::Clock;
+use super::ids::{OrderId, Symbol};
+use super::order_ack::OrderAck;
+use super::orderbook_config::OrderbookConfig;
+use super::orderbook_sum::OrderBookSummarized;
+use super::orderbooks_manager::OrderbooksManager;
+use super::subscription::Subscription;
+use super::update_bus::UpdateBus;
+use crate::enums::side::OrderSide;
+use crate::structs::order::Order;
+use crossbeam_channel::{bounded, unbounded, Sender};
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use std::thread;
+
+/// Work sent to a shard, run on the shard thread with exclusive access to its orderbooks
+type ShardCommand = Box<dyn FnOnce(&mut OrderbooksManager) + Send>;
+
+/// Orderbooks spread over shards, each shard being a thread owning the orderbooks of its symbols.
+///
+/// The handle is cheap to clone and can be used from any thread: commands are routed by symbol
+/// over lock-free channels and run one after the other on the shard owning the symbol, so an
+/// orderbook is only ever accessed by a single thread. Every shard publishes on the same bus,
+/// and the risk engine, the accounts and the clock of the template manager are shared.
+/// A shard thread stops once every handle is dropped.
+#[derive(Debug, Clone)]
+pub struct ShardedManager {
+    shards: Vec<Sender<ShardCommand>>,
+    bus: UpdateBus,
+    /// Clock of the shards, stamping the orders as they are sent
+    clock: Arc<dyn Clock>,
+}
+
+impl ShardedManager {
+    /// Create a sharded manager with default settings
+    ///
+    /// #Parameters
+    /// * 'shard_count' - The number of shard threads
+    pub fn new(shard_count: usize) -> ShardedManager {
+        ShardedManager::with_template(&OrderbooksManager::new(), shard_count)
+    }
+
+    /// Create a sharded manager whose shards share the bus, the risk engine, the accounts and the clock of a manager
+    ///
+    /// #Parameters
+    /// * 'template' - The manager the shards are configured from, its orderbooks are not copied
+    /// * 'shard_count' - The number of shard threads
+    pub fn with_template(template: &OrderbooksManager, shard_count: usize) -> ShardedManager {
+        assert!(
+            shard_count > 0,
+            "a sharded manager needs at least one shard"
+        );
+        let shards = (0..shard_count)
+            .map(|_| {
+                let mut manager = template.sibling();
+                let (tx, rx) = unbounded::<ShardCommand>();
+                thread::spawn(move || {
+                    for command in rx {
+                        command(&mut manager);
+                    }
+                });
+                tx
+            })
+            .collect();
+        ShardedManager {
+            shards,
+            bus: template.bus.clone(),
+            clock: template.clock.clone(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard owning a symbol
+    pub fn shard_of(&self, symbol: Symbol) -> usize {
+        (symbol.0 % self.shards.len() as u128) as usize
+    }
+
+    /// Run a function on the shard owning a symbol and wait for its result
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID, selecting the shard
+    /// * 'command' - The function, given the manager of the shard
+    ///
+    /// #Returns
+    /// * Result<R, Error> - The result of the function, an error if the shard is gone
+    pub fn execute<R, F>(&self, symbol: Symbol, command: F) -> Result<R, Error>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut OrderbooksManager) -> R + Send + 'static,
+    {
+        let (tx, rx) = bounded(1);
+        let shard_gone = || Error::new(ErrorKind::BrokenPipe, "Shard stopped");
+        self.shards[self.shard_of(symbol)]
+            .send(Box::new(move |manager: &mut OrderbooksManager| {
+                let _ = tx.send(command(manager));
+            }))
+            .map_err(|_| shard_gone())?;
+        rx.recv().map_err(|_| shard_gone())
+    }
+
+    /// Create a new orderbook on the shard owning the symbol
+    pub fn new_orderbook(&self, symbol: Symbol) -> Result<(), Error> {
+        self.new_orderbook_with_config(symbol, OrderbookConfig::default())
+    }
+
+    /// Create a new orderbook behaving as configured on the shard owning the symbol
+    pub fn new_orderbook_with_config(
+        &self,
+        symbol: Symbol,
+        config: OrderbookConfig,
+    ) -> Result<(), Error> {
+        self.execute(symbol, move |manager| {
+            if manager.orderbooks.contains_key(&symbol) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "Orderbook already exists",
+                ));
+            }
+            manager.new_orderbook_with_config(symbol, config);
+            Ok(())
+        })?
+    }
+
+    pub fn remove_orderbook(&self, symbol: Symbol) -> Result<Vec<Order>, Error> {
+        self.execute(symbol, move |manager| manager.remove_orderbook(symbol))?
+    }
+
+    pub fn add_order(&self, order: Order) -> Result<OrderAck, Error> {
+        let received_at = self.clock.monotonic();
+        self.execute(order.symbol, move |manager| {
+            manager.add_order_received(order, received_at)
+        })?
+    }
+
+    pub fn cancel_order(
+        &self,
+        order_id: OrderId,
+        symbol: Symbol,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.execute(symbol, move |manager| {
+            manager.cancel_order(order_id, symbol, side)
+        })?
+    }
+
+    pub fn amend_order_price(
+        &self,
+        symbol: Symbol,
+        order_id: OrderId,
+        price: f64,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.execute(symbol, move |manager| {
+            manager.amend_order_price(symbol, order_id, price, side)
+        })?
+    }
+
+    pub fn amend_order_quantity(
+        &self,
+        symbol: Symbol,
+        order_id: OrderId,
+        quantity: f64,
+        side: OrderSide,
+    ) -> Result<OrderAck, Error> {
+        self.execute(symbol, move |manager| {
+            manager.amend_order_quantity(symbol, order_id, quantity, side)
+        })?
+    }
+
+    pub fn get_orderbook(&self, symbol: Symbol) -> Result<OrderBookSummarized, Error> {
+        self.execute(symbol, move |manager| manager.get_orderbook(symbol))?
+    }
+
+    pub fn snapshot(&self, symbol: Symbol) -> Result<BookSnapshot, Error> {
+        self.execute(symbol, move |manager| manager.snapshot(symbol))?
+    }
+
+    /// Symbols of the orderbooks of every shard, sorted
+    pub fn list_symbols(&self) -> Result<Vec<Symbol>, Error> {
+        let mut symbols = Vec::new();
+        for shard in 0..self.shards.len() {
+            // any symbol routed to the shard selects it
+            symbols.extend(self.execute(Symbol(shard as u128), |manager| manager.list_symbols())?);
+        }
+        symbols.sort();
+        Ok(symbols)
+    }
+
+    /// Subscribe to the updates of every shard published from now on
+    pub fn subscribe_updates(&self) -> Subscription {
+        self.bus.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::orderbook_update_type::OrderbookUpdateType;
+    use futures_util::StreamExt;
+    use ulid::Ulid;
+
+    #[tokio::test]
+    async fn test_orders_from_several_threads() {
+        let manager = ShardedManager::new(4);
+        let symbols: Vec<Symbol> = (0..8).map(|_| Ulid::new().into()).collect();
+        for symbol in symbols.iter() {
+            manager.new_orderbook(*symbol).unwrap();
+        }
+        assert!(manager.new_orderbook(symbols[0]).is_err());
+        let mut updates = manager.subscribe_updates();
+
+        let threads: Vec<_> = symbols
+            .iter()
+            .map(|&symbol| {
+                let manager = manager.clone();
+                thread::spawn(move || {
+                    for side in [OrderSide::Buy, OrderSide::Sell] {
+                        let order = Order::new(
+                            Ulid::new().into(),
+                            symbol,
+                            side,
+                            1.0,
+                            Some(1.0),
+                            OrderType::Limit,
+                        );
+                        manager.add_order(order).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut trades = 0;
+        while trades < symbols.len() {
+            if updates.next().await.unwrap().update_type == OrderbookUpdateType::NewTrades {
+                trades += 1;
+            }
+        }
+        let mut listed = symbols.clone();
+        listed.sort();
+        assert_eq!(manager.list_symbols().unwrap(), listed);
+        assert!(manager.snapshot(symbols[0]).unwrap().bids.is_empty());
+    }
+}
@@ -0,0 +1,15 @@
+use super::order::Order;
+use crate::enums::shutdown_policy::ShutdownPolicy;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of `OrderbooksManager::shutdown`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    pub policy: ShutdownPolicy,
+    /// Orders cancelled with the Cancel policy
+    #[serde(rename = "cancelledOrders")]
+    pub cancelled_orders: Vec<Order>,
+    /// Orders left resting with the Persist policy
+    #[serde(rename = "restingOrders")]
+    pub resting_orders: Vec<Order>,
+}
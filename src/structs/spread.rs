@@ -0,0 +1,136 @@
+use super::ids::{OrderId, Symbol, UserId};
+use super::level_book::PriceLevel;
+use super::order_ack::OrderAck;
+use super::orderbook::Orderbook;
+use crate::enums::self_trade_prevention::SelfTradePrevention;
+use crate::enums::side::OrderSide;
+use serde::{Deserialize, Serialize};
+
+/// Synthetic instrument trading leg A against leg B: buying the spread buys A and sells B,
+/// its price is the price of A minus the price of B
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpreadDefinition {
+    /// Symbol ID of the spread, which has no orderbook of its own
+    pub symbol: Symbol,
+    #[serde(rename = "legA")]
+    pub leg_a: Symbol,
+    #[serde(rename = "legB")]
+    pub leg_b: Symbol,
+}
+
+/// Spread price implied by the best levels of the two outright books
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImpliedPrice {
+    pub price: f64,
+    /// Quantity available on both legs at these prices
+    pub quantity: f64,
+    #[serde(rename = "legAPrice")]
+    pub leg_a_price: f64,
+    #[serde(rename = "legBPrice")]
+    pub leg_b_price: f64,
+}
+
+impl SpreadDefinition {
+    /// Side of each leg when the spread is bought or sold
+    ///
+    /// #Returns
+    /// * (OrderSide, OrderSide) - The sides of leg A and leg B
+    pub fn leg_sides(side: OrderSide) -> (OrderSide, OrderSide) {
+        match side {
+            OrderSide::Buy => (OrderSide::Buy, OrderSide::Sell),
+            OrderSide::Sell => (OrderSide::Sell, OrderSide::Buy),
+        }
+    }
+
+    /// Implied prices of the spread: selling it sells A at its best bid and buys B at its best ask,
+    /// buying it buys A at its best ask and sells B at its best bid
+    ///
+    /// #Parameters
+    /// * 'leg_a' - The orderbook of leg A
+    /// * 'leg_b' - The orderbook of leg B
+    ///
+    /// #Returns
+    /// * (Option<ImpliedPrice>, Option<ImpliedPrice>) - The implied bid and ask, None when a leg has no liquidity
+    pub fn implied(
+        &self,
+        leg_a: &Orderbook,
+        leg_b: &Orderbook,
+    ) -> (Option<ImpliedPrice>, Option<ImpliedPrice>) {
+        let (a_bids, a_asks) = leg_a.depth(1);
+        let (b_bids, b_asks) = leg_b.depth(1);
+        let imply = |a: Option<&PriceLevel>, b: Option<&PriceLevel>| {
+            let (a, b) = (a?, b?);
+            Some(ImpliedPrice {
+                price: a.price - b.price,
+                quantity: a.quantity.min(b.quantity),
+                leg_a_price: a.price,
+                leg_b_price: b.price,
+            })
+        };
+        (
+            imply(a_bids.first(), b_asks.first()),
+            imply(a_asks.first(), b_bids.first()),
+        )
+    }
+}
+
+/// Quantity a leg order can take at a price: the visible quantity of the resting orders at that price.
+/// When the orderbook prevents self trades the orders of its own user are skipped, and the orders
+/// behind the first of them are left out if it would cancel the leg. Nothing when the orderbook does
+/// not match orders.
+///
+/// #Parameters
+/// * 'book' - The orderbook of the leg
+/// * 'side' - The side of the leg order
+/// * 'price' - The price of the leg order, the best price of the other side
+/// * 'user_id' - The user sending the leg order
+pub fn takeable(book: &Orderbook, side: OrderSide, price: f64, user_id: UserId) -> f64 {
+    if !book.state.matches_orders() {
+        return 0.0;
+    }
+    let makers = match side {
+        OrderSide::Buy => &book.asks,
+        OrderSide::Sell => &book.bids,
+    };
+    let mut level: Vec<_> = makers
+        .iter_ref()
+        .filter(|o| o.price == Some(price))
+        .collect();
+    level.sort_by_key(|o| o.arrival_seq);
+    let stp = book.self_trade_prevention;
+    level
+        .into_iter()
+        .filter(|o| stp != SelfTradePrevention::CancelMaker || o.user_id != user_id)
+        .take_while(|o| stp == SelfTradePrevention::Allow || o.user_id != user_id)
+        .map(|o| o.quantity)
+        .sum()
+}
+
+/// Simultaneous execution of the two legs of a spread order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpreadExecution {
+    /// Spread quantity executed, the quantity both legs traded
+    pub quantity: f64,
+    /// Spread price of the execution, the price of leg A minus the price of leg B
+    pub price: f64,
+    #[serde(rename = "legAOrderId")]
+    pub leg_a_order_id: OrderId,
+    #[serde(rename = "legAPrice")]
+    pub leg_a_price: f64,
+    /// Quantity leg A traded, more than the spread quantity when leg B did not trade all of it
+    #[serde(rename = "legAQuantity")]
+    pub leg_a_quantity: f64,
+    #[serde(rename = "legBOrderId")]
+    pub leg_b_order_id: OrderId,
+    #[serde(rename = "legBPrice")]
+    pub leg_b_price: f64,
+    #[serde(rename = "legBQuantity")]
+    pub leg_b_quantity: f64,
+}
+
+/// Result of a spread order: its acknowledgement and the executions of its legs
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SpreadAck {
+    pub ack: OrderAck,
+    pub executions: Vec<SpreadExecution>,
+}
@@ -0,0 +1,314 @@
+use super::orderbook_update::OrderbookUpdate;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::overflow_policy::OverflowPolicy;
+use crossbeam_channel::{RecvError, TryRecvError};
+use futures_util::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct QueueState {
+    items: VecDeque<OrderbookUpdate>,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    waker: Option<Waker>,
+    /// The publisher side is gone or the queue overflowed with the Error policy
+    closed: bool,
+    /// The subscriber side is gone
+    disconnected: bool,
+    overflowed: bool,
+    dropped: u64,
+}
+
+#[derive(Debug)]
+struct Queue {
+    state: Mutex<QueueState>,
+    space: Condvar,
+    items: Condvar,
+}
+
+/// Receiving end of a subscription to the orderbook updates.
+///
+/// It can be consumed as an async `Stream`, which never blocks the executor, or with the
+/// blocking `recv` / non blocking `try_recv` methods.
+#[derive(Debug)]
+pub struct Subscription {
+    queue: Arc<Queue>,
+}
+
+/// Publishing end of a subscription, held by the update bus
+#[derive(Debug)]
+pub struct SubscriptionSender {
+    queue: Arc<Queue>,
+}
+
+/// Create a subscription queue
+///
+/// #Parameters
+/// * 'capacity' - The maximum number of queued updates, None for an unbounded queue
+/// * 'policy' - What to do when an update is published to a full queue
+pub fn subscription(
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+) -> (SubscriptionSender, Subscription) {
+    let queue = Arc::new(Queue {
+        state: Mutex::new(QueueState {
+            items: VecDeque::new(),
+            capacity: capacity.map(|c| c.max(1)),
+            policy,
+            waker: None,
+            closed: false,
+            disconnected: false,
+            overflowed: false,
+            dropped: 0,
+        }),
+        space: Condvar::new(),
+        items: Condvar::new(),
+    });
+    (
+        SubscriptionSender {
+            queue: queue.clone(),
+        },
+        Subscription { queue },
+    )
+}
+
+impl SubscriptionSender {
+    /// Number of updates queued and not received yet
+    pub fn queued(&self) -> usize {
+        self.queue.state.lock().unwrap().items.len()
+    }
+
+    /// Queue the update according to the overflow policy
+    ///
+    /// #Returns
+    /// * bool - false when the subscription is over and the sender can be dropped
+    pub fn send(&self, update: OrderbookUpdate) -> bool {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if state.disconnected || state.closed {
+                return false;
+            }
+            match state.capacity {
+                Some(capacity) if state.items.len() >= capacity => match state.policy {
+                    OverflowPolicy::DropOldest => {
+                        state.items.pop_front();
+                        state.dropped += 1;
+                        break;
+                    }
+                    OverflowPolicy::Block => {
+                        state = self.queue.space.wait(state).unwrap();
+                    }
+                    OverflowPolicy::Coalesce => {
+                        match Self::coalesced(&state.items, &update) {
+                            Some(position) => state.items.remove(position),
+                            None => state.items.pop_front(),
+                        };
+                        state.dropped += 1;
+                        break;
+                    }
+                    OverflowPolicy::Error => {
+                        state.overflowed = true;
+                        state.closed = true;
+                        state.dropped += 1;
+                        Self::notify(&self.queue, &mut state);
+                        return false;
+                    }
+                },
+                _ => break,
+            }
+        }
+        state.items.push_back(update);
+        Self::notify(&self.queue, &mut state);
+        true
+    }
+
+    /// Position of the queued update superseded by a new one: the last update of the same order
+    fn coalesced(items: &VecDeque<OrderbookUpdate>, update: &OrderbookUpdate) -> Option<usize> {
+        let key = |u: &OrderbookUpdate| match u.update_type {
+            OrderbookUpdateType::NewTrades => None,
+            _ => u.order.map(|o| (u.symbol, o.id)),
+        };
+        let update_key = key(update)?;
+        items
+            .iter()
+            .rposition(|queued| key(queued) == Some(update_key))
+    }
+
+    fn notify(queue: &Queue, state: &mut QueueState) {
+        queue.items.notify_all();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for SubscriptionSender {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.closed = true;
+        Self::notify(&self.queue, &mut state);
+    }
+}
+
+impl Subscription {
+    /// Block until an update is available, fails once the subscription is closed and drained
+    pub fn recv(&self) -> Result<OrderbookUpdate, RecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(update) = state.items.pop_front() {
+                self.queue.space.notify_all();
+                return Ok(update);
+            }
+            if state.closed {
+                return Err(RecvError);
+            }
+            state = self.queue.items.wait(state).unwrap();
+        }
+    }
+
+    /// Return an update if one is queued
+    pub fn try_recv(&self) -> Result<OrderbookUpdate, TryRecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        match state.items.pop_front() {
+            Some(update) => {
+                self.queue.space.notify_all();
+                Ok(update)
+            }
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Number of queued updates
+    pub fn len(&self) -> usize {
+        self.queue.state.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of updates lost because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().unwrap().dropped
+    }
+
+    /// Whether the subscription was closed because the queue overflowed with the Error policy
+    pub fn is_overflowed(&self) -> bool {
+        self.queue.state.lock().unwrap().overflowed
+    }
+}
+
+impl Stream for Subscription {
+    type Item = OrderbookUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.queue.state.lock().unwrap();
+        if let Some(update) = state.items.pop_front() {
+            self.queue.space.notify_all();
+            return Poll::Ready(Some(update));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.disconnected = true;
+        state.items.clear();
+        self.queue.space.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ids::OrderId;
+    use crate::structs::order::Order;
+    use futures_util::StreamExt;
+
+    fn update(sequence: u64) -> OrderbookUpdate {
+        OrderbookUpdate {
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (tx, rx) = subscription(Some(2), OverflowPolicy::DropOldest);
+        for sequence in 1..=3 {
+            assert!(tx.send(update(sequence)));
+        }
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.recv().unwrap().sequence, 2);
+        assert_eq!(rx.recv().unwrap().sequence, 3);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_coalesce_keeps_the_latest_state_of_each_order() {
+        let order = |id: u128, update_type: OrderbookUpdateType| OrderbookUpdate {
+            update_type,
+            order: Some(Order {
+                id: OrderId(id),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (tx, rx) = subscription(Some(2), OverflowPolicy::Coalesce);
+        assert!(tx.send(order(1, OrderbookUpdateType::New)));
+        assert!(tx.send(order(2, OrderbookUpdateType::New)));
+        assert!(tx.send(order(1, OrderbookUpdateType::Cancel)));
+        assert!(tx.send(update(3)));
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(rx.recv().unwrap().update_type, OrderbookUpdateType::Cancel);
+        assert_eq!(rx.recv().unwrap().sequence, 3);
+    }
+
+    #[test]
+    fn test_error_policy_closes_subscription() {
+        let (tx, rx) = subscription(Some(1), OverflowPolicy::Error);
+        assert!(tx.send(update(1)));
+        assert!(!tx.send(update(2)));
+        assert!(rx.is_overflowed());
+        assert_eq!(rx.recv().unwrap().sequence, 1);
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_block_policy_waits_for_room() {
+        let (tx, rx) = subscription(Some(1), OverflowPolicy::Block);
+        let publisher = std::thread::spawn(move || {
+            for sequence in 1..=3 {
+                tx.send(update(sequence));
+            }
+        });
+        let received: Vec<u64> = (0..3).map(|_| rx.recv().unwrap().sequence).collect();
+        publisher.join().unwrap();
+        assert_eq!(received, vec![1, 2, 3]);
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_is_woken_by_publisher() {
+        let (tx, mut rx) = subscription(None, OverflowPolicy::default());
+        let publisher = tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            tx.send(update(1));
+        });
+        assert_eq!(rx.next().await.unwrap().sequence, 1);
+        publisher.await.unwrap();
+        assert!(rx.next().await.is_none());
+    }
+}
@@ -0,0 +1,391 @@
+use super::ids::{Symbol, TenantId, UserId};
+use super::orderbook_update::OrderbookUpdate;
+use super::orderbooks_manager::OrderbooksManager;
+use super::subscription::Subscription;
+use super::tenant::TenantRegistry;
+use crate::enums::market_feed::MarketFeed;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::overflow_policy::OverflowPolicy;
+use futures_util::Stream;
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Criteria an update must meet to be delivered, an empty criterion matches everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateFilter {
+    pub symbols: HashSet<Symbol>,
+    pub update_types: Vec<OrderbookUpdateType>,
+    pub user_id: Option<UserId>,
+    /// Only the updates of the orderbooks of this tenant, checked against the tenants of the manager
+    pub tenant: Option<TenantId>,
+}
+
+impl UpdateFilter {
+    /// Whether the update involves the user, as the owner of the order or a side of the trade
+    pub fn involves_user(update: &OrderbookUpdate, user_id: UserId) -> bool {
+        update.order.is_some_and(|o| o.user_id == user_id)
+            || update
+                .trade
+                .as_ref()
+                .is_some_and(|t| t.buy_user_id == user_id || t.sell_user_id == user_id)
+    }
+
+    pub fn matches(&self, update: &OrderbookUpdate) -> bool {
+        (self.symbols.is_empty() || self.symbols.contains(&update.symbol))
+            && (self.update_types.is_empty() || self.update_types.contains(&update.update_type))
+            && self
+                .user_id
+                .is_none_or(|user_id| UpdateFilter::involves_user(update, user_id))
+    }
+}
+
+/// Subscription delivering only the updates matching a filter, in the flavor of its feed
+#[derive(Debug)]
+pub struct FilteredSubscription {
+    subscription: Subscription,
+    filter: UpdateFilter,
+    feed: MarketFeed,
+    /// Tenants owning the orderbooks, for the tenant of the filter
+    tenants: TenantRegistry,
+}
+
+impl FilteredSubscription {
+    pub fn new(subscription: Subscription, filter: UpdateFilter) -> FilteredSubscription {
+        FilteredSubscription {
+            subscription,
+            filter,
+            feed: MarketFeed::default(),
+            tenants: TenantRegistry::default(),
+        }
+    }
+
+    /// Deliver the updates in the flavor of a feed, market-by-order by default
+    pub fn with_feed(mut self, feed: MarketFeed) -> FilteredSubscription {
+        self.feed = feed;
+        self
+    }
+
+    /// Check the tenant of the filter against these tenants, the orderbooks given to the tenant
+    /// later on are included
+    pub fn with_tenants(mut self, tenants: TenantRegistry) -> FilteredSubscription {
+        self.tenants = tenants;
+        self
+    }
+
+    pub fn filter(&self) -> &UpdateFilter {
+        &self.filter
+    }
+
+    pub fn feed(&self) -> MarketFeed {
+        self.feed
+    }
+
+    /// The update as delivered by the feed, None when the feed has nothing to deliver for it
+    fn view(&self, update: OrderbookUpdate) -> Option<OrderbookUpdate> {
+        if !self.filter.matches(&update) {
+            return None;
+        }
+        if let Some(tenant) = self.filter.tenant {
+            if self.tenants.tenant_of_book(update.symbol) != Some(tenant) {
+                return None;
+            }
+        }
+        match self.feed {
+            MarketFeed::MarketByOrder => Some(update),
+            MarketFeed::MarketByPrice if update.levels.is_empty() => None,
+            MarketFeed::MarketByPrice => Some(OrderbookUpdate {
+                symbol: update.symbol,
+                update_type: update.update_type,
+                sequence: update.sequence,
+                timestamp: update.timestamp,
+                levels: update.levels,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Number of updates lost because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.subscription.dropped()
+    }
+}
+
+impl Stream for FilteredSubscription {
+    type Item = OrderbookUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.subscription).poll_next(cx) {
+                Poll::Ready(Some(update)) => {
+                    if let Some(update) = self.view(update) {
+                        return Poll::Ready(Some(update));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+/// Builder of a filtered subscription to the updates of an OrderbooksManager
+///
+/// ```ignore
+/// let updates = orderbooks_manager
+///     .subscribe()
+///     .symbol(symbol)
+///     .update_types([OrderbookUpdateType::NewTrades, OrderbookUpdateType::Filled])
+///     .user(user_id)
+///     .stream();
+/// ```
+#[derive(Debug)]
+pub struct SubscriptionBuilder<'a> {
+    manager: &'a OrderbooksManager,
+    filter: UpdateFilter,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    feed: MarketFeed,
+}
+
+impl<'a> SubscriptionBuilder<'a> {
+    pub fn new(manager: &'a OrderbooksManager) -> SubscriptionBuilder<'a> {
+        SubscriptionBuilder {
+            manager,
+            filter: UpdateFilter::default(),
+            capacity: manager.subscription_capacity,
+            policy: manager.overflow_policy,
+            feed: MarketFeed::default(),
+        }
+    }
+
+    /// Only deliver the updates of this symbol, can be called several times
+    pub fn symbol(mut self, symbol: Symbol) -> Self {
+        self.filter.symbols.insert(symbol);
+        self
+    }
+
+    /// Only deliver the updates of these symbols
+    pub fn symbols(mut self, symbols: impl IntoIterator<Item = Symbol>) -> Self {
+        self.filter.symbols.extend(symbols);
+        self
+    }
+
+    /// Only deliver the updates of this type, can be called several times
+    pub fn update_type(mut self, update_type: OrderbookUpdateType) -> Self {
+        if !self.filter.update_types.contains(&update_type) {
+            self.filter.update_types.push(update_type);
+        }
+        self
+    }
+
+    /// Only deliver the updates of these types
+    pub fn update_types(
+        mut self,
+        update_types: impl IntoIterator<Item = OrderbookUpdateType>,
+    ) -> Self {
+        for update_type in update_types {
+            self = self.update_type(update_type);
+        }
+        self
+    }
+
+    /// Only deliver the updates involving this user
+    pub fn user(mut self, user_id: UserId) -> Self {
+        self.filter.user_id = Some(user_id);
+        self
+    }
+
+    /// Only deliver the updates of the orderbooks of this tenant
+    pub fn tenant(mut self, tenant: TenantId) -> Self {
+        self.filter.tenant = Some(tenant);
+        self
+    }
+
+    /// Override the queue settings of the manager for this subscription
+    pub fn capacity(mut self, capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        self.capacity = capacity;
+        self.policy = policy;
+        self
+    }
+
+    /// Receive the updates order by order, or only the price levels they changed
+    pub fn feed(mut self, feed: MarketFeed) -> Self {
+        self.feed = feed;
+        self
+    }
+
+    /// Subscribe, the stream receives the matching updates published from now on
+    pub fn stream(self) -> FilteredSubscription {
+        let subscription = self
+            .manager
+            .subscribe_updates_with(self.capacity, self.policy);
+        FilteredSubscription::new(subscription, self.filter)
+            .with_feed(self.feed)
+            .with_tenants(self.manager.tenants.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::level_action::LevelAction;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::level_book::LevelDelta;
+    use crate::structs::order::Order;
+    use futures_util::StreamExt;
+    use ulid::Ulid;
+
+    #[tokio::test]
+    async fn test_filtered_subscription() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol1: Symbol = Ulid::new().into();
+        let symbol2: Symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol1);
+        orderbooks_manager.new_orderbook(symbol2);
+        let user: UserId = Ulid::new().into();
+
+        let mut symbol_stream = orderbooks_manager
+            .subscribe()
+            .symbol(symbol2)
+            .update_type(OrderbookUpdateType::Place)
+            .stream();
+        let mut user_stream = orderbooks_manager
+            .subscribe()
+            .user(user)
+            .update_types([OrderbookUpdateType::Cancel, OrderbookUpdateType::NewTrades])
+            .stream();
+
+        let order1 = Order::new(
+            user,
+            symbol1,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order2 = Order::new(
+            Ulid::new().into(),
+            symbol2,
+            OrderSide::Buy,
+            1.0,
+            Some(1.0),
+            OrderType::Limit,
+        );
+        let order3 = Order::new(
+            Ulid::new().into(),
+            symbol2,
+            OrderSide::Sell,
+            1.0,
+            Some(2.0),
+            OrderType::Limit,
+        );
+        let _ = orderbooks_manager.add_order(order1);
+        let _ = orderbooks_manager.add_order(order2);
+        let _ = orderbooks_manager.add_order(order3);
+        let _ = orderbooks_manager.cancel_order(order1.id, symbol1, order1.side);
+
+        let update = symbol_stream.next().await.unwrap();
+        assert_eq!(
+            update.order,
+            Some(Order {
+                arrival_seq: 1,
+                ..order2
+            })
+        );
+        let update = symbol_stream.next().await.unwrap();
+        assert_eq!(
+            update.order,
+            Some(Order {
+                arrival_seq: 2,
+                ..order3
+            })
+        );
+
+        let update = user_stream.next().await.unwrap();
+        assert_eq!(update.update_type, OrderbookUpdateType::Cancel);
+        assert_eq!(update.cancel_id, Some(order1.id));
+    }
+
+    #[tokio::test]
+    async fn test_market_by_price_feed() {
+        let mut orderbooks_manager = OrderbooksManager::new();
+        let symbol: Symbol = Ulid::new().into();
+        orderbooks_manager.new_orderbook(symbol);
+        let mut by_order = orderbooks_manager.subscribe().symbol(symbol).stream();
+        let mut by_price = orderbooks_manager
+            .subscribe()
+            .symbol(symbol)
+            .feed(MarketFeed::MarketByPrice)
+            .stream();
+
+        let order = |side, quantity, price| {
+            Order::new(
+                Ulid::new().into(),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        let first = order(OrderSide::Buy, 1.0, 10.0);
+        let second = order(OrderSide::Buy, 2.0, 10.0);
+        let _ = orderbooks_manager.add_order(first);
+        let _ = orderbooks_manager.add_order(second);
+        let _ = orderbooks_manager.cancel_order(first.id, symbol, first.side);
+        let _ = orderbooks_manager.cancel_order(second.id, symbol, second.side);
+
+        let delta = |action, quantity, orders| LevelDelta {
+            side: OrderSide::Buy,
+            action,
+            price: 10.0,
+            quantity,
+            orders,
+        };
+        // the New updates leave the levels as they are and are not delivered
+        let update = by_price.next().await.unwrap();
+        assert_eq!(update.update_type, OrderbookUpdateType::Place);
+        assert_eq!(update.order, None);
+        assert_eq!(update.levels, vec![delta(LevelAction::Add, 1.0, 1)]);
+        let update = by_price.next().await.unwrap();
+        assert_eq!(update.levels, vec![delta(LevelAction::Change, 3.0, 2)]);
+        let update = by_price.next().await.unwrap();
+        assert_eq!(update.update_type, OrderbookUpdateType::Cancel);
+        assert_eq!(update.levels, vec![delta(LevelAction::Change, 2.0, 1)]);
+        let update = by_price.next().await.unwrap();
+        assert_eq!(update.levels, vec![delta(LevelAction::Delete, 0.0, 0)]);
+
+        // the same levels ride on the order by order updates
+        let mut levels = Vec::new();
+        while levels.len() < 4 {
+            levels.extend(by_order.next().await.unwrap().levels);
+        }
+        assert_eq!(
+            levels,
+            vec![
+                delta(LevelAction::Add, 1.0, 1),
+                delta(LevelAction::Change, 3.0, 2),
+                delta(LevelAction::Change, 2.0, 1),
+                delta(LevelAction::Delete, 0.0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_matches_trade_users() {
+        let user: UserId = Ulid::new().into();
+        let filter = UpdateFilter {
+            user_id: Some(user),
+            ..Default::default()
+        };
+        let mut update = OrderbookUpdate {
+            update_type: OrderbookUpdateType::NewTrades,
+            trade: Some(Default::default()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&update));
+        update.trade.as_mut().unwrap().sell_user_id = user;
+        assert!(filter.matches(&update));
+    }
+}
@@ -0,0 +1,387 @@
+use super::ids::Symbol;
+use super::level_book::PriceLevel;
+use super::order::Order;
+use super::orderbook_config::is_multiple_of;
+use super::orderbook_sum::OrderBookSummarized;
+use super::orderbook_update::OrderbookUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// Reference data of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    /// The asset bought and sold
+    #[serde(rename = "baseAsset")]
+    pub base_asset: u128,
+    /// The asset the prices are expressed in
+    #[serde(rename = "quoteAsset")]
+    pub quote_asset: u128,
+    /// Price increment, None to accept any precision
+    #[serde(rename = "tickSize", default)]
+    pub tick_size: Option<f64>,
+    /// Smallest quantity of an order, hidden quantity included
+    #[serde(rename = "minQuantity", default)]
+    pub min_quantity: Option<f64>,
+    /// Smallest price times quantity of an order, market orders having no price are not checked
+    #[serde(rename = "minNotional", default)]
+    pub min_notional: Option<f64>,
+    /// Decimal places the emitted prices are rounded to, None to leave them as computed
+    #[serde(rename = "pricePrecision", default)]
+    pub price_precision: Option<u32>,
+    /// Decimal places the emitted quantities are rounded to, None to leave them as computed
+    #[serde(rename = "quantityPrecision", default)]
+    pub quantity_precision: Option<u32>,
+}
+
+/// Decimal places of the summary percentages once the quantities of a symbol are rounded
+pub const PERCENT_PRECISION: u32 = 2;
+
+/// Round a value to a number of decimal places
+pub fn round_to(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Decimal places the prices and quantities of a symbol are emitted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Precision {
+    pub price: Option<u32>,
+    pub quantity: Option<u32>,
+}
+
+impl Precision {
+    pub fn round_price(&self, price: f64) -> f64 {
+        self.price
+            .map_or(price, |decimals| round_to(price, decimals))
+    }
+
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        self.quantity
+            .map_or(quantity, |decimals| round_to(quantity, decimals))
+    }
+
+    pub fn round_level(&self, level: PriceLevel) -> PriceLevel {
+        PriceLevel {
+            price: self.round_price(level.price),
+            quantity: self.round_quantity(level.quantity),
+            ..level
+        }
+    }
+
+    /// Round the prices and quantities of the trade and the level changes of an update
+    pub fn round_update(&self, update: &mut OrderbookUpdate) {
+        if let Some(trade) = update.trade.as_mut() {
+            trade.price = self.round_price(trade.price);
+            trade.quantity = self.round_quantity(trade.quantity);
+        }
+        for delta in update.levels.iter_mut() {
+            delta.price = self.round_price(delta.price);
+            delta.quantity = self.round_quantity(delta.quantity);
+        }
+        update.reference_price = update.reference_price.map(|price| self.round_price(price));
+    }
+
+    /// Round the prices, the quantities and, with the quantities, the percentages of a summary
+    pub fn round_summary(&self, summary: &mut OrderBookSummarized) {
+        summary.mid_price = self.round_price(summary.mid_price);
+        summary.reference_price = summary.reference_price.map(|price| self.round_price(price));
+        for level in summary.bids.iter_mut().chain(summary.asks.iter_mut()) {
+            level.price = self.round_price(level.price);
+            level.qty = self.round_quantity(level.qty);
+            level.qty_sum = self.round_quantity(level.qty_sum);
+            if self.quantity.is_some() {
+                level.qty_percent = round_to(level.qty_percent, PERCENT_PRECISION);
+            }
+        }
+        for level in summary
+            .bid_liquidity
+            .iter_mut()
+            .chain(summary.ask_liquidity.iter_mut())
+        {
+            level.price = self.round_price(level.price);
+            level.visible_qty = self.round_quantity(level.visible_qty);
+            level.total_qty = self.round_quantity(level.total_qty);
+        }
+    }
+}
+
+impl SymbolInfo {
+    /// A symbol exchanging two assets without trading rules
+    pub fn new(base_asset: u128, quote_asset: u128) -> SymbolInfo {
+        SymbolInfo {
+            base_asset,
+            quote_asset,
+            tick_size: None,
+            min_quantity: None,
+            min_notional: None,
+            price_precision: None,
+            quantity_precision: None,
+        }
+    }
+
+    /// Decimal places the prices and quantities of the symbol are emitted with
+    pub fn precision(&self) -> Precision {
+        Precision {
+            price: self.price_precision,
+            quantity: self.quantity_precision,
+        }
+    }
+
+    /// Check an order follows the trading rules of the symbol
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An InvalidInput error naming the broken rule
+    pub fn validate(&self, order: &Order) -> Result<(), Error> {
+        let quantity = order.quantity + order.hidden_quantity;
+        if let (Some(tick_size), Some(price)) = (self.tick_size, order.price) {
+            if !is_multiple_of(price, tick_size) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Order price precision beyond the tick size",
+                ));
+            }
+        }
+        if self.min_quantity.is_some_and(|minimum| quantity < minimum) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Order quantity below the minimum lot",
+            ));
+        }
+        if let (Some(minimum), Some(price)) = (self.min_notional, order.price) {
+            if price * quantity < minimum {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Order notional below the minimum",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reference data of the symbols traded by the engine. Clones share the same symbols.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    symbols: Arc<Mutex<HashMap<Symbol, SymbolInfo>>>,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> SymbolRegistry {
+        SymbolRegistry::default()
+    }
+
+    /// Register a symbol, replacing its previous reference data
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'info' - The reference data of the symbol
+    pub fn register(&self, symbol: Symbol, info: SymbolInfo) {
+        self.symbols.lock().unwrap().insert(symbol, info);
+    }
+
+    /// Remove a symbol
+    ///
+    /// #Returns
+    /// * Option<SymbolInfo> - The reference data of the symbol, None if it was not registered
+    pub fn unregister(&self, symbol: Symbol) -> Option<SymbolInfo> {
+        self.symbols.lock().unwrap().remove(&symbol)
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Option<SymbolInfo> {
+        self.symbols.lock().unwrap().get(&symbol).copied()
+    }
+
+    /// Decimal places the prices and quantities of a symbol are emitted with, none for an
+    /// unregistered symbol
+    pub fn precision(&self, symbol: Symbol) -> Precision {
+        self.get(symbol)
+            .map(|info| info.precision())
+            .unwrap_or_default()
+    }
+
+    /// Round a price to the precision of its symbol
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'price' - The price
+    ///
+    /// #Returns
+    /// * f64 - The rounded price, unchanged if the symbol has no price precision
+    pub fn round_price(&self, symbol: Symbol, price: f64) -> f64 {
+        self.precision(symbol).round_price(price)
+    }
+
+    /// Round a quantity to the precision of its symbol
+    ///
+    /// #Parameters
+    /// * 'symbol' - The symbol ID
+    /// * 'quantity' - The quantity
+    ///
+    /// #Returns
+    /// * f64 - The rounded quantity, unchanged if the symbol has no quantity precision
+    pub fn round_quantity(&self, symbol: Symbol, quantity: f64) -> f64 {
+        self.precision(symbol).round_quantity(quantity)
+    }
+
+    /// Registered symbols
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.symbols.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Check an order against the reference data of its symbol. An empty registry lets every
+    /// order through, once a symbol is registered the orders of the other symbols are rejected.
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A NotFound error for an unknown symbol, an InvalidInput error naming the broken rule otherwise
+    pub fn validate(&self, order: &Order) -> Result<(), Error> {
+        let symbols = self.symbols.lock().unwrap();
+        if symbols.is_empty() {
+            return Ok(());
+        }
+        match symbols.get(&order.symbol) {
+            Some(info) => info.validate(order),
+            None => Err(Error::new(ErrorKind::NotFound, "Unknown symbol")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::ids::UserId;
+    use crate::structs::orderbooks_manager::OrderbooksManager;
+
+    fn reason(registry: &SymbolRegistry, order: &Order) -> Option<String> {
+        registry.validate(order).err().map(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_reference_data_validation() {
+        let registry = SymbolRegistry::new();
+        let (symbol, other) = (Symbol(1), Symbol(2));
+        let order = |symbol, quantity, price| {
+            Order::new(
+                UserId(1),
+                symbol,
+                OrderSide::Buy,
+                quantity,
+                price,
+                OrderType::Limit,
+            )
+        };
+        assert_eq!(reason(&registry, &order(other, 1.0, Some(1.0))), None);
+
+        registry.register(
+            symbol,
+            SymbolInfo {
+                tick_size: Some(0.5),
+                min_quantity: Some(2.0),
+                min_notional: Some(10.0),
+                ..SymbolInfo::new(1, 2)
+            },
+        );
+        let rejected = |quantity, price| reason(&registry, &order(symbol, quantity, price));
+        assert_eq!(rejected(4.0, Some(5.0)), None);
+        assert_eq!(
+            reason(&registry, &order(other, 4.0, Some(5.0))).unwrap(),
+            "Unknown symbol"
+        );
+        assert_eq!(
+            rejected(4.0, Some(5.25)).unwrap(),
+            "Order price precision beyond the tick size"
+        );
+        assert_eq!(
+            rejected(1.0, Some(50.0)).unwrap(),
+            "Order quantity below the minimum lot"
+        );
+        assert_eq!(
+            rejected(2.0, Some(4.5)).unwrap(),
+            "Order notional below the minimum"
+        );
+        // a market order has no notional to check
+        assert_eq!(rejected(2.0, None), None);
+    }
+
+    #[test]
+    fn test_emitted_prices_are_rounded() {
+        let mut manager = OrderbooksManager::new();
+        let symbol = Symbol(1);
+        manager.new_orderbook(symbol);
+        manager.symbols.register(
+            symbol,
+            SymbolInfo {
+                price_precision: Some(2),
+                quantity_precision: Some(4),
+                ..SymbolInfo::new(1, 2)
+            },
+        );
+        assert_eq!(manager.round_price(symbol, 0.1 + 0.2), 0.3);
+        assert_eq!(manager.round_price(Symbol(2), 0.1 + 0.2), 0.1 + 0.2);
+        let order = |side, quantity, price| {
+            Order::new(
+                UserId(1),
+                symbol,
+                side,
+                quantity,
+                Some(price),
+                OrderType::Limit,
+            )
+        };
+        for price in [0.1 + 0.2, 0.2, 0.1] {
+            manager
+                .add_order(order(OrderSide::Sell, 1.0, price))
+                .unwrap();
+        }
+        let summary = manager.get_orderbook(symbol).unwrap();
+        assert_eq!(summary.asks[0].price, 0.1);
+        assert_eq!(summary.asks[2].price, 0.3);
+        assert_eq!(summary.asks[0].qty_percent, 33.33);
+
+        let updates = manager.subscribe_updates();
+        manager.add_order(order(OrderSide::Buy, 3.0, 0.31)).unwrap();
+        let trade_prices: Vec<f64> = std::iter::from_fn(|| updates.try_recv().ok())
+            .filter_map(|update| update.trade)
+            .map(|trade| trade.price)
+            .collect();
+        assert_eq!(trade_prices, vec![0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn test_the_engine_settles_the_unrounded_trades() {
+        let mut manager = OrderbooksManager::new();
+        let symbol = Symbol(1);
+        let (base, quote) = (1, 2);
+        let (buyer, seller) = (UserId(1), UserId(2));
+        manager.new_orderbook(symbol);
+        manager.symbols.register(
+            symbol,
+            SymbolInfo {
+                quantity_precision: Some(0),
+                ..SymbolInfo::new(base, quote)
+            },
+        );
+        let accounts = manager.enable_accounts();
+        accounts.register_market(symbol, base, quote);
+        accounts.deposit(buyer, quote, 10.0);
+        accounts.deposit(seller, base, 1.0);
+        let order =
+            |user_id, side| Order::new(user_id, symbol, side, 0.4, Some(10.0), OrderType::Limit);
+        manager.add_order(order(seller, OrderSide::Sell)).unwrap();
+        let updates = manager.subscribe_updates();
+        manager.add_order(order(buyer, OrderSide::Buy)).unwrap();
+
+        // the market sees the quantity rounded, the accounts are settled with what traded
+        let trade = std::iter::from_fn(|| updates.try_recv().ok())
+            .find_map(|update| update.trade)
+            .unwrap();
+        assert_eq!(trade.quantity, 0.0);
+        let accounts = manager.accounts.as_ref().unwrap();
+        assert_eq!(accounts.balance(seller, quote).available, 4.0);
+        assert_eq!(accounts.balance(buyer, base).available, 0.4);
+        assert_eq!(manager.risk.position(buyer, symbol), 0.4);
+    }
+}
@@ -0,0 +1,345 @@
+use super::ids::{OrderId, Symbol, TenantId, UserId};
+use super::order::Order;
+use super::orderbook_config::OrderbookConfig;
+use super::orderbook_update::OrderbookUpdate;
+use super::orderbooks_manager::OrderbooksManager;
+use super::subscription_builder::FilteredSubscription;
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::metrics::SymbolMetrics;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+
+/// Resources a tenant may use, None for no limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TenantQuota {
+    /// Orderbooks owned by the tenant
+    #[serde(rename = "maxBooks", default)]
+    pub max_books: Option<usize>,
+    /// Orders of the users of the tenant accepted and not closed yet
+    #[serde(rename = "maxOpenOrders", default)]
+    pub max_open_orders: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct TenantState {
+    quotas: HashMap<TenantId, TenantQuota>,
+    books: HashMap<Symbol, TenantId>,
+    users: HashMap<UserId, TenantId>,
+    /// Open orders in the orderbooks of a tenant
+    open_orders: HashMap<OrderId, TenantId>,
+    open_counts: HashMap<TenantId, usize>,
+}
+
+impl TenantState {
+    fn check_tenant(&self, tenant: TenantId) -> Result<(), Error> {
+        match self.quotas.contains_key(&tenant) {
+            true => Ok(()),
+            false => Err(Error::new(ErrorKind::NotFound, "Tenant not found")),
+        }
+    }
+}
+
+/// Logical exchanges sharing an engine: the orderbooks and the users belong to tenants, a user only
+/// trades the orderbooks of its tenant, and each tenant is held to its quotas.
+/// Orderbooks and users without a tenant keep trading together. Clones share the same tenants.
+#[derive(Debug, Clone, Default)]
+pub struct TenantRegistry {
+    state: Arc<Mutex<TenantState>>,
+}
+
+impl TenantRegistry {
+    pub fn new() -> TenantRegistry {
+        TenantRegistry::default()
+    }
+
+    /// Register a tenant, replacing its quota if it is already registered
+    ///
+    /// #Parameters
+    /// * 'tenant' - The tenant ID
+    /// * 'quota' - The resources the tenant may use
+    pub fn register(&self, tenant: TenantId, quota: TenantQuota) {
+        self.state.lock().unwrap().quotas.insert(tenant, quota);
+    }
+
+    pub fn quota(&self, tenant: TenantId) -> Option<TenantQuota> {
+        self.state.lock().unwrap().quotas.get(&tenant).copied()
+    }
+
+    /// Registered tenants, sorted
+    pub fn tenants(&self) -> Vec<TenantId> {
+        let mut tenants: Vec<TenantId> =
+            self.state.lock().unwrap().quotas.keys().copied().collect();
+        tenants.sort();
+        tenants
+    }
+
+    /// Make a user a member of a tenant, moving it from its previous tenant
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A NotFound error if the tenant is not registered
+    pub fn assign_user(&self, user_id: UserId, tenant: TenantId) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.check_tenant(tenant)?;
+        state.users.insert(user_id, tenant);
+        Ok(())
+    }
+
+    /// Give an orderbook to a tenant, within the number of books of its quota
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A NotFound error if the tenant is not registered, AlreadyExists if the
+    ///   book belongs to another tenant, PermissionDenied once the quota is reached
+    pub fn assign_book(&self, symbol: Symbol, tenant: TenantId) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.check_tenant(tenant)?;
+        match state.books.get(&symbol) {
+            Some(&owner) if owner == tenant => return Ok(()),
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "Orderbook of another tenant",
+                ))
+            }
+            None => {}
+        }
+        let books = state.books.values().filter(|&&t| t == tenant).count();
+        if state.quotas[&tenant]
+            .max_books
+            .is_some_and(|maximum| books >= maximum)
+        {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Orderbooks quota of the tenant reached",
+            ));
+        }
+        state.books.insert(symbol, tenant);
+        Ok(())
+    }
+
+    /// Take an orderbook back from its tenant, e.g. once it is removed
+    pub fn release_book(&self, symbol: Symbol) {
+        self.state.lock().unwrap().books.remove(&symbol);
+    }
+
+    pub fn tenant_of_user(&self, user_id: UserId) -> Option<TenantId> {
+        self.state.lock().unwrap().users.get(&user_id).copied()
+    }
+
+    pub fn tenant_of_book(&self, symbol: Symbol) -> Option<TenantId> {
+        self.state.lock().unwrap().books.get(&symbol).copied()
+    }
+
+    /// Orderbooks of a tenant, sorted
+    pub fn books(&self, tenant: TenantId) -> Vec<Symbol> {
+        let state = self.state.lock().unwrap();
+        let mut books: Vec<Symbol> = state
+            .books
+            .iter()
+            .filter(|(_, &t)| t == tenant)
+            .map(|(&symbol, _)| symbol)
+            .collect();
+        books.sort();
+        books
+    }
+
+    /// Number of open orders in the orderbooks of a tenant
+    pub fn open_orders(&self, tenant: TenantId) -> usize {
+        let state = self.state.lock().unwrap();
+        state.open_counts.get(&tenant).copied().unwrap_or(0)
+    }
+
+    /// Check an order stays within the tenant of its orderbook and the quota of the tenant
+    ///
+    /// #Returns
+    /// * Result<(), Error> - A PermissionDenied error if the user and the orderbook belong to
+    ///   different tenants or if the open orders quota of the tenant is reached
+    pub fn check_order(&self, order: &Order) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        let tenant = state.books.get(&order.symbol).copied();
+        if state.users.get(&order.user_id).copied() != tenant {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Orderbook of another tenant",
+            ));
+        }
+        let Some(tenant) = tenant else {
+            return Ok(());
+        };
+        let open = state.open_counts.get(&tenant).copied().unwrap_or(0);
+        if state
+            .quotas
+            .get(&tenant)
+            .and_then(|quota| quota.max_open_orders)
+            .is_some_and(|maximum| open >= maximum)
+        {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Open orders quota of the tenant reached",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Count an accepted order against the quota of the tenant of its orderbook
+    pub fn on_order_accepted(&self, order: &Order) {
+        let mut state = self.state.lock().unwrap();
+        let Some(tenant) = state.books.get(&order.symbol).copied() else {
+            return;
+        };
+        if state.open_orders.insert(order.id, tenant).is_none() {
+            *state.open_counts.entry(tenant).or_default() += 1;
+        }
+    }
+
+    /// Uncount an accepted order which never reached its book
+    pub fn on_order_revoked(&self, order_id: OrderId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tenant) = state.open_orders.remove(&order_id) {
+            if let Some(count) = state.open_counts.get_mut(&tenant) {
+                *count -= 1;
+            }
+        }
+    }
+
+    /// Release the orders leaving their book
+    pub fn on_update(&self, update: &OrderbookUpdate) {
+        if let (
+            OrderbookUpdateType::Cancel
+            | OrderbookUpdateType::Filled
+            | OrderbookUpdateType::Expired,
+            Some(order),
+        ) = (update.update_type, update.order)
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(tenant) = state.open_orders.remove(&order.id) {
+                if let Some(count) = state.open_counts.get_mut(&tenant) {
+                    *count -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl OrderbooksManager {
+    /// Create a new orderbook owned by a tenant, only the users of the tenant trade it
+    ///
+    /// Parameters
+    /// * 'tenant' : The tenant ID
+    /// * 'symbol' : The symbol ID
+    /// * 'config' : The configuration of the orderbook
+    ///
+    /// #Returns
+    /// * Result<(), Error> - An error if the orderbook exists, the tenant is unknown or its quota of orderbooks is reached
+    pub fn new_tenant_orderbook(
+        &mut self,
+        tenant: TenantId,
+        symbol: Symbol,
+        config: OrderbookConfig,
+    ) -> Result<(), Error> {
+        if self.orderbooks.contains_key(&symbol) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "Orderbook already exists",
+            ));
+        }
+        self.tenants.assign_book(symbol, tenant)?;
+        self.new_orderbook_with_config(symbol, config);
+        Ok(())
+    }
+
+    /// Counters of the orderbooks of a tenant, totalled
+    ///
+    /// Parameters
+    /// * 'tenant' : The tenant ID
+    pub fn tenant_metrics(&self, tenant: TenantId) -> SymbolMetrics {
+        let metrics = self.metrics_recorder.snapshot();
+        let mut total = SymbolMetrics::default();
+        for symbol in self.tenants.books(tenant) {
+            if let Some(book_metrics) = metrics.symbol(symbol) {
+                total.merge(book_metrics);
+            }
+        }
+        total
+    }
+
+    /// Subscribe to the updates of the orderbooks of a tenant, the books given to the tenant later on included
+    ///
+    /// Parameters
+    /// * 'tenant' : The tenant ID
+    pub fn subscribe_tenant(&self, tenant: TenantId) -> FilteredSubscription {
+        self.subscribe().tenant(tenant).stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use futures_util::StreamExt;
+    use ulid::Ulid;
+
+    #[tokio::test]
+    async fn test_tenants_are_isolated() {
+        let mut manager = OrderbooksManager::new();
+        let (first, second) = (TenantId(1), TenantId(2));
+        let quota = TenantQuota {
+            max_books: Some(1),
+            max_open_orders: Some(2),
+        };
+        manager.tenants.register(first, quota);
+        manager.tenants.register(second, TenantQuota::default());
+        let (book, other_book): (Symbol, Symbol) = (Ulid::new().into(), Ulid::new().into());
+        manager
+            .new_tenant_orderbook(first, book, OrderbookConfig::default())
+            .unwrap();
+        let error = manager
+            .new_tenant_orderbook(first, other_book, OrderbookConfig::default())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        manager
+            .new_tenant_orderbook(second, other_book, OrderbookConfig::default())
+            .unwrap();
+        let (user, outsider): (UserId, UserId) = (Ulid::new().into(), Ulid::new().into());
+        manager.tenants.assign_user(user, first).unwrap();
+        manager.tenants.assign_user(outsider, second).unwrap();
+        let mut updates = manager.subscribe_tenant(first);
+        let order = |user_id, symbol, side| {
+            Order::new(user_id, symbol, side, 1.0, Some(10.0), OrderType::Limit)
+        };
+
+        let error = manager
+            .add_order(order(outsider, book, OrderSide::Buy))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Orderbook of another tenant");
+        manager
+            .add_order(order(outsider, other_book, OrderSide::Buy))
+            .unwrap();
+        let cancelled = order(user, book, OrderSide::Buy);
+        manager.add_order(cancelled).unwrap();
+        manager
+            .add_order(order(user, book, OrderSide::Buy))
+            .unwrap();
+        let error = manager
+            .add_order(order(user, book, OrderSide::Buy))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Open orders quota of the tenant reached");
+
+        // the orders leaving the book free the quota
+        manager
+            .cancel_order(cancelled.id, book, cancelled.side)
+            .unwrap();
+        manager
+            .add_order(order(user, book, OrderSide::Sell))
+            .unwrap();
+        assert_eq!(manager.tenants.open_orders(first), 0);
+
+        let metrics = manager.tenant_metrics(first);
+        assert_eq!((metrics.orders_accepted, metrics.trades), (3, 1));
+        assert_eq!(manager.tenant_metrics(second).orders_accepted, 1);
+        let first_update = updates.next().await.unwrap();
+        assert_eq!(first_update.symbol, book);
+    }
+}
@@ -1,26 +1,139 @@
-
 use serde::{Deserialize, Serialize};
 use ulid::Ulid;
-use std::time::Instant;
 
+use crate::enums::liquidity::Liquidity;
+use crate::enums::side::OrderSide;
 use crate::enums::trade_status::TradeStatus;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::{Metadata, Order};
+use crate::structs::trade_history::TradeHistory;
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub id: Option<u128>,
-    pub buy_order_id: u128,
-    pub sell_order_id: u128,
-    pub buy_user_id: u128,
-    pub sell_user_id: u128,
+    pub buy_order_id: OrderId,
+    pub sell_order_id: OrderId,
+    pub buy_user_id: UserId,
+    pub sell_user_id: UserId,
+    /// Side of the aggressive order, the one which took the liquidity
+    #[serde(default)]
+    pub taker_side: OrderSide,
+    /// Order which was resting in the book
+    #[serde(default)]
+    pub maker_order_id: OrderId,
+    /// Fee charged to the maker, negative for a rebate
+    #[serde(default)]
+    pub maker_fee: f64,
+    /// Fee charged to the taker
+    #[serde(default)]
+    pub taker_fee: f64,
+    /// Quantity of the buy order left after the execution, hidden quantity included, 0 once filled
+    #[serde(default)]
+    pub buy_remaining: f64,
+    /// Quantity of the sell order left after the execution, hidden quantity included, 0 once filled
+    #[serde(default)]
+    pub sell_remaining: f64,
+    #[serde(default)]
+    pub buy_liquidity: Liquidity,
+    #[serde(default)]
+    pub sell_liquidity: Liquidity,
     pub price: f64,
     pub quantity: f64,
     pub status: TradeStatus,
-    pub symbol: u128,
+    pub symbol: Symbol,
     pub created_at: Option<u64>,
     pub updated_at: Option<u64>,
+    /// Metadata of the buy order
+    #[serde(default)]
+    pub buy_metadata: Option<Metadata>,
+    /// Metadata of the sell order
+    #[serde(default)]
+    pub sell_metadata: Option<Metadata>,
 }
 
 impl Trade {
+    /// Build an execution between a buy and a sell order
+    ///
+    /// #Parameters
+    /// * `symbol` - The symbol of the trade
+    /// * `price` - The execution price
+    /// * `quantity` - The executed quantity
+    /// * `buy` - The buy order, with its quantity before the execution
+    /// * `sell` - The sell order, with its quantity before the execution
+    /// * `taker_side` - The side of the aggressive order, the other order is the maker
+    pub fn between(
+        symbol: Symbol,
+        price: f64,
+        quantity: f64,
+        buy: &Order,
+        sell: &Order,
+        taker_side: OrderSide,
+    ) -> Trade {
+        Trade {
+            id: None,
+            symbol,
+            price,
+            quantity,
+            buy_order_id: buy.id,
+            sell_order_id: sell.id,
+            buy_user_id: buy.user_id,
+            sell_user_id: sell.user_id,
+            taker_side,
+            maker_order_id: match taker_side {
+                OrderSide::Buy => sell.id,
+                OrderSide::Sell => buy.id,
+            },
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            buy_remaining: (buy.quantity + buy.hidden_quantity - quantity).max(0.0),
+            sell_remaining: (sell.quantity + sell.hidden_quantity - quantity).max(0.0),
+            buy_liquidity: Trade::liquidity(OrderSide::Buy, taker_side),
+            sell_liquidity: Trade::liquidity(OrderSide::Sell, taker_side),
+            status: Default::default(),
+            created_at: None,
+            updated_at: None,
+            buy_metadata: None,
+            sell_metadata: None,
+        }
+    }
+
+    fn liquidity(side: OrderSide, taker_side: OrderSide) -> Liquidity {
+        if side == taker_side {
+            Liquidity::Taker
+        } else {
+            Liquidity::Maker
+        }
+    }
+
+    /// Quantity left of an order of the trade after the execution
+    ///
+    /// #Parameters
+    /// * `order_id` - The order ID
+    ///
+    /// #Returns
+    /// * Option<f64> - None if the order is not a side of the trade
+    pub fn remaining(&self, order_id: OrderId) -> Option<f64> {
+        if order_id == self.buy_order_id {
+            Some(self.buy_remaining)
+        } else if order_id == self.sell_order_id {
+            Some(self.sell_remaining)
+        } else {
+            None
+        }
+    }
+
+    /// Side of the order which arrived last, the aggressor when two resting orders cross
+    ///
+    /// #Parameters
+    /// * `bid` - The buy order
+    /// * `ask` - The sell order
+    pub fn later_side(bid: &Order, ask: &Order) -> OrderSide {
+        if bid.created_at > ask.created_at {
+            OrderSide::Buy
+        } else {
+            OrderSide::Sell
+        }
+    }
 
     /// Generate a Trade with 10 in price and 2 in quantity for testing purpose
     /// #Parameters
@@ -28,86 +141,127 @@ impl Trade {
     /// * `buy_order_id` - The order_id of the buy order
     /// * `sell_order_id` - The order_id of the sell order
     pub fn get_trade_10_2(
-        symbol: u128,
-        buy_order_id: u128,
-        sell_order_id: u128,
-        buy_user_id: u128,
-        sell_user_id: u128,
+        symbol: Symbol,
+        buy_order_id: OrderId,
+        sell_order_id: OrderId,
+        buy_user_id: UserId,
+        sell_user_id: UserId,
     ) -> Trade {
         Trade {
             id: Some(Ulid::new().into()),
             symbol,
             price: 10.0,
             quantity: 2.0,
-            created_at: Some(Instant::now().elapsed().as_secs()),
-            updated_at: Some(Instant::now().elapsed().as_secs()),
+            created_at: Some(TradeHistory::now()),
+            updated_at: Some(TradeHistory::now()),
+            buy_metadata: None,
+            sell_metadata: None,
             status: Default::default(),
             buy_order_id,
             sell_order_id,
             buy_user_id,
             sell_user_id,
+            taker_side: OrderSide::Buy,
+            maker_order_id: sell_order_id,
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            buy_remaining: 0.0,
+            sell_remaining: 0.0,
+            buy_liquidity: Liquidity::Taker,
+            sell_liquidity: Liquidity::Maker,
         }
     }
 
     pub fn get_trade_10_5(
-        symbol: u128,
-        buy_order_id: u128,
-        sell_order_id: u128,
-        buy_user_id: u128,
-        sell_user_id: u128,
+        symbol: Symbol,
+        buy_order_id: OrderId,
+        sell_order_id: OrderId,
+        buy_user_id: UserId,
+        sell_user_id: UserId,
     ) -> Trade {
         Trade {
             id: Some(Ulid::new().into()),
             symbol,
             price: 10.0,
             quantity: 5.0,
-            created_at: Some(Instant::now().elapsed().as_secs()),
-            updated_at: Some(Instant::now().elapsed().as_secs()),
+            created_at: Some(TradeHistory::now()),
+            updated_at: Some(TradeHistory::now()),
+            buy_metadata: None,
+            sell_metadata: None,
             status: Default::default(),
             buy_order_id,
             sell_order_id,
             buy_user_id,
             sell_user_id,
+            taker_side: OrderSide::Buy,
+            maker_order_id: sell_order_id,
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            buy_remaining: 0.0,
+            sell_remaining: 0.0,
+            buy_liquidity: Liquidity::Taker,
+            sell_liquidity: Liquidity::Maker,
         }
     }
 
     pub fn get_trade_15_2(
-        symbol: u128,
-        buy_order_id: u128,
-        sell_order_id: u128,
-        buy_user_id: u128,
-        sell_user_id: u128,
+        symbol: Symbol,
+        buy_order_id: OrderId,
+        sell_order_id: OrderId,
+        buy_user_id: UserId,
+        sell_user_id: UserId,
     ) -> Trade {
         Trade {
             id: Some(Ulid::new().into()),
             symbol,
             price: 15.0,
             quantity: 2.0,
-            created_at: Some(Instant::now().elapsed().as_secs()),
-            updated_at: Some(Instant::now().elapsed().as_secs()),
+            created_at: Some(TradeHistory::now()),
+            updated_at: Some(TradeHistory::now()),
+            buy_metadata: None,
+            sell_metadata: None,
             status: Default::default(),
             buy_order_id,
             sell_order_id,
             buy_user_id,
             sell_user_id,
+            taker_side: OrderSide::Buy,
+            maker_order_id: sell_order_id,
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            buy_remaining: 0.0,
+            sell_remaining: 0.0,
+            buy_liquidity: Liquidity::Taker,
+            sell_liquidity: Liquidity::Maker,
         }
     }
 }
 
 impl Default for Trade {
     fn default() -> Self {
+        let sell_order_id = Ulid::new().into();
         Trade {
             id: None,
             buy_order_id: Ulid::new().into(),
-            sell_order_id: Ulid::new().into(),
+            sell_order_id,
             buy_user_id: Ulid::new().into(),
             sell_user_id: Ulid::new().into(),
+            taker_side: OrderSide::Buy,
+            maker_order_id: sell_order_id,
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            buy_remaining: 0.0,
+            sell_remaining: 0.0,
+            buy_liquidity: Liquidity::Taker,
+            sell_liquidity: Liquidity::Maker,
             price: 0.0,
             quantity: 0.0,
             status: Default::default(),
             symbol: Ulid::new().into(),
-            created_at: Some(Instant::now().elapsed().as_secs()),
-            updated_at: Some(Instant::now().elapsed().as_secs()),
+            created_at: Some(TradeHistory::now()),
+            updated_at: Some(TradeHistory::now()),
+            buy_metadata: None,
+            sell_metadata: None,
         }
     }
 }
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// Number of executions kept by default for the analytics window
+pub const DEFAULT_TRADE_HISTORY_CAPACITY: usize = 10_000;
+
+/// An execution recorded for analytics purpose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Execution {
+    pub timestamp: u64,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// Rolling history of the executions of an orderbook, used to compute VWAP and TWAP
+#[derive(Debug, Clone)]
+pub struct TradeHistory {
+    executions: VecDeque<Execution>,
+    capacity: usize,
+}
+
+impl Default for TradeHistory {
+    fn default() -> Self {
+        TradeHistory::new(DEFAULT_TRADE_HISTORY_CAPACITY)
+    }
+}
+
+impl TradeHistory {
+    /// Create a new trade history keeping at most `capacity` executions
+    pub fn new(capacity: usize) -> TradeHistory {
+        TradeHistory {
+            executions: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+        }
+    }
+
+    /// Current time in milliseconds since UNIX epoch
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    /// Record an execution happening now
+    pub fn record(&mut self, price: f64, quantity: f64) {
+        self.record_at(TradeHistory::now(), price, quantity);
+    }
+
+    /// Record an execution at a given timestamp (milliseconds)
+    pub fn record_at(&mut self, timestamp: u64, price: f64, quantity: f64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.executions.len() == self.capacity {
+            self.executions.pop_front();
+        }
+        self.executions.push_back(Execution {
+            timestamp,
+            price,
+            quantity,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.executions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.executions.is_empty()
+    }
+
+    /// Last execution recorded
+    pub fn last(&self) -> Option<&Execution> {
+        self.executions.back()
+    }
+
+    fn window(&self, now: u64, window: Duration) -> impl Iterator<Item = &Execution> {
+        let start = now.saturating_sub(window.as_millis() as u64);
+        self.executions.iter().filter(move |e| e.timestamp >= start)
+    }
+
+    /// Volume weighted average price over the window ending now
+    pub fn vwap(&self, window: Duration) -> Option<f64> {
+        self.vwap_at(TradeHistory::now(), window)
+    }
+
+    /// Volume weighted average price over the window ending at `now`
+    pub fn vwap_at(&self, now: u64, window: Duration) -> Option<f64> {
+        let (notional, volume) = self.window(now, window).fold((0.0, 0.0), |(n, v), e| {
+            (n + e.price * e.quantity, v + e.quantity)
+        });
+        if volume > 0.0 {
+            Some(notional / volume)
+        } else {
+            None
+        }
+    }
+
+    /// Time weighted average price over the window ending now
+    pub fn twap(&self, window: Duration) -> Option<f64> {
+        self.twap_at(TradeHistory::now(), window)
+    }
+
+    /// Time weighted average price over the window ending at `now`.
+    /// Each execution price is weighted by the time it stayed the last traded price,
+    /// when all executions share the same timestamp the simple average is returned.
+    pub fn twap_at(&self, now: u64, window: Duration) -> Option<f64> {
+        let executions: Vec<&Execution> = self.window(now, window).collect();
+        if executions.is_empty() {
+            return None;
+        }
+        let mut weighted = 0.0;
+        let mut elapsed = 0.0;
+        for (i, execution) in executions.iter().enumerate() {
+            let end = executions.get(i + 1).map_or(now, |e| e.timestamp);
+            let dt = end.saturating_sub(execution.timestamp) as f64;
+            weighted += execution.price * dt;
+            elapsed += dt;
+        }
+        if elapsed > 0.0 {
+            Some(weighted / elapsed)
+        } else {
+            Some(executions.iter().map(|e| e.price).sum::<f64>() / executions.len() as f64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vwap() {
+        let mut history = TradeHistory::default();
+        history.record_at(1_000, 10.0, 1.0);
+        history.record_at(2_000, 11.0, 3.0);
+        history.record_at(3_000, 12.0, 1.0);
+        assert_eq!(history.vwap_at(3_000, Duration::from_secs(10)), Some(11.0));
+        // Only the last two executions are in the window
+        assert_eq!(history.vwap_at(3_000, Duration::from_secs(1)), Some(11.25));
+        assert_eq!(history.vwap_at(10_000, Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_twap() {
+        let mut history = TradeHistory::default();
+        history.record_at(0, 10.0, 1.0);
+        history.record_at(3_000, 20.0, 100.0);
+        // 10 for 3s, 20 for 1s
+        assert_eq!(history.twap_at(4_000, Duration::from_secs(10)), Some(12.5));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut history = TradeHistory::new(2);
+        history.record_at(0, 10.0, 1.0);
+        history.record_at(0, 20.0, 1.0);
+        history.record_at(0, 30.0, 1.0);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.twap_at(0, Duration::from_secs(1)), Some(25.0));
+    }
+}
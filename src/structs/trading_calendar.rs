@@ -0,0 +1,209 @@
+use super::auction::AuctionResult;
+use super::ids::Symbol;
+use super::order::Order;
+use crate::enums::session_phase::SessionPhase;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+/// Milliseconds in a day, the period of the trading hours
+pub const DAY_MILLIS: u64 = 86_400_000;
+
+/// Daily trading hours of a symbol, in milliseconds since midnight UTC.
+/// A session closing before it opens runs over midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradingHours {
+    pub open: u64,
+    pub close: u64,
+    /// Length of the call auction ending at the close, None when the session has no closing auction
+    #[serde(rename = "closingAuction")]
+    pub closing_auction: Option<u64>,
+}
+
+impl TradingHours {
+    pub fn new(open: u64, close: u64) -> TradingHours {
+        TradingHours {
+            open,
+            close,
+            closing_auction: None,
+        }
+    }
+
+    /// End the session with a call auction run at the close
+    ///
+    /// #Parameters
+    /// * 'duration' - The length of the auction call before the close, in milliseconds
+    pub fn with_closing_auction(mut self, duration: u64) -> TradingHours {
+        self.closing_auction = Some(duration);
+        self
+    }
+
+    /// Check the times are within a day and the closing auction within the session
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.open >= DAY_MILLIS || self.close >= DAY_MILLIS || self.open == self.close {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Trading hours must open and close at different times of the day",
+            ));
+        }
+        let length = (self.close + DAY_MILLIS - self.open) % DAY_MILLIS;
+        if self
+            .closing_auction
+            .is_some_and(|duration| duration == 0 || duration >= length)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Closing auction must be shorter than the session",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Phase of the session at a time
+    ///
+    /// #Parameters
+    /// * 'now' - The time in milliseconds since UNIX epoch
+    pub fn phase_at(&self, now: u64) -> SessionPhase {
+        let time = now % DAY_MILLIS;
+        let within = |from: u64, to: u64| match from <= to {
+            true => from <= time && time < to,
+            false => from <= time || time < to,
+        };
+        let auction_start = self
+            .closing_auction
+            .map(|duration| (self.close + DAY_MILLIS - duration) % DAY_MILLIS)
+            .unwrap_or(self.close);
+        if within(self.open, auction_start) {
+            SessionPhase::Open
+        } else if self.closing_auction.is_some() && within(auction_start, self.close) {
+            SessionPhase::ClosingAuction
+        } else {
+            SessionPhase::Closed
+        }
+    }
+}
+
+/// Event emitted when the session of a symbol changes phase
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTransition {
+    pub symbol: Symbol,
+    pub phase: SessionPhase,
+    /// Time of the transition in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    /// Day orders expired at the close
+    pub expired: Vec<Order>,
+    /// Result of the auction run at the close
+    pub auction: Option<AuctionResult>,
+}
+
+/// Trading hours of the symbols and the phase their session is in
+#[derive(Debug, Clone, Default)]
+pub struct TradingCalendar {
+    hours: HashMap<Symbol, TradingHours>,
+    phases: HashMap<Symbol, SessionPhase>,
+    listeners: Vec<Sender<SessionTransition>>,
+}
+
+impl TradingCalendar {
+    pub fn new() -> TradingCalendar {
+        TradingCalendar::default()
+    }
+
+    /// Set the trading hours of a symbol, its phase is given by the next run
+    pub fn set_hours(&mut self, symbol: Symbol, hours: TradingHours) {
+        self.hours.insert(symbol, hours);
+        self.phases.remove(&symbol);
+    }
+
+    /// Remove the trading hours of a symbol, which then trades all day
+    pub fn remove(&mut self, symbol: Symbol) -> Option<TradingHours> {
+        self.phases.remove(&symbol);
+        self.hours.remove(&symbol)
+    }
+
+    pub fn hours(&self, symbol: Symbol) -> Option<&TradingHours> {
+        self.hours.get(&symbol)
+    }
+
+    /// Phase the session of a symbol was in at the last run, None before the first one
+    pub fn phase(&self, symbol: Symbol) -> Option<SessionPhase> {
+        self.phases.get(&symbol).copied()
+    }
+
+    /// Record the phase of every session at a time
+    ///
+    /// #Parameters
+    /// * 'now' - The current time in milliseconds since UNIX epoch
+    ///
+    /// #Returns
+    /// * Vec<(Symbol, SessionPhase)> - The symbols whose session changed phase since the last run,
+    ///   with their new phase. Every symbol changes at its first run
+    pub fn transitions(&mut self, now: u64) -> Vec<(Symbol, SessionPhase)> {
+        let mut transitions = Vec::new();
+        for (symbol, hours) in self.hours.iter() {
+            let phase = hours.phase_at(now);
+            if self.phases.insert(*symbol, phase) != Some(phase) {
+                transitions.push((*symbol, phase));
+            }
+        }
+        transitions
+    }
+
+    /// Receive every session transition emitted from now on
+    pub fn subscribe(&mut self) -> Receiver<SessionTransition> {
+        let (tx, rx) = unbounded();
+        self.listeners.push(tx);
+        rx
+    }
+
+    /// Send the transition to every listener, listeners which are gone are removed
+    pub fn publish(&mut self, transition: &SessionTransition) {
+        self.listeners
+            .retain(|listener| listener.send(transition.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600_000;
+
+    #[test]
+    fn test_phases_of_the_trading_hours() {
+        let hours = TradingHours::new(9 * HOUR, 17 * HOUR).with_closing_auction(HOUR / 6);
+        assert!(hours.validate().is_ok());
+        let day = 20_000 * DAY_MILLIS;
+        assert_eq!(hours.phase_at(day + 8 * HOUR), SessionPhase::Closed);
+        assert_eq!(hours.phase_at(day + 9 * HOUR), SessionPhase::Open);
+        assert_eq!(
+            hours.phase_at(day + 17 * HOUR - 1),
+            SessionPhase::ClosingAuction
+        );
+        assert_eq!(hours.phase_at(day + 17 * HOUR), SessionPhase::Closed);
+
+        // over midnight
+        let night = TradingHours::new(22 * HOUR, 2 * HOUR);
+        assert_eq!(night.phase_at(day + 23 * HOUR), SessionPhase::Open);
+        assert_eq!(night.phase_at(day + HOUR), SessionPhase::Open);
+        assert_eq!(night.phase_at(day + 12 * HOUR), SessionPhase::Closed);
+
+        assert!(TradingHours::new(9 * HOUR, 9 * HOUR).validate().is_err());
+        assert!(TradingHours::new(9 * HOUR, 10 * HOUR)
+            .with_closing_auction(HOUR)
+            .validate()
+            .is_err());
+
+        let mut calendar = TradingCalendar::new();
+        let symbol = Symbol(1);
+        calendar.set_hours(symbol, hours);
+        assert_eq!(calendar.phase(symbol), None);
+        assert_eq!(
+            calendar.transitions(day + 10 * HOUR),
+            vec![(symbol, SessionPhase::Open)]
+        );
+        assert!(calendar.transitions(day + 11 * HOUR).is_empty());
+        assert_eq!(calendar.phase(symbol), Some(SessionPhase::Open));
+    }
+}
@@ -0,0 +1,91 @@
+use super::orderbook_update::OrderbookUpdate;
+use super::subscription::{subscription, Subscription, SubscriptionSender};
+use crate::enums::overflow_policy::OverflowPolicy;
+use std::sync::{Arc, Mutex};
+
+/// Fan-out of the orderbook updates: every subscriber receives its own copy of every update
+/// published after it subscribed.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateBus {
+    subscribers: Arc<Mutex<Vec<SubscriptionSender>>>,
+}
+
+impl UpdateBus {
+    pub fn new() -> UpdateBus {
+        UpdateBus::default()
+    }
+
+    /// Register a new subscriber with an unbounded queue
+    ///
+    /// #Returns
+    /// * Subscription - The receiving end of the subscriber queue
+    pub fn subscribe(&self) -> Subscription {
+        self.subscribe_with(None, OverflowPolicy::default())
+    }
+
+    /// Register a new subscriber with a bounded queue
+    ///
+    /// #Parameters
+    /// * 'capacity' - The maximum number of queued updates, None for an unbounded queue
+    /// * 'policy' - What to do when an update is published to a full queue
+    pub fn subscribe_with(&self, capacity: Option<usize>, policy: OverflowPolicy) -> Subscription {
+        let (tx, rx) = subscription(capacity, policy);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send the update to every subscriber, subscribers which are gone or overflowed are removed.
+    /// With the Block policy this waits until every blocking subscriber has room.
+    /// The update is moved into the last subscriber queue, the others receive a copy.
+    pub fn publish(&self, update: OrderbookUpdate) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let mut update = Some(update);
+        let mut remaining = subscribers.len();
+        subscribers.retain(|subscriber| {
+            remaining -= 1;
+            let update = match remaining {
+                0 => update.take().unwrap(),
+                _ => update.clone().unwrap(),
+            };
+            subscriber.send(update)
+        });
+    }
+
+    /// Close every subscription, the subscribers receive the updates already queued then the end of the stream
+    pub fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+
+    /// Number of updates queued for the most lagging subscriber
+    pub fn max_queued(&self) -> usize {
+        let subscribers = self.subscribers.lock().unwrap();
+        subscribers.iter().map(|s| s.queued()).max().unwrap_or(0)
+    }
+
+    /// Number of registered subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_subscriber_receives_every_update() {
+        let bus = UpdateBus::new();
+        let rx1 = bus.subscribe();
+        let rx2 = bus.subscribe();
+        bus.publish(OrderbookUpdate {
+            sequence: 1,
+            ..Default::default()
+        });
+        assert_eq!(rx1.try_recv().unwrap().sequence, 1);
+        assert_eq!(rx2.try_recv().unwrap().sequence, 1);
+
+        drop(rx1);
+        bus.publish(OrderbookUpdate::default());
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+use super::rng::SplitMix64;
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol, UserId};
+use crate::structs::order::Order;
+use serde::{Deserialize, Serialize};
+
+/// Command of a generated order flow
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FlowCommand {
+    /// Add a limit or a market order
+    Add(Order),
+    /// Cancel an order, which may already be filled or cancelled
+    Cancel { order_id: OrderId, side: OrderSide },
+    /// Move a resting order to a new price, which may cross the book
+    AmendPrice {
+        order_id: OrderId,
+        side: OrderSide,
+        price: f64,
+    },
+    /// Change the quantity of a resting order
+    AmendQuantity {
+        order_id: OrderId,
+        side: OrderSide,
+        quantity: f64,
+    },
+}
+
+/// Shape of the generated order flow
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlowConfig {
+    pub symbol: Symbol,
+    /// Number of users placing the orders, their IDs go from 1 to `users`
+    pub users: u32,
+    /// Price the limit orders are placed around
+    pub mid_price: f64,
+    pub tick_size: f64,
+    /// Distance in ticks of the limit prices from the mid price, on each side
+    pub price_range: u32,
+    /// Largest order quantity, the quantities are whole numbers from 1
+    pub max_quantity: u32,
+    /// Share of the orders which are market orders
+    pub market_ratio: f64,
+    /// Share of the commands which cancel a previously added order
+    pub cancel_ratio: f64,
+    /// Share of the commands which amend the price or the quantity of a previously added order
+    pub amend_ratio: f64,
+}
+
+impl Default for FlowConfig {
+    fn default() -> Self {
+        FlowConfig {
+            symbol: Symbol(1),
+            users: 8,
+            mid_price: 100.0,
+            tick_size: 1.0,
+            price_range: 10,
+            max_quantity: 10,
+            market_ratio: 0.1,
+            cancel_ratio: 0.2,
+            amend_ratio: 0.1,
+        }
+    }
+}
+
+/// Generator of random but valid command sequences, for property testing an integration of the engine.
+/// The same seed always produces the same sequence.
+///
+/// The order IDs are unique and the creation times strictly increase so that the time priority
+/// is never ambiguous, the prices are multiples of the tick size and the quantities whole numbers
+/// so that the fills are exact.
+#[derive(Debug, Clone)]
+pub struct OrderFlowGenerator {
+    pub config: FlowConfig,
+    rng: SplitMix64,
+    /// Last order ID and creation time handed out
+    sequence: u64,
+    /// Limit orders added so far, the candidates of the cancels and the amends
+    limit_orders: Vec<(OrderId, OrderSide)>,
+}
+
+impl OrderFlowGenerator {
+    /// Create a generator with the default flow
+    ///
+    /// #Parameters
+    /// * 'seed' - The seed of the random number generator
+    pub fn new(seed: u64) -> OrderFlowGenerator {
+        OrderFlowGenerator::with_config(FlowConfig::default(), seed)
+    }
+
+    /// Create a generator
+    ///
+    /// #Parameters
+    /// * 'config' - The shape of the flow
+    /// * 'seed' - The seed of the random number generator
+    pub fn with_config(config: FlowConfig, seed: u64) -> OrderFlowGenerator {
+        OrderFlowGenerator {
+            config,
+            rng: SplitMix64::new(seed),
+            sequence: 0,
+            limit_orders: Vec::new(),
+        }
+    }
+
+    /// Draw a limit price within the range around the mid price
+    fn limit_price(&mut self) -> f64 {
+        let config = self.config;
+        let range = config.price_range as i64;
+        let offset = self.rng.next_in(0, 2 * range as u64) as i64 - range;
+        (config.mid_price + offset as f64 * config.tick_size).max(config.tick_size)
+    }
+
+    /// Generate the next order
+    pub fn next_order(&mut self) -> Order {
+        self.sequence += 1;
+        let config = self.config;
+        let side = match self.rng.next_u64() % 2 {
+            0 => OrderSide::Buy,
+            _ => OrderSide::Sell,
+        };
+        let user_id = UserId(self.rng.next_in(1, config.users.max(1) as u64) as u128);
+        let quantity = self.rng.next_in(1, config.max_quantity.max(1) as u64) as f64;
+        let (order_type, price) = if self.rng.next_f64() < config.market_ratio {
+            (OrderType::Market, None)
+        } else {
+            (OrderType::Limit, Some(self.limit_price()))
+        };
+        let mut order = Order::new(user_id, config.symbol, side, quantity, price, order_type);
+        order.id = OrderId(self.sequence as u128);
+        order.created_at = self.sequence;
+        order.updated_at = self.sequence;
+        if order_type == OrderType::Limit {
+            self.limit_orders.push((order.id, side));
+        }
+        order
+    }
+
+    /// Generate the next command, a cancel or an amend of a previously added limit order or a new order
+    pub fn next_command(&mut self) -> FlowCommand {
+        let config = self.config;
+        if self.limit_orders.is_empty() {
+            return FlowCommand::Add(self.next_order());
+        }
+        let draw = self.rng.next_f64();
+        if draw >= config.cancel_ratio + config.amend_ratio {
+            return FlowCommand::Add(self.next_order());
+        }
+        let index = self.rng.next_u64() as usize % self.limit_orders.len();
+        let (order_id, side) = self.limit_orders[index];
+        if draw < config.cancel_ratio {
+            return FlowCommand::Cancel { order_id, side };
+        }
+        if self.rng.next_f64() < 0.5 {
+            return FlowCommand::AmendPrice {
+                order_id,
+                side,
+                price: self.limit_price(),
+            };
+        }
+        let quantity = self.rng.next_in(1, config.max_quantity.max(1) as u64) as f64;
+        FlowCommand::AmendQuantity {
+            order_id,
+            side,
+            quantity,
+        }
+    }
+
+    /// Generate a sequence of commands
+    ///
+    /// #Parameters
+    /// * 'count' - The number of commands
+    pub fn commands(&mut self, count: usize) -> Vec<FlowCommand> {
+        (0..count).map(|_| self.next_command()).collect()
+    }
+}
+
+impl Iterator for OrderFlowGenerator {
+    type Item = FlowCommand;
+
+    fn next(&mut self) -> Option<FlowCommand> {
+        Some(self.next_command())
+    }
+}
@@ -0,0 +1,184 @@
+pub mod generator;
+pub mod reference;
+pub mod rng;
+pub mod scheduler;
+
+use crate::enums::orderbook_update_type::OrderbookUpdateType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol};
+use crate::structs::order::Order;
+use crate::structs::order_ack::OrderAck;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use crate::structs::trade::Trade;
+use generator::FlowCommand;
+use reference::ReferenceBook;
+use std::io::{Error, ErrorKind};
+
+/// What two trades must agree on: the orders, the price, the quantity, the aggressor side and the quantities left
+fn trade_key(trade: &Trade) -> (OrderId, OrderId, f64, f64, OrderSide, f64, f64) {
+    (
+        trade.buy_order_id,
+        trade.sell_order_id,
+        trade.price,
+        trade.quantity,
+        trade.taker_side,
+        trade.buy_remaining,
+        trade.sell_remaining,
+    )
+}
+
+/// What two resting orders must agree on: the ID, the price and the remaining quantity
+fn order_key(order: &Order) -> (OrderId, Option<f64>, f64) {
+    (order.id, order.price, order.quantity)
+}
+
+fn mismatch(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Apply a command to the orderbook of a manager
+///
+/// #Parameters
+/// * 'manager' - The manager holding the orderbook
+/// * 'symbol' - The symbol ID of the orderbook
+/// * 'command' - The command
+///
+/// #Returns
+/// * Result<Option<OrderAck>, Error> - The acknowledgement of an add or a cancel, None for an amend
+pub fn apply_command(
+    manager: &mut OrderbooksManager,
+    symbol: Symbol,
+    command: &FlowCommand,
+) -> Result<Option<OrderAck>, Error> {
+    match *command {
+        FlowCommand::Add(order) => manager.add_order(order).map(Some),
+        FlowCommand::Cancel { order_id, side } => {
+            manager.cancel_order(order_id, symbol, side).map(Some)
+        }
+        FlowCommand::AmendPrice {
+            order_id,
+            side,
+            price,
+        } => manager
+            .amend_order_price(symbol, order_id, price, side)
+            .map(|_| None),
+        FlowCommand::AmendQuantity {
+            order_id,
+            side,
+            quantity,
+        } => manager
+            .amend_order_quantity(symbol, order_id, quantity, side)
+            .map(|_| None),
+    }
+}
+
+/// Run a command sequence through the orderbook of a manager and through the reference matcher,
+/// the engine must produce the same trades after each command and end with the same book.
+/// The orderbook must be empty and use the default configuration.
+/// A cancel or an amend of an order which already left the book is not an error.
+///
+/// #Parameters
+/// * 'manager' - The manager holding the orderbook
+/// * 'symbol' - The symbol ID of the orderbook, the symbol of the generated orders
+/// * 'commands' - The commands, e.g. from an `OrderFlowGenerator`
+///
+/// #Returns
+/// * Result<(), Error> - InvalidData describing the first divergence, or the error of a rejected command
+pub fn check_against_reference(
+    manager: &mut OrderbooksManager,
+    symbol: Symbol,
+    commands: &[FlowCommand],
+) -> Result<(), Error> {
+    let updates = manager.subscribe_updates();
+    let mut reference = ReferenceBook::new(symbol);
+    for (index, command) in commands.iter().enumerate() {
+        match apply_command(manager, symbol, command) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        reference.time = manager.clock.now();
+        let trades: Vec<_> = std::iter::from_fn(|| updates.try_recv().ok())
+            .filter(|u| u.update_type == OrderbookUpdateType::NewTrades)
+            .filter_map(|u| u.trade.as_ref().map(trade_key))
+            .collect();
+        let expected: Vec<_> = reference.apply(command).iter().map(trade_key).collect();
+        if trades != expected {
+            return Err(mismatch(format!(
+                "command {} {:?}: the engine traded {:?} instead of {:?}",
+                index, command, trades, expected
+            )));
+        }
+    }
+    let orderbook = manager
+        .orderbooks
+        .get(&symbol)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Orderbook not found"))?;
+    for (side, heap) in [
+        (OrderSide::Buy, &orderbook.bids),
+        (OrderSide::Sell, &orderbook.asks),
+    ] {
+        let orders: Vec<_> = heap
+            .sorted_refs()
+            .into_iter()
+            .rev()
+            .map(order_key)
+            .collect();
+        let expected: Vec<_> = reference.orders(side).iter().map(order_key).collect();
+        if orders != expected {
+            return Err(mismatch(format!(
+                "the {} side of the engine is {:?} instead of {:?}",
+                side, orders, expected
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generator::{FlowConfig, OrderFlowGenerator};
+    use super::*;
+
+    #[test]
+    fn test_generator_is_deterministic() {
+        let commands = OrderFlowGenerator::new(42).commands(100);
+        assert_eq!(commands, OrderFlowGenerator::new(42).commands(100));
+        assert_ne!(commands, OrderFlowGenerator::new(43).commands(100));
+        assert!(commands
+            .iter()
+            .any(|c| matches!(c, FlowCommand::Cancel { .. })));
+        assert!(commands
+            .iter()
+            .any(|c| matches!(c, FlowCommand::AmendPrice { .. })));
+    }
+
+    #[test]
+    fn test_engine_matches_the_reference() {
+        for seed in 0..20 {
+            let config = FlowConfig {
+                symbol: Symbol(7),
+                ..Default::default()
+            };
+            let commands = OrderFlowGenerator::with_config(config, seed).commands(500);
+            let mut manager = OrderbooksManager::new();
+            manager.new_orderbook(config.symbol);
+            check_against_reference(&mut manager, config.symbol, &commands).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_divergences_are_reported() {
+        let mut generator = OrderFlowGenerator::new(1);
+        let order = generator.next_order();
+        let mut manager = OrderbooksManager::new();
+        manager.new_orderbook(order.symbol);
+        // An order the reference doesn't know about
+        let mut unknown = generator.next_order();
+        unknown.order_type = crate::enums::order_type::OrderType::Limit;
+        unknown.price = Some(1.0);
+        manager.add_order(unknown).unwrap();
+        let error = check_against_reference(&mut manager, order.symbol, &[FlowCommand::Add(order)])
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
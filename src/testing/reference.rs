@@ -0,0 +1,200 @@
+use super::generator::FlowCommand;
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::{OrderId, Symbol};
+use crate::structs::order::Order;
+use crate::structs::trade::Trade;
+use std::cmp::Ordering;
+
+/// Naive price-time matcher used as an oracle: the orders sit in plain vectors and the best
+/// order is searched for at every step.
+/// It follows the rules of the default orderbook configuration: limit orders cross at the price of
+/// the resting order, market orders sweep the opposite side at the resting prices and their remainder is
+/// cancelled, a user may trade with itself, and an amend loses the time priority when it changes the
+/// price or increases the quantity.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceBook {
+    pub symbol: Symbol,
+    /// Time stamped on the amended orders which lose their priority, set by the caller
+    pub time: u64,
+    bids: Vec<Order>,
+    asks: Vec<Order>,
+    /// Last arrival sequence number given to an order, the time priority
+    arrivals: u64,
+}
+
+/// Priority order of a side: better price first, then first arrived first
+fn priority(order: &Order, other: &Order) -> Ordering {
+    let by_price = order.price.unwrap().total_cmp(&other.price.unwrap());
+    match order.side {
+        OrderSide::Buy => by_price.reverse(),
+        OrderSide::Sell => by_price,
+    }
+    .then(order.arrival_seq.cmp(&other.arrival_seq))
+}
+
+/// Position of the best order of a side
+fn best(orders: &[Order]) -> Option<usize> {
+    (0..orders.len()).min_by(|&a, &b| priority(&orders[a], &orders[b]))
+}
+
+impl ReferenceBook {
+    pub fn new(symbol: Symbol) -> ReferenceBook {
+        ReferenceBook {
+            symbol,
+            ..Default::default()
+        }
+    }
+
+    /// Apply a command
+    ///
+    /// #Returns
+    /// * Vec<Trade> - The trades executed, without ID, time nor fees
+    pub fn apply(&mut self, command: &FlowCommand) -> Vec<Trade> {
+        match *command {
+            FlowCommand::Add(order) if order.order_type == OrderType::Market => self.sweep(order),
+            FlowCommand::Add(mut order) => {
+                order.arrival_seq = self.next_arrival();
+                match order.side {
+                    OrderSide::Buy => self.bids.push(order),
+                    OrderSide::Sell => self.asks.push(order),
+                }
+                self.cross(order.side)
+            }
+            FlowCommand::Cancel { order_id, side } => {
+                self.side_mut(side).retain(|o| o.id != order_id);
+                Vec::new()
+            }
+            FlowCommand::AmendPrice {
+                order_id,
+                side,
+                price,
+            } => {
+                let (time, arrival) = (self.time, self.next_arrival());
+                self.amend(order_id, side, |o| {
+                    if o.price != Some(price) {
+                        o.created_at = time;
+                        o.arrival_seq = arrival;
+                    }
+                    o.price = Some(price);
+                });
+                self.cross(side)
+            }
+            FlowCommand::AmendQuantity {
+                order_id,
+                side,
+                quantity,
+            } => {
+                let (time, arrival) = (self.time, self.next_arrival());
+                self.amend(order_id, side, |o| {
+                    if quantity > o.quantity {
+                        o.created_at = time;
+                        o.arrival_seq = arrival;
+                    }
+                    o.quantity = quantity;
+                });
+                self.cross(side)
+            }
+        }
+    }
+
+    fn next_arrival(&mut self) -> u64 {
+        self.arrivals += 1;
+        self.arrivals
+    }
+
+    fn side_mut(&mut self, side: OrderSide) -> &mut Vec<Order> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    /// Modify a resting order in place
+    fn amend(&mut self, order_id: OrderId, side: OrderSide, modify: impl FnOnce(&mut Order)) {
+        if let Some(order) = self.side_mut(side).iter_mut().find(|o| o.id == order_id) {
+            modify(order);
+        }
+    }
+
+    /// Take a quantity from a resting order, it leaves the book once filled
+    fn fill(&mut self, side: OrderSide, index: usize, quantity: f64) {
+        let orders = self.side_mut(side);
+        orders[index].quantity -= quantity;
+        if orders[index].quantity <= 0.0 {
+            orders.remove(index);
+        }
+    }
+
+    /// Match the crossing orders, the incoming or amended order being the aggressor
+    fn cross(&mut self, taker_side: OrderSide) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        while let (Some(b), Some(a)) = (best(&self.bids), best(&self.asks)) {
+            let (bid, ask) = (self.bids[b], self.asks[a]);
+            if bid.price < ask.price {
+                break;
+            }
+            let quantity = bid.quantity.min(ask.quantity);
+            let price = match taker_side {
+                OrderSide::Buy => ask.price,
+                OrderSide::Sell => bid.price,
+            };
+            trades.push(Trade::between(
+                self.symbol,
+                price.unwrap(),
+                quantity,
+                &bid,
+                &ask,
+                taker_side,
+            ));
+            self.fill(OrderSide::Buy, b, quantity);
+            self.fill(OrderSide::Sell, a, quantity);
+        }
+        trades
+    }
+
+    fn sweep(&mut self, taker: Order) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        let maker_side = match taker.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        let mut remaining = taker.quantity;
+        while remaining > 0.0 {
+            let Some(index) = best(self.side_mut(maker_side)) else {
+                break;
+            };
+            let maker = self.side_mut(maker_side)[index];
+            let quantity = maker.quantity.min(remaining);
+            let current = Order {
+                quantity: remaining,
+                ..taker
+            };
+            let (buy, sell) = match taker.side {
+                OrderSide::Buy => (&current, &maker),
+                OrderSide::Sell => (&maker, &current),
+            };
+            trades.push(Trade::between(
+                self.symbol,
+                maker.price.unwrap(),
+                quantity,
+                buy,
+                sell,
+                taker.side,
+            ));
+            self.fill(maker_side, index, quantity);
+            remaining -= quantity;
+        }
+        trades
+    }
+
+    /// Resting orders of a side, best first
+    pub fn orders(&self, side: OrderSide) -> Vec<Order> {
+        let mut orders = match side {
+            OrderSide::Buy => self.bids.clone(),
+            OrderSide::Sell => self.asks.clone(),
+        };
+        orders.sort_by(priority);
+        orders
+    }
+}
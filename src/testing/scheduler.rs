@@ -0,0 +1,353 @@
+use super::apply_command;
+use super::generator::FlowCommand;
+use super::rng::SplitMix64;
+use crate::structs::clock::{Clock, MockClock};
+use crate::structs::ids::Symbol;
+use crate::structs::order_ack::OrderAck;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Delay between the submission of a command and its processing by the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LatencyProfile {
+    /// Delay every command goes through
+    pub base: Duration,
+    /// Largest extra delay, drawn uniformly for each command
+    pub jitter: Duration,
+}
+
+impl LatencyProfile {
+    /// Profile with a constant delay
+    pub fn fixed(delay: Duration) -> LatencyProfile {
+        LatencyProfile {
+            base: delay,
+            jitter: Duration::ZERO,
+        }
+    }
+}
+
+/// Command on its way to the engine
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledCommand {
+    /// Submission number, breaks the ties between the commands processed at the same time
+    pub sequence: u64,
+    /// Submitter of the command, e.g. a user or a gateway, which selects the latency profile
+    pub source: u128,
+    /// Submission time in nanoseconds since UNIX epoch
+    pub submitted_at: u64,
+    /// Processing time in nanoseconds since UNIX epoch
+    pub processed_at: u64,
+    pub command: FlowCommand,
+}
+
+/// Command processed by the engine with its outcome
+#[derive(Debug)]
+pub struct ProcessedCommand {
+    pub scheduled: ScheduledCommand,
+    /// The acknowledgement of an add or a cancel, None for an amend
+    pub result: Result<Option<OrderAck>, Error>,
+}
+
+/// Deterministic scheduler injecting delays between the submission of the commands and their
+/// processing, to reproduce the races between amends, cancels and fills that real threads only
+/// trigger by chance. The commands are processed in the order of their processing time, then of
+/// their submission, and the same seed always draws the same delays.
+///
+/// The scheduler drives a simulated clock: create the manager with `OrderbooksManager::with_clock`
+/// and the `clock` of the scheduler so that the order and trade times follow the processing times.
+#[derive(Debug, Clone)]
+pub struct DeterministicScheduler {
+    clock: MockClock,
+    rng: SplitMix64,
+    /// Profile of the sources without their own
+    pub default_latency: LatencyProfile,
+    pub latencies: HashMap<u128, LatencyProfile>,
+    /// Commands waiting to be processed, by processing time and submission number
+    pending: BTreeMap<(u64, u64), ScheduledCommand>,
+    sequence: u64,
+}
+
+impl DeterministicScheduler {
+    /// Create a scheduler without delays
+    ///
+    /// #Parameters
+    /// * 'seed' - The seed of the random number generator drawing the jitter
+    pub fn new(seed: u64) -> DeterministicScheduler {
+        DeterministicScheduler {
+            clock: MockClock::new(1_000_000_000),
+            rng: SplitMix64::new(seed),
+            default_latency: LatencyProfile::default(),
+            latencies: HashMap::new(),
+            pending: BTreeMap::new(),
+            sequence: 0,
+        }
+    }
+
+    /// Set the profile of the sources without their own
+    pub fn with_default_latency(mut self, profile: LatencyProfile) -> DeterministicScheduler {
+        self.default_latency = profile;
+        self
+    }
+
+    /// Set the profile of a source
+    ///
+    /// #Parameters
+    /// * 'source' - The submitter ID
+    /// * 'profile' - Its latency profile
+    pub fn with_latency(mut self, source: u128, profile: LatencyProfile) -> DeterministicScheduler {
+        self.latencies.insert(source, profile);
+        self
+    }
+
+    /// Simulated clock of the scheduler, to share with the manager
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::new(self.clock.clone())
+    }
+
+    /// Simulated time in nanoseconds since UNIX epoch
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    /// Commands waiting to be processed, in processing order
+    pub fn pending(&self) -> impl Iterator<Item = &ScheduledCommand> {
+        self.pending.values()
+    }
+
+    /// Submit a command now, its delay is drawn from the profile of its source
+    ///
+    /// #Parameters
+    /// * 'source' - The submitter ID
+    /// * 'command' - The command
+    ///
+    /// #Returns
+    /// * u64 - The processing time in nanoseconds since UNIX epoch
+    pub fn submit(&mut self, source: u128, command: FlowCommand) -> u64 {
+        let profile = *self.latencies.get(&source).unwrap_or(&self.default_latency);
+        let jitter = self.rng.next_in(0, profile.jitter.as_nanos() as u64);
+        let delay = profile.base + Duration::from_nanos(jitter);
+        self.submit_with_delay(source, command, delay)
+    }
+
+    /// Submit a command now with an explicit delay, ignoring the latency profiles
+    ///
+    /// #Parameters
+    /// * 'source' - The submitter ID
+    /// * 'command' - The command
+    /// * 'delay' - The delay before its processing
+    ///
+    /// #Returns
+    /// * u64 - The processing time in nanoseconds since UNIX epoch
+    pub fn submit_with_delay(
+        &mut self,
+        source: u128,
+        command: FlowCommand,
+        delay: Duration,
+    ) -> u64 {
+        self.sequence += 1;
+        let submitted_at = self.now();
+        let processed_at = submitted_at + delay.as_nanos() as u64;
+        self.pending.insert(
+            (processed_at, self.sequence),
+            ScheduledCommand {
+                sequence: self.sequence,
+                source,
+                submitted_at,
+                processed_at,
+                command,
+            },
+        );
+        processed_at
+    }
+
+    /// Advance the simulated time, processing the commands which fall due on the way
+    ///
+    /// #Parameters
+    /// * 'manager' - The manager holding the orderbook
+    /// * 'symbol' - The symbol ID of the orderbook
+    /// * 'duration' - The simulated duration
+    ///
+    /// #Returns
+    /// * Vec<ProcessedCommand> - The commands processed, in processing order
+    pub fn advance(
+        &mut self,
+        manager: &mut OrderbooksManager,
+        symbol: Symbol,
+        duration: Duration,
+    ) -> Vec<ProcessedCommand> {
+        let end = self.now() + duration.as_nanos() as u64;
+        let processed = self.process_until(manager, symbol, end);
+        self.clock.set(end);
+        processed
+    }
+
+    /// Process all the pending commands, the time ends at the last processing time
+    ///
+    /// #Parameters
+    /// * 'manager' - The manager holding the orderbook
+    /// * 'symbol' - The symbol ID of the orderbook
+    ///
+    /// #Returns
+    /// * Vec<ProcessedCommand> - The commands processed, in processing order
+    pub fn run(
+        &mut self,
+        manager: &mut OrderbooksManager,
+        symbol: Symbol,
+    ) -> Vec<ProcessedCommand> {
+        self.process_until(manager, symbol, u64::MAX)
+    }
+
+    fn process_until(
+        &mut self,
+        manager: &mut OrderbooksManager,
+        symbol: Symbol,
+        end: u64,
+    ) -> Vec<ProcessedCommand> {
+        let mut processed = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if entry.key().0 > end {
+                break;
+            }
+            let scheduled = entry.remove();
+            self.clock.set(scheduled.processed_at.max(self.now()));
+            let result = apply_command(manager, symbol, &scheduled.command);
+            processed.push(ProcessedCommand { scheduled, result });
+        }
+        processed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::order_status::OrderStatus;
+    use crate::enums::order_type::OrderType;
+    use crate::enums::side::OrderSide;
+    use crate::structs::ids::{OrderId, UserId};
+    use crate::structs::order::Order;
+    use std::io::ErrorKind;
+
+    fn order(id: u128, side: OrderSide, quantity: f64, price: Option<f64>) -> Order {
+        let order_type = match price {
+            Some(_) => OrderType::Limit,
+            None => OrderType::Market,
+        };
+        let mut order = Order::new(UserId(id), Symbol(1), side, quantity, price, order_type);
+        order.id = OrderId(id);
+        order
+    }
+
+    /// A maker cancels its ask while a taker sends a market buy: the latencies decide who wins
+    fn race(maker_latency: u64, taker_latency: u64) -> Vec<ProcessedCommand> {
+        let mut scheduler = DeterministicScheduler::new(0)
+            .with_latency(
+                1,
+                LatencyProfile::fixed(Duration::from_micros(maker_latency)),
+            )
+            .with_latency(
+                2,
+                LatencyProfile::fixed(Duration::from_micros(taker_latency)),
+            );
+        let mut manager = OrderbooksManager::with_clock(scheduler.clock());
+        manager.new_orderbook(Symbol(1));
+        manager
+            .add_order(order(10, OrderSide::Sell, 5.0, Some(100.0)))
+            .unwrap();
+
+        scheduler.submit(
+            1,
+            FlowCommand::Cancel {
+                order_id: OrderId(10),
+                side: OrderSide::Sell,
+            },
+        );
+        let mut processed = scheduler.advance(&mut manager, Symbol(1), Duration::from_micros(2));
+        scheduler.submit(2, FlowCommand::Add(order(20, OrderSide::Buy, 5.0, None)));
+        processed.extend(scheduler.run(&mut manager, Symbol(1)));
+        processed
+    }
+
+    #[test]
+    fn test_injected_delays_reorder_a_cancel_and_a_fill() {
+        // The cancel is slow: the market order arrives first and fills the ask
+        let processed = race(10, 1);
+        assert_eq!(processed.len(), 2);
+        assert_eq!(processed[0].scheduled.source, 2);
+        assert_eq!(processed[0].scheduled.processed_at, 1_000_003_000);
+        let fill = processed[0].result.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(fill.status, OrderStatus::Filled);
+        let cancel = processed[1].result.as_ref().unwrap_err();
+        assert_eq!(cancel.kind(), ErrorKind::NotFound);
+
+        // The cancel is fast: the market order finds an empty book
+        let processed = race(1, 10);
+        assert_eq!(processed[0].scheduled.source, 1);
+        let cancel = processed[0].result.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(cancel.status, OrderStatus::Cancelled);
+        let fill = processed[1].result.as_ref().unwrap().as_ref().unwrap();
+        assert_ne!(fill.status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn test_jitter_is_reproducible() {
+        let profile = LatencyProfile {
+            base: Duration::from_micros(5),
+            jitter: Duration::from_micros(20),
+        };
+        let schedule = |seed| {
+            let mut scheduler = DeterministicScheduler::new(seed).with_default_latency(profile);
+            (0..50)
+                .map(|id| {
+                    let command = FlowCommand::AmendQuantity {
+                        order_id: OrderId(id),
+                        side: OrderSide::Buy,
+                        quantity: 1.0,
+                    };
+                    scheduler.submit(id, command)
+                })
+                .collect::<Vec<_>>()
+        };
+        let times = schedule(3);
+        assert_eq!(times, schedule(3));
+        assert_ne!(times, schedule(4));
+        assert!(times
+            .iter()
+            .all(|&t| (1_000_005_000..=1_000_025_000).contains(&t)));
+    }
+
+    #[test]
+    fn test_amend_racing_a_fill() {
+        // An amend moving a bid through the ask and a cancel of the ask fall due together
+        let mut scheduler = DeterministicScheduler::new(0);
+        let mut manager = OrderbooksManager::with_clock(scheduler.clock());
+        manager.new_orderbook(Symbol(1));
+        manager
+            .add_order(order(10, OrderSide::Sell, 5.0, Some(100.0)))
+            .unwrap();
+        manager
+            .add_order(order(20, OrderSide::Buy, 5.0, Some(99.0)))
+            .unwrap();
+        let updates = manager.subscribe_updates();
+        let amend = FlowCommand::AmendPrice {
+            order_id: OrderId(20),
+            side: OrderSide::Buy,
+            price: 100.0,
+        };
+        let cancel = FlowCommand::Cancel {
+            order_id: OrderId(10),
+            side: OrderSide::Sell,
+        };
+        scheduler.submit_with_delay(2, amend, Duration::from_micros(5));
+        scheduler.submit_with_delay(1, cancel, Duration::from_micros(5));
+        // Same processing time, the earlier submission goes first
+        let processed = scheduler.run(&mut manager, Symbol(1));
+        assert_eq!(processed[0].scheduled.command, amend);
+        assert!(std::iter::from_fn(|| updates.try_recv().ok()).any(|u| u.trade.is_some()));
+        assert!(manager.orderbooks[&Symbol(1)].asks.is_empty());
+        assert_eq!(scheduler.now(), 1_000_005_000);
+    }
+}
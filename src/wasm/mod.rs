@@ -0,0 +1,200 @@
+//! JS bindings for client-side orderbooks, enabled with the `wasm` feature.
+//!
+//! ```sh
+//! cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/orderbook.wasm
+//! ```
+//!
+//! ```js
+//! const book = new WasmOrderbook();
+//! const ack = JSON.parse(book.placeOrder("1", "buy", 2.0, 10.0));
+//! book.placeOrder("2", "sell", 1.0, undefined); // market order
+//! const { bids, asks } = JSON.parse(book.depth(10));
+//! book.cancelOrder(ack.orderId, "buy");
+//! ```
+//!
+//! IDs are exchanged as decimal strings since they do not fit in a JS number.
+
+use crate::enums::order_status::OrderStatus;
+use crate::enums::order_type::OrderType;
+use crate::enums::side::OrderSide;
+use crate::structs::ids::Symbol;
+use crate::structs::level_book::PriceLevel;
+use crate::structs::order::Order;
+use crate::structs::orderbooks_manager::OrderbooksManager;
+use serde::Serialize;
+use std::io::{Error, ErrorKind};
+use wasm_bindgen::prelude::*;
+
+/// Acknowledgment of an order, with its ID as a string
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WasmAck {
+    order_id: String,
+    status: OrderStatus,
+    filled_quantity: f64,
+    reject_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct WasmDepth {
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn parse_id<T: From<u128>>(value: &str) -> Result<T, Error> {
+    value
+        .parse::<u128>()
+        .map(T::from)
+        .map_err(|_| invalid("Invalid ID"))
+}
+
+fn parse_side(value: &str) -> Result<OrderSide, Error> {
+    match value.to_ascii_lowercase().as_str() {
+        "buy" => Ok(OrderSide::Buy),
+        "sell" => Ok(OrderSide::Sell),
+        _ => Err(invalid("Side must be buy or sell")),
+    }
+}
+
+fn js_error(error: Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// A single orderbook running in the browser, for client-side simulations
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmOrderbook {
+    manager: OrderbooksManager,
+    symbol: Symbol,
+}
+
+impl WasmOrderbook {
+    fn place(
+        &mut self,
+        user_id: &str,
+        side: &str,
+        quantity: f64,
+        price: Option<f64>,
+    ) -> Result<WasmAck, Error> {
+        let order_type = match price {
+            Some(_) => OrderType::Limit,
+            None => OrderType::Market,
+        };
+        let order = Order::new(
+            parse_id(user_id)?,
+            self.symbol,
+            parse_side(side)?,
+            quantity,
+            price,
+            order_type,
+        );
+        let ack = self.manager.add_order(order)?;
+        Ok(WasmAck {
+            order_id: ack.order_id.to_string(),
+            status: ack.status,
+            filled_quantity: ack.filled_quantity,
+            reject_reason: ack.reject_reason,
+        })
+    }
+
+    fn cancel(&mut self, order_id: &str, side: &str) -> Result<bool, Error> {
+        let order_id = parse_id(order_id)?;
+        match self
+            .manager
+            .cancel_order(order_id, self.symbol, parse_side(side)?)
+        {
+            Ok(ack) => Ok(!ack.is_rejected()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn levels(&self, levels: usize) -> WasmDepth {
+        let (bids, asks) = self.manager.orderbooks[&self.symbol].depth(levels);
+        let precision = self.manager.symbols.precision(self.symbol);
+        let round = |levels: Vec<_>| {
+            levels
+                .into_iter()
+                .map(|l| precision.round_level(l))
+                .collect()
+        };
+        WasmDepth {
+            bids: round(bids),
+            asks: round(asks),
+        }
+    }
+}
+
+impl Default for WasmOrderbook {
+    fn default() -> WasmOrderbook {
+        WasmOrderbook::new()
+    }
+}
+
+#[wasm_bindgen]
+impl WasmOrderbook {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmOrderbook {
+        let mut manager = OrderbooksManager::new();
+        let symbol = Symbol(1);
+        manager.new_orderbook(symbol);
+        WasmOrderbook { manager, symbol }
+    }
+
+    /// Place an order, a limit order when a price is given and a market order otherwise.
+    /// Returns the acknowledgment as JSON: `{"orderId", "status", "filledQuantity", "rejectReason"}`
+    #[wasm_bindgen(js_name = placeOrder)]
+    pub fn place_order(
+        &mut self,
+        user_id: &str,
+        side: &str,
+        quantity: f64,
+        price: Option<f64>,
+    ) -> Result<String, JsError> {
+        let ack = self
+            .place(user_id, side, quantity, price)
+            .map_err(js_error)?;
+        Ok(serde_json::to_string(&ack)?)
+    }
+
+    /// Cancel a resting order, returns false if it is not in the book
+    #[wasm_bindgen(js_name = cancelOrder)]
+    pub fn cancel_order(&mut self, order_id: &str, side: &str) -> Result<bool, JsError> {
+        self.cancel(order_id, side).map_err(js_error)
+    }
+
+    /// The best price levels of each side as JSON: `{"bids": [{"price", "quantity", "orders"}], "asks": [...]}`
+    pub fn depth(&self, levels: usize) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.levels(levels))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_cancel_and_depth() {
+        let mut book = WasmOrderbook::new();
+        let bid = book.place("1", "buy", 2.0, Some(10.0)).unwrap();
+        assert_eq!(bid.status, OrderStatus::Open);
+        book.place("1", "BUY", 1.0, Some(9.0)).unwrap();
+        let taker = book.place("2", "sell", 1.0, None).unwrap();
+        assert_eq!(taker.filled_quantity, 1.0);
+
+        let depth = book.levels(1);
+        assert_eq!(depth.bids.len(), 1);
+        assert_eq!((depth.bids[0].price, depth.bids[0].quantity), (10.0, 1.0));
+        assert!(depth.asks.is_empty());
+
+        assert!(book.cancel(&bid.order_id, "buy").unwrap());
+        assert!(!book.cancel(&bid.order_id, "buy").unwrap());
+        assert!(book.place("1", "up", 1.0, Some(1.0)).is_err());
+        assert!(book.place("user", "buy", 1.0, Some(1.0)).is_err());
+    }
+}